@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::locks::lock_recovering;
+
+/// Source of the current time, so time-based behavior can be driven by tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        MockClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *lock_recovering(&self.now) += by;
+    }
+
+    pub fn set(&self, to: SystemTime) {
+        *lock_recovering(&self.now) = to;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *lock_recovering(&self.now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_moves_only_when_advanced() {
+        let clock = MockClock::default();
+        let shared = clock.clone();
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH);
+        shared.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(5));
+    }
+}
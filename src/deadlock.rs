@@ -7,11 +7,12 @@ use crate::fts::search::Search;
 use crate::fts::default::DefaultSearchIdx;
 use crate::storage::memory::InMemoryStorage;
 
+/// `from_tx` is waiting for `to_tx` to release its lock on `resource`
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
-struct WaitForEdge {
-    from_tx: u64,
-    to_tx: u64,
-    resource: String,
+pub struct WaitForEdge {
+    pub from_tx: u64,
+    pub to_tx: u64,
+    pub resource: String,
 }
 
 pub struct DeadlockDetector {
@@ -44,19 +45,55 @@ impl DeadlockDetector {
         }
     }
 
+    /// Drops the edges of transactions waiting on `holding_tx` for any of `resources`,
+    /// once it has released its locks on them
+    pub fn remove_waits_on(&mut self, holding_tx: u64, resources: &[String]) {
+        for edges in self.wait_for_graph.values_mut() {
+            edges.retain(|edge| edge.to_tx != holding_tx || !resources.contains(&edge.resource));
+        }
+        self.wait_for_graph.retain(|_, edges| !edges.is_empty());
+    }
+
+    /// Snapshot of every wait-for edge, ordered by waiting transaction then holder
+    pub fn wait_for_graph(&self) -> Vec<WaitForEdge> {
+        let mut edges: Vec<WaitForEdge> = self.wait_for_graph.values().flatten().cloned().collect();
+        edges.sort_by(|a, b| (a.from_tx, a.to_tx, &a.resource).cmp(&(b.from_tx, b.to_tx, &b.resource)));
+        edges
+    }
+
+    /// First cycle found in the wait-for graph, as the transaction ids along it. Each
+    /// transaction waits on the next and the last one waits on the first; the cycle is
+    /// rotated to start at its lowest id.
+    pub fn find_deadlock_cycle(&self) -> Option<Vec<u64>> {
+        let mut starts: Vec<u64> = self.wait_for_graph.keys().copied().collect();
+        starts.sort();
+        starts.into_iter().find_map(|start_tx| self.find_cycle(start_tx)).map(|mut cycle| {
+            if let Some(lowest) = cycle.iter().enumerate().min_by_key(|(_, tx)| **tx).map(|(idx, _)| idx) {
+                cycle.rotate_left(lowest);
+            }
+            cycle
+        })
+    }
+
     pub fn detect_deadlock<S, FTS>(&self, transactions: &[&Transaction<S, FTS>]) -> Option<u64>
     where
         S: Storage + IndexManager + Clone,
         FTS: Search + Clone,
         FTS::NewArgs: Clone,
     {
-        for &start_tx in self.wait_for_graph.keys() {
-            if let Some(cycle) = self.find_cycle(start_tx) {
-                // Return the youngest transaction in the cycle
-                return Some(self.select_victim(&cycle, transactions));
-            }
-        }
-        None
+        self.detect_deadlock_cycle(transactions).map(|(victim, _)| victim)
+    }
+
+    /// Like `detect_deadlock`, also returning the cycle the victim was chosen from
+    pub fn detect_deadlock_cycle<S, FTS>(&self, transactions: &[&Transaction<S, FTS>]) -> Option<(u64, Vec<u64>)>
+    where
+        S: Storage + IndexManager + Clone,
+        FTS: Search + Clone,
+        FTS::NewArgs: Clone,
+    {
+        // Return the youngest transaction in the cycle
+        self.find_deadlock_cycle()
+            .map(|cycle| (self.select_victim(&cycle, transactions), cycle))
     }
 
     fn find_cycle(&self, start_tx: u64) -> Option<Vec<u64>> {
@@ -297,4 +334,58 @@ mod tests {
         let victim = detector.select_victim(&cycle, &transactions);
         assert_eq!(victim, tx3.get_id());
     }
+
+    #[test]
+    fn test_reported_deadlock_cycle() {
+        let mut detector = DeadlockDetector::new();
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+
+        let tx1 = Transaction::create(db.clone(), IsolationLevel::Serializable);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let tx2 = Transaction::create(db.clone(), IsolationLevel::Serializable);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let tx3 = Transaction::create(db.clone(), IsolationLevel::Serializable);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let tx4 = Transaction::create(db.clone(), IsolationLevel::Serializable);
+        let transactions: Vec<&Transaction<InMemoryStorage, DefaultSearchIdx>> = vec![&tx1, &tx2, &tx3, &tx4];
+
+        // T2 -> T3 -> T1 -> T2, with T4 waiting on the cycle from outside
+        detector.add_wait(tx2.get_id(), tx3.get_id(), "users".to_string());
+        detector.add_wait(tx3.get_id(), tx1.get_id(), "posts".to_string());
+        detector.add_wait(tx1.get_id(), tx2.get_id(), "comments".to_string());
+        detector.add_wait(tx4.get_id(), tx1.get_id(), "users".to_string());
+
+        let graph = detector.wait_for_graph();
+        assert_eq!(graph.len(), 4);
+        assert!(graph.windows(2).all(|pair| pair[0].from_tx <= pair[1].from_tx));
+        assert!(graph.contains(&WaitForEdge {
+            from_tx: tx1.get_id(),
+            to_tx: tx2.get_id(),
+            resource: "comments".to_string(),
+        }));
+
+        // Transaction ids are random, so the cycle starts at whichever is lowest
+        let cycle = detector.find_deadlock_cycle().unwrap();
+        let mut members = cycle.clone();
+        members.sort();
+        let mut expected = vec![tx1.get_id(), tx2.get_id(), tx3.get_id()];
+        expected.sort();
+        assert_eq!(members, expected);
+        assert_eq!(cycle[0], expected[0]);
+        for (idx, from_tx) in cycle.iter().enumerate() {
+            let to_tx = cycle[(idx + 1) % cycle.len()];
+            assert!(graph.iter().any(|edge| edge.from_tx == *from_tx && edge.to_tx == to_tx));
+        }
+
+        let (victim, reported) = detector.detect_deadlock_cycle(&transactions).unwrap();
+        assert_eq!(victim, tx3.get_id());
+        assert_eq!(reported, cycle);
+
+        let error = crate::error::ReefDBError::Deadlock(cycle.clone());
+        assert_eq!(
+            error.to_string(),
+            format!("Transaction aborted due to deadlock: {} -> {} -> {} -> {}",
+                cycle[0], cycle[1], cycle[2], cycle[0])
+        );
+    }
 } 
\ No newline at end of file
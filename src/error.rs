@@ -1,25 +1,79 @@
+use std::error::Error;
 use std::fmt;
 use std::io;
+use std::sync::Arc;
+
+/// Underlying error kept for `source()`. Compared by message so `ReefDBError` stays `PartialEq`
+#[derive(Debug, Clone)]
+pub struct ErrorCause(Arc<dyn Error + Send + Sync>);
+
+impl ErrorCause {
+    pub fn new<E: Error + Send + Sync + 'static>(error: E) -> Self {
+        ErrorCause(Arc::new(error))
+    }
+}
+
+impl PartialEq for ErrorCause {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub enum ReefDBError {
     TableNotFound(String),
     ColumnNotFound(String),
     SavepointNotFound(String),
-    SavepointNotActive(String),
     TransactionNotActive,
     TransactionNotFound(u64),
     DuplicateKey(String),
     LockAcquisitionFailed(String),
+    /// A mutex guarding shared state was poisoned; names the lock, e.g. "WAL lock"
+    LockPoisoned(String),
     WALError(String),
     MVCCError(String),
-    IoError(String),
+    IoError(ErrorCause),
     DeadlockDetected(String),
-    Deadlock,
+    /// Transaction ids forming the wait-for cycle; each waits on the next, the last on the first
+    Deadlock(Vec<u64>),
     LockConflict(String),
     InvalidIsolationLevel(String),
     Other(String),
     WriteConflict(String),
+    /// A concurrent transaction committed first; the transaction was aborted and can be retried
+    SerializationFailure(String),
+    JoinTooLarge(usize),
+    /// Schema version found on disk and the newest version the caller supports
+    SchemaVersionMismatch(u32, u32),
+    /// A value was supplied for a generated column, which is always computed
+    GeneratedColumnWrite(String),
+    /// A row references a value its foreign key column's parent doesn't hold, or a change
+    /// to the parent would leave rows pointing at a value that is gone
+    ForeignKeyViolation(String),
+    /// The NOT NULL foreign keys of a new table close a cycle, written as the path of tables
+    ForeignKeyCycle(String),
+    /// Column name and its maximum length in characters
+    ValueTooLong(String, usize),
+    /// Column name and the value that is not one of its enum labels
+    InvalidEnumValue(String, String),
+    /// The statement was stopped through the transaction's `CancellationToken`
+    Cancelled,
+    /// Column name and why its value doesn't convert to the requested Rust type
+    ValueConversion(String, String),
+    /// A row has a different number of values than the columns it fills
+    ColumnCountMismatch { expected: usize, found: usize },
+    /// SQL that doesn't parse: what went wrong and the byte offset in the input where it did
+    ParseError { message: String, position: usize },
+    /// An `AS OF` read asked for history that version retention no longer keeps
+    SnapshotTooOld(String),
+    /// A table name falls in the namespace kept for system catalogs
+    ReservedName(String),
 }
 
 impl fmt::Display for ReefDBError {
@@ -28,34 +82,95 @@ impl fmt::Display for ReefDBError {
             ReefDBError::TableNotFound(table) => write!(f, "Table not found: {}", table),
             ReefDBError::ColumnNotFound(column) => write!(f, "Column not found: {}", column),
             ReefDBError::SavepointNotFound(sp) => write!(f, "Savepoint not found: {}", sp),
-            ReefDBError::SavepointNotActive(sp) => write!(f, "Savepoint is not active: {}", sp),
             ReefDBError::TransactionNotActive => write!(f, "Transaction is not active"),
             ReefDBError::TransactionNotFound(id) => write!(f, "Transaction not found: {}", id),
             ReefDBError::DuplicateKey(key) => write!(f, "Duplicate key violation: {}", key),
             ReefDBError::LockAcquisitionFailed(msg) => write!(f, "Failed to acquire lock: {}", msg),
+            ReefDBError::LockPoisoned(lock) => write!(f, "Failed to acquire {}", lock),
             ReefDBError::WALError(msg) => write!(f, "WAL error: {}", msg),
             ReefDBError::MVCCError(msg) => write!(f, "MVCC error: {}", msg),
             ReefDBError::IoError(msg) => write!(f, "IO error: {}", msg),
             ReefDBError::DeadlockDetected(msg) => write!(f, "Deadlock detected: {}", msg),
-            ReefDBError::Deadlock => write!(f, "Transaction aborted due to deadlock"),
+            ReefDBError::Deadlock(cycle) => {
+                write!(f, "Transaction aborted due to deadlock")?;
+                if let Some(first) = cycle.first() {
+                    let path: Vec<String> = cycle.iter().chain(std::iter::once(first)).map(|id| id.to_string()).collect();
+                    write!(f, ": {}", path.join(" -> "))?;
+                }
+                Ok(())
+            }
             ReefDBError::LockConflict(msg) => write!(f, "Lock conflict: {}", msg),
             ReefDBError::InvalidIsolationLevel(level) => write!(f, "Invalid isolation level: {}", level),
             ReefDBError::Other(msg) => write!(f, "{}", msg),
             ReefDBError::WriteConflict(msg) => write!(f, "Write conflict: {}", msg),
+            ReefDBError::SerializationFailure(msg) => write!(f, "Could not serialize access: {}", msg),
+            ReefDBError::JoinTooLarge(limit) => write!(f, "Join exceeded the limit of {} intermediate rows", limit),
+            ReefDBError::SchemaVersionMismatch(found, supported) => {
+                write!(f, "Schema version {} is newer than the supported version {}", found, supported)
+            }
+            ReefDBError::GeneratedColumnWrite(column) => write!(f, "Cannot write to generated column {}", column),
+            ReefDBError::ForeignKeyViolation(msg) => write!(f, "Foreign key violation: {}", msg),
+            ReefDBError::ValueTooLong(column, max) => {
+                write!(f, "Value too long for column {}: maximum length is {} characters", column, max)
+            }
+            ReefDBError::InvalidEnumValue(column, value) => {
+                write!(f, "Invalid value '{}' for enum column {}", value, column)
+            }
+            ReefDBError::Cancelled => write!(f, "Statement cancelled"),
+            ReefDBError::ValueConversion(column, reason) => write!(f, "Cannot convert column {}: {}", column, reason),
+            ReefDBError::ColumnCountMismatch { expected, found } => {
+                write!(f, "Expected {} values but got {}", expected, found)
+            }
+            ReefDBError::ParseError { message, position } => write!(f, "Parse error at byte {}: {}", position, message),
+            ReefDBError::SnapshotTooOld(msg) => write!(f, "Snapshot too old: {}", msg),
+            ReefDBError::ReservedName(name) => write!(f, "Reserved name: {}", name),
+            ReefDBError::ForeignKeyCycle(path) => write!(f, "NOT NULL foreign keys form a cycle with no valid insert order: {}", path),
         }
     }
 }
 
-impl std::error::Error for ReefDBError {}
+// Lock poisoning is reported through `LockPoisoned` by name only: a `PoisonError` owns the
+// guard, which is neither `Send` nor `'static`, so it can't be kept as a source
+impl Error for ReefDBError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReefDBError::IoError(cause) => Some(cause.0.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<io::Error> for ReefDBError {
     fn from(error: io::Error) -> Self {
-        ReefDBError::IoError(error.to_string())
+        ReefDBError::IoError(ErrorCause::new(error))
     }
 }
 
 impl From<bincode::Error> for ReefDBError {
     fn from(error: bincode::Error) -> Self {
-        ReefDBError::IoError(error.to_string())
+        ReefDBError::IoError(ErrorCause::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_error_is_send_sync_with_source() {
+        assert_send_sync::<ReefDBError>();
+
+        let error = ReefDBError::from(io::Error::new(io::ErrorKind::NotFound, "missing file"));
+        assert_eq!(error.to_string(), "IO error: missing file");
+        let source = error.source().expect("IO errors keep their cause");
+        assert_eq!(source.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::NotFound);
+
+        // Errors cross threads and box into `dyn Error`
+        let boxed: Box<dyn Error + Send + Sync> = std::thread::spawn(move || Box::new(error) as Box<dyn Error + Send + Sync>)
+            .join()
+            .unwrap();
+        assert!(boxed.source().is_some());
     }
 }
@@ -74,6 +74,11 @@ impl<T: Tokenizer + Serialize + for<'de> Deserialize<'de>> Search for OnDiskInve
         self.index.update_document(table, column, row_id, text);
         self.save_to_file(&self.file_path).unwrap();
     }
+
+    fn remove_table(&mut self, table: &str) {
+        self.index.remove_table(table);
+        self.save_to_file(&self.file_path).unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -9,4 +9,5 @@ pub trait Search {
     fn add_document(&mut self, table: &str, column: &str, row_id: usize, text: &str);
     fn remove_document(&mut self, table: &str, column: &str, row_id: usize);
     fn update_document(&mut self, table: &str, column: &str, row_id: usize, text: &str);
+    fn remove_table(&mut self, table: &str);
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 use crate::sql::data_value::DataValue;
+use crate::sql::decimal::Decimal;
+use crate::sql::json::Json;
+use crate::sql::uuid::Uuid;
 use crate::error::ReefDBError;
-use crate::functions::{Function, FunctionArg, FunctionArgType, FunctionReturnType, FunctionRegistry};
+use crate::functions::{Function, FunctionArg, FunctionArgType, FunctionHandler, FunctionReturnType, FunctionRegistry};
 use std::fmt;
 use crate::sql::clauses::full_text_search::ranking::{TSRanking, NORM_LENGTH};
 use crate::fts::text_processor::{TextProcessor, TsVector, ProcessedQuery, TSQuery};
@@ -14,88 +17,269 @@ impl fmt::Display for DataValue {
             DataValue::Integer(i) => write!(f, "{}", i),
             DataValue::Boolean(b) => write!(f, "{}", b),
             DataValue::Float(fl) => write!(f, "{}", fl),
+            DataValue::Decimal(d) => write!(f, "{}", d),
             DataValue::Date(d) => write!(f, "{}", d),
             DataValue::Timestamp(t) => write!(f, "{}", t),
+            DataValue::Uuid(u) => write!(f, "{}", u),
+            DataValue::Json(j) => write!(f, "{}", j),
+            DataValue::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
             DataValue::TSVector(v) => write!(f, "{}", v),
             DataValue::TSQuery(q) => write!(f, "{}", q),
             DataValue::Null => write!(f, "NULL"),
             DataValue::Function { name, args } => write!(f, "Function({:?}, {:?})", name, args),
+            DataValue::Column(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+fn register_arithmetic(registry: &mut FunctionRegistry, name: &str, handler: FunctionHandler) -> Result<(), ReefDBError> {
+    registry.register(Function {
+        name: name.to_string(),
+        args: vec![
+            FunctionArg::new("a".to_string(), FunctionArgType::Any),
+            FunctionArg::new("b".to_string(), FunctionArgType::Any),
+        ],
+        return_type: FunctionReturnType::Any,
+        handler,
+    })
+}
+
+// Two integers give an integer (overflow is an error); decimals combined with decimals or
+// integers stay exact; otherwise both sides are taken as floats. NULL on either side gives
+// NULL. Division by zero is handled by the caller.
+fn arithmetic(
+    name: &str,
+    args: &[DataValue],
+    int_op: fn(i64, i64) -> Option<i64>,
+    decimal_op: fn(&Decimal, &Decimal) -> Option<Decimal>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<DataValue, ReefDBError> {
+    let as_decimal = |value: &DataValue| match value {
+        DataValue::Integer(i) => Some(Decimal::from_i64(*i)),
+        DataValue::Decimal(d) => Some(*d),
+        _ => None,
+    };
+    let as_float = |value: &DataValue| match value {
+        DataValue::Integer(i) => Some(*i as f64),
+        DataValue::Float(f) => Some(*f),
+        DataValue::Decimal(d) => Some(d.to_f64()),
+        _ => None,
+    };
+    match args {
+        [DataValue::Null, _] | [_, DataValue::Null] => Ok(DataValue::Null),
+        [DataValue::Integer(a), DataValue::Integer(b)] => int_op(*a, *b)
+            .map(DataValue::Integer)
+            .ok_or_else(|| ReefDBError::Other(format!("Integer overflow in {}", name))),
+        [a @ DataValue::Decimal(_), b] | [a, b @ DataValue::Decimal(_)]
+            if as_decimal(a).is_some() && as_decimal(b).is_some() =>
+        {
+            as_decimal(a).zip(as_decimal(b))
+                .and_then(|(a, b)| decimal_op(&a, &b))
+                .map(DataValue::Decimal)
+                .ok_or_else(|| ReefDBError::Other(format!("Decimal overflow in {}", name)))
         }
+        [a, b] => match (as_float(a), as_float(b)) {
+            (Some(a), Some(b)) => Ok(DataValue::Float(float_op(a, b))),
+            _ => Err(ReefDBError::Other(format!("Invalid argument types for {}", name))),
+        },
+        _ => Err(ReefDBError::Other(format!("Invalid argument count for {}", name))),
     }
 }
 
 pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBError> {
     // String functions
+    // CONCAT skips NULLs and concatenates the display form of other values
     registry.register(Function {
         name: "concat".to_string(),
+        args: vec![FunctionArg::new("str".to_string(), FunctionArgType::Any).variadic()],
+        return_type: FunctionReturnType::String,
+        handler: |args| {
+            Ok(DataValue::Text(args.iter()
+                .filter(|arg| !matches!(arg, DataValue::Null))
+                .map(|arg| arg.to_string())
+                .collect::<String>()
+                .into()))
+        },
+    })?;
+
+    registry.register(Function {
+        name: "lower".to_string(),
+        args: vec![FunctionArg::new("str".to_string(), FunctionArgType::String)],
+        return_type: FunctionReturnType::String,
+        handler: |args| match args.as_slice() {
+            [DataValue::Text(s)] => Ok(DataValue::Text(s.to_lowercase().into())),
+            [DataValue::Null] => Ok(DataValue::Null),
+            _ => Err(ReefDBError::Other("Invalid argument types for lower".to_string())),
+        },
+    })?;
+
+    registry.register(Function {
+        name: "upper".to_string(),
+        args: vec![FunctionArg::new("str".to_string(), FunctionArgType::String)],
+        return_type: FunctionReturnType::String,
+        handler: |args| match args.as_slice() {
+            [DataValue::Text(s)] => Ok(DataValue::Text(s.to_uppercase().into())),
+            [DataValue::Null] => Ok(DataValue::Null),
+            _ => Err(ReefDBError::Other("Invalid argument types for upper".to_string())),
+        },
+    })?;
+
+    // Length in characters rather than bytes
+    registry.register(Function {
+        name: "length".to_string(),
+        args: vec![FunctionArg::new("str".to_string(), FunctionArgType::String)],
+        return_type: FunctionReturnType::Integer,
+        handler: |args| match args.as_slice() {
+            [DataValue::Text(s)] => Ok(DataValue::Integer(s.chars().count() as i64)),
+            [DataValue::Null] => Ok(DataValue::Null),
+            _ => Err(ReefDBError::Other("Invalid argument types for length".to_string())),
+        },
+    })?;
+
+    // SUBSTR(str, start [, len]) with a 1-based start, counted in characters
+    registry.register(Function {
+        name: "substr".to_string(),
         args: vec![
-            FunctionArg {
-                name: "str1".to_string(),
-                arg_type: FunctionArgType::String,
-                is_optional: false,
-            },
-            FunctionArg {
-                name: "str2".to_string(),
-                arg_type: FunctionArgType::String,
-                is_optional: false,
-            },
+            FunctionArg::new("str".to_string(), FunctionArgType::String),
+            FunctionArg::new("start".to_string(), FunctionArgType::Integer),
+            FunctionArg::new("len".to_string(), FunctionArgType::Integer).optional(),
         ],
         return_type: FunctionReturnType::String,
         handler: |args| {
-            if let [DataValue::Text(s1), DataValue::Text(s2)] = args.as_slice() {
-                Ok(DataValue::Text(format!("{}{}", s1, s2)))
-            } else {
-                Err(ReefDBError::Other("Invalid argument types for concat".to_string()))
+            let (s, start, len) = match args.as_slice() {
+                [DataValue::Null, ..] | [_, DataValue::Null, ..] | [_, _, DataValue::Null] => return Ok(DataValue::Null),
+                [DataValue::Text(s), DataValue::Integer(start)] => (s, *start, None),
+                [DataValue::Text(s), DataValue::Integer(start), DataValue::Integer(len)] => (s, *start, Some(*len)),
+                _ => return Err(ReefDBError::Other("Invalid argument types for substr".to_string())),
+            };
+            if len.is_some_and(|len| len < 0) {
+                return Err(ReefDBError::Other("Negative length for substr".to_string()));
+            }
+            // Positions before the first character still consume the length
+            let end = len.map(|len| start.saturating_add(len));
+            let skip = (start.max(1) - 1) as usize;
+            let take = end.map_or(usize::MAX, |end| (end - start.max(1)).max(0) as usize);
+            Ok(DataValue::Text(s.chars().skip(skip).take(take).collect::<String>().into()))
+        },
+    })?;
+
+    // Numeric functions. The arithmetic ones also back `+ - * /` in generated column expressions
+    register_arithmetic(registry, "add", |args| arithmetic("add", &args, i64::checked_add, Decimal::checked_add, |a, b| a + b))?;
+    register_arithmetic(registry, "subtract", |args| arithmetic("subtract", &args, i64::checked_sub, Decimal::checked_sub, |a, b| a - b))?;
+    register_arithmetic(registry, "multiply", |args| arithmetic("multiply", &args, i64::checked_mul, Decimal::checked_mul, |a, b| a * b))?;
+    // Decimal quotients keep the wider scale of the two operands
+    register_arithmetic(registry, "divide", |args| match args.as_slice() {
+        [_, DataValue::Integer(0)] => Ok(DataValue::Null),
+        [_, DataValue::Float(b)] if *b == 0.0 => Ok(DataValue::Null),
+        [_, DataValue::Decimal(b)] if b.units() == 0 => Ok(DataValue::Null),
+        _ => arithmetic("divide", &args, i64::checked_div, |a, b| a.checked_div(b, a.scale().max(b.scale())), |a, b| a / b),
+    })?;
+
+    registry.register(Function {
+        name: "abs".to_string(),
+        args: vec![FunctionArg::new("x".to_string(), FunctionArgType::Any)],
+        return_type: FunctionReturnType::Any,
+        handler: |args| match args.as_slice() {
+            [DataValue::Integer(x)] => x.checked_abs()
+                .map(DataValue::Integer)
+                .ok_or_else(|| ReefDBError::Other("Integer overflow in abs".to_string())),
+            [DataValue::Float(x)] => Ok(DataValue::Float(x.abs())),
+            [DataValue::Null] => Ok(DataValue::Null),
+            _ => Err(ReefDBError::Other("Invalid argument types for abs".to_string())),
+        },
+    })?;
+
+    // ROUND(x [, digits]) rounds halves away from zero; integers are returned unchanged
+    registry.register(Function {
+        name: "round".to_string(),
+        args: vec![
+            FunctionArg::new("x".to_string(), FunctionArgType::Any),
+            FunctionArg::new("digits".to_string(), FunctionArgType::Integer).optional(),
+        ],
+        return_type: FunctionReturnType::Any,
+        handler: |args| match args.as_slice() {
+            [DataValue::Null, ..] | [_, DataValue::Null] => Ok(DataValue::Null),
+            [DataValue::Integer(x), ..] => Ok(DataValue::Integer(*x)),
+            [DataValue::Float(x)] => Ok(DataValue::Float(x.round())),
+            [DataValue::Float(x), DataValue::Integer(digits)] => {
+                let scale = 10f64.powi((*digits).clamp(-308, 308) as i32);
+                Ok(DataValue::Float((x * scale).round() / scale))
             }
+            _ => Err(ReefDBError::Other("Invalid argument types for round".to_string())),
         },
     })?;
 
-    // Numeric functions
+    // MOD(a, b) takes the sign of the dividend and is NULL when dividing by zero
     registry.register(Function {
-        name: "add".to_string(),
+        name: "mod".to_string(),
         args: vec![
-            FunctionArg {
-                name: "a".to_string(),
-                arg_type: FunctionArgType::Integer,
-                is_optional: false,
-            },
-            FunctionArg {
-                name: "b".to_string(),
-                arg_type: FunctionArgType::Integer,
-                is_optional: false,
-            },
+            FunctionArg::new("a".to_string(), FunctionArgType::Any),
+            FunctionArg::new("b".to_string(), FunctionArgType::Any),
         ],
-        return_type: FunctionReturnType::Integer,
+        return_type: FunctionReturnType::Any,
         handler: |args| {
-            if let [DataValue::Integer(a), DataValue::Integer(b)] = args.as_slice() {
-                Ok(DataValue::Integer(a + b))
-            } else {
-                Err(ReefDBError::Other("Invalid argument types for add".to_string()))
+            let as_float = |value: &DataValue| match value {
+                DataValue::Integer(i) => Some(*i as f64),
+                DataValue::Float(f) => Some(*f),
+                _ => None,
+            };
+            match args.as_slice() {
+                [DataValue::Null, _] | [_, DataValue::Null] => Ok(DataValue::Null),
+                [DataValue::Integer(a), DataValue::Integer(b)] => {
+                    // wrapping_rem avoids the i64::MIN % -1 overflow panic
+                    Ok(if *b == 0 { DataValue::Null } else { DataValue::Integer(a.wrapping_rem(*b)) })
+                }
+                [a, b] => match (as_float(a), as_float(b)) {
+                    (Some(a), Some(b)) => Ok(if b == 0.0 { DataValue::Null } else { DataValue::Float(a % b) }),
+                    _ => Err(ReefDBError::Other("Invalid argument types for mod".to_string())),
+                },
+                _ => Err(ReefDBError::Other("Invalid argument count for mod".to_string())),
             }
         },
     })?;
 
     registry.register(Function {
-        name: "multiply".to_string(),
+        name: "gen_uuid".to_string(),
+        args: vec![],
+        return_type: FunctionReturnType::Any,
+        handler: |_| Ok(DataValue::Uuid(Uuid::new_v4())),
+    })?;
+
+    // JSON_EXTRACT(doc, path) returns scalars as SQL values and objects or arrays as JSON.
+    // Text documents are parsed first; a bad document or path, or a missing member, is NULL.
+    registry.register(Function {
+        name: "json_extract".to_string(),
         args: vec![
-            FunctionArg {
-                name: "a".to_string(),
-                arg_type: FunctionArgType::Integer,
-                is_optional: false,
-            },
-            FunctionArg {
-                name: "b".to_string(),
-                arg_type: FunctionArgType::Integer,
-                is_optional: false,
-            },
+            FunctionArg::new("doc".to_string(), FunctionArgType::Any),
+            FunctionArg::new("path".to_string(), FunctionArgType::String),
         ],
-        return_type: FunctionReturnType::Integer,
+        return_type: FunctionReturnType::Any,
         handler: |args| {
-            if let [DataValue::Integer(a), DataValue::Integer(b)] = args.as_slice() {
-                Ok(DataValue::Integer(a * b))
-            } else {
-                Err(ReefDBError::Other("Invalid argument types for multiply".to_string()))
-            }
+            let doc = match &args[0] {
+                DataValue::Json(doc) => doc.clone(),
+                DataValue::Text(text) => match Json::parse(text) {
+                    Ok(doc) => doc,
+                    Err(_) => return Ok(DataValue::Null),
+                },
+                DataValue::Null => return Ok(DataValue::Null),
+                _ => return Err(ReefDBError::Other("Invalid argument types for json_extract".to_string())),
+            };
+            let DataValue::Text(path) = &args[1] else {
+                return Ok(DataValue::Null);
+            };
+            Ok(match doc.extract(path) {
+                None | Some(serde_json::Value::Null) => DataValue::Null,
+                Some(serde_json::Value::Bool(b)) => DataValue::Boolean(*b),
+                Some(serde_json::Value::String(s)) => DataValue::Text(s.as_str().into()),
+                Some(serde_json::Value::Number(n)) => match n.as_i64() {
+                    Some(i) => DataValue::Integer(i),
+                    None => n.as_f64().map_or(DataValue::Null, DataValue::Float),
+                },
+                Some(value) => DataValue::Json(Json::new(value.clone())),
+            })
         },
     })?;
 
@@ -105,8 +289,9 @@ pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBEr
         args: vec![
             FunctionArg {
                 name: "text".to_string(),
-                arg_type: FunctionArgType::String,
+                arg_type: FunctionArgType::Any,
                 is_optional: false,
+                is_variadic: false,
             },
         ],
         return_type: FunctionReturnType::TSVector,
@@ -115,6 +300,9 @@ pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBEr
                 let processor = DefaultTextProcessor::new();
                 let vector = processor.process_document(text, None);
                 Ok(DataValue::TSVector(vector))
+            } else if let [DataValue::TSVector(vector)] = args.as_slice() {
+                // A TSVECTOR column is already processed
+                Ok(DataValue::TSVector(vector.clone()))
             } else {
                 Err(ReefDBError::Other("Invalid argument types for to_tsvector".to_string()))
             }
@@ -128,6 +316,7 @@ pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBEr
                 name: "query".to_string(),
                 arg_type: FunctionArgType::String,
                 is_optional: false,
+                is_variadic: false,
             },
         ],
         return_type: FunctionReturnType::TSQuery,
@@ -150,11 +339,13 @@ pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBEr
                 name: "tsvector".to_string(),
                 arg_type: FunctionArgType::TSVector,
                 is_optional: false,
+                is_variadic: false,
             },
             FunctionArg {
                 name: "tsquery".to_string(),
                 arg_type: FunctionArgType::TSQuery,
                 is_optional: false,
+                is_variadic: false,
             },
         ],
         return_type: FunctionReturnType::Float,
@@ -185,12 +376,13 @@ pub fn register_builtins(registry: &mut FunctionRegistry) -> Result<(), ReefDBEr
                 name: "value".to_string(),
                 arg_type: FunctionArgType::Any,
                 is_optional: false,
+                is_variadic: false,
             },
         ],
         return_type: FunctionReturnType::String,
         handler: |args| {
             if let [value] = args.as_slice() {
-                Ok(DataValue::Text(value.to_string()))
+                Ok(DataValue::Text(value.to_string().into()))
             } else {
                 Err(ReefDBError::Other("Invalid argument count for to_string".to_string()))
             }
@@ -213,11 +405,11 @@ mod tests {
         let result = registry.call(
             "concat",
             vec![
-                DataValue::Text("Hello, ".to_string()),
-                DataValue::Text("World!".to_string()),
+                DataValue::Text("Hello, ".into()),
+                DataValue::Text("World!".into()),
             ],
         ).unwrap();
-        assert_eq!(result, DataValue::Text("Hello, World!".to_string()));
+        assert_eq!(result, DataValue::Text("Hello, World!".into()));
 
         // Test add
         let result = registry.call(
@@ -238,6 +430,73 @@ mod tests {
             "to_string",
             vec![DataValue::Integer(42)],
         ).unwrap();
-        assert_eq!(result, DataValue::Text("42".to_string()));
+        assert_eq!(result, DataValue::Text("42".into()));
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let mut registry = FunctionRegistry::new();
+        register_builtins(&mut registry).unwrap();
+        let text = |s: &str| DataValue::Text(s.into());
+
+        assert_eq!(registry.call("LOWER", vec![text("ReefDB")]).unwrap(), text("reefdb"));
+        assert_eq!(registry.call("upper", vec![text("straße")]).unwrap(), text("STRASSE"));
+        assert_eq!(registry.call("length", vec![text("héllo")]).unwrap(), DataValue::Integer(5));
+        assert_eq!(registry.call("length", vec![text("日本語")]).unwrap(), DataValue::Integer(3));
+
+        let substr = |args: Vec<DataValue>| registry.call("substr", args).unwrap();
+        assert_eq!(substr(vec![text("héllo"), DataValue::Integer(2), DataValue::Integer(3)]), text("éll"));
+        assert_eq!(substr(vec![text("héllo"), DataValue::Integer(3)]), text("llo"));
+        assert_eq!(substr(vec![text("héllo"), DataValue::Integer(0), DataValue::Integer(2)]), text("h"));
+        assert_eq!(substr(vec![text("héllo"), DataValue::Integer(9), DataValue::Integer(2)]), text(""));
+
+        assert_eq!(registry.call("lower", vec![DataValue::Null]).unwrap(), DataValue::Null);
+        assert!(registry.call("lower", vec![DataValue::Integer(1)]).is_err());
+        assert!(registry.call("length", vec![DataValue::Boolean(true)]).is_err());
+        assert!(registry.call("substr", vec![text("abc"), DataValue::Integer(1), DataValue::Integer(-1)]).is_err());
+
+        // CONCAT takes any number of arguments and skips NULLs
+        assert_eq!(
+            registry.call("concat", vec![text("a"), DataValue::Null, DataValue::Integer(1), DataValue::Boolean(true)]).unwrap(),
+            text("a1true")
+        );
+        assert_eq!(registry.call("concat", vec![DataValue::Null]).unwrap(), text(""));
+        assert!(registry.call("concat", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_numeric_functions() {
+        let mut registry = FunctionRegistry::new();
+        register_builtins(&mut registry).unwrap();
+        let call = |name: &str, args: Vec<DataValue>| registry.call(name, args).unwrap();
+
+        assert_eq!(call("abs", vec![DataValue::Integer(-7)]), DataValue::Integer(7));
+        assert_eq!(call("abs", vec![DataValue::Float(-2.5)]), DataValue::Float(2.5));
+        assert!(registry.call("abs", vec![DataValue::Integer(i64::MIN)]).is_err());
+        assert!(registry.call("abs", vec![DataValue::Text("x".into())]).is_err());
+
+        // Halves round away from zero
+        assert_eq!(call("round", vec![DataValue::Float(2.5)]), DataValue::Float(3.0));
+        assert_eq!(call("round", vec![DataValue::Float(-2.5)]), DataValue::Float(-3.0));
+        assert_eq!(call("round", vec![DataValue::Float(1.25), DataValue::Integer(1)]), DataValue::Float(1.3));
+        assert_eq!(call("round", vec![DataValue::Float(-1.25), DataValue::Integer(1)]), DataValue::Float(-1.3));
+        assert_eq!(call("round", vec![DataValue::Float(1250.0), DataValue::Integer(-2)]), DataValue::Float(1300.0));
+        assert_eq!(call("round", vec![DataValue::Integer(-42), DataValue::Integer(1)]), DataValue::Integer(-42));
+
+        assert_eq!(call("mod", vec![DataValue::Integer(7), DataValue::Integer(3)]), DataValue::Integer(1));
+        assert_eq!(call("mod", vec![DataValue::Integer(-7), DataValue::Integer(3)]), DataValue::Integer(-1));
+        assert_eq!(call("mod", vec![DataValue::Integer(7), DataValue::Integer(-3)]), DataValue::Integer(1));
+        assert_eq!(call("mod", vec![DataValue::Float(7.5), DataValue::Integer(2)]), DataValue::Float(1.5));
+        assert_eq!(call("mod", vec![DataValue::Integer(7), DataValue::Integer(0)]), DataValue::Null);
+        assert_eq!(call("mod", vec![DataValue::Float(7.5), DataValue::Float(0.0)]), DataValue::Null);
+        assert_eq!(call("mod", vec![DataValue::Integer(i64::MIN), DataValue::Integer(-1)]), DataValue::Integer(0));
+
+        assert_eq!(call("subtract", vec![DataValue::Integer(2), DataValue::Integer(5)]), DataValue::Integer(-3));
+        assert_eq!(call("multiply", vec![DataValue::Float(2.5), DataValue::Integer(4)]), DataValue::Float(10.0));
+        assert_eq!(call("divide", vec![DataValue::Integer(7), DataValue::Integer(2)]), DataValue::Integer(3));
+        assert_eq!(call("divide", vec![DataValue::Integer(7), DataValue::Integer(0)]), DataValue::Null);
+        assert_eq!(call("add", vec![DataValue::Null, DataValue::Integer(1)]), DataValue::Null);
+        assert!(registry.call("add", vec![DataValue::Integer(i64::MAX), DataValue::Integer(1)]).is_err());
+        assert!(registry.call("multiply", vec![DataValue::Text("a".into()), DataValue::Integer(1)]).is_err());
     }
 } 
\ No newline at end of file
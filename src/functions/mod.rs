@@ -18,6 +18,8 @@ pub struct FunctionArg {
     pub name: String,
     pub arg_type: FunctionArgType,
     pub is_optional: bool,
+    /// The last argument may be repeated any number of times
+    pub is_variadic: bool,
 }
 
 impl Default for FunctionArg {
@@ -26,6 +28,7 @@ impl Default for FunctionArg {
             name: String::new(),
             arg_type: FunctionArgType::Any,
             is_optional: false,
+            is_variadic: false,
         }
     }
 }
@@ -36,6 +39,7 @@ impl FunctionArg {
             name,
             arg_type,
             is_optional: false,
+            is_variadic: false,
         }
     }
 
@@ -43,6 +47,11 @@ impl FunctionArg {
         self.is_optional = true;
         self
     }
+
+    pub fn variadic(mut self) -> Self {
+        self.is_variadic = true;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -82,15 +91,17 @@ impl FunctionRegistry {
     }
 
     pub fn register(&mut self, function: Function) -> Result<(), ReefDBError> {
-        if self.functions.contains_key(&function.name) {
+        // Function names are case-insensitive, like the rest of SQL
+        let key = function.name.to_lowercase();
+        if self.functions.contains_key(&key) {
             return Err(ReefDBError::Other(format!("Function {} already registered", function.name)));
         }
-        self.functions.insert(function.name.clone(), function);
+        self.functions.insert(key, function);
         Ok(())
     }
 
     pub fn get(&self, name: &str) -> Option<&Function> {
-        self.functions.get(name)
+        self.functions.get(&name.to_lowercase())
     }
 
     pub fn call(&self, name: &str, args: Vec<DataValue>) -> Result<DataValue, ReefDBError> {
@@ -101,9 +112,10 @@ impl FunctionRegistry {
         // Count required arguments (non-optional)
         let required_args = function.args.iter().filter(|arg| !arg.is_optional).count();
         let max_args = function.args.len();
+        let variadic = function.args.last().is_some_and(|arg| arg.is_variadic);
 
         // Validate argument count
-        if args.len() < required_args || args.len() > max_args {
+        if args.len() < required_args || (args.len() > max_args && !variadic) {
             return Err(ReefDBError::Other(format!(
                 "Function '{}' expects {} to {} arguments, got {}. Required arguments: {}",
                 name,
@@ -118,8 +130,9 @@ impl FunctionRegistry {
             )));
         }
 
-        // Validate argument types
-        for (i, (arg, provided)) in function.args.iter().zip(args.iter()).enumerate() {
+        // Validate argument types, checking extra variadic arguments against the last one
+        for (i, provided) in args.iter().enumerate() {
+            let arg = &function.args[i.min(max_args - 1)];
             let type_matches = match (provided, &arg.arg_type) {
                 (DataValue::Text(_), FunctionArgType::String) => true,
                 (DataValue::Integer(_), FunctionArgType::Integer) => true,
@@ -128,6 +141,8 @@ impl FunctionRegistry {
                 (DataValue::TSVector(_), FunctionArgType::TSVector) => true,
                 (DataValue::TSQuery(_), FunctionArgType::TSQuery) => true,
                 (_, FunctionArgType::Any) => true,
+                // NULL is accepted anywhere; handlers decide how it propagates
+                (DataValue::Null, _) => true,
                 _ => false,
             };
 
@@ -168,11 +183,13 @@ mod tests {
                     name: "a".to_string(),
                     arg_type: FunctionArgType::Integer,
                     is_optional: false,
+                    is_variadic: false,
                 },
                 FunctionArg {
                     name: "b".to_string(),
                     arg_type: FunctionArgType::Integer,
                     is_optional: false,
+                    is_variadic: false,
                 },
             ],
             return_type: FunctionReturnType::Integer,
@@ -209,26 +226,29 @@ mod tests {
                     name: "str1".to_string(),
                     arg_type: FunctionArgType::String,
                     is_optional: false,
+                    is_variadic: false,
                 },
                 FunctionArg {
                     name: "str2".to_string(),
                     arg_type: FunctionArgType::String,
                     is_optional: false,
+                    is_variadic: false,
                 },
                 FunctionArg {
                     name: "separator".to_string(),
                     arg_type: FunctionArgType::String,
                     is_optional: true,
+                    is_variadic: false,
                 },
             ],
             return_type: FunctionReturnType::String,
             handler: |args| {
                 match args.as_slice() {
                     [DataValue::Text(s1), DataValue::Text(s2), DataValue::Text(sep)] => {
-                        Ok(DataValue::Text(format!("{}{}{}", s1, sep, s2)))
+                        Ok(DataValue::Text(format!("{}{}{}", s1, sep, s2).into()))
                     }
                     [DataValue::Text(s1), DataValue::Text(s2)] => {
-                        Ok(DataValue::Text(format!("{} {}", s1, s2)))
+                        Ok(DataValue::Text(format!("{} {}", s1, s2).into()))
                     }
                     _ => Err(ReefDBError::Other("Invalid argument types".to_string()))
                 }
@@ -240,7 +260,7 @@ mod tests {
         // Test: Too few arguments
         let err = registry.call(
             "concat_with_sep",
-            vec![DataValue::Text("Hello".to_string())]
+            vec![DataValue::Text("Hello".into())]
         ).unwrap_err();
         assert!(err.to_string().contains("expects 2 to 3 arguments, got 1"));
         assert!(err.to_string().contains("Required arguments: str1, str2"));
@@ -249,7 +269,7 @@ mod tests {
         let err = registry.call(
             "concat_with_sep",
             vec![
-                DataValue::Text("Hello".to_string()),
+                DataValue::Text("Hello".into()),
                 DataValue::Integer(42),
             ]
         ).unwrap_err();
@@ -259,21 +279,21 @@ mod tests {
         let result = registry.call(
             "concat_with_sep",
             vec![
-                DataValue::Text("Hello".to_string()),
-                DataValue::Text("World".to_string()),
+                DataValue::Text("Hello".into()),
+                DataValue::Text("World".into()),
             ]
         ).unwrap();
-        assert_eq!(result, DataValue::Text("Hello World".to_string()));
+        assert_eq!(result, DataValue::Text("Hello World".into()));
 
         // Test: Optional argument provided
         let result = registry.call(
             "concat_with_sep",
             vec![
-                DataValue::Text("Hello".to_string()),
-                DataValue::Text("World".to_string()),
-                DataValue::Text(", ".to_string()),
+                DataValue::Text("Hello".into()),
+                DataValue::Text("World".into()),
+                DataValue::Text(", ".into()),
             ]
         ).unwrap();
-        assert_eq!(result, DataValue::Text("Hello, World".to_string()));
+        assert_eq!(result, DataValue::Text("Hello, World".into()));
     }
 } 
\ No newline at end of file
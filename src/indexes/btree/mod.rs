@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
 use std::collections::HashSet;
+use std::ops::Bound;
 use serde::{Deserialize, Serialize};
+use crate::sql::data_value::DataValue;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BTreeIndex {
@@ -15,6 +17,60 @@ impl BTreeIndex {
         }
     }
 
+    /// Encodes a column value the way index entries are keyed. Integers keep their
+    /// variant tag but are written big-endian with the sign bit flipped, so integer keys
+    /// sort numerically.
+    pub fn key_for(value: &DataValue) -> Vec<u8> {
+        let mut key = bincode::serialize(value).unwrap_or_default();
+        if let DataValue::Integer(n) = value {
+            let at = key.len() - 8;
+            key[at..].copy_from_slice(&((*n as u64) ^ (1 << 63)).to_be_bytes());
+        }
+        key
+    }
+
+    /// The value `key_for` encoded
+    pub fn value_for(key: &[u8]) -> Option<DataValue> {
+        match bincode::deserialize(key).ok()? {
+            DataValue::Integer(_) => {
+                let bytes: [u8; 8] = key[key.len() - 8..].try_into().ok()?;
+                Some(DataValue::Integer((u64::from_be_bytes(bytes) ^ (1 << 63)) as i64))
+            }
+            value => Some(value),
+        }
+    }
+
+    /// Key under a text index: the raw UTF-8 bytes, which sort the way the strings do.
+    /// Values that aren't text keep their usual encoding.
+    pub fn text_key_for(value: &DataValue) -> Vec<u8> {
+        match value {
+            DataValue::Text(text) => text.as_bytes().to_vec(),
+            other => Self::key_for(other),
+        }
+    }
+
+    /// Builds an index over column `column_idx` of `rows`, using 1-based row ids like `push_value`
+    pub fn from_rows(rows: &[Vec<DataValue>], column_idx: usize) -> Self {
+        let mut index = BTreeIndex::new();
+        for (i, row) in rows.iter().enumerate() {
+            if let Some(value) = row.get(column_idx) {
+                index.add_entry(Self::key_for(value), i + 1);
+            }
+        }
+        index
+    }
+
+    /// `from_rows` with the keys of a text index
+    pub fn from_text_rows(rows: &[Vec<DataValue>], column_idx: usize) -> Self {
+        let mut index = BTreeIndex::new();
+        for (i, row) in rows.iter().enumerate() {
+            if let Some(value) = row.get(column_idx) {
+                index.add_entry(Self::text_key_for(value), i + 1);
+            }
+        }
+        index
+    }
+
     pub fn add_entry(&mut self, value: Vec<u8>, row_id: usize) {
         self.index
             .entry(value)
@@ -32,11 +88,7 @@ impl BTreeIndex {
     }
 
     pub fn search(&self, value: Vec<u8>) -> Option<&HashSet<usize>> {
-        println!("BTreeIndex::search - Searching for value: {:?}", value);
-        println!("BTreeIndex::search - Current index contents: {:?}", self.index);
-        let result = self.index.get(&value);
-        println!("BTreeIndex::search - Found result: {:?}", result);
-        result
+        self.index.get(&value)
     }
 
     pub fn range_search(&self, start: Vec<u8>, end: Vec<u8>) -> HashSet<usize> {
@@ -47,6 +99,29 @@ impl BTreeIndex {
         results
     }
 
+    /// First non-NULL value more than one row is indexed under, decoded from its key
+    pub fn first_duplicate(&self) -> Option<DataValue> {
+        let null = Self::key_for(&DataValue::Null);
+        self.index.iter()
+            .find(|(key, rows)| rows.len() > 1 && **key != null)
+            .and_then(|(key, _)| Self::value_for(key))
+    }
+
+    /// Row ids of every key starting with `prefix`, in key order
+    pub fn prefix_search(&self, prefix: &[u8]) -> Vec<usize> {
+        self.index.range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .flat_map(|(_, rows)| rows.iter().copied())
+            .collect()
+    }
+
+    /// Row ids in key order, from the first key after `after`, or from the first key
+    pub fn ids_after(&self, after: Option<Vec<u8>>) -> impl Iterator<Item = usize> + '_ {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        self.index.range((start, Bound::Unbounded))
+            .flat_map(|(_, rows)| rows.iter().copied())
+    }
+
     pub fn iter(&self) -> std::collections::btree_map::Iter<Vec<u8>, HashSet<usize>> {
         self.index.iter()
     }
@@ -114,4 +189,41 @@ mod tests {
         assert!(result.contains(&1));
         assert!(result.contains(&2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_first_duplicate_ignores_nulls() {
+        let rows = vec![
+            vec![DataValue::Null],
+            vec![DataValue::Integer(1)],
+            vec![DataValue::Null],
+        ];
+        assert_eq!(BTreeIndex::from_rows(&rows, 0).first_duplicate(), None);
+
+        let rows = vec![vec![DataValue::Integer(1)], vec![DataValue::Integer(1)]];
+        assert_eq!(BTreeIndex::from_rows(&rows, 0).first_duplicate(), Some(DataValue::Integer(1)));
+    }
+
+    #[test]
+    fn test_integer_keys_sort_numerically() {
+        let values = [-300, -1, 0, 1, 255, 256, i64::MAX];
+        let rows: Vec<Vec<DataValue>> = values.iter().rev().map(|n| vec![DataValue::Integer(*n)]).collect();
+        let index = BTreeIndex::from_rows(&rows, 0);
+        let decoded: Vec<DataValue> = index.iter().filter_map(|(key, _)| BTreeIndex::value_for(key)).collect();
+        assert_eq!(decoded, values.map(DataValue::Integer));
+
+        // Row ids are 1-based and the rows were added in reverse
+        let after = BTreeIndex::key_for(&DataValue::Integer(0));
+        assert_eq!(index.ids_after(Some(after)).collect::<Vec<_>>(), vec![4, 3, 2, 1]);
+        assert_eq!(index.ids_after(None).count(), values.len());
+    }
+
+    #[test]
+    fn test_prefix_search_over_text_keys() {
+        let mut index = BTreeIndex::new();
+        for (i, name) in ["Bookshelf", "Book", "Boot", "Ebook", "Books"].iter().enumerate() {
+            index.add_entry(BTreeIndex::text_key_for(&DataValue::Text((*name).into())), i + 1);
+        }
+        assert_eq!(index.prefix_search(b"Book"), vec![2, 5, 1]);
+        assert!(index.prefix_search(b"Z").is_empty());
+    }
+}
@@ -162,6 +162,10 @@ impl<T: Tokenizer> GinIndex<T> {
         self.add_document(table, column, row_id, text);
     }
 
+    pub fn remove_table(&mut self, table: &str) {
+        self.index.remove(table);
+    }
+
     pub fn search(&self, table: &str, column: &str, query: &str) -> HashSet<usize> {
         if let Some(table_entry) = self.index.get(table) {
             if let Some(column_entry) = table_entry.get(column) {
@@ -209,6 +213,10 @@ impl<T: Tokenizer + Serialize + for<'de> Deserialize<'de>> Search for GinIndex<T
     fn update_document(&mut self, table: &str, column: &str, row_id: usize, text: &str) {
         GinIndex::update_document(self, table, column, row_id, text)
     }
+
+    fn remove_table(&mut self, table: &str) {
+        GinIndex::remove_table(self, table)
+    }
 }
 
 #[cfg(test)]
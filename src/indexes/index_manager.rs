@@ -332,12 +332,12 @@ impl OnDiskIndexManager {
             .create(true)
             .append(true)
             .open(&wal_path)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
         
         let mut writer = BufWriter::new(file);
         bincode::serialize_into(&mut writer, entry)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
-        writer.flush().map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
+        writer.flush().map_err(ReefDBError::from)?;
         Ok(())
     }
 
@@ -348,7 +348,7 @@ impl OnDiskIndexManager {
         }
 
         let mut file = File::open(&wal_path)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
         
         let mut active_txns: HashMap<u64, Vec<IndexUpdate>> = HashMap::new();
         
@@ -415,7 +415,7 @@ impl OnDiskIndexManager {
         }
 
         // Clear WAL after recovery
-        std::fs::remove_file(wal_path).map_err(|e| ReefDBError::IoError(e.to_string()))?;
+        std::fs::remove_file(wal_path).map_err(ReefDBError::from)?;
         Ok(())
     }
 }
@@ -426,7 +426,7 @@ impl IndexManager for OnDiskIndexManager {
             .entry(table.to_string())
             .or_insert_with(HashMap::new)
             .insert(column.to_string(), index_type);
-        self.save().map_err(|e| ReefDBError::IoError(e.to_string()))?;
+        self.save().map_err(ReefDBError::from)?;
         Ok(())
     }
 
@@ -464,7 +464,7 @@ impl IndexManager for OnDiskIndexManager {
                     gin.add_document(table, column, row_id, &new_text);
                 }
             }
-            self.save().map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            self.save().map_err(ReefDBError::from)?;
             Ok(())
         } else {
             Err(ReefDBError::Other(format!("Index not found for {}.{}", table, column)))
@@ -54,6 +54,11 @@ impl KeyFormat {
         )
     }
     
+    /// Prefix shared by every row key of a table: r:{table_name}:
+    pub fn row_prefix(table_name: &str) -> String {
+        format!("{}{}{}{}", KEY_NAMESPACE_ROW, KEY_SEPARATOR, table_name, KEY_SEPARATOR)
+    }
+    
     /// Create an index key
     pub fn index(table_name: &str, column_name: &str, value: &str) -> String {
         format!(
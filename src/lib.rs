@@ -1,15 +1,20 @@
 use functions::{register_builtins, FunctionRegistry};
-use result::{QueryResult, ColumnInfo};
+use result::{QueryResult, ColumnInfo, KeysetPage};
 use sql::column::ColumnType;
 use sql::data_type::DataType;
 
 use crate::sql::{
     clauses::{
-        join_clause::JoinClause,
-        wheres::where_type::WhereType,
+        join_clause::{JoinClause, JoinType},
+        wheres::where_type::{WhereClause, WhereType},
         order_by::{OrderByClause, OrderDirection},
     },
+    collation::Collation,
     column_def::ColumnDef,
+    case_expression::CaseExpression,
+    window::{WindowFunction, WindowFunctionKind},
+    aggregate::AggregateFunction,
+    constraints::constraint::Constraint,
     data_value::DataValue,
     table_reference::TableReference,
     column::Column,
@@ -18,26 +23,38 @@ use crate::sql::{
         create::CreateStatement,
         drop::DropStatement,
         alter::{AlterStatement, AlterType},
-        insert::InsertStatement,
-        select::SelectStatement,
+        insert::{ConflictAction, InsertStatement, OnConflict},
+        merge::{MergeSide, MergeStatement, MergeValue},
+        select::{CommonTableExpression, RowLimit, SelectStatement},
         update::UpdateStatement,
         delete::DeleteStatement,
-        create_index::CreateIndexStatement,
+        create_index::{expression_key, CreateIndexStatement, IndexType as CreateIndexType},
         drop_index::DropIndexStatement,
     },
 };
 use crate::result::ReefDBResult;
 use crate::error::ReefDBError;
-use crate::transaction::IsolationLevel;
+use crate::transaction::{IsolationLevel, RowChange, RowDelta, RowDeltas};
 use crate::transaction_manager::TransactionManager;
-use crate::wal::WriteAheadLog;
+use crate::plan_cache::{PlanCache, PlanCacheStats};
+use crate::clock::{Clock, SystemClock};
+use crate::metrics::{MetricsSink, NoopMetrics};
+use crate::ttl::Expiry;
+use crate::wal::{WriteAheadLog, WALOperation};
 use crate::mvcc::MVCCManager;
-use crate::storage::{Storage, TableStorage};
+use crate::locks::{lock_recovering, LockEntry};
+use crate::storage::{temporary::TemporaryOverlay, ShadowedTable, Storage, TableStorage};
 use crate::indexes::{index_manager::IndexManager, btree::BTreeIndex, index_manager::IndexType};
 use crate::fts::search::Search;
+use crate::statistics::{TableStatistics, DEFAULT_HISTOGRAM_BUCKETS};
+use crate::sql::operators::op::{like_prefix, Op};
+use crate::sql::constraints::generated::referenced_columns;
+use std::collections::{HashMap, HashSet};
 use std::any::Any;
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Utc};
 
 pub mod storage;
 pub mod transaction;
@@ -46,6 +63,7 @@ pub mod wal;
 pub mod error;
 pub mod acid;
 pub mod result;
+pub mod row;
 pub mod mvcc;
 pub mod deadlock;
 pub mod sql;
@@ -55,6 +73,11 @@ pub mod locks;
 pub mod key_format;
 pub mod fts;
 pub mod functions;
+pub mod statistics;
+pub mod metrics;
+pub mod plan_cache;
+pub mod clock;
+pub mod ttl;
 #[cfg(test)]
 pub mod tests;
 
@@ -64,19 +87,40 @@ pub type MmapReefDB = ReefDB<storage::mmap::MmapStorage, fts::default::DefaultSe
 
 impl InMemoryReefDB {
     pub fn create_in_memory() -> Result<Self, ReefDBError> {
+        Self::create_on_storage(storage::memory::InMemoryStorage::new())
+    }
+
+    /// In-memory database whose tables keep a single copy of each distinct text value,
+    /// for tables with many repeated values such as categories or statuses
+    pub fn create_in_memory_with_interned_text() -> Result<Self, ReefDBError> {
+        Self::create_on_storage(storage::memory::InMemoryStorage::with_interned_text())
+    }
+
+    fn create_on_storage(storage: storage::memory::InMemoryStorage) -> Result<Self, ReefDBError> {
         let mut function_registry = FunctionRegistry::new();
         register_builtins(&mut function_registry)?;
         let mut db = ReefDB {
             tables: TableStorage::new(),
             inverted_index: fts::default::DefaultSearchIdx::new(),
-            storage: storage::memory::InMemoryStorage::new(),
+            storage: TemporaryOverlay::over(storage),
             transaction_manager: None,
             data_dir: None,
             autocommit: true,
             autocommit_isolation_level: IsolationLevel::ReadCommitted,
             mvcc_manager: Arc::new(Mutex::new(MVCCManager::new())),
             current_transaction_id: None,
+            pending_constraints: None,
+            written_rows: None,
+            expression_indexes: HashMap::new(),
+            unique_indexes: HashMap::new(),
+            text_indexes: HashMap::new(),
             function_registry: function_registry,
+            statistics: Arc::new(Mutex::new(HashMap::new())),
+            plan_cache: Arc::new(Mutex::new(PlanCache::default())),
+            clock: Arc::new(SystemClock),
+            metrics: Arc::new(NoopMetrics),
+            sequences: Arc::new(Mutex::new(HashMap::new())),
+            reserved_prefix: DEFAULT_RESERVED_PREFIX.to_string(),
         };
         db.transaction_manager = Some(TransactionManager::create(
             db.clone(),
@@ -89,7 +133,7 @@ impl InMemoryReefDB {
 impl OnDiskReefDB {
     pub fn create_on_disk(kv_path: String, _index_path: String) -> Result<Self, ReefDBError> {
         let mut db = ReefDB::<storage::disk::OnDiskStorage, fts::default::DefaultSearchIdx>::create_with_args(
-            storage::disk::OnDiskStorage::new(kv_path.clone()),
+            storage::disk::OnDiskStorage::new(kv_path.clone())?,
             Default::default(),
         );
         db.transaction_manager = Some(TransactionManager::create(
@@ -98,6 +142,27 @@ impl OnDiskReefDB {
         ));
         Ok(db)
     }
+
+    /// Opens an on-disk database, refusing it if its schema version is newer than `supported`
+    pub fn open_with_schema_version(kv_path: String, index_path: String, supported: u32) -> Result<Self, ReefDBError> {
+        let db = Self::create_on_disk(kv_path, index_path)?;
+        db.check_schema_version(supported)?;
+        Ok(db)
+    }
+}
+
+/// Iterations a recursive CTE may run before the query is abandoned as non-terminating
+pub const MAX_RECURSIVE_CTE_ITERATIONS: usize = 1000;
+
+/// Table names starting with this prefix are kept for system catalogs unless
+/// `ReefDB::set_reserved_prefix` picks another
+pub const DEFAULT_RESERVED_PREFIX: &str = "reef_";
+
+/// Output columns a WITH TIES limit compares rows on, the last `appended` of which were
+/// only added to the query's projection for the comparison
+pub(crate) struct TieKeys {
+    columns: Vec<Column>,
+    appended: usize,
 }
 
 #[derive(Clone)]
@@ -107,14 +172,37 @@ where
 {
     pub(crate) tables: TableStorage,
     pub(crate) inverted_index: FTS,
-    pub(crate) storage: S,
+    /// The backend, with this copy's temporary tables layered on top in memory
+    pub(crate) storage: TemporaryOverlay<S>,
     pub(crate) transaction_manager: Option<TransactionManager<S, FTS>>,
     pub(crate) data_dir: Option<PathBuf>,
     pub(crate) autocommit: bool,
     pub(crate) autocommit_isolation_level: IsolationLevel,
     pub(crate) mvcc_manager: Arc<Mutex<MVCCManager>>,
     pub(crate) current_transaction_id: Option<u64>,
+    /// (table, column) pairs whose deferred constraint checks wait for COMMIT. Only set
+    /// inside an explicit BEGIN and on the copies transactions run their statements on;
+    /// implicit autocommit transactions check immediately.
+    pub(crate) pending_constraints: Option<Vec<(String, String)>>,
+    /// Rows INSERT, UPDATE, DELETE and MERGE wrote, recorded where they're matched when set.
+    /// Transactions' copies set it so the transaction manager can log each statement's rows.
+    pub(crate) written_rows: Option<Vec<RowChange>>,
+    /// Expression indexes per table, as the name each is stored under and the expression
+    /// whose value it keys rows by
+    pub(crate) expression_indexes: HashMap<String, Vec<(String, DataValue)>>,
+    /// Indexes created with CREATE UNIQUE INDEX, by table
+    pub(crate) unique_indexes: HashMap<String, Vec<String>>,
+    /// Indexes created with CREATE TEXT INDEX, by table; their keys are the raw text
+    pub(crate) text_indexes: HashMap<String, Vec<String>>,
     pub(crate) function_registry: FunctionRegistry,
+    pub(crate) statistics: Arc<Mutex<HashMap<String, TableStatistics>>>,
+    pub(crate) plan_cache: Arc<Mutex<PlanCache>>,
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Sink told how many rows of each table a SELECT read
+    pub(crate) metrics: Arc<dyn MetricsSink>,
+    /// Last value handed out by each sequence, shared with transactions' copies of the database
+    pub(crate) sequences: Arc<Mutex<HashMap<String, i64>>>,
+    pub(crate) reserved_prefix: String,
 }
 
 impl<S: Storage + IndexManager + Clone + Any, FTS: Search + Clone> ReefDB<S, FTS>
@@ -122,19 +210,38 @@ where
     FTS::NewArgs: Clone + Default,
 {
     fn create_with_args(storage: S, fts_args: FTS::NewArgs) -> Self {
+        let sequences = Self::stored_sequence_values(&storage);
+        // The mirror starts out holding the tables already stored
+        let mut tables = TableStorage::new();
+        for table_name in storage.table_names() {
+            if let Some((schema, rows)) = storage.get_table_ref(&table_name) {
+                tables.insert_table(table_name, schema.clone(), rows.clone());
+            }
+        }
         let mut function_registry = FunctionRegistry::new();
         register_builtins(&mut function_registry).unwrap();
         let mut db = ReefDB {
-            tables: TableStorage::new(),
+            tables,
             inverted_index: FTS::new(fts_args),
-            storage,
+            storage: TemporaryOverlay::over(storage),
             transaction_manager: None,
             data_dir: None,
             autocommit: true,
             autocommit_isolation_level: IsolationLevel::ReadCommitted,
             mvcc_manager: Arc::new(Mutex::new(MVCCManager::new())),
             current_transaction_id: None,
+            pending_constraints: None,
+            written_rows: None,
+            expression_indexes: HashMap::new(),
+            unique_indexes: HashMap::new(),
+            text_indexes: HashMap::new(),
             function_registry: function_registry,
+            statistics: Arc::new(Mutex::new(HashMap::new())),
+            plan_cache: Arc::new(Mutex::new(PlanCache::default())),
+            clock: Arc::new(SystemClock),
+            metrics: Arc::new(NoopMetrics),
+            sequences: Arc::new(Mutex::new(sequences)),
+            reserved_prefix: DEFAULT_RESERVED_PREFIX.to_string(),
         };
 
         let transaction_manager = Some(TransactionManager::create(
@@ -145,6 +252,37 @@ where
         db
     }
 
+    // Sequences aren't stored, so each picks up after the largest value already in a column
+    // that takes its default from it
+    fn stored_sequence_values(storage: &S) -> HashMap<String, i64> {
+        let mut sequences = HashMap::new();
+        for table_name in storage.table_names() {
+            let Some((schema, rows)) = storage.get_table_ref(&table_name) else {
+                continue;
+            };
+            for (idx, column) in schema.iter().enumerate() {
+                let Some(DataValue::Function { name, args }) = column.default_value() else {
+                    continue;
+                };
+                let [DataValue::Text(sequence)] = args.as_slice() else {
+                    continue;
+                };
+                if !name.eq_ignore_ascii_case("nextval") {
+                    continue;
+                }
+                let largest = rows.iter()
+                    .filter_map(|row| match row.get(idx) {
+                        Some(DataValue::Integer(value)) => Some(*value),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let value = sequences.entry(sequence.to_string()).or_insert(0);
+                *value = largest.max(*value);
+            }
+        }
+        sequences
+    }
 
     fn verify_table_exists(&self, table_name: &str) -> Result<(), ReefDBError> {
         if !self.storage.table_exists(table_name) {
@@ -158,16 +296,192 @@ where
             .ok_or_else(|| ReefDBError::TableNotFound(table_name.to_string()))
     }
 
-    fn handle_create(&mut self, name: String, columns: Vec<ColumnDef>) -> Result<ReefDBResult, ReefDBError> {
+    fn handle_create_statement(&mut self, create_stmt: CreateStatement) -> Result<ReefDBResult, ReefDBError> {
+        match create_stmt {
+            CreateStatement::Table(name, columns, if_not_exists) => self.handle_create(name, columns, if_not_exists),
+            CreateStatement::TableWithTtl(name, columns, if_not_exists, ttl) => {
+                self.handle_create_with_ttl(name, columns, if_not_exists, ttl)
+            }
+            CreateStatement::TemporaryTable(name, columns, if_not_exists) => {
+                self.handle_create_temporary(name, columns, if_not_exists)
+            }
+        }
+    }
+
+    // A temporary table takes the place of any table of the same name for this copy of
+    // the database. It lives in the storage's in-memory layer, so it is never persisted;
+    // the table it hides keeps its rows and indexes there, out of reach until it is dropped.
+    fn handle_create_temporary(&mut self, name: String, columns: Vec<ColumnDef>, if_not_exists: bool) -> Result<ReefDBResult, ReefDBError> {
+        if self.tables.is_temporary(&name) {
+            if if_not_exists {
+                return Ok(ReefDBResult::CreateTable);
+            }
+            return Err(ReefDBError::Other(format!("Table {} already exists", name)));
+        }
+        let shadowed = self.shadow_table(&name);
+        self.storage.add_temporary(&name);
+        match self.handle_create(name.clone(), columns, false) {
+            Ok(result) => {
+                self.tables.add_temporary(name, shadowed);
+                Ok(result)
+            }
+            Err(e) => {
+                self.storage.drop_table(&name);
+                if let Some(shadowed) = shadowed {
+                    self.restore_shadowed_table(&name, shadowed);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Takes `name` out of the mirror and the index registries, returning what's needed to put them back
+    fn shadow_table(&mut self, name: &str) -> Option<ShadowedTable> {
+        let (schema, rows) = self.storage.get_table_ref(name).cloned()?;
+        self.tables.drop_table(name);
+        Some(ShadowedTable {
+            schema,
+            rows,
+            expression_indexes: self.expression_indexes.remove(name).unwrap_or_default(),
+            unique_indexes: self.unique_indexes.remove(name).unwrap_or_default(),
+            text_indexes: self.text_indexes.remove(name).unwrap_or_default(),
+        })
+    }
+
+    fn restore_shadowed_table(&mut self, name: &str, shadowed: ShadowedTable) {
+        self.tables.insert_table(name.to_string(), shadowed.schema, shadowed.rows);
+        for (registry, entries) in [
+            (&mut self.unique_indexes, shadowed.unique_indexes),
+            (&mut self.text_indexes, shadowed.text_indexes),
+        ] {
+            if !entries.is_empty() {
+                registry.insert(name.to_string(), entries);
+            }
+        }
+        if !shadowed.expression_indexes.is_empty() {
+            self.expression_indexes.insert(name.to_string(), shadowed.expression_indexes);
+        }
+    }
+
+    fn drop_table_indexes(&mut self, name: &str, schema: &[ColumnDef], expression_indexes: &[(String, DataValue)]) {
+        for column in schema {
+            self.storage.drop_index(name, &column.name);
+        }
+        for (key, _) in expression_indexes {
+            self.storage.drop_index(name, key);
+        }
+    }
+
+    // Only this copy of the database ever had the table, so the shared MVCC versions
+    // and statistics are left alone
+    fn drop_temporary_table(&mut self, name: &str) -> Result<(), ReefDBError> {
+        let Some(shadowed) = self.tables.take_temporary(name) else {
+            return Ok(());
+        };
+        let schema = self.storage.get_schema_ref(name).cloned().unwrap_or_default();
+        let expression_indexes = self.expression_indexes.remove(name).unwrap_or_default();
+        self.drop_table_indexes(name, &schema, &expression_indexes);
+        self.unique_indexes.remove(name);
+        self.text_indexes.remove(name);
+        self.storage.drop_table(name);
+        self.tables.drop_table(name);
+        match shadowed {
+            Some(shadowed) => self.restore_shadowed_table(name, shadowed),
+            None => self.inverted_index.remove_table(name),
+        }
+        Ok(())
+    }
+
+    /// Drops every temporary table, as happens when the transaction that made them ends
+    pub(crate) fn drop_temporary_tables(&mut self) -> Result<(), ReefDBError> {
+        for name in self.tables.temporary_names() {
+            self.drop_temporary_table(&name)?;
+        }
+        Ok(())
+    }
+
+    // Rows carry their insertion time in a TTL column appended to the table
+    fn handle_create_with_ttl(&mut self, name: String, mut columns: Vec<ColumnDef>, if_not_exists: bool, ttl: Duration) -> Result<ReefDBResult, ReefDBError> {
+        if columns.iter().any(|c| c.name == ttl::INSERTED_AT_COLUMN) {
+            return Err(ReefDBError::Other(format!(
+                "Table {} cannot have a TTL and its own {} column", name, ttl::INSERTED_AT_COLUMN
+            )));
+        }
+        columns.push(ttl::inserted_at_column(ttl));
+        self.handle_create(name, columns, if_not_exists)
+    }
+
+    /// Which rows of `schema`'s table have outlived its TTL, by the database's clock
+    pub(crate) fn expiry(&self, schema: &[ColumnDef]) -> Expiry {
+        Expiry::new(schema, self.clock.now())
+    }
+
+    /// Replaces the clock used for row expiry, WAL timestamps and fsync intervals and MVCC
+    /// commit times, e.g. with a `MockClock` in tests
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        if let Some(tm) = &mut self.transaction_manager {
+            tm.set_clock(clock.clone());
+        }
+        lock_recovering(&self.mvcc_manager).set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Sink notified of the rows SELECTs read, and through the transaction manager of lock
+    /// waits, deadlocks and commits
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        if let Some(tm) = &mut self.transaction_manager {
+            tm.set_metrics_sink(sink.clone());
+        }
+        self.metrics = sink;
+    }
+
+    /// Sets the prefix of the table names kept for system catalogs, matched case-insensitively.
+    /// An empty prefix reserves nothing.
+    pub fn set_reserved_prefix(&mut self, prefix: impl Into<String>) {
+        let prefix = prefix.into();
+        if let Some(tm) = &mut self.transaction_manager {
+            tm.set_reserved_prefix(&prefix);
+        }
+        self.reserved_prefix = prefix;
+    }
+
+    fn handle_create(&mut self, name: String, columns: Vec<ColumnDef>, if_not_exists: bool) -> Result<ReefDBResult, ReefDBError> {
         if columns.is_empty() {
             return Err(ReefDBError::Other("Cannot create table with empty column list".to_string()));
         }
+        let prefix = &self.reserved_prefix;
+        if !prefix.is_empty() && name.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix)) {
+            return Err(ReefDBError::ReservedName(name));
+        }
         
         // Check if table exists in either storage or tables
         if self.storage.table_exists(&name) || self.tables.table_exists(&name) {
+            if if_not_exists {
+                return Ok(ReefDBResult::CreateTable);
+            }
             return Err(ReefDBError::Other(format!("Table {} already exists", name)));
         }
         
+        // Generated columns may only read the table's stored columns
+        for column in columns.iter() {
+            if let Some(expression) = column.generated_expression() {
+                for referenced in referenced_columns(expression) {
+                    match columns.iter().find(|c| c.name == referenced) {
+                        None => return Err(ReefDBError::ColumnNotFound(referenced.to_string())),
+                        Some(c) if c.is_generated() => {
+                            return Err(ReefDBError::Other(format!(
+                                "Generated column {} cannot reference generated column {}",
+                                column.name, referenced
+                            )));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        self.check_foreign_key_cycles(&name, &columns)?;
+
         // Create table in both storage and tables
         self.storage.insert_table(name.clone(), columns.clone(), vec![]);
         self.tables.insert_table(name.clone(), columns.clone(), vec![]);
@@ -194,31 +508,29 @@ where
             schema.clone()
         };
 
-        // Validate number of values matches number of columns
-        if values.len() != schema.len() {
-            return Err(ReefDBError::Other(format!(
-                "Number of values ({}) does not match number of columns ({})",
-                values.len(),
-                schema.len()
-            )));
-        }
-
-        // Validate value types match column types
-        for (value, column) in values.iter().zip(schema.iter()) {
-            if !value.matches_type(&column.data_type) {
-                return Err(ReefDBError::Other(format!(
-                    "Value type mismatch for column {}: expected {:?}, got {:?}",
-                    column.name,
-                    column.data_type,
-                    value
-                )));
-            }
-        }
+        let values = Self::coerce_row(&schema, self.with_generated_columns(&schema, values)?)?;
+        Self::validate_row(&schema, &values)?;
+        self.check_foreign_keys(&table_name, &schema, &values, &[])?;
+        self.check_deferred_keys(&table_name, &schema, std::slice::from_ref(&values))?;
+        self.check_unique_indexes(&table_name, &schema, std::slice::from_ref(&values))?;
 
         // Insert the values into both storage and tables
         let row_id = self.storage.push_value(&table_name, values.clone())?;
+        // Carry on with the row as stored, whose text may be shared with other rows
+        let values = self.storage.get_table_ref(&table_name)
+            .and_then(|(_, rows)| rows.last().cloned())
+            .unwrap_or(values);
         self.tables.push_value(&table_name, values.clone())?;
 
+        // Keep B-Tree indexes in step with the new row
+        for (i, col) in schema.iter().enumerate() {
+            if let Ok(IndexType::BTree(_)) = self.storage.get_index(&table_name, &col.name) {
+                let key = self.index_key(&table_name, &col.name, &values[i]);
+                self.storage.update_index(&table_name, &col.name, vec![], key, row_id)?;
+            }
+        }
+        self.index_expressions(&table_name, &schema, &values, row_id)?;
+
         // Update FTS index for any FTS columns
         for (i, col) in schema.iter().enumerate() {
             if col.data_type == DataType::TSVector {
@@ -227,214 +539,1652 @@ where
                 }
             }
         }
+        self.record_written(WALOperation::Insert, &table_name, vec![values]);
 
         Ok(ReefDBResult::Insert(row_id))
     }
 
-    fn handle_select(
-        &self,
-        table_ref: TableReference,
-        columns: Vec<Column>,
-        where_clause: Option<WhereType>,
-        joins: Vec<JoinClause>,
-        order_by: Vec<OrderByClause>,
-    ) -> Result<ReefDBResult, ReefDBError> {
-        self.verify_table_exists(&table_ref.name)?;
-        let (schema, data) = self.get_table_schema(&table_ref.name)?;
-        
-        let mut result = Vec::new();
-        
-        // Get joined schemas if needed
-        let mut joined_schemas = Vec::new();
-        if !joins.is_empty() {
-            for join in &joins {
-                let (join_schema, _) = self.get_table_schema(&join.table_ref.name)?;
-                joined_schemas.push((join.table_ref.name.as_str(), join_schema.as_slice()));
+    // INSERT ... ON CONFLICT. The row is checked against the table's PRIMARY KEY and UNIQUE
+    // values before anything is written. A clash either skips the row, reported as
+    // Update(0), or applies the DO UPDATE assignments to the row it clashed with.
+    fn handle_upsert(&mut self, table_name: String, values: Vec<DataValue>, on_conflict: OnConflict) -> Result<ReefDBResult, ReefDBError> {
+        let schema = {
+            let (schema, _) = self.get_table_schema(&table_name)?;
+            schema.clone()
+        };
+        let is_unique = |c: &ColumnDef| c.constraints.iter().any(|k| matches!(k, Constraint::PrimaryKey | Constraint::Unique));
+        let targets = if on_conflict.columns.is_empty() {
+            (0..schema.len()).filter(|&i| is_unique(&schema[i])).collect()
+        } else {
+            let mut targets = Vec::with_capacity(on_conflict.columns.len());
+            for name in &on_conflict.columns {
+                let i = schema.iter().position(|c| &c.name == name)
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(name.clone()))?;
+                if !is_unique(&schema[i]) {
+                    return Err(ReefDBError::Other(format!(
+                        "ON CONFLICT column {} is not a PRIMARY KEY or UNIQUE column", name
+                    )));
+                }
+                targets.push(i);
+            }
+            targets
+        };
+
+        let row = Self::coerce_row(&schema, self.with_generated_columns(&schema, values.clone())?)?;
+        let existing = self.storage.get_table_ref(&table_name).and_then(|(_, rows)| {
+            targets.iter().find_map(|&i| {
+                if row.get(i).is_none_or(|value| *value == DataValue::Null) {
+                    return None;
+                }
+                let collation = schema[i].collation();
+                let key = collation.key(&row[i]);
+                rows.iter()
+                    .find(|existing| collation.key(&existing[i]) == key)
+                    .map(|existing| (i, existing[i].clone()))
+            })
+        });
+
+        match (existing, on_conflict.action) {
+            (None, _) => self.handle_insert(table_name, values),
+            (Some(_), ConflictAction::DoNothing) => Ok(ReefDBResult::Update(0)),
+            (Some((i, key)), ConflictAction::DoUpdate(assignments)) => {
+                let clash = WhereClause::new(schema[i].name.clone(), Op::Equal, key, None);
+                self.handle_update(table_name, assignments, Some(WhereType::Regular(clash)))
             }
         }
+    }
 
-        // Handle joins if present
-        if !joins.is_empty() {
-            self.handle_join_select(&table_ref.name, schema, data, &columns, where_clause, &joins, &mut result)?;
-        } else {
-            self.handle_simple_select(&table_ref.name, schema, data, &columns, where_clause, &mut result)?;
+    // Rows are written without their generated columns and may also leave out every column
+    // with a DEFAULT; this slots the supplied values into the remaining columns in order, fills
+    // in the defaults and computes the generated columns. Tables with neither pass through.
+    fn with_generated_columns(&self, schema: &[ColumnDef], values: Vec<DataValue>) -> Result<Vec<DataValue>, ReefDBError> {
+        let generated = schema.iter().find(|c| c.is_generated());
+        let defaulted = schema.iter().filter(|c| !c.is_generated() && c.default_value().is_some()).count();
+        if generated.is_none() && defaulted == 0 {
+            return Ok(values);
+        }
+        let stored = schema.iter().filter(|c| !c.is_generated()).count();
+        let use_defaults = defaulted > 0 && values.len() == stored - defaulted;
+        if values.len() != stored && !use_defaults {
+            match generated {
+                Some(generated) if values.len() == schema.len() => {
+                    return Err(ReefDBError::GeneratedColumnWrite(generated.name.clone()));
+                }
+                Some(_) => {
+                    return Err(ReefDBError::ColumnCountMismatch { expected: stored, found: values.len() });
+                }
+                // validate_row reports the mismatch
+                None => return Ok(values),
+            }
         }
 
-        // Apply ordering if present
-        if !order_by.is_empty() {
-            result.sort_by(|(_, row1), (_, row2)| {
-                for order_clause in &order_by {
-                    let col_name = &order_clause.column.name;
-                    let col_idx = schema.iter().position(|col| col.name == *col_name)
-                        .expect("Column not found in schema");
-                    
-                    let cmp = row1[col_idx].partial_cmp(&row2[col_idx])
-                        .unwrap_or(std::cmp::Ordering::Equal);
-                    
-                    match order_clause.direction {
-                        OrderDirection::Asc => if cmp != std::cmp::Ordering::Equal { return cmp; },
-                        OrderDirection::Desc => if cmp != std::cmp::Ordering::Equal { return cmp.reverse(); },
-                    }
+        let mut values = values.into_iter();
+        let mut row = Vec::with_capacity(schema.len());
+        for column in schema {
+            let value = match column.default_value() {
+                _ if column.is_generated() => DataValue::Null,
+                Some(default) if use_defaults => self.evaluate_expression(default, &[], &[], &[], &[])?,
+                _ => values.next().unwrap_or(DataValue::Null),
+            };
+            row.push(value);
+        }
+        self.compute_generated_columns(schema, &mut row)?;
+        Ok(row)
+    }
+
+    fn compute_generated_columns(&self, schema: &[ColumnDef], row: &mut [DataValue]) -> Result<(), ReefDBError> {
+        for (i, column) in schema.iter().enumerate() {
+            if let Some(expression) = column.generated_expression() {
+                row[i] = self.evaluate_expression(expression, row, &[], schema, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects a new table whose NOT NULL foreign keys close a cycle through other tables:
+    /// no row of any table on it could be inserted first. Nullable links break the cycle
+    /// (insert with NULL, update later) and a self-reference is satisfiable by a row that
+    /// references itself, so only multi-table all-NOT NULL cycles are errors. References
+    /// to tables that don't exist yet are allowed so a cycle can be declared at all.
+    fn check_foreign_key_cycles(&self, name: &str, columns: &[ColumnDef]) -> Result<(), ReefDBError> {
+        let required_parents = |schema: &[ColumnDef], table: &str| -> Vec<String> {
+            schema.iter()
+                .filter(|c| c.is_not_null())
+                .filter_map(|c| c.foreign_key())
+                .filter(|fk| fk.table_name != table)
+                .map(|fk| fk.table_name.clone())
+                .collect()
+        };
+
+        let mut visited = HashSet::new();
+        let mut stack: Vec<Vec<String>> = required_parents(columns, name).into_iter()
+            .map(|parent| vec![name.to_string(), parent])
+            .collect();
+        while let Some(path) = stack.pop() {
+            let table = path.last().expect("path is never empty");
+            if table == name {
+                return Err(ReefDBError::ForeignKeyCycle(path.join(" -> ")));
+            }
+            if !visited.insert(table.clone()) {
+                continue;
+            }
+            if let Some(schema) = self.storage.get_schema_ref(table) {
+                for parent in required_parents(schema, table) {
+                    let mut next = path.clone();
+                    next.push(parent);
+                    stack.push(next);
                 }
-                std::cmp::Ordering::Equal
-            });
+            }
         }
+        Ok(())
+    }
 
-        // Create column info
-        let column_info = if joins.is_empty() {
-            ColumnInfo::from_schema_and_columns(schema, &columns, &table_ref.name)?
-        } else {
-            ColumnInfo::from_joined_schemas(schema, &table_ref.name, &joined_schemas, &columns)?
+    /// Every non-NULL foreign key value must exist in the referenced column. A row may
+    /// satisfy its own self-reference, and `batch` lets rows of one bulk load reference
+    /// each other. Each check looks one level up only, so cyclic schemas can't recurse.
+    fn check_foreign_keys(&self, table_name: &str, schema: &[ColumnDef], values: &[DataValue], batch: &[Vec<DataValue>]) -> Result<(), ReefDBError> {
+        for i in 0..schema.len() {
+            self.check_references(table_name, schema, i, &[values], batch)?;
+        }
+        Ok(())
+    }
+
+    // Foreign key check of column `i` over `rows`, which are laid out like `schema`. Values
+    // are looked up in the referenced column's B-Tree index when it has one; otherwise the
+    // parent's keys are gathered once for the whole set of rows.
+    fn check_references(&self, table_name: &str, schema: &[ColumnDef], i: usize, rows: &[&[DataValue]], batch: &[Vec<DataValue>]) -> Result<(), ReefDBError> {
+        let column = &schema[i];
+        let Some(fk) = column.foreign_key() else { return Ok(()) };
+        if column.is_deferred() && self.pending_constraints.is_some() {
+            return Ok(());
+        }
+        // Not created yet: checked once the referenced table exists
+        let Some((parent_schema, parent_rows)) = self.storage.get_table_ref(&fk.table_name) else { return Ok(()) };
+        let parent_index = parent_schema.iter()
+            .position(|c| c.name == fk.column_name)
+            .ok_or_else(|| ReefDBError::ColumnNotFound(format!("{}.{}", fk.table_name, fk.column_name)))?;
+
+        let same_table = fk.table_name == table_name;
+        let index = match self.storage.get_index(&fk.table_name, &fk.column_name) {
+            Ok(IndexType::BTree(btree)) => Some(btree),
+            _ => None,
         };
-        
-        Ok(ReefDBResult::Select(QueryResult::with_columns(result, column_info)))
+        let parent_keys: Option<HashSet<Vec<u8>>> = match index {
+            None if rows.len() > 1 => Some(parent_rows.iter().map(|row| BTreeIndex::key_for(&row[parent_index])).collect()),
+            _ => None,
+        };
+        let batch_keys: HashSet<Vec<u8>> = match same_table {
+            true => batch.iter().map(|row| BTreeIndex::key_for(&row[parent_index])).collect(),
+            false => HashSet::new(),
+        };
+        for values in rows {
+            let value = &values[i];
+            if *value == DataValue::Null {
+                continue;
+            }
+            let key = BTreeIndex::key_for(value);
+            let found = (same_table && values.get(parent_index) == Some(value))
+                || batch_keys.contains(&key)
+                || match (index, &parent_keys) {
+                    (Some(btree), _) => btree.search(self.index_key(&fk.table_name, &fk.column_name, value)).is_some(),
+                    (None, Some(keys)) => keys.contains(&key),
+                    (None, None) => parent_rows.iter().any(|row| row.get(parent_index) == Some(value)),
+                };
+            if !found {
+                return Err(ReefDBError::ForeignKeyViolation(format!(
+                    "{}.{} = {:?} has no matching {}.{}",
+                    table_name, column.name, value, fk.table_name, fk.column_name
+                )));
+            }
+        }
+        Ok(())
     }
 
-    fn handle_simple_select(
-        &self,
-        table_name: &str,
-        schema: &Vec<ColumnDef>,
-        data: &Vec<Vec<DataValue>>,
-        columns: &[Column],
-        where_clause: Option<WhereType>,
-        result: &mut Vec<(usize, Vec<DataValue>)>,
-    ) -> Result<(), ReefDBError> {
-        for (i, row) in data.iter().enumerate() {
-            let include_row = if let Some(where_clause) = &where_clause {
-                self.evaluate_where_clause(where_clause, row, &[], schema, &[], table_name)?
-            } else {
-                true
-            };
+    /// A DELETE or UPDATE may not take a value out of a referenced column while rows of
+    /// another table, or other rows of this one, still point at it. `old` holds the rows
+    /// as they were; `deleted` the positions about to be removed, which no longer count
+    /// as holding or referencing a value. Deferred referencing columns are queued instead.
+    fn check_referencing_rows(&mut self, table_name: &str, old: &[Vec<DataValue>], deleted: &HashSet<usize>) -> Result<(), ReefDBError> {
+        let mut referencing = Vec::new();
+        for child in self.storage.table_names() {
+            let Some(child_schema) = self.storage.get_schema_ref(&child) else { continue };
+            for (i, column) in child_schema.iter().enumerate() {
+                if column.foreign_key().is_some_and(|fk| fk.table_name == table_name) {
+                    referencing.push((child.clone(), i, column.clone()));
+                }
+            }
+        }
+        referencing.retain(|(child, _, column)| !self.defer_check(child, column));
+        if referencing.is_empty() {
+            return Ok(());
+        }
 
-            if include_row {
-                let mut selected_values = Vec::new();
-                for col in columns {
-                    if col.name == "*" {
-                        selected_values.extend(row.iter().cloned());
-                    } else {
-                        match &col.column_type {
-                            ColumnType::Regular(_) => {
-                                let col_idx = schema.iter()
-                                    .position(|c| c.name == col.name)
-                                    .ok_or_else(|| ReefDBError::ColumnNotFound(col.name.clone()))?;
-                                selected_values.push(row[col_idx].clone());
-                            }
-                            ColumnType::Function(name, args) => {
-                                let value = self.evaluate_column(col, row, schema)?;
-                                selected_values.push(value);
-                            }
-                            ColumnType::Wildcard => {
-                                selected_values.extend(row.iter().cloned());
-                            }
-                        }
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        for (child, i, column) in &referencing {
+            let Some(fk) = column.foreign_key() else { continue };
+            let Some(parent_index) = schema.iter().position(|c| c.name == fk.column_name) else { continue };
+            let mut removed: HashMap<Vec<u8>, &DataValue> = old.iter()
+                .map(|row| &row[parent_index])
+                .filter(|value| **value != DataValue::Null)
+                .map(|value| (BTreeIndex::key_for(value), value))
+                .collect();
+            // Values another remaining row still holds stay valid
+            match self.storage.get_index(table_name, &fk.column_name) {
+                Ok(IndexType::BTree(btree)) => removed.retain(|_, value| {
+                    btree.search(self.index_key(table_name, &fk.column_name, value))
+                        .is_none_or(|ids| ids.iter().all(|id| deleted.contains(&(id - 1))))
+                }),
+                _ => for (position, row) in rows.iter().enumerate() {
+                    if !deleted.contains(&position) {
+                        removed.remove(&BTreeIndex::key_for(&row[parent_index]));
                     }
-                }
-                result.push((i, selected_values));
+                },
+            }
+            if removed.is_empty() {
+                continue;
+            }
+            let Some((_, child_rows)) = self.storage.get_table_ref(child) else { continue };
+            let still_referenced = child_rows.iter().enumerate()
+                .filter(|(position, _)| child != table_name || !deleted.contains(position))
+                .find_map(|(_, row)| removed.get(&BTreeIndex::key_for(&row[*i])));
+            if let Some(value) = still_referenced {
+                return Err(ReefDBError::ForeignKeyViolation(format!(
+                    "{}.{} = {:?} is still referenced by {}.{}",
+                    table_name, fk.column_name, value, child, column.name
+                )));
             }
         }
         Ok(())
     }
 
-    fn handle_join_select(
-        &self,
-        table_name: &str,
-        schema: &Vec<ColumnDef>,
-        data: &Vec<Vec<DataValue>>,
-        columns: &[Column],
-        where_clause: Option<WhereType>,
-        joins: &[JoinClause],
-        result: &mut Vec<(usize, Vec<DataValue>)>,
-    ) -> Result<(), ReefDBError> {
-        for join in joins {
-            if let Some((join_schema, join_data)) = self.storage.get_table_ref(&join.table_ref.name) {
-                let left_col_idx = schema.iter()
-                    .position(|c| c.name == join.on.0.column_name)
-                    .ok_or_else(|| ReefDBError::ColumnNotFound(join.on.0.column_name.clone()))?;
-                let right_col_idx = join_schema.iter()
-                    .position(|c| c.name == join.on.1.column_name)
-                    .ok_or_else(|| ReefDBError::ColumnNotFound(join.on.1.column_name.clone()))?;
+    fn validate_row(schema: &[ColumnDef], values: &[DataValue]) -> Result<(), ReefDBError> {
+        // Validate number of values matches number of columns
+        if values.len() != schema.len() {
+            return Err(ReefDBError::ColumnCountMismatch { expected: schema.len(), found: values.len() });
+        }
 
-                for (i, row) in data.iter().enumerate() {
-                    for join_row in join_data.iter() {
-                        if row[left_col_idx] == join_row[right_col_idx] {
-                            let include_row = if let Some(where_clause) = &where_clause {
-                                self.evaluate_where_clause(where_clause, row, join_row, schema, join_schema, table_name)?
-                            } else {
-                                true
-                            };
-
-                            if include_row {
-                                let mut selected_values = Vec::new();
-                                for col in columns {
-                                    if col.name == "*" {
-                                        selected_values.extend(row.iter().cloned());
-                                        selected_values.extend(join_row.iter().cloned());
+        // Validate value types match column types
+        for (value, column) in values.iter().zip(schema.iter()) {
+            if !value.matches_type(&column.data_type) {
+                return Err(ReefDBError::Other(format!(
+                    "Value type mismatch for column {}: expected {:?}, got {:?}",
+                    column.name,
+                    column.data_type,
+                    value
+                )));
+            }
+            Self::check_domain(column, value)?;
+        }
+        Ok(())
+    }
+
+    /// Validates the SET list of an UPDATE: every column exists and isn't generated, and
+    /// each value is brought to its column's representation and checked against its type
+    /// and domain, as inserted values are
+    pub(crate) fn check_assignments(schema: &[ColumnDef], updates: &mut [(String, DataValue)]) -> Result<(), ReefDBError> {
+        for (col_name, value) in updates.iter_mut() {
+            let column = schema.iter()
+                .find(|c| &c.name == col_name)
+                .ok_or_else(|| ReefDBError::ColumnNotFound(col_name.clone()))?;
+            if column.is_generated() {
+                return Err(ReefDBError::GeneratedColumnWrite(col_name.clone()));
+            }
+            *value = Self::coerce_value(column, value.clone())?;
+
+            if !value.matches_type(&column.data_type) {
+                return Err(ReefDBError::Other(format!(
+                    "Value type mismatch for column {}: expected {:?}, got {:?}",
+                    col_name,
+                    column.data_type,
+                    value
+                )));
+            }
+            Self::check_domain(column, value)?;
+        }
+        Ok(())
+    }
+
+    // Brings numeric literals to the representation of their column, e.g. DECIMAL scale
+    fn coerce_row(schema: &[ColumnDef], values: Vec<DataValue>) -> Result<Vec<DataValue>, ReefDBError> {
+        if values.len() != schema.len() {
+            return Ok(values);
+        }
+        values.into_iter()
+            .zip(schema)
+            .map(|(value, column)| Self::coerce_value(column, value))
+            .collect()
+    }
+
+    fn coerce_value(column: &ColumnDef, value: DataValue) -> Result<DataValue, ReefDBError> {
+        value.coerce_to(&column.data_type)
+            .map_err(|msg| ReefDBError::Other(format!("Invalid value for column {}: {}", column.name, msg)))
+    }
+
+    // Limits the column type places on text: VARCHAR length and ENUM labels
+    fn check_domain(column: &ColumnDef, value: &DataValue) -> Result<(), ReefDBError> {
+        let DataValue::Text(text) = value else {
+            return Ok(());
+        };
+        if let Some(max) = column.data_type.max_length() {
+            if text.chars().count() > max {
+                return Err(ReefDBError::ValueTooLong(column.name.clone(), max));
+            }
+        }
+        if let DataType::Enum(labels) = &column.data_type {
+            if !labels.iter().any(|label| **label == **text) {
+                return Err(ReefDBError::InvalidEnumValue(column.name.clone(), text.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a deferred column's checks for COMMIT when an explicit transaction is open.
+    /// Returns false when the column has to be checked now.
+    fn defer_check(&mut self, table_name: &str, column: &ColumnDef) -> bool {
+        let Some(pending) = self.pending_constraints.as_mut() else { return false };
+        if !column.is_deferred() {
+            return false;
+        }
+        let entry = (table_name.to_string(), column.name.clone());
+        if !pending.contains(&entry) {
+            pending.push(entry);
+        }
+        true
+    }
+
+    // Storage leaves UNIQUE and PRIMARY KEY to us on deferred columns: they're queued inside
+    // an explicit transaction and otherwise checked against the rows about to be written.
+    fn check_deferred_keys(&mut self, table_name: &str, schema: &[ColumnDef], new_rows: &[Vec<DataValue>]) -> Result<(), ReefDBError> {
+        for (i, column) in schema.iter().enumerate() {
+            if !column.is_deferred() || self.defer_check(table_name, column) || !Self::is_key(column) {
+                continue;
+            }
+            let (_, rows) = self.get_table_schema(table_name)?;
+            Self::check_unique(table_name, column, i, rows.iter().chain(new_rows))?;
+        }
+        Ok(())
+    }
+
+    fn is_key(column: &ColumnDef) -> bool {
+        column.constraints.iter().any(|c| matches!(c, Constraint::PrimaryKey | Constraint::Unique))
+    }
+
+    fn check_unique<'a>(
+        table_name: &str,
+        column: &ColumnDef,
+        index: usize,
+        rows: impl Iterator<Item = &'a Vec<DataValue>>,
+    ) -> Result<(), ReefDBError> {
+        let collation = column.collation();
+        let mut seen = HashSet::new();
+        for value in rows.map(|row| &row[index]).filter(|value| **value != DataValue::Null) {
+            if !seen.insert(BTreeIndex::key_for(&collation.key(value))) {
+                return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, column.name, value)));
+            }
+        }
+        Ok(())
+    }
+
+    // Value a row is keyed by in the index named `index`: a column's value or an expression's result
+    fn index_value(&self, table_name: &str, index: &str, schema: &[ColumnDef], row: &[DataValue]) -> Result<DataValue, ReefDBError> {
+        let expression = self.expression_indexes.get(table_name)
+            .and_then(|indexes| indexes.iter().find(|(key, _)| key == index));
+        if let Some((_, expression)) = expression {
+            return self.evaluate_expression(expression, row, &[], schema, &[]);
+        }
+        let i = schema.iter().position(|c| c.name == index)
+            .ok_or_else(|| ReefDBError::ColumnNotFound(index.to_string()))?;
+        Ok(row[i].clone())
+    }
+
+    // Rows about to be inserted may not repeat a value a unique index already holds, or
+    // one another's. NULLs never clash.
+    fn check_unique_indexes(&self, table_name: &str, schema: &[ColumnDef], new_rows: &[Vec<DataValue>]) -> Result<(), ReefDBError> {
+        for index in self.unique_indexes.get(table_name).into_iter().flatten() {
+            let Ok(IndexType::BTree(btree)) = self.storage.get_index(table_name, index) else { continue };
+            let mut seen = HashSet::new();
+            for row in new_rows {
+                let value = self.index_value(table_name, index, schema, row)?;
+                if value == DataValue::Null {
+                    continue;
+                }
+                let key = BTreeIndex::key_for(&value);
+                if btree.search(key.clone()).is_some() || !seen.insert(key) {
+                    return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, index, value)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // UNIQUE, PRIMARY KEY and FOREIGN KEY checks of one column on rewritten rows only. A
+    // changed key is looked up in the column's B-Tree index when it has a plain one;
+    // otherwise the stored values are compared against the changed keys, as inserts do.
+    fn check_changed_rows(&self, table_name: &str, column: &ColumnDef, changed: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let Some(i) = schema.iter().position(|c| c.name == column.name) else { return Ok(()) };
+        let column = &schema[i];
+        if Self::is_key(column) {
+            let duplicate = |value: &DataValue| ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, column.name, value));
+            let collation = column.collation();
+            let indexed = match self.storage.get_index(table_name, &column.name) {
+                Ok(IndexType::BTree(btree)) if collation == Collation::Binary && !self.is_text_index(table_name, &column.name) => Some(btree),
+                _ => None,
+            };
+            let mut keys = HashSet::new();
+            for (position, _) in changed {
+                let value = &rows[*position][i];
+                if *value == DataValue::Null {
+                    continue;
+                }
+                if !keys.insert(BTreeIndex::key_for(&collation.key(value))) {
+                    return Err(duplicate(value));
+                }
+                if indexed.and_then(|btree| btree.search(BTreeIndex::key_for(value))).is_some_and(|ids| ids.len() > 1) {
+                    return Err(duplicate(value));
+                }
+            }
+            if indexed.is_none() {
+                let positions: HashSet<usize> = changed.iter().map(|(position, _)| *position).collect();
+                let clash = rows.iter().enumerate()
+                    .filter(|(position, row)| !positions.contains(position) && row[i] != DataValue::Null)
+                    .find(|(_, row)| keys.contains(&BTreeIndex::key_for(&collation.key(&row[i]))));
+                if let Some((_, row)) = clash {
+                    return Err(duplicate(&row[i]));
+                }
+            }
+        }
+        let changed_rows: Vec<&[DataValue]> = changed.iter().map(|(position, _)| rows[*position].as_slice()).collect();
+        self.check_references(table_name, schema, i, &changed_rows, &[])
+    }
+
+    /// Runs the UNIQUE, PRIMARY KEY and FOREIGN KEY checks of one column over every row of
+    /// its table. A table or column dropped since the check was queued has nothing to check.
+    fn check_column_constraints(&self, table_name: &str, column_name: &str) -> Result<(), ReefDBError> {
+        let Ok((schema, rows)) = self.get_table_schema(table_name) else { return Ok(()) };
+        let Some(i) = schema.iter().position(|c| c.name == column_name) else { return Ok(()) };
+        let column = &schema[i];
+        if Self::is_key(column) {
+            Self::check_unique(table_name, column, i, rows.iter())?;
+        }
+        let rows: Vec<&[DataValue]> = rows.iter().map(Vec::as_slice).collect();
+        self.check_references(table_name, schema, i, &rows, &[])
+    }
+
+    /// Runs the FOREIGN KEY checks of the queued deferred columns over every row of their
+    /// tables, with nothing pending so they aren't skipped again. Their key checks are left
+    /// to `stage_transaction`, which checks every row a transaction writes against the
+    /// shared tables.
+    pub(crate) fn check_pending_references(&mut self) -> Result<(), ReefDBError> {
+        let Some(pending) = self.pending_constraints.take() else { return Ok(()) };
+        let checked = pending.iter().try_for_each(|(table_name, column_name)| {
+            let Ok((schema, rows)) = self.get_table_schema(table_name) else { return Ok(()) };
+            let Some(i) = schema.iter().position(|c| c.name == *column_name) else { return Ok(()) };
+            let rows: Vec<&[DataValue]> = rows.iter().map(Vec::as_slice).collect();
+            self.check_references(table_name, schema, i, &rows, &[])
+        });
+        self.pending_constraints = Some(pending);
+        checked
+    }
+
+    /// Bulk load: validates every row up front so nothing is written on error, appends them
+    /// all, then brings B-Tree and FTS indexes up to date column by column. Returns the
+    /// number of rows loaded.
+    pub fn copy_into(&mut self, table_name: &str, rows: impl IntoIterator<Item = Vec<DataValue>>) -> Result<usize, ReefDBError> {
+        let schema = self.get_table_schema(table_name)?.0.clone();
+        let rows = rows.into_iter()
+            .map(|row| Self::coerce_row(&schema, self.with_generated_columns(&schema, row)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row in &rows {
+            Self::validate_row(&schema, row)?;
+        }
+        let loaded: Vec<&[DataValue]> = rows.iter().map(Vec::as_slice).collect();
+        for i in 0..schema.len() {
+            self.check_references(table_name, &schema, i, &loaded, &rows)?;
+        }
+        self.check_deferred_keys(table_name, &schema, &rows)?;
+        self.check_unique_indexes(table_name, &schema, &rows)?;
+
+        let row_ids = self.storage.append_rows(table_name, rows.clone())?;
+        self.tables.append_rows(table_name, rows.clone())?;
+
+        for (i, col) in schema.iter().enumerate() {
+            if let Ok(IndexType::BTree(_)) = self.storage.get_index(table_name, &col.name) {
+                for (row, row_id) in rows.iter().zip(&row_ids) {
+                    let key = self.index_key(table_name, &col.name, &row[i]);
+                    self.storage.update_index(table_name, &col.name, vec![], key, *row_id)?;
+                }
+            }
+            if col.data_type == DataType::TSVector {
+                for (row, row_id) in rows.iter().zip(&row_ids) {
+                    if let DataValue::Text(text) = &row[i] {
+                        self.inverted_index.add_document(table_name, &col.name, *row_id, text);
+                    }
+                }
+            }
+        }
+        for (row, row_id) in rows.iter().zip(&row_ids) {
+            self.index_expressions(table_name, &schema, row, *row_id)?;
+        }
+        Ok(rows.len())
+    }
+
+    fn handle_select(
+        &self,
+        table_ref: TableReference,
+        columns: Vec<Column>,
+        where_clause: Option<WhereType>,
+        joins: Vec<JoinClause>,
+        order_by: Vec<OrderByClause>,
+    ) -> Result<ReefDBResult, ReefDBError> {
+        self.verify_table_exists(&table_ref.name)?;
+        let (schema, data) = self.get_table_schema(&table_ref.name)?;
+        
+        let mut result = Vec::new();
+        
+        // Get joined schemas if needed
+        let mut joined_schemas = Vec::new();
+        if !joins.is_empty() {
+            for join in &joins {
+                let (join_schema, _) = self.get_table_schema(&join.table_ref.name)?;
+                joined_schemas.push((join.table_ref.name.as_str(), join_schema.as_slice()));
+            }
+        }
+
+        // Handle joins if present
+        if !joins.is_empty() {
+            self.handle_join_select(&table_ref.name, schema, data, &columns, where_clause, &joins, &mut result)?;
+        } else {
+            self.handle_simple_select(&table_ref.name, schema, data, &columns, where_clause, &mut result)?;
+        }
+
+        // Without ORDER BY, fall back to primary-key order so the result doesn't depend on
+        // how the backend stores rows. Null-padded rows of an outer join have no source row
+        // and go last.
+        if order_by.is_empty() {
+            if let Some(pk) = schema.iter().position(|c| c.constraints.contains(&Constraint::PrimaryKey)) {
+                result.sort_by(|(a, _), (b, _)| match (data.get(*a), data.get(*b)) {
+                    (Some(a), Some(b)) => schema[pk].compare(&a[pk], &b[pk]).unwrap_or(std::cmp::Ordering::Equal),
+                    (a, b) => a.is_none().cmp(&b.is_none()),
+                });
+            }
+        }
+
+        // Aggregates leave a single row whose ORDER BY keys are output columns
+        if columns.iter().any(|col| matches!(col.column_type, ColumnType::Aggregate(_))) {
+            Self::sort_output(&mut result, &columns, schema, &order_by)?;
+        } else if !order_by.is_empty() {
+            let keys = order_by.iter()
+                .map(|clause| {
+                    schema.iter().position(|col| col.name == clause.column.name)
+                        .map(|idx| (idx, &clause.direction))
+                        .ok_or_else(|| ReefDBError::ColumnNotFound(clause.column.name.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // Keys are compared on the source rows, as the projection may leave them out
+            result.sort_by(|(a, _), (b, _)| {
+                let (Some(row1), Some(row2)) = (data.get(*a), data.get(*b)) else {
+                    return data.get(*a).is_none().cmp(&data.get(*b).is_none());
+                };
+                for &(col_idx, direction) in &keys {
+                    let cmp = schema[col_idx].compare(&row1[col_idx], &row2[col_idx])
+                        .unwrap_or(std::cmp::Ordering::Equal);
+                    
+                    match direction {
+                        OrderDirection::Asc => if cmp != std::cmp::Ordering::Equal { return cmp; },
+                        OrderDirection::Desc => if cmp != std::cmp::Ordering::Equal { return cmp.reverse(); },
+                    }
+                }
+                std::cmp::Ordering::Equal
+            });
+        }
+
+        // Create column info
+        let column_info = if joins.is_empty() {
+            ColumnInfo::from_schema_and_columns(schema, &columns, &table_ref.name)?
+        } else {
+            ColumnInfo::from_joined_schemas(schema, &table_ref.name, &joined_schemas, &columns)?
+        };
+        
+        Ok(ReefDBResult::Select(QueryResult::with_columns(result, column_info)))
+    }
+
+    /// Materializes each CTE as a table the main query can read, then drops them all again,
+    /// whether or not the query succeeded
+    fn handle_with(&mut self, ctes: Vec<CommonTableExpression>, query: SelectStatement) -> Result<ReefDBResult, ReefDBError> {
+        let mut materialized = Vec::new();
+        let mut result = Ok(());
+        for cte in &ctes {
+            if self.storage.table_exists(&cte.name) || self.tables.table_exists(&cte.name) {
+                result = Err(ReefDBError::Other(format!("CTE {} has the same name as an existing table", cte.name)));
+                break;
+            }
+            // CTE rows live in the in-memory layer of the storage, never in the backend
+            self.storage.add_temporary(&cte.name);
+            materialized.push(cte.name.clone());
+            if let Err(e) = self.materialize_cte(cte) {
+                result = Err(e);
+                break;
+            }
+        }
+
+        let result = result.and_then(|_| self.execute_statement_in_transaction(Statement::Select(query)));
+        for name in materialized {
+            self.storage.drop_table(&name);
+        }
+        result
+    }
+
+    // Runs the anchor, then re-runs the recursive term with the CTE table holding only the
+    // previous iteration's rows until an iteration adds nothing. The table is left holding
+    // every row produced.
+    fn materialize_cte(&mut self, cte: &CommonTableExpression) -> Result<(), ReefDBError> {
+        let anchor = self.select_rows(cte.anchor.clone())?;
+        let names: Vec<String> = if cte.columns.is_empty() {
+            anchor.columns.iter().map(|c| c.name.clone()).collect()
+        } else if cte.columns.len() == anchor.columns.len() {
+            cte.columns.clone()
+        } else {
+            return Err(ReefDBError::Other(format!(
+                "CTE {} names {} columns but its query returns {}",
+                cte.name, cte.columns.len(), anchor.columns.len()
+            )));
+        };
+        let schema: Vec<ColumnDef> = names.iter().zip(&anchor.columns)
+            .map(|(name, info)| ColumnDef::new(name, info.data_type.clone(), vec![]))
+            .collect();
+
+        let union_all = cte.recursive.as_ref().is_none_or(|term| term.union_all);
+        let row_key = |row: &[DataValue]| row.iter().map(BTreeIndex::key_for).collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        let mut all = Vec::new();
+        for (_, row) in anchor.rows {
+            if union_all || seen.insert(row_key(&row)) {
+                all.push(row);
+            }
+        }
+
+        if let Some(term) = &cte.recursive {
+            let mut working = all.clone();
+            let mut iterations = 0;
+            while !working.is_empty() {
+                if iterations == MAX_RECURSIVE_CTE_ITERATIONS {
+                    return Err(ReefDBError::Other(format!(
+                        "Recursive CTE {} did not finish within {} iterations",
+                        cte.name, MAX_RECURSIVE_CTE_ITERATIONS
+                    )));
+                }
+                iterations += 1;
+
+                self.storage.insert_table(cte.name.clone(), schema.clone(), working);
+                let produced = self.select_rows(term.query.clone())?;
+                if produced.columns.len() != schema.len() {
+                    return Err(ReefDBError::Other(format!(
+                        "Recursive term of CTE {} returns {} columns, expected {}",
+                        cte.name, produced.columns.len(), schema.len()
+                    )));
+                }
+                working = produced.rows.into_iter()
+                    .map(|(_, row)| row)
+                    .filter(|row| union_all || seen.insert(row_key(row)))
+                    .collect();
+                all.extend(working.iter().cloned());
+            }
+        }
+
+        self.storage.insert_table(cte.name.clone(), schema, all);
+        Ok(())
+    }
+
+    /// Runs the query, then keeps only the first row for each distinct value of `keys`.
+    /// The keys must be among the query's output columns.
+    fn handle_distinct_on(&mut self, keys: Vec<Column>, query: SelectStatement) -> Result<ReefDBResult, ReefDBError> {
+        let result = self.select_rows(query)?;
+        let positions = keys.iter()
+            .map(|key| result.columns.iter()
+                .position(|info| info.name == key.name)
+                .ok_or_else(|| ReefDBError::ColumnNotFound(key.name.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut seen = HashSet::new();
+        let rows = result.rows.into_iter()
+            .filter(|(_, row)| seen.insert(positions.iter().map(|&i| BTreeIndex::key_for(&row[i])).collect::<Vec<_>>()))
+            .collect();
+        Ok(ReefDBResult::Select(QueryResult::with_columns(rows, result.columns)))
+    }
+
+    /// One row per group of the rows `query` selects that agree on every key. Output
+    /// columns must be group keys or aggregates; groups come in key order unless the
+    /// query orders them.
+    // Row history only exists as the MVCC versions the transaction manager writes;
+    // storage holds the current rows alone
+    fn handle_as_of(&self) -> Result<ReefDBResult, ReefDBError> {
+        Err(ReefDBError::Other("AS OF reads are served by the transaction manager".to_string()))
+    }
+
+    fn handle_group_by(&mut self, keys: Vec<Column>, query: SelectStatement) -> Result<ReefDBResult, ReefDBError> {
+        self.handle_grouping_sets(vec![keys], query)
+    }
+
+    /// The groups of each set of keys in turn. A key of another set that this one leaves
+    /// out is NULL in its rows; the empty set makes a single group of every row.
+    fn handle_grouping_sets(&mut self, sets: Vec<Vec<Column>>, query: SelectStatement) -> Result<ReefDBResult, ReefDBError> {
+        let SelectStatement::FromTable(table_ref, columns, where_clause, joins, order_by) = query else {
+            return Err(ReefDBError::Other("GROUP BY needs a SELECT from a table".to_string()));
+        };
+        if !joins.is_empty() {
+            return Err(ReefDBError::Other("GROUP BY is not supported with joins".to_string()));
+        }
+        self.verify_table_exists(&table_ref.name)?;
+        let (schema, data) = self.get_table_schema(&table_ref.name)?;
+        let mut rows = Vec::new();
+        for row in data {
+            if let Some(where_clause) = &where_clause {
+                if !self.evaluate_where_clause(where_clause, row, &[], schema, &[], &table_ref.name)? {
+                    continue;
+                }
+            }
+            rows.push(row.as_slice());
+        }
+
+        let mut result = Vec::new();
+        for keys in &sets {
+            let key_positions = keys.iter()
+                .map(|key| schema.iter()
+                    .position(|c| c.name == key.name)
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(key.name.clone())))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut groups: Vec<Vec<&[DataValue]>> = Vec::new();
+            let mut group_of = HashMap::new();
+            for &row in &rows {
+                let key: Vec<Vec<u8>> = key_positions.iter().map(|&i| BTreeIndex::key_for(&row[i])).collect();
+                let group = *group_of.entry(key).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push(row);
+            }
+            // Grouping by nothing aggregates over all rows, even when there are none
+            if keys.is_empty() && groups.is_empty() {
+                groups.push(Vec::new());
+            }
+            groups.sort_by(|a, b| key_positions.iter()
+                .map(|&i| schema[i].compare(&a[0][i], &b[0][i]).unwrap_or(std::cmp::Ordering::Equal))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal));
+
+            for group in &groups {
+                let row = columns.iter().map(|col| match &col.column_type {
+                    ColumnType::Aggregate(aggregate) => Self::evaluate_aggregate(aggregate, schema, group),
+                    ColumnType::Regular(_) if keys.iter().any(|key| key.name == col.name) => {
+                        let position = schema.iter().position(|c| c.name == col.name)
+                            .ok_or_else(|| ReefDBError::ColumnNotFound(col.name.clone()))?;
+                        Ok(group[0][position].clone())
+                    }
+                    ColumnType::Regular(_) if sets.iter().flatten().any(|key| key.name == col.name) => Ok(DataValue::Null),
+                    _ => Err(ReefDBError::Other(format!(
+                        "Column {} must appear in GROUP BY or be aggregated", col.name
+                    ))),
+                }).collect::<Result<Vec<_>, _>>()?;
+                result.push((result.len(), row));
+            }
+        }
+
+        Self::sort_output(&mut result, &columns, schema, &order_by)?;
+        let column_info = ColumnInfo::from_schema_and_columns(schema, &columns, &table_ref.name)?;
+        Ok(ReefDBResult::Select(QueryResult::with_columns(result, column_info)))
+    }
+
+    // Sorts rows already projected to `columns`. An ORDER BY key names an output column,
+    // or repeats one of the query's aggregates.
+    fn sort_output(
+        rows: &mut [(usize, Vec<DataValue>)],
+        columns: &[Column],
+        schema: &[ColumnDef],
+        order_by: &[OrderByClause],
+    ) -> Result<(), ReefDBError> {
+        let keys = order_by.iter()
+            .map(|clause| {
+                let position = columns.iter()
+                    .position(|col| match clause.column.column_type {
+                        ColumnType::Aggregate(_) => col.column_type == clause.column.column_type,
+                        _ => col.name == clause.column.name,
+                    })
+                    .or_else(|| columns.iter().position(|col| col.name == clause.column.name))
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(clause.column.name.clone()))?;
+                // Key columns keep their own comparison, e.g. collation
+                let definition = match &columns[position].column_type {
+                    ColumnType::Regular(name) => schema.iter().find(|c| &c.name == name),
+                    _ => None,
+                };
+                Ok((position, definition, &clause.direction))
+            })
+            .collect::<Result<Vec<_>, ReefDBError>>()?;
+
+        rows.sort_by(|(_, a), (_, b)| {
+            for (position, definition, direction) in &keys {
+                let (a, b) = (&a[*position], &b[*position]);
+                let cmp = match definition {
+                    Some(definition) => definition.compare(a, b),
+                    None => a.partial_cmp(b),
+                }.unwrap_or(std::cmp::Ordering::Equal);
+                if cmp.is_ne() {
+                    return match direction {
+                        OrderDirection::Asc => cmp,
+                        OrderDirection::Desc => cmp.reverse(),
+                    };
+                }
+            }
+            std::cmp::Ordering::Equal
+        });
+        Ok(())
+    }
+
+    /// The first rows of `query`'s result. WITH TIES also keeps the rows after the last
+    /// one that tie with it on every ORDER BY key.
+    fn handle_limit(&mut self, limit: RowLimit, query: SelectStatement) -> Result<ReefDBResult, ReefDBError> {
+        let (query, ties) = Self::prepare_row_limit(limit, query)?;
+        let result = self.select_rows(query)?;
+        Ok(ReefDBResult::Select(Self::limit_rows(limit, &ties, result)?))
+    }
+
+    // The ORDER BY keys the rows of a WITH TIES limit are compared on. Keys the query
+    // doesn't select are appended to its projection and stripped again after the cut.
+    pub(crate) fn prepare_row_limit(
+        limit: RowLimit,
+        mut query: SelectStatement,
+    ) -> Result<(SelectStatement, TieKeys), ReefDBError> {
+        let mut ties = TieKeys { columns: Vec::new(), appended: 0 };
+        if !limit.with_ties {
+            return Ok((query, ties));
+        }
+        let (columns, order_by) = Self::projection_and_order(&query);
+        if order_by.is_empty() {
+            return Err(ReefDBError::Other("WITH TIES needs an ORDER BY".to_string()));
+        }
+        let wildcard = columns.iter().any(|col| col.name == "*");
+        let mut missing = Vec::new();
+        for clause in order_by {
+            let selected = columns.iter()
+                .find(|col| match clause.column.column_type {
+                    ColumnType::Aggregate(_) => col.column_type == clause.column.column_type,
+                    _ => col.name == clause.column.name,
+                })
+                .or_else(|| columns.iter().find(|col| col.column_type == clause.column.column_type));
+            match selected {
+                Some(col) => ties.columns.push(Column { table: clause.column.table.clone(), ..col.clone() }),
+                None if wildcard => ties.columns.push(clause.column.clone()),
+                None => missing.push(clause.column.clone()),
+            }
+        }
+        if !missing.is_empty() {
+            let projection = match &mut query {
+                SelectStatement::FromTable(_, columns, ..) => columns,
+                SelectStatement::AsOf(_, inner) => match inner.as_mut() {
+                    SelectStatement::FromTable(_, columns, ..) => columns,
+                    _ => return Err(ReefDBError::ColumnNotFound(missing[0].name.clone())),
+                },
+                _ => return Err(ReefDBError::ColumnNotFound(missing[0].name.clone())),
+            };
+            projection.extend(missing.iter().cloned());
+            ties.appended = missing.len();
+            ties.columns.extend(missing);
+        }
+        Ok((query, ties))
+    }
+
+    fn projection_and_order(query: &SelectStatement) -> (&[Column], &[OrderByClause]) {
+        match query {
+            SelectStatement::FromTable(_, columns, _, _, order_by) => (columns, order_by),
+            SelectStatement::With(_, query)
+            | SelectStatement::DistinctOn(_, query)
+            | SelectStatement::GroupBy(_, query)
+            | SelectStatement::GroupingSets(_, query)
+            | SelectStatement::AsOf(_, query)
+            | SelectStatement::Limit(_, query) => Self::projection_and_order(query),
+        }
+    }
+
+    pub(crate) fn limit_rows(limit: RowLimit, ties: &TieKeys, result: QueryResult) -> Result<QueryResult, ReefDBError> {
+        let QueryResult { mut columns, mut rows, .. } = result;
+        let positions = ties.columns.iter()
+            .map(|key| columns.iter()
+                .position(|info| info.name == key.name
+                    && (key.table.is_none() || info.table.is_none() || info.table == key.table))
+                .ok_or_else(|| ReefDBError::ColumnNotFound(key.name.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut end = limit.count.min(rows.len());
+        if limit.with_ties && end > 0 {
+            let last = &rows[end - 1].1;
+            let tied = |row: &[DataValue]| positions.iter().all(|&i| row[i] == last[i]);
+            end += rows[end..].iter().take_while(|(_, row)| tied(row)).count();
+        }
+        rows.truncate(end);
+
+        let width = columns.len() - ties.appended;
+        columns.truncate(width);
+        for (_, row) in &mut rows {
+            row.truncate(width);
+        }
+        Ok(QueryResult::with_columns(rows, columns))
+    }
+
+    fn select_rows(&mut self, select: SelectStatement) -> Result<QueryResult, ReefDBError> {
+        match self.execute_statement_in_transaction(Statement::Select(select))? {
+            ReefDBResult::Select(result) => Ok(result),
+            other => Err(ReefDBError::Other(format!("Expected rows from SELECT, got {:?}", other))),
+        }
+    }
+
+    fn handle_simple_select(
+        &self,
+        table_name: &str,
+        schema: &Vec<ColumnDef>,
+        data: &Vec<Vec<DataValue>>,
+        columns: &[Column],
+        where_clause: Option<WhereType>,
+        result: &mut Vec<(usize, Vec<DataValue>)>,
+    ) -> Result<(), ReefDBError> {
+        let positions = where_clause.as_ref()
+            .and_then(|where_clause| self.indexed_rows(table_name, where_clause))
+            .unwrap_or_else(|| (0..data.len()).collect());
+        self.metrics.rows_scanned(table_name, positions.len());
+        let expiry = self.expiry(schema);
+        for i in positions {
+            let Some(row) = data.get(i).filter(|row| !expiry.is_expired(row)) else { continue };
+            let include_row = if let Some(where_clause) = &where_clause {
+                self.evaluate_where_clause(where_clause, row, &[], schema, &[], table_name)?
+            } else {
+                true
+            };
+
+            if include_row {
+                let mut selected_values = Vec::new();
+                for col in columns {
+                    if col.name == "*" {
+                        selected_values.extend(row.iter().cloned());
+                    } else {
+                        match &col.column_type {
+                            ColumnType::Regular(_) => {
+                                let col_idx = schema.iter()
+                                    .position(|c| c.name == col.name)
+                                    .ok_or_else(|| ReefDBError::ColumnNotFound(col.name.clone()))?;
+                                selected_values.push(row[col_idx].clone());
+                            }
+                            ColumnType::Function(name, args) => {
+                                let value = self.evaluate_column(col, row, schema)?;
+                                selected_values.push(value);
+                            }
+                            ColumnType::Case(case) => {
+                                selected_values.push(self.evaluate_case(case, row, &[], schema, &[], table_name)?);
+                            }
+                            ColumnType::Concat(operands) => {
+                                selected_values.push(self.evaluate_concat(operands, row, &[], schema, &[])?);
+                            }
+                            // Filled in once every row is selected
+                            ColumnType::Window(_) | ColumnType::Aggregate(_) => selected_values.push(DataValue::Null),
+                            ColumnType::Wildcard => {
+                                selected_values.extend(row.iter().cloned());
+                            }
+                        }
+                    }
+                }
+                result.push((i, selected_values));
+            }
+        }
+
+        if columns.iter().any(|col| matches!(col.column_type, ColumnType::Aggregate(_))) {
+            let source: Vec<&[DataValue]> = result.iter().map(|(i, _)| data[*i].as_slice()).collect();
+            *result = vec![(0, Self::aggregate_row(columns, schema, &source)?)];
+            return Ok(());
+        }
+
+        let mut position = 0;
+        for col in columns {
+            match &col.column_type {
+                ColumnType::Window(window) => {
+                    let source: Vec<&[DataValue]> = result.iter().map(|(i, _)| data[*i].as_slice()).collect();
+                    let values = Self::window_values(window, schema, &source)?;
+                    for ((_, selected), value) in result.iter_mut().zip(values) {
+                        selected[position] = value;
+                    }
+                    position += 1;
+                }
+                _ if col.name == "*" => position += schema.len(),
+                _ => position += 1,
+            }
+        }
+        Ok(())
+    }
+
+    // The single row of a query without GROUP BY whose columns are all aggregates. It is
+    // produced even when no rows matched.
+    fn aggregate_row(columns: &[Column], schema: &[ColumnDef], rows: &[&[DataValue]]) -> Result<Vec<DataValue>, ReefDBError> {
+        columns.iter().map(|col| {
+            let ColumnType::Aggregate(aggregate) = &col.column_type else {
+                return Err(ReefDBError::Other(format!(
+                    "Column {} must be aggregated when the query has aggregates", col.name
+                )));
+            };
+            Self::evaluate_aggregate(aggregate, schema, rows)
+        }).collect()
+    }
+
+    fn evaluate_aggregate(aggregate: &AggregateFunction, schema: &[ColumnDef], rows: &[&[DataValue]]) -> Result<DataValue, ReefDBError> {
+        let column = aggregate.column.as_ref()
+            .map(|name| schema.iter()
+                .position(|c| &c.name == name)
+                .ok_or_else(|| ReefDBError::ColumnNotFound(name.clone())))
+            .transpose()?;
+        aggregate.evaluate(rows, column).map_err(ReefDBError::Other)
+    }
+
+    // One value per row: rows are ordered by partition then the window's ORDER BY, and
+    // numbering restarts in each partition. Rows tied on the ORDER BY keys share a rank.
+    fn window_values(window: &WindowFunction, schema: &[ColumnDef], rows: &[&[DataValue]]) -> Result<Vec<DataValue>, ReefDBError> {
+        let column_index = |name: &str| schema.iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| ReefDBError::ColumnNotFound(name.to_string()));
+        let partition_keys = window.partition_by.iter()
+            .map(|name| column_index(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let order_keys = window.order_by.iter()
+            .map(|clause| Ok((column_index(&clause.column.name)?, clause.direction.clone())))
+            .collect::<Result<Vec<_>, ReefDBError>>()?;
+
+        let compare = |a: &[DataValue], b: &[DataValue], idx: usize| {
+            a[idx].partial_cmp(&b[idx]).unwrap_or(std::cmp::Ordering::Equal)
+        };
+        let same_partition = |a: &[DataValue], b: &[DataValue]| {
+            partition_keys.iter().all(|&idx| compare(a, b, idx).is_eq())
+        };
+        let tied = |a: &[DataValue], b: &[DataValue]| {
+            order_keys.iter().all(|(idx, _)| compare(a, b, *idx).is_eq())
+        };
+
+        let mut order: Vec<usize> = (0..rows.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (rows[a], rows[b]);
+            partition_keys.iter()
+                .map(|&idx| compare(a, b, idx))
+                .chain(order_keys.iter().map(|(idx, direction)| match direction {
+                    OrderDirection::Asc => compare(a, b, *idx),
+                    OrderDirection::Desc => compare(a, b, *idx).reverse(),
+                }))
+                .find(|cmp| cmp.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut values = vec![DataValue::Null; rows.len()];
+        let (mut row_number, mut rank, mut dense_rank) = (0, 0, 0);
+        let mut previous: Option<&[DataValue]> = None;
+        for &i in &order {
+            let row = rows[i];
+            match previous {
+                Some(prev) if same_partition(prev, row) => {
+                    row_number += 1;
+                    if !tied(prev, row) {
+                        rank = row_number;
+                        dense_rank += 1;
+                    }
+                }
+                _ => (row_number, rank, dense_rank) = (1, 1, 1),
+            }
+            values[i] = DataValue::Integer(match window.kind {
+                WindowFunctionKind::RowNumber => row_number,
+                WindowFunctionKind::Rank => rank,
+                WindowFunctionKind::DenseRank => dense_rank,
+            });
+            previous = Some(row);
+        }
+        Ok(values)
+    }
+
+    fn handle_join_select(
+        &self,
+        table_name: &str,
+        schema: &Vec<ColumnDef>,
+        data: &Vec<Vec<DataValue>>,
+        columns: &[Column],
+        where_clause: Option<WhereType>,
+        joins: &[JoinClause],
+        result: &mut Vec<(usize, Vec<DataValue>)>,
+    ) -> Result<(), ReefDBError> {
+        let expiry = self.expiry(schema);
+        self.metrics.rows_scanned(table_name, data.len());
+        for join in joins {
+            if let Some((join_schema, join_data)) = self.storage.get_table_ref(&join.table_ref.name) {
+                let left_col_idx = schema.iter()
+                    .position(|c| c.name == join.on.0.column_name)
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(join.on.0.column_name.clone()))?;
+                let right_col_idx = join_schema.iter()
+                    .position(|c| c.name == join.on.1.column_name)
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(join.on.1.column_name.clone()))?;
+
+                // Predicates that only read the joined table are applied once up front,
+                // through an index when possible, instead of once per left row. Outer
+                // joins keep every row for null-padding, so WHERE runs after the join.
+                let pushdown = where_clause.as_ref().filter(|_| join.join_type == JoinType::Inner);
+                let join_expiry = self.expiry(join_schema);
+                let join_candidates: Vec<usize> = self.join_candidates(table_name, schema, &join.table_ref.name, join_schema, join_data, pushdown)
+                    .into_iter()
+                    .filter(|&j| !join_expiry.is_expired(&join_data[j]))
+                    .collect();
+
+                let mut emit = |i: usize, row: &[DataValue], join_row: &[DataValue]| -> Result<(), ReefDBError> {
+                    let include_row = if let Some(where_clause) = &where_clause {
+                        self.evaluate_where_clause(where_clause, row, join_row, schema, join_schema, table_name)?
+                    } else {
+                        true
+                    };
+
+                    if include_row {
+                        let mut selected_values = Vec::new();
+                        for col in columns {
+                            if col.name == "*" {
+                                selected_values.extend(row.iter().cloned());
+                                selected_values.extend(join_row.iter().cloned());
+                            } else if let ColumnType::Case(case) = &col.column_type {
+                                selected_values.push(self.evaluate_case(case, row, join_row, schema, join_schema, table_name)?);
+                            } else if let ColumnType::Concat(operands) = &col.column_type {
+                                selected_values.push(self.evaluate_concat(operands, row, join_row, schema, join_schema)?);
+                            } else if let ColumnType::Window(_) = &col.column_type {
+                                return Err(ReefDBError::Other("Window functions are not supported with joins".to_string()));
+                            } else if let ColumnType::Aggregate(_) = &col.column_type {
+                                return Err(ReefDBError::Other("Aggregates are not supported with joins".to_string()));
+                            } else {
+                                let value = if let Some(table) = &col.table {
+                                    if table == &join.table_ref.name {
+                                        if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
+                                            join_row[idx].clone()
+                                        } else {
+                                            continue;
+                                        }
                                     } else {
-                                        let value = if let Some(table) = &col.table {
-                                            if table == &join.table_ref.name {
-                                                if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
-                                                    join_row[idx].clone()
-                                                } else {
-                                                    continue;
-                                                }
-                                            } else {
-                                                if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
-                                                    row[idx].clone()
-                                                } else {
-                                                    continue;
-                                                }
-                                            }
+                                        if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
+                                            row[idx].clone()
                                         } else {
-                                            if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
-                                                row[idx].clone()
-                                            } else if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
-                                                join_row[idx].clone()
-                                            } else {
-                                                continue;
-                                            }
-                                        };
-                                        selected_values.push(value);
+                                            continue;
+                                        }
                                     }
-                                }
-                                result.push((i, selected_values));
+                                } else {
+                                    if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
+                                        row[idx].clone()
+                                    } else if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
+                                        join_row[idx].clone()
+                                    } else {
+                                        continue;
+                                    }
+                                };
+                                selected_values.push(value);
+                            }
+                        }
+                        result.push((i, selected_values));
+                    }
+                    Ok(())
+                };
+
+                // NULL keys only match under `<=>`; outer joins pad the unmatched side with NULLs
+                let collation = schema[left_col_idx].collation().combine(join_schema[right_col_idx].collation());
+                let keys_match = |left: &DataValue, right: &DataValue| if join.null_safe {
+                    collation.evaluate(&Op::NullSafeEqual, left, right)
+                } else {
+                    collation.sql_eq(left, right)
+                };
+                let mut join_row_matched = vec![false; join_data.len()];
+                for (i, row) in data.iter().enumerate().filter(|(_, row)| !expiry.is_expired(row)) {
+                    let mut matched = false;
+                    for &j in &join_candidates {
+                        if keys_match(&row[left_col_idx], &join_data[j][right_col_idx]) {
+                            matched = true;
+                            join_row_matched[j] = true;
+                            emit(i, row, &join_data[j])?;
+                        }
+                    }
+                    if !matched && matches!(join.join_type, JoinType::Left | JoinType::Full) {
+                        emit(i, row, &vec![DataValue::Null; join_schema.len()])?;
+                    }
+                }
+                if matches!(join.join_type, JoinType::Right | JoinType::Full) {
+                    let null_row = vec![DataValue::Null; schema.len()];
+                    for &j in join_candidates.iter().filter(|&&j| !join_row_matched[j]) {
+                        emit(data.len() + j, &null_row, &join_data[j])?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Conjuncts of `where_clause` resolved against the joined table by
+    // `evaluate_where_clause`, with the joined column's index
+    fn join_table_predicates<'a>(
+        where_clause: &'a WhereType,
+        main_table: &str,
+        schema: &[ColumnDef],
+        join_schema: &[ColumnDef],
+        predicates: &mut Vec<(usize, &'a WhereClause)>,
+    ) {
+        match where_clause {
+            WhereType::And(left, right) => {
+                Self::join_table_predicates(left, main_table, schema, join_schema, predicates);
+                Self::join_table_predicates(right, main_table, schema, join_schema, predicates);
+            }
+            WhereType::Regular(clause) if clause.function.is_none() && !matches!(clause.value, DataValue::Function { .. }) => {
+                let on_join_table = match &clause.table {
+                    Some(table) => table != main_table,
+                    None => !schema.iter().any(|c| c.name == clause.col_name),
+                };
+                if on_join_table {
+                    if let Some(idx) = join_schema.iter().position(|c| c.name == clause.col_name) {
+                        predicates.push((idx, clause));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Positions of the joined rows that can pass `where_clause`, in table order. An
+    // equality on a B-Tree indexed column is answered from the index, and only the rows
+    // it finds count as read.
+    fn join_candidates(
+        &self,
+        main_table: &str,
+        schema: &[ColumnDef],
+        join_table: &str,
+        join_schema: &[ColumnDef],
+        join_data: &[Vec<DataValue>],
+        where_clause: Option<&WhereType>,
+    ) -> Vec<usize> {
+        let mut predicates = Vec::new();
+        if let Some(where_clause) = where_clause {
+            Self::join_table_predicates(where_clause, main_table, schema, join_schema, &mut predicates);
+        }
+        if predicates.is_empty() {
+            self.metrics.rows_scanned(join_table, join_data.len());
+            return (0..join_data.len()).collect();
+        }
+
+        // Float keys are skipped since 0.0 and -0.0 are equal but encode differently,
+        // decimal and UUID columns since a literal's encoding differs from the stored value's,
+        // and NOCASE columns since the index holds the values as written
+        let indexed = predicates.iter().find_map(|(idx, clause)| {
+            if clause.operator != Op::Equal
+                || matches!(clause.value, DataValue::Float(_))
+                || matches!(join_schema[*idx].data_type, DataType::Decimal { .. } | DataType::Uuid)
+                || join_schema[*idx].collation() != Collation::Binary
+                || self.is_text_index(join_table, &join_schema[*idx].name)
+            {
+                return None;
+            }
+            match self.storage.get_index(join_table, &join_schema[*idx].name) {
+                Ok(IndexType::BTree(btree)) => {
+                    let mut positions: Vec<usize> = btree.search(BTreeIndex::key_for(&clause.value))
+                        .map(|row_ids| row_ids.iter().filter_map(|id| id.checked_sub(1)).collect())
+                        .unwrap_or_default();
+                    positions.sort_unstable();
+                    Some(positions)
+                }
+                _ => None,
+            }
+        });
+
+        let read = indexed.unwrap_or_else(|| (0..join_data.len()).collect());
+        self.metrics.rows_scanned(join_table, read.len());
+        read.into_iter()
+            .filter(|&j| {
+                j < join_data.len() && predicates.iter().all(|(idx, clause)| {
+                    join_schema[*idx].collation().evaluate(&clause.operator, &join_data[j][*idx], &clause.value)
+                })
+            })
+            .collect()
+    }
+
+    // Brings the generated columns of the rewritten rows up to date after an update
+    fn recompute_generated_columns(&mut self, table_name: &str, changed: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let mut recomputed = Vec::with_capacity(changed.len());
+        for (i, _) in changed {
+            let mut row = rows[*i].clone();
+            self.compute_generated_columns(schema, &mut row)?;
+            recomputed.push((*i, row));
+        }
+        if let Some((_, stored)) = self.storage.get_table(table_name) {
+            for (i, row) in recomputed {
+                stored[i] = row;
+            }
+        }
+        Ok(())
+    }
+
+    // Deletes shift the row ids of every row after the first one removed, so the table's
+    // B-Tree indexes are rebuilt from its rows afterwards
+    fn rebuild_btree_indexes(&mut self, table_name: &str) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let rebuilt: Vec<(String, BTreeIndex)> = schema.iter().enumerate()
+            .filter(|(_, col)| matches!(self.storage.get_index(table_name, &col.name), Ok(IndexType::BTree(_))))
+            .map(|(i, col)| match self.is_text_index(table_name, &col.name) {
+                true => (col.name.clone(), BTreeIndex::from_text_rows(rows, i)),
+                false => (col.name.clone(), BTreeIndex::from_rows(rows, i)),
+            })
+            .collect();
+        for (column, btree) in rebuilt {
+            self.storage.create_index(table_name, &column, IndexType::BTree(btree))?;
+        }
+        for (key, expression) in self.expression_indexes.get(table_name).cloned().unwrap_or_default() {
+            let btree = self.expression_btree(table_name, &expression, self.is_text_index(table_name, &key))?;
+            self.storage.create_index(table_name, &key, IndexType::BTree(btree))?;
+        }
+        Ok(())
+    }
+
+    // Names of the table's B-Tree indexes, column and expression ones alike
+    fn btree_index_names(&self, table_name: &str, schema: &[ColumnDef]) -> Vec<String> {
+        schema.iter()
+            .filter(|col| matches!(self.storage.get_index(table_name, &col.name), Ok(IndexType::BTree(_))))
+            .map(|col| col.name.clone())
+            .chain(self.expression_indexes.get(table_name).into_iter().flatten().map(|(key, _)| key.clone()))
+            .collect()
+    }
+
+    // Moves the B-Tree entries of rewritten rows from the keys of their `old` values to
+    // those of their stored ones. Updates keep row ids, so no other entry changes.
+    fn reindex_rows(&mut self, table_name: &str, old: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let mut moves = Vec::new();
+        for index in self.btree_index_names(table_name, schema) {
+            for (i, old_row) in old {
+                let old_key = self.index_key(table_name, &index, &self.index_value(table_name, &index, schema, old_row)?);
+                let new_key = self.index_key(table_name, &index, &self.index_value(table_name, &index, schema, &rows[*i])?);
+                if old_key != new_key {
+                    moves.push((index.clone(), old_key, new_key, i + 1));
+                }
+            }
+        }
+        for (index, old_key, new_key, row_id) in moves {
+            self.storage.update_index(table_name, &index, old_key, new_key, row_id)?;
+        }
+        Ok(())
+    }
+
+    // Puts rewritten rows back to their `old` values, index entries included
+    fn restore_rows(&mut self, table_name: &str, old: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        let current: Vec<(usize, Vec<DataValue>)> = {
+            let (_, rows) = self.get_table_schema(table_name)?;
+            old.iter().map(|(i, _)| (*i, rows[*i].clone())).collect()
+        };
+        if let Some((_, rows)) = self.storage.get_table(table_name) {
+            for (i, row) in old {
+                rows[*i] = row.clone();
+            }
+        }
+        self.reindex_rows(table_name, &current)
+    }
+
+    // Keeps rows a statement wrote when this copy records them for its transaction
+    fn record_written(&mut self, operation: WALOperation, table_name: &str, rows: Vec<Vec<DataValue>>) {
+        if let Some(written) = self.written_rows.as_mut().filter(|_| !rows.is_empty()) {
+            written.push(RowChange { operation, table_name: table_name.to_string(), rows });
+        }
+    }
+
+    // Records the stored values of rewritten rows that changed, given their `old` ones. A
+    // row whose primary key changed is recorded as deleted and inserted again, since rows
+    // are published on their key.
+    fn record_rewritten(&mut self, table_name: &str, old: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        if self.written_rows.is_none() {
+            return Ok(());
+        }
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let pk_idx = schema.iter()
+            .position(|c| c.constraints.contains(&Constraint::PrimaryKey))
+            .unwrap_or(0);
+        let (mut updated, mut moved_from, mut moved_to) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, old_row) in old {
+            let row = &rows[*i];
+            if row == old_row {
+                continue;
+            }
+            if row[pk_idx] == old_row[pk_idx] {
+                updated.push(row.clone());
+            } else {
+                moved_from.push(old_row.clone());
+                moved_to.push(row.clone());
+            }
+        }
+        self.record_written(WALOperation::Update, table_name, updated);
+        self.record_written(WALOperation::Delete, table_name, moved_from);
+        self.record_written(WALOperation::Insert, table_name, moved_to);
+        Ok(())
+    }
+
+    // Rewritten rows may not share a unique index key with any other row once the statement
+    // has run, so rows may still trade values within one statement. NULLs never clash.
+    fn check_changed_unique_keys(&self, table_name: &str, changed: &[(usize, Vec<DataValue>)]) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        for index in self.unique_indexes.get(table_name).into_iter().flatten() {
+            let Ok(IndexType::BTree(btree)) = self.storage.get_index(table_name, index) else { continue };
+            for (i, _) in changed {
+                let value = self.index_value(table_name, index, schema, &rows[*i])?;
+                if value == DataValue::Null {
+                    continue;
+                }
+                if btree.search(self.index_key(table_name, index, &value)).is_some_and(|ids| ids.len() > 1) {
+                    return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, index, value)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_text_index(&self, table_name: &str, index: &str) -> bool {
+        self.text_indexes.get(table_name).is_some_and(|indexes| indexes.iter().any(|name| name == index))
+    }
+
+    // Encodes a value the way the index named `index` keys it
+    fn index_key(&self, table_name: &str, index: &str, value: &DataValue) -> Vec<u8> {
+        if self.is_text_index(table_name, index) {
+            BTreeIndex::text_key_for(value)
+        } else {
+            BTreeIndex::key_for(value)
+        }
+    }
+
+    // B-Tree over the value `expression` computes for each of the table's rows, keyed like a
+    // text index when `text` is set
+    fn expression_btree(&self, table_name: &str, expression: &DataValue, text: bool) -> Result<BTreeIndex, ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let mut btree = BTreeIndex::new();
+        for (i, row) in rows.iter().enumerate() {
+            let value = self.evaluate_expression(expression, row, &[], schema, &[])?;
+            let key = if text { BTreeIndex::text_key_for(&value) } else { BTreeIndex::key_for(&value) };
+            btree.add_entry(key, i + 1);
+        }
+        Ok(btree)
+    }
+
+    // Columns an expression reads
+    fn expression_columns(expression: &DataValue) -> Vec<String> {
+        match expression {
+            DataValue::Function { args, .. } => args.iter().flat_map(Self::expression_columns).collect(),
+            DataValue::Column(name) => vec![name.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    // Adds a newly stored row to the table's expression indexes
+    fn index_expressions(&mut self, table_name: &str, schema: &[ColumnDef], row: &[DataValue], row_id: usize) -> Result<(), ReefDBError> {
+        for (key, expression) in self.expression_indexes.get(table_name).cloned().unwrap_or_default() {
+            let value = self.evaluate_expression(&expression, row, &[], schema, &[])?;
+            let index_key = self.index_key(table_name, &key, &value);
+            self.storage.update_index(table_name, &key, vec![], index_key, row_id)?;
+        }
+        Ok(())
+    }
+
+    /// Positions of the rows an index narrows `where_clause` down to, or None when no
+    /// conjunct can use one. An expression index serves equality on its expression and a
+    /// text index serves LIKE with a literal prefix. The caller still applies the full
+    /// predicate to the rows returned.
+    pub(crate) fn indexed_rows(&self, table_name: &str, where_clause: &WhereType) -> Option<Vec<usize>> {
+        match where_clause {
+            WhereType::Regular(clause) => {
+                let index = match &clause.function {
+                    Some(function) => expression_key(function),
+                    None => clause.col_name.clone(),
+                };
+                let Ok(IndexType::BTree(btree)) = self.storage.get_index(table_name, &index) else { return None };
+                let mut positions: Vec<usize> = match (&clause.operator, &clause.value) {
+                    (Op::Like, DataValue::Text(pattern)) if self.is_text_index(table_name, &index) => {
+                        // NOCASE columns compare lowercased, which the index doesn't hold
+                        if clause.function.is_none() {
+                            let (schema, _) = self.get_table_schema(table_name).ok()?;
+                            let column = schema.iter().find(|c| c.name == index)?;
+                            if column.collation() != Collation::Binary {
+                                return None;
                             }
                         }
+                        let prefix = like_prefix(pattern);
+                        if prefix.is_empty() {
+                            return None;
+                        }
+                        btree.prefix_search(prefix.as_bytes())
+                    }
+                    // Float keys are skipped since 0.0 and -0.0 are equal but encode differently
+                    (Op::Equal, value) if clause.function.is_some()
+                        && !matches!(value, DataValue::Function { .. } | DataValue::Float(_) | DataValue::Null) =>
+                    {
+                        btree.search(self.index_key(table_name, &index, value))
+                            .map(|row_ids| row_ids.iter().copied().collect())
+                            .unwrap_or_default()
                     }
+                    _ => return None,
+                };
+                positions = positions.into_iter().filter_map(|id| id.checked_sub(1)).collect();
+                positions.sort_unstable();
+                Some(positions)
+            }
+            WhereType::And(left, right) => self.indexed_rows(table_name, left)
+                .or_else(|| self.indexed_rows(table_name, right)),
+            _ => None,
+        }
+    }
+
+    fn evaluate_column(&self, column: &Column, row: &[DataValue], schema: &[ColumnDef]) -> Result<DataValue, ReefDBError> {
+        match &column.column_type {
+            ColumnType::Regular(name) => {
+                if let Some(idx) = schema.iter().position(|c| c.name == *name) {
+                    Ok(row[idx].clone())
+                } else {
+                    Err(ReefDBError::ColumnNotFound(name.clone()))
                 }
             }
+            ColumnType::Function(name, args) => {
+                // Evaluate function arguments
+                let evaluated_args = args.iter()
+                    .map(|arg| self.evaluate_expression(arg, row, &[], schema, &[]))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Call function
+                self.function_registry.call(name, evaluated_args)
+            }
+            ColumnType::Case(case) => self.evaluate_case(case, row, &[], schema, &[], ""),
+            ColumnType::Concat(operands) => self.evaluate_concat(operands, row, &[], schema, &[]),
+            ColumnType::Window(_) | ColumnType::Aggregate(_) => {
+                Err(ReefDBError::Other("Window functions and aggregates are evaluated over the whole result".to_string()))
+            }
+            ColumnType::Wildcard => {
+                Err(ReefDBError::Other("Cannot evaluate wildcard in expression".to_string()))
+            }
         }
-        Ok(())
     }
 
-    
-    fn evaluate_column(&self, column: &Column, row: &[DataValue], schema: &[ColumnDef]) -> Result<DataValue, ReefDBError> {
-        match &column.column_type {
-            ColumnType::Regular(name) => {
-                if let Some(idx) = schema.iter().position(|c| c.name == *name) {
+    // Function arguments are parsed as bare text, so text naming a column of either row
+    // resolves to that column's value and any other text stays a literal
+    fn evaluate_expression(
+        &self,
+        value: &DataValue,
+        row: &[DataValue],
+        join_row: &[DataValue],
+        schema: &[ColumnDef],
+        join_schema: &[ColumnDef],
+    ) -> Result<DataValue, ReefDBError> {
+        match value {
+            DataValue::Function { name, args } => {
+                let evaluated_args = args.iter()
+                    .map(|arg| self.evaluate_expression(arg, row, join_row, schema, join_schema))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // now() reads the injected clock and nextval() the database's sequences, so
+                // neither fits the registry's stateless functions
+                match (name.to_lowercase().as_str(), evaluated_args.as_slice()) {
+                    ("now", []) => Ok(DataValue::Timestamp(
+                        DateTime::<Utc>::from(self.clock.now()).format("%Y-%m-%d %H:%M:%S").to_string()
+                    )),
+                    ("nextval", [DataValue::Text(sequence)]) => Ok(DataValue::Integer(self.next_sequence_value(sequence))),
+                    _ => self.function_registry.call(name, evaluated_args),
+                }
+            }
+            DataValue::Column(name) => {
+                let col_name = name.rsplit_once('.').map_or(name.as_str(), |(_, column)| column);
+                if let Some(idx) = schema.iter().position(|c| c.name == col_name) {
                     Ok(row[idx].clone())
+                } else if let Some(idx) = join_schema.iter().position(|c| c.name == col_name) {
+                    Ok(join_row[idx].clone())
                 } else {
                     Err(ReefDBError::ColumnNotFound(name.clone()))
                 }
             }
-            ColumnType::Function(name, args) => {
-                // Evaluate function arguments
-                let mut evaluated_args = Vec::new();
-                for arg in args {
-                    let arg_value = match arg {
-                        DataValue::Text(s) => Ok(DataValue::Text(s.clone())),
-                        DataValue::Function { name, args } => self.function_registry.call(name, args.clone()),
-                        _ => Ok(arg.clone()),
-                    }?;
-                    evaluated_args.push(arg_value);
-                }
-                
-                // Call function
-                self.function_registry.call(name, evaluated_args)
+            _ => Ok(value.clone()),
+        }
+    }
+
+    /// Advances the named sequence and returns its new value, starting from 1. Sequences are
+    /// created on first use and, as in other databases, don't roll back with a transaction.
+    pub fn next_sequence_value(&self, sequence: &str) -> i64 {
+        let mut sequences = lock_recovering(&self.sequences);
+        let value = sequences.entry(sequence.to_string()).or_insert(0);
+        *value += 1;
+        *value
+    }
+
+    // Operands are concatenated in their display form; any NULL operand makes the result NULL
+    fn evaluate_concat(
+        &self,
+        operands: &[DataValue],
+        row: &[DataValue],
+        join_row: &[DataValue],
+        schema: &[ColumnDef],
+        join_schema: &[ColumnDef],
+    ) -> Result<DataValue, ReefDBError> {
+        let mut result = String::new();
+        for operand in operands {
+            match self.evaluate_expression(operand, row, join_row, schema, join_schema)? {
+                DataValue::Null => return Ok(DataValue::Null),
+                value => result.push_str(&value.to_string()),
             }
-            ColumnType::Wildcard => {
-                Err(ReefDBError::Other("Cannot evaluate wildcard in expression".to_string()))
+        }
+        Ok(DataValue::Text(result.into()))
+    }
+
+    // Result of the first branch whose condition holds for the row, else the ELSE value or NULL
+    fn evaluate_case(
+        &self,
+        case: &CaseExpression,
+        row: &[DataValue],
+        join_row: &[DataValue],
+        schema: &[ColumnDef],
+        join_schema: &[ColumnDef],
+        main_table: &str,
+    ) -> Result<DataValue, ReefDBError> {
+        for (condition, value) in &case.branches {
+            if self.evaluate_where_clause(condition, row, join_row, schema, join_schema, main_table)? {
+                return Ok(value.clone());
             }
         }
+        Ok(case.else_value.clone().unwrap_or(DataValue::Null))
     }
 
     fn evaluate_where_clause(
@@ -448,6 +2198,11 @@ where
     ) -> Result<bool, ReefDBError> {
         match where_clause {
             WhereType::Regular(clause) => {
+                if let Some(function) = &clause.function {
+                    let left = self.evaluate_expression(function, row, join_row, schema, join_schema)?;
+                    let right = self.evaluate_expression(&clause.value, row, join_row, schema, join_schema)?;
+                    return Ok(clause.operator.evaluate(&left, &right));
+                }
                 let (col_idx, row_to_check, schema_to_use) = if let Some(table) = &clause.table {
                     if table == main_table {
                         let idx = schema.iter()
@@ -473,29 +2228,13 @@ where
 
                 // Handle function calls in the value
                 let evaluated_value = match &clause.value {
-                    DataValue::Function { name, args } => {
-                        let mut evaluated_args = Vec::new();
-                        for arg in args {
-                            let arg_value = match arg {
-                                DataValue::Text(col_name) => {
-                                    // Treat text values as column references
-                                    let col = Column {
-                                        name: col_name.clone(),
-                                        table: None,
-                                        column_type: ColumnType::Regular(col_name.clone()),
-                                    };
-                                    self.evaluate_column(&col, row_to_check, schema_to_use)?
-                                }
-                                _ => arg.clone(),
-                            };
-                            evaluated_args.push(arg_value);
-                        }
-                        self.function_registry.call(name, evaluated_args)?
+                    DataValue::Function { .. } => {
+                        self.evaluate_expression(&clause.value, row_to_check, &[], schema_to_use, &[])?
                     }
                     _ => clause.value.clone(),
                 };
 
-                Ok(clause.operator.evaluate(&row_to_check[col_idx], &evaluated_value))
+                Ok(schema_to_use[col_idx].collation().evaluate(&clause.operator, &row_to_check[col_idx], &evaluated_value))
             }
             WhereType::FTS(clause) => {
                 let table_name = if let Some(table) = &clause.column.table {
@@ -554,21 +2293,8 @@ where
         self.verify_table_exists(&table_name)?;
         let (schema, _) = self.get_table_schema(&table_name)?;
 
-        // Validate update columns exist and value types match
-        for (col_name, value) in &updates {
-            let column = schema.iter()
-                .find(|c| &c.name == col_name)
-                .ok_or_else(|| ReefDBError::ColumnNotFound(col_name.clone()))?;
-
-            if !value.matches_type(&column.data_type) {
-                return Err(ReefDBError::Other(format!(
-                    "Value type mismatch for column {}: expected {:?}, got {:?}",
-                    col_name,
-                    column.data_type,
-                    value
-                )));
-            }
-        }
+        let mut updates = updates;
+        Self::check_assignments(schema, &mut updates)?;
 
         // Validate where clause column exists if present
         if let Some(where_clause) = &where_clause {
@@ -577,16 +2303,181 @@ where
 
         // Convert WhereType to simple where clause for storage layer
         let storage_where = where_clause.and_then(|w| match w {
-            WhereType::Regular(clause) => Some((clause.col_name, clause.value)),
+            WhereType::Regular(clause) => {
+                let value = match schema.iter().find(|c| c.name == clause.col_name) {
+                    Some(column) => clause.value.clone().coerce_to(&column.data_type).unwrap_or(clause.value),
+                    None => clause.value,
+                };
+                Some((clause.col_name, value))
+            }
             WhereType::FTS(_) => None, // FTS not supported for updates
             WhereType::And(_, _) => None, // Complex conditions not supported for updates
             WhereType::Or(_, _) => None, // Complex conditions not supported for updates
         });
 
+        // Assigned key and reference columns are checked on the rewritten rows once the
+        // update has run, so rows may trade values within one statement
+        let constrained: Vec<ColumnDef> = schema.iter()
+            .filter(|c| updates.iter().any(|(name, _)| *name == c.name))
+            .filter(|c| Self::is_key(c) || c.foreign_key().is_some())
+            .cloned()
+            .collect();
+
+        // The rows storage is about to rewrite, kept so their index entries can be moved
+        // and so a failed check can put them back
+        let where_index = match &storage_where {
+            Some((column, _)) => Some(schema.iter().position(|c| c.name == *column)
+                .ok_or_else(|| ReefDBError::ColumnNotFound(column.clone()))?),
+            None => None,
+        };
+        let changed: Vec<(usize, Vec<DataValue>)> = self.get_table_schema(&table_name)?.1.iter()
+            .enumerate()
+            .filter(|(_, row)| match (&storage_where, where_index) {
+                (Some((_, value)), Some(i)) => row[i] == *value,
+                _ => true,
+            })
+            .map(|(i, row)| (i, row.clone()))
+            .collect();
+
+        let has_generated = schema.iter().any(ColumnDef::is_generated);
         let updated_count = self.storage.update_table(&table_name, updates, storage_where);
+        if updated_count > 0 && has_generated {
+            self.recompute_generated_columns(&table_name, &changed)?;
+        }
+        if updated_count > 0 {
+            self.reindex_rows(&table_name, &changed)?;
+            let immediate: Vec<&ColumnDef> = constrained.iter()
+                .filter(|column| !self.defer_check(&table_name, column))
+                .collect();
+            let checked = immediate.into_iter()
+                .try_for_each(|column| self.check_changed_rows(&table_name, column, &changed))
+                .and_then(|_| self.check_changed_unique_keys(&table_name, &changed))
+                .and_then(|_| {
+                    let old: Vec<Vec<DataValue>> = changed.iter().map(|(_, row)| row.clone()).collect();
+                    self.check_referencing_rows(&table_name, &old, &HashSet::new())
+                });
+            if let Err(err) = checked {
+                self.restore_rows(&table_name, &changed)?;
+                return Err(err);
+            }
+            self.record_rewritten(&table_name, &changed)?;
+        }
         Ok(ReefDBResult::Update(updated_count))
     }
 
+    // MERGE pairs every source row with the target rows equal to it on the ON columns.
+    // Matched target rows get the UPDATE assignments and unmatched source rows are
+    // inserted. All new rows are built and checked before the target is written, so an
+    // action that fails leaves the table as it was.
+    fn handle_merge(&mut self, merge: MergeStatement) -> Result<ReefDBResult, ReefDBError> {
+        let (schema, target_rows) = self.get_table_schema(&merge.target.name)?.clone();
+        let (source_schema, source_rows) = self.get_table_schema(&merge.source.name)?.clone();
+
+        let (left, right) = &merge.on;
+        let (target_key, source_key) = match (
+            merge.resolve(left, &schema, &source_schema)?,
+            merge.resolve(right, &schema, &source_schema)?,
+        ) {
+            ((MergeSide::Target, t), (MergeSide::Source, s)) | ((MergeSide::Source, s), (MergeSide::Target, t)) => (t, s),
+            _ => return Err(ReefDBError::Other(
+                "MERGE ON must compare a target column with a source column".to_string()
+            )),
+        };
+        let collation = schema[target_key].collation();
+        let mut targets_by_key: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+        for (i, row) in target_rows.iter().enumerate() {
+            if row[target_key] != DataValue::Null {
+                targets_by_key.entry(BTreeIndex::key_for(&collation.key(&row[target_key]))).or_default().push(i);
+            }
+        }
+
+        let value_of = |value: &MergeValue, target: Option<&[DataValue]>, source: &[DataValue]| match value {
+            MergeValue::Value(value) => self.evaluate_expression(value, &[], &[], &[], &[]),
+            MergeValue::Column(column) => match (merge.resolve(column, &schema, &source_schema)?, target) {
+                ((MergeSide::Source, i), _) => Ok(source[i].clone()),
+                ((MergeSide::Target, i), Some(target)) => Ok(target[i].clone()),
+                ((MergeSide::Target, _), None) => Err(ReefDBError::Other(format!(
+                    "Column {} has no target row to read in WHEN NOT MATCHED", column.column_name
+                ))),
+            },
+        };
+
+        let mut updates: Vec<(usize, Vec<DataValue>)> = Vec::new();
+        let mut inserts = Vec::new();
+        for source in &source_rows {
+            let matched = match &source[source_key] {
+                DataValue::Null => None,
+                key => targets_by_key.get(&BTreeIndex::key_for(&collation.key(key))),
+            };
+            match (matched, &merge.when_matched, &merge.when_not_matched) {
+                (Some(matched), Some(assignments), _) => {
+                    for &i in matched {
+                        if updates.iter().any(|(updated, _)| *updated == i) {
+                            return Err(ReefDBError::Other(format!(
+                                "MERGE matched a row of {} with more than one source row", merge.target.name
+                            )));
+                        }
+                        let mut row = target_rows[i].clone();
+                        for (name, value) in assignments {
+                            let column = schema.iter().position(|c| &c.name == name)
+                                .ok_or_else(|| ReefDBError::ColumnNotFound(name.clone()))?;
+                            if schema[column].is_generated() {
+                                return Err(ReefDBError::GeneratedColumnWrite(name.clone()));
+                            }
+                            row[column] = Self::coerce_value(&schema[column], value_of(value, Some(&target_rows[i]), source)?)?;
+                        }
+                        self.compute_generated_columns(&schema, &mut row)?;
+                        Self::validate_row(&schema, &row)?;
+                        self.check_foreign_keys(&merge.target.name, &schema, &row, &[])?;
+                        updates.push((i, row));
+                    }
+                }
+                (None, _, Some(values)) => inserts.push(
+                    values.iter().map(|value| value_of(value, None, source)).collect::<Result<Vec<_>, _>>()?
+                ),
+                _ => {}
+            }
+        }
+
+        let updated = updates.len();
+        let old: Vec<(usize, Vec<DataValue>)> = updates.iter().map(|(i, _)| (*i, target_rows[*i].clone())).collect();
+        if let Some((_, rows)) = self.storage.get_table(&merge.target.name) {
+            for (i, row) in updates {
+                rows[i] = row;
+            }
+        }
+        if updated > 0 {
+            for column in &schema {
+                self.defer_check(&merge.target.name, column);
+            }
+            self.reindex_rows(&merge.target.name, &old)?;
+            let old_rows: Vec<Vec<DataValue>> = old.iter().map(|(_, row)| row.clone()).collect();
+            let checked = self.check_changed_unique_keys(&merge.target.name, &old)
+                .and_then(|_| self.check_referencing_rows(&merge.target.name, &old_rows, &HashSet::new()));
+            if let Err(err) = checked {
+                self.restore_rows(&merge.target.name, &old)?;
+                return Err(err);
+            }
+        }
+        let inserted = match inserts.is_empty() {
+            true => 0,
+            false => match self.copy_into(&merge.target.name, inserts) {
+                Ok(inserted) => inserted,
+                Err(err) => {
+                    self.restore_rows(&merge.target.name, &old)?;
+                    return Err(err);
+                }
+            },
+        };
+        self.record_rewritten(&merge.target.name, &old)?;
+        if inserted > 0 && self.written_rows.is_some() {
+            let (_, rows) = self.get_table_schema(&merge.target.name)?;
+            let rows = rows[rows.len() - inserted..].to_vec();
+            self.record_written(WALOperation::Insert, &merge.target.name, rows);
+        }
+        Ok(ReefDBResult::Merge { updated, inserted })
+    }
+
     fn validate_where_clause(&self, where_clause: &WhereType, schema: &[ColumnDef]) -> Result<(), ReefDBError> {
         match where_clause {
             WhereType::Regular(clause) => {
@@ -632,7 +2523,36 @@ where
             WhereType::Or(_, _) => None, // Complex conditions not supported for deletes
         });
 
+        // Rows of other tables may not be left pointing at the deleted rows
+        let referenced = self.storage.table_names().iter()
+            .filter_map(|child| self.storage.get_schema_ref(child))
+            .flatten()
+            .any(|column| column.foreign_key().is_some_and(|fk| fk.table_name == table_name));
+        let mut old = Vec::new();
+        if referenced || self.written_rows.is_some() {
+            let where_index = match &storage_where {
+                Some((column, _)) => schema.iter().position(|c| c.name == *column),
+                None => None,
+            };
+            let (_, rows) = self.get_table_schema(&table_name)?;
+            let (deleted, matched): (HashSet<usize>, Vec<Vec<DataValue>>) = rows.iter().enumerate()
+                .filter(|(_, row)| match (&storage_where, where_index) {
+                    (Some((_, value)), Some(i)) => row[i] == *value,
+                    _ => true,
+                })
+                .map(|(i, row)| (i, row.clone()))
+                .unzip();
+            if referenced {
+                self.check_referencing_rows(&table_name, &matched, &deleted)?;
+            }
+            old = matched;
+        }
+
         let deleted_count = self.storage.delete_table(&table_name, storage_where);
+        if deleted_count > 0 {
+            self.rebuild_btree_indexes(&table_name)?;
+            self.record_written(WALOperation::Delete, &table_name, old);
+        }
         Ok(ReefDBResult::Delete(deleted_count))
     }
 
@@ -666,28 +2586,191 @@ where
             }
         }
 
+        let version = self.storage.schema_version();
+        self.storage.set_schema_version(version + 1);
+        lock_recovering(&self.plan_cache).invalidate_table(&table_name);
         Ok(ReefDBResult::AlterTable)
     }
 
-    fn handle_drop(&mut self, table_name: String) -> Result<ReefDBResult, ReefDBError> {
+    fn handle_drop(&mut self, table_name: String, if_exists: bool) -> Result<ReefDBResult, ReefDBError> {
+        if self.tables.is_temporary(&table_name) {
+            self.drop_temporary_table(&table_name)?;
+            return Ok(ReefDBResult::DropTable);
+        }
+        if if_exists && !self.storage.table_exists(&table_name) {
+            return Ok(ReefDBResult::DropTable);
+        }
         self.verify_table_exists(&table_name)?;
+
+        // Drop every index defined on the table's columns
+        let column_names: Vec<String> = self.storage.get_schema_ref(&table_name)
+            .map(|schema| schema.iter().map(|c| c.name.clone()).collect())
+            .unwrap_or_default();
+        for column_name in &column_names {
+            self.storage.drop_index(&table_name, column_name);
+        }
+        for (key, _) in self.expression_indexes.remove(&table_name).unwrap_or_default() {
+            self.storage.drop_index(&table_name, &key);
+        }
+        self.unique_indexes.remove(&table_name);
+        self.text_indexes.remove(&table_name);
+
+        // Purge row versions so a recreated table does not see old data
+        lock_recovering(&self.mvcc_manager)
+            .drop_table(&table_name);
+
+        self.inverted_index.remove_table(&table_name);
+
+        lock_recovering(&self.statistics).remove(&table_name);
+        lock_recovering(&self.plan_cache).invalidate_table(&table_name);
+
         self.storage.drop_table(&table_name);
         self.tables.drop_table(&table_name);
         Ok(ReefDBResult::DropTable)
     }
 
+    fn handle_show_tables(&self) -> Result<ReefDBResult, ReefDBError> {
+        let rows = self.storage.table_names()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (i, vec![DataValue::Text(name.into())]))
+            .collect();
+        let columns = vec![ColumnInfo {
+            name: "table_name".to_string(),
+            data_type: DataType::Text,
+            table: None,
+            nullable: false,
+        }];
+        Ok(ReefDBResult::Select(QueryResult::with_columns(rows, columns)))
+    }
+
+    fn handle_show_locks(&self) -> Result<ReefDBResult, ReefDBError> {
+        let tm = self.transaction_manager.as_ref()
+            .ok_or_else(|| ReefDBError::Other("Transaction manager not initialized".to_string()))?;
+        Ok(Self::show_locks_result(tm.lock_snapshot()))
+    }
+
+    /// One row per held or queued lock: transaction, resource, lock type and whether
+    /// it is granted or waiting
+    pub(crate) fn show_locks_result(entries: Vec<LockEntry>) -> ReefDBResult {
+        let rows = entries.into_iter()
+            .enumerate()
+            .map(|(i, entry)| (i, vec![
+                DataValue::Integer(entry.transaction_id as i64),
+                DataValue::Text(entry.resource.into()),
+                DataValue::Text(format!("{:?}", entry.lock_type).into()),
+                DataValue::Text(if entry.granted { "granted" } else { "waiting" }.into()),
+            ]))
+            .collect();
+        let column = |name: &str, data_type| ColumnInfo {
+            name: name.to_string(),
+            data_type,
+            table: None,
+            nullable: false,
+        };
+        let columns = vec![
+            column("transaction_id", DataType::Integer),
+            column("resource", DataType::Text),
+            column("lock_type", DataType::Text),
+            column("status", DataType::Text),
+        ];
+        ReefDBResult::Select(QueryResult::with_columns(rows, columns))
+    }
+
+    fn handle_describe(&self, table_name: &str) -> Result<ReefDBResult, ReefDBError> {
+        let (schema, _) = self.storage.get_table_ref(table_name)
+            .ok_or_else(|| ReefDBError::TableNotFound(table_name.to_string()))?;
+
+        let rows = schema.iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let constraints = column.constraints.iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (i, vec![
+                    DataValue::Text(column.name.as_str().into()),
+                    DataValue::Text(column.data_type.to_string().into()),
+                    DataValue::Text(constraints.into()),
+                ])
+            })
+            .collect();
+        let columns = ["column_name", "data_type", "constraints"].iter()
+            .map(|name| ColumnInfo {
+                name: name.to_string(),
+                data_type: DataType::Text,
+                table: None,
+                nullable: false,
+            })
+            .collect();
+        Ok(ReefDBResult::Select(QueryResult::with_columns(rows, columns)))
+    }
+
+    fn handle_analyze(&self, table_name: &str) -> Result<ReefDBResult, ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let stats = TableStatistics::analyze(schema, rows, DEFAULT_HISTOGRAM_BUCKETS);
+        lock_recovering(&self.statistics)
+            .insert(table_name.to_string(), stats);
+        Ok(ReefDBResult::Analyze)
+    }
+
+    fn handle_set_schema_version(&mut self, version: u32) -> Result<ReefDBResult, ReefDBError> {
+        self.storage.set_schema_version(version);
+        Ok(ReefDBResult::SetSchemaVersion)
+    }
+
     fn handle_create_index(&mut self, stmt: CreateIndexStatement) -> Result<ReefDBResult, ReefDBError> {
         self.verify_table_exists(&stmt.table_name)?;
         let (schema, _) = self.get_table_schema(&stmt.table_name)?;
 
-        // Verify column exists
-        if !schema.iter().any(|c| c.name == stmt.column_name) {
-            return Err(ReefDBError::ColumnNotFound(stmt.column_name));
+        let text = stmt.index_type == CreateIndexType::Text;
+        if text && stmt.unique {
+            return Err(ReefDBError::Other("A TEXT index cannot be UNIQUE".to_string()));
+        }
+        let btree = match &stmt.expression {
+            Some(expression) => {
+                // The expression has to read at least one of the table's columns
+                if !Self::expression_columns(expression).iter().any(|name| schema.iter().any(|c| &c.name == name)) {
+                    return Err(ReefDBError::ColumnNotFound(stmt.column_name));
+                }
+                self.expression_btree(&stmt.table_name, expression, text)?
+            }
+            None => {
+                // Verify column exists
+                let column_idx = schema.iter().position(|c| c.name == stmt.column_name)
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(stmt.column_name.clone()))?;
+
+                // Create B-Tree index over the rows already in the table
+                let (_, rows) = self.get_table_schema(&stmt.table_name)?;
+                if text {
+                    BTreeIndex::from_text_rows(rows, column_idx)
+                } else {
+                    BTreeIndex::from_rows(rows, column_idx)
+                }
+            }
+        };
+        if stmt.unique {
+            if let Some(value) = btree.first_duplicate() {
+                return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", stmt.table_name, stmt.column_name, value)));
+            }
         }
+        self.storage.create_index(&stmt.table_name, &stmt.column_name, IndexType::BTree(btree))?;
 
-        // Create B-Tree index
-        let btree = BTreeIndex::new();
-        self.storage.create_index(&stmt.table_name, &stmt.column_name, IndexType::BTree(btree));
+        if let Some(expression) = stmt.expression {
+            let indexes = self.expression_indexes.entry(stmt.table_name.clone()).or_default();
+            indexes.retain(|(key, _)| *key != stmt.column_name);
+            indexes.push((stmt.column_name.clone(), expression));
+        }
+        let text_indexes = self.text_indexes.entry(stmt.table_name.clone()).or_default();
+        text_indexes.retain(|index| *index != stmt.column_name);
+        if text {
+            text_indexes.push(stmt.column_name.clone());
+        }
+        let unique = self.unique_indexes.entry(stmt.table_name).or_default();
+        unique.retain(|index| *index != stmt.column_name);
+        if stmt.unique {
+            unique.push(stmt.column_name);
+        }
 
         Ok(ReefDBResult::CreateIndex)
     }
@@ -696,13 +2779,25 @@ where
         self.verify_table_exists(&stmt.table_name)?;
         let (schema, _) = self.get_table_schema(&stmt.table_name)?;
 
-        // Verify column exists
-        if !schema.iter().any(|c| c.name == stmt.column_name) {
+        let is_expression = self.expression_indexes.get(&stmt.table_name)
+            .is_some_and(|indexes| indexes.iter().any(|(key, _)| *key == stmt.column_name));
+        if is_expression {
+            if let Some(indexes) = self.expression_indexes.get_mut(&stmt.table_name) {
+                indexes.retain(|(key, _)| *key != stmt.column_name);
+            }
+        } else if !schema.iter().any(|c| c.name == stmt.column_name) {
+            // Verify column exists
             return Err(ReefDBError::ColumnNotFound(stmt.column_name));
         }
 
-        // Drop the index
+        // Drop the index, and with it any uniqueness it enforced
         self.storage.drop_index(&stmt.table_name, &stmt.column_name);
+        if let Some(unique) = self.unique_indexes.get_mut(&stmt.table_name) {
+            unique.retain(|index| *index != stmt.column_name);
+        }
+        if let Some(text_indexes) = self.text_indexes.get_mut(&stmt.table_name) {
+            text_indexes.retain(|index| *index != stmt.column_name);
+        }
 
         Ok(ReefDBResult::DropIndex)
     }
@@ -725,16 +2820,10 @@ where
             if let Some(tm) = &mut self.transaction_manager {
                 let restored_state = tm.rollback_to_savepoint(tx_id, &name)?;
                 
-                // First clear both states
+                // Restore both states, temporary tables back in the storage's in-memory layer
                 self.tables = TableStorage::new();
-                self.storage.clear();
-                
-                // Then restore from the savepoint state
-                for (table_name, (columns, rows)) in restored_state.tables.iter() {
-                    // Create the table in both storage and tables
-                    self.storage.insert_table(table_name.clone(), columns.clone(), rows.clone());
-                    self.tables.insert_table(table_name.clone(), columns.clone(), rows.clone());
-                }
+                self.tables.restore_from(&restored_state);
+                self.storage.restore_from(&restored_state);
                 
                 Ok(ReefDBResult::RollbackToSavepoint)
             } else {
@@ -770,9 +2859,20 @@ where
 
     fn handle_commit(&mut self) -> Result<ReefDBResult, ReefDBError> {
         if let Some(tx_id) = self.current_transaction_id {
+            // Deferred checks run with nothing pending so they aren't skipped again. A
+            // violation fails the commit and leaves the transaction open to be fixed.
+            if let Some(pending) = self.pending_constraints.take() {
+                if let Some(err) = pending.iter()
+                    .find_map(|(table, column)| self.check_column_constraints(table, column).err())
+                {
+                    self.pending_constraints = Some(pending);
+                    return Err(err);
+                }
+            }
             if let Some(tm) = &mut self.transaction_manager {
                 tm.commit_transaction(tx_id)?;
                 self.current_transaction_id = None;
+                self.drop_temporary_tables()?;
                 Ok(ReefDBResult::Commit)
             } else {
                 Err(ReefDBError::Other("Transaction manager not initialized".to_string()))
@@ -800,6 +2900,7 @@ where
                 }
                 let tx_id = self.transaction_manager.as_mut().unwrap().begin_transaction(IsolationLevel::ReadCommitted)?;
                 self.current_transaction_id = Some(tx_id);
+                self.pending_constraints = Some(Vec::new());
                 return Ok(ReefDBResult::BeginTransaction);
             }
             Statement::Commit => return self.handle_commit(),
@@ -846,24 +2947,23 @@ where
                     if self.current_transaction_id.is_some() {
                         return Err(ReefDBError::Other("Cannot begin a transaction within another transaction".to_string()));
                     }
-                    self.handle_begin_transaction()
+                    let result = self.handle_begin_transaction()?;
+                    self.pending_constraints = Some(Vec::new());
+                    Ok(result)
                 }
                 Statement::Create(create_stmt) => {
-                    match create_stmt {
-                        CreateStatement::Table(table_name, columns) => {
-                            if !self.autocommit && self.current_transaction_id.is_none() {
-                                // Start an implicit transaction for DDL statements
-                                let tx_id = self.transaction_manager.as_mut().unwrap().begin_transaction(IsolationLevel::ReadCommitted)?;
-                                self.current_transaction_id = Some(tx_id);
-                                let result = self.handle_create(table_name, columns)?;
-                                // Commit the implicit transaction
-                                self.transaction_manager.as_mut().unwrap().commit_transaction(tx_id)?;
-                                self.current_transaction_id = None;
-                                Ok(result)
-                            } else {
-                                self.handle_create(table_name, columns)
-                            }
-                        }
+                    if !self.autocommit && self.current_transaction_id.is_none() {
+                        // Start an implicit transaction for DDL statements
+                        let tx_id = self.transaction_manager.as_mut().unwrap().begin_transaction(IsolationLevel::ReadCommitted)?;
+                        self.current_transaction_id = Some(tx_id);
+                        let result = self.handle_create_statement(create_stmt)?;
+                        // Commit the implicit transaction, which ends any temporary table it made
+                        self.transaction_manager.as_mut().unwrap().commit_transaction(tx_id)?;
+                        self.current_transaction_id = None;
+                        self.drop_temporary_tables()?;
+                        Ok(result)
+                    } else {
+                        self.handle_create_statement(create_stmt)
                     }
                 }
                 Statement::Drop(drop_stmt) => {
@@ -993,14 +3093,15 @@ where
 
     fn execute_statement_in_transaction(&mut self, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
         match stmt {
-            Statement::Create(CreateStatement::Table(name, columns)) => {
-                self.handle_create(name, columns)
-            }
+            Statement::Create(create_stmt) => self.handle_create_statement(create_stmt),
             Statement::Insert(insert_stmt) => {
                 match insert_stmt {
                     InsertStatement::IntoTable(table_name, values) => {
                         self.handle_insert(table_name, values)
                     }
+                    InsertStatement::Upsert(table_name, values, on_conflict) => {
+                        self.handle_upsert(table_name, values, on_conflict)
+                    }
                 }
             }
             Statement::Select(select_stmt) => {
@@ -1008,8 +3109,15 @@ where
                     SelectStatement::FromTable(table_ref, columns, where_clause, joins, order_by) => {
                         self.handle_select(table_ref, columns, where_clause, joins, order_by)
                     }
+                    SelectStatement::With(ctes, query) => self.handle_with(ctes, *query),
+                    SelectStatement::DistinctOn(keys, query) => self.handle_distinct_on(keys, *query),
+                    SelectStatement::GroupBy(keys, query) => self.handle_group_by(keys, *query),
+                    SelectStatement::GroupingSets(sets, query) => self.handle_grouping_sets(sets, *query),
+                    SelectStatement::Limit(limit, query) => self.handle_limit(limit, *query),
+                    SelectStatement::AsOf(_, _) => self.handle_as_of(),
                 }
             }
+            Statement::Merge(merge) => self.handle_merge(merge),
             Statement::Update(update_stmt) => {
                 match update_stmt {
                     UpdateStatement::UpdateTable(table_name, updates, where_clause) => {
@@ -1028,7 +3136,7 @@ where
                 self.handle_alter(alter_stmt.table_name, alter_stmt.alter_type)
             }
             Statement::Drop(drop_stmt) => {
-                self.handle_drop(drop_stmt.table_name)
+                self.handle_drop(drop_stmt.table_name, drop_stmt.if_exists)
             }
             Statement::CreateIndex(create_idx_stmt) => {
                 self.handle_create_index(create_idx_stmt)
@@ -1051,15 +3159,48 @@ where
             Statement::Commit => {
                 self.handle_commit()
             }
+            Statement::ShowTables => {
+                self.handle_show_tables()
+            }
+            Statement::ShowLocks => {
+                self.handle_show_locks()
+            }
+            Statement::Describe(table_name) => {
+                self.handle_describe(&table_name)
+            }
+            Statement::Analyze(table_name) => {
+                self.handle_analyze(&table_name)
+            }
+            Statement::SetSchemaVersion(version) => {
+                self.handle_set_schema_version(version)
+            }
         }
     }
 
+    /// Parses and runs one SQL statement.
+    ///
+    /// A SELECT without ORDER BY returns rows in ascending primary-key order of its FROM
+    /// table, whatever the storage backend; tables without a primary key keep storage order.
     pub fn query(&mut self, sql: &str) -> Result<ReefDBResult, ReefDBError> {
         use crate::sql::parser::Parser;
-        let stmt = Parser::parse_sql(sql)?;
+        let key = PlanCache::normalize(sql);
+        let cached = lock_recovering(&self.plan_cache).get(&key);
+        let stmt = match cached {
+            Some(stmt) => stmt,
+            None => {
+                let stmt = Parser::parse_sql(sql)?;
+                lock_recovering(&self.plan_cache).insert(key, &stmt);
+                stmt
+            }
+        };
         self.execute_statement(stmt)
     }
 
+    /// Hit and miss counts of the cache of parsed statements used by `query`
+    pub fn plan_cache_stats(&self) -> PlanCacheStats {
+        lock_recovering(&self.plan_cache).stats()
+    }
+
     pub fn set_autocommit(&mut self, enabled: bool) {
         self.autocommit = enabled;
     }
@@ -1075,4 +3216,373 @@ where
     pub fn get_autocommit_isolation_level(&self) -> IsolationLevel {
         self.autocommit_isolation_level
     }
+
+    /// Sets the isolation level `TransactionManager::begin_transaction_default` uses
+    pub fn set_default_isolation_level(&mut self, level: IsolationLevel) -> Result<(), ReefDBError> {
+        let tm = self.transaction_manager.as_mut()
+            .ok_or_else(|| ReefDBError::Other("Transaction manager not initialized".to_string()))?;
+        tm.set_default_isolation_level(level);
+        Ok(())
+    }
+
+    /// The database with `level` as its default isolation level, for setting it up as it's
+    /// created, e.g. `InMemoryReefDB::create_in_memory()?.with_default_isolation_level(level)?`
+    pub fn with_default_isolation_level(mut self, level: IsolationLevel) -> Result<Self, ReefDBError> {
+        self.set_default_isolation_level(level)?;
+        Ok(self)
+    }
+
+    /// Schema version recorded for this database
+    pub fn schema_version(&self) -> u32 {
+        self.storage.schema_version()
+    }
+
+    /// Fails with `SchemaVersionMismatch` when the database was migrated past `supported`,
+    /// i.e. by newer code whose schema this code doesn't understand
+    pub fn check_schema_version(&self, supported: u32) -> Result<(), ReefDBError> {
+        let found = self.schema_version();
+        if found > supported {
+            return Err(ReefDBError::SchemaVersionMismatch(found, supported));
+        }
+        Ok(())
+    }
+
+    /// Point-in-time recovery: applies the row operations of every transaction in `wal` that
+    /// committed at or before `cutoff`, in commit order, and returns how many were applied.
+    /// The WAL doesn't record schemas, so the tables must already exist (e.g. restored from a
+    /// base backup taken before the logged work).
+    pub fn replay_until(&mut self, wal: &mut WriteAheadLog, cutoff: SystemTime) -> Result<usize, ReefDBError> {
+        let entries = wal.committed_entries_until(cutoff)?;
+        for entry in &entries {
+            if entry.operation == WALOperation::BulkInsert {
+                self.copy_into(&entry.table_name, entry.rows()?)?;
+                continue;
+            }
+            let row = entry.row()?;
+            if entry.operation == WALOperation::Insert {
+                self.handle_insert(entry.table_name.clone(), row)?;
+                continue;
+            }
+
+            let (schema, _) = self.get_table_schema(&entry.table_name)?;
+            let pk_idx = schema.iter()
+                .position(|c| c.constraints.contains(&Constraint::PrimaryKey))
+                .unwrap_or(0);
+            let pk = Some((schema[pk_idx].name.clone(), row[pk_idx].clone()));
+            if entry.operation == WALOperation::Update {
+                let updates = schema.iter().map(|c| c.name.clone()).zip(row).collect();
+                self.storage.update_table(&entry.table_name, updates, pk);
+            } else {
+                self.storage.delete_table(&entry.table_name, pk);
+            }
+        }
+        Ok(entries.len())
+    }
+
+    /// Resolves the row changes of a committing transaction against the shared tables and
+    /// checks them, so that publishing them can't fail halfway. Updated and deleted rows are
+    /// matched on their primary key as `replay_until` does, and rows other transactions
+    /// committed meanwhile stay. An insert may not land on a key that is still taken, and the
+    /// rows written may not share a key column or unique index value with one another or with
+    /// a row they leave alone, deferred columns included. Tables in `replaced` are published
+    /// whole and have no delta.
+    pub(crate) fn stage_transaction(&self, replaced: &HashSet<String>, changes: &[RowChange]) -> Result<RowDeltas, ReefDBError> {
+        // Where the rows of each key seen so far are: stored at a position, or appended
+        enum Slot {
+            Stored(usize),
+            Appended(usize),
+        }
+        struct Staging {
+            delta: RowDelta,
+            located: HashMap<Vec<u8>, Vec<Slot>>,
+            // Stored positions by key, read off the rows when the key column has no index
+            scanned: Option<HashMap<Vec<u8>, Vec<usize>>>,
+        }
+
+        let mut staged: HashMap<&str, Staging> = HashMap::new();
+        for change in changes.iter().filter(|change| !replaced.contains(&change.table_name)) {
+            let (schema, rows) = self.get_table_schema(&change.table_name)?;
+            let pk_idx = schema.iter()
+                .position(|c| c.constraints.contains(&Constraint::PrimaryKey));
+            let has_pk = pk_idx.is_some();
+            let pk_idx = pk_idx.unwrap_or(0);
+            let indexed = match self.storage.get_index(&change.table_name, &schema[pk_idx].name) {
+                Ok(IndexType::BTree(btree)) if !self.is_text_index(&change.table_name, &schema[pk_idx].name) => Some(btree),
+                _ => None,
+            };
+            let Staging { delta, located, scanned } = staged.entry(&change.table_name)
+                .or_insert_with(|| Staging { delta: RowDelta::default(), located: HashMap::new(), scanned: None });
+            for row in &change.rows {
+                let key = BTreeIndex::key_for(&row[pk_idx]);
+                let slots = located.entry(key.clone()).or_insert_with(|| {
+                    let positions: Vec<usize> = match indexed {
+                        Some(btree) => btree.search(key.clone())
+                            .map(|ids| ids.iter().map(|id| id - 1).collect())
+                            .unwrap_or_default(),
+                        None => scanned.get_or_insert_with(|| {
+                            let mut by_key: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+                            for (position, row) in rows.iter().enumerate() {
+                                by_key.entry(BTreeIndex::key_for(&row[pk_idx])).or_default().push(position);
+                            }
+                            by_key
+                        }).get(&key).cloned().unwrap_or_default(),
+                    };
+                    positions.into_iter().map(Slot::Stored).collect()
+                });
+                match change.operation {
+                    WALOperation::Update => {
+                        for slot in slots.iter() {
+                            match slot {
+                                Slot::Stored(position) => { delta.rewrites.insert(*position, row.clone()); }
+                                Slot::Appended(i) => delta.appends[*i] = Some(row.clone()),
+                            }
+                        }
+                    }
+                    WALOperation::Delete => {
+                        for slot in slots.drain(..) {
+                            match slot {
+                                Slot::Stored(position) => {
+                                    delta.rewrites.remove(&position);
+                                    delta.deletes.insert(position);
+                                }
+                                Slot::Appended(i) => delta.appends[i] = None,
+                            }
+                        }
+                    }
+                    _ => {
+                        if has_pk && !slots.is_empty() {
+                            return Err(ReefDBError::DuplicateKey(format!(
+                                "{}.{} = {:?}", change.table_name, schema[pk_idx].name, row[pk_idx]
+                            )));
+                        }
+                        slots.push(Slot::Appended(delta.appends.len()));
+                        delta.appends.push(Some(row.clone()));
+                    }
+                }
+            }
+        }
+
+        let deltas: RowDeltas = staged.into_iter()
+            .map(|(table_name, staging)| (table_name.to_string(), staging.delta))
+            .collect();
+        for (table_name, delta) in &deltas {
+            self.check_row_delta(table_name, delta)?;
+        }
+        Ok(deltas)
+    }
+
+    // The rows `delta` writes may not repeat a key column or unique index value among
+    // themselves, or share one with a stored row the delta leaves alone. NULLs never clash.
+    // Expression indexes are evaluated on every written row, so indexing them can't fail
+    // once the delta is applied.
+    fn check_row_delta(&self, table_name: &str, delta: &RowDelta) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let written: Vec<&Vec<DataValue>> = delta.rewrites.values().chain(delta.appended()).collect();
+        if written.is_empty() {
+            return Ok(());
+        }
+        let kept = |position: usize| !delta.rewrites.contains_key(&position) && !delta.deletes.contains(&position);
+
+        for (i, column) in schema.iter().enumerate().filter(|(_, column)| Self::is_key(column)) {
+            let collation = column.collation();
+            let indexed = match self.storage.get_index(table_name, &column.name) {
+                Ok(IndexType::BTree(btree)) if collation == Collation::Binary && !self.is_text_index(table_name, &column.name) => Some(btree),
+                _ => None,
+            };
+            let values: Vec<DataValue> = written.iter().map(|row| row[i].clone()).collect();
+            let stored = rows.iter().enumerate().map(|(position, row)| (position, &row[i]));
+            Self::check_written_keys(table_name, &column.name, &values, |value| BTreeIndex::key_for(&collation.key(value)), indexed, stored, kept)?;
+        }
+        for (index, _) in self.expression_indexes.get(table_name).into_iter().flatten() {
+            for row in &written {
+                self.index_value(table_name, index, schema, row)?;
+            }
+        }
+        for index in self.unique_indexes.get(table_name).into_iter().flatten() {
+            let Ok(IndexType::BTree(btree)) = self.storage.get_index(table_name, index) else { continue };
+            let values = written.iter()
+                .map(|row| self.index_value(table_name, index, schema, row))
+                .collect::<Result<Vec<_>, _>>()?;
+            Self::check_written_keys(table_name, index, &values, |value| self.index_key(table_name, index, value), Some(btree), std::iter::empty(), kept)?;
+        }
+        Ok(())
+    }
+
+    // Fails when two of `values` share a key, or one shares it with a stored row `kept` says
+    // stays. Stored keys are looked up in `btree` when there is one, otherwise read off `stored`.
+    fn check_written_keys<'a>(
+        table_name: &str,
+        index: &str,
+        values: &[DataValue],
+        key: impl Fn(&DataValue) -> Vec<u8>,
+        btree: Option<&BTreeIndex>,
+        stored: impl Iterator<Item = (usize, &'a DataValue)>,
+        kept: impl Fn(usize) -> bool,
+    ) -> Result<(), ReefDBError> {
+        let duplicate = |value: &DataValue| ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, index, value));
+        let mut keys = HashMap::new();
+        for value in values.iter().filter(|value| **value != DataValue::Null) {
+            if keys.insert(key(value), value).is_some() {
+                return Err(duplicate(value));
+            }
+        }
+        match btree {
+            Some(btree) => {
+                for (key, value) in &keys {
+                    if btree.search(key.clone()).is_some_and(|ids| ids.iter().any(|id| kept(id - 1))) {
+                        return Err(duplicate(value));
+                    }
+                }
+            }
+            None => {
+                let clash = stored
+                    .filter(|(position, value)| **value != DataValue::Null && kept(*position))
+                    .find(|(_, value)| keys.contains_key(&key(value)));
+                if let Some((_, value)) = clash {
+                    return Err(duplicate(value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the writes of a committing transaction whose own copy of the database is
+    /// `state`. Tables it created, dropped or altered are taken whole from `state`, and only
+    /// their cached plans are dropped. The other tables get the row deltas
+    /// `stage_transaction` resolved, with their indexes kept in step row by row.
+    pub(crate) fn publish_transaction(&mut self, state: &ReefDB<S, FTS>, replaced: &HashSet<String>, deltas: RowDeltas) -> Result<(), ReefDBError> {
+        for table_name in replaced {
+            match state.storage.get_table_ref(table_name) {
+                Some((schema, rows)) => {
+                    self.storage.insert_table(table_name.clone(), schema.clone(), rows.clone());
+                    self.tables.insert_table(table_name.clone(), schema.clone(), rows.clone());
+                    self.rebuild_btree_indexes(table_name)?;
+                }
+                None => {
+                    self.storage.drop_table(table_name);
+                    self.tables.drop_table(table_name);
+                }
+            }
+            lock_recovering(&self.plan_cache).invalidate_table(table_name);
+        }
+        if !replaced.is_empty() {
+            self.storage.set_schema_version(state.storage.schema_version());
+        }
+
+        for (table_name, delta) in &deltas {
+            self.apply_row_delta(table_name, delta)?;
+        }
+        self.tables.recount_rows();
+        Ok(())
+    }
+
+    // Writes a staged row delta to storage and to the table mirror. Rewritten rows then move
+    // their index entries and appended rows get new ones, unless rows were deleted: that
+    // shifts row ids, so the indexes are rebuilt as handle_delete does.
+    fn apply_row_delta(&mut self, table_name: &str, delta: &RowDelta) -> Result<(), ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let schema = schema.clone();
+        let stored = rows.len();
+        let old: Vec<(usize, Vec<DataValue>)> = delta.rewrites.keys()
+            .map(|position| (*position, rows[*position].clone()))
+            .collect();
+        if let Some((_, rows)) = self.storage.get_table(table_name) {
+            delta.apply(rows);
+        }
+        // The mirror holds the same rows unless the database was written around it
+        match self.tables.get_table(table_name) {
+            Some((_, rows)) if rows.len() == stored => delta.apply(rows),
+            _ => {
+                let (_, rows) = self.get_table_schema(table_name)?;
+                let rows = rows.clone();
+                self.tables.insert_table(table_name.to_string(), schema.clone(), rows);
+            }
+        }
+
+        if !delta.deletes.is_empty() {
+            return self.rebuild_btree_indexes(table_name);
+        }
+        self.reindex_rows(table_name, &old)?;
+        for (offset, row) in delta.appended().enumerate() {
+            let row_id = stored + offset + 1;
+            for (i, col) in schema.iter().enumerate() {
+                if let Ok(IndexType::BTree(_)) = self.storage.get_index(table_name, &col.name) {
+                    let key = self.index_key(table_name, &col.name, &row[i]);
+                    self.storage.update_index(table_name, &col.name, vec![], key, row_id)?;
+                }
+            }
+            self.index_expressions(table_name, &schema, row, row_id)?;
+        }
+        Ok(())
+    }
+
+    /// Keyset pagination: returns up to `limit` rows of `table_name` in primary-key order,
+    /// starting right after the key `after` (from the start when `None`). With a B-Tree
+    /// index on the key, the page is read by seeking to the cursor in the index, so the
+    /// cost doesn't grow with how deep the page is. Without one, rows at or before the
+    /// cursor are filtered out in one pass and only the page itself gets sorted.
+    pub fn select_page(&self, table_name: &str, after: Option<&DataValue>, limit: usize) -> Result<KeysetPage, ReefDBError> {
+        let (schema, rows) = self.get_table_schema(table_name)?;
+        let pk_idx = schema.iter()
+            .position(|c| c.constraints.contains(&Constraint::PrimaryKey))
+            .ok_or_else(|| ReefDBError::Other(format!("Table {} has no primary key to paginate on", table_name)))?;
+        let expiry = self.expiry(schema);
+
+        let (rows, has_more): (Vec<Vec<DataValue>>, bool) = match self.ordered_key_index(table_name, &schema[pk_idx], after) {
+            Some(btree) => {
+                let start = after.map(|after| self.index_key(table_name, &schema[pk_idx].name, after));
+                let mut remaining = btree.ids_after(start)
+                    .filter_map(|id| rows.get(id.checked_sub(1)?))
+                    .filter(|row| !expiry.is_expired(row));
+                let page = remaining.by_ref().take(limit).cloned().collect();
+                (page, remaining.next().is_some())
+            }
+            None => {
+                let mut remaining: Vec<&Vec<DataValue>> = rows.iter()
+                    .filter(|row| after.is_none_or(|after| row[pk_idx] > *after) && !expiry.is_expired(row))
+                    .collect();
+                let has_more = remaining.len() > limit;
+                if has_more {
+                    remaining.select_nth_unstable_by(limit, |a, b| a[pk_idx].cmp(&b[pk_idx]));
+                    remaining.truncate(limit);
+                }
+                remaining.sort_by(|a, b| a[pk_idx].cmp(&b[pk_idx]));
+                (remaining.into_iter().cloned().collect(), has_more)
+            }
+        };
+
+        let next_cursor = if has_more {
+            rows.last().map(|row| row[pk_idx].clone())
+        } else {
+            None
+        };
+        Ok(KeysetPage { rows, next_cursor })
+    }
+
+    // The B-Tree index on `column`, if its keys sort the way the column's values do and
+    // `after` is keyed the same way: integer keys, or text keys of a text index
+    fn ordered_key_index(&self, table_name: &str, column: &ColumnDef, after: Option<&DataValue>) -> Option<&BTreeIndex> {
+        let Ok(IndexType::BTree(btree)) = self.storage.get_index(table_name, &column.name) else { return None };
+        let text_index = self.is_text_index(table_name, &column.name);
+        let ordered = match column.data_type {
+            DataType::Integer => !text_index && after.is_none_or(|after| matches!(after, DataValue::Integer(_))),
+            DataType::Text => text_index && column.collation() == Collation::Binary
+                && after.is_none_or(|after| matches!(after, DataValue::Text(_))),
+            _ => false,
+        };
+        ordered.then_some(btree)
+    }
+
+    /// Statistics collected by the last `ANALYZE` of `table_name`, if any
+    pub fn table_statistics(&self, table_name: &str) -> Option<TableStatistics> {
+        lock_recovering(&self.statistics).get(table_name).cloned()
+    }
+
+    /// Estimated fraction of `table_name` rows matching `column <op> value`.
+    /// Returns `None` until the table has been analyzed or for non-numeric columns.
+    pub fn estimate_selectivity(&self, table_name: &str, column: &str, op: &Op, value: &DataValue) -> Option<f64> {
+        lock_recovering(&self.statistics)
+            .get(table_name)?
+            .estimate_selectivity(column, op, value)
+    }
 }
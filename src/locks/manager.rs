@@ -1,84 +1,303 @@
 use std::collections::HashMap;
 use crate::error::ReefDBError;
+use crate::key_format::COMPOSITE_KEY_SEPARATOR;
 use super::types::LockType;
 
+#[derive(Debug, Clone, PartialEq)]
+enum LockResource {
+    Table(String),
+    // (table, primary key)
+    Row(String, String),
+}
+
+// A lock granted to a transaction and the weaker locks it replaced on the same resource,
+// so the grant can be undone when the transaction rolls back to a savepoint
+#[derive(Debug)]
+struct Grant {
+    resource: LockResource,
+    granted: LockType,
+    replaced: Vec<LockType>,
+}
+
+/// A lock a transaction holds, or is queued for, as reported by `SHOW LOCKS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockEntry {
+    pub transaction_id: u64,
+    /// Table name, or {table}#{primary_key} for a row lock
+    pub resource: String,
+    pub lock_type: LockType,
+    /// False while the transaction waits to upgrade its lock on the table
+    pub granted: bool,
+}
+
 #[derive(Debug)]
 pub struct LockManager {
     pub(crate) table_locks: HashMap<String, Vec<(u64, LockType)>>,
+    // Row locks keyed by (table, primary key)
+    pub(crate) row_locks: HashMap<(String, String), Vec<(u64, LockType)>>,
+    // Holders waiting to strengthen their lock on a table, in arrival order
+    pub(crate) upgrade_queue: HashMap<String, Vec<(u64, LockType)>>,
+    // Locks each transaction was granted, oldest first
+    grants: HashMap<u64, Vec<Grant>>,
 }
 
 impl LockManager {
     pub fn new() -> Self {
         LockManager {
             table_locks: HashMap::new(),
+            row_locks: HashMap::new(),
+            upgrade_queue: HashMap::new(),
+            grants: HashMap::new(),
         }
     }
 
+    /// Resource name used for a row in wait-for edges and error messages: {table}#{primary_key}
+    pub fn row_resource(table_name: &str, primary_key: &str) -> String {
+        format!("{}{}{}", table_name, COMPOSITE_KEY_SEPARATOR, primary_key)
+    }
+
     pub fn acquire_lock(&mut self, transaction_id: u64, table_name: &str, lock_type: LockType) -> Result<(), ReefDBError> {
-        let locks = self.table_locks.entry(table_name.to_string()).or_insert_with(Vec::new);
-        
-        // Check if this transaction already has a lock on the table
-        let existing_lock = locks.iter().find(|(id, _)| *id == transaction_id);
-        
-        if let Some((_, existing_lock_type)) = existing_lock {
-            // If requesting the same lock type, return success
-            if *existing_lock_type == lock_type {
-                return Ok(());
-            }
-            
-            // For lock upgrades (shared -> exclusive), we need to check for conflicts
-            if *existing_lock_type == LockType::Shared && lock_type == LockType::Exclusive {
-                // Check if any other transaction holds a shared lock
-                if locks.iter().any(|(id, lt)| *id != transaction_id && *lt == LockType::Shared) {
-                    return Err(ReefDBError::LockConflict(format!(
-                        "Lock conflict: Transaction {} cannot upgrade to {:?} lock on table {} due to existing shared locks",
-                        transaction_id, lock_type, table_name
-                    )));
+        let upgrading = self.is_upgrade(transaction_id, table_name, &lock_type);
+        let locks = self.table_locks.entry(table_name.to_string()).or_default();
+
+        // Nothing to do if a lock this transaction already holds grants the requested access
+        if locks.iter().any(|(id, held)| *id == transaction_id && held.covers(&lock_type)) {
+            return Ok(());
+        }
+
+        // Check for conflicts with other transactions, including their intention locks
+        if let Some((existing_id, existing_lock)) = locks.iter()
+            .find(|(id, held)| *id != transaction_id && !held.is_compatible_with(&lock_type))
+        {
+            let message = format!(
+                "Lock conflict: Transaction {} cannot acquire {:?} lock on table {} while transaction {} holds {:?}",
+                transaction_id, lock_type, table_name, existing_id, existing_lock
+            );
+            // An upgrade keeps its place in the queue until the other holders release
+            if upgrading {
+                let queue = self.upgrade_queue.entry(table_name.to_string()).or_default();
+                match queue.iter_mut().find(|(id, _)| *id == transaction_id) {
+                    Some((_, queued)) => *queued = lock_type,
+                    None => queue.push((transaction_id, lock_type)),
                 }
-                // Remove the shared lock and add the exclusive lock
-                locks.retain(|(id, _)| *id != transaction_id);
-                locks.push((transaction_id, lock_type));
-                return Ok(());
             }
-            
-            // For lock downgrades (exclusive -> shared), just add the shared lock
-            if *existing_lock_type == LockType::Exclusive && lock_type == LockType::Shared {
-                locks.push((transaction_id, lock_type));
-                return Ok(());
+            return Err(ReefDBError::LockConflict(message));
+        }
+
+        // New requests don't overtake a queued upgrade they would block
+        if !upgrading {
+            if let Some((waiter, _)) = self.upgrade_queue.get(table_name).into_iter().flatten()
+                .find(|(id, queued)| *id != transaction_id && !queued.is_compatible_with(&lock_type))
+            {
+                return Err(ReefDBError::LockConflict(format!(
+                    "Lock conflict: Transaction {} cannot acquire {:?} lock on table {} while transaction {} waits to upgrade",
+                    transaction_id, lock_type, table_name, waiter
+                )));
             }
         }
-        
-        // Check for conflicts with other transactions
-        for (existing_id, existing_lock) in locks.iter() {
-            if *existing_id != transaction_id {
-                match (existing_lock, &lock_type) {
-                    // Shared locks are compatible with each other
-                    (LockType::Shared, LockType::Shared) => continue,
-                    // All other combinations are incompatible
-                    _ => {
-                        return Err(ReefDBError::LockConflict(format!(
-                            "Lock conflict: Transaction {} cannot acquire {:?} lock on table {} held by transaction {}",
-                            transaction_id, lock_type, table_name, existing_id
-                        )));
-                    }
-                }
+
+        // Upgrades replace the weaker locks this transaction held on the table
+        let locks = self.table_locks.entry(table_name.to_string()).or_default();
+        let mut replaced = Vec::new();
+        locks.retain(|(id, held)| {
+            let keep = !(*id == transaction_id && lock_type.covers(held));
+            if !keep {
+                replaced.push(held.clone());
+            }
+            keep
+        });
+        locks.push((transaction_id, lock_type.clone()));
+        self.dequeue_upgrade(transaction_id, table_name);
+        self.grants.entry(transaction_id).or_default().push(Grant {
+            resource: LockResource::Table(table_name.to_string()),
+            granted: lock_type,
+            replaced,
+        });
+
+        Ok(())
+    }
+
+    /// Whether `transaction_id` already holds a lock on the table that doesn't cover `lock_type`,
+    /// e.g. a reader that now wants to write
+    pub fn is_upgrade(&self, transaction_id: u64, table_name: &str, lock_type: &LockType) -> bool {
+        let held: Vec<&LockType> = self.table_locks
+            .get(table_name)
+            .into_iter()
+            .flatten()
+            .filter(|(id, _)| *id == transaction_id)
+            .map(|(_, held)| held)
+            .collect();
+        !held.is_empty() && !held.iter().any(|held| held.covers(lock_type))
+    }
+
+    /// Transactions queued to upgrade their lock on `table_name`, oldest first
+    pub fn upgrade_waiters(&self, table_name: &str) -> Vec<u64> {
+        self.upgrade_queue
+            .get(table_name)
+            .map(|queue| queue.iter().map(|(id, _)| *id).collect())
+            .unwrap_or_default()
+    }
+
+    fn dequeue_upgrade(&mut self, transaction_id: u64, table_name: &str) {
+        if let Some(queue) = self.upgrade_queue.get_mut(table_name) {
+            queue.retain(|(id, _)| *id != transaction_id);
+            if queue.is_empty() {
+                self.upgrade_queue.remove(table_name);
             }
         }
-        
-        // Add the lock to the table's lock list
-        locks.push((transaction_id, lock_type));
-     
+    }
+
+    /// Locks a single row identified by `(table_name, primary_key)`. The row lock is paired
+    /// with an intention lock on the table, so it only conflicts with locks on the same row
+    /// or with another transaction's incompatible table lock.
+    pub fn acquire_row_lock(&mut self, transaction_id: u64, table_name: &str, primary_key: &str, lock_type: LockType) -> Result<(), ReefDBError> {
+        if let Some((holder, _)) = self.row_locks
+            .get(&(table_name.to_string(), primary_key.to_string()))
+            .into_iter()
+            .flatten()
+            .find(|(id, held)| *id != transaction_id && !held.is_compatible_with(&lock_type))
+        {
+            return Err(ReefDBError::LockConflict(format!(
+                "Lock conflict: Transaction {} cannot acquire {:?} lock on row {} held by transaction {}",
+                transaction_id, lock_type, Self::row_resource(table_name, primary_key), holder
+            )));
+        }
+        self.acquire_lock(transaction_id, table_name, lock_type.intention())?;
+
+        let locks = self.row_locks
+            .entry((table_name.to_string(), primary_key.to_string()))
+            .or_default();
+        let replaced = match locks.iter_mut().find(|(id, _)| *id == transaction_id) {
+            // Upgrade in place; a held exclusive lock already covers shared access
+            Some((_, held)) if held.covers(&lock_type) => return Ok(()),
+            Some((_, held)) => vec![std::mem::replace(held, lock_type.clone())],
+            None => {
+                locks.push((transaction_id, lock_type.clone()));
+                Vec::new()
+            }
+        };
+        self.grants.entry(transaction_id).or_default().push(Grant {
+            resource: LockResource::Row(table_name.to_string(), primary_key.to_string()),
+            granted: lock_type,
+            replaced,
+        });
         Ok(())
     }
 
+    /// Every held and queued lock, ordered by resource then transaction. Callers take it
+    /// while holding the manager's mutex, so it is one consistent view.
+    pub fn snapshot(&self) -> Vec<LockEntry> {
+        let entry = |resource: &str, (id, lock_type): &(u64, LockType), granted| LockEntry {
+            transaction_id: *id,
+            resource: resource.to_string(),
+            lock_type: lock_type.clone(),
+            granted,
+        };
+        let mut entries: Vec<LockEntry> = self.table_locks.iter()
+            .flat_map(|(table, locks)| locks.iter().map(move |lock| entry(table, lock, true)))
+            .chain(self.row_locks.iter().flat_map(|((table, key), locks)| {
+                let resource = Self::row_resource(table, key);
+                locks.iter().map(move |lock| entry(&resource, lock, true))
+            }))
+            .chain(self.upgrade_queue.iter()
+                .flat_map(|(table, queue)| queue.iter().map(move |lock| entry(table, lock, false))))
+            .collect();
+        entries.sort_by(|a, b| (&a.resource, a.transaction_id, !a.granted).cmp(&(&b.resource, b.transaction_id, !b.granted)));
+        entries
+    }
+
+    /// Number of locks granted to the transaction so far; a savepoint records it so that
+    /// rolling back can give up what was granted after it
+    pub fn grant_count(&self, transaction_id: u64) -> usize {
+        self.grants.get(&transaction_id).map_or(0, Vec::len)
+    }
+
+    /// Undoes the transaction's grants after the first `mark`, newest first: new locks are
+    /// released and upgraded ones go back to what they were. Returns the resources whose
+    /// locks changed, as named in wait-for edges.
+    pub fn release_locks_since(&mut self, transaction_id: u64, mark: usize) -> Vec<String> {
+        let undone = match self.grants.get_mut(&transaction_id) {
+            Some(grants) if grants.len() > mark => grants.split_off(mark),
+            _ => return Vec::new(),
+        };
+        let mut resources = Vec::new();
+        for grant in undone.into_iter().rev() {
+            let (locks, resource) = match &grant.resource {
+                LockResource::Table(table) => (self.table_locks.get_mut(table), table.clone()),
+                LockResource::Row(table, key) => (
+                    self.row_locks.get_mut(&(table.clone(), key.clone())),
+                    Self::row_resource(table, key),
+                ),
+            };
+            if let Some(locks) = locks {
+                if let Some(i) = locks.iter().position(|(id, held)| *id == transaction_id && *held == grant.granted) {
+                    locks.remove(i);
+                }
+                locks.extend(grant.replaced.into_iter().map(|held| (transaction_id, held)));
+            }
+            if !resources.contains(&resource) {
+                resources.push(resource);
+            }
+        }
+        for queue in self.upgrade_queue.values_mut() {
+            queue.retain(|(id, _)| *id != transaction_id);
+        }
+        self.table_locks.retain(|_, locks| !locks.is_empty());
+        self.row_locks.retain(|_, locks| !locks.is_empty());
+        self.upgrade_queue.retain(|_, queue| !queue.is_empty());
+        resources
+    }
+
+    /// Other transactions whose row lock, or whose table lock conflicting with the
+    /// required intention lock, prevents `transaction_id` from taking `lock_type` on the row
+    pub fn row_lock_conflicts(&self, transaction_id: u64, table_name: &str, primary_key: &str, lock_type: &LockType) -> Vec<u64> {
+        let intention = lock_type.intention();
+        let row_holders = self.row_locks
+            .get(&(table_name.to_string(), primary_key.to_string()))
+            .into_iter()
+            .flatten()
+            .filter(|(_, held)| !held.is_compatible_with(lock_type));
+        let table_holders = self.table_locks
+            .get(table_name)
+            .into_iter()
+            .flatten()
+            .filter(|(_, held)| !held.is_compatible_with(&intention));
+
+        let mut holders: Vec<u64> = row_holders
+            .chain(table_holders)
+            .filter(|(id, _)| *id != transaction_id)
+            .map(|(id, _)| *id)
+            .collect();
+        holders.sort();
+        holders.dedup();
+        holders
+    }
+
+    pub fn has_row_lock(&self, transaction_id: u64, table_name: &str, primary_key: &str) -> bool {
+        self.row_locks
+            .get(&(table_name.to_string(), primary_key.to_string()))
+            .is_some_and(|locks| locks.iter().any(|(id, _)| *id == transaction_id))
+    }
+
     pub fn release_transaction_locks(&mut self, transaction_id: u64) {
         for locks in self.table_locks.values_mut() {
             locks.retain(|(id, _)| *id != transaction_id);
         }
+        for locks in self.row_locks.values_mut() {
+            locks.retain(|(id, _)| *id != transaction_id);
+        }
+        for queue in self.upgrade_queue.values_mut() {
+            queue.retain(|(id, _)| *id != transaction_id);
+        }
+        self.grants.remove(&transaction_id);
         // Clean up empty lock lists
         self.table_locks.retain(|_, locks| !locks.is_empty());
+        self.row_locks.retain(|_, locks| !locks.is_empty());
+        self.upgrade_queue.retain(|_, queue| !queue.is_empty());
     }
 
+    /// Transactions holding any lock on `table_name`, including intention locks taken
+    /// on behalf of row locks
     pub fn get_lock_holders(&self, table_name: &str) -> Vec<u64> {
         self.table_locks
             .get(table_name)
@@ -86,10 +305,30 @@ impl LockManager {
             .unwrap_or_default()
     }
 
+    /// Transactions holding a lock on any row of `table_name`
+    pub fn get_row_lock_holders(&self, table_name: &str) -> Vec<u64> {
+        let mut holders: Vec<u64> = self.row_locks.iter()
+            .filter(|((table, _), _)| table == table_name)
+            .flat_map(|(_, locks)| locks.iter().map(|(id, _)| *id))
+            .collect();
+        holders.sort();
+        holders.dedup();
+        holders
+    }
+
+    /// Whether the transaction holds a shared or exclusive lock on the table itself;
+    /// intention locks only announce row locks and are reported by `has_intention_lock`
     pub fn has_lock(&self, transaction_id: u64, table_name: &str) -> bool {
         self.table_locks
             .get(table_name)
-            .map(|locks| locks.iter().any(|(id, _)| *id == transaction_id))
+            .map(|locks| locks.iter().any(|(id, held)| *id == transaction_id && !held.is_intention()))
+            .unwrap_or(false)
+    }
+
+    pub fn has_intention_lock(&self, transaction_id: u64, table_name: &str) -> bool {
+        self.table_locks
+            .get(table_name)
+            .map(|locks| locks.iter().any(|(id, held)| *id == transaction_id && held.is_intention()))
             .unwrap_or(false)
     }
 }
@@ -183,4 +422,156 @@ mod tests {
             Err(ReefDBError::LockConflict(_))
         ));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_row_locks() {
+        let mut manager = LockManager::new();
+
+        // Different rows of the same table don't conflict
+        assert!(manager.acquire_row_lock(1, "users", "1", LockType::Exclusive).is_ok());
+        assert!(manager.acquire_row_lock(2, "users", "2", LockType::Exclusive).is_ok());
+        assert!(matches!(
+            manager.acquire_row_lock(2, "users", "1", LockType::Shared),
+            Err(ReefDBError::LockConflict(_))
+        ));
+        assert_eq!(manager.row_lock_conflicts(2, "users", "1", &LockType::Shared), vec![1]);
+
+        // Table locks (DDL) conflict with row locks held by others
+        assert!(matches!(
+            manager.acquire_lock(3, "users", LockType::Exclusive),
+            Err(ReefDBError::LockConflict(_))
+        ));
+        assert!(manager.acquire_lock(3, "posts", LockType::Exclusive).is_ok());
+        assert!(matches!(
+            manager.acquire_row_lock(1, "posts", "7", LockType::Shared),
+            Err(ReefDBError::LockConflict(_))
+        ));
+
+        assert_eq!(manager.get_row_lock_holders("users"), vec![1, 2]);
+        manager.release_transaction_locks(1);
+        assert!(!manager.has_row_lock(1, "users", "1"));
+        assert!(manager.acquire_row_lock(2, "users", "1", LockType::Exclusive).is_ok());
+    }
+
+    #[test]
+    fn test_intention_lock_matrix() {
+        use LockType::*;
+        let types = [IntentionShared, IntentionExclusive, Shared, Exclusive];
+        // Rows/columns in the order above: held lock vs requested lock
+        let expected = [
+            [true, true, true, false],
+            [true, true, false, false],
+            [true, false, true, false],
+            [false, false, false, false],
+        ];
+        for (i, held) in types.iter().enumerate() {
+            for (j, requested) in types.iter().enumerate() {
+                let mut manager = LockManager::new();
+                manager.acquire_lock(1, "users", held.clone()).unwrap();
+                assert_eq!(
+                    manager.acquire_lock(2, "users", requested.clone()).is_ok(),
+                    expected[i][j],
+                    "held {:?}, requested {:?}", held, requested
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_row_locks_take_intention_locks() {
+        let mut manager = LockManager::new();
+
+        // A row write takes IX on the table and X on the row
+        manager.acquire_row_lock(1, "users", "1", LockType::Exclusive).unwrap();
+        assert!(manager.has_intention_lock(1, "users"));
+        assert!(!manager.has_lock(1, "users"));
+        assert!(manager.has_row_lock(1, "users", "1"));
+        assert_eq!(manager.get_lock_holders("users"), vec![1]);
+
+        // A row read takes IS, which coexists with IX
+        manager.acquire_row_lock(2, "users", "2", LockType::Shared).unwrap();
+        assert_eq!(manager.table_locks["users"], vec![(1, LockType::IntentionExclusive), (2, LockType::IntentionShared)]);
+
+        // Table S conflicts with IX but not IS; table X conflicts with both
+        assert!(manager.acquire_lock(3, "users", LockType::Shared).is_err());
+        assert!(manager.acquire_lock(3, "users", LockType::Exclusive).is_err());
+        manager.release_transaction_locks(1);
+        assert!(manager.acquire_lock(3, "users", LockType::Shared).is_ok());
+
+        // With a table S held by another transaction, row reads pass but row writes don't
+        assert!(manager.acquire_row_lock(2, "users", "3", LockType::Shared).is_ok());
+        assert!(matches!(
+            manager.acquire_row_lock(2, "users", "3", LockType::Exclusive),
+            Err(ReefDBError::LockConflict(_))
+        ));
+        assert_eq!(manager.row_lock_conflicts(2, "users", "3", &LockType::Exclusive), vec![3]);
+
+        // Upgrading IS to IX replaces the weaker intention lock
+        manager.release_transaction_locks(3);
+        manager.acquire_row_lock(2, "users", "3", LockType::Exclusive).unwrap();
+        assert_eq!(manager.table_locks["users"], vec![(2, LockType::IntentionExclusive)]);
+
+        // A transaction's own table X covers row access without extra locks
+        manager.acquire_lock(2, "users", LockType::Exclusive).unwrap();
+        assert!(manager.acquire_row_lock(2, "users", "4", LockType::Exclusive).is_ok());
+        assert_eq!(manager.table_locks["users"], vec![(2, LockType::Exclusive)]);
+    }
+
+    #[test]
+    fn test_shared_to_exclusive_upgrade() {
+        let mut manager = LockManager::new();
+
+        // A sole reader upgrades in place
+        manager.acquire_lock(1, "users", LockType::Shared).unwrap();
+        assert!(manager.is_upgrade(1, "users", &LockType::Exclusive));
+        manager.acquire_lock(1, "users", LockType::Exclusive).unwrap();
+        assert_eq!(manager.table_locks["users"], vec![(1, LockType::Exclusive)]);
+        manager.release_transaction_locks(1);
+
+        // With another reader the upgrade is queued
+        manager.acquire_lock(1, "users", LockType::Shared).unwrap();
+        manager.acquire_lock(2, "users", LockType::Shared).unwrap();
+        assert!(matches!(
+            manager.acquire_lock(1, "users", LockType::Exclusive),
+            Err(ReefDBError::LockConflict(_))
+        ));
+        assert_eq!(manager.upgrade_waiters("users"), vec![1]);
+
+        // New readers don't overtake the queued upgrade
+        assert!(matches!(
+            manager.acquire_lock(3, "users", LockType::Shared),
+            Err(ReefDBError::LockConflict(_))
+        ));
+
+        manager.release_transaction_locks(2);
+        manager.acquire_lock(1, "users", LockType::Exclusive).unwrap();
+        assert!(manager.upgrade_waiters("users").is_empty());
+        assert_eq!(manager.table_locks["users"], vec![(1, LockType::Exclusive)]);
+    }
+
+    #[test]
+    fn test_release_locks_since_undoes_later_grants() {
+        let mut manager = LockManager::new();
+        manager.acquire_lock(1, "users", LockType::Shared).unwrap();
+        let mark = manager.grant_count(1);
+
+        // An upgrade and a row lock after the mark
+        manager.acquire_lock(1, "users", LockType::Exclusive).unwrap();
+        manager.acquire_row_lock(1, "orders", "7", LockType::Exclusive).unwrap();
+        assert!(manager.acquire_lock(2, "users", LockType::Shared).is_err());
+
+        let mut released = manager.release_locks_since(1, mark);
+        released.sort();
+        assert_eq!(released, vec!["orders".to_string(), LockManager::row_resource("orders", "7"), "users".to_string()]);
+
+        // The upgrade went back to the shared lock held at the mark
+        assert_eq!(manager.table_locks.get("users"), Some(&vec![(1, LockType::Shared)]));
+        assert!(!manager.has_row_lock(1, "orders", "7"));
+        assert!(manager.get_lock_holders("orders").is_empty());
+        manager.acquire_lock(2, "users", LockType::Shared).unwrap();
+        manager.acquire_row_lock(2, "orders", "7", LockType::Exclusive).unwrap();
+
+        // Nothing to undo at or past the newest grant
+        assert!(manager.release_locks_since(1, mark).is_empty());
+    }
+}
@@ -1,5 +1,18 @@
 mod manager;
 mod types;
 
-pub use manager::LockManager;
-pub use types::LockType;
\ No newline at end of file
+pub use manager::{LockEntry, LockManager};
+pub use types::LockType;
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `mutex`, taking over the guard if another thread panicked while holding it.
+///
+/// Only used for state that stays consistent when an update is cut short: the lock manager,
+/// the deadlock detector and the savepoint manager only track bookkeeping for live
+/// transactions, the MVCC manager makes versions visible by marking a transaction committed
+/// last, and table statistics are a cache. The WAL and the database storage are not
+/// recovered, as a half-applied write to them would outlive the panic.
+pub(crate) fn lock_recovering<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
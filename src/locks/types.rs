@@ -2,4 +2,44 @@
 pub enum LockType {
     Shared,
     Exclusive,
-} 
\ No newline at end of file
+    /// Taken on a table by a transaction that reads individual rows of it
+    IntentionShared,
+    /// Taken on a table by a transaction that writes individual rows of it
+    IntentionExclusive,
+}
+
+impl LockType {
+    /// Whether a lock of this type can be held alongside `other` by a different transaction
+    pub fn is_compatible_with(&self, other: &LockType) -> bool {
+        use LockType::*;
+        match (self, other) {
+            (Exclusive, _) | (_, Exclusive) => false,
+            (IntentionShared, _) | (_, IntentionShared) => true,
+            (Shared, Shared) => true,
+            (IntentionExclusive, IntentionExclusive) => true,
+            (Shared, IntentionExclusive) | (IntentionExclusive, Shared) => false,
+        }
+    }
+
+    /// Whether holding this lock already grants everything `other` would
+    pub fn covers(&self, other: &LockType) -> bool {
+        use LockType::*;
+        match (self, other) {
+            (Exclusive, _) => true,
+            (Shared, IntentionShared) | (IntentionExclusive, IntentionShared) => true,
+            (held, requested) => held == requested,
+        }
+    }
+
+    /// Table-level intention lock that must accompany a row lock of this type
+    pub fn intention(&self) -> LockType {
+        match self {
+            LockType::Shared | LockType::IntentionShared => LockType::IntentionShared,
+            LockType::Exclusive | LockType::IntentionExclusive => LockType::IntentionExclusive,
+        }
+    }
+
+    pub fn is_intention(&self) -> bool {
+        matches!(self, LockType::IntentionShared | LockType::IntentionExclusive)
+    }
+}
@@ -0,0 +1,28 @@
+/// Receives execution events from `TransactionManager` and the SELECTs of `ReefDB`, for
+/// feeding counters or a metrics backend. Every method defaults to doing nothing, so a sink implements only what it
+/// records. Calls happen on the executing thread and should return quickly.
+pub trait MetricsSink: Send + Sync {
+    /// A statement read `rows` rows of `table`
+    fn rows_scanned(&self, _table: &str, _rows: usize) {}
+
+    /// A SELECT produced `rows` result rows
+    fn rows_returned(&self, _rows: usize) {}
+
+    /// `transaction_id` asked the lock manager for a lock on `resource`, a table or `table#key` row
+    fn lock_requested(&self, _transaction_id: u64, _resource: &str) {}
+
+    /// `transaction_id` was queued behind other holders to upgrade its lock on table `resource`.
+    /// Plain conflicts fail at once with `LockConflict` and are not reported here
+    fn lock_waited(&self, _transaction_id: u64, _resource: &str) {}
+
+    /// `transaction_id` was aborted to break a deadlock
+    fn deadlock(&self, _transaction_id: u64) {}
+
+    fn transaction_committed(&self, _transaction_id: u64) {}
+}
+
+/// Sink that discards everything; the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl MetricsSink for NoopMetrics {}
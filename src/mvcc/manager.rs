@@ -1,5 +1,7 @@
-use std::collections::HashSet;
-use std::time::SystemTime;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use crate::clock::{Clock, SystemClock};
 use crate::error::ReefDBError;
 use crate::sql::data_value::DataValue;
 use crate::key_format::KeyFormat;
@@ -7,10 +9,25 @@ use crate::transaction::IsolationLevel;
 use crate::mvcc::version::{Version, VersionStore};
 use crate::mvcc::transaction_state::TransactionState;
 
+/// How much committed history vacuum keeps beyond what live transactions can see,
+/// bounding how far back `snapshot_as_of` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionRetention {
+    /// Only what current readers and the oldest active snapshot can see
+    #[default]
+    Minimal,
+    /// The newest N committed versions of each row
+    Versions(usize),
+    /// Every version that was current at some point within this long ago
+    Duration(Duration),
+}
+
 pub struct MVCCManager {
     version_store: VersionStore,
     transaction_state: TransactionState,
     tables: HashSet<String>,
+    clock: Arc<dyn Clock>,
+    retention: VersionRetention,
 }
 
 impl MVCCManager {
@@ -19,23 +36,64 @@ impl MVCCManager {
             version_store: VersionStore::new(),
             transaction_state: TransactionState::new(),
             tables: HashSet::new(),
+            clock: Arc::new(SystemClock),
+            retention: VersionRetention::default(),
         }
     }
 
+    /// Clock transaction start and commit times are taken from
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// History vacuum keeps for time-travel reads
+    pub fn set_retention(&mut self, retention: VersionRetention) {
+        self.retention = retention;
+    }
+
     pub fn begin_transaction(&mut self, transaction_id: u64) {
-        self.transaction_state.begin_transaction(transaction_id);
+        self.transaction_state.begin_transaction(transaction_id, self.clock.now());
     }
 
     pub fn set_isolation_level(&mut self, transaction_id: u64, isolation_level: IsolationLevel) {
         self.transaction_state.set_isolation_level(transaction_id, isolation_level);
     }
 
+    /// First-committer-wins check for serializable transactions: fails with
+    /// `SerializationFailure` if another transaction committed a version of a key this
+    /// transaction wrote after it started
+    pub fn check_write_conflicts(&self, transaction_id: u64) -> Result<(), ReefDBError> {
+        if self.transaction_state.get_isolation_level(transaction_id) != Some(IsolationLevel::Serializable) {
+            return Ok(());
+        }
+        let (Some(keys), Some(start_time)) = (
+            self.transaction_state.get_transaction_writes(transaction_id),
+            self.transaction_state.get_transaction_start_time(transaction_id),
+        ) else {
+            return Ok(());
+        };
+        for key in keys {
+            let conflict = self.version_store.get_versions(key).and_then(|versions| versions.iter().find(|v| {
+                v.transaction_id != transaction_id
+                    && self.transaction_state.is_transaction_committed(v.transaction_id)
+                    && v.timestamp > start_time
+            }));
+            if let Some(version) = conflict {
+                return Err(ReefDBError::SerializationFailure(format!(
+                    "transaction {} committed a newer version of {}", version.transaction_id, key
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn commit(&mut self, transaction_id: u64) -> Result<(), ReefDBError> {
         println!("[DEBUG] Committing transaction {}", transaction_id);
+        self.check_write_conflicts(transaction_id)?;
         // Update the timestamp for all versions of this transaction
         if let Some(keys) = self.transaction_state.get_transaction_writes(transaction_id) {
             println!("[DEBUG] Found keys to update for transaction {}: {:?}", transaction_id, keys);
-            let commit_time = SystemTime::now();
+            let commit_time = self.clock.now();
             for key in keys {
                 if let Some(versions) = self.version_store.get_versions_mut(&key) {
                     println!("[DEBUG] Updating versions for key {}", key);
@@ -108,6 +166,19 @@ impl MVCCManager {
         Ok(())
     }
 
+    /// Keeps the row `key` held in storage before this transaction's first write to it, so
+    /// reads as of earlier times still find it once the write is published to storage.
+    /// `original` is empty for a row that didn't exist.
+    pub fn record_original(&mut self, key: &str, original: Vec<DataValue>) {
+        self.version_store.store_original(key, original);
+    }
+
+    /// Writes a tombstone: a version with no values, marking the row deleted for readers
+    /// of this transaction and, once it commits, everyone else
+    pub fn delete(&mut self, transaction_id: u64, key: String) -> Result<(), ReefDBError> {
+        self.write(transaction_id, key, Vec::new())
+    }
+
     pub fn read_committed(&self, transaction_id: u64, key: &str) -> Result<Option<Vec<DataValue>>, ReefDBError> {
         println!("[DEBUG] Reading committed value for key {} in transaction {}", key, transaction_id);
         if let Some(KeyFormat::Row { table_name, version: _, primary_key }) = KeyFormat::parse(key) {
@@ -133,6 +204,31 @@ impl MVCCManager {
         }
     }
 
+    /// Latest committed value of every row of `table_name`, keyed by base row key; a
+    /// deleted row maps to an empty tombstone. Readers take this snapshot and release the
+    /// manager lock before scanning.
+    pub fn committed_snapshot(&self, table_name: &str) -> HashMap<String, Vec<DataValue>> {
+        let committed_transactions = self.transaction_state.get_committed_transactions();
+        self.version_store.latest_committed_with_prefix(&KeyFormat::row_prefix(table_name), committed_transactions)
+    }
+
+    /// What `transaction_id` reads of `table_name`: the committed snapshot with the
+    /// transaction's own uncommitted writes laid over it, at every isolation level
+    pub fn transaction_snapshot(&self, transaction_id: u64, table_name: &str) -> HashMap<String, Vec<DataValue>> {
+        let mut rows = self.committed_snapshot(table_name);
+        let prefix = KeyFormat::row_prefix(table_name);
+        let own_keys = self.transaction_state.get_transaction_writes(transaction_id).into_iter().flatten()
+            .filter(|key| key.starts_with(&prefix));
+        for key in own_keys {
+            let own = self.version_store.get_versions(key)
+                .and_then(|versions| versions.iter().find(|v| v.transaction_id == transaction_id));
+            if let Some(version) = own {
+                rows.insert(key.clone(), version.value.clone());
+            }
+        }
+        rows
+    }
+
     pub fn read_uncommitted(&self, key: &str) -> Result<Option<Vec<DataValue>>, ReefDBError> {
         if let Some(KeyFormat::Row { table_name, version: _, primary_key }) = KeyFormat::parse(key) {
             let base_key = KeyFormat::row(&table_name, 0, &primary_key);
@@ -148,6 +244,46 @@ impl MVCCManager {
         }
     }
 
+    /// Committed value of every row of `table_name` at `at`, keyed by base row key, or
+    /// None if vacuum has removed versions that read would need. Rows without a version
+    /// committed by then are left out.
+    pub fn snapshot_as_of(&self, table_name: &str, at: SystemTime) -> Option<HashMap<String, Vec<DataValue>>> {
+        let committed_transactions = self.transaction_state.get_committed_transactions();
+        self.version_store.committed_as_of_with_prefix(&KeyFormat::row_prefix(table_name), committed_transactions, at)
+    }
+
+    /// Removes row versions that neither current reads, the snapshot of the oldest
+    /// active transaction nor the retention setting need. Returns the number of versions
+    /// removed.
+    pub fn vacuum(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut horizon = self.transaction_state.oldest_active_start_time().unwrap_or(now);
+        let keep = match self.retention {
+            VersionRetention::Minimal => 0,
+            VersionRetention::Versions(count) => count,
+            VersionRetention::Duration(duration) => {
+                horizon = horizon.min(now.checked_sub(duration).unwrap_or(SystemTime::UNIX_EPOCH));
+                0
+            }
+        };
+        let committed_transactions = self.transaction_state.get_committed_transactions();
+        self.version_store.vacuum(committed_transactions, horizon, keep)
+    }
+
+    /// Total number of row versions held, committed or not
+    pub fn version_count(&self) -> usize {
+        self.version_store.version_count()
+    }
+
+    /// Number of versions held for the row `key` refers to
+    pub fn key_version_count(&self, key: &str) -> usize {
+        let Some(KeyFormat::Row { table_name, version: _, primary_key }) = KeyFormat::parse(key) else {
+            return 0;
+        };
+        self.version_store.get_versions(&KeyFormat::row(&table_name, 0, &primary_key))
+            .map_or(0, Vec::len)
+    }
+
     pub fn is_active(&self, transaction_id: u64) -> bool {
         self.transaction_state.is_transaction_active(transaction_id)
     }
@@ -156,6 +292,16 @@ impl MVCCManager {
         Ok(self.tables.contains(table_name))
     }
 
+    /// Purges every version stored for the table's rows so a table recreated
+    /// under the same name starts without history. Returns the number of
+    /// row keys removed.
+    pub fn drop_table(&mut self, table_name: &str) -> usize {
+        let removed = self.version_store.remove_keys_with_prefix(&KeyFormat::row_prefix(table_name));
+        self.transaction_state.clear_table_writes(table_name);
+        self.tables.remove(table_name);
+        removed
+    }
+
     pub fn create_table(&mut self, transaction_id: u64, table_name: String) -> Result<(), ReefDBError> {
         if !self.transaction_state.is_transaction_active(transaction_id) {
             return Err(ReefDBError::Other("Transaction not found".to_string()));
@@ -168,8 +314,8 @@ impl MVCCManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use std::thread;
-    use std::time::Duration;
 
     #[test]
     fn test_mvcc_manager_new() {
@@ -315,4 +461,143 @@ mod tests {
         
         Ok(())
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_vacuum_keeps_versions_visible_to_active_snapshots() -> Result<(), ReefDBError> {
+        let mut manager = MVCCManager::new();
+        let key = KeyFormat::row("users", 0, "1");
+        let write_and_commit = |manager: &mut MVCCManager, tx: u64| -> Result<(), ReefDBError> {
+            manager.begin_transaction(tx);
+            manager.write(tx, key.clone(), vec![DataValue::Integer(tx as i64)])?;
+            manager.commit(tx)?;
+            thread::sleep(Duration::from_millis(2));
+            Ok(())
+        };
+
+        for tx in 1..=5 {
+            write_and_commit(&mut manager, tx)?;
+        }
+        // A long-running reader whose snapshot still sees the value from transaction 5
+        manager.begin_transaction(100);
+        thread::sleep(Duration::from_millis(2));
+        for tx in 6..=8 {
+            write_and_commit(&mut manager, tx)?;
+        }
+        // An in-flight writer's version must survive vacuuming too
+        manager.begin_transaction(9);
+        manager.write(9, key.clone(), vec![DataValue::Integer(9)])?;
+
+        assert_eq!(manager.vacuum(), 4);
+        let remaining: Vec<u64> = manager.version_store.get_versions(&key).unwrap()
+            .iter().map(|v| v.transaction_id).collect();
+        assert_eq!(remaining.len(), 5);
+        assert!(remaining.iter().all(|tx| *tx >= 5));
+        assert_eq!(manager.read_committed(100, &key)?, Some(vec![DataValue::Integer(8)]));
+
+        // Once the reader finishes only the latest committed version is needed
+        manager.rollback(100)?;
+        assert_eq!(manager.vacuum(), 3);
+        let mut remaining: Vec<u64> = manager.version_store.get_versions(&key).unwrap()
+            .iter().map(|v| v.transaction_id).collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![8, 9]);
+        assert_eq!(manager.read_committed(100, &key)?, Some(vec![DataValue::Integer(8)]));
+        assert_eq!(manager.read_uncommitted(&key)?, Some(vec![DataValue::Integer(9)]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_count_tracks_updates_and_vacuum() -> Result<(), ReefDBError> {
+        let mut manager = MVCCManager::new();
+        let alice = KeyFormat::row("users", 0, "1");
+        let bob = KeyFormat::row("users", 0, "2");
+        assert_eq!(manager.version_count(), 0);
+
+        for tx in 1..=4 {
+            manager.begin_transaction(tx);
+            manager.write(tx, alice.clone(), vec![DataValue::Integer(tx as i64)])?;
+            if tx % 2 == 0 {
+                manager.write(tx, bob.clone(), vec![DataValue::Integer(tx as i64)])?;
+            }
+            manager.commit(tx)?;
+            thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(manager.version_count(), 6);
+        assert_eq!(manager.key_version_count(&alice), 4);
+        // Any version number in the key addresses the same row
+        assert_eq!(manager.key_version_count(&KeyFormat::row("users", 7, "2")), 2);
+        assert_eq!(manager.key_version_count(&KeyFormat::row("users", 0, "3")), 0);
+
+        assert_eq!(manager.vacuum(), 4);
+        assert_eq!(manager.version_count(), 2);
+        assert_eq!(manager.key_version_count(&alice), 1);
+        assert_eq!(manager.key_version_count(&bob), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_as_of_within_and_past_retention() -> Result<(), ReefDBError> {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let clock = MockClock::new(start);
+        let mut manager = MVCCManager::new();
+        manager.set_clock(Arc::new(clock.clone()));
+        let key = KeyFormat::row("users", 0, "1");
+        // Values 1, 2 and 3 committed at start, start + 10s and start + 20s
+        for tx in 1..=3 {
+            manager.begin_transaction(tx);
+            manager.write(tx, key.clone(), vec![DataValue::Integer(tx as i64)])?;
+            manager.commit(tx)?;
+            clock.advance(Duration::from_secs(10));
+        }
+        let value_at = |manager: &MVCCManager, secs: u64| manager
+            .snapshot_as_of("users", start + Duration::from_secs(secs))
+            .map(|rows| rows.get(&key).cloned());
+
+        // 25 seconds of history reaches back to the first version
+        manager.set_retention(VersionRetention::Duration(Duration::from_secs(25)));
+        assert_eq!(manager.vacuum(), 0);
+        assert_eq!(value_at(&manager, 5), Some(Some(vec![DataValue::Integer(1)])));
+        assert_eq!(value_at(&manager, 15), Some(Some(vec![DataValue::Integer(2)])));
+
+        // 12 seconds keeps what was current at start + 18s, so the first version goes
+        manager.set_retention(VersionRetention::Duration(Duration::from_secs(12)));
+        assert_eq!(manager.vacuum(), 1);
+        assert_eq!(value_at(&manager, 15), Some(Some(vec![DataValue::Integer(2)])));
+        assert_eq!(value_at(&manager, 5), None);
+
+        // Keeping a single version leaves only the current value readable
+        manager.set_retention(VersionRetention::Versions(1));
+        assert_eq!(manager.vacuum(), 1);
+        assert_eq!(value_at(&manager, 15), None);
+        assert_eq!(value_at(&manager, 25), Some(Some(vec![DataValue::Integer(3)])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_committer_wins_under_serializable() -> Result<(), ReefDBError> {
+        let mut manager = MVCCManager::new();
+        let key = KeyFormat::row("users", 0, "1");
+        for tx in [1, 2, 3, 4] {
+            manager.begin_transaction(tx);
+        }
+        for tx in [1, 2] {
+            manager.set_isolation_level(tx, IsolationLevel::Serializable);
+        }
+
+        manager.write(1, key.clone(), vec![DataValue::Integer(1)])?;
+        manager.write(2, key.clone(), vec![DataValue::Integer(2)])?;
+        manager.commit(1)?;
+        assert!(matches!(manager.commit(2), Err(ReefDBError::SerializationFailure(_))));
+        assert!(manager.is_active(2));
+        manager.rollback(2)?;
+        assert_eq!(manager.read_committed(2, &key)?, Some(vec![DataValue::Integer(1)]));
+
+        // Read committed keeps last-writer-wins
+        manager.write(3, key.clone(), vec![DataValue::Integer(3)])?;
+        manager.write(4, key.clone(), vec![DataValue::Integer(4)])?;
+        manager.commit(3)?;
+        manager.commit(4)?;
+        assert_eq!(manager.read_committed(4, &key)?, Some(vec![DataValue::Integer(4)]));
+        Ok(())
+    }
+}
@@ -2,6 +2,6 @@ mod manager;
 mod version;
 mod transaction_state;
 
-pub use manager::MVCCManager;
+pub use manager::{MVCCManager, VersionRetention};
 pub use version::Version;
 pub use transaction_state::TransactionState; 
\ No newline at end of file
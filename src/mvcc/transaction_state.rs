@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 use crate::error::ReefDBError;
 use crate::transaction::IsolationLevel;
+use crate::key_format::KeyFormat;
 
 pub struct TransactionState {
     transaction_writes: HashMap<u64, HashSet<String>>,
@@ -33,10 +34,10 @@ impl TransactionState {
         self.transaction_isolation_levels.is_empty()
     }
 
-    pub fn begin_transaction(&mut self, transaction_id: u64) {
+    pub fn begin_transaction(&mut self, transaction_id: u64, started: SystemTime) {
         println!("[DEBUG] Beginning transaction: {}", transaction_id);
         self.active_transactions.insert(transaction_id);
-        self.transaction_timestamps.insert(transaction_id, SystemTime::now());
+        self.transaction_timestamps.insert(transaction_id, started);
         // Default to READ COMMITTED if not specified
         self.transaction_isolation_levels.insert(transaction_id, IsolationLevel::ReadCommitted);
         println!("[DEBUG] Transaction {} started. Active transactions: {:?}", transaction_id, self.active_transactions);
@@ -90,6 +91,14 @@ impl TransactionState {
             .insert(primary_key);
     }
 
+    pub fn clear_table_writes(&mut self, table_name: &str) {
+        self.table_writes.remove(table_name);
+        let prefix = KeyFormat::row_prefix(table_name);
+        for keys in self.transaction_writes.values_mut() {
+            keys.retain(|key| !key.starts_with(&prefix));
+        }
+    }
+
     pub fn is_transaction_active(&self, transaction_id: u64) -> bool {
         self.active_transactions.contains(&transaction_id)
     }
@@ -114,6 +123,14 @@ impl TransactionState {
     pub fn get_transaction_start_time(&self, transaction_id: u64) -> Option<SystemTime> {
         self.transaction_timestamps.get(&transaction_id).cloned()
     }
+
+    /// Start time of the longest-running active transaction
+    pub fn oldest_active_start_time(&self) -> Option<SystemTime> {
+        self.active_transactions.iter()
+            .filter_map(|id| self.transaction_timestamps.get(id))
+            .min()
+            .cloned()
+    }
 }
 
 #[cfg(test)]
@@ -125,7 +142,7 @@ mod tests {
         let mut state = TransactionState::new();
         
         // Begin transaction
-        state.begin_transaction(1);
+        state.begin_transaction(1, SystemTime::now());
         assert!(state.is_transaction_active(1));
         assert!(!state.is_transaction_committed(1));
         
@@ -139,7 +156,7 @@ mod tests {
     fn test_transaction_rollback() {
         let mut state = TransactionState::new();
         
-        state.begin_transaction(1);
+        state.begin_transaction(1, SystemTime::now());
         state.record_write(1, "key1".to_string(), "table1".to_string(), "pk1".to_string());
         
         state.rollback_transaction(1).unwrap();
@@ -13,6 +13,11 @@ pub struct Version {
 
 pub struct VersionStore {
     versions: HashMap<String, Vec<Version>>,
+    // Per key, the oldest commit time vacuum kept: history before it is gone
+    pruned: HashMap<String, SystemTime>,
+    // Per key, the stored row from before its first version, read as of earlier times;
+    // empty when the row didn't exist yet
+    originals: HashMap<String, Vec<DataValue>>,
 }
 
 impl Version {
@@ -66,6 +71,8 @@ impl VersionStore {
     pub fn new() -> Self {
         Self {
             versions: HashMap::new(),
+            pruned: HashMap::new(),
+            originals: HashMap::new(),
         }
     }
 
@@ -88,6 +95,14 @@ impl VersionStore {
         println!("[DEBUG] Current versions for key {}: {:?}", key, versions);
     }
 
+    /// Keeps `value` as what `key` held before any version was stored for it. Only the
+    /// first call for a key without versions counts.
+    pub fn store_original(&mut self, key: &str, value: Vec<DataValue>) {
+        if !self.versions.contains_key(key) && !self.pruned.contains_key(key) {
+            self.originals.entry(key.to_string()).or_insert(value);
+        }
+    }
+
     pub fn get_latest_committed_version(&self, key: &str, committed_transactions: &HashSet<u64>) -> Option<&Version> {
         self.versions.get(key).and_then(|versions| {
             versions
@@ -152,6 +167,85 @@ impl VersionStore {
         }
     }
 
+    /// Removes every key starting with `prefix` and returns how many were removed
+    pub fn remove_keys_with_prefix(&mut self, prefix: &str) -> usize {
+        let before = self.versions.len();
+        self.versions.retain(|key, _| !key.starts_with(prefix));
+        self.pruned.retain(|key, _| !key.starts_with(prefix));
+        self.originals.retain(|key, _| !key.starts_with(prefix));
+        before - self.versions.len()
+    }
+
+    /// Latest committed value of every key starting with `prefix`
+    pub fn latest_committed_with_prefix(&self, prefix: &str, committed_transactions: &HashSet<u64>) -> HashMap<String, Vec<DataValue>> {
+        self.versions.keys()
+            .filter(|key| key.starts_with(prefix))
+            .filter_map(|key| {
+                self.get_latest_committed_version(key, committed_transactions)
+                    .map(|version| (key.clone(), version.value.clone()))
+            })
+            .collect()
+    }
+
+    /// Value committed at `at` for every key starting with `prefix` that had one, falling
+    /// back to the value from before its first version, or None when vacuum already
+    /// removed history one of those keys would need
+    pub fn committed_as_of_with_prefix(&self, prefix: &str, committed_transactions: &HashSet<u64>, at: SystemTime) -> Option<HashMap<String, Vec<DataValue>>> {
+        if self.pruned.iter().any(|(key, kept_from)| key.starts_with(prefix) && *kept_from > at) {
+            return None;
+        }
+        Some(self.versions.keys()
+            .filter(|key| key.starts_with(prefix))
+            .filter_map(|key| {
+                self.get_latest_committed_version_before(key, committed_transactions, at)
+                    .map(|version| &version.value)
+                    .or_else(|| self.originals.get(key))
+                    .map(|value| (key.clone(), value.clone()))
+            })
+            .collect())
+    }
+
+    /// Drops committed versions shadowed by a newer committed version from at or before
+    /// `horizon`, which no snapshot taken at or after `horizon` can see, while keeping at
+    /// least the newest `keep` committed versions of each key. Uncommitted versions are
+    /// always kept. Returns the number of versions removed.
+    pub fn vacuum(&mut self, committed_transactions: &HashSet<u64>, horizon: SystemTime, keep: usize) -> usize {
+        let mut removed = 0;
+        for (key, versions) in self.versions.iter_mut() {
+            // Versions sharing the newest visible timestamp are all kept, since readers
+            // break that tie differently
+            let visible = versions.iter()
+                .filter(|v| committed_transactions.contains(&v.transaction_id) && v.timestamp <= horizon)
+                .map(|v| v.timestamp)
+                .max();
+            let Some(mut cutoff) = visible else { continue };
+            if keep > 0 {
+                let mut committed: Vec<SystemTime> = versions.iter()
+                    .filter(|v| committed_transactions.contains(&v.transaction_id))
+                    .map(|v| v.timestamp)
+                    .collect();
+                committed.sort_by(|a, b| b.cmp(a));
+                match committed.get(keep - 1) {
+                    Some(newest_kept) => cutoff = cutoff.min(*newest_kept),
+                    None => continue,
+                }
+            }
+            let before = versions.len();
+            versions.retain(|v| !committed_transactions.contains(&v.transaction_id) || v.timestamp >= cutoff);
+            if versions.len() < before {
+                removed += before - versions.len();
+                self.pruned.insert(key.clone(), cutoff);
+                self.originals.remove(key);
+            }
+        }
+        removed
+    }
+
+    /// Number of versions stored across all keys
+    pub fn version_count(&self) -> usize {
+        self.versions.values().map(Vec::len).sum()
+    }
+
     pub fn get_versions(&self, key: &str) -> Option<&Vec<Version>> {
         self.versions.get(key)
     }
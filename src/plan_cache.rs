@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use crate::sql::statements::{
+    delete::DeleteStatement, insert::InsertStatement, select::SelectStatement,
+    update::UpdateStatement, Statement,
+};
+
+/// Parsed statements kept by `PlanCache` unless configured otherwise
+pub const DEFAULT_PLAN_CACHE_CAPACITY: usize = 128;
+
+/// Hit and miss counts of a `PlanCache`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlanCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+#[derive(Debug, Clone)]
+struct CachedPlan {
+    statement: Statement,
+    tables: Vec<String>,
+    last_used: u64,
+}
+
+/// Least-recently-used cache of parsed DML and SELECT statements, keyed by their SQL with
+/// whitespace normalized. Entries are dropped when a table they read changes schema.
+#[derive(Debug, Clone)]
+pub struct PlanCache {
+    capacity: usize,
+    entries: HashMap<String, CachedPlan>,
+    clock: u64,
+    stats: PlanCacheStats,
+}
+
+impl Default for PlanCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_PLAN_CACHE_CAPACITY)
+    }
+}
+
+impl PlanCache {
+    pub fn new(capacity: usize) -> Self {
+        PlanCache {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            stats: PlanCacheStats::default(),
+        }
+    }
+
+    /// Collapses whitespace runs outside string literals and drops a trailing `;`, so
+    /// queries differing only in layout share an entry
+    pub fn normalize(sql: &str) -> String {
+        let mut normalized = String::with_capacity(sql.len());
+        let mut in_literal = false;
+        let mut pending_space = false;
+        for c in sql.trim().trim_end_matches(';').trim_end().chars() {
+            if !in_literal && c.is_whitespace() {
+                pending_space = true;
+                continue;
+            }
+            if pending_space {
+                normalized.push(' ');
+                pending_space = false;
+            }
+            if c == '\'' {
+                in_literal = !in_literal;
+            }
+            normalized.push(c);
+        }
+        normalized
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Statement> {
+        self.clock += 1;
+        match self.entries.get_mut(key) {
+            Some(plan) => {
+                plan.last_used = self.clock;
+                self.stats.hits += 1;
+                Some(plan.statement.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches `statement` if it is a query or DML statement, evicting the least recently
+    /// used entry when full
+    pub fn insert(&mut self, key: String, statement: &Statement) {
+        let Some(tables) = Self::cacheable_tables(statement) else { return };
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            if let Some(oldest) = self.entries.iter()
+                .min_by_key(|(_, plan)| plan.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(key, CachedPlan { statement: statement.clone(), tables, last_used: self.clock });
+    }
+
+    /// Drops every cached statement that reads or writes `table`
+    pub fn invalidate_table(&mut self, table: &str) {
+        self.entries.retain(|_, plan| !plan.tables.iter().any(|t| t == table));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> PlanCacheStats {
+        PlanCacheStats { entries: self.entries.len(), ..self.stats }
+    }
+
+    // Tables the statement depends on, or None for statements that aren't cached
+    fn cacheable_tables(statement: &Statement) -> Option<Vec<String>> {
+        match statement {
+            Statement::Select(select) => {
+                let mut tables = Vec::new();
+                Self::select_tables(select, &mut tables);
+                Some(tables)
+            }
+            Statement::Insert(InsertStatement::IntoTable(table, _) | InsertStatement::Upsert(table, _, _))
+            | Statement::Update(UpdateStatement::UpdateTable(table, _, _))
+            | Statement::Delete(DeleteStatement::FromTable(table, _)) => Some(vec![table.clone()]),
+            Statement::Merge(merge) => Some(vec![merge.target.name.clone(), merge.source.name.clone()]),
+            _ => None,
+        }
+    }
+
+    fn select_tables(select: &SelectStatement, tables: &mut Vec<String>) {
+        match select {
+            SelectStatement::FromTable(table_ref, _, _, joins, _) => {
+                tables.push(table_ref.name.clone());
+                tables.extend(joins.iter().map(|join| join.table_ref.name.clone()));
+            }
+            SelectStatement::With(ctes, query) => {
+                for cte in ctes {
+                    Self::select_tables(&cte.anchor, tables);
+                    if let Some(term) = &cte.recursive {
+                        Self::select_tables(&term.query, tables);
+                    }
+                }
+                Self::select_tables(query, tables);
+            }
+            SelectStatement::DistinctOn(_, query)
+            | SelectStatement::GroupBy(_, query)
+            | SelectStatement::GroupingSets(_, query)
+            | SelectStatement::AsOf(_, query)
+            | SelectStatement::Limit(_, query) => {
+                Self::select_tables(query, tables)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::parser::Parser;
+
+    #[test]
+    fn test_normalize_keeps_literals() {
+        assert_eq!(
+            PlanCache::normalize("  SELECT *\n   FROM  users WHERE name = 'a  b' ; "),
+            "SELECT * FROM users WHERE name = 'a  b'"
+        );
+    }
+
+    #[test]
+    fn test_lru_eviction_and_invalidation() {
+        let mut cache = PlanCache::new(2);
+        for sql in ["SELECT * FROM a", "SELECT * FROM b"] {
+            cache.insert(sql.to_string(), &Parser::parse_sql(sql).unwrap());
+        }
+        assert!(cache.get("SELECT * FROM a").is_some());
+
+        // b is the least recently used
+        cache.insert("SELECT * FROM c".to_string(), &Parser::parse_sql("SELECT * FROM c").unwrap());
+        assert!(cache.get("SELECT * FROM b").is_none());
+        assert!(cache.get("SELECT * FROM a").is_some());
+
+        cache.invalidate_table("a");
+        assert!(cache.get("SELECT * FROM a").is_none());
+        assert_eq!(cache.stats(), PlanCacheStats { hits: 2, misses: 2, entries: 1 });
+
+        // DDL is never cached
+        cache.insert("DROP TABLE c".to_string(), &Parser::parse_sql("DROP TABLE c").unwrap());
+        assert_eq!(cache.stats().entries, 1);
+    }
+}
@@ -7,6 +7,7 @@ use crate::sql::column_def::ColumnDef;
 use crate::sql::constraints::constraint::Constraint;
 use crate::sql::column::ColumnType;
 use crate::error::ReefDBError;
+use crate::row::{FromRow, Row};
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct ColumnInfo {
@@ -16,6 +17,16 @@ pub struct ColumnInfo {
     pub nullable: bool,
 }
 
+// Result type of a function column, falling back to Float as used by ranking functions
+fn function_result_type(name: &str) -> DataType {
+    match name.to_lowercase().as_str() {
+        "lower" | "upper" | "substr" | "concat" | "to_string" => DataType::Text,
+        "length" | "add" | "subtract" | "multiply" | "divide" => DataType::Integer,
+        "to_tsvector" => DataType::TSVector,
+        _ => DataType::Float,
+    }
+}
+
 impl ColumnInfo {
     pub fn from_schema_and_columns(
         schema: &[ColumnDef],
@@ -46,10 +57,44 @@ impl ColumnInfo {
                         })
                     },
                     ColumnType::Function(name, args) => {
-                        // For function-generated columns, assume they are nullable and use Float type for ranking functions
+                        // For function-generated columns, assume they are nullable
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: function_result_type(name),
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Case(case) => {
                         Ok(ColumnInfo {
                             name: col.name.clone(),
-                            data_type: DataType::Float,
+                            data_type: case.result_type(),
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Concat(_) => {
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: DataType::Text,
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Window(_) => {
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: DataType::Integer,
+                            table: None,
+                            nullable: false,
+                        })
+                    },
+                    ColumnType::Aggregate(aggregate) => {
+                        let argument = aggregate.column.as_ref()
+                            .and_then(|name| schema.iter().find(|c| &c.name == name));
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: aggregate.result_type(argument.map(|c| &c.data_type)),
                             table: None,
                             nullable: true,
                         })
@@ -144,10 +189,45 @@ impl ColumnInfo {
                         }
                     },
                     ColumnType::Function(name, args) => {
-                        // For function-generated columns, assume they are nullable and use Float type for ranking functions
+                        // For function-generated columns, assume they are nullable
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: function_result_type(name),
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Case(case) => {
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: case.result_type(),
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Concat(_) => {
                         Ok(ColumnInfo {
                             name: col.name.clone(),
-                            data_type: DataType::Float,
+                            data_type: DataType::Text,
+                            table: None,
+                            nullable: true,
+                        })
+                    },
+                    ColumnType::Window(_) => {
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: DataType::Integer,
+                            table: None,
+                            nullable: false,
+                        })
+                    },
+                    ColumnType::Aggregate(aggregate) => {
+                        let argument = aggregate.column.as_ref().and_then(|name| main_schema.iter()
+                            .chain(joined_tables.iter().flat_map(|(_, schema)| schema.iter()))
+                            .find(|c| &c.name == name));
+                        Ok(ColumnInfo {
+                            name: col.name.clone(),
+                            data_type: aggregate.result_type(argument.map(|c| &c.data_type)),
                             table: None,
                             nullable: true,
                         })
@@ -159,6 +239,14 @@ impl ColumnInfo {
     }
 }
 
+/// One page of a keyset-paginated scan, see `ReefDB::select_page`
+#[derive(PartialEq, Debug, Clone)]
+pub struct KeysetPage {
+    pub rows: Vec<Vec<DataValue>>,
+    /// Primary key of the last row, to pass as `after` for the next page; `None` on the last page
+    pub next_cursor: Option<DataValue>,
+}
+
 #[derive(PartialEq, Debug)]
 pub struct QueryResult {
     pub columns: Vec<ColumnInfo>,
@@ -208,6 +296,13 @@ impl QueryResult {
     pub fn get_column_by_name(&self, name: &str) -> Option<&ColumnInfo> {
         self.columns.iter().find(|col| col.name == name)
     }
+
+    /// Converts every row with `T::from_row`, in result order
+    pub fn map_rows<T: FromRow>(&self) -> Result<Vec<T>, ReefDBError> {
+        self.rows.iter()
+            .map(|(_, values)| T::from_row(&Row::new(&self.columns, values)))
+            .collect()
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -217,6 +312,8 @@ pub enum ReefDBResult {
     CreateTable,
     Update(usize),
     Delete(usize),
+    /// Target rows a MERGE updated and inserted
+    Merge { updated: usize, inserted: usize },
     AlterTable,
     DropTable,
     CreateIndex,
@@ -226,4 +323,6 @@ pub enum ReefDBResult {
     ReleaseSavepoint,
     BeginTransaction,
     Commit,
+    Analyze,
+    SetSchemaVersion,
 }
@@ -0,0 +1,159 @@
+use crate::{
+    error::ReefDBError,
+    result::ColumnInfo,
+    sql::{data_value::DataValue, decimal::Decimal, json::Json, uuid::Uuid},
+};
+
+/// Converts one column value into a Rust type. NULL only converts into `Option`.
+pub trait FromValue: Sized {
+    /// Describes the mismatch when the value can't be converted
+    fn from_value(value: &DataValue) -> Result<Self, String>;
+}
+
+/// Builds a value, typically a struct with a field per column, from one result row
+pub trait FromRow: Sized {
+    fn from_row(row: &Row<'_>) -> Result<Self, ReefDBError>;
+}
+
+/// A result row whose values are looked up by column name
+pub struct Row<'a> {
+    columns: &'a [ColumnInfo],
+    values: &'a [DataValue],
+}
+
+impl<'a> Row<'a> {
+    pub fn new(columns: &'a [ColumnInfo], values: &'a [DataValue]) -> Self {
+        Row { columns, values }
+    }
+
+    pub fn value(&self, column: &str) -> Result<&'a DataValue, ReefDBError> {
+        self.columns.iter()
+            .position(|info| info.name == column)
+            .and_then(|i| self.values.get(i))
+            .ok_or_else(|| ReefDBError::ColumnNotFound(column.to_string()))
+    }
+
+    pub fn get<T: FromValue>(&self, column: &str) -> Result<T, ReefDBError> {
+        T::from_value(self.value(column)?)
+            .map_err(|reason| ReefDBError::ValueConversion(column.to_string(), reason))
+    }
+}
+
+fn mismatch<T>(expected: &str, value: &DataValue) -> Result<T, String> {
+    match value {
+        DataValue::Null => Err(format!("expected {}, got NULL", expected)),
+        other => Err(format!("expected {}, got {}", expected, other)),
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Null => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl FromValue for DataValue {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Integer(i) => Ok(*i),
+            other => mismatch("an integer", other),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Float(f) => Ok(*f),
+            DataValue::Integer(i) => Ok(*i as f64),
+            DataValue::Decimal(d) => Ok(d.to_f64()),
+            other => mismatch("a number", other),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Boolean(b) => Ok(*b),
+            other => mismatch("a boolean", other),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Text(s) => Ok(s.to_string()),
+            DataValue::Date(s) | DataValue::Timestamp(s) => Ok(s.clone()),
+            other => mismatch("text", other),
+        }
+    }
+}
+
+impl FromValue for Decimal {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Decimal(d) => Ok(*d),
+            DataValue::Integer(i) => Ok(Decimal::from_i64(*i)),
+            other => mismatch("a decimal", other),
+        }
+    }
+}
+
+impl FromValue for Uuid {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Uuid(uuid) => Ok(*uuid),
+            other => mismatch("a UUID", other),
+        }
+    }
+}
+
+impl FromValue for Json {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Json(json) => Ok(json.clone()),
+            other => mismatch("JSON", other),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &DataValue) -> Result<Self, String> {
+        match value {
+            DataValue::Array(items) => items.iter().map(T::from_value).collect(),
+            other => mismatch("an array", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::data_type::DataType;
+
+    #[test]
+    fn test_values_convert_by_column_name() {
+        let column = |name: &str, data_type| ColumnInfo { name: name.to_string(), data_type, table: None, nullable: true };
+        let columns = vec![column("id", DataType::Integer), column("nick", DataType::Text)];
+        let values = vec![DataValue::Integer(7), DataValue::Null];
+        let row = Row::new(&columns, &values);
+
+        assert_eq!(row.get::<i64>("id").unwrap(), 7);
+        assert_eq!(row.get::<f64>("id").unwrap(), 7.0);
+        assert_eq!(row.get::<Option<String>>("nick").unwrap(), None);
+        assert!(matches!(row.get::<String>("nick"), Err(ReefDBError::ValueConversion(column, _)) if column == "nick"));
+        assert!(matches!(row.get::<bool>("id"), Err(ReefDBError::ValueConversion(_, _))));
+        assert_eq!(row.get::<i64>("email"), Err(ReefDBError::ColumnNotFound("email".to_string())));
+    }
+}
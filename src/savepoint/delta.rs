@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use crate::{
+    sql::{column_def::ColumnDef, data_value::DataValue},
+    storage::TableStorage,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TableDelta {
+    /// The table didn't exist in the older state
+    Absent,
+    /// The schema changed or the table was dropped, so the whole older table is kept
+    Table(Vec<ColumnDef>, Vec<Vec<DataValue>>),
+    /// Same schema: the older row count and the older rows that differ by position
+    Rows { len: usize, changed: Vec<(usize, Vec<DataValue>)> },
+}
+
+/// Changes that turn a table state back into an older one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct StateDelta {
+    tables: HashMap<String, TableDelta>,
+}
+
+impl StateDelta {
+    /// Delta that, applied to `newer`, yields `older`
+    pub(crate) fn between(newer: &TableStorage, older: &TableStorage) -> Self {
+        let mut tables = HashMap::new();
+        for (name, (older_schema, older_rows)) in &older.tables {
+            let delta = match newer.tables.get(name) {
+                Some((newer_schema, newer_rows)) if newer_schema == older_schema => {
+                    let changed: Vec<_> = older_rows.iter()
+                        .enumerate()
+                        .filter(|(idx, row)| newer_rows.get(*idx) != Some(row))
+                        .map(|(idx, row)| (idx, row.clone()))
+                        .collect();
+                    if changed.is_empty() && newer_rows.len() == older_rows.len() {
+                        continue;
+                    }
+                    TableDelta::Rows { len: older_rows.len(), changed }
+                }
+                _ => TableDelta::Table(older_schema.clone(), older_rows.clone()),
+            };
+            tables.insert(name.clone(), delta);
+        }
+        for name in newer.tables.keys() {
+            if !older.tables.contains_key(name) {
+                tables.insert(name.clone(), TableDelta::Absent);
+            }
+        }
+        StateDelta { tables }
+    }
+
+    pub(crate) fn apply(&self, state: &mut TableStorage) {
+        for (name, delta) in &self.tables {
+            match delta {
+                TableDelta::Absent => {
+                    state.tables.remove(name);
+                }
+                TableDelta::Table(schema, rows) => {
+                    state.tables.insert(name.clone(), (schema.clone(), rows.clone()));
+                }
+                TableDelta::Rows { len, changed } => {
+                    let Some((_, rows)) = state.tables.get_mut(name) else {
+                        continue;
+                    };
+                    rows.truncate(*len);
+                    // Indexes are ascending, so rows past the end are appended in order
+                    for (idx, row) in changed {
+                        if *idx < rows.len() {
+                            rows[*idx] = row.clone();
+                        } else {
+                            rows.push(row.clone());
+                        }
+                    }
+                }
+            }
+        }
+        state.recount_rows();
+    }
+
+    /// Number of rows the delta holds a copy of
+    #[cfg(test)]
+    pub(crate) fn stored_rows(&self) -> usize {
+        self.tables.values()
+            .map(|delta| match delta {
+                TableDelta::Absent => 0,
+                TableDelta::Table(_, rows) => rows.len(),
+                TableDelta::Rows { changed, .. } => changed.len(),
+            })
+            .sum()
+    }
+}
@@ -3,76 +3,167 @@ use crate::{
     error::ReefDBError,
     storage::TableStorage,
 };
-use super::savepoint::{Savepoint, SavepointState};
+use super::delta::StateDelta;
+
+struct DeltaSavepoint {
+    name: String,
+    handle: SavepointHandle,
+    // Turns the state of the next savepoint back into this one's; `None` for the newest
+    undo: Option<StateDelta>,
+    mark: SavepointMark,
+}
+
+/// How far a transaction had got when a savepoint was taken, so what came after can be undone
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct SavepointMark {
+    /// The transaction's `LockManager::grant_count`
+    pub locks: usize,
+    /// Row changes the transaction had logged
+    pub changes: usize,
+}
+
+// Only the newest savepoint's state is stored in full; older ones are rebuilt
+// from it by applying undo deltas newest to oldest
+struct TransactionSavepoints {
+    latest: TableStorage,
+    savepoints: Vec<DeltaSavepoint>,
+}
+
+impl TransactionSavepoints {
+    fn position(&self, name: &str) -> Result<usize, ReefDBError> {
+        self.savepoints.iter()
+            .position(|sp| sp.name == name)
+            .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))
+    }
+
+    fn name_of(&self, handle: SavepointHandle) -> Option<&str> {
+        self.savepoints.iter()
+            .find(|sp| sp.handle == handle)
+            .map(|sp| sp.name.as_str())
+    }
+
+    fn state_at(&self, idx: usize) -> TableStorage {
+        let mut state = self.latest.clone();
+        for savepoint in self.savepoints[idx..].iter().rev() {
+            if let Some(undo) = &savepoint.undo {
+                undo.apply(&mut state);
+            }
+        }
+        state
+    }
+}
+
+/// Refers to a savepoint without its name. Anonymous savepoints are listed under the
+/// handle's display form, e.g. `#3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SavepointHandle(u64);
+
+impl std::fmt::Display for SavepointHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
 
 pub struct SavepointManager {
-    savepoints: HashMap<u64, Vec<Savepoint>>,
+    savepoints: HashMap<u64, TransactionSavepoints>,
+    next_handle: u64,
 }
 
 impl SavepointManager {
     pub fn new() -> Self {
         SavepointManager {
             savepoints: HashMap::new(),
+            next_handle: 1,
         }
     }
 
-    pub(crate) fn create_savepoint(&mut self, transaction_id: u64, name: String, tables: TableStorage) -> Result<(), ReefDBError> {
+    /// Saves `tables` as the savepoint's state, along with `mark`, handed back by `mark`
+    pub(crate) fn create_savepoint(&mut self, transaction_id: u64, name: String, tables: TableStorage, mark: SavepointMark) -> Result<SavepointHandle, ReefDBError> {
+        let handle = SavepointHandle(self.next_handle);
+        let Some(transaction_savepoints) = self.savepoints.get_mut(&transaction_id) else {
+            self.next_handle += 1;
+            self.savepoints.insert(transaction_id, TransactionSavepoints {
+                latest: tables,
+                savepoints: vec![DeltaSavepoint { name, handle, undo: None, mark }],
+            });
+            return Ok(handle);
+        };
+
         // Check if savepoint with same name already exists
-        if let Some(transaction_savepoints) = self.savepoints.get(&transaction_id) {
-            if transaction_savepoints.iter().any(|sp| sp.name == name) {
-                return Err(ReefDBError::Other(format!("Savepoint {} already exists", name)));
-            }
+        if transaction_savepoints.savepoints.iter().any(|sp| sp.name == name) {
+            return Err(ReefDBError::Other(format!("Savepoint {} already exists", name)));
         }
 
-        let savepoint = Savepoint {
-            name,
-            table_snapshot: tables,
-            timestamp: std::time::SystemTime::now(),
-            state: SavepointState::Active,
-        };
+        if let Some(previous) = transaction_savepoints.savepoints.last_mut() {
+            previous.undo = Some(StateDelta::between(&tables, &transaction_savepoints.latest));
+        }
+        transaction_savepoints.latest = tables;
+        transaction_savepoints.savepoints.push(DeltaSavepoint { name, handle, undo: None, mark });
+        self.next_handle += 1;
+        Ok(handle)
+    }
 
-        self.savepoints
-            .entry(transaction_id)
-            .or_insert_with(Vec::new)
-            .push(savepoint);
+    /// Saves `tables` as a savepoint named after its handle, skipping handles whose name
+    /// the transaction already uses for a savepoint of its own
+    pub(crate) fn create_anonymous_savepoint(&mut self, transaction_id: u64, tables: TableStorage, mark: SavepointMark) -> Result<SavepointHandle, ReefDBError> {
+        let taken = self.list_savepoints(transaction_id);
+        while taken.contains(&SavepointHandle(self.next_handle).to_string()) {
+            self.next_handle += 1;
+        }
+        let name = SavepointHandle(self.next_handle).to_string();
+        self.create_savepoint(transaction_id, name, tables, mark)
+    }
 
-        Ok(())
+    /// Current name of the savepoint `handle` refers to, which fails once it was
+    /// released or rolled back past
+    pub(crate) fn savepoint_name(&self, transaction_id: u64, handle: SavepointHandle) -> Result<String, ReefDBError> {
+        self.savepoints.get(&transaction_id)
+            .and_then(|transaction_savepoints| transaction_savepoints.name_of(handle))
+            .map(str::to_string)
+            .ok_or_else(|| ReefDBError::SavepointNotFound(handle.to_string()))
     }
 
     pub(crate) fn rollback_to_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<TableStorage, ReefDBError> {
         let transaction_savepoints = self.savepoints.get_mut(&transaction_id)
             .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
+        let savepoint_index = transaction_savepoints.position(name)?;
 
-        let savepoint_index = transaction_savepoints.iter()
-            .position(|sp| sp.name == name)
-            .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
-
-        if transaction_savepoints[savepoint_index].state != SavepointState::Active {
-            return Err(ReefDBError::SavepointNotActive(name.to_string()));
-        }
-
-        // Get the savepoint state
-        let snapshot = transaction_savepoints[savepoint_index].table_snapshot.clone();
+        let snapshot = transaction_savepoints.state_at(savepoint_index);
 
-        // Remove all savepoints after this one
-        transaction_savepoints.truncate(savepoint_index + 1);
+        // Remove all savepoints after this one, leaving it the newest
+        transaction_savepoints.savepoints.truncate(savepoint_index + 1);
+        transaction_savepoints.savepoints[savepoint_index].undo = None;
+        transaction_savepoints.latest = snapshot.clone();
 
         Ok(snapshot)
     }
 
-    pub(crate) fn release_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<(), ReefDBError> {
-        let transaction_savepoints = self.savepoints.get_mut(&transaction_id)
+    pub(crate) fn mark(&self, transaction_id: u64, name: &str) -> Result<SavepointMark, ReefDBError> {
+        let transaction_savepoints = self.savepoints.get(&transaction_id)
             .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
+        Ok(transaction_savepoints.savepoints[transaction_savepoints.position(name)?].mark)
+    }
 
-        let savepoint_index = transaction_savepoints.iter()
-            .position(|sp| sp.name == name)
+    pub(crate) fn release_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<(), ReefDBError> {
+        let transaction_savepoints = self.savepoints.get_mut(&transaction_id)
             .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
+        let savepoint_index = transaction_savepoints.position(name)?;
 
-        if transaction_savepoints[savepoint_index].state != SavepointState::Active {
-            return Err(ReefDBError::SavepointNotActive(name.to_string()));
+        if savepoint_index > 0 {
+            // The previous savepoint must now undo straight from the state after the released one
+            let older = transaction_savepoints.state_at(savepoint_index - 1);
+            if savepoint_index + 1 == transaction_savepoints.savepoints.len() {
+                transaction_savepoints.savepoints[savepoint_index - 1].undo = None;
+                transaction_savepoints.latest = older;
+            } else {
+                let newer = transaction_savepoints.state_at(savepoint_index + 1);
+                transaction_savepoints.savepoints[savepoint_index - 1].undo = Some(StateDelta::between(&newer, &older));
+            }
+        }
+        transaction_savepoints.savepoints.remove(savepoint_index);
+        if transaction_savepoints.savepoints.is_empty() {
+            self.savepoints.remove(&transaction_id);
         }
-
-        transaction_savepoints.remove(savepoint_index);
         Ok(())
     }
 
@@ -80,32 +171,51 @@ impl SavepointManager {
         self.savepoints.remove(&transaction_id);
     }
 
-    pub(crate) fn get_active_savepoints(&self, transaction_id: u64) -> Vec<String> {
+    /// Names of the transaction's savepoints, oldest first
+    pub fn list_savepoints(&self, transaction_id: u64) -> Vec<String> {
         self.savepoints.get(&transaction_id)
-            .map(|savepoints| {
-                savepoints.iter()
-                    .filter(|sp| sp.state == SavepointState::Active)
+            .map(|transaction_savepoints| {
+                transaction_savepoints.savepoints.iter()
                     .map(|sp| sp.name.clone())
                     .collect()
             })
             .unwrap_or_default()
     }
+
+    /// Number of rows held for the transaction's savepoints: one full copy of the
+    /// newest savepoint plus the rows each older one changed
+    #[cfg(test)]
+    pub(crate) fn stored_rows(&self, transaction_id: u64) -> usize {
+        self.savepoints.get(&transaction_id)
+            .map(|transaction_savepoints| {
+                let latest: usize = transaction_savepoints.latest.tables.values()
+                    .map(|(_, rows)| rows.len())
+                    .sum();
+                let deltas: usize = transaction_savepoints.savepoints.iter()
+                    .filter_map(|sp| sp.undo.as_ref())
+                    .map(StateDelta::stored_rows)
+                    .sum();
+                latest + deltas
+            })
+            .unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sql::{column_def::ColumnDef, data_type::DataType, data_value::DataValue};
 
     #[test]
     fn test_create_savepoint() {
         let mut manager = SavepointManager::new();
         let tables = TableStorage::new();
         
-        assert!(manager.create_savepoint(1, "sp1".to_string(), tables.clone()).is_ok());
-        assert_eq!(manager.get_active_savepoints(1), vec!["sp1"]);
+        assert!(manager.create_savepoint(1, "sp1".to_string(), tables.clone(), SavepointMark::default()).is_ok());
+        assert_eq!(manager.list_savepoints(1), vec!["sp1"]);
         
         // Test duplicate savepoint
-        assert!(manager.create_savepoint(1, "sp1".to_string(), tables).is_err());
+        assert!(manager.create_savepoint(1, "sp1".to_string(), tables, SavepointMark::default()).is_err());
     }
 
     #[test]
@@ -113,18 +223,18 @@ mod tests {
         let mut manager = SavepointManager::new();
         let mut tables = TableStorage::new();
         
-        manager.create_savepoint(1, "sp1".to_string(), tables.clone()).unwrap();
+        manager.create_savepoint(1, "sp1".to_string(), tables.clone(), SavepointMark::default()).unwrap();
         
         // Modify tables after savepoint
         tables = TableStorage::new(); // Simulating modification
         
-        manager.create_savepoint(1, "sp2".to_string(), tables.clone()).unwrap();
+        manager.create_savepoint(1, "sp2".to_string(), tables.clone(), SavepointMark::default()).unwrap();
         
         // Rollback to first savepoint
         let rolled_back_tables = manager.rollback_to_savepoint(1, "sp1").unwrap();
         
         // Check that sp2 is no longer active
-        assert_eq!(manager.get_active_savepoints(1), vec!["sp1"]);
+        assert_eq!(manager.list_savepoints(1), vec!["sp1"]);
     }
 
     #[test]
@@ -132,11 +242,11 @@ mod tests {
         let mut manager = SavepointManager::new();
         let tables = TableStorage::new();
         
-        manager.create_savepoint(1, "sp1".to_string(), tables.clone()).unwrap();
-        manager.create_savepoint(1, "sp2".to_string(), tables).unwrap();
+        manager.create_savepoint(1, "sp1".to_string(), tables.clone(), SavepointMark::default()).unwrap();
+        manager.create_savepoint(1, "sp2".to_string(), tables, SavepointMark::default()).unwrap();
         
         assert!(manager.release_savepoint(1, "sp1").is_ok());
-        assert_eq!(manager.get_active_savepoints(1), vec!["sp2"]);
+        assert_eq!(manager.list_savepoints(1), vec!["sp2"]);
     }
 
     #[test]
@@ -144,10 +254,127 @@ mod tests {
         let mut manager = SavepointManager::new();
         let tables = TableStorage::new();
         
-        manager.create_savepoint(1, "sp1".to_string(), tables.clone()).unwrap();
-        manager.create_savepoint(1, "sp2".to_string(), tables).unwrap();
+        manager.create_savepoint(1, "sp1".to_string(), tables.clone(), SavepointMark::default()).unwrap();
+        manager.create_savepoint(1, "sp2".to_string(), tables, SavepointMark::default()).unwrap();
         
         manager.clear_transaction_savepoints(1);
-        assert!(manager.get_active_savepoints(1).is_empty());
+        assert!(manager.list_savepoints(1).is_empty());
+    }
+
+    #[test]
+    fn test_anonymous_savepoint_names_avoid_named_ones() {
+        let mut manager = SavepointManager::new();
+        let tables = TableStorage::new();
+
+        manager.create_savepoint(1, "#2".to_string(), tables.clone(), SavepointMark::default()).unwrap();
+        let first = manager.create_anonymous_savepoint(1, tables.clone(), SavepointMark::default()).unwrap();
+        let second = manager.create_anonymous_savepoint(1, tables, SavepointMark::default()).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(manager.list_savepoints(1), vec!["#2", "#3", "#4"]);
+        assert_eq!(manager.savepoint_name(1, second).unwrap(), "#4");
+
+        manager.release_savepoint(1, "#4").unwrap();
+        assert_eq!(manager.savepoint_name(1, second), Err(ReefDBError::SavepointNotFound("#4".to_string())));
+        // Handles belong to the transaction that created them
+        assert!(manager.savepoint_name(2, first).is_err());
+    }
+
+    fn users_state(rows: Vec<Vec<DataValue>>) -> TableStorage {
+        let mut state = TableStorage::new();
+        state.tables.insert("users".to_string(), (
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![]),
+                ColumnDef::new("name", DataType::Text, vec![]),
+            ],
+            rows,
+        ));
+        state.recount_rows();
+        state
+    }
+
+    fn user(id: i64, name: &str) -> Vec<DataValue> {
+        vec![DataValue::Integer(id), DataValue::Text(name.into())]
+    }
+
+    #[test]
+    fn test_delta_rollback_matches_full_snapshots() {
+        let mut manager = SavepointManager::new();
+
+        // The same states the old implementation would have cloned in full
+        let mut snapshots = Vec::new();
+        let mut state = users_state((0..10).map(|id| user(id, "initial")).collect());
+        snapshots.push(state.clone());
+
+        state.tables.get_mut("users").unwrap().1[3] = user(3, "updated");
+        snapshots.push(state.clone());
+
+        state.tables.get_mut("users").unwrap().1.remove(5);
+        snapshots.push(state.clone());
+
+        state.tables.get_mut("users").unwrap().1.push(user(42, "appended"));
+        state.tables.insert("orders".to_string(), (vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![vec![DataValue::Integer(1)]]));
+        snapshots.push(state.clone());
+
+        state.tables.remove("orders");
+        let (schema, rows) = state.tables.get_mut("users").unwrap();
+        schema.push(ColumnDef::new("age", DataType::Integer, vec![]));
+        rows.truncate(2);
+        for row in rows.iter_mut() {
+            row.push(DataValue::Integer(30));
+        }
+        snapshots.push(state.clone());
+
+        for (idx, snapshot) in snapshots.iter().enumerate() {
+            let mut snapshot = snapshot.clone();
+            snapshot.recount_rows();
+            manager.create_savepoint(1, format!("sp{}", idx), snapshot, SavepointMark::default()).unwrap();
+        }
+
+        // Releasing a savepoint in the middle must not disturb its neighbours
+        manager.release_savepoint(1, "sp2").unwrap();
+        assert_eq!(manager.list_savepoints(1), vec!["sp0", "sp1", "sp3", "sp4"]);
+
+        for (name, expected) in [("sp4", 4), ("sp3", 3), ("sp1", 1), ("sp0", 0)] {
+            let restored = manager.rollback_to_savepoint(1, name).unwrap();
+            assert_eq!(restored.tables, snapshots[expected].tables, "rolling back to {}", name);
+            assert_eq!(
+                restored.row_count("users"),
+                Some(snapshots[expected].tables["users"].1.len())
+            );
+        }
+
+        // Rolling back to the same savepoint again still works
+        assert_eq!(manager.rollback_to_savepoint(1, "sp0").unwrap().tables, snapshots[0].tables);
+    }
+
+    #[test]
+    fn test_release_newest_savepoint() {
+        let mut manager = SavepointManager::new();
+        let first = users_state(vec![user(1, "a")]);
+        let second = users_state(vec![user(1, "b")]);
+        manager.create_savepoint(1, "sp1".to_string(), first.clone(), SavepointMark::default()).unwrap();
+        manager.create_savepoint(1, "sp2".to_string(), second, SavepointMark::default()).unwrap();
+
+        manager.release_savepoint(1, "sp2").unwrap();
+        manager.create_savepoint(1, "sp3".to_string(), users_state(vec![user(1, "c")]), SavepointMark::default()).unwrap();
+        assert_eq!(manager.rollback_to_savepoint(1, "sp1").unwrap().tables, first.tables);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_savepoints_store_only_changed_rows() {
+        let mut manager = SavepointManager::new();
+        let mut state = users_state((0..1000).map(|id| user(id, "initial")).collect());
+
+        for idx in 0..10 {
+            manager.create_savepoint(1, format!("sp{}", idx), state.clone(), SavepointMark::default()).unwrap();
+            state.tables.get_mut("users").unwrap().1[idx] = user(idx as i64, "changed");
+        }
+
+        // Full snapshots would hold 10 000 rows
+        assert_eq!(manager.stored_rows(1), 1000 + 9);
+        assert_eq!(
+            manager.rollback_to_savepoint(1, "sp0").unwrap().tables["users"].1,
+            (0..1000).map(|id| user(id, "initial")).collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,5 +1,5 @@
-mod savepoint;
 mod manager;
+mod delta;
 
-pub use manager::SavepointManager;
-pub use savepoint::{Savepoint, SavepointState}; 
\ No newline at end of file
+pub use manager::{SavepointHandle, SavepointManager};
+pub(crate) use manager::SavepointMark;
@@ -0,0 +1,185 @@
+use nom::{
+    IResult,
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::multispace0,
+    combinator::{map, value, verify},
+    sequence::tuple,
+};
+
+use crate::sql::{
+    column_def::column_name,
+    data_type::DataType,
+    data_value::DataValue,
+    decimal::Decimal,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateKind {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// `COUNT(*)` or `<function>(column)` computed over the rows the query selects, per group with GROUP BY.
+/// NULL inputs are skipped; over no rows COUNT gives 0 and the others NULL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateFunction {
+    pub kind: AggregateKind,
+    /// Argument column, `None` for `COUNT(*)`
+    pub column: Option<String>,
+}
+
+impl AggregateFunction {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, kind) = alt((
+            value(AggregateKind::Count, tag_no_case("COUNT")),
+            value(AggregateKind::Sum, tag_no_case("SUM")),
+            value(AggregateKind::Avg, tag_no_case("AVG")),
+            value(AggregateKind::Min, tag_no_case("MIN")),
+            value(AggregateKind::Max, tag_no_case("MAX")),
+        ))(input)?;
+        let (input, (_, _, _, column, _, _)) = tuple((
+            multispace0,
+            tag("("),
+            multispace0,
+            verify(
+                alt((
+                    value(None, tag("*")),
+                    map(column_name, |name: &str| Some(name.to_string())),
+                )),
+                |column: &Option<String>| column.is_some() || kind == AggregateKind::Count,
+            ),
+            multispace0,
+            tag(")"),
+        ))(input)?;
+        Ok((input, AggregateFunction { kind, column }))
+    }
+
+    /// Default output column name
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            AggregateKind::Count => "count",
+            AggregateKind::Sum => "sum",
+            AggregateKind::Avg => "avg",
+            AggregateKind::Min => "min",
+            AggregateKind::Max => "max",
+        }
+    }
+
+    /// Type of the result given the argument column's type
+    pub fn result_type(&self, argument: Option<&DataType>) -> DataType {
+        match (self.kind, argument) {
+            (AggregateKind::Count, _) => DataType::Integer,
+            (AggregateKind::Sum, Some(DataType::Integer)) => DataType::Integer,
+            (AggregateKind::Sum | AggregateKind::Avg, Some(decimal @ DataType::Decimal { .. })) => decimal.clone(),
+            (AggregateKind::Sum | AggregateKind::Avg, _) => DataType::Float,
+            (AggregateKind::Min | AggregateKind::Max, argument) => argument.cloned().unwrap_or(DataType::Integer),
+        }
+    }
+
+    /// Aggregates the values at `column` of `rows`; `column` is `None` only for `COUNT(*)`
+    pub fn evaluate(&self, rows: &[&[DataValue]], column: Option<usize>) -> Result<DataValue, String> {
+        let Some(column) = column else {
+            return Ok(DataValue::Integer(rows.len() as i64));
+        };
+        let values: Vec<&DataValue> = rows.iter()
+            .map(|row| &row[column])
+            .filter(|value| !matches!(value, DataValue::Null))
+            .collect();
+
+        match self.kind {
+            AggregateKind::Count => Ok(DataValue::Integer(values.len() as i64)),
+            AggregateKind::Min => Ok(Self::extreme(&values, std::cmp::Ordering::Less)),
+            AggregateKind::Max => Ok(Self::extreme(&values, std::cmp::Ordering::Greater)),
+            AggregateKind::Sum | AggregateKind::Avg if values.is_empty() => Ok(DataValue::Null),
+            AggregateKind::Sum | AggregateKind::Avg => self.numeric(&values),
+        }
+    }
+
+    fn extreme(values: &[&DataValue], wanted: std::cmp::Ordering) -> DataValue {
+        values.iter()
+            .copied()
+            .reduce(|best, value| if value.partial_cmp(best) == Some(wanted) { value } else { best })
+            .cloned()
+            .unwrap_or(DataValue::Null)
+    }
+
+    // SUM and AVG: exact over integers and decimals, floating point once a float is involved
+    fn numeric(&self, values: &[&DataValue]) -> Result<DataValue, String> {
+        let not_numeric = |value: &DataValue| format!("{} expects numeric values, got {}", self.name().to_uppercase(), value);
+        if values.iter().any(|value| matches!(value, DataValue::Decimal(_))) {
+            let decimals = values.iter()
+                .map(|value| match value {
+                    DataValue::Decimal(decimal) => Ok(*decimal),
+                    DataValue::Integer(i) => Ok(Decimal::from_i64(*i)),
+                    other => Err(not_numeric(other)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let result = match self.kind {
+                AggregateKind::Avg => Decimal::avg(&decimals),
+                _ => Decimal::sum(&decimals),
+            };
+            return result.map(DataValue::Decimal).ok_or_else(|| "Decimal overflow".to_string());
+        }
+
+        let all_integers = values.iter().all(|value| matches!(value, DataValue::Integer(_)));
+        if all_integers && self.kind == AggregateKind::Sum {
+            return values.iter()
+                .try_fold(0i64, |total, value| match value {
+                    DataValue::Integer(i) => total.checked_add(*i),
+                    _ => None,
+                })
+                .map(DataValue::Integer)
+                .ok_or_else(|| "Integer overflow in SUM".to_string());
+        }
+
+        let floats = values.iter()
+            .map(|value| match value {
+                DataValue::Integer(i) => Ok(*i as f64),
+                DataValue::Float(f) => Ok(*f),
+                other => Err(not_numeric(other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let sum: f64 = floats.iter().sum();
+        Ok(DataValue::Float(match self.kind {
+            AggregateKind::Avg => sum / floats.len() as f64,
+            _ => sum,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(input: &str) -> AggregateFunction {
+        AggregateFunction::parse(input).unwrap().1
+    }
+
+    #[test]
+    fn test_parse_aggregates() {
+        assert_eq!(aggregate("COUNT(*)"), AggregateFunction { kind: AggregateKind::Count, column: None });
+        assert_eq!(aggregate("max( age )"), AggregateFunction { kind: AggregateKind::Max, column: Some("age".to_string()) });
+        assert!(AggregateFunction::parse("SUM(*)").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_skips_nulls() {
+        let rows = [
+            vec![DataValue::Integer(3)],
+            vec![DataValue::Null],
+            vec![DataValue::Integer(5)],
+        ];
+        let rows: Vec<&[DataValue]> = rows.iter().map(Vec::as_slice).collect();
+        let eval = |input: &str, column| aggregate(input).evaluate(&rows, column).unwrap();
+        assert_eq!(eval("COUNT(*)", None), DataValue::Integer(3));
+        assert_eq!(eval("COUNT(a)", Some(0)), DataValue::Integer(2));
+        assert_eq!(eval("SUM(a)", Some(0)), DataValue::Integer(8));
+        assert_eq!(eval("AVG(a)", Some(0)), DataValue::Float(4.0));
+        assert_eq!(eval("MIN(a)", Some(0)), DataValue::Integer(3));
+        assert_eq!(eval("MAX(a)", Some(0)), DataValue::Integer(5));
+    }
+}
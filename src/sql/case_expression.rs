@@ -0,0 +1,94 @@
+use nom::{
+    IResult,
+    bytes::complete::tag_no_case,
+    character::complete::{multispace0, multispace1},
+    combinator::opt,
+    multi::many1,
+    sequence::{preceded, tuple},
+};
+
+use crate::sql::{
+    clauses::wheres::where_type::{parse_where_expression, WhereType},
+    data_type::DataType,
+    data_value::DataValue,
+};
+
+/// `CASE WHEN <condition> THEN <value> ... [ELSE <value>] END`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseExpression {
+    pub branches: Vec<(WhereType, DataValue)>,
+    /// Result when no condition matches; `NULL` when absent
+    pub else_value: Option<DataValue>,
+}
+
+impl CaseExpression {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, _) = tag_no_case("CASE")(input)?;
+        let (input, branches) = many1(Self::parse_branch)(input)?;
+        let (input, else_value) = opt(preceded(
+            tuple((multispace1, tag_no_case("ELSE"), multispace1)),
+            DataValue::parse,
+        ))(input)?;
+        let (input, _) = tuple((multispace1, tag_no_case("END")))(input)?;
+        Ok((input, CaseExpression { branches, else_value }))
+    }
+
+    fn parse_branch(input: &str) -> IResult<&str, (WhereType, DataValue)> {
+        let (input, _) = tuple((multispace1, tag_no_case("WHEN"), multispace1))(input)?;
+        let (input, condition) = parse_where_expression(input)?;
+        let (input, _) = tuple((multispace0, tag_no_case("THEN"), multispace1))(input)?;
+        let (input, value) = DataValue::parse(input)?;
+        Ok((input, (condition, value)))
+    }
+
+    /// Type of the first non-null result, which every branch is expected to share
+    pub fn result_type(&self) -> DataType {
+        self.branches.iter()
+            .map(|(_, value)| value)
+            .chain(self.else_value.iter())
+            .find_map(|value| match value {
+                DataValue::Text(_) => Some(DataType::Text),
+                DataValue::Integer(_) => Some(DataType::Integer),
+                DataValue::Boolean(_) => Some(DataType::Boolean),
+                DataValue::Float(_) => Some(DataType::Float),
+                DataValue::Decimal(d) => Some(DataType::Decimal { precision: d.precision(), scale: d.scale() }),
+                DataValue::Date(_) => Some(DataType::Date),
+                DataValue::Timestamp(_) => Some(DataType::Timestamp),
+                DataValue::Uuid(_) => Some(DataType::Uuid),
+                DataValue::Json(_) => Some(DataType::Json),
+                DataValue::TSVector(_) => Some(DataType::TSVector),
+                _ => None,
+            })
+            .unwrap_or(DataType::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{clauses::wheres::where_type::WhereClause, operators::op::Op};
+
+    #[test]
+    fn test_parse_case_expression() {
+        let (rest, case) = CaseExpression::parse(
+            "CASE WHEN age >= 18 THEN 'adult' WHEN age > 12 THEN 'teen' ELSE 'child' END FROM users"
+        ).unwrap();
+        assert_eq!(rest, " FROM users");
+        assert_eq!(case.branches, vec![
+            (
+                WhereType::Regular(WhereClause::new("age".to_string(), Op::GreaterThanOrEqual, DataValue::Integer(18), None)),
+                DataValue::Text("adult".into()),
+            ),
+            (
+                WhereType::Regular(WhereClause::new("age".to_string(), Op::GreaterThan, DataValue::Integer(12), None)),
+                DataValue::Text("teen".into()),
+            ),
+        ]);
+        assert_eq!(case.else_value, Some(DataValue::Text("child".into())));
+        assert_eq!(case.result_type(), DataType::Text);
+
+        let (_, case) = CaseExpression::parse("CASE WHEN active = true THEN 1 END").unwrap();
+        assert_eq!(case.else_value, None);
+        assert!(CaseExpression::parse("CASE ELSE 1 END").is_err());
+    }
+}
@@ -13,6 +13,7 @@ use std::fmt;
 use crate::sql::{
     column_def::table_name,
     column_value_pair::ColumnValuePair,
+    operators::op::null_safe_equal,
     table_reference::TableReference,
 };
 
@@ -21,6 +22,8 @@ pub struct JoinClause {
     pub join_type: JoinType,
     pub table_ref: TableReference,
     pub on: (ColumnValuePair, ColumnValuePair),
+    /// Joined with `<=>`, so rows whose keys are both NULL match
+    pub null_safe: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -53,6 +56,7 @@ impl JoinClause {
                 alias: None,
             },
             on,
+            null_safe: false,
         }
     }
 
@@ -71,7 +75,7 @@ impl JoinClause {
         let (input, _) = multispace1(input)?;
         let (input, col1) = ColumnValuePair::parse(input)?;
         let (input, _) = multispace0(input)?;
-        let (input, _) = tag("=")(input)?;
+        let (input, null_safe) = alt((value(true, null_safe_equal), value(false, tag("="))))(input)?;
         let (input, _) = multispace0(input)?;
         let (input, col2) = ColumnValuePair::parse(input)?;
 
@@ -84,6 +88,7 @@ impl JoinClause {
                     alias: alias.map(|a| a.to_string()),
                 },
                 on: (col1, col2),
+                null_safe,
             },
         ))
     }
@@ -149,4 +154,18 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn join_parse_null_safe_test() {
+        let (_, join) = JoinClause::parse("LEFT JOIN t2 ON t1.code <=> t2.code").unwrap();
+        assert!(join.null_safe);
+        assert_eq!(join.on.1.column_name, "code");
+
+        let (input, join) = JoinClause::parse("INNER JOIN t2 ON t1.code IS NOT DISTINCT FROM t2.code").unwrap();
+        assert_eq!(input, "");
+        assert!(join.null_safe);
+
+        let (_, join) = JoinClause::parse("INNER JOIN t2 ON t1.code = t2.code").unwrap();
+        assert!(!join.null_safe);
+    }
 }
@@ -8,7 +8,10 @@ use nom::{
     combinator::{opt, map},
 };
 
-use crate::sql::column::Column;
+use crate::sql::{
+    aggregate::AggregateFunction,
+    column::{Column, ColumnType},
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum OrderDirection {
@@ -40,7 +43,15 @@ impl OrderByClause {
 }
 
 fn parse_order_by_item(input: &str) -> IResult<&str, OrderByClause> {
-    let (input, column) = Column::parse(input)?;
+    let (input, column) = alt((
+        // An aggregate of the query's output, e.g. `ORDER BY COUNT(*)`
+        map(AggregateFunction::parse, |aggregate| Column {
+            table: None,
+            name: aggregate.name().to_string(),
+            column_type: ColumnType::Aggregate(aggregate),
+        }),
+        Column::parse,
+    ))(input)?;
     let (input, _) = multispace0(input)?;
     let (input, direction) = opt(alt((
         map(tag_no_case("DESC"), |_| OrderDirection::Desc),
@@ -57,7 +68,6 @@ fn parse_order_by_item(input: &str) -> IResult<&str, OrderByClause> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sql::column::ColumnType;
 
     #[test]
     fn test_parse_order_by_simple() {
@@ -94,4 +104,14 @@ mod tests {
         assert_eq!(clauses[0].column.column_type, ColumnType::Regular("age".to_string()));
         assert_eq!(clauses[0].direction, OrderDirection::Asc);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_parse_order_by_aggregate() {
+        let (remaining, clauses) = OrderByClause::parse("ORDER BY COUNT(*) DESC, country").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(clauses[0].column.name, "count");
+        assert!(matches!(clauses[0].column.column_type, ColumnType::Aggregate(_)));
+        assert_eq!(clauses[0].direction, OrderDirection::Desc);
+        assert_eq!(clauses[1].column.column_type, ColumnType::Regular("country".to_string()));
+    }
+}
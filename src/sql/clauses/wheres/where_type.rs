@@ -26,6 +26,8 @@ pub struct WhereClause {
     pub operator: Op,
     pub value: DataValue,
     pub table: Option<String>,
+    /// Function call compared instead of the plain column, e.g. `LOWER(name) = 'bob'`
+    pub function: Option<DataValue>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -43,10 +45,40 @@ impl WhereClause {
             operator,
             value,
             table,
+            function: None,
         }
     }
 
     pub fn parse(input: &str) -> IResult<&str, Self> {
+        alt((Self::parse_function_comparison, Self::parse_column_comparison))(input)
+    }
+
+    fn parse_function_comparison(input: &str) -> IResult<&str, Self> {
+        let (input, function) = DataValue::parse_function(input)?;
+        let (input, operator) = delimited(
+            multispace0,
+            Op::parse,
+            multispace0
+        )(input)?;
+        let (input, value) = DataValue::parse(input)?;
+
+        let col_name = match &function {
+            DataValue::Function { name, args } => format!("{}({})", name, args.iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")),
+            _ => unreachable!("parse_function always yields a function"),
+        };
+        Ok((input, WhereClause {
+            col_name,
+            operator,
+            value,
+            table: None,
+            function: Some(function),
+        }))
+    }
+
+    fn parse_column_comparison(input: &str) -> IResult<&str, Self> {
         let (input, col) = Column::parse(input)?;
         let (input, operator) = delimited(
             multispace0,
@@ -60,6 +92,7 @@ impl WhereClause {
             operator,
             value,
             table: col.table,
+            function: None,
         }))
     }
 }
@@ -184,7 +217,7 @@ mod tests {
                         assert_eq!(left_clause.value, DataValue::Integer(18));
                         assert_eq!(right_clause.col_name, "status");
                         assert_eq!(right_clause.operator, Op::Equal);
-                        assert_eq!(right_clause.value, DataValue::Text("active".to_string()));
+                        assert_eq!(right_clause.value, DataValue::Text("active".into()));
                     }
                     _ => panic!("Expected two Regular clauses"),
                 }
@@ -262,7 +295,7 @@ mod tests {
                                 assert_eq!(left_clause.value, DataValue::Integer(35));
                                 assert_eq!(right_clause.col_name, "status");
                                 assert_eq!(right_clause.operator, Op::Equal);
-                                assert_eq!(right_clause.value, DataValue::Text("active".to_string()));
+                                assert_eq!(right_clause.value, DataValue::Text("active".into()));
                             }
                             _ => panic!("Expected two Regular clauses in first AND"),
                         }
@@ -271,7 +304,7 @@ mod tests {
                             (WhereType::Regular(left_clause), WhereType::Regular(right_clause)) => {
                                 assert_eq!(left_clause.col_name, "country");
                                 assert_eq!(left_clause.operator, Op::Equal);
-                                assert_eq!(left_clause.value, DataValue::Text("UK".to_string()));
+                                assert_eq!(left_clause.value, DataValue::Text("UK".into()));
                                 assert_eq!(right_clause.col_name, "year");
                                 assert_eq!(right_clause.operator, Op::Equal);
                                 assert_eq!(right_clause.value, DataValue::Integer(2021));
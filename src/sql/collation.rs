@@ -0,0 +1,75 @@
+use nom::{branch::alt, bytes::complete::tag_no_case, combinator::value, IResult};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt};
+
+use crate::sql::{data_value::DataValue, operators::op::Op};
+
+/// How a text column compares its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Collation {
+    /// Byte-wise, so case-sensitive
+    #[default]
+    Binary,
+    /// Case-insensitive: text is compared by its lowercase form
+    NoCase,
+}
+
+impl Collation {
+    pub fn parse(input: &str) -> IResult<&str, Collation> {
+        alt((
+            value(Collation::Binary, tag_no_case("BINARY")),
+            value(Collation::NoCase, tag_no_case("NOCASE")),
+        ))(input)
+    }
+
+    /// Case-insensitive when either side is, e.g. for a join between two columns
+    pub fn combine(self, other: Collation) -> Collation {
+        if self == Collation::NoCase || other == Collation::NoCase {
+            Collation::NoCase
+        } else {
+            Collation::Binary
+        }
+    }
+
+    /// The value as it compares under this collation
+    pub fn key<'a>(&self, value: &'a DataValue) -> Cow<'a, DataValue> {
+        match (self, value) {
+            (Collation::NoCase, DataValue::Text(text)) => Cow::Owned(DataValue::Text(text.to_lowercase().into())),
+            _ => Cow::Borrowed(value),
+        }
+    }
+
+    pub fn evaluate(&self, op: &Op, left: &DataValue, right: &DataValue) -> bool {
+        op.evaluate(&self.key(left), &self.key(right))
+    }
+
+    /// `DataValue::sql_eq` under this collation
+    pub fn sql_eq(&self, left: &DataValue, right: &DataValue) -> bool {
+        self.key(left).sql_eq(&self.key(right))
+    }
+}
+
+impl fmt::Display for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Collation::Binary => write!(f, "BINARY"),
+            Collation::NoCase => write!(f, "NOCASE"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nocase_compares_lowercase_text() {
+        let text = |s: &str| DataValue::Text(s.into());
+        assert_eq!(Collation::parse("nocase"), Ok(("", Collation::NoCase)));
+        assert!(Collation::NoCase.evaluate(&Op::Equal, &text("Alice"), &text("aLICE")));
+        assert!(!Collation::Binary.evaluate(&Op::Equal, &text("Alice"), &text("alice")));
+        assert!(Collation::NoCase.evaluate(&Op::LessThan, &text("alice"), &text("Bob")));
+        assert!(!Collation::NoCase.sql_eq(&DataValue::Null, &DataValue::Null));
+        assert_eq!(Collation::Binary.combine(Collation::NoCase), Collation::NoCase);
+    }
+}
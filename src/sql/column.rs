@@ -11,6 +11,9 @@ use nom::{
 };
 use crate::sql::data_value::DataValue;
 use super::function_parser::{parse_function, FunctionCall};
+use super::case_expression::CaseExpression;
+use super::window::WindowFunction;
+use super::aggregate::AggregateFunction;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Column {
@@ -24,6 +27,13 @@ pub enum ColumnType {
     Regular(String),
     Wildcard,
     Function(String, Vec<DataValue>),
+    Case(CaseExpression),
+    /// `a || b || ...`, NULL if any operand is NULL
+    Concat(Vec<DataValue>),
+    /// Ranking function computed over the rows the query selects
+    Window(WindowFunction),
+    /// Aggregate computed over all the rows the query selects, or over each GROUP BY group
+    Aggregate(AggregateFunction),
 }
 
 impl Column {
@@ -9,7 +9,9 @@ use nom::{
 };
 use serde::{Deserialize, Serialize};
 
-use super::{data_type::DataType, constraints::constraint::Constraint};
+use std::cmp::Ordering;
+
+use super::{collation::Collation, data_type::DataType, data_value::DataValue, constraints::{constraint::Constraint, foreignkey::ForeignKeyConstraint}};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ColumnDef {
@@ -43,6 +45,64 @@ impl ColumnDef {
             constraints,
         }
     }
+    /// Expression a generated column is computed from
+    pub fn generated_expression(&self) -> Option<&DataValue> {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            Constraint::Generated(expression) => Some(expression),
+            _ => None,
+        })
+    }
+
+    pub fn is_generated(&self) -> bool {
+        self.generated_expression().is_some()
+    }
+
+    /// Collation named by a COLLATE clause, case-sensitive by default
+    pub fn collation(&self) -> Collation {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            Constraint::Collate(collation) => Some(*collation),
+            _ => None,
+        }).unwrap_or_default()
+    }
+
+    /// Orders two values of this column under its type and collation
+    pub fn compare(&self, a: &DataValue, b: &DataValue) -> Option<Ordering> {
+        let collation = self.collation();
+        self.data_type.compare(&collation.key(a), &collation.key(b))
+    }
+
+    pub fn default_value(&self) -> Option<&DataValue> {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            Constraint::Default(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Table and column this column references, if it is a foreign key
+    pub fn foreign_key(&self) -> Option<&ForeignKeyConstraint> {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            Constraint::ForeignKey(fk) => Some(fk),
+            _ => None,
+        })
+    }
+
+    /// How long after this column's time a row expires, if the column has a TTL
+    pub fn ttl(&self) -> Option<std::time::Duration> {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            Constraint::Ttl(ttl) => Some(*ttl),
+            _ => None,
+        })
+    }
+
+    /// Whether the column's key and reference checks wait for COMMIT inside a transaction
+    pub fn is_deferred(&self) -> bool {
+        self.constraints.contains(&Constraint::Deferrable { initially_deferred: true })
+    }
+
+    pub fn is_not_null(&self) -> bool {
+        self.constraints.contains(&Constraint::NotNull)
+    }
+
     pub fn parse(input: &str) -> IResult<&str, ColumnDef> {
         let (input, name) = column_name(input)?; // Use custom column_name() instead of alphanumeric1
         let (input, _) = multispace1(input)?;
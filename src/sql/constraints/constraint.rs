@@ -1,7 +1,18 @@
-use nom::{branch::alt, bytes::complete::{tag, tag_no_case}, combinator::map, IResult};
+use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
+    character::complete::{digit1, multispace1},
+    combinator::{map, map_res, opt},
+    sequence::{pair, preceded, tuple},
+    IResult,
+};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
 
 use super::foreignkey::ForeignKeyConstraint;
+use super::generated::{format_expression, parse_generated};
+use crate::sql::{collation::Collation, data_value::DataValue};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Constraint {
@@ -9,6 +20,18 @@ pub enum Constraint {
     PrimaryKey,
     Unique,
     ForeignKey(ForeignKeyConstraint),
+    /// Value computed from other columns of the row on every write, e.g. `AS (price * quantity)`
+    Generated(DataValue),
+    /// Value or function call used when an insert leaves the column out, e.g. `DEFAULT gen_uuid()`
+    Default(DataValue),
+    /// `COLLATE NOCASE`: how the column's text is compared
+    Collate(Collation),
+    /// `DEFERRABLE [INITIALLY DEFERRED | INITIALLY IMMEDIATE]`: whether the column's UNIQUE,
+    /// PRIMARY KEY and FOREIGN KEY checks wait until an explicit transaction commits
+    Deferrable { initially_deferred: bool },
+    /// `TTL <seconds>` on a TIMESTAMP column: rows expire this long after the column's time
+    /// and are no longer read. Tables created `WITH (TTL = n)` get such an `inserted_at` column.
+    Ttl(Duration),
     // You can add more constraints here as needed.
 }
 
@@ -19,10 +42,57 @@ impl Constraint {
             map(tag_no_case("PRIMARY KEY"), |_| Constraint::PrimaryKey),
             map(tag_no_case("UNIQUE"), |_| Constraint::Unique),
             ForeignKeyConstraint::parse,
+            map(parse_generated, Constraint::Generated),
+            map(
+                preceded(pair(tag_no_case("DEFAULT"), multispace1), DataValue::parse),
+                Constraint::Default,
+            ),
+            map(
+                preceded(pair(tag_no_case("COLLATE"), multispace1), Collation::parse),
+                Constraint::Collate,
+            ),
+            parse_deferrable,
+            map(
+                preceded(pair(tag_no_case("TTL"), multispace1), map_res(digit1, str::parse::<u64>)),
+                |seconds| Constraint::Ttl(Duration::from_secs(seconds)),
+            ),
         ))(input)
     }
 }
 
+fn parse_deferrable(input: &str) -> IResult<&str, Constraint> {
+    let (input, _) = tag_no_case("DEFERRABLE")(input)?;
+    let (input, initially) = opt(preceded(
+        tuple((multispace1, tag_no_case("INITIALLY"), multispace1)),
+        alt((
+            map(tag_no_case("DEFERRED"), |_| true),
+            map(tag_no_case("IMMEDIATE"), |_| false),
+        )),
+    ))(input)?;
+    Ok((input, Constraint::Deferrable { initially_deferred: initially.unwrap_or(false) }))
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::NotNull => write!(f, "NOT NULL"),
+            Constraint::PrimaryKey => write!(f, "PRIMARY KEY"),
+            Constraint::Unique => write!(f, "UNIQUE"),
+            Constraint::ForeignKey(fk) => {
+                write!(f, "FOREIGN KEY ({}) REFERENCES {}", fk.column_name, fk.table_name)
+            }
+            Constraint::Generated(expression) => {
+                write!(f, "GENERATED ALWAYS AS {}", format_expression(expression))
+            }
+            Constraint::Default(value) => write!(f, "DEFAULT {}", format_expression(value)),
+            Constraint::Collate(collation) => write!(f, "COLLATE {}", collation),
+            Constraint::Deferrable { initially_deferred: true } => write!(f, "DEFERRABLE INITIALLY DEFERRED"),
+            Constraint::Deferrable { initially_deferred: false } => write!(f, "DEFERRABLE INITIALLY IMMEDIATE"),
+            Constraint::Ttl(ttl) => write!(f, "TTL {}", ttl.as_secs()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::sql::constraints::foreignkey::ForeignKeyConstraint;
@@ -47,5 +117,24 @@ mod tests {
                 })
             ))
         );
+        assert_eq!(
+            Constraint::parse("DEFERRABLE INITIALLY DEFERRED"),
+            Ok(("", Constraint::Deferrable { initially_deferred: true }))
+        );
+        assert_eq!(
+            Constraint::parse("DEFERRABLE"),
+            Ok(("", Constraint::Deferrable { initially_deferred: false }))
+        );
+        assert_eq!(
+            Constraint::parse("TTL 60"),
+            Ok(("", Constraint::Ttl(std::time::Duration::from_secs(60))))
+        );
+        assert_eq!(
+            Constraint::parse("DEFAULT now()"),
+            Ok(("", Constraint::Default(crate::sql::data_value::DataValue::Function {
+                name: "now".to_string(),
+                args: vec![],
+            })))
+        );
     }
 }
@@ -0,0 +1,153 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::{multispace0, multispace1, one_of},
+    combinator::{map, opt},
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+use crate::sql::{column_def::column_name, data_value::DataValue};
+
+/// Parses `[GENERATED ALWAYS] AS (expr)`. Column references in `expr` are kept as
+/// `DataValue::Column` and the operators become nested `add`/`subtract`/`multiply`/`divide`
+/// calls, so the expression evaluator can compute the column from the rest of the row.
+pub fn parse_generated(input: &str) -> IResult<&str, DataValue> {
+    preceded(
+        tuple((
+            opt(tuple((tag_no_case("GENERATED"), multispace1, tag_no_case("ALWAYS"), multispace1))),
+            tag_no_case("AS"),
+            multispace0,
+        )),
+        parenthesized,
+    )(input)
+}
+
+fn parenthesized(input: &str) -> IResult<&str, DataValue> {
+    delimited(pair(tag("("), multispace0), expression, pair(multispace0, tag(")")))(input)
+}
+
+// Left-associative chain of `operand` joined by the given operators
+fn chain<'a>(
+    input: &'a str,
+    operators: &'static str,
+    operand: fn(&'a str) -> IResult<&'a str, DataValue>,
+) -> IResult<&'a str, DataValue> {
+    let (input, first) = operand(input)?;
+    let (input, rest) = many0(pair(delimited(multispace0, one_of(operators), multispace0), operand))(input)?;
+    let value = rest.into_iter().fold(first, |left, (operator, right)| {
+        let name = match operator {
+            '+' => "add",
+            '-' => "subtract",
+            '*' => "multiply",
+            _ => "divide",
+        };
+        DataValue::Function { name: name.to_string(), args: vec![left, right] }
+    });
+    Ok((input, value))
+}
+
+fn expression(input: &str) -> IResult<&str, DataValue> {
+    chain(input, "+-", term)
+}
+
+fn term(input: &str) -> IResult<&str, DataValue> {
+    chain(input, "*/", factor)
+}
+
+// Identifiers are tried before literals so a column such as `info` isn't read as a float
+fn factor(input: &str) -> IResult<&str, DataValue> {
+    alt((
+        parenthesized,
+        DataValue::parse_function,
+        map(column_name, |name: &str| DataValue::Column(name.to_string())),
+        DataValue::parse,
+    ))(input)
+}
+
+/// Column names an expression reads, in the order they appear
+pub fn referenced_columns(expression: &DataValue) -> Vec<&str> {
+    match expression {
+        DataValue::Column(name) => vec![name.as_str()],
+        DataValue::Function { args, .. } => args.iter().flat_map(referenced_columns).collect(),
+        _ => vec![],
+    }
+}
+
+/// SQL text for an expression produced by `parse_generated`
+pub fn format_expression(expression: &DataValue) -> String {
+    match expression {
+        DataValue::Function { name, args } => {
+            let operator = match name.as_str() {
+                "add" => Some("+"),
+                "subtract" => Some("-"),
+                "multiply" => Some("*"),
+                "divide" => Some("/"),
+                _ => None,
+            };
+            match (operator, args.as_slice()) {
+                (Some(operator), [left, right]) => {
+                    format!("({} {} {})", format_expression(left), operator, format_expression(right))
+                }
+                _ => {
+                    let args: Vec<String> = args.iter().map(format_expression).collect();
+                    format!("{}({})", name, args.join(", "))
+                }
+            }
+        }
+        DataValue::Text(text) => format!("'{}'", text.replace('\'', "''")),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<DataValue>) -> DataValue {
+        DataValue::Function { name: name.to_string(), args }
+    }
+
+    fn col(name: &str) -> DataValue {
+        DataValue::Column(name.to_string())
+    }
+
+    #[test]
+    fn test_parse_generated() {
+        assert_eq!(
+            parse_generated("AS (price * quantity)"),
+            Ok(("", call("multiply", vec![col("price"), col("quantity")])))
+        );
+        // Multiplication binds tighter and operators associate to the left
+        assert_eq!(
+            parse_generated("GENERATED ALWAYS AS (a - b - c * 2)"),
+            Ok(("", call("subtract", vec![
+                call("subtract", vec![col("a"), col("b")]),
+                call("multiply", vec![col("c"), DataValue::Integer(2)]),
+            ])))
+        );
+        assert_eq!(
+            parse_generated("as ((a + 1.5) / abs(b))"),
+            Ok(("", call("divide", vec![
+                call("add", vec![col("a"), DataValue::Float(1.5)]),
+                call("abs", vec![col("b")]),
+            ])))
+        );
+        assert!(parse_generated("AS price").is_err());
+
+        let expression = parse_generated("AS (price * (quantity - 1))").unwrap().1;
+        assert_eq!(referenced_columns(&expression), vec!["price", "quantity"]);
+        assert_eq!(format_expression(&expression), "(price * (quantity - 1))");
+        assert_eq!(parse_generated(&format!("AS {}", format_expression(&expression))), Ok(("", expression)));
+
+        // Quoted text is a literal, not a column, even when it spells one
+        let expression = parse_generated("AS (length('price') + quantity)").unwrap().1;
+        assert_eq!(expression, call("add", vec![
+            call("length", vec![DataValue::Text("price".into())]),
+            col("quantity"),
+        ]));
+        assert_eq!(referenced_columns(&expression), vec!["quantity"]);
+        assert_eq!(format_expression(&expression), "(length('price') + quantity)");
+    }
+}
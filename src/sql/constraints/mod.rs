@@ -1,2 +1,3 @@
 pub mod constraint;
-pub mod foreignkey;
\ No newline at end of file
+pub mod foreignkey;
+pub mod generated;
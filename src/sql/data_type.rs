@@ -1,31 +1,154 @@
-use nom::{branch::alt, bytes::complete::tag_no_case, combinator::map, IResult};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_while},
+    character::complete::{digit1, multispace0},
+    combinator::{map, map_res, opt},
+    multi::separated_list1,
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
 use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+use crate::sql::data_value::DataValue;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DataType {
     Text,
+    /// Text limited to the given number of characters
+    Varchar(usize),
     Integer,
+    /// Fixed-point number with `precision` total digits, `scale` of them after the point
+    Decimal { precision: u32, scale: u32 },
     TSVector,
     Boolean,
     Float,
     Date,
     Timestamp,
+    Uuid,
+    Json,
+    /// Variable-length list of values of the element type, written `TEXT[]`
+    Array(Box<DataType>),
+    /// Text restricted to the listed labels, which sort in declaration order
+    Enum(Vec<String>),
     Null
 }
 
 impl DataType {
     pub fn parse(input: &str) -> IResult<&str, DataType> {
+        let (input, data_type) = Self::parse_scalar(input)?;
+        let (input, array) = opt(tag("[]"))(input)?;
+        Ok((input, if array.is_some() { DataType::Array(Box::new(data_type)) } else { data_type }))
+    }
+
+    fn parse_scalar(input: &str) -> IResult<&str, DataType> {
         alt((
             map(tag_no_case("TEXT"), |_| DataType::Text),
+            Self::parse_varchar,
+            Self::parse_decimal,
+            Self::parse_enum,
             map(tag_no_case("INTEGER"), |_| DataType::Integer),
             map(alt((tag_no_case("TSVECTOR"), tag_no_case("TSVector"))), |_| DataType::TSVector),
             map(tag_no_case("BOOLEAN"), |_| DataType::Boolean),
             map(tag_no_case("FLOAT"), |_| DataType::Float),
             map(tag_no_case("DATE"), |_| DataType::Date),
             map(tag_no_case("TIMESTAMP"), |_| DataType::Timestamp),
+            map(tag_no_case("UUID"), |_| DataType::Uuid),
+            map(tag_no_case("JSON"), |_| DataType::Json),
             map(tag_no_case("NULL"), |_| DataType::Null),
         ))(input)
     }
+
+    fn parse_varchar(input: &str) -> IResult<&str, DataType> {
+        map(
+            preceded(
+                tag_no_case("VARCHAR"),
+                delimited(
+                    tuple((multispace0, tag("("), multispace0)),
+                    map_res(digit1, str::parse::<usize>),
+                    tuple((multispace0, tag(")"))),
+                ),
+            ),
+            DataType::Varchar,
+        )(input)
+    }
+
+    // DECIMAL(p[, s]) or its synonym NUMERIC
+    fn parse_decimal(input: &str) -> IResult<&str, DataType> {
+        let number = |input| map_res(digit1, str::parse::<u32>)(input);
+        map(
+            preceded(
+                alt((tag_no_case("DECIMAL"), tag_no_case("NUMERIC"))),
+                delimited(
+                    tuple((multispace0, tag("("), multispace0)),
+                    tuple((number, opt(preceded(tuple((multispace0, tag(","), multispace0)), number)))),
+                    tuple((multispace0, tag(")"))),
+                ),
+            ),
+            |(precision, scale)| DataType::Decimal { precision, scale: scale.unwrap_or(0) },
+        )(input)
+    }
+
+    // ENUM('label', ...)
+    fn parse_enum(input: &str) -> IResult<&str, DataType> {
+        map(
+            preceded(
+                tag_no_case("ENUM"),
+                delimited(
+                    tuple((multispace0, tag("("), multispace0)),
+                    separated_list1(
+                        delimited(multispace0, tag(","), multispace0),
+                        map(delimited(tag("'"), take_while(|c| c != '\''), tag("'")), str::to_string),
+                    ),
+                    tuple((multispace0, tag(")"))),
+                ),
+            ),
+            DataType::Enum,
+        )(input)
+    }
+
+    /// Orders two values of this type for ORDER BY: enum labels by declaration order,
+    /// everything else by value
+    pub fn compare(&self, a: &DataValue, b: &DataValue) -> Option<Ordering> {
+        if let (DataType::Enum(labels), DataValue::Text(a), DataValue::Text(b)) = (self, a, b) {
+            let position = |label: &str| labels.iter().position(|l| l == label);
+            return Some(position(a).cmp(&position(b)));
+        }
+        a.partial_cmp(b)
+    }
+
+    /// Maximum length in characters, for bounded text types
+    pub fn max_length(&self) -> Option<usize> {
+        match self {
+            DataType::Varchar(max) => Some(*max),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Text => "TEXT",
+            DataType::Varchar(max) => return write!(f, "VARCHAR({})", max),
+            DataType::Decimal { precision, scale } => return write!(f, "DECIMAL({},{})", precision, scale),
+            DataType::Array(element) => return write!(f, "{}[]", element),
+            DataType::Enum(labels) => {
+                let labels: Vec<String> = labels.iter().map(|label| format!("'{}'", label)).collect();
+                return write!(f, "ENUM({})", labels.join(", "));
+            }
+            DataType::Integer => "INTEGER",
+            DataType::TSVector => "TSVECTOR",
+            DataType::Boolean => "BOOLEAN",
+            DataType::Float => "FLOAT",
+            DataType::Date => "DATE",
+            DataType::Timestamp => "TIMESTAMP",
+            DataType::Uuid => "UUID",
+            DataType::Json => "JSON",
+            DataType::Null => "NULL",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 #[cfg(test)]
@@ -42,5 +165,18 @@ mod tests {
         assert_eq!(DataType::parse("DATE"), Ok(("", DataType::Date)));
         assert_eq!(DataType::parse("TIMESTAMP"), Ok(("", DataType::Timestamp)));
         assert_eq!(DataType::parse("NULL"), Ok(("", DataType::Null)));
+        assert_eq!(DataType::parse("UUID"), Ok(("", DataType::Uuid)));
+        assert_eq!(DataType::parse("JSON"), Ok(("", DataType::Json)));
+        assert_eq!(DataType::parse("TEXT[]"), Ok(("", DataType::Array(Box::new(DataType::Text)))));
+        assert_eq!(DataType::Array(Box::new(DataType::Integer)).to_string(), "INTEGER[]");
+        let status = DataType::Enum(vec!["open".to_string(), "closed".to_string()]);
+        assert_eq!(DataType::parse("ENUM('open', 'closed')"), Ok(("", status.clone())));
+        assert_eq!(status.to_string(), "ENUM('open', 'closed')");
+        assert_eq!(DataType::parse("VARCHAR(50)"), Ok(("", DataType::Varchar(50))));
+        assert_eq!(DataType::parse("varchar ( 8 )"), Ok(("", DataType::Varchar(8))));
+        assert_eq!(DataType::Varchar(50).to_string(), "VARCHAR(50)");
+        assert_eq!(DataType::parse("DECIMAL(10,2)"), Ok(("", DataType::Decimal { precision: 10, scale: 2 })));
+        assert_eq!(DataType::parse("numeric(5)"), Ok(("", DataType::Decimal { precision: 5, scale: 0 })));
+        assert_eq!(DataType::Decimal { precision: 10, scale: 2 }.to_string(), "DECIMAL(10,2)");
     }
 }
@@ -1,6 +1,6 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag, tag_no_case, take_while1},
+    bytes::complete::{tag, tag_no_case},
     character::complete::{multispace0, multispace1, alphanumeric1, digit1},
     combinator::{map, opt, value, recognize},
     multi::separated_list0,
@@ -9,7 +9,7 @@ use nom::{
     IResult,
 };
 use serde::{Deserialize, Serialize};
-use std::{fmt, cmp::Ordering};
+use std::{fmt, cmp::Ordering, sync::Arc};
 use crate::fts::text_processor::{TsVector, TSQuery};
 
 use crate::sql::{
@@ -17,16 +17,23 @@ use crate::sql::{
     column_value_pair::{ColumnValuePair, identifier},
     table_reference::TableReference,
     data_type::DataType,
+    decimal::Decimal,
+    json::Json,
+    uuid::Uuid,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DataValue {
-    Text(String),
+    Text(Arc<str>),
     Integer(i64),
     Boolean(bool),
     Float(f64),
+    Decimal(Decimal),
     Date(String),
     Timestamp(String),
+    Uuid(Uuid),
+    Json(Json),
+    Array(Vec<DataValue>),
     TSVector(TsVector),
     TSQuery(TSQuery),
     Null,
@@ -34,6 +41,9 @@ pub enum DataValue {
         name: String,
         args: Vec<DataValue>,
     },
+    /// A column named in an expression, as `column` or `table.column`; it reads the row the
+    /// expression is evaluated against, while `Text` is always a literal
+    Column(String),
 }
 
 impl PartialOrd for DataValue {
@@ -45,13 +55,22 @@ impl PartialOrd for DataValue {
             (DataValue::TSQuery(a), DataValue::TSQuery(b)) => Some(a.tokens.len().cmp(&b.tokens.len())),
             (DataValue::Boolean(a), DataValue::Boolean(b)) => Some(a.cmp(b)),
             (DataValue::Float(a), DataValue::Float(b)) => a.partial_cmp(b),
+            (DataValue::Decimal(a), DataValue::Decimal(b)) => Some(a.cmp(b)),
+            (DataValue::Decimal(a), DataValue::Integer(b)) => Some(a.cmp(&Decimal::from_i64(*b))),
+            (DataValue::Integer(a), DataValue::Decimal(b)) => Some(Decimal::from_i64(*a).cmp(b)),
+            (DataValue::Decimal(a), DataValue::Float(b)) => a.to_f64().partial_cmp(b),
+            (DataValue::Float(a), DataValue::Decimal(b)) => a.partial_cmp(&b.to_f64()),
             (DataValue::Date(a), DataValue::Date(b)) => Some(a.cmp(b)),
             (DataValue::Timestamp(a), DataValue::Timestamp(b)) => Some(a.cmp(b)),
+            (DataValue::Uuid(a), DataValue::Uuid(b)) => Some(a.cmp(b)),
+            (DataValue::Uuid(a), DataValue::Text(b)) => Uuid::parse(b).map(|b| a.cmp(&b)),
+            (DataValue::Text(a), DataValue::Uuid(b)) => Uuid::parse(a).map(|a| a.cmp(b)),
+            (DataValue::Array(a), DataValue::Array(b)) => a.partial_cmp(b),
             (DataValue::Null, DataValue::Null) => Some(Ordering::Equal),
             (DataValue::Null, _) => Some(Ordering::Less),
             (_, DataValue::Null) => Some(Ordering::Greater),
-            (DataValue::Function { .. }, _) => None,
-            (_, DataValue::Function { .. }) => None,
+            (DataValue::Function { .. } | DataValue::Column(_), _) => None,
+            (_, DataValue::Function { .. } | DataValue::Column(_)) => None,
             _ => None,
         }
     }
@@ -66,16 +85,64 @@ impl Ord for DataValue {
 }
 
 impl DataValue {
+    /// SQL equality as used for join keys: NULL is not equal to anything, itself included
+    pub fn sql_eq(&self, other: &DataValue) -> bool {
+        !matches!(self, DataValue::Null) && !matches!(other, DataValue::Null) && self == other
+    }
+
+    /// Converts a literal for storage in a column of `data_type`: DECIMAL columns get integer
+    /// and float literals as decimals rounded to the column's scale, FLOAT columns get decimal
+    /// literals as floats, and JSON columns parse text. Other values are returned unchanged. Errors when the value needs more digits than
+    /// the precision allows or isn't valid JSON.
+    pub fn coerce_to(self, data_type: &DataType) -> Result<DataValue, String> {
+        match (&self, data_type) {
+            (DataValue::Text(text), DataType::Uuid) => {
+                return Uuid::parse(text).map(DataValue::Uuid).ok_or_else(|| format!("{} is not a valid UUID", text));
+            }
+            (DataValue::Text(text), DataType::Json) => return Json::parse(text).map(DataValue::Json),
+            (DataValue::Decimal(d), DataType::Float) => return Ok(DataValue::Float(d.to_f64())),
+            (DataValue::Array(_), DataType::Array(element_type)) => {
+                let DataValue::Array(elements) = self else { unreachable!() };
+                return elements.into_iter()
+                    .map(|element| element.coerce_to(element_type))
+                    .collect::<Result<_, _>>()
+                    .map(DataValue::Array);
+            }
+            _ => {}
+        }
+        let DataType::Decimal { precision, scale } = *data_type else {
+            return Ok(self);
+        };
+        let decimal = match &self {
+            DataValue::Decimal(d) => *d,
+            DataValue::Integer(i) => Decimal::from_i64(*i),
+            DataValue::Float(f) => Decimal::from_f64(*f).ok_or_else(|| format!("{} is not a valid DECIMAL", f))?,
+            _ => return Ok(self),
+        };
+        match decimal.rescale(scale) {
+            Some(d) if d.precision() <= precision => Ok(DataValue::Decimal(d)),
+            _ => Err(format!("{} does not fit in {}", decimal, data_type)),
+        }
+    }
+
     pub fn matches_type(&self, data_type: &DataType) -> bool {
         match (self, data_type) {
             (DataValue::Text(_), DataType::Text) => true,
+            (DataValue::Text(_), DataType::Varchar(_)) => true,
+            (DataValue::Text(_), DataType::Enum(_)) => true,
             (DataValue::TSVector(_), DataType::TSVector) => true,
             (DataValue::Text(_), DataType::TSVector) => true,
             (DataValue::Integer(_), DataType::Integer) => true,
             (DataValue::Boolean(_), DataType::Boolean) => true,
             (DataValue::Float(_), DataType::Float) => true,
+            (DataValue::Decimal(_), DataType::Decimal { .. }) => true,
             (DataValue::Date(_), DataType::Date) => true,
             (DataValue::Timestamp(_), DataType::Timestamp) => true,
+            (DataValue::Uuid(_), DataType::Uuid) => true,
+            (DataValue::Json(_), DataType::Json) => true,
+            (DataValue::Array(elements), DataType::Array(element_type)) => {
+                elements.iter().all(|element| element.matches_type(element_type))
+            }
             (DataValue::Null, _) => true,
             (DataValue::Function { .. }, _) => true,
             _ => false,
@@ -93,9 +160,34 @@ impl DataValue {
             Self::parse_float,
             Self::parse_boolean,
             Self::parse_null,
+            Self::parse_array_literal,
         ))(input)
     }
 
+    /// `[value, ...]` with any literals as elements
+    pub fn parse_array_literal(input: &str) -> IResult<&str, DataValue> {
+        map(
+            delimited(
+                tuple((tag("["), multispace0)),
+                separated_list0(delimited(multispace0, tag(","), multispace0), Self::parse),
+                tuple((multispace0, tag("]"))),
+            ),
+            DataValue::Array,
+        )(input)
+    }
+
+    /// Array containment: every element of `other` (or `other` itself, if it is not an
+    /// array) is in `self`. Anything but an array contains nothing.
+    pub fn contains(&self, other: &DataValue) -> bool {
+        let DataValue::Array(elements) = self else {
+            return false;
+        };
+        match other {
+            DataValue::Array(wanted) => wanted.iter().all(|value| elements.contains(value)),
+            value => elements.contains(value),
+        }
+    }
+
     fn parse_integer(input: &str) -> IResult<&str, DataValue> {
         let (input, value) = recognize(tuple((
             opt(tag("-")),
@@ -110,7 +202,13 @@ impl DataValue {
             )));
         }
         
-        Ok((input, DataValue::Integer(value.parse().unwrap())))
+        // Integers too large for an i64 stay exact as decimals
+        match value.parse() {
+            Ok(integer) => Ok((input, DataValue::Integer(integer))),
+            Err(_) => Decimal::parse(value)
+                .map(|decimal| (input, DataValue::Decimal(decimal)))
+                .ok_or_else(|| nom::Err::Error(nom::error::Error::new(value, nom::error::ErrorKind::Digit))),
+        }
     }
     
     fn parse_boolean(input: &str) -> IResult<&str, DataValue> {
@@ -120,9 +218,15 @@ impl DataValue {
         ))(input)
     }
     
+    // Literals with more digits than a float holds are kept exact as decimals, so a DECIMAL
+    // column stores them as written
     fn parse_float(input: &str) -> IResult<&str, DataValue> {
-        let (input, value) = double(input)?;
-        Ok((input, DataValue::Float(value)))
+        let (input, literal) = recognize(double)(input)?;
+        let (_, value) = double(literal)?;
+        match Decimal::parse(literal) {
+            Some(decimal) if Decimal::from_f64(value) != Some(decimal) => Ok((input, DataValue::Decimal(decimal))),
+            _ => Ok((input, DataValue::Float(value))),
+        }
     }
     
     fn parse_date(input: &str) -> IResult<&str, DataValue> {
@@ -173,8 +277,9 @@ impl DataValue {
         let mut chars = input.chars();
         let mut pos = 0;
     
+        // Byte offset into input, so multibyte characters slice correctly
         while let Some(c) = chars.next() {
-            pos += 1;
+            pos += c.len_utf8();
             if c == '\'' {
                 // Look ahead for another quote
                 if let Some(next_c) = chars.clone().next() {
@@ -187,7 +292,7 @@ impl DataValue {
                     }
                 }
                 // This is the end of the string
-                return Ok((&input[pos..], DataValue::Text(result)));
+                return Ok((&input[pos..], DataValue::Text(result.into())));
             } else {
                 result.push(c);
             }
@@ -215,7 +320,7 @@ impl DataValue {
                         Self::parse_float,
                         Self::parse_boolean,
                         Self::parse_null,
-                        map(identifier, |s: &str| DataValue::Text(s.to_string())),
+                        map(identifier, |s: &str| DataValue::Column(s.to_string())),
                     )),
                 ),
                 tuple((multispace0, tag(")"))),
@@ -238,7 +343,7 @@ impl DataValue {
                 delimited(multispace0, tag(","), multispace0),
                 alt((
                     Self::parse_float,
-                    map(identifier, |s: &str| DataValue::Text(s.to_string())),
+                    map(identifier, |s: &str| DataValue::Text(s.into())),
                 )),
             ),
             tuple((multispace0, tag("]"), multispace0)),
@@ -252,9 +357,9 @@ impl DataValue {
                     f.to_string()
                 }
             },
-            DataValue::Text(s) => s.clone(),
+            DataValue::Text(s) => s.to_string(),
             _ => "".to_string(),
-        }).collect::<Vec<_>>().join(", ")))))
+        }).collect::<Vec<_>>().join(", ")).into())))
     }
 }
 
@@ -266,7 +371,7 @@ mod tests {
     fn parse_test() {
         assert_eq!(
             DataValue::parse("'Hello World'"),
-            Ok(("", DataValue::Text("Hello World".to_string())))
+            Ok(("", DataValue::Text("Hello World".into())))
         );
         assert_eq!(
             DataValue::parse("123"),
@@ -303,8 +408,8 @@ mod tests {
             Ok(("", DataValue::Function {
                 name: "ts_rank".to_string(),
                 args: vec![
-                    DataValue::Text("vector".to_string()),
-                    DataValue::Text("query".to_string()),
+                    DataValue::Column("vector".to_string()),
+                    DataValue::Column("query".to_string()),
                 ],
             }))
         );
@@ -314,8 +419,8 @@ mod tests {
             Ok(("", DataValue::Function {
                 name: "ts_rank".to_string(),
                 args: vec![
-                    DataValue::Text("vector".to_string()),
-                    DataValue::Text("query".to_string()),
+                    DataValue::Column("vector".to_string()),
+                    DataValue::Column("query".to_string()),
                     DataValue::Integer(1),
                 ],
             }))
@@ -326,9 +431,9 @@ mod tests {
             Ok(("", DataValue::Function {
                 name: "ts_rank".to_string(),
                 args: vec![
-                    DataValue::Text("[0.1, 0.2, 0.4, 1.0]".to_string()),
-                    DataValue::Text("vector".to_string()),
-                    DataValue::Text("query".to_string()),
+                    DataValue::Text("[0.1, 0.2, 0.4, 1.0]".into()),
+                    DataValue::Column("vector".to_string()),
+                    DataValue::Column("query".to_string()),
                 ],
             }))
         );
@@ -340,11 +445,11 @@ mod tests {
                 args: vec![
                     DataValue::Function {
                         name: "to_tsvector".to_string(),
-                        args: vec![DataValue::Text("content".to_string())],
+                        args: vec![DataValue::Column("content".to_string())],
                     },
                     DataValue::Function {
                         name: "to_tsquery".to_string(),
-                        args: vec![DataValue::Text("rust".to_string())],
+                        args: vec![DataValue::Text("rust".into())],
                     },
                 ],
             }))
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+/// Largest scale a `Decimal` can carry; `i128` holds 38 significant digits
+pub const MAX_DECIMAL_DIGITS: u32 = 38;
+
+/// Fixed-point number: `units / 10^scale`. Arithmetic is exact and returns `None` on overflow.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+fn pow10(exp: u32) -> Option<i128> {
+    10i128.checked_pow(exp)
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Self {
+        Decimal { units, scale }
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        Decimal::new(value as i128, 0)
+    }
+
+    /// Takes the shortest decimal form of the float, so the literal `19.99` becomes exactly 19.99
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        Self::parse(&value.to_string())
+    }
+
+    /// Parses `[-]digits[.digits]`
+    pub fn parse(input: &str) -> Option<Self> {
+        let (negative, digits) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+            || whole.len() + fraction.len() > MAX_DECIMAL_DIGITS as usize
+        {
+            return None;
+        }
+        let units: i128 = format!("{}{}", whole, fraction).parse().ok()?;
+        Some(Decimal::new(if negative { -units } else { units }, fraction.len() as u32))
+    }
+
+    pub fn units(&self) -> i128 {
+        self.units
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Total number of significant digits, at least 1
+    pub fn precision(&self) -> u32 {
+        let digits = self.units.unsigned_abs().checked_ilog10().map_or(1, |log| log + 1);
+        digits.max(self.scale)
+    }
+
+    /// Changes the scale, rounding half away from zero when digits are dropped
+    pub fn rescale(&self, scale: u32) -> Option<Self> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Some(*self),
+            Ordering::Greater => {
+                let units = self.units.checked_mul(pow10(scale - self.scale)?)?;
+                Some(Decimal::new(units, scale))
+            }
+            Ordering::Less => {
+                let divisor = pow10(self.scale - scale)?;
+                let (quotient, remainder) = (self.units / divisor, self.units % divisor);
+                let units = if remainder.unsigned_abs() * 2 >= divisor.unsigned_abs() {
+                    quotient + self.units.signum()
+                } else {
+                    quotient
+                };
+                Some(Decimal::new(units, scale))
+            }
+        }
+    }
+
+    // Both operands brought to the larger scale
+    fn aligned(&self, other: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.rescale(scale)?.units, other.rescale(scale)?.units, scale))
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::new(a.checked_add(b)?, scale))
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::new(a.checked_sub(b)?, scale))
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Self> {
+        let scale = self.scale + other.scale;
+        if scale > MAX_DECIMAL_DIGITS {
+            return None;
+        }
+        Some(Decimal::new(self.units.checked_mul(other.units)?, scale))
+    }
+
+    /// Quotient rounded to `scale` digits; `None` when dividing by zero or on overflow
+    pub fn checked_div(&self, other: &Decimal, scale: u32) -> Option<Self> {
+        if other.units == 0 {
+            return None;
+        }
+        // One extra digit so the final rescale can round
+        let exp = (scale + 1 + other.scale).checked_sub(self.scale);
+        let (numerator, denominator) = match exp {
+            Some(exp) => (self.units.checked_mul(pow10(exp)?)?, other.units),
+            None => (self.units, other.units.checked_mul(pow10(self.scale - scale - 1 - other.scale)?)?),
+        };
+        Decimal::new(numerator / denominator, scale + 1).rescale(scale)
+    }
+
+    /// Exact sum; `None` for no values or on overflow
+    pub fn sum<'a>(values: impl IntoIterator<Item = &'a Decimal>) -> Option<Self> {
+        let mut values = values.into_iter();
+        let first = *values.next()?;
+        values.try_fold(first, |total, value| total.checked_add(value))
+    }
+
+    /// Mean rounded to the widest scale among the inputs
+    pub fn avg<'a>(values: impl IntoIterator<Item = &'a Decimal> + Clone) -> Option<Self> {
+        let count = values.clone().into_iter().count();
+        let scale = values.clone().into_iter().map(|value| value.scale).max()?;
+        Self::sum(values)?.checked_div(&Decimal::new(count as i128, 0), scale)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.units as f64 / 10f64.powi(self.scale as i32)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.aligned(other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // Too wide to align exactly; the integer parts differ by far more than rounding
+            None => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let sign = if self.units < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(dec("19.99").to_string(), "19.99");
+        assert_eq!(dec("-0.05").to_string(), "-0.05");
+        assert_eq!(dec("42").to_string(), "42");
+        assert_eq!(Decimal::from_f64(19.99).unwrap(), dec("19.99"));
+        assert!(Decimal::parse("1.2.3").is_none());
+        assert!(Decimal::parse("abc").is_none());
+        assert_eq!(dec("123.45").precision(), 5);
+        assert_eq!(dec("0.05").precision(), 2);
+    }
+
+    #[test]
+    fn test_rescale_rounds_half_away_from_zero() {
+        assert_eq!(dec("2.345").rescale(2).unwrap().to_string(), "2.35");
+        assert_eq!(dec("-2.345").rescale(2).unwrap().to_string(), "-2.35");
+        assert_eq!(dec("2.344").rescale(2).unwrap().to_string(), "2.34");
+        assert_eq!(dec("2.5").rescale(3).unwrap().to_string(), "2.500");
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        assert_eq!(dec("0.1").checked_add(&dec("0.2")).unwrap(), dec("0.3"));
+        assert_eq!(dec("1.50").checked_sub(&dec("0.25")).unwrap().to_string(), "1.25");
+        assert_eq!(dec("1.5").checked_mul(&dec("2.25")).unwrap().to_string(), "3.375");
+        assert_eq!(dec("10").checked_div(&dec("3"), 2).unwrap().to_string(), "3.33");
+        assert_eq!(dec("2").checked_div(&dec("3"), 2).unwrap().to_string(), "0.67");
+        assert!(dec("1").checked_div(&dec("0"), 2).is_none());
+        assert!(dec("1.5") > dec("1.49"));
+        assert_eq!(dec("1.50"), dec("1.5"));
+    }
+
+    #[test]
+    fn test_sum_and_avg_are_exact() {
+        let values = vec![dec("0.10"); 10];
+        assert_eq!(Decimal::sum(&values).unwrap().to_string(), "1.00");
+        assert_eq!(Decimal::avg(&values).unwrap().to_string(), "0.10");
+        assert!(Decimal::sum(&[]).is_none());
+    }
+}
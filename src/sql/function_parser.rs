@@ -51,20 +51,20 @@ fn identifier(input: &str) -> IResult<&str, &str> {
 // Parser for a single argument
 fn parse_argument(input: &str) -> IResult<&str, DataValue> {
     alt((
-        map(tag("*"), |s: &str| DataValue::Text(s.to_string())),
+        map(tag("*"), |s: &str| DataValue::Text(s.into())),
         map(
             delimited(
                 tag("'"),
                 nom::bytes::complete::take_until("'"),
                 tag("'")
             ),
-            |s: &str| DataValue::Text(s.to_string())
+            |s: &str| DataValue::Text(s.into())
         ),
         map(parse_function_call, |f| DataValue::Function {
             name: f.name,
             args: f.args,
         }),
-        map(identifier, |s: &str| DataValue::Text(s.to_string())),
+        map(identifier, |s: &str| DataValue::Column(s.to_string())),
     ))(input)
 }
 
@@ -134,7 +134,7 @@ mod tests {
         let (remaining, result) = parse_function(input).unwrap();
         assert_eq!(remaining, "");
         assert_eq!(result.name, "count");
-        assert_eq!(result.args, vec![DataValue::Text("*".to_string())]);
+        assert_eq!(result.args, vec![DataValue::Text("*".into())]);
         assert_eq!(result.alias, None);
     }
 
@@ -144,7 +144,7 @@ mod tests {
         let (remaining, result) = parse_function(input).unwrap();
         assert_eq!(remaining, "");
         assert_eq!(result.name, "count");
-        assert_eq!(result.args, vec![DataValue::Text("*".to_string())]);
+        assert_eq!(result.args, vec![DataValue::Text("*".into())]);
         assert_eq!(result.alias, Some("total".to_string()));
     }
 
@@ -154,7 +154,7 @@ mod tests {
         let (remaining, result) = parse_function(input).unwrap();
         assert_eq!(remaining, "");
         assert_eq!(result.name, "to_tsquery");
-        assert_eq!(result.args, vec![DataValue::Text("rust & web".to_string())]);
+        assert_eq!(result.args, vec![DataValue::Text("rust & web".into())]);
     }
 
     #[test]
@@ -170,7 +170,7 @@ mod tests {
         match &result.args[0] {
             DataValue::Function { name, args } => {
                 assert_eq!(name, "to_tsvector");
-                assert_eq!(*args, vec![DataValue::Text("content".to_string())]);
+                assert_eq!(*args, vec![DataValue::Column("content".to_string())]);
             },
             _ => panic!("Expected a nested function"),
         }
@@ -178,7 +178,7 @@ mod tests {
         match &result.args[1] {
             DataValue::Function { name, args } => {
                 assert_eq!(name, "to_tsquery");
-                assert_eq!(*args, vec![DataValue::Text("rust".to_string())]);
+                assert_eq!(*args, vec![DataValue::Text("rust".into())]);
             },
             _ => panic!("Expected a nested function"),
         }
@@ -0,0 +1,105 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+
+/// Parsed JSON document. Serialized as its compact text, since row encodings such as
+/// bincode can't hold a self-describing `serde_json::Value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json(Value);
+
+enum PathStep<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+impl Json {
+    pub fn new(value: Value) -> Self {
+        Json(value)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map(Json).map_err(|e| format!("Invalid JSON: {}", e))
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    /// Follows a path such as `$.items[0].name`: `$` is the document, `.key` selects an
+    /// object member and `[n]` an array element. A malformed path or a missing member
+    /// gives `None`.
+    pub fn extract(&self, path: &str) -> Option<&Value> {
+        Self::parse_path(path)?.into_iter().try_fold(&self.0, |value, step| match step {
+            PathStep::Key(key) => value.as_object()?.get(key),
+            PathStep::Index(index) => value.as_array()?.get(index),
+        })
+    }
+
+    fn parse_path(path: &str) -> Option<Vec<PathStep<'_>>> {
+        let mut rest = path.trim().strip_prefix('$')?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                if end == 0 {
+                    return None;
+                }
+                steps.push(PathStep::Key(&after_dot[..end]));
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']')?;
+                steps.push(PathStep::Index(after_bracket[..end].trim().parse().ok()?));
+                rest = &after_bracket[end + 1..];
+            } else {
+                return None;
+            }
+        }
+        Some(steps)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Json {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Json::parse(&text).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_keys_and_indices() {
+        let doc = Json::parse(r#"{"user": {"name": "ann", "tags": ["a", "b"]}, "n": 3}"#).unwrap();
+        assert_eq!(doc.extract("$.user.name"), Some(&json!("ann")));
+        assert_eq!(doc.extract("$.user.tags[1]"), Some(&json!("b")));
+        assert_eq!(doc.extract("$.n"), Some(&json!(3)));
+        assert_eq!(doc.extract("$"), Some(doc.value()));
+        assert_eq!(doc.extract("$.user.tags[5]"), None);
+        assert_eq!(doc.extract("$.missing"), None);
+        assert_eq!(doc.extract("user.name"), None);
+        assert_eq!(doc.extract("$.user..name"), None);
+        assert_eq!(doc.extract("$.user.tags[x]"), None);
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let doc = Json::parse(r#"{"a": [1, 2.5, null]}"#).unwrap();
+        let bytes = bincode::serialize(&doc).unwrap();
+        assert_eq!(bincode::deserialize::<Json>(&bytes).unwrap(), doc);
+    }
+}
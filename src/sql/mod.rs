@@ -1,14 +1,22 @@
 pub mod clauses;
 pub mod column;
+pub mod collation;
 pub mod column_def;
 pub mod column_value_pair;
 pub mod data_value;
+pub mod decimal;
+pub mod json;
+pub mod uuid;
 pub mod operators;
 pub mod parser;
+pub mod script;
 pub mod statements;
 pub mod table_reference;
 pub mod data_type;
 pub mod constraints;
 pub mod table;
 pub mod function_parser;
+pub mod case_expression;
+pub mod window;
+pub mod aggregate;
 pub use self::statements::Statement;
@@ -1,4 +1,11 @@
-use nom::{branch::alt, IResult, combinator::map, bytes::complete::{tag_no_case, tag}};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::multispace1,
+    combinator::{map, recognize},
+    sequence::tuple,
+    IResult,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Op {
@@ -10,12 +17,36 @@ pub enum Op {
     GreaterThanOrEqual,
     LessThanOrEqual,
     TextSearch,
+    /// `@>`: the array on the left holds every element of the right side
+    Contains,
+    /// `LIKE`: `%` matches any run of characters and `_` any single character
+    Like,
+    /// `<=>` or `IS NOT DISTINCT FROM`: equality under which NULL equals NULL
+    NullSafeEqual,
+}
+
+/// `<=>` or its spelled-out form `IS NOT DISTINCT FROM`
+pub fn null_safe_equal(input: &str) -> IResult<&str, &str> {
+    alt((
+        tag("<=>"),
+        recognize(tuple((
+            tag_no_case("IS"),
+            multispace1,
+            tag_no_case("NOT"),
+            multispace1,
+            tag_no_case("DISTINCT"),
+            multispace1,
+            tag_no_case("FROM"),
+        ))),
+    ))(input)
 }
 
 impl Op {
     pub fn parse(input: &str) -> IResult<&str, Op> {
         alt((
             map(tag("@@"), |_| Op::TextSearch),
+            map(tag("@>"), |_| Op::Contains),
+            map(null_safe_equal, |_| Op::NullSafeEqual),
             map(tag_no_case(">="), |_| Op::GreaterThanOrEqual),
             map(tag_no_case("<="), |_| Op::LessThanOrEqual),
             map(tag_no_case(">"), |_| Op::GreaterThan),
@@ -23,12 +54,23 @@ impl Op {
             map(tag_no_case("="), |_| Op::Equal),
             map(tag_no_case("MATCH"), |_| Op::Match),
             map(tag_no_case("!="), |_| Op::NotEqual),
+            map(tag_no_case("LIKE"), |_| Op::Like),
         ))(input)
     }
 
     pub fn evaluate(&self, left: &crate::sql::data_value::DataValue, right: &crate::sql::data_value::DataValue) -> bool {
+        use crate::sql::data_value::DataValue;
+        // Decimals compare by value against other numbers, UUIDs against text in UUID form
+        let decimal = matches!(left, DataValue::Decimal(_)) || matches!(right, DataValue::Decimal(_))
+            || matches!((left, right), (DataValue::Uuid(_), DataValue::Text(_)) | (DataValue::Text(_), DataValue::Uuid(_)));
+        // `=` and `!=` are never true when either side is NULL; `<=>` treats two NULLs as equal
+        let null = matches!(left, DataValue::Null) || matches!(right, DataValue::Null);
         match self {
-            Op::Equal => left == right,
+            Op::NullSafeEqual if null => left == right,
+            Op::Equal | Op::NotEqual if null => false,
+            Op::Equal | Op::NullSafeEqual if decimal => left.partial_cmp(right) == Some(std::cmp::Ordering::Equal),
+            Op::NotEqual if decimal => left.partial_cmp(right) != Some(std::cmp::Ordering::Equal),
+            Op::Equal | Op::NullSafeEqual => left == right,
             Op::NotEqual => left != right,
             Op::GreaterThan => left > right,
             Op::LessThan => left < right,
@@ -36,8 +78,48 @@ impl Op {
             Op::LessThanOrEqual => left <= right,
             Op::Match => false, // FTS matching is handled separately
             Op::TextSearch => false, // Full-text search matching is handled separately in the FTS module
+            Op::Contains => left.contains(right),
+            Op::Like => match (left, right) {
+                (DataValue::Text(text), DataValue::Text(pattern)) => like(text, pattern),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Literal text a LIKE pattern starts with, up to its first wildcard
+pub fn like_prefix(pattern: &str) -> &str {
+    let end = pattern.find(['%', '_']).unwrap_or(pattern.len());
+    &pattern[..end]
+}
+
+// Backtracks to the last `%` on a mismatch, letting it absorb one more character
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    backtrack = Some((star, absorbed + 1));
+                    p = star + 1;
+                    t = absorbed + 1;
+                }
+                None => return false,
+            },
         }
     }
+    pattern[p..].iter().all(|&c| c == '%')
 }
 
 #[cfg(test)]
@@ -55,6 +137,38 @@ mod tests {
         assert_eq!(Op::parse(">="), Ok(("", Op::GreaterThanOrEqual)));
         assert_eq!(Op::parse("<="), Ok(("", Op::LessThanOrEqual)));
         assert_eq!(Op::parse("@@"), Ok(("", Op::TextSearch)));
+        assert_eq!(Op::parse("@>"), Ok(("", Op::Contains)));
+        assert_eq!(Op::parse("LIKE"), Ok(("", Op::Like)));
+        assert_eq!(Op::parse("<=>"), Ok(("", Op::NullSafeEqual)));
+        assert_eq!(Op::parse("is not  distinct from"), Ok(("", Op::NullSafeEqual)));
+    }
+
+    #[test]
+    fn null_safe_equal_test() {
+        let null = DataValue::Null;
+        let one = DataValue::Integer(1);
+        assert!(!Op::Equal.evaluate(&null, &null));
+        assert!(Op::NullSafeEqual.evaluate(&null, &null));
+        assert!(!Op::Equal.evaluate(&null, &one));
+        assert!(!Op::NullSafeEqual.evaluate(&null, &one));
+        assert!(!Op::NotEqual.evaluate(&null, &one));
+        assert!(Op::Equal.evaluate(&one, &one));
+        assert!(Op::NullSafeEqual.evaluate(&one, &one));
+    }
+
+    #[test]
+    fn like_test() {
+        let text = |s: &str| DataValue::Text(s.into());
+        assert!(Op::Like.evaluate(&text("Book of Days"), &text("Book%")));
+        assert!(Op::Like.evaluate(&text("Book"), &text("Book%")));
+        assert!(!Op::Like.evaluate(&text("A Book"), &text("Book%")));
+        assert!(Op::Like.evaluate(&text("A Book"), &text("%Book")));
+        assert!(Op::Like.evaluate(&text("abcbd"), &text("a%b_")));
+        assert!(!Op::Like.evaluate(&text("abc"), &text("a_")));
+        assert!(!Op::Like.evaluate(&DataValue::Null, &text("%")));
+        assert_eq!(super::like_prefix("Book%"), "Book");
+        assert_eq!(super::like_prefix("B_ok"), "B");
+        assert_eq!(super::like_prefix("%Book"), "");
     }
 
     #[test]
@@ -1,20 +1,145 @@
-use nom::IResult;
-use crate::sql::statements::Statement;
+use nom::error::ErrorKind;
+use crate::sql::{script::{split_statements, strip_comments}, statements::Statement};
 use crate::error::ReefDBError;
 
 pub struct Parser;
 
 impl Parser {
+    /// Parses one statement. `--` and `/* */` comments may appear anywhere outside string
+    /// literals.
     pub fn parse_sql(input: &str) -> Result<Statement, ReefDBError> {
-        match Statement::parse(input) {
-            Ok((remaining, stmt)) => {
-                if remaining.trim().is_empty() {
-                    Ok(stmt)
-                } else {
-                    Err(ReefDBError::Other(format!("Unexpected input after statement: {}", remaining)))
-                }
-            }
-            Err(e) => Err(ReefDBError::Other(format!("Failed to parse SQL: {}", e))),
+        let input = strip_comments(input);
+        Statement::parse(&input)
+            .map(|(_, stmt)| stmt)
+            .map_err(|e| parse_error(&input, 0, e))
+    }
+
+    /// Parses a script of statements separated by semicolons. A statement that doesn't
+    /// parse fails the whole script, with a position relative to the start of `input`.
+    pub fn parse_statements(input: &str) -> Result<Vec<Statement>, ReefDBError> {
+        split_statements(input).into_iter()
+            .map(|(offset, text)| {
+                let text = strip_comments(text);
+                Statement::parse(&text)
+                    .map(|(_, stmt)| stmt)
+                    .map_err(|e| parse_error(&text, offset, e))
+            })
+            .collect()
+    }
+}
+
+/// Describes where parsing `text`, which starts at byte `offset` of the caller's input,
+/// stopped and what the parser expected there.
+fn parse_error(text: &str, offset: usize, error: nom::Err<nom::error::Error<&str>>) -> ReefDBError {
+    let (rest, kind) = match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => (e.input.trim_start(), Some(e.code)),
+        nom::Err::Incomplete(_) => ("", None),
+    };
+    let found = match rest.split_whitespace().next() {
+        Some(token) => format!("'{}'", token),
+        None => "end of input".to_string(),
+    };
+    let message = if rest.len() == text.trim_start().len() {
+        format!("expected a statement such as SELECT, INSERT, UPDATE or CREATE, found {}", found)
+    } else {
+        format!("expected {}, found {}", kind.map_or("more input", expected), found)
+    };
+    ReefDBError::ParseError { message, position: offset + text.len() - rest.len() }
+}
+
+fn expected(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Eof => "end of statement",
+        ErrorKind::Tag | ErrorKind::Char | ErrorKind::OneOf => "a keyword or symbol",
+        ErrorKind::Digit | ErrorKind::Float => "a number",
+        ErrorKind::Alpha | ErrorKind::AlphaNumeric | ErrorKind::TakeWhile1 => "a name",
+        ErrorKind::MultiSpace | ErrorKind::Space => "whitespace",
+        _ => "valid SQL",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{data_value::DataValue, statements::insert::InsertStatement};
+
+    #[test]
+    fn test_parse_sql_skips_comments() {
+        let plain = Parser::parse_sql("SELECT id, name FROM users WHERE id = 1").unwrap();
+        for sql in [
+            "-- leading comment\nSELECT id, name FROM users WHERE id = 1",
+            "/* leading */ SELECT id, name FROM users WHERE id = 1",
+            "SELECT id, name FROM users WHERE id = 1 -- trailing comment",
+            "SELECT id, name FROM users WHERE id = 1 /* trailing */",
+            "SELECT id, /* inline */ name FROM users -- why\n WHERE id = 1",
+            "SELECT id,/* no spaces */name FROM users WHERE id = 1",
+        ] {
+            assert_eq!(Parser::parse_sql(sql).unwrap(), plain, "{}", sql);
+        }
+
+        // Comment markers inside string literals are text
+        assert_eq!(
+            Parser::parse_sql("INSERT INTO notes VALUES (1, '-- not a comment /* nor this */') -- but this is").unwrap(),
+            Statement::Insert(InsertStatement::IntoTable("notes".to_string(), vec![
+                DataValue::Integer(1),
+                DataValue::Text("-- not a comment /* nor this */".into()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_statements() {
+        let script = "
+            CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);
+            -- seed data
+            INSERT INTO notes VALUES (1, 'a; b');
+            SELECT * FROM notes /* ; */;
+        ";
+        let statements = Parser::parse_statements(script).unwrap();
+        assert_eq!(statements.len(), 3);
+        assert!(matches!(statements[0], Statement::Create(_)));
+        assert_eq!(statements[1], Statement::Insert(InsertStatement::IntoTable("notes".to_string(), vec![
+            DataValue::Integer(1),
+            DataValue::Text("a; b".into()),
+        ])));
+        assert!(matches!(statements[2], Statement::Select(_)));
+        assert_eq!(Parser::parse_statements("  ; -- nothing\n").unwrap(), vec![]);
+
+        // The second statement fails where its garbage starts
+        let script = "SELECT * FROM notes; SELECT * FROM notes WHERE id = 1 garbage";
+        match Parser::parse_statements(script) {
+            Err(ReefDBError::ParseError { position, .. }) => assert_eq!(position, script.find("garbage").unwrap()),
+            other => panic!("Expected a parse error, got {:?}", other),
         }
+        assert!(Parser::parse_statements("SELECT * FROM notes; SELEC * FROM notes").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let parse_error = |sql| match Parser::parse_sql(sql) {
+            Err(ReefDBError::ParseError { message, position }) => (message, position),
+            other => panic!("Expected a parse error for {}, got {:?}", sql, other),
+        };
+
+        let (message, position) = parse_error("SELECT name FORM users");
+        assert_eq!(position, 12);
+        assert_eq!(message, "expected a keyword or symbol, found 'FORM'");
+
+        let (message, position) = parse_error("  SELEC * FROM users");
+        assert_eq!(position, 2);
+        assert!(message.contains("found 'SELEC'"), "{}", message);
+
+        let sql = "INSERT INTO users VALUES (1, 'a' 'b')";
+        assert_eq!(parse_error(sql).1, sql.find("'b'").unwrap());
+
+        let sql = "-- set up\nCREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT";
+        let (message, position) = parse_error(sql);
+        assert_eq!(position, sql.len());
+        assert!(message.ends_with("found end of input"), "{}", message);
+
+        assert_eq!(
+            Parser::parse_sql("SELECT id FROM users WHERE id = 1 extra").unwrap_err().to_string(),
+            "Parse error at byte 34: expected end of statement, found 'extra'",
+        );
     }
-} 
\ No newline at end of file
+}
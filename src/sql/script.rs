@@ -0,0 +1,123 @@
+use std::{borrow::Cow, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Segment {
+    Code,
+    /// `'text'` or `"identifier"`, a doubled quote standing for itself
+    Quoted,
+    /// `-- ...` up to the end of the line, or `/* ... */`
+    Comment,
+}
+
+// Splits SQL text into runs of code, quoted text and comments. An unterminated string
+// or block comment runs to the end of the input.
+fn segments(input: &str) -> Vec<(Segment, Range<usize>)> {
+    let bytes = input.as_bytes();
+    let mut segments = Vec::new();
+    let mut code_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let end = match (bytes[i], bytes.get(i + 1)) {
+            (quote @ (b'\'' | b'"'), _) => {
+                let mut end = i + 1;
+                loop {
+                    match bytes[end..].iter().position(|&b| b == quote) {
+                        Some(offset) if bytes.get(end + offset + 1) == Some(&quote) => end += offset + 2,
+                        Some(offset) => break end + offset + 1,
+                        None => break bytes.len(),
+                    }
+                }
+            }
+            (b'-', Some(b'-')) => input[i..].find('\n').map_or(bytes.len(), |offset| i + offset),
+            (b'/', Some(b'*')) => input[i + 2..].find("*/").map_or(bytes.len(), |offset| i + 2 + offset + 2),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        if code_start < i {
+            segments.push((Segment::Code, code_start..i));
+        }
+        let segment = if matches!(bytes[i], b'\'' | b'"') { Segment::Quoted } else { Segment::Comment };
+        segments.push((segment, i..end));
+        i = end;
+        code_start = end;
+    }
+    if code_start < bytes.len() {
+        segments.push((Segment::Code, code_start..bytes.len()));
+    }
+    segments
+}
+
+/// Blanks out `-- line` and `/* block */` comments, leaving string literals alone. Each
+/// comment byte becomes a space, except newlines, so offsets into the result still point
+/// at the same place in the original text.
+pub fn strip_comments(input: &str) -> Cow<'_, str> {
+    let segments = segments(input);
+    if !segments.iter().any(|(segment, _)| *segment == Segment::Comment) {
+        return Cow::Borrowed(input);
+    }
+    let mut stripped = String::with_capacity(input.len());
+    for (segment, range) in segments {
+        let text = &input[range];
+        if segment != Segment::Comment {
+            stripped.push_str(text);
+            continue;
+        }
+        for c in text.chars() {
+            match c {
+                '\n' => stripped.push('\n'),
+                c => stripped.extend(std::iter::repeat_n(' ', c.len_utf8())),
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Byte offset and text of each statement of a script, split at semicolons outside string
+/// literals and comments. Pieces holding nothing but whitespace and comments are skipped.
+pub fn split_statements(input: &str) -> Vec<(usize, &str)> {
+    let mut boundaries = Vec::new();
+    for (segment, range) in segments(input) {
+        if segment == Segment::Code {
+            boundaries.extend(input[range.clone()].match_indices(';').map(|(offset, _)| range.start + offset));
+        }
+    }
+    boundaries.push(input.len());
+
+    let mut start = 0;
+    let mut statements = Vec::new();
+    for end in boundaries {
+        let text = &input[start..end];
+        if !strip_comments(text).trim().is_empty() {
+            statements.push((start, text));
+        }
+        start = end + 1;
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_comments_keeps_offsets_and_strings() {
+        assert!(matches!(strip_comments("SELECT * FROM users"), Cow::Borrowed(_)));
+        assert_eq!(strip_comments("SELECT 1 -- one\nFROM t"), "SELECT 1       \nFROM t");
+        assert_eq!(strip_comments("a/* é */b"), "a        b");
+        assert_eq!(strip_comments("'-- kept' /* gone */"), "'-- kept'           ");
+        assert_eq!(strip_comments("'it''s /* here */'"), "'it''s /* here */'");
+        assert_eq!(strip_comments("x /* open"), "x        ");
+    }
+
+    #[test]
+    fn test_split_statements_outside_strings_and_comments() {
+        let script = "INSERT INTO t VALUES ('a;b'); -- c;d\nSELECT 1 /* ; */;; ";
+        assert_eq!(split_statements(script), vec![
+            (0, "INSERT INTO t VALUES ('a;b')"),
+            (29, " -- c;d\nSELECT 1 /* ; */"),
+        ]);
+        assert!(split_statements(" -- only a comment\n").is_empty());
+    }
+}
@@ -1,24 +1,45 @@
 use crate::sql::column_def::{ColumnDef, table_name};
 use nom::{
+    branch::alt,
     bytes::complete::{tag, tag_no_case},
-    character::complete::{multispace0, multispace1},
+    character::complete::{digit1, multispace0, multispace1},
     multi::separated_list1,
     sequence::{delimited, tuple, terminated},
-    combinator::opt,
+    combinator::{cond, map_res, opt},
     IResult,
 };
+use std::time::Duration;
 
 use super::Statement;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum CreateStatement {
-    Table(String, Vec<ColumnDef>),
+    /// Table name, column definitions and whether `IF NOT EXISTS` was given
+    Table(String, Vec<ColumnDef>, bool),
+    /// `CREATE TABLE ... WITH (TTL = <seconds>)`: a table whose rows expire once they are
+    /// older than the duration
+    TableWithTtl(String, Vec<ColumnDef>, bool, Duration),
+    /// `CREATE TEMPORARY TABLE`: a table that only the creating transaction sees and that is
+    /// dropped when it commits or rolls back. It hides a table of the same name meanwhile.
+    TemporaryTable(String, Vec<ColumnDef>, bool),
 }
 
 impl CreateStatement {
     pub fn parse(input: &str) -> IResult<&str, Statement> {
-        let (input, _) = tag_no_case("CREATE TABLE")(input)?;
-        let (input, _) = multispace1(input)?;
+        let (input, _) = tuple((tag_no_case("CREATE"), multispace1))(input)?;
+        let (input, temporary) = opt(terminated(
+            alt((tag_no_case("TEMPORARY"), tag_no_case("TEMP"))),
+            multispace1,
+        ))(input)?;
+        let (input, _) = tuple((tag_no_case("TABLE"), multispace1))(input)?;
+        let (input, if_not_exists) = opt(tuple((
+            tag_no_case("IF"),
+            multispace1,
+            tag_no_case("NOT"),
+            multispace1,
+            tag_no_case("EXISTS"),
+            multispace1,
+        )))(input)?;
         let (input, table_name) = table_name(input)?;
         let (input, _) = multispace0(input)?;
         let (input, columns) = delimited(
@@ -29,14 +50,32 @@ impl CreateStatement {
             ),
             tuple((multispace0, opt(tuple((tag_no_case(","), multispace0))), tag_no_case(")"))),
         )(input)?;
+        // Temporary tables are gone before any row could expire
+        let (input, ttl) = cond(temporary.is_none(), opt(parse_ttl))(input)?;
 
+        let (name, if_not_exists) = (table_name.to_string(), if_not_exists.is_some());
         Ok((
             input,
-            Statement::Create(CreateStatement::Table(table_name.to_string(), columns)),
+            Statement::Create(match (temporary, ttl.flatten()) {
+                (Some(_), _) => CreateStatement::TemporaryTable(name, columns, if_not_exists),
+                (None, Some(ttl)) => CreateStatement::TableWithTtl(name, columns, if_not_exists, ttl),
+                (None, None) => CreateStatement::Table(name, columns, if_not_exists),
+            }),
         ))
     }
 }
 
+// ` WITH (TTL = <seconds>)`
+fn parse_ttl(input: &str) -> IResult<&str, Duration> {
+    let (input, _) = tuple((
+        multispace1, tag_no_case("WITH"), multispace0, tag("("), multispace0,
+        tag_no_case("TTL"), multispace0, tag("="), multispace0,
+    ))(input)?;
+    let (input, seconds) = map_res(digit1, str::parse::<u64>)(input)?;
+    let (input, _) = tuple((multispace0, tag(")")))(input)?;
+    Ok((input, Duration::from_secs(seconds)))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::sql::{data_type::DataType, statements::Statement};
@@ -70,7 +109,8 @@ mod tests {
                             data_type: DataType::TSVector,
                             constraints: vec![],
                         },
-                    ]
+                    ],
+                    false,
                 ))
             ))
         );
@@ -99,9 +139,71 @@ mod tests {
                             data_type: DataType::Text,
                             constraints: vec![],
                         },
-                    ]
+                    ],
+                    false,
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_if_not_exists_test() {
+        use super::CreateStatement;
+        use crate::sql::column_def::ColumnDef;
+
+        assert_eq!(
+            CreateStatement::parse("CREATE TABLE IF NOT EXISTS users (id INTEGER)"),
+            Ok((
+                "",
+                Statement::Create(CreateStatement::Table(
+                    "users".to_string(),
+                    vec![ColumnDef::new("id", DataType::Integer, vec![])],
+                    true,
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_ttl_test() {
+        use super::CreateStatement;
+        use crate::sql::column_def::ColumnDef;
+        use std::time::Duration;
+
+        assert_eq!(
+            CreateStatement::parse("CREATE TABLE cache (id INTEGER) WITH (ttl = 60)"),
+            Ok((
+                "",
+                Statement::Create(CreateStatement::TableWithTtl(
+                    "cache".to_string(),
+                    vec![ColumnDef::new("id", DataType::Integer, vec![])],
+                    false,
+                    Duration::from_secs(60),
                 ))
             ))
         );
     }
+
+    #[test]
+    fn parse_temporary_test() {
+        use super::CreateStatement;
+        use crate::sql::column_def::ColumnDef;
+
+        let expected = Statement::Create(CreateStatement::TemporaryTable(
+            "scratch".to_string(),
+            vec![ColumnDef::new("id", DataType::Integer, vec![])],
+            false,
+        ));
+        assert_eq!(CreateStatement::parse("CREATE TEMPORARY TABLE scratch (id INTEGER)"), Ok(("", expected.clone())));
+        assert_eq!(CreateStatement::parse("create temp table scratch (id INTEGER)"), Ok(("", expected)));
+        assert!(matches!(
+            CreateStatement::parse("CREATE TEMP TABLE IF NOT EXISTS scratch (id INTEGER)"),
+            Ok(("", Statement::Create(CreateStatement::TemporaryTable(_, _, true))))
+        ));
+        // No TTL on a temporary table
+        assert_eq!(
+            CreateStatement::parse("CREATE TEMP TABLE scratch (id INTEGER) WITH (TTL = 5)").map(|(input, _)| input),
+            Ok(" WITH (TTL = 5)"),
+        );
+    }
 }
@@ -1,46 +1,84 @@
 use nom::{
+    branch::alt,
     bytes::complete::{tag_no_case, tag},
     character::complete::{multispace0, multispace1, alphanumeric1},
-    combinator::opt,
-    sequence::tuple,
+    combinator::{map, opt, verify},
+    sequence::{terminated, tuple},
     IResult,
 };
 
 use super::Statement;
+use crate::sql::column_value_pair::identifier;
+use crate::sql::data_value::DataValue;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum IndexType {
     BTree,
     GIN,
+    /// B-Tree keyed by the raw text, so values sort as strings and prefix LIKE can scan a range
+    Text,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CreateIndexStatement {
     pub table_name: String,
+    /// Indexed column, or for an expression index the name it is stored under, e.g. `LOWER(email)`
     pub column_name: String,
     pub index_type: IndexType,
+    /// Function call whose result is indexed instead of a plain column
+    pub expression: Option<DataValue>,
+    /// CREATE UNIQUE INDEX: no two rows may share a non-NULL indexed value
+    pub unique: bool,
+}
+
+/// Name an expression index is stored under, e.g. `LOWER(email)`. A WHERE clause comparing
+/// the same call finds the index by this name.
+pub fn expression_key(expression: &DataValue) -> String {
+    match expression {
+        DataValue::Function { name, args } => format!(
+            "{}({})",
+            name.to_uppercase(),
+            args.iter().map(expression_key).collect::<Vec<_>>().join(", ")
+        ),
+        other => other.to_string(),
+    }
 }
 
 impl CreateIndexStatement {
     pub fn parse(input: &str) -> IResult<&str, Statement> {
         let (input, _) = tag_no_case("CREATE")(input)?;
         let (input, _) = multispace1(input)?;
+        let (input, unique) = opt(tuple((
+            tag_no_case("UNIQUE"),
+            multispace1
+        )))(input)?;
         
         // Optional index type
-        let (input, index_type) = opt(tuple((
-            tag_no_case("GIN"),
+        let (input, index_type) = opt(terminated(
+            alt((
+                map(tag_no_case("GIN"), |_| IndexType::GIN),
+                map(tag_no_case("TEXT"), |_| IndexType::Text),
+            )),
             multispace1
-        )))(input)?;
+        ))(input)?;
         
         let (input, _) = tag_no_case("INDEX")(input)?;
         let (input, _) = multispace1(input)?;
+        // Indexes are addressed by table and column, so a name is accepted but not kept
+        let (input, _) = opt(terminated(
+            verify(identifier, |name: &str| !name.eq_ignore_ascii_case("ON")),
+            multispace1,
+        ))(input)?;
         let (input, _) = tag_no_case("ON")(input)?;
         let (input, _) = multispace1(input)?;
         let (input, table_name) = alphanumeric1(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = tag("(")(input)?;
         let (input, _) = multispace0(input)?;
-        let (input, column_name) = alphanumeric1(input)?;
+        let (input, (column_name, expression)) = alt((
+            map(DataValue::parse_function, |function| (expression_key(&function), Some(function))),
+            map(alphanumeric1, |column: &str| (column.to_string(), None)),
+        ))(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = tag(")")(input)?;
         
@@ -48,8 +86,10 @@ impl CreateIndexStatement {
             input,
             Statement::CreateIndex(CreateIndexStatement {
                 table_name: table_name.to_string(),
-                column_name: column_name.to_string(),
-                index_type: if index_type.is_some() { IndexType::GIN } else { IndexType::BTree },
+                column_name,
+                index_type: index_type.unwrap_or(IndexType::BTree),
+                expression,
+                unique: unique.is_some(),
             }),
         ))
     }
@@ -69,6 +109,7 @@ mod tests {
                 assert_eq!(stmt.table_name, "users");
                 assert_eq!(stmt.column_name, "id");
                 assert_eq!(stmt.index_type, IndexType::BTree);
+                assert!(!stmt.unique);
             }
             _ => panic!("Expected CreateIndex statement"),
         }
@@ -103,4 +144,53 @@ mod tests {
             _ => panic!("Expected CreateIndex statement"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_expression_index_parse() {
+        let input = "CREATE INDEX idx_email ON users (lower(email))";
+        let (remaining, stmt) = CreateIndexStatement::parse(input).unwrap();
+        assert_eq!(remaining, "");
+        match stmt {
+            Statement::CreateIndex(stmt) => {
+                assert_eq!(stmt.table_name, "users");
+                assert_eq!(stmt.column_name, "LOWER(email)");
+                assert_eq!(stmt.index_type, IndexType::BTree);
+                assert_eq!(stmt.expression, Some(DataValue::Function {
+                    name: "lower".to_string(),
+                    args: vec![DataValue::Column("email".to_string())],
+                }));
+            }
+            _ => panic!("Expected CreateIndex statement"),
+        }
+    }
+
+    #[test]
+    fn test_create_unique_index_parse() {
+        let input = "CREATE UNIQUE INDEX idx_email ON users (email)";
+        let (remaining, stmt) = CreateIndexStatement::parse(input).unwrap();
+        assert_eq!(remaining, "");
+        match stmt {
+            Statement::CreateIndex(stmt) => {
+                assert_eq!(stmt.table_name, "users");
+                assert_eq!(stmt.column_name, "email");
+                assert_eq!(stmt.index_type, IndexType::BTree);
+                assert!(stmt.unique);
+            }
+            _ => panic!("Expected CreateIndex statement"),
+        }
+    }
+
+    #[test]
+    fn test_create_text_index_parse() {
+        let input = "CREATE TEXT INDEX ON books (name)";
+        let (remaining, stmt) = CreateIndexStatement::parse(input).unwrap();
+        assert_eq!(remaining, "");
+        match stmt {
+            Statement::CreateIndex(stmt) => {
+                assert_eq!(stmt.column_name, "name");
+                assert_eq!(stmt.index_type, IndexType::Text);
+            }
+            _ => panic!("Expected CreateIndex statement"),
+        }
+    }
+}
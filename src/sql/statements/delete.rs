@@ -94,7 +94,7 @@ mod tests {
                 assert_eq!(table_name, "users");
                 assert_eq!(where_clause.col_name, "status");
                 assert_eq!(where_clause.operator, Op::Equal);
-                assert_eq!(where_clause.value, DataValue::Text("inactive".to_string()));
+                assert_eq!(where_clause.value, DataValue::Text("inactive".into()));
             }
             _ => panic!("Expected Delete statement with where clause"),
         }
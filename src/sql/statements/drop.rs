@@ -1,6 +1,8 @@
 use nom::{
     bytes::complete::tag_no_case,
     character::complete::{multispace1, alphanumeric1},
+    combinator::opt,
+    sequence::tuple,
     IResult,
 };
 
@@ -9,18 +11,26 @@ use super::Statement;
 #[derive(Debug, PartialEq, Clone)]
 pub struct DropStatement {
     pub table_name: String,
+    pub if_exists: bool,
 }
 
 impl DropStatement {
     pub fn parse(input: &str) -> IResult<&str, Statement> {
         let (input, _) = tag_no_case("DROP TABLE")(input)?;
         let (input, _) = multispace1(input)?;
+        let (input, if_exists) = opt(tuple((
+            tag_no_case("IF"),
+            multispace1,
+            tag_no_case("EXISTS"),
+            multispace1,
+        )))(input)?;
         let (input, table_name) = alphanumeric1(input)?;
         
         Ok((
             input,
             Statement::Drop(DropStatement {
                 table_name: table_name.to_string(),
+                if_exists: if_exists.is_some(),
             }),
         ))
     }
@@ -41,6 +51,20 @@ mod tests {
             statement,
             Statement::Drop(DropStatement {
                 table_name: "users".to_string(),
+                if_exists: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_drop_table_if_exists() {
+        let (remaining, statement) = DropStatement::parse("DROP TABLE IF EXISTS users").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            statement,
+            Statement::Drop(DropStatement {
+                table_name: "users".to_string(),
+                if_exists: true,
             })
         );
     }
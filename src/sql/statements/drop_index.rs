@@ -1,10 +1,14 @@
 use nom::{
+    branch::alt,
     bytes::complete::tag_no_case,
     character::complete::{multispace1, alphanumeric1},
+    combinator::map,
     IResult,
 };
 
 use super::Statement;
+use super::create_index::expression_key;
+use crate::sql::data_value::DataValue;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DropIndexStatement {
@@ -19,14 +23,17 @@ impl DropIndexStatement {
         let (input, table_name) = alphanumeric1(input)?;
         let (input, _) = multispace1(input)?;
         let (input, _) = tag_no_case("(")(input)?;
-        let (input, column_name) = alphanumeric1(input)?;
+        let (input, column_name) = alt((
+            map(DataValue::parse_function, |function| expression_key(&function)),
+            map(alphanumeric1, str::to_string),
+        ))(input)?;
         let (input, _) = tag_no_case(")")(input)?;
         
         Ok((
             input,
             Statement::DropIndex(DropIndexStatement {
                 table_name: table_name.to_string(),
-                column_name: column_name.to_string(),
+                column_name,
             }),
         ))
     }
@@ -49,4 +56,18 @@ mod tests {
             ))
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_drop_expression_index_parse() {
+        assert_eq!(
+            DropIndexStatement::parse("DROP INDEX ON users (lower(email))"),
+            Ok((
+                "",
+                Statement::DropIndex(DropIndexStatement {
+                    table_name: "users".to_string(),
+                    column_name: "LOWER(email)".to_string(),
+                })
+            ))
+        );
+    }
+}
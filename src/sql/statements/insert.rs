@@ -1,20 +1,66 @@
 use crate::sql::data_value::DataValue;
-use crate::sql::column_def::table_name;
+use crate::sql::column_def::{column_name, table_name};
 
 use nom::{
+    branch::alt,
     bytes::complete::{tag, tag_no_case},
     character::complete::{alphanumeric1, multispace0, multispace1},
-    multi::separated_list0,
-    sequence::{delimited, tuple},
+    multi::{separated_list0, separated_list1},
+    sequence::{delimited, preceded, tuple},
     IResult,
-    combinator::opt,
+    combinator::{map, opt},
 };
 
-use super::Statement;
+use super::{update::parse_assignments, Statement};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum InsertStatement {
     IntoTable(String, Vec<DataValue>),
+    /// `INSERT ... ON CONFLICT`: a row that clashes with an existing one on a PRIMARY KEY
+    /// or UNIQUE column is skipped or turned into an update of that row
+    Upsert(String, Vec<DataValue>, OnConflict),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OnConflict {
+    /// PRIMARY KEY or UNIQUE columns a clash is looked for on; empty means all of them
+    pub columns: Vec<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConflictAction {
+    DoNothing,
+    /// Assignments applied to the existing row
+    DoUpdate(Vec<(String, DataValue)>),
+}
+
+impl OnConflict {
+    // ON CONFLICT [(col, ...)] DO NOTHING | DO UPDATE SET col = value, ...
+    // DO UPDATE needs the columns, as it has to know which row it updates
+    fn parse(input: &str) -> IResult<&str, OnConflict> {
+        let (input, _) = tuple((tag_no_case("ON"), multispace1, tag_no_case("CONFLICT"), multispace0))(input)?;
+        let (input, columns) = opt(delimited(
+            tuple((tag("("), multispace0)),
+            separated_list1(tuple((multispace0, tag(","), multispace0)), column_name),
+            tuple((multispace0, tag(")"), multispace0)),
+        ))(input)?;
+        let columns: Vec<String> = columns.unwrap_or_default().into_iter().map(str::to_string).collect();
+        let (rest, action) = preceded(
+            tuple((tag_no_case("DO"), multispace1)),
+            alt((
+                map(tag_no_case("NOTHING"), |_| ConflictAction::DoNothing),
+                map(
+                    preceded(tuple((tag_no_case("UPDATE"), multispace1, tag_no_case("SET"), multispace1)), parse_assignments),
+                    ConflictAction::DoUpdate,
+                ),
+            )),
+        )(input)?;
+        if columns.is_empty() && matches!(action, ConflictAction::DoUpdate(_)) {
+            return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)));
+        }
+        Ok((rest, OnConflict { columns, action }))
+    }
 }
 
 impl InsertStatement {
@@ -49,11 +95,14 @@ impl InsertStatement {
         )(input)?;
 
         let values: Vec<DataValue> = values.into_iter().collect();
+        let (input, on_conflict) = opt(preceded(multispace1, OnConflict::parse))(input)?;
 
-        Ok((
-            input,
-            Statement::Insert(InsertStatement::IntoTable(table_name.to_string(), values)),
-        ))
+        let table_name = table_name.to_string();
+        let insert = match on_conflict {
+            Some(on_conflict) => InsertStatement::Upsert(table_name, values, on_conflict),
+            None => InsertStatement::IntoTable(table_name, values),
+        };
+        Ok((input, Statement::Insert(insert)))
     }
 }
 
@@ -72,7 +121,7 @@ mod tests {
                 assert_eq!(table_name, "users");
                 assert_eq!(values, vec![
                     DataValue::Integer(1),
-                    DataValue::Text("Alice".to_string()),
+                    DataValue::Text("Alice".into()),
                 ]);
             }
             _ => panic!("Expected Insert statement"),
@@ -89,7 +138,7 @@ mod tests {
                 assert_eq!(table_name, "users");
                 assert_eq!(values, vec![
                     DataValue::Integer(1),
-                    DataValue::Text("Alice".to_string()),
+                    DataValue::Text("Alice".into()),
                 ]);
             }
             _ => panic!("Expected Insert statement"),
@@ -106,12 +155,42 @@ mod tests {
                 assert_eq!(table_name, "articles");
                 assert_eq!(values, vec![
                     DataValue::Integer(1),
-                    DataValue::Text("Rust's Guide".to_string()),
-                    DataValue::Text("Learn Rust's features".to_string()),
-                    DataValue::Text("english".to_string()),
+                    DataValue::Text("Rust's Guide".into()),
+                    DataValue::Text("Learn Rust's features".into()),
+                    DataValue::Text("english".into()),
                 ]);
             }
             _ => panic!("Expected Insert statement"),
         }
     }
+
+    #[test]
+    fn parse_insert_on_conflict() {
+        let (remaining, stmt) = InsertStatement::parse("INSERT INTO users VALUES (1, 'Alice') ON CONFLICT DO NOTHING").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(stmt, Statement::Insert(InsertStatement::Upsert(
+            "users".to_string(),
+            vec![DataValue::Integer(1), DataValue::Text("Alice".into())],
+            OnConflict { columns: vec![], action: ConflictAction::DoNothing },
+        )));
+
+        let (remaining, stmt) = InsertStatement::parse(
+            "INSERT INTO users VALUES (1, 'Alice') ON CONFLICT (id) DO UPDATE SET name = 'Alice', visits = 2"
+        ).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(stmt, Statement::Insert(InsertStatement::Upsert(
+            "users".to_string(),
+            vec![DataValue::Integer(1), DataValue::Text("Alice".into())],
+            OnConflict {
+                columns: vec!["id".to_string()],
+                action: ConflictAction::DoUpdate(vec![
+                    ("name".to_string(), DataValue::Text("Alice".into())),
+                    ("visits".to_string(), DataValue::Integer(2)),
+                ]),
+            },
+        )));
+
+        // DO UPDATE has to name the columns that identify the row
+        assert!(Statement::parse("INSERT INTO users VALUES (1, 'Alice') ON CONFLICT DO UPDATE SET name = 'Bob'").is_err());
+    }
 }
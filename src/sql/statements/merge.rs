@@ -0,0 +1,187 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::{alphanumeric1, multispace0, multispace1},
+    combinator::{map, opt},
+    multi::separated_list1,
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+use crate::error::ReefDBError;
+use crate::sql::{
+    column_def::{table_name, ColumnDef},
+    column_value_pair::{identifier, ColumnValuePair},
+    data_value::DataValue,
+    table_reference::TableReference,
+};
+
+use super::Statement;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeStatement {
+    pub target: TableReference,
+    pub source: TableReference,
+    /// Columns the ON condition compares for equality, one on each table in either order
+    pub on: (ColumnValuePair, ColumnValuePair),
+    /// `WHEN MATCHED THEN UPDATE SET` assignments to the target row
+    pub when_matched: Option<Vec<(String, MergeValue)>>,
+    /// `WHEN NOT MATCHED THEN INSERT VALUES` row for the target, in column order
+    pub when_not_matched: Option<Vec<MergeValue>>,
+}
+
+/// A value in a MERGE action: a literal, or a column of the source or target row
+#[derive(Debug, PartialEq, Clone)]
+pub enum MergeValue {
+    Value(DataValue),
+    Column(ColumnValuePair),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MergeSide {
+    Target,
+    Source,
+}
+
+impl MergeStatement {
+    /// Table and position of a column named in the statement. A qualifier is matched
+    /// against each table's alias or name; an unqualified name must belong to one table.
+    pub fn resolve(
+        &self,
+        column: &ColumnValuePair,
+        target_schema: &[ColumnDef],
+        source_schema: &[ColumnDef],
+    ) -> Result<(MergeSide, usize), ReefDBError> {
+        let qualifier = column.table_name.as_str();
+        let named = |table: &TableReference| table.name == qualifier || table.alias.as_deref() == Some(qualifier);
+        let position = |schema: &[ColumnDef]| schema.iter().position(|c| c.name == column.column_name);
+        let found = if qualifier.is_empty() {
+            match (position(target_schema), position(source_schema)) {
+                (Some(_), Some(_)) => {
+                    return Err(ReefDBError::Other(format!("Column {} is ambiguous in MERGE", column.column_name)));
+                }
+                (Some(i), None) => Some((MergeSide::Target, i)),
+                (None, Some(i)) => Some((MergeSide::Source, i)),
+                (None, None) => None,
+            }
+        } else if named(&self.target) {
+            position(target_schema).map(|i| (MergeSide::Target, i))
+        } else if named(&self.source) {
+            position(source_schema).map(|i| (MergeSide::Source, i))
+        } else {
+            return Err(ReefDBError::TableNotFound(qualifier.to_string()));
+        };
+        found.ok_or_else(|| ReefDBError::ColumnNotFound(match qualifier {
+            "" => column.column_name.clone(),
+            qualifier => format!("{}.{}", qualifier, column.column_name),
+        }))
+    }
+}
+
+fn table_with_alias(input: &str) -> IResult<&str, TableReference> {
+    let (input, name) = table_name(input)?;
+    let (input, alias) = opt(preceded(
+        tuple((multispace1, tag_no_case("AS"), multispace1)),
+        identifier,
+    ))(input)?;
+    Ok((input, TableReference { name: name.to_string(), alias: alias.map(str::to_string) }))
+}
+
+fn merge_value(input: &str) -> IResult<&str, MergeValue> {
+    alt((
+        map(DataValue::parse, MergeValue::Value),
+        map(ColumnValuePair::parse, MergeValue::Column),
+    ))(input)
+}
+
+fn when_matched(input: &str) -> IResult<&str, Vec<(String, MergeValue)>> {
+    preceded(
+        tuple((
+            tag_no_case("WHEN"), multispace1, tag_no_case("MATCHED"), multispace1,
+            tag_no_case("THEN"), multispace1, tag_no_case("UPDATE"), multispace1,
+            tag_no_case("SET"), multispace1,
+        )),
+        separated_list1(
+            delimited(multispace0, tag(","), multispace0),
+            map(
+                tuple((alphanumeric1, delimited(multispace0, tag("="), multispace0), merge_value)),
+                |(column, _, value)| (column.to_string(), value),
+            ),
+        ),
+    )(input)
+}
+
+fn when_not_matched(input: &str) -> IResult<&str, Vec<MergeValue>> {
+    preceded(
+        tuple((
+            tag_no_case("WHEN"), multispace1, tag_no_case("NOT"), multispace1,
+            tag_no_case("MATCHED"), multispace1, tag_no_case("THEN"), multispace1,
+            tag_no_case("INSERT"), multispace1, tag_no_case("VALUES"), multispace0,
+        )),
+        delimited(
+            tuple((tag("("), multispace0)),
+            separated_list1(delimited(multispace0, tag(","), multispace0), merge_value),
+            tuple((multispace0, tag(")"))),
+        ),
+    )(input)
+}
+
+impl MergeStatement {
+    pub fn parse(input: &str) -> IResult<&str, Statement> {
+        let (input, _) = tuple((tag_no_case("MERGE"), multispace1, tag_no_case("INTO"), multispace1))(input)?;
+        let (input, target) = table_with_alias(input)?;
+        let (input, _) = tuple((multispace1, tag_no_case("USING"), multispace1))(input)?;
+        let (input, source) = table_with_alias(input)?;
+        let (input, _) = tuple((multispace1, tag_no_case("ON"), multispace1))(input)?;
+        let (input, left) = ColumnValuePair::parse(input)?;
+        let (input, _) = delimited(multispace0, tag("="), multispace0)(input)?;
+        let (input, right) = ColumnValuePair::parse(input)?;
+        let (input, when_matched) = opt(preceded(multispace1, when_matched))(input)?;
+        let (rest, when_not_matched) = opt(preceded(multispace1, when_not_matched))(input)?;
+        if when_matched.is_none() && when_not_matched.is_none() {
+            return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)));
+        }
+
+        Ok((rest, Statement::Merge(MergeStatement {
+            target,
+            source,
+            on: (left, right),
+            when_matched,
+            when_not_matched,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_merge_test() {
+        let input = "MERGE INTO accounts AS a USING updates AS u ON a.id = u.id \
+                     WHEN MATCHED THEN UPDATE SET balance = u.balance, active = true \
+                     WHEN NOT MATCHED THEN INSERT VALUES (u.id, u.balance, true)";
+        let (remaining, stmt) = MergeStatement::parse(input).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(stmt, Statement::Merge(MergeStatement {
+            target: TableReference { name: "accounts".to_string(), alias: Some("a".to_string()) },
+            source: TableReference { name: "updates".to_string(), alias: Some("u".to_string()) },
+            on: (ColumnValuePair::new("id", "a"), ColumnValuePair::new("id", "u")),
+            when_matched: Some(vec![
+                ("balance".to_string(), MergeValue::Column(ColumnValuePair::new("balance", "u"))),
+                ("active".to_string(), MergeValue::Value(DataValue::Boolean(true))),
+            ]),
+            when_not_matched: Some(vec![
+                MergeValue::Column(ColumnValuePair::new("id", "u")),
+                MergeValue::Column(ColumnValuePair::new("balance", "u")),
+                MergeValue::Value(DataValue::Boolean(true)),
+            ]),
+        }));
+
+        // Either action alone is enough, but one is required
+        let (_, stmt) = MergeStatement::parse("MERGE INTO accounts USING updates ON accounts.id = updates.id \
+                                               WHEN NOT MATCHED THEN INSERT VALUES (updates.id, 0, false)").unwrap();
+        assert!(matches!(stmt, Statement::Merge(MergeStatement { when_matched: None, when_not_matched: Some(_), .. })));
+        assert!(Statement::parse("MERGE INTO accounts USING updates ON accounts.id = updates.id").is_err());
+    }
+}
@@ -1,17 +1,20 @@
 use self::{
     create::CreateStatement, delete::DeleteStatement, insert::InsertStatement,
     select::SelectStatement, update::UpdateStatement, alter::AlterStatement, drop::DropStatement,
-    create_index::CreateIndexStatement, drop_index::DropIndexStatement,
+    create_index::CreateIndexStatement, drop_index::DropIndexStatement, merge::MergeStatement,
 };
 
 use nom::{
     branch::alt,
     bytes::complete::{tag_no_case, take_while1},
     character::complete::{multispace0, multispace1},
+    combinator::recognize,
     sequence::{preceded, tuple},
     IResult,
 };
 
+use crate::sql::column_def::table_name;
+
 pub mod create;
 pub mod delete;
 pub mod insert;
@@ -21,6 +24,7 @@ pub mod alter;
 pub mod drop;
 pub mod create_index;
 pub mod drop_index;
+pub mod merge;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
@@ -33,11 +37,17 @@ pub enum Statement {
     Drop(DropStatement),
     CreateIndex(CreateIndexStatement),
     DropIndex(DropIndexStatement),
+    Merge(MergeStatement),
     Savepoint(SavepointStatement),
     RollbackToSavepoint(String),
     ReleaseSavepoint(String),
     BeginTransaction,
     Commit,
+    ShowTables,
+    ShowLocks,
+    Describe(String),
+    Analyze(String),
+    SetSchemaVersion(u32),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -55,6 +65,52 @@ fn parse_commit(input: &str) -> IResult<&str, Statement> {
     Ok((input, Statement::Commit))
 }
 
+fn parse_show_tables(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tuple((tag_no_case("SHOW"), multispace1, tag_no_case("TABLES")))(input)?;
+    Ok((input, Statement::ShowTables))
+}
+
+fn parse_show_locks(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tuple((tag_no_case("SHOW"), multispace1, tag_no_case("LOCKS")))(input)?;
+    Ok((input, Statement::ShowLocks))
+}
+
+fn parse_describe(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = alt((
+        tag_no_case("DESCRIBE"),
+        recognize(tuple((
+            tag_no_case("SHOW"),
+            multispace1,
+            tag_no_case("COLUMNS"),
+            multispace1,
+            tag_no_case("FROM"),
+        ))),
+    ))(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, name) = table_name(input)?;
+    Ok((input, Statement::Describe(name.to_string())))
+}
+
+fn parse_analyze(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag_no_case("ANALYZE")(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, name) = table_name(input)?;
+    Ok((input, Statement::Analyze(name.to_string())))
+}
+
+fn parse_set_schema_version(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tuple((
+        tag_no_case("SET"),
+        multispace1,
+        tag_no_case("SCHEMA"),
+        multispace1,
+        tag_no_case("VERSION"),
+        multispace1,
+    ))(input)?;
+    let (input, version) = nom::character::complete::u32(input)?;
+    Ok((input, Statement::SetSchemaVersion(version)))
+}
+
 fn parse_savepoint(input: &str) -> IResult<&str, Statement> {
     let (input, _) = tag_no_case("SAVEPOINT")(input)?;
     let (input, _) = multispace1(input)?;
@@ -76,32 +132,57 @@ fn parse_release_savepoint(input: &str) -> IResult<&str, Statement> {
     Ok((input, Statement::ReleaseSavepoint(name.to_string())))
 }
 
+type StatementParser = fn(&str) -> IResult<&str, Statement>;
+
+const STATEMENT_PARSERS: [StatementParser; 20] = [
+    CreateStatement::parse,
+    InsertStatement::parse,
+    SelectStatement::parse,
+    UpdateStatement::parse,
+    DeleteStatement::parse,
+    AlterStatement::parse,
+    DropStatement::parse,
+    CreateIndexStatement::parse,
+    DropIndexStatement::parse,
+    MergeStatement::parse,
+    parse_savepoint,
+    parse_rollback_to_savepoint,
+    parse_release_savepoint,
+    parse_begin_transaction,
+    parse_commit,
+    parse_show_tables,
+    parse_show_locks,
+    parse_describe,
+    parse_analyze,
+    parse_set_schema_version,
+];
+
 impl Statement {
+    /// Parses one statement, which must make up all of `input`. When no statement kind
+    /// matches, the error is the one from the kind that got furthest into the input, so
+    /// it points at the likely mistake rather than at the first keyword.
     pub fn parse(input: &str) -> IResult<&str, Statement> {
         let (input, _) = multispace0(input)?;
-        let (input, stmt) = alt((
-            CreateStatement::parse,
-            InsertStatement::parse,
-            SelectStatement::parse,
-            UpdateStatement::parse,
-            DeleteStatement::parse,
-            AlterStatement::parse,
-            DropStatement::parse,
-            CreateIndexStatement::parse,
-            DropIndexStatement::parse,
-            parse_savepoint,
-            parse_rollback_to_savepoint,
-            parse_release_savepoint,
-            parse_begin_transaction,
-            parse_commit,
-        ))(input)?;
-        let (input, _) = multispace0(input)?;
-        if !input.is_empty() {
-            return Err(nom::Err::Error(nom::error::Error::new(
-                input,
-                nom::error::ErrorKind::Eof
-            )));
+        let mut furthest: Option<nom::error::Error<&str>> = None;
+        for parser in STATEMENT_PARSERS {
+            let error = match parser(input) {
+                Ok((rest, stmt)) => {
+                    let (rest, _) = multispace0(rest)?;
+                    if !rest.is_empty() {
+                        return Err(nom::Err::Error(nom::error::Error::new(
+                            rest,
+                            nom::error::ErrorKind::Eof
+                        )));
+                    }
+                    return Ok((rest, stmt));
+                }
+                Err(nom::Err::Error(error)) => error,
+                Err(e) => return Err(e),
+            };
+            if furthest.as_ref().is_none_or(|f| error.input.len() < f.input.len()) {
+                furthest = Some(error);
+            }
         }
-        Ok((input, stmt))
+        Err(nom::Err::Error(furthest.expect("STATEMENT_PARSERS is not empty")))
     }
 }
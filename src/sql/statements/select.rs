@@ -1,9 +1,9 @@
 use nom::IResult;
 use nom::{
     branch::alt,
-    bytes::complete::{tag, tag_no_case},
-    character::complete::{alpha1, alphanumeric1, multispace0, multispace1},
-    combinator::{map, opt, recognize},
+    bytes::complete::{is_not, tag, tag_no_case},
+    character::complete::{alpha1, alphanumeric1, digit1, multispace0, multispace1},
+    combinator::{map, map_res, opt, recognize, verify},
     multi::{many0, separated_list0, separated_list1},
     sequence::{delimited, preceded, terminated, tuple},
 };
@@ -14,57 +14,278 @@ use crate::sql::{
         order_by::OrderByClause,
     },
     column::{Column, ColumnType},
+    case_expression::CaseExpression,
+    window::WindowFunction,
+    aggregate::AggregateFunction,
     data_value::DataValue,
     table_reference::TableReference,
     operators::op::Op,
 };
 use crate::sql::statements::Statement;
+use chrono::DateTime;
+use std::time::SystemTime;
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum SelectStatement {
     FromTable(TableReference, Vec<Column>, Option<WhereType>, Vec<JoinClause>, Vec<OrderByClause>),
+    /// `WITH [RECURSIVE] <cte>, ... <select>`: each CTE is readable as a table by the
+    /// CTEs after it and by the main query
+    With(Vec<CommonTableExpression>, Box<SelectStatement>),
+    /// `SELECT DISTINCT ON (<col>, ...) ...`: keeps the first row, in result order, for each
+    /// distinct combination of the listed output columns
+    DistinctOn(Vec<Column>, Box<SelectStatement>),
+    /// `SELECT ... GROUP BY <col>, ...`: one row per distinct combination of the keys. The
+    /// inner query's ORDER BY sorts the groups and may name an aggregate by its alias or
+    /// by the aggregate itself.
+    GroupBy(Vec<Column>, Box<SelectStatement>),
+    /// `GROUP BY GROUPING SETS ((<col>, ...), ...)`: the rows of one GROUP BY per set, in
+    /// set order. Keys a set leaves out are NULL in its rows. `ROLLUP (a, b)` is parsed as
+    /// the sets `(a, b), (a), ()`.
+    GroupingSets(Vec<Vec<Column>>, Box<SelectStatement>),
+    /// `SELECT ... FROM <table> AS OF '<RFC 3339 timestamp>' ...`: reads the row versions
+    /// committed at or before that time. Served from MVCC history by the transaction manager.
+    AsOf(SystemTime, Box<SelectStatement>),
+    /// `LIMIT <n>` or `FETCH FIRST <n> ROWS { ONLY | WITH TIES }`: the first rows of the
+    /// query's result, in its ORDER BY order
+    Limit(RowLimit, Box<SelectStatement>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RowLimit {
+    pub count: usize,
+    /// Also keep the rows after the last one that tie with it on every ORDER BY key
+    pub with_ties: bool,
+}
+
+/// `name [(col, ...)] AS (<anchor> [UNION [ALL] <recursive term>])`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommonTableExpression {
+    pub name: String,
+    /// Column names; taken from the anchor's result when empty
+    pub columns: Vec<String>,
+    pub anchor: SelectStatement,
+    pub recursive: Option<RecursiveTerm>,
+}
+
+/// Query re-run against the rows the previous iteration produced until it yields none
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecursiveTerm {
+    pub query: SelectStatement,
+    /// `UNION ALL` keeps rows already produced; `UNION` drops them
+    pub union_all: bool,
 }
 
 impl SelectStatement {
     pub fn parse(input: &str) -> IResult<&str, Statement> {
-        let (input, _) = tag_no_case("SELECT")(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, columns) = parse_column_list(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("FROM")(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, table_ref) = parse_table_reference(input)?;
-        let (input, joins) = many0(delimited(
-            multispace0,
-            JoinClause::parse,
-            multispace0
-        ))(input)?;
-        let (input, where_clause) = opt(preceded(
-            multispace0,
-            parse_where_clause
-        ))(input)?;
-
-        let (input, order_by_clauses) = opt(preceded(
-            multispace0,
-            OrderByClause::parse
-        ))(input)?;
-
-        let (input, _) = multispace0(input)?;
-        Ok((input, Statement::Select(SelectStatement::FromTable(
-            table_ref,
-            columns,
-            where_clause,
-            joins,
-            order_by_clauses.unwrap_or_default(),
-        ))))
+        map(alt((parse_with, parse_select)), Statement::Select)(input)
     }
 }
 
+fn parse_with(input: &str) -> IResult<&str, SelectStatement> {
+    let (input, _) = tuple((tag_no_case("WITH"), multispace1))(input)?;
+    let (input, recursive) = opt(tuple((tag_no_case("RECURSIVE"), multispace1)))(input)?;
+    let (input, ctes) = separated_list1(
+        delimited(multispace0, tag(","), multispace0),
+        |input| parse_cte(input, recursive.is_some()),
+    )(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, query) = parse_select(input)?;
+    Ok((input, SelectStatement::With(ctes, Box::new(query))))
+}
+
+// Only `WITH RECURSIVE` CTEs may have a recursive term
+fn parse_cte(input: &str, recursive: bool) -> IResult<&str, CommonTableExpression> {
+    let (input, name) = identifier(input)?;
+    let (input, columns) = opt(delimited(
+        tuple((multispace0, tag("("), multispace0)),
+        separated_list1(delimited(multispace0, tag(","), multispace0), identifier),
+        tuple((multispace0, tag(")"))),
+    ))(input)?;
+    let (input, _) = tuple((multispace1, tag_no_case("AS"), multispace0, tag("("), multispace0))(input)?;
+    let (input, anchor) = parse_select(input)?;
+    let (input, term) = if recursive {
+        opt(map(
+            tuple((
+                multispace0,
+                tag_no_case("UNION"),
+                opt(preceded(multispace1, tag_no_case("ALL"))),
+                multispace1,
+                parse_select,
+            )),
+            |(_, _, all, _, query)| RecursiveTerm { query, union_all: all.is_some() },
+        ))(input)?
+    } else {
+        (input, None)
+    };
+    let (input, _) = tuple((multispace0, tag(")")))(input)?;
+    Ok((input, CommonTableExpression {
+        name: name.to_string(),
+        columns: columns.unwrap_or_default().into_iter().map(str::to_string).collect(),
+        anchor,
+        recursive: term,
+    }))
+}
+
+fn parse_select(input: &str) -> IResult<&str, SelectStatement> {
+    let (input, _) = tag_no_case("SELECT")(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, distinct_on) = opt(terminated(parse_distinct_on, multispace0))(input)?;
+    let (input, columns) = parse_column_list(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, _) = tag_no_case("FROM")(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, table_ref) = parse_table_reference(input)?;
+    let (input, as_of) = opt(preceded(
+        tuple((multispace1, tag_no_case("AS"), multispace1, tag_no_case("OF"), multispace1)),
+        parse_as_of_time,
+    ))(input)?;
+    let (input, joins) = many0(delimited(
+        multispace0,
+        JoinClause::parse,
+        multispace0
+    ))(input)?;
+    let (input, where_clause) = opt(preceded(
+        multispace0,
+        parse_where_clause
+    ))(input)?;
+    let (input, group_by) = opt(preceded(
+        multispace0,
+        alt((map(parse_grouping_sets, Grouping::Sets), map(parse_group_by, Grouping::Keys))),
+    ))(input)?;
+
+    let (input, order_by_clauses) = opt(preceded(
+        multispace0,
+        OrderByClause::parse
+    ))(input)?;
+    let (input, row_limit) = opt(preceded(multispace0, parse_row_limit))(input)?;
+
+    let (input, _) = multispace0(input)?;
+    let select = SelectStatement::FromTable(
+        table_ref,
+        columns,
+        where_clause,
+        joins,
+        order_by_clauses.unwrap_or_default(),
+    );
+    let select = match group_by {
+        Some(Grouping::Keys(keys)) => SelectStatement::GroupBy(keys, Box::new(select)),
+        Some(Grouping::Sets(sets)) => SelectStatement::GroupingSets(sets, Box::new(select)),
+        None => select,
+    };
+    let select = match distinct_on {
+        Some(keys) => SelectStatement::DistinctOn(keys, Box::new(select)),
+        None => select,
+    };
+    let select = match as_of {
+        Some(at) => SelectStatement::AsOf(at, Box::new(select)),
+        None => select,
+    };
+    Ok((input, match row_limit {
+        Some(limit) => SelectStatement::Limit(limit, Box::new(select)),
+        None => select,
+    }))
+}
+
+// `LIMIT <n>` or `FETCH { FIRST | NEXT } <n> { ROW | ROWS } { ONLY | WITH TIES }`
+fn parse_row_limit(input: &str) -> IResult<&str, RowLimit> {
+    let count = || map_res(digit1, str::parse::<usize>);
+    alt((
+        map(preceded(tuple((tag_no_case("LIMIT"), multispace1)), count()), |count| RowLimit { count, with_ties: false }),
+        map(
+            tuple((
+                tag_no_case("FETCH"),
+                multispace1,
+                alt((tag_no_case("FIRST"), tag_no_case("NEXT"))),
+                multispace1,
+                count(),
+                multispace1,
+                alt((tag_no_case("ROWS"), tag_no_case("ROW"))),
+                multispace1,
+                alt((
+                    map(tag_no_case("ONLY"), |_| false),
+                    map(tuple((tag_no_case("WITH"), multispace1, tag_no_case("TIES"))), |_| true),
+                )),
+            )),
+            |(_, _, _, _, count, _, _, _, with_ties)| RowLimit { count, with_ties },
+        ),
+    ))(input)
+}
+
+// A quoted RFC 3339 timestamp such as '2024-01-01T00:00:00Z'
+fn parse_as_of_time(input: &str) -> IResult<&str, SystemTime> {
+    map_res(
+        delimited(tag("'"), is_not("'"), tag("'")),
+        |text: &str| DateTime::parse_from_rfc3339(text).map(SystemTime::from),
+    )(input)
+}
+
+// `DISTINCT ON (col, ...)`
+fn parse_distinct_on(input: &str) -> IResult<&str, Vec<Column>> {
+    let (input, _) = tuple((tag_no_case("DISTINCT"), multispace1, tag_no_case("ON"), multispace0, tag("("), multispace0))(input)?;
+    let (input, keys) = parse_key_columns(input)?;
+    let (input, _) = tuple((multispace0, tag(")")))(input)?;
+    Ok((input, keys))
+}
+
+enum Grouping {
+    Keys(Vec<Column>),
+    Sets(Vec<Vec<Column>>),
+}
+
+// `GROUP BY col, ...`
+fn parse_group_by(input: &str) -> IResult<&str, Vec<Column>> {
+    let (input, _) = tuple((tag_no_case("GROUP"), multispace1, tag_no_case("BY"), multispace1))(input)?;
+    parse_key_columns(input)
+}
+
+// `GROUP BY ROLLUP (col, ...)` or `GROUP BY GROUPING SETS (<set>, ...)`, where a set is
+// a parenthesized, possibly empty, column list or a single column
+fn parse_grouping_sets(input: &str) -> IResult<&str, Vec<Vec<Column>>> {
+    let (input, _) = tuple((tag_no_case("GROUP"), multispace1, tag_no_case("BY"), multispace1))(input)?;
+    alt((
+        map(
+            preceded(tuple((tag_no_case("ROLLUP"), multispace0)), parenthesized(parse_key_columns)),
+            |keys| (0..=keys.len()).rev().map(|n| keys[..n].to_vec()).collect(),
+        ),
+        preceded(
+            tuple((tag_no_case("GROUPING"), multispace1, tag_no_case("SETS"), multispace0)),
+            parenthesized(separated_list1(
+                delimited(multispace0, tag(","), multispace0),
+                alt((
+                    parenthesized(map(opt(parse_key_columns), Option::unwrap_or_default)),
+                    map(key_column, |key| vec![key]),
+                )),
+            )),
+        ),
+    ))(input)
+}
+
+fn parenthesized<'a, O>(
+    inner: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    delimited(tuple((tag("("), multispace0)), inner, tuple((multispace0, tag(")"))))
+}
+
+// Comma-separated, optionally table-qualified column names
+fn parse_key_columns(input: &str) -> IResult<&str, Vec<Column>> {
+    separated_list1(delimited(multispace0, tag(","), multispace0), key_column)(input)
+}
+
+fn key_column(input: &str) -> IResult<&str, Column> {
+    map(tuple((opt(terminated(identifier, tag("."))), identifier)), |(table, name)| Column {
+        table: table.map(|t| t.to_string()),
+        name: name.to_string(),
+        column_type: ColumnType::Regular(name.to_string()),
+    })(input)
+}
+
 fn parse_table_reference(input: &str) -> IResult<&str, TableReference> {
     let (input, name) = identifier(input)?;
+    // `AS OF` starts a time-travel clause rather than naming an alias
     let (input, alias) = opt(preceded(
         delimited(multispace0, tag_no_case("AS"), multispace1),
-        identifier
+        verify(identifier, |alias: &str| !alias.eq_ignore_ascii_case("OF"))
     ))(input)?;
     Ok((input, TableReference {
         name: name.to_string(),
@@ -81,6 +302,27 @@ fn identifier(input: &str) -> IResult<&str, &str> {
     )(input)
 }
 
+// Operands are literals, function calls, or column references like function arguments
+fn parse_concat_operand(input: &str) -> IResult<&str, DataValue> {
+    alt((
+        DataValue::parse,
+        map(
+            recognize(tuple((opt(terminated(identifier, tag("."))), identifier))),
+            |name: &str| DataValue::Column(name.to_string())
+        ),
+    ))(input)
+}
+
+// At least two operands joined by `||`
+fn parse_concat_operands(input: &str) -> IResult<&str, Vec<DataValue>> {
+    let (input, first) = parse_concat_operand(input)?;
+    let (input, rest) = nom::multi::many1(preceded(
+        delimited(multispace0, tag("||"), multispace0),
+        parse_concat_operand
+    ))(input)?;
+    Ok((input, std::iter::once(first).chain(rest).collect()))
+}
+
 fn parse_column_list(input: &str) -> IResult<&str, Vec<Column>> {
     alt((
         // Handle SELECT *
@@ -96,6 +338,51 @@ fn parse_column_list(input: &str) -> IResult<&str, Vec<Column>> {
         separated_list1(
             delimited(multispace0, tag(","), multispace0),
             alt((
+                // Handle `||` concatenation with optional alias
+                map(
+                    tuple((
+                        parse_concat_operands,
+                        opt(preceded(
+                            delimited(multispace0, tag_no_case("as"), multispace1),
+                            identifier
+                        ))
+                    )),
+                    |(operands, alias)| Column {
+                        table: None,
+                        name: alias.map(|a| a.to_string()).unwrap_or_else(|| "concat".to_string()),
+                        column_type: ColumnType::Concat(operands),
+                    }
+                ),
+                // Handle window functions with optional alias
+                map(
+                    tuple((
+                        WindowFunction::parse,
+                        opt(preceded(
+                            delimited(multispace0, tag_no_case("as"), multispace1),
+                            identifier
+                        ))
+                    )),
+                    |(window, alias)| Column {
+                        table: None,
+                        name: alias.map(|a| a.to_string()).unwrap_or_else(|| window.name().to_string()),
+                        column_type: ColumnType::Window(window),
+                    }
+                ),
+                // Handle aggregates with optional alias
+                map(
+                    tuple((
+                        AggregateFunction::parse,
+                        opt(preceded(
+                            delimited(multispace0, tag_no_case("as"), multispace1),
+                            identifier
+                        ))
+                    )),
+                    |(aggregate, alias)| Column {
+                        table: None,
+                        name: alias.map(|a| a.to_string()).unwrap_or_else(|| aggregate.name().to_string()),
+                        column_type: ColumnType::Aggregate(aggregate),
+                    }
+                ),
                 // Handle function calls with optional alias
                 map(
                     tuple((
@@ -111,24 +398,27 @@ fn parse_column_list(input: &str) -> IResult<&str, Vec<Column>> {
                             Column {
                                 table: None,
                                 name: alias_name,
-                                column_type: ColumnType::Function(
-                                    name, 
-                                    args.into_iter()
-                                        .map(|arg| match &arg {
-                                            DataValue::Text(s) => DataValue::Text(s.clone()),
-                                            DataValue::Function { name, args } => DataValue::Function {
-                                                name: name.clone(),
-                                                args: args.clone(),
-                                            },
-                                            _ => DataValue::Text(arg.to_string()),
-                                        })
-                                        .collect()
-                                ),
+                                column_type: ColumnType::Function(name, args),
                             }
                         },
                         _ => panic!("Expected function"),
                     }
                 ),
+                // Handle CASE expressions with optional alias
+                map(
+                    tuple((
+                        CaseExpression::parse,
+                        opt(preceded(
+                            delimited(multispace0, tag_no_case("as"), multispace1),
+                            identifier
+                        ))
+                    )),
+                    |(case, alias)| Column {
+                        table: None,
+                        name: alias.map(|a| a.to_string()).unwrap_or_else(|| "case".to_string()),
+                        column_type: ColumnType::Case(case),
+                    }
+                ),
                 // Handle regular columns with optional table prefix
                 map(
                     tuple((
@@ -182,6 +472,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_select_as_of_test() {
+        let (remaining, statement) = SelectStatement::parse(
+            "SELECT name FROM users AS OF '2024-01-01T00:00:00Z' WHERE id = 1"
+        ).unwrap();
+        assert_eq!(remaining, "");
+        let Statement::Select(SelectStatement::AsOf(at, query)) = statement else {
+            panic!("Expected AS OF select");
+        };
+        assert_eq!(at, SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_704_067_200));
+        assert!(matches!(*query, SelectStatement::FromTable(ref table, _, Some(_), _, _) if table.name == "users"));
+
+        // An alias can come first; a timestamp that isn't RFC 3339 doesn't parse
+        let (_, statement) = SelectStatement::parse("SELECT u.name FROM users AS u AS OF '2024-01-01T01:00:00+01:00'").unwrap();
+        assert!(matches!(statement, Statement::Select(SelectStatement::AsOf(time, _)) if time == at));
+        assert!(Statement::parse("SELECT name FROM users AS OF 'yesterday'").is_err());
+    }
+
     #[test]
     fn parse_select_with_order_by_test() {
         let input = "SELECT name FROM users ORDER BY age DESC, name ASC";
@@ -371,4 +679,126 @@ mod tests {
             _ => panic!("Expected Select statement with join"),
         }
     }
+
+    #[test]
+    fn parse_select_concat_test() {
+        let (_, statement) = SelectStatement::parse(
+            "SELECT id, users.first || ' ' || last AS fullname FROM users"
+        ).unwrap();
+        match statement {
+            Statement::Select(SelectStatement::FromTable(_, columns, _, _, _)) => {
+                assert_eq!(columns.len(), 2);
+                assert_eq!(columns[1].name, "fullname");
+                assert_eq!(columns[1].column_type, ColumnType::Concat(vec![
+                    DataValue::Column("users.first".to_string()),
+                    DataValue::Text(" ".into()),
+                    DataValue::Column("last".to_string()),
+                ]));
+            }
+            _ => panic!("Expected Select statement"),
+        }
+    }
+
+    #[test]
+    fn parse_with_recursive_test() {
+        let (rest, statement) = SelectStatement::parse(
+            "WITH RECURSIVE tree (id) AS (SELECT id FROM nodes WHERE id = 1 UNION ALL \
+             SELECT nodes.id FROM nodes INNER JOIN tree ON nodes.parent = tree.id) SELECT id FROM tree"
+        ).unwrap();
+        assert_eq!(rest, "");
+        match statement {
+            Statement::Select(SelectStatement::With(ctes, query)) => {
+                assert_eq!(ctes.len(), 1);
+                assert_eq!(ctes[0].name, "tree");
+                assert_eq!(ctes[0].columns, vec!["id".to_string()]);
+                let term = ctes[0].recursive.as_ref().expect("recursive term");
+                assert!(term.union_all);
+                assert!(matches!(&term.query, SelectStatement::FromTable(table, _, _, joins, _)
+                    if table.name == "nodes" && joins.len() == 1));
+                assert!(matches!(*query, SelectStatement::FromTable(table, _, _, _, _) if table.name == "tree"));
+            }
+            _ => panic!("Expected WITH statement"),
+        }
+    }
+
+    #[test]
+    fn parse_distinct_on_test() {
+        let (rest, statement) = SelectStatement::parse(
+            "SELECT DISTINCT ON (user_id, orders.region) * FROM orders ORDER BY user_id, created DESC"
+        ).unwrap();
+        assert_eq!(rest, "");
+        match statement {
+            Statement::Select(SelectStatement::DistinctOn(keys, query)) => {
+                let names: Vec<&str> = keys.iter().map(|key| key.name.as_str()).collect();
+                assert_eq!(names, vec!["user_id", "region"]);
+                assert_eq!(keys[1].table, Some("orders".to_string()));
+                assert!(matches!(*query, SelectStatement::FromTable(table, _, _, _, order_by)
+                    if table.name == "orders" && order_by.len() == 2));
+            }
+            _ => panic!("Expected DISTINCT ON statement"),
+        }
+    }
+
+    #[test]
+    fn parse_group_by_test() {
+        let (rest, statement) = SelectStatement::parse(
+            "SELECT user_id, COUNT(*) AS orders FROM orders WHERE total > 5 GROUP BY user_id ORDER BY COUNT(*) DESC"
+        ).unwrap();
+        assert_eq!(rest, "");
+        match statement {
+            Statement::Select(SelectStatement::GroupBy(keys, query)) => {
+                assert_eq!(keys.len(), 1);
+                assert_eq!(keys[0].name, "user_id");
+                match *query {
+                    SelectStatement::FromTable(table, columns, where_clause, _, order_by) => {
+                        assert_eq!(table.name, "orders");
+                        assert_eq!(columns[1].name, "orders");
+                        assert!(where_clause.is_some());
+                        assert!(matches!(&order_by[0].column.column_type, ColumnType::Aggregate(_)));
+                        assert_eq!(order_by[0].direction, OrderDirection::Desc);
+                    }
+                    other => panic!("Expected grouped SELECT, got {:?}", other),
+                }
+            }
+            _ => panic!("Expected GROUP BY statement"),
+        }
+    }
+
+    #[test]
+    fn parse_grouping_sets_test() {
+        let sets = |sql: &str| match SelectStatement::parse(sql).unwrap() {
+            ("", Statement::Select(SelectStatement::GroupingSets(sets, _))) => sets.into_iter()
+                .map(|set| set.into_iter().map(|key| key.name).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            other => panic!("Expected GROUPING SETS statement, got {:?}", other),
+        };
+        assert_eq!(
+            sets("SELECT a, b, COUNT(*) FROM t GROUP BY ROLLUP (a, b)"),
+            vec![vec!["a", "b"], vec!["a"], vec![]]
+        );
+        assert_eq!(
+            sets("SELECT a, b, COUNT(*) FROM t GROUP BY GROUPING SETS ((a, b), b, ( ))"),
+            vec![vec!["a", "b"], vec!["b"], vec![]]
+        );
+    }
+
+    #[test]
+    fn parse_row_limit_test() {
+        let limit = |sql: &str| match SelectStatement::parse(sql).unwrap() {
+            ("", Statement::Select(SelectStatement::Limit(limit, query))) => {
+                assert!(matches!(*query, SelectStatement::FromTable(..)));
+                limit
+            }
+            other => panic!("Expected a limited select, got {:?}", other),
+        };
+        assert_eq!(limit("SELECT * FROM scores ORDER BY points DESC LIMIT 3"), RowLimit { count: 3, with_ties: false });
+        assert_eq!(
+            limit("SELECT * FROM scores ORDER BY points DESC FETCH FIRST 3 ROWS WITH TIES"),
+            RowLimit { count: 3, with_ties: true },
+        );
+        assert_eq!(
+            limit("SELECT * FROM scores ORDER BY points fetch next 1 row only"),
+            RowLimit { count: 1, with_ties: false },
+        );
+    }
 }
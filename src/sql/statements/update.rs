@@ -38,17 +38,7 @@ impl UpdateStatement {
             multispace1
         )(input)?;
 
-        let (input, updates) = separated_list1(
-            delimited(multispace0, tag(","), multispace0),
-            map(
-                tuple((
-                    alphanumeric1,
-                    delimited(multispace0, tag("="), multispace0),
-                    DataValue::parse
-                )),
-                |(col, _, val)| (col.to_string(), val)
-            )
-        )(input)?;
+        let (input, updates) = parse_assignments(input)?;
 
         let (input, where_clause) = opt(preceded(
             multispace1,
@@ -65,6 +55,21 @@ impl UpdateStatement {
     }
 }
 
+/// `col = value, ...` after SET, shared with `INSERT ... ON CONFLICT DO UPDATE SET`
+pub(crate) fn parse_assignments(input: &str) -> IResult<&str, Vec<(String, DataValue)>> {
+    separated_list1(
+        delimited(multispace0, tag(","), multispace0),
+        map(
+            tuple((
+                alphanumeric1,
+                delimited(multispace0, tag("="), multispace0),
+                DataValue::parse
+            )),
+            |(col, _, val)| (col.to_string(), val)
+        )
+    )(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,7 +89,7 @@ mod tests {
                 assert_eq!(table_name, "users");
                 assert_eq!(updates.len(), 1);
                 assert_eq!(updates[0].0, "name");
-                assert_eq!(updates[0].1, DataValue::Text("John".to_string()));
+                assert_eq!(updates[0].1, DataValue::Text("John".into()));
                 assert_eq!(where_clause.col_name, "id");
                 assert_eq!(where_clause.operator, Op::Equal);
                 assert_eq!(where_clause.value, DataValue::Integer(1));
@@ -103,14 +108,14 @@ mod tests {
                 assert_eq!(table_name, "users");
                 assert_eq!(updates.len(), 3);
                 assert_eq!(updates[0].0, "name");
-                assert_eq!(updates[0].1, DataValue::Text("John".to_string()));
+                assert_eq!(updates[0].1, DataValue::Text("John".into()));
                 assert_eq!(updates[1].0, "age");
                 assert_eq!(updates[1].1, DataValue::Integer(30));
                 assert_eq!(updates[2].0, "status");
-                assert_eq!(updates[2].1, DataValue::Text("active".to_string()));
+                assert_eq!(updates[2].1, DataValue::Text("active".into()));
                 assert_eq!(where_clause.col_name, "status");
                 assert_eq!(where_clause.operator, Op::Equal);
-                assert_eq!(where_clause.value, DataValue::Text("active".to_string()));
+                assert_eq!(where_clause.value, DataValue::Text("active".into()));
             }
             _ => panic!("Expected Update statement with where clause"),
         }
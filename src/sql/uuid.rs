@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 128-bit identifier. Equality and ordering follow the byte representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Uuid([u8; 16]);
+
+impl Uuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Random (version 4) UUID
+    pub fn new_v4() -> Self {
+        let mut bytes: [u8; 16] = rand::random();
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Uuid(bytes)
+    }
+
+    /// Parses the canonical hyphenated form, e.g. `550e8400-e29b-41d4-a716-446655440000`
+    pub fn parse(input: &str) -> Option<Self> {
+        let groups: Vec<&str> = input.split('-').collect();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len()
+            || groups.iter().zip(lengths).any(|(group, len)| group.len() != len)
+        {
+            return None;
+        }
+        let hex: String = groups.concat();
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = [0u8; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+        }
+        Some(Uuid(bytes))
+    }
+
+    pub fn version(&self) -> u8 {
+        self.0[6] >> 4
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_round_trip() {
+        let text = "550e8400-e29b-41d4-a716-446655440000";
+        let uuid = Uuid::parse(text).unwrap();
+        assert_eq!(uuid.to_string(), text);
+        assert_eq!(Uuid::parse("550E8400-E29B-41D4-A716-446655440000"), Some(uuid));
+        assert!(Uuid::parse("550e8400e29b41d4a716446655440000").is_none());
+        assert!(Uuid::parse("550e8400-e29b-41d4-a716-44665544000g").is_none());
+    }
+
+    #[test]
+    fn test_new_v4_sets_version_and_variant() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        assert_ne!(a, b);
+        assert_eq!(a.version(), 4);
+        assert_eq!(a.as_bytes()[8] & 0xc0, 0x80);
+        assert_eq!(Uuid::parse(&a.to_string()), Some(a));
+    }
+}
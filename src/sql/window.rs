@@ -0,0 +1,95 @@
+use nom::{
+    IResult,
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::{multispace0, multispace1},
+    combinator::{map, opt, value},
+    multi::separated_list1,
+    sequence::{delimited, tuple},
+};
+
+use crate::sql::{
+    clauses::order_by::OrderByClause,
+    column_def::column_name,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFunctionKind {
+    RowNumber,
+    /// Ties share a rank and leave a gap after them: 1, 1, 3
+    Rank,
+    /// Ties share a rank without a gap: 1, 1, 2
+    DenseRank,
+}
+
+/// `<function>() OVER ([PARTITION BY col, ...] [ORDER BY ...])`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFunction {
+    pub kind: WindowFunctionKind,
+    pub partition_by: Vec<String>,
+    pub order_by: Vec<OrderByClause>,
+}
+
+impl WindowFunction {
+    pub fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, kind) = alt((
+            value(WindowFunctionKind::RowNumber, tag_no_case("ROW_NUMBER")),
+            value(WindowFunctionKind::DenseRank, tag_no_case("DENSE_RANK")),
+            value(WindowFunctionKind::Rank, tag_no_case("RANK")),
+        ))(input)?;
+        let (input, _) = tuple((multispace0, tag("("), multispace0, tag(")")))(input)?;
+        let (input, _) = tuple((multispace1, tag_no_case("OVER"), multispace0, tag("("), multispace0))(input)?;
+        let (input, partition_by) = opt(map(
+            tuple((
+                tag_no_case("PARTITION"),
+                multispace1,
+                tag_no_case("BY"),
+                multispace1,
+                separated_list1(delimited(multispace0, tag(","), multispace0), column_name),
+                multispace0,
+            )),
+            |(_, _, _, _, columns, _)| columns.into_iter().map(str::to_string).collect(),
+        ))(input)?;
+        let (input, order_by) = opt(OrderByClause::parse)(input)?;
+        let (input, _) = tuple((multispace0, tag(")")))(input)?;
+        Ok((input, WindowFunction {
+            kind,
+            partition_by: partition_by.unwrap_or_default(),
+            order_by: order_by.unwrap_or_default(),
+        }))
+    }
+
+    /// Default output column name
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            WindowFunctionKind::RowNumber => "row_number",
+            WindowFunctionKind::Rank => "rank",
+            WindowFunctionKind::DenseRank => "dense_rank",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::clauses::order_by::OrderDirection;
+
+    #[test]
+    fn test_parse_window_function() {
+        let (rest, window) = WindowFunction::parse(
+            "DENSE_RANK() OVER (PARTITION BY dept ORDER BY salary DESC) AS r FROM staff"
+        ).unwrap();
+        assert_eq!(rest, " AS r FROM staff");
+        assert_eq!(window.kind, WindowFunctionKind::DenseRank);
+        assert_eq!(window.partition_by, vec!["dept".to_string()]);
+        assert_eq!(window.order_by.len(), 1);
+        assert_eq!(window.order_by[0].column.name, "salary");
+        assert_eq!(window.order_by[0].direction, OrderDirection::Desc);
+
+        let (_, window) = WindowFunction::parse("rank() OVER (ORDER BY score)").unwrap();
+        assert_eq!(window.kind, WindowFunctionKind::Rank);
+        assert!(window.partition_by.is_empty());
+
+        assert!(WindowFunction::parse("RANK()").is_err());
+    }
+}
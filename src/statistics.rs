@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::sql::{
+    column_def::ColumnDef,
+    data_type::DataType,
+    data_value::DataValue,
+    operators::op::Op,
+};
+
+/// Number of equi-width buckets built per column by `ANALYZE`
+pub const DEFAULT_HISTOGRAM_BUCKETS: usize = 10;
+
+/// Equi-width histogram over the non-null values of a numeric column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnHistogram {
+    pub min: f64,
+    pub max: f64,
+    pub buckets: Vec<usize>,
+}
+
+/// Statistics gathered for a table by the last `ANALYZE`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TableStatistics {
+    pub row_count: usize,
+    pub histograms: HashMap<String, ColumnHistogram>,
+}
+
+fn numeric_value(value: &DataValue) -> Option<f64> {
+    match value {
+        DataValue::Integer(i) => Some(*i as f64),
+        DataValue::Float(f) if f.is_finite() => Some(*f),
+        DataValue::Decimal(d) => Some(d.to_f64()),
+        _ => None,
+    }
+}
+
+impl ColumnHistogram {
+    /// Builds a histogram from `values`, returning `None` when there is nothing to bucket
+    pub fn build(values: &[f64], bucket_count: usize) -> Option<Self> {
+        if values.is_empty() || bucket_count == 0 {
+            return None;
+        }
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mut histogram = ColumnHistogram {
+            min,
+            max,
+            buckets: vec![0; bucket_count],
+        };
+        for value in values {
+            let idx = histogram.bucket_index(*value);
+            histogram.buckets[idx] += 1;
+        }
+        Some(histogram)
+    }
+
+    pub fn total(&self) -> usize {
+        self.buckets.iter().sum()
+    }
+
+    fn bucket_width(&self) -> f64 {
+        (self.max - self.min) / self.buckets.len() as f64
+    }
+
+    fn bucket_index(&self, value: f64) -> usize {
+        let width = self.bucket_width();
+        if width == 0.0 || value <= self.min {
+            return 0;
+        }
+        // The maximum falls into the last bucket rather than one past it
+        (((value - self.min) / width) as usize).min(self.buckets.len() - 1)
+    }
+
+    // Fraction of values strictly below `value`, interpolating inside its bucket
+    fn fraction_below(&self, value: f64) -> f64 {
+        let total = self.total();
+        if total == 0 || value <= self.min {
+            return 0.0;
+        }
+        if value > self.max {
+            return 1.0;
+        }
+        let width = self.bucket_width();
+        if width == 0.0 {
+            return 0.0;
+        }
+        let idx = self.bucket_index(value);
+        let below: usize = self.buckets[..idx].iter().sum();
+        let bucket_start = self.min + width * idx as f64;
+        let partial = self.buckets[idx] as f64 * ((value - bucket_start) / width);
+        (below as f64 + partial) / total as f64
+    }
+
+    fn fraction_equal(&self, value: f64) -> f64 {
+        let total = self.total();
+        if total == 0 || value < self.min || value > self.max {
+            return 0.0;
+        }
+        let idx = self.bucket_index(value);
+        if self.bucket_width() == 0.0 {
+            return self.buckets[idx] as f64 / total as f64;
+        }
+        // Assume values are spread evenly over the integers a bucket spans
+        let distinct = self.bucket_width().max(1.0);
+        (self.buckets[idx] as f64 / distinct) / total as f64
+    }
+
+    /// Estimated fraction of rows matching `column <op> value`, in `[0.0, 1.0]`
+    pub fn selectivity(&self, op: &Op, value: f64) -> f64 {
+        let estimate = match op {
+            Op::Equal | Op::NullSafeEqual => self.fraction_equal(value),
+            Op::NotEqual => 1.0 - self.fraction_equal(value),
+            Op::LessThan => self.fraction_below(value),
+            Op::LessThanOrEqual => self.fraction_below(value) + self.fraction_equal(value),
+            Op::GreaterThan => 1.0 - self.fraction_below(value) - self.fraction_equal(value),
+            Op::GreaterThanOrEqual => 1.0 - self.fraction_below(value),
+            Op::Match | Op::TextSearch | Op::Contains | Op::Like => 1.0,
+        };
+        estimate.clamp(0.0, 1.0)
+    }
+}
+
+impl TableStatistics {
+    /// Scans `rows` and builds a histogram for every integer and float column
+    pub fn analyze(schema: &[ColumnDef], rows: &[Vec<DataValue>], bucket_count: usize) -> Self {
+        let mut histograms = HashMap::new();
+        for (idx, column) in schema.iter().enumerate() {
+            if !matches!(column.data_type, DataType::Integer | DataType::Float) {
+                continue;
+            }
+            let values: Vec<f64> = rows.iter()
+                .filter_map(|row| row.get(idx).and_then(numeric_value))
+                .collect();
+            if let Some(histogram) = ColumnHistogram::build(&values, bucket_count) {
+                histograms.insert(column.name.clone(), histogram);
+            }
+        }
+        TableStatistics {
+            row_count: rows.len(),
+            histograms,
+        }
+    }
+
+    /// Estimated selectivity of `column <op> value`, or `None` without a usable histogram
+    pub fn estimate_selectivity(&self, column: &str, op: &Op, value: &DataValue) -> Option<f64> {
+        let histogram = self.histograms.get(column)?;
+        Some(histogram.selectivity(op, numeric_value(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_equi_width() {
+        let values: Vec<f64> = (0..100).map(|v| v as f64).collect();
+        let histogram = ColumnHistogram::build(&values, 4).unwrap();
+        assert_eq!(histogram.min, 0.0);
+        assert_eq!(histogram.max, 99.0);
+        assert_eq!(histogram.buckets, vec![25, 25, 25, 25]);
+
+        assert!((histogram.selectivity(&Op::LessThan, 50.0) - 0.5).abs() < 0.02);
+        assert_eq!(histogram.selectivity(&Op::GreaterThan, 1000.0), 0.0);
+        assert_eq!(histogram.selectivity(&Op::LessThan, -5.0), 0.0);
+        assert!((histogram.selectivity(&Op::Equal, 10.0) - 0.01).abs() < 0.005);
+    }
+
+    #[test]
+    fn test_histogram_single_value() {
+        let histogram = ColumnHistogram::build(&[7.0, 7.0, 7.0], 10).unwrap();
+        assert_eq!(histogram.total(), 3);
+        assert_eq!(histogram.selectivity(&Op::Equal, 7.0), 1.0);
+        assert_eq!(histogram.selectivity(&Op::NotEqual, 7.0), 0.0);
+        assert!(ColumnHistogram::build(&[], 10).is_none());
+    }
+}
@@ -1,19 +1,25 @@
 use crate::sql::column_def::ColumnDef;
 use crate::sql::data_value::DataValue;
+use crate::sql::decimal::Decimal;
 use crate::sql::data_type::DataType;
 use bincode::{deserialize, serialize};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Read, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use flate2::{read::GzDecoder, write::GzEncoder};
 
 use super::Storage;
 use crate::error::ReefDBError;
 use crate::sql::constraints::constraint::Constraint;
 use crate::indexes::{IndexManager, IndexType};
 use crate::indexes::index_manager::IndexUpdate;
+use crate::indexes::btree::BTreeIndex;
 use crate::fts::search::Search;
+use crate::locks::lock_recovering;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OnDiskIndexManager {
@@ -58,13 +64,26 @@ impl OnDiskIndexManager {
         }
     }
 
+    /// (table, column) pairs of every B-Tree index, sorted for stable output
+    pub fn btree_columns(&self) -> Vec<(String, String)> {
+        let mut columns: Vec<(String, String)> = self.indexes.iter()
+            .flat_map(|(table, table_indexes)| {
+                table_indexes.iter()
+                    .filter(|(_, index)| matches!(index, IndexType::BTree(_)))
+                    .map(move |(column, _)| (table.clone(), column.clone()))
+            })
+            .collect();
+        columns.sort();
+        columns
+    }
+
     fn save(&self) -> Result<(), ReefDBError> {
         let encoded_data = serialize(self)
             .map_err(|e| ReefDBError::Other(format!("Serialization error: {}", e)))?;
         let mut file = File::create(&self.file_path)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
         file.write_all(&encoded_data)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
         Ok(())
     }
 }
@@ -169,71 +188,759 @@ impl IndexManager for OnDiskIndexManager {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Compression applied to the table data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+/// Encodes the table data payload of a data file
+pub trait TableSerializer {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ReefDBError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ReefDBError>;
+}
+
+/// Compact binary encoding; the default
+pub struct BincodeSerializer;
+
+/// Human-readable encoding, handy when inspecting data files
+pub struct JsonSerializer;
+
+impl TableSerializer for BincodeSerializer {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ReefDBError> {
+        serialize(value).map_err(|e| ReefDBError::Other(format!("Serialization error: {}", e)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ReefDBError> {
+        deserialize(bytes).map_err(|e| ReefDBError::Other(format!("Deserialization error: {}", e)))
+    }
+}
+
+impl TableSerializer for JsonSerializer {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ReefDBError> {
+        serde_json::to_vec(value).map_err(|e| ReefDBError::Other(format!("Serialization error: {}", e)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ReefDBError> {
+        serde_json::from_slice(bytes).map_err(|e| ReefDBError::Other(format!("Deserialization error: {}", e)))
+    }
+}
+
+/// Serializer a data file is written with, recorded in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SerializationFormat {
+    #[default]
+    Bincode,
+    Json,
+}
+
+impl SerializationFormat {
+    fn tag(self) -> u8 {
+        match self {
+            SerializationFormat::Bincode => 0,
+            SerializationFormat::Json => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(SerializationFormat::Bincode),
+            1 => Some(SerializationFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl TableSerializer for SerializationFormat {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ReefDBError> {
+        match self {
+            SerializationFormat::Bincode => BincodeSerializer.encode(value),
+            SerializationFormat::Json => JsonSerializer.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ReefDBError> {
+        match self {
+            SerializationFormat::Bincode => BincodeSerializer.decode(bytes),
+            SerializationFormat::Json => JsonSerializer.decode(bytes),
+        }
+    }
+}
+
+// Data files start with a magic, a compression byte, a format byte and a row format
+// byte. "RDB4" files go on with the length of a directory as a little-endian u64, the
+// directory, then a section per table, encoded and compressed on its own so each table
+// can be read without the others. "RDB3" files hold every table in a single payload
+// instead, "RDB2" files also have no row format byte and hold row format 0, and "RDB1"
+// files have no format byte either and are always bincode; files with no magic at all
+// predate the header and hold the uncompressed bincode payload directly.
+const FILE_MAGIC_V1: &[u8; 4] = b"RDB1";
+const FILE_MAGIC_V2: &[u8; 4] = b"RDB2";
+const FILE_MAGIC_V3: &[u8; 4] = b"RDB3";
+const FILE_MAGIC: &[u8; 4] = b"RDB4";
+const HEADER_LEN: usize = 15;
+
+/// Layout of the rows in data files written by this version. Rows of older layouts are
+/// migrated as they load; files with a newer one are refused.
+///
+/// 0. Rows as written, which may be shorter than their table's schema
+/// 1. Every row holds a value for each column of its table's schema
+///
+/// New `DataValue` variants go at the end of the enum so stored rows keep decoding; a
+/// change to how existing values are encoded needs a new version and a decoder for the
+/// previous one in `migrate_rows`.
+pub const ROW_FORMAT_VERSION: u8 = 1;
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Gzip),
+            _ => None,
+        }
+    }
+
+    fn compress(self, bytes: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    fn decompress(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut decoded = Vec::new();
+                GzDecoder::new(bytes).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+fn check_row_format(version: u8) -> std::io::Result<u8> {
+    if version > ROW_FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "row format {} is newer than the supported {}", version, ROW_FORMAT_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+// Splits a data file written before "RDB4" into its header settings, row format and the
+// decompressed payload
+fn decode_file(contents: &[u8]) -> std::io::Result<(Compression, SerializationFormat, u8, Vec<u8>)> {
+    let (rest, has_format, has_row_format) = if let Some(rest) = contents.strip_prefix(FILE_MAGIC_V3.as_slice()) {
+        (rest, true, true)
+    } else if let Some(rest) = contents.strip_prefix(FILE_MAGIC_V2.as_slice()) {
+        (rest, true, false)
+    } else if let Some(rest) = contents.strip_prefix(FILE_MAGIC_V1.as_slice()) {
+        (rest, false, false)
+    } else {
+        return Ok((Compression::None, SerializationFormat::Bincode, 0, contents.to_vec()));
+    };
+
+    let (&tag, rest) = rest.split_first()
+        .ok_or_else(|| invalid_data("truncated data file header".to_string()))?;
+    let compression = Compression::from_tag(tag)
+        .ok_or_else(|| invalid_data(format!("unknown compression {}", tag)))?;
+    let (format, payload) = if has_format {
+        let (&tag, payload) = rest.split_first()
+            .ok_or_else(|| invalid_data("truncated data file header".to_string()))?;
+        let format = SerializationFormat::from_tag(tag)
+            .ok_or_else(|| invalid_data(format!("unknown serialization format {}", tag)))?;
+        (format, payload)
+    } else {
+        (SerializationFormat::Bincode, rest)
+    };
+    let (row_format, payload) = if has_row_format {
+        let (&version, payload) = payload.split_first()
+            .ok_or_else(|| invalid_data("truncated data file header".to_string()))?;
+        (check_row_format(version)?, payload)
+    } else {
+        (0, payload)
+    };
+
+    Ok((compression, format, row_format, compression.decompress(payload)?))
+}
+
+// Brings rows stored in an older row format up to the current one
+fn migrate_rows((schema, rows): &mut Table, row_format: u8) {
+    if row_format < 1 {
+        // Columns a row lacks take their literal default, or NULL
+        for row in rows.iter_mut().filter(|row| row.len() < schema.len()) {
+            let missing = schema[row.len()..].iter().map(|column| match column.default_value() {
+                Some(DataValue::Function { .. }) | None => DataValue::Null,
+                Some(value) => value.clone(),
+            });
+            row.extend(missing.collect::<Vec<_>>());
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct OnDiskStorage {
     file_path: String,
-    tables: HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>,
+    #[serde(skip)]
+    pool: BufferPool,
     index_manager: OnDiskIndexManager,
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    format: SerializationFormat,
+    #[serde(default)]
+    schema_version: u32,
+    // Why the data file couldn't be opened by `Storage::new`; the storage then refuses to
+    // write over it and every fallible call reports this
+    #[serde(skip)]
+    open_error: Option<String>,
+}
+
+type Table = (Vec<ColumnDef>, Vec<Vec<DataValue>>);
+type TableMap = HashMap<String, Table>;
+
+/// Buffer pool activity of an `OnDiskStorage` since it was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Table reads served from memory
+    pub hits: u64,
+    /// Table reads that had to load the table from the data file
+    pub misses: u64,
+    pub evictions: u64,
+    /// Writes of the data file forced by evicting a table with unsaved changes
+    pub flushes: u64,
+}
+
+// The tables known to the storage, loaded or not. Without a capacity every table stays
+// loaded and changes are written through; with one, the least recently used tables are
+// dropped from memory once more than the capacity are loaded, and changes are only
+// written when a changed table is evicted, the storage is flushed or it is dropped.
+// Reads load a table through a shared reference but never drop one, so what they hand
+// out lives as long as the borrow; evictions wait for the next change or flush.
+#[derive(Debug, Default)]
+struct BufferPool {
+    capacity: Option<usize>,
+    frames: HashMap<String, Frame>,
+    clock: AtomicU64,
+    stats: Mutex<BufferPoolStats>,
+    // Copy of every table handed out by `get_all_tables`, dropped on the next change
+    all_tables: OnceLock<TableMap>,
+    layout: Mutex<FileLayout>,
+    // A dropped table or changed index or schema version that isn't written yet
+    unsaved: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct Frame {
+    table: OnceLock<Table>,
+    last_used: AtomicU64,
+    dirty: AtomicBool,
+}
+
+// A copy holds its own tables, so changing one through a copy leaves the other alone
+impl Clone for Frame {
+    fn clone(&self) -> Self {
+        Frame {
+            table: self.table.clone(),
+            last_used: AtomicU64::new(self.last_used.load(Ordering::Relaxed)),
+            dirty: AtomicBool::new(self.dirty.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Clone for BufferPool {
+    fn clone(&self) -> Self {
+        BufferPool {
+            capacity: self.capacity,
+            frames: self.frames.clone(),
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            stats: Mutex::new(*lock_recovering(&self.stats)),
+            all_tables: self.all_tables.clone(),
+            layout: Mutex::new(lock_recovering(&self.layout).clone()),
+            unsaved: AtomicBool::new(self.unsaved.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl BufferPool {
+    fn loaded(tables: TableMap) -> Self {
+        let frames = tables.into_iter()
+            .map(|(name, table)| (name, Frame { table: OnceLock::from(table), ..Frame::default() }))
+            .collect();
+        BufferPool { frames, ..BufferPool::default() }
+    }
+
+    fn touch(&self, frame: &Frame) {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+        frame.last_used.store(now, Ordering::Relaxed);
+    }
+
+    fn record(&self, update: impl FnOnce(&mut BufferPoolStats)) {
+        update(&mut lock_recovering(&self.stats));
+    }
+
+    fn is_loaded(frame: &Frame) -> bool {
+        frame.table.get().is_some()
+    }
+
+    fn has_unsaved_changes(&self) -> bool {
+        self.unsaved.load(Ordering::Relaxed)
+            || self.frames.values().any(|frame| frame.dirty.load(Ordering::Relaxed))
+    }
+
+    // Whether evicting the table would lose it: it changed, or the data file has no
+    // section of its own for it yet
+    fn needs_write(&self, table_name: &str, frame: &Frame) -> bool {
+        frame.dirty.load(Ordering::Relaxed) || !lock_recovering(&self.layout).sections.contains_key(table_name)
+    }
+
+    // Least recently used loaded table other than `keep`, once more than the capacity are loaded
+    fn victim(&self, keep: &str) -> Option<String> {
+        let capacity = self.capacity?;
+        let loaded = self.frames.values().filter(|frame| Self::is_loaded(frame)).count();
+        if loaded <= capacity {
+            return None;
+        }
+        self.frames.iter()
+            .filter(|(name, frame)| name.as_str() != keep && Self::is_loaded(frame))
+            .min_by_key(|(_, frame)| frame.last_used.load(Ordering::Relaxed))
+            .map(|(name, _)| name.clone())
+    }
+}
+
+// A table's section of the data file: its offset from the start of the file and its length
+type Section = (u64, u64);
+
+// Header settings and table sections of the data file as last read or written. Files
+// written before "RDB4" have no sections; all their tables load when they are opened.
+#[derive(Clone, Debug, Default)]
+struct FileLayout {
+    compression: Compression,
+    format: SerializationFormat,
+    row_format: u8,
+    sections: HashMap<String, Section>,
+}
+
+impl FileLayout {
+    fn read_section(&self, file_path: &str, table_name: &str) -> Result<Option<Vec<u8>>, ReefDBError> {
+        let Some(&(offset, len)) = self.sections.get(table_name) else {
+            return Ok(None);
+        };
+        let mut file = File::open(file_path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    fn load(&self, file_path: &str, table_name: &str) -> Result<Option<Table>, ReefDBError> {
+        let Some(bytes) = self.read_section(file_path, table_name)? else {
+            return Ok(None);
+        };
+        let mut table = self.format.decode(&self.compression.decompress(&bytes)?)?;
+        migrate_rows(&mut table, self.row_format);
+        Ok(Some(table))
+    }
+}
+
+// Directory of an "RDB4" data file: the section of each table, with offsets counted from
+// the end of the directory, the B-Tree index columns and the schema version
+type Directory = (Vec<(String, u64, u64)>, Vec<(String, String)>, u32);
+
+// Tables loaded from a data file, its layout, B-Tree index columns and schema version
+type DataFile = (TableMap, FileLayout, Vec<(String, String)>, u32);
+
+// Reads the directory of an "RDB4" data file, leaving its tables on disk, or every table
+// of an older one
+fn read_data_file(file_path: &str) -> Result<DataFile, ReefDBError> {
+    let mut file = File::open(file_path)?;
+    let mut contents = Vec::new();
+    (&mut file).take(HEADER_LEN as u64).read_to_end(&mut contents)?;
+    if contents.len() == HEADER_LEN && contents.starts_with(FILE_MAGIC) {
+        let compression = Compression::from_tag(contents[4])
+            .ok_or_else(|| invalid_data(format!("unknown compression {}", contents[4])))?;
+        let format = SerializationFormat::from_tag(contents[5])
+            .ok_or_else(|| invalid_data(format!("unknown serialization format {}", contents[5])))?;
+        let row_format = check_row_format(contents[6])?;
+        let directory_len = u64::from_le_bytes(contents[7..].try_into().unwrap());
+        let mut directory = Vec::new();
+        (&mut file).take(directory_len).read_to_end(&mut directory)?;
+        if directory.len() as u64 != directory_len {
+            return Err(invalid_data("truncated data file directory".to_string()).into());
+        }
+        let (entries, btree_columns, schema_version): Directory = format.decode(&directory)?;
+        let start = HEADER_LEN as u64 + directory_len;
+        let sections = entries.into_iter()
+            .map(|(name, offset, len)| (name, (start + offset, len)))
+            .collect();
+        let layout = FileLayout { compression, format, row_format, sections };
+        return Ok((TableMap::new(), layout, btree_columns, schema_version));
+    }
+
+    file.read_to_end(&mut contents)?;
+    // A file created but never written holds no tables yet
+    if contents.is_empty() {
+        return Ok((TableMap::new(), FileLayout::default(), Vec::new(), 0));
+    }
+    let (_, file_format, row_format, contents) = decode_file(&contents)?;
+    // Older files lack the schema version, and before that the index definitions
+    let (mut tables, btree_columns, schema_version): (TableMap, Vec<(String, String)>, u32) = file_format.decode(&contents)
+        .or_else(|_| file_format.decode::<(TableMap, Vec<(String, String)>)>(&contents)
+            .map(|(tables, btree_columns)| (tables, btree_columns, 0)))
+        .or_else(|_| file_format.decode::<TableMap>(&contents).map(|tables| (tables, Vec::new(), 0)))?;
+    for table in tables.values_mut() {
+        migrate_rows(table, row_format);
+    }
+    Ok((tables, FileLayout::default(), btree_columns, schema_version))
+}
+
+fn missing_table(table_name: &str, file_path: &str) -> ReefDBError {
+    ReefDBError::Other(format!("Table {} is missing from {}", table_name, file_path))
+}
+
+// UNIQUE, NOT NULL and PRIMARY KEY checks of a row against the rows already in its table
+fn check_row<'a>(columns: &[ColumnDef], existing: impl Iterator<Item = &'a Vec<DataValue>> + Clone, row: &[DataValue]) -> Result<(), ReefDBError> {
+    for (i, (column, value)) in columns.iter().zip(row.iter()).enumerate() {
+        // Check UNIQUE constraint. Deferred columns are checked by ReefDB instead,
+        // before the write or at commit.
+        if column.constraints.contains(&Constraint::Unique) && !column.is_deferred() {
+            for existing_row in existing.clone() {
+                if column.collation().key(&existing_row[i]) == column.collation().key(value) {
+                    return Err(ReefDBError::Other(format!(
+                        "Unique constraint violation for column {} with value {:?}",
+                        column.name, value
+                    )));
+                }
+            }
+        }
+
+        // Check NOT NULL constraint
+        if column.constraints.contains(&Constraint::NotNull) {
+            if let DataValue::Text(text) = value {
+                if text.is_empty() {
+                    return Err(ReefDBError::Other(format!(
+                        "NOT NULL constraint violation for column {}",
+                        column.name
+                    )));
+                }
+            }
+        }
+
+        // Check PRIMARY KEY constraint
+        if column.constraints.contains(&Constraint::PrimaryKey) && !column.is_deferred() {
+            for existing_row in existing.clone() {
+                if column.collation().key(&existing_row[i]) == column.collation().key(value) {
+                    return Err(ReefDBError::Other(format!(
+                        "Primary key violation for column {} with value {:?}",
+                        column.name, value
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
 impl OnDiskStorage {
-    pub fn new(file_path: String) -> Self {
-        let tables = if Path::new(&file_path).exists() {
+    /// Opens the data file at `file_path`, failing if it can't be read or decoded rather
+    /// than starting empty and overwriting it on the next save
+    pub fn new(file_path: String) -> Result<Self, ReefDBError> {
+        Self::with_options(file_path, Compression::None, SerializationFormat::Bincode)
+    }
+
+    /// Opens the data file at `file_path`, writing it with `compression` from now on.
+    /// Existing files load whatever compression they were written with.
+    pub fn with_compression(file_path: String, compression: Compression) -> Result<Self, ReefDBError> {
+        Self::with_options(file_path, compression, SerializationFormat::Bincode)
+    }
+
+    /// Opens the data file at `file_path`, keeping at most `capacity` tables in memory and
+    /// loading each one from the file the first time it is used. Changes stay in memory
+    /// until their table is evicted or the storage is flushed or dropped.
+    pub fn with_buffer_pool(file_path: String, capacity: usize) -> Result<Self, ReefDBError> {
+        Self::open(file_path, Compression::None, SerializationFormat::Bincode, Some(capacity))
+    }
+
+    /// Opens the data file at `file_path`, writing it with `compression` and `format` from
+    /// now on. Existing files load with whatever settings their header records.
+    pub fn with_options(file_path: String, compression: Compression, format: SerializationFormat) -> Result<Self, ReefDBError> {
+        Self::open(file_path, compression, format, None)
+    }
+
+    fn open(file_path: String, compression: Compression, format: SerializationFormat, capacity: Option<usize>) -> Result<Self, ReefDBError> {
+        let (tables, layout, btree_columns, schema_version) = if Path::new(&file_path).exists() {
             println!("Loading existing file: {}", file_path);
-            let mut file = File::open(&file_path).unwrap();
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).unwrap();
-            println!("Read {} bytes", contents.len());
-            let tables = deserialize(&contents).unwrap_or_default();
-            println!("Loaded tables: {:?}", tables);
-            tables
+            read_data_file(&file_path)?
         } else {
             println!("File does not exist: {}", file_path);
-            HashMap::new()
+            (HashMap::new(), FileLayout::default(), Vec::new(), 0)
         };
 
-        OnDiskStorage {
+        let mut pool = BufferPool::loaded(tables);
+        pool.capacity = capacity;
+        for table_name in layout.sections.keys() {
+            pool.frames.entry(table_name.clone()).or_default();
+        }
+        *lock_recovering(&pool.layout) = layout;
+        let mut storage = OnDiskStorage {
             file_path: file_path.clone(),
-            tables,
+            pool,
             index_manager: OnDiskIndexManager::new(file_path),
+            compression,
+            format,
+            schema_version,
+            open_error: None,
+        };
+        // Without a buffer pool every table is kept in memory from the start
+        if capacity.is_none() {
+            for table_name in storage.table_names() {
+                storage.table(&table_name)?;
+            }
+        }
+        storage.rebuild_indexes(&btree_columns)?;
+        // Tables of a file written before "RDB4" all loaded at once
+        storage.evict_past_capacity("")?;
+        Ok(storage)
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn format(&self) -> SerializationFormat {
+        self.format
+    }
+
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        *lock_recovering(&self.pool.stats)
+    }
+
+    fn check_open(&self) -> Result<(), ReefDBError> {
+        match &self.open_error {
+            Some(e) => Err(ReefDBError::Other(format!("Failed to open {}: {}", self.file_path, e))),
+            None => Ok(()),
+        }
+    }
+
+    // Rebuilds B-Tree indexes from the loaded rows so they can't go stale
+    // relative to the table data, even if the index file was lost
+    fn rebuild_indexes(&mut self, btree_columns: &[(String, String)]) -> Result<(), ReefDBError> {
+        for (table, column) in btree_columns {
+            let Some((schema, rows)) = self.table(table)? else {
+                continue;
+            };
+            let Some(column_idx) = schema.iter().position(|c| &c.name == column) else {
+                continue;
+            };
+            let index = BTreeIndex::from_rows(rows, column_idx);
+            let _ = self.index_manager.create_index(table, column, IndexType::BTree(index));
+        }
+        Ok(())
+    }
+
+    // The table from memory, loading it from the data file if it was evicted
+    fn table(&self, table_name: &str) -> Result<Option<&Table>, ReefDBError> {
+        let Some(frame) = self.pool.frames.get(table_name) else {
+            return Ok(None);
+        };
+        self.pool.touch(frame);
+        if let Some(table) = frame.table.get() {
+            self.pool.record(|stats| stats.hits += 1);
+            return Ok(Some(table));
+        }
+        self.pool.record(|stats| stats.misses += 1);
+        let table = lock_recovering(&self.pool.layout).load(&self.file_path, table_name)?
+            .ok_or_else(|| missing_table(table_name, &self.file_path))?;
+        Ok(Some(frame.table.get_or_init(|| table)))
+    }
+
+    // The table for a change, which counts it as unsaved until written. Without a buffer
+    // pool, a change that couldn't be written is retried first and its error reported.
+    fn table_mut(&mut self, table_name: &str) -> Result<Option<&mut Table>, ReefDBError> {
+        self.check_open()?;
+        if self.pool.capacity.is_none() && self.pool.has_unsaved_changes() {
+            self.sync()?;
+        }
+        if self.table(table_name)?.is_none() {
+            return Ok(None);
+        }
+        self.evict_past_capacity(table_name)?;
+        self.pool.all_tables = OnceLock::new();
+        let Some(frame) = self.pool.frames.get_mut(table_name) else {
+            return Ok(None);
+        };
+        *frame.dirty.get_mut() = true;
+        Ok(frame.table.get_mut())
+    }
+
+    fn evict_past_capacity(&mut self, keep: &str) -> Result<(), ReefDBError> {
+        while let Some(victim) = self.pool.victim(keep) {
+            self.evict(&victim)?;
+        }
+        Ok(())
+    }
+
+    fn evict(&mut self, table_name: &str) -> Result<(), ReefDBError> {
+        let Some(frame) = self.pool.frames.get(table_name) else {
+            return Ok(());
+        };
+        if self.pool.needs_write(table_name, frame) {
+            self.sync()?;
+            self.pool.record(|stats| stats.flushes += 1);
+        }
+        if let Some(frame) = self.pool.frames.get_mut(table_name) {
+            if frame.table.take().is_some() {
+                self.pool.record(|stats| stats.evictions += 1);
+            }
+        }
+        Ok(())
+    }
+
+    // Writes a change straight to the data file unless the buffer pool holds it back
+    fn persist(&mut self) {
+        self.pool.all_tables = OnceLock::new();
+        *self.pool.unsaved.get_mut() = true;
+        if self.pool.capacity.is_none() {
+            self.save();
+        }
+    }
+
+    fn encode_table(&self, table: &Table) -> Result<Vec<u8>, ReefDBError> {
+        Ok(self.compression.compress(self.format.encode(table)?)?)
+    }
+
+    fn encode(&self) -> Result<(Vec<u8>, FileLayout), ReefDBError> {
+        let layout = lock_recovering(&self.pool.layout);
+        // Evicted tables are unchanged since they were last written, so their sections are
+        // copied as they are unless the file was written with other settings
+        let reusable = layout.compression == self.compression
+            && layout.format == self.format
+            && layout.row_format == ROW_FORMAT_VERSION;
+        let mut names: Vec<&String> = self.pool.frames.keys().collect();
+        names.sort();
+        let mut entries = Vec::new();
+        let mut sections = Vec::new();
+        let mut offset = 0;
+        for table_name in names {
+            let section = match self.pool.frames[table_name].table.get() {
+                Some(table) => self.encode_table(table)?,
+                None if reusable => layout.read_section(&self.file_path, table_name)?
+                    .ok_or_else(|| missing_table(table_name, &self.file_path))?,
+                None => self.encode_table(&layout.load(&self.file_path, table_name)?
+                    .ok_or_else(|| missing_table(table_name, &self.file_path))?)?,
+            };
+            entries.push((table_name.clone(), offset, section.len() as u64));
+            offset += section.len() as u64;
+            sections.push(section);
         }
+        let directory = self.format.encode(&(&entries, self.index_manager.btree_columns(), self.schema_version))?;
+
+        let mut contents = FILE_MAGIC.to_vec();
+        contents.extend([self.compression.tag(), self.format.tag(), ROW_FORMAT_VERSION]);
+        contents.extend((directory.len() as u64).to_le_bytes());
+        contents.extend(directory);
+        let start = contents.len() as u64;
+        contents.extend(sections.concat());
+        let sections = entries.into_iter()
+            .map(|(name, offset, len)| (name, (start + offset, len)))
+            .collect();
+        let layout = FileLayout {
+            compression: self.compression,
+            format: self.format,
+            row_format: ROW_FORMAT_VERSION,
+            sections,
+        };
+        Ok((contents, layout))
     }
 
+    /// Writes the data file. If that fails the changes stay unsaved, and the next change,
+    /// `sync` or `flush` writes them again and reports the error.
     pub fn save(&self) {
-        println!("Saving tables: {:?}", self.tables);
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&self.file_path)
-            .unwrap();
-        let mut writer = BufWriter::new(file);
-        let serialized = serialize(&self.tables).unwrap();
-        println!("Writing {} bytes", serialized.len());
-        writer.write_all(&serialized).unwrap();
-        writer.flush().unwrap();
+        self.pool.unsaved.store(true, Ordering::Relaxed);
+        let _ = self.sync();
     }
 
-    pub fn sync(&self) -> std::io::Result<()> {
+    pub fn sync(&self) -> Result<(), ReefDBError> {
+        self.check_open()?;
+        let (serialized, layout) = self.encode()?;
         let file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&self.file_path)?;
         let mut writer = BufWriter::new(file);
-        let serialized = serialize(&self.tables).unwrap();
         writer.write_all(&serialized)?;
         writer.flush()?;
+        *lock_recovering(&self.pool.layout) = layout;
+        for frame in self.pool.frames.values() {
+            frame.dirty.store(false, Ordering::Relaxed);
+        }
+        self.pool.unsaved.store(false, Ordering::Relaxed);
         Ok(())
     }
 }
 
-unsafe impl Send for OnDiskStorage {}
-unsafe impl Sync for OnDiskStorage {}
+// A copy starts from what is on disk, so dropping either one only writes its own changes
+impl Clone for OnDiskStorage {
+    fn clone(&self) -> Self {
+        if self.pool.has_unsaved_changes() {
+            let _ = self.sync();
+        }
+        OnDiskStorage {
+            file_path: self.file_path.clone(),
+            pool: self.pool.clone(),
+            index_manager: self.index_manager.clone(),
+            compression: self.compression,
+            format: self.format,
+            schema_version: self.schema_version,
+            open_error: self.open_error.clone(),
+        }
+    }
+}
+
+// Changes the buffer pool held back reach the data file when the storage goes away.
+// Dropping can't report a failed write; `flush` first to be told about one.
+impl Drop for OnDiskStorage {
+    fn drop(&mut self) {
+        if self.pool.has_unsaved_changes() {
+            let _ = self.sync();
+        }
+    }
+}
 
 impl Storage for OnDiskStorage {
     type NewArgs = String;
 
+    // A data file that can't be opened leaves an empty storage that won't write over it
+    // and reports why on every fallible call
     fn new(args: Self::NewArgs) -> Self {
-        Self::new(args)
+        Self::new(args.clone()).unwrap_or_else(|e| OnDiskStorage {
+            file_path: args.clone(),
+            pool: BufferPool::default(),
+            index_manager: OnDiskIndexManager::new(args),
+            compression: Compression::None,
+            format: SerializationFormat::Bincode,
+            schema_version: 0,
+            open_error: Some(e.to_string()),
+        })
     }
 
     fn insert_table(
@@ -242,62 +949,36 @@ impl Storage for OnDiskStorage {
         columns: Vec<ColumnDef>,
         row: Vec<Vec<DataValue>>,
     ) {
-        self.tables.insert(table_name, (columns, row));
+        let table = OnceLock::from((columns, row));
+        self.pool.frames.insert(table_name.clone(), Frame { table, dirty: AtomicBool::new(true), ..Frame::default() });
+        // A table that can't be made room for yet stays loaded; the next change or flush
+        // evicts it or reports why it can't
+        let _ = self.evict_past_capacity(&table_name);
         // Ensure changes are persisted to disk
-        self.save();
+        self.persist();
     }
 
     fn get_table(
         &mut self,
         table_name: &str,
     ) -> Option<&mut (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
-        self.tables.get_mut(table_name)
+        // One that can't be loaded is missing here; fallible calls report why
+        self.table_mut(table_name).ok().flatten()
     }
 
     fn table_exists(&self, table_name: &str) -> bool {
-        self.tables.contains_key(table_name)
+        self.pool.frames.contains_key(table_name)
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.pool.frames.keys().cloned().collect();
+        names.sort();
+        names
     }
 
     fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError> {
-        if let Some((columns, rows)) = self.get_table(table_name) {
-            // Validate constraints
-            for (i, (column, value)) in columns.iter().zip(row.iter()).enumerate() {
-                // Check UNIQUE constraint
-                if column.constraints.contains(&Constraint::Unique) {
-                    for existing_row in rows.iter() {
-                        if existing_row[i] == *value {
-                            return Err(ReefDBError::Other(format!(
-                                "Unique constraint violation for column {} with value {:?}",
-                                column.name, value
-                            )));
-                        }
-                    }
-                }
-                
-                // Check NOT NULL constraint
-                if column.constraints.contains(&Constraint::NotNull) {
-                    if let DataValue::Text(text) = value {
-                        if text.is_empty() {
-                            return Err(ReefDBError::Other(format!(
-                                "NOT NULL constraint violation for column {}",
-                                column.name
-                            )));
-                        }
-                    }
-                }
-                
-                // Check PRIMARY KEY constraint
-                if column.constraints.contains(&Constraint::PrimaryKey) {
-                    for existing_row in rows.iter() {
-                        if existing_row[i] == *value {
-                            return Err(ReefDBError::Other(format!(
-                                "Primary key violation for column {} with value {:?}",
-                                column.name, value
-                            )));
-                        }
-                    }
-                }
-            }
+        if let Some((columns, rows)) = self.table_mut(table_name)? {
+            check_row(columns, rows.iter(), &row)?;
 
             // Get the rowid before modifying the table
             let rowid = rows.len() + 1;
@@ -306,7 +987,9 @@ impl Storage for OnDiskStorage {
             rows.push(row);
 
             // Save after all modifications are done
-            let _ = self.sync();
+            if self.pool.capacity.is_none() {
+                let _ = self.sync();
+            }
 
             // Return the rowid (1-based index)
             Ok(rowid)
@@ -316,6 +999,24 @@ impl Storage for OnDiskStorage {
         }
     }
 
+    // Every row is checked before any is added, so a failing batch leaves the table as it
+    // was, and the data file is written once for the whole batch
+    fn append_rows(&mut self, table_name: &str, batch: Vec<Vec<DataValue>>) -> Result<Vec<usize>, ReefDBError> {
+        let Some((columns, rows)) = self.table_mut(table_name)? else {
+            return Err(ReefDBError::TableNotFound(table_name.to_string()));
+        };
+        for (i, row) in batch.iter().enumerate() {
+            check_row(columns, rows.iter().chain(&batch[..i]), row)?;
+        }
+        let first = rows.len() + 1;
+        rows.extend(batch);
+        let row_ids = (first..=rows.len()).collect();
+        if self.pool.capacity.is_none() {
+            self.sync()?;
+        }
+        Ok(row_ids)
+    }
+
     fn update_table(
         &mut self,
         table_name: &str,
@@ -341,7 +1042,7 @@ impl Storage for OnDiskStorage {
                 last_updated_row_id = idx + 1; // Convert to 1-based index
             }
         }
-        self.save();
+        self.persist();
         last_updated_row_id
     }
 
@@ -365,40 +1066,43 @@ impl Storage for OnDiskStorage {
             }
             idx += 1;
         }
-        self.save();
+        self.persist();
         idx
     }
 
     fn get_table_ref(&self, table_name: &str) -> Option<&(Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
-        self.tables.get(table_name)
+        self.table(table_name).ok().flatten()
     }
 
     fn remove_table(&mut self, table_name: &str) -> bool {
-        let exists = self.tables.remove(table_name).is_some();
+        let exists = self.pool.frames.remove(table_name).is_some();
         if exists {
-            self.save();
+            self.persist();
         }
         exists
     }
 
     fn add_column(&mut self, table_name: &str, column_def: ColumnDef) -> Result<(), ReefDBError> {
-        if let Some((schema, data)) = self.tables.get_mut(table_name) {
+        if let Some((schema, data)) = self.table_mut(table_name)? {
             schema.push(column_def.clone());
             // Add default value for the new column in all existing rows
             let default_value = match column_def.data_type {
                 DataType::Integer => DataValue::Integer(0),
                 DataType::Float => DataValue::Float(0.0),
+                DataType::Decimal { scale, .. } => DataValue::Decimal(Decimal::new(0, scale)),
                 DataType::Boolean => DataValue::Boolean(false),
-                DataType::Text => DataValue::Text("".to_string()),
+                DataType::Text | DataType::Varchar(_) => DataValue::Text("".into()),
                 DataType::Date => DataValue::Date("1970-01-01".to_string()),
                 DataType::Timestamp => DataValue::Timestamp("1970-01-01 00:00:00".to_string()),
-                DataType::TSVector => DataValue::Text("".to_string()),
-                DataType::Null => DataValue::Null,
+                DataType::TSVector => DataValue::Text("".into()),
+                DataType::Uuid | DataType::Json | DataType::Null => DataValue::Null,
+                DataType::Array(_) => DataValue::Array(vec![]),
+                DataType::Enum(ref labels) => labels.first().map_or(DataValue::Null, |label| DataValue::Text(label.as_str().into())),
             };
             for row in data.iter_mut() {
                 row.push(default_value.clone());
             }
-            self.save();
+            self.persist();
             Ok(())
         } else {
             Err(ReefDBError::TableNotFound(table_name.to_string()))
@@ -406,14 +1110,14 @@ impl Storage for OnDiskStorage {
     }
 
     fn drop_column(&mut self, table_name: &str, column_name: &str) -> Result<(), ReefDBError> {
-        if let Some((schema, data)) = self.tables.get_mut(table_name) {
+        if let Some((schema, data)) = self.table_mut(table_name)? {
             if let Some(idx) = schema.iter().position(|col| col.name == column_name) {
                 schema.remove(idx);
                 // Remove the column data from all rows
                 for row in data.iter_mut() {
                     row.remove(idx);
                 }
-                self.save();
+                self.persist();
                 Ok(())
             } else {
                 Err(ReefDBError::ColumnNotFound(column_name.to_string()))
@@ -424,10 +1128,10 @@ impl Storage for OnDiskStorage {
     }
 
     fn rename_column(&mut self, table_name: &str, old_name: &str, new_name: &str) -> Result<(), ReefDBError> {
-        if let Some((schema, _)) = self.tables.get_mut(table_name) {
+        if let Some((schema, _)) = self.table_mut(table_name)? {
             if let Some(col) = schema.iter_mut().find(|col| col.name == old_name) {
                 col.name = new_name.to_string();
-                self.save();
+                self.persist();
                 Ok(())
             } else {
                 Err(ReefDBError::ColumnNotFound(old_name.to_string()))
@@ -438,8 +1142,8 @@ impl Storage for OnDiskStorage {
     }
 
     fn drop_table(&mut self, table_name: &str) {
-        if self.tables.remove(table_name).is_some() {
-            self.save();
+        if self.pool.frames.remove(table_name).is_some() {
+            self.persist();
         }
     }
 
@@ -448,7 +1152,9 @@ impl Storage for OnDiskStorage {
     }
 
     fn clear(&mut self) {
-        self.tables.clear();
+        self.pool.frames.clear();
+        self.pool.all_tables = OnceLock::new();
+        *lock_recovering(&self.pool.layout) = FileLayout::default();
         // Also clear the on-disk storage
         let file_path = &self.file_path;
         if let Some(parent) = std::path::Path::new(file_path).parent() {
@@ -464,18 +1170,47 @@ impl Storage for OnDiskStorage {
         }
     }
 
+    // Reads evicted tables from the data file into a copy rather than growing the pool past
+    // its capacity. One that can't be read is left out; fallible calls report why.
     fn get_all_tables(&self) -> &HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
-        &self.tables
+        self.pool.all_tables.get_or_init(|| {
+            let layout = lock_recovering(&self.pool.layout);
+            self.pool.frames.iter()
+                .filter_map(|(name, frame)| match frame.table.get() {
+                    Some(table) => Some((name.clone(), table.clone())),
+                    None => layout.load(&self.file_path, name).ok().flatten().map(|table| (name.clone(), table)),
+                })
+                .collect()
+        })
+    }
+
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+        self.save();
+    }
+
+    // Also evicts whatever reads loaded past the capacity
+    fn flush(&mut self) -> Result<(), ReefDBError> {
+        self.sync()?;
+        self.evict_past_capacity("")
     }
 }
 
 impl IndexManager for OnDiskStorage {
     fn create_index(&mut self, table: &str, column: &str, index_type: IndexType) -> Result<(), ReefDBError> {
-        self.index_manager.create_index(table, column, index_type)
+        self.index_manager.create_index(table, column, index_type)?;
+        // Persist the definition alongside the table data
+        self.save();
+        Ok(())
     }
 
     fn drop_index(&mut self, table: &str, column: &str) {
-        self.index_manager.drop_index(table, column)
+        self.index_manager.drop_index(table, column);
+        self.save();
     }
 
     fn get_index(&self, table: &str, column: &str) -> Result<&IndexType, ReefDBError> {
@@ -512,7 +1247,7 @@ mod tests {
         
         // Create and populate storage
         {
-            let mut storage = OnDiskStorage::new(file_path.clone());
+            let mut storage = OnDiskStorage::new(file_path.clone()).unwrap();
             let columns = vec![
                 ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
                 ColumnDef::new("name", DataType::Text, vec![]),
@@ -521,12 +1256,12 @@ mod tests {
             let rows = vec![
                 vec![
                     DataValue::Integer(1),
-                    DataValue::Text("John".to_string()),
+                    DataValue::Text("John".into()),
                     DataValue::Integer(20),
                 ],
                 vec![
                     DataValue::Integer(2),
-                    DataValue::Text("Jane".to_string()),
+                    DataValue::Text("Jane".into()),
                     DataValue::Integer(25),
                 ],
             ];
@@ -535,18 +1270,29 @@ mod tests {
 
         // Create new storage instance and verify persistence
         {
-            let mut storage = OnDiskStorage::new(file_path);
+            let mut storage = OnDiskStorage::new(file_path).unwrap();
             let (schema, rows) = storage.get_table("users").unwrap();
             assert_eq!(schema.len(), 3);
             assert_eq!(rows.len(), 2);
             assert_eq!(rows[0].len(), 3);
             assert_eq!(rows[1].len(), 3);
             assert_eq!(rows[0][0], DataValue::Integer(1));
-            assert_eq!(rows[0][1], DataValue::Text("John".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("John".into()));
             assert_eq!(rows[0][2], DataValue::Integer(20));
             assert_eq!(rows[1][0], DataValue::Integer(2));
-            assert_eq!(rows[1][1], DataValue::Text("Jane".to_string()));
+            assert_eq!(rows[1][1], DataValue::Text("Jane".into()));
             assert_eq!(rows[1][2], DataValue::Integer(25));
         }
     }
+
+    #[test]
+    fn test_newer_row_format_is_refused() {
+        let mut contents = FILE_MAGIC_V3.to_vec();
+        contents.extend([Compression::None.tag(), SerializationFormat::Bincode.tag(), ROW_FORMAT_VERSION]);
+        contents.extend(b"payload");
+        assert_eq!(decode_file(&contents).unwrap(), (Compression::None, SerializationFormat::Bincode, ROW_FORMAT_VERSION, b"payload".to_vec()));
+
+        contents[6] = ROW_FORMAT_VERSION + 1;
+        assert_eq!(decode_file(&contents).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
 }
@@ -1,18 +1,24 @@
 use crate::sql::column_def::ColumnDef;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::any::Any;
+use std::sync::Arc;
 
 use super::Storage;
 use crate::sql::data_value::DataValue;
+use crate::sql::decimal::Decimal;
 use crate::sql::data_type::DataType;
 use crate::error::ReefDBError;
 use crate::sql::constraints::constraint::Constraint;
 use crate::indexes::index_manager::{IndexManager, DefaultIndexManager, IndexUpdate};
+use crate::indexes::btree::BTreeIndex;
 
 #[derive(Clone)]
 pub struct InMemoryStorage {
     tables: HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>,
     index_manager: DefaultIndexManager,
+    schema_version: u32,
+    // One shared copy of every distinct text value written, when interning is on
+    interned_text: Option<HashSet<Arc<str>>>,
 }
 
 impl InMemoryStorage {
@@ -20,6 +26,32 @@ impl InMemoryStorage {
         InMemoryStorage {
             tables: HashMap::new(),
             index_manager: DefaultIndexManager::new(),
+            schema_version: 0,
+            interned_text: None,
+        }
+    }
+
+    /// Storage where equal `DataValue::Text` values written through `insert_table`,
+    /// `push_value`, `append_rows` and `update_table` share one allocation. Values compare
+    /// and sort as before; the shared copies live as long as the storage.
+    pub fn with_interned_text() -> Self {
+        InMemoryStorage {
+            interned_text: Some(HashSet::new()),
+            ..Self::new()
+        }
+    }
+
+    fn intern<'a>(&mut self, values: impl IntoIterator<Item = &'a mut DataValue>) {
+        let Some(interned) = &mut self.interned_text else { return };
+        for value in values {
+            if let DataValue::Text(text) = value {
+                match interned.get(text) {
+                    Some(shared) => *text = shared.clone(),
+                    None => {
+                        interned.insert(text.clone());
+                    }
+                }
+            }
         }
     }
 }
@@ -34,8 +66,9 @@ impl Storage for InMemoryStorage {
         &mut self,
         table_name: String,
         columns: Vec<ColumnDef>,
-        row: Vec<Vec<DataValue>>,
+        mut row: Vec<Vec<DataValue>>,
     ) {
+        self.intern(row.iter_mut().flatten());
         self.tables.insert(table_name, (columns, row));
     }
 
@@ -50,14 +83,22 @@ impl Storage for InMemoryStorage {
         self.tables.contains_key(table_name)
     }
 
-    fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError> {
+    fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn push_value(&mut self, table_name: &str, mut row: Vec<DataValue>) -> Result<usize, ReefDBError> {
+        self.intern(row.iter_mut());
         if let Some((columns, rows)) = self.get_table(table_name) {
             // Validate constraints
             for (i, (column, value)) in columns.iter().zip(row.iter()).enumerate() {
-                // Check UNIQUE constraint
-                if column.constraints.contains(&Constraint::Unique) {
+                // Check UNIQUE constraint. Deferred columns are checked by ReefDB instead,
+                // before the write or at commit.
+                if column.constraints.contains(&Constraint::Unique) && !column.is_deferred() {
                     for existing_row in rows.iter() {
-                        if existing_row[i] == *value {
+                        if column.collation().key(&existing_row[i]) == column.collation().key(value) {
                             return Err(ReefDBError::Other(format!(
                                 "Unique constraint violation for column {} with value {:?}",
                                 column.name, value
@@ -79,9 +120,9 @@ impl Storage for InMemoryStorage {
                 }
                 
                 // Check PRIMARY KEY constraint
-                if column.constraints.contains(&Constraint::PrimaryKey) {
+                if column.constraints.contains(&Constraint::PrimaryKey) && !column.is_deferred() {
                     for existing_row in rows.iter() {
-                        if existing_row[i] == *value {
+                        if column.collation().key(&existing_row[i]) == column.collation().key(value) {
                             return Err(ReefDBError::Other(format!(
                                 "Primary key violation for column {} with value {:?}",
                                 column.name, value
@@ -102,12 +143,64 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    fn append_rows(&mut self, table_name: &str, mut new_rows: Vec<Vec<DataValue>>) -> Result<Vec<usize>, ReefDBError> {
+        self.intern(new_rows.iter_mut().flatten());
+        let (columns, rows) = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| ReefDBError::TableNotFound(table_name.to_string()))?;
+
+        // Same checks as push_value, but uniqueness is checked against a set of keys built once
+        // per column instead of rescanning the table for every row. Nothing is appended on error.
+        for (i, column) in columns.iter().enumerate() {
+            if column.constraints.contains(&Constraint::NotNull)
+                && new_rows.iter().any(|row| matches!(&row[i], DataValue::Text(text) if text.is_empty()))
+            {
+                return Err(ReefDBError::Other(format!(
+                    "NOT NULL constraint violation for column {}",
+                    column.name
+                )));
+            }
+
+            let kind = if column.is_deferred() {
+                continue;
+            } else if column.constraints.contains(&Constraint::PrimaryKey) {
+                "Primary key violation"
+            } else if column.constraints.contains(&Constraint::Unique) {
+                "Unique constraint violation"
+            } else {
+                continue;
+            };
+            let collation = column.collation();
+            let mut seen: HashSet<Vec<u8>> = rows.iter().map(|row| BTreeIndex::key_for(&collation.key(&row[i]))).collect();
+            for row in &new_rows {
+                if !seen.insert(BTreeIndex::key_for(&collation.key(&row[i]))) {
+                    return Err(ReefDBError::Other(format!(
+                        "{} for column {} with value {:?}",
+                        kind, column.name, row[i]
+                    )));
+                }
+            }
+        }
+
+        let first_id = rows.len() + 1;
+        rows.extend(new_rows);
+        Ok((first_id..=rows.len()).collect())
+    }
+
+    fn reserve(&mut self, table_name: &str, additional: usize) {
+        if let Some((_, rows)) = self.tables.get_mut(table_name) {
+            rows.reserve(additional);
+        }
+    }
+
     fn update_table(
         &mut self,
         table_name: &str,
-        updates: Vec<(String, DataValue)>,
+        mut updates: Vec<(String, DataValue)>,
         where_clause: Option<(String, DataValue)>,
     ) -> usize {
+        self.intern(updates.iter_mut().map(|(_, value)| value));
         let (schema, rows) = self.get_table(table_name).unwrap();
         let mut updated_count = 0;
 
@@ -169,12 +262,15 @@ impl Storage for InMemoryStorage {
             let default_value = match column_def.data_type {
                 DataType::Integer => DataValue::Integer(0),
                 DataType::Float => DataValue::Float(0.0),
+                DataType::Decimal { scale, .. } => DataValue::Decimal(Decimal::new(0, scale)),
                 DataType::Boolean => DataValue::Boolean(false),
-                DataType::Text => DataValue::Text("".to_string()),
+                DataType::Text | DataType::Varchar(_) => DataValue::Text("".into()),
                 DataType::Date => DataValue::Date("1970-01-01".to_string()),
                 DataType::Timestamp => DataValue::Timestamp("1970-01-01 00:00:00".to_string()),
-                DataType::TSVector => DataValue::Text("".to_string()),
-                DataType::Null => DataValue::Null,
+                DataType::TSVector => DataValue::Text("".into()),
+                DataType::Uuid | DataType::Json | DataType::Null => DataValue::Null,
+                DataType::Array(_) => DataValue::Array(vec![]),
+                DataType::Enum(ref labels) => labels.first().map_or(DataValue::Null, |label| DataValue::Text(label.as_str().into())),
             };
             for row in data.iter_mut() {
                 row.push(default_value.clone());
@@ -230,6 +326,14 @@ impl Storage for InMemoryStorage {
     fn get_all_tables(&self) -> &HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
         &self.tables
     }
+
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+    }
 }
 
 impl IndexManager for InMemoryStorage {
@@ -280,12 +384,12 @@ mod tests {
         let rows = vec![
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("John".to_string()),
+                DataValue::Text("John".into()),
                 DataValue::Integer(20),
             ],
             vec![
                 DataValue::Integer(2),
-                DataValue::Text("Jane".to_string()),
+                DataValue::Text("Jane".into()),
                 DataValue::Integer(25),
             ],
         ];
@@ -296,10 +400,81 @@ mod tests {
         assert_eq!(rows[0].len(), 3);
         assert_eq!(rows[1].len(), 3);
         assert_eq!(rows[0][0], DataValue::Integer(1));
-        assert_eq!(rows[0][1], DataValue::Text("John".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("John".into()));
         assert_eq!(rows[0][2], DataValue::Integer(20));
         assert_eq!(rows[1][0], DataValue::Integer(2));
-        assert_eq!(rows[1][1], DataValue::Text("Jane".to_string()));
+        assert_eq!(rows[1][1], DataValue::Text("Jane".into()));
         assert_eq!(rows[1][2], DataValue::Integer(25));
     }
+
+    fn count_reallocations(storage: &mut InMemoryStorage, rows: usize) -> usize {
+        let mut reallocations = 0;
+        let mut capacity = storage.get_table_ref("bulk").unwrap().1.capacity();
+        for i in 0..rows {
+            storage.push_value("bulk", vec![DataValue::Integer(i as i64)]).unwrap();
+            let new_capacity = storage.get_table_ref("bulk").unwrap().1.capacity();
+            if new_capacity != capacity {
+                reallocations += 1;
+                capacity = new_capacity;
+            }
+        }
+        reallocations
+    }
+
+    #[test]
+    fn test_reserve_reduces_reallocations() {
+        let columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+
+        let mut storage = InMemoryStorage::new();
+        storage.insert_table("bulk".to_string(), columns.clone(), vec![]);
+        let without_reserve = count_reallocations(&mut storage, 10_000);
+
+        let mut storage = InMemoryStorage::new();
+        storage.insert_table("bulk".to_string(), columns, vec![]);
+        storage.reserve("bulk", 10_000);
+        let with_reserve = count_reallocations(&mut storage, 10_000);
+
+        assert!(without_reserve > 0);
+        assert_eq!(with_reserve, 0);
+        assert_eq!(storage.get_table_ref("bulk").unwrap().1.len(), 10_000);
+    }
+
+    // Distinct text allocations held by the `status` column of `orders`
+    fn text_allocations(storage: &InMemoryStorage) -> usize {
+        storage.get_table_ref("orders").unwrap().1.iter()
+            .filter_map(|row| match &row[1] {
+                DataValue::Text(text) => Some(Arc::as_ptr(text) as *const u8),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    #[test]
+    fn test_interned_text_shares_repeated_values() {
+        let statuses = ["pending", "paid", "shipped", "delivered", "returned"];
+        let columns = vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+            ColumnDef::new("status", DataType::Text, vec![]),
+        ];
+        // Each row is parsed from its own statement, so its text arrives in a fresh allocation
+        let row = |id: usize| vec![DataValue::Integer(id as i64), DataValue::Text(statuses[id % 5].to_string().into())];
+
+        let mut plain = InMemoryStorage::new();
+        let mut interned = InMemoryStorage::with_interned_text();
+        for storage in [&mut plain, &mut interned] {
+            storage.insert_table("orders".to_string(), columns.clone(), (0..500).map(row).collect());
+            for id in 500..1_000 {
+                storage.push_value("orders", row(id)).unwrap();
+            }
+            storage.append_rows("orders", (1_000..1_500).map(row).collect()).unwrap();
+        }
+        assert_eq!(text_allocations(&plain), 1_500);
+        assert_eq!(text_allocations(&interned), statuses.len());
+
+        // Values read back the same either way
+        assert_eq!(plain.get_table_ref("orders"), interned.get_table_ref("orders"));
+        interned.update_table("orders", vec![("status".to_string(), DataValue::Text("paid".to_string().into()))], None);
+        assert_eq!(text_allocations(&interned), 1);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::sql::column_def::ColumnDef;
 use crate::sql::data_value::DataValue;
+use crate::sql::decimal::Decimal;
 use crate::error::ReefDBError;
 use crate::indexes::{IndexManager, IndexType};
 use crate::indexes::index_manager::{IndexUpdate, DefaultIndexManager};
@@ -13,11 +14,14 @@ use std::any::Any;
 use super::Storage;
 use crate::sql::data_type::DataType;
 
+type TableMap = HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>;
+
 #[derive(Debug)]
 pub struct MmapStorage {
     file_path: String,
     tables: HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>,
     index_manager: DefaultIndexManager,
+    schema_version: u32,
     #[allow(dead_code)]
     mmap: Option<MmapMut>,
 }
@@ -28,6 +32,7 @@ impl Clone for MmapStorage {
             file_path: self.file_path.clone(),
             tables: self.tables.clone(),
             index_manager: self.index_manager.clone(),
+            schema_version: self.schema_version,
             mmap: None,
         }
     }
@@ -35,7 +40,7 @@ impl Clone for MmapStorage {
 
 impl MmapStorage {
     pub fn new(file_path: String) -> Self {
-        let tables = if Path::new(&file_path).exists() {
+        let (tables, schema_version) = if Path::new(&file_path).exists() {
             let file = OpenOptions::new()
                 .read(true)
                 .write(true)
@@ -49,27 +54,28 @@ impl MmapStorage {
             let mmap = unsafe { MmapOptions::new().map_mut(&file).unwrap() };
             
             if mmap.len() > 0 {
-                match deserialize(&mmap[..]) {
-                    Ok(tables) => tables,
-                    Err(_) => HashMap::new(),
-                }
+                // Files written before the schema version was stored hold only the tables
+                deserialize::<(TableMap, u32)>(&mmap[..])
+                    .or_else(|_| deserialize::<TableMap>(&mmap[..]).map(|tables| (tables, 0)))
+                    .unwrap_or_default()
             } else {
-                HashMap::new()
+                (HashMap::new(), 0)
             }
         } else {
-            HashMap::new()
+            (HashMap::new(), 0)
         };
 
         MmapStorage {
             file_path,
             tables,
             index_manager: DefaultIndexManager::new(),
+            schema_version,
             mmap: None,
         }
     }
 
     fn save(&mut self) -> Result<(), ReefDBError> {
-        let serialized = serialize(&self.tables)
+        let serialized = serialize(&(&self.tables, self.schema_version))
             .map_err(|e| ReefDBError::Other(format!("Serialization error: {}", e)))?;
 
         let file = OpenOptions::new()
@@ -77,19 +83,19 @@ impl MmapStorage {
             .write(true)
             .create(true)
             .open(&self.file_path)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
 
         // Ensure file is large enough
         let required_size = serialized.len() as u64;
         file.set_len(required_size)
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
 
         // Create new memory mapping
         let mut mmap = unsafe { 
             MmapOptions::new()
                 .len(serialized.len())
                 .map_mut(&file)
-                .map_err(|e| ReefDBError::IoError(e.to_string()))?
+                .map_err(ReefDBError::from)?
         };
 
         // Write data to memory map
@@ -97,7 +103,7 @@ impl MmapStorage {
         
         // Sync changes to disk
         mmap.flush()
-            .map_err(|e| ReefDBError::IoError(e.to_string()))?;
+            .map_err(ReefDBError::from)?;
 
         self.mmap = Some(mmap);
         Ok(())
@@ -107,12 +113,15 @@ impl MmapStorage {
         match data_type {
             DataType::Integer => DataValue::Integer(0),
             DataType::Float => DataValue::Float(0.0),
+            DataType::Decimal { scale, .. } => DataValue::Decimal(Decimal::new(0, *scale)),
             DataType::Boolean => DataValue::Boolean(false),
-            DataType::Text => DataValue::Text("".to_string()),
+            DataType::Text | DataType::Varchar(_) => DataValue::Text("".into()),
             DataType::Date => DataValue::Date("1970-01-01".to_string()),
             DataType::Timestamp => DataValue::Timestamp("1970-01-01 00:00:00".to_string()),
             DataType::TSVector => DataValue::TSVector(TsVector::new(vec![])),
-            DataType::Null => DataValue::Null,
+            DataType::Uuid | DataType::Json | DataType::Null => DataValue::Null,
+            DataType::Array(_) => DataValue::Array(vec![]),
+            DataType::Enum(labels) => labels.first().map_or(DataValue::Null, |label| DataValue::Text(label.as_str().into())),
         }
     }
 }
@@ -149,6 +158,12 @@ impl Storage for MmapStorage {
         self.tables.contains_key(table_name)
     }
 
+    fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError> {
         let len = if let Some((_, rows)) = self.tables.get_mut(table_name) {
             rows.push(row);
@@ -282,6 +297,19 @@ impl Storage for MmapStorage {
         &self.tables
     }
 
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+        let _ = self.save();
+    }
+
+    fn flush(&mut self) -> Result<(), ReefDBError> {
+        self.save()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -339,12 +367,12 @@ mod tests {
             let rows = vec![
                 vec![
                     DataValue::Integer(1),
-                    DataValue::Text("John".to_string()),
+                    DataValue::Text("John".into()),
                     DataValue::Integer(20),
                 ],
                 vec![
                     DataValue::Integer(2),
-                    DataValue::Text("Jane".to_string()),
+                    DataValue::Text("Jane".into()),
                     DataValue::Integer(25),
                 ],
             ];
@@ -360,11 +388,26 @@ mod tests {
             assert_eq!(rows[0].len(), 3);
             assert_eq!(rows[1].len(), 3);
             assert_eq!(rows[0][0], DataValue::Integer(1));
-            assert_eq!(rows[0][1], DataValue::Text("John".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("John".into()));
             assert_eq!(rows[0][2], DataValue::Integer(20));
             assert_eq!(rows[1][0], DataValue::Integer(2));
-            assert_eq!(rows[1][1], DataValue::Text("Jane".to_string()));
+            assert_eq!(rows[1][1], DataValue::Text("Jane".into()));
             assert_eq!(rows[1][2], DataValue::Integer(25));
         }
     }
+
+    #[test]
+    fn test_schema_version_persists() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let file_path = temp_file.path().to_string_lossy().to_string();
+        {
+            let mut storage = MmapStorage::new(file_path.clone());
+            storage.insert_table("users".to_string(), vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![]);
+            storage.set_schema_version(4);
+        }
+
+        let storage = MmapStorage::new(file_path);
+        assert_eq!(storage.schema_version(), 4);
+        assert!(storage.table_exists("users"));
+    }
 } 
\ No newline at end of file
@@ -1,13 +1,29 @@
 use std::collections::HashMap;
-use crate::{sql::column_def::ColumnDef, sql::{data_value::DataValue, data_type::DataType}, error::ReefDBError};
+use crate::{sql::column_def::ColumnDef, sql::{data_value::DataValue, data_type::DataType, decimal::Decimal}, error::ReefDBError};
 
 pub mod disk;
 pub mod memory;
 pub mod mmap;
+pub mod temporary;
 
 #[derive(Clone, Debug)]
 pub struct TableStorage {
     pub tables: HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>,
+    // Live row count per table, kept alongside the rows for cardinality estimates
+    row_counts: HashMap<String, usize>,
+    // Temporary tables of the transaction owning this copy, with the table each one hides
+    temporary: HashMap<String, Option<ShadowedTable>>,
+    schema_version: u32,
+}
+
+/// A table hidden by a temporary table of the same name, put back when that one is dropped
+#[derive(Clone, Debug)]
+pub struct ShadowedTable {
+    pub schema: Vec<ColumnDef>,
+    pub rows: Vec<Vec<DataValue>>,
+    pub expression_indexes: Vec<(String, DataValue)>,
+    pub unique_indexes: Vec<String>,
+    pub text_indexes: Vec<String>,
 }
 
 impl Default for TableStorage {
@@ -20,17 +36,80 @@ impl TableStorage {
     pub fn new() -> Self {
         TableStorage {
             tables: HashMap::new(),
+            row_counts: HashMap::new(),
+            temporary: HashMap::new(),
+            schema_version: 0,
         }
     }
 
     pub fn restore_from(&mut self, other: &TableStorage) {
         self.tables = other.tables.clone();
+        self.temporary = other.temporary.clone();
+        self.schema_version = other.schema_version;
+        // Recount from the restored rows so snapshots built through `tables` stay accurate
+        self.recount_rows();
+    }
+
+    /// Rebuilds the row counts after `tables` was modified directly
+    pub(crate) fn recount_rows(&mut self) {
+        self.row_counts = self.tables
+            .iter()
+            .map(|(name, (_, rows))| (name.clone(), rows.len()))
+            .collect();
     }
 
     pub fn clone(&self) -> TableStorage {
         TableStorage {
             tables: self.tables.clone(),
+            row_counts: self.row_counts.clone(),
+            temporary: self.temporary.clone(),
+            schema_version: self.schema_version,
+        }
+    }
+
+    /// Number of rows currently stored in `table_name`, or `None` if the table doesn't exist
+    pub fn row_count(&self, table_name: &str) -> Option<usize> {
+        self.row_counts.get(table_name).copied()
+    }
+
+    /// Whether `table_name` is a temporary table
+    pub fn is_temporary(&self, table_name: &str) -> bool {
+        self.temporary.contains_key(table_name)
+    }
+
+    /// Names of the temporary tables, sorted alphabetically
+    pub fn temporary_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.temporary.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn add_temporary(&mut self, table_name: String, shadowed: Option<ShadowedTable>) {
+        self.temporary.insert(table_name, shadowed);
+    }
+
+    // `None` if the table isn't temporary, otherwise the table it hid, if any
+    pub(crate) fn take_temporary(&mut self, table_name: &str) -> Option<Option<ShadowedTable>> {
+        self.temporary.remove(table_name)
+    }
+
+    // Keeps `other`'s temporary tables marked as such in a state rebuilt from its rows
+    pub(crate) fn copy_temporary_from(&mut self, other: &TableStorage) {
+        self.temporary = other.temporary.clone();
+    }
+
+    /// The state as other transactions may see it: temporary tables are left out and the
+    /// tables they hid are back
+    pub fn without_temporary(&self) -> TableStorage {
+        let mut state = self.clone();
+        for (table_name, shadowed) in state.temporary.drain() {
+            state.tables.remove(&table_name);
+            if let Some(shadowed) = shadowed {
+                state.tables.insert(table_name, (shadowed.schema, shadowed.rows));
+            }
         }
+        state.recount_rows();
+        state
     }
 }
 
@@ -61,6 +140,15 @@ pub trait Storage: std::any::Any {
     fn get_table_ref(&self, table_name: &str) -> Option<&(Vec<ColumnDef>, Vec<Vec<DataValue>>)>;
     fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError>;
 
+    // Capacity hint for bulk loads; backends that can preallocate rows override this
+    fn reserve(&mut self, _table_name: &str, _additional: usize) {}
+
+    // Appends a batch of rows and returns their row ids; the default goes through push_value row by row
+    fn append_rows(&mut self, table_name: &str, rows: Vec<Vec<DataValue>>) -> Result<Vec<usize>, ReefDBError> {
+        self.reserve(table_name, rows.len());
+        rows.into_iter().map(|row| self.push_value(table_name, row)).collect()
+    }
+
     fn update_table(
         &mut self,
         table_name: &str,
@@ -76,6 +164,9 @@ pub trait Storage: std::any::Any {
 
     fn table_exists(&self, table_name: &str) -> bool;
 
+    // Names of all tables, sorted alphabetically
+    fn table_names(&self) -> Vec<String>;
+
     fn get_schema(&mut self, table_name: &str) -> Option<&mut Vec<ColumnDef>> {
         self.get_table(table_name).map(|(schema, _)| schema)
     }
@@ -100,6 +191,17 @@ pub trait Storage: std::any::Any {
     // Get all tables and their data
     fn get_all_tables(&self) -> &HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)>;
 
+    /// Schema version of the database, bumped by `ALTER TABLE` and set by `SET SCHEMA VERSION`.
+    /// Persistent backends keep it with their data.
+    fn schema_version(&self) -> u32;
+
+    fn set_schema_version(&mut self, version: u32);
+
+    /// Writes any state not yet persisted; storages that write through have nothing to do
+    fn flush(&mut self) -> Result<(), ReefDBError> {
+        Ok(())
+    }
+
     // Restore storage state from a TableStorage
     fn restore_from(&mut self, state: &TableStorage) {
         self.clear();
@@ -122,9 +224,18 @@ impl Storage for TableStorage {
         columns: Vec<ColumnDef>,
         row: Vec<Vec<DataValue>>,
     ) {
+        self.row_counts.insert(table_name.clone(), row.len());
         self.tables.insert(table_name, (columns, row));
     }
 
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+    }
+
     fn get_table(&mut self, table_name: &str) -> Option<&mut (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
         self.tables.get_mut(table_name)
     }
@@ -134,8 +245,9 @@ impl Storage for TableStorage {
     }
 
     fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError> {
-        if let Some((_, rows)) = self.get_table(table_name) {
+        if let Some((_, rows)) = self.tables.get_mut(table_name) {
             rows.push(row);
+            self.row_counts.insert(table_name.to_string(), rows.len());
             Ok(rows.len())
         } else {
             Err(ReefDBError::TableNotFound(table_name.to_string()))
@@ -178,7 +290,7 @@ impl Storage for TableStorage {
         where_clause: Option<(String, DataValue)>,
     ) -> usize {
         let mut deleted_count = 0;
-        if let Some((columns, rows)) = self.get_table(table_name) {
+        if let Some((columns, rows)) = self.tables.get_mut(table_name) {
             let initial_len = rows.len();
             if let Some((col, val)) = where_clause {
                 if let Some(col_idx) = columns.iter().position(|c| c.name == col) {
@@ -189,6 +301,7 @@ impl Storage for TableStorage {
                 deleted_count = rows.len();
                 rows.clear();
             }
+            self.row_counts.insert(table_name.to_string(), rows.len());
         }
         deleted_count
     }
@@ -197,7 +310,14 @@ impl Storage for TableStorage {
         self.tables.contains_key(table_name)
     }
 
+    fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     fn remove_table(&mut self, table_name: &str) -> bool {
+        self.row_counts.remove(table_name);
         self.tables.remove(table_name).is_some()
     }
 
@@ -208,12 +328,15 @@ impl Storage for TableStorage {
             let default_value = match column_def.data_type {
                 DataType::Integer => DataValue::Integer(0),
                 DataType::Float => DataValue::Float(0.0),
+                DataType::Decimal { scale, .. } => DataValue::Decimal(Decimal::new(0, scale)),
                 DataType::Boolean => DataValue::Boolean(false),
-                DataType::Text => DataValue::Text("".to_string()),
+                DataType::Text | DataType::Varchar(_) => DataValue::Text("".into()),
                 DataType::Date => DataValue::Date("1970-01-01".to_string()),
                 DataType::Timestamp => DataValue::Timestamp("1970-01-01 00:00:00".to_string()),
-                DataType::TSVector => DataValue::Text("".to_string()),
-                DataType::Null => DataValue::Null,
+                DataType::TSVector => DataValue::Text("".into()),
+                DataType::Uuid | DataType::Json | DataType::Null => DataValue::Null,
+                DataType::Array(_) => DataValue::Array(vec![]),
+                DataType::Enum(ref labels) => labels.first().map_or(DataValue::Null, |label| DataValue::Text(label.as_str().into())),
             };
             for row in data.iter_mut() {
                 row.push(default_value.clone());
@@ -255,6 +378,7 @@ impl Storage for TableStorage {
     }
 
     fn drop_table(&mut self, table_name: &str) {
+        self.row_counts.remove(table_name);
         self.tables.remove(table_name);
     }
 
@@ -264,6 +388,7 @@ impl Storage for TableStorage {
 
     fn clear(&mut self) {
         self.tables.clear();
+        self.row_counts.clear();
     }
 
     fn get_all_tables(&self) -> &HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
@@ -274,3 +399,38 @@ impl Storage for TableStorage {
 pub use disk::OnDiskStorage;
 pub use memory::InMemoryStorage;
 pub use mmap::MmapStorage;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acid::AcidManager;
+    use crate::transaction::IsolationLevel;
+
+    #[test]
+    fn test_row_count_tracks_inserts_deletes_and_rollback() {
+        let mut tables = TableStorage::new();
+        let columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+        tables.insert_table("users".to_string(), columns, vec![]);
+        assert_eq!(tables.row_count("users"), Some(0));
+        assert_eq!(tables.row_count("missing"), None);
+
+        for id in 1..=3 {
+            tables.push_value("users", vec![DataValue::Integer(id)]).unwrap();
+        }
+        assert_eq!(tables.delete_table("users", Some(("id".to_string(), DataValue::Integer(2)))), 1);
+        assert_eq!(tables.row_count("users"), Some(2));
+
+        // Changes made after the snapshot are undone by the rollback, counts included
+        let mut acid = AcidManager::new(tables.clone(), IsolationLevel::Serializable);
+        acid.begin_atomic(&tables);
+        tables.push_value("users", vec![DataValue::Integer(4)]).unwrap();
+        tables.delete_table("users", None);
+        assert_eq!(tables.row_count("users"), Some(0));
+
+        tables.restore_from(&acid.rollback_atomic());
+        assert_eq!(tables.row_count("users"), Some(2));
+
+        tables.drop_table("users");
+        assert_eq!(tables.row_count("users"), None);
+    }
+}
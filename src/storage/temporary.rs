@@ -0,0 +1,298 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+use super::memory::InMemoryStorage;
+use super::{Storage, TableStorage};
+use crate::error::ReefDBError;
+use crate::indexes::index_manager::{IndexManager, IndexType, IndexUpdate};
+use crate::sql::column_def::ColumnDef;
+use crate::sql::data_value::DataValue;
+
+/// A storage backend with an in-memory layer on top for temporary tables. A temporary
+/// table and its indexes only ever live in that layer, so they are never persisted or
+/// shared through the backend. While it exists it hides any backend table of the same
+/// name, which stays in the backend untouched until the temporary table is dropped.
+#[derive(Clone)]
+pub struct TemporaryOverlay<S> {
+    base: S,
+    temporary: InMemoryStorage,
+    names: HashSet<String>,
+}
+
+impl<S> TemporaryOverlay<S> {
+    pub fn over(base: S) -> Self {
+        TemporaryOverlay {
+            base,
+            temporary: InMemoryStorage::new(),
+            names: HashSet::new(),
+        }
+    }
+
+    /// The backend, without the temporary tables
+    pub fn base(&self) -> &S {
+        &self.base
+    }
+
+    /// Makes `table_name` temporary: from now on the name refers to a table of the
+    /// in-memory layer, created with `insert_table`, until it is dropped
+    pub fn add_temporary(&mut self, table_name: &str) {
+        self.names.insert(table_name.to_string());
+    }
+
+    pub fn is_temporary(&self, table_name: &str) -> bool {
+        self.names.contains(table_name)
+    }
+
+    /// Replaces the temporary tables with those of `state`, leaving the backend alone
+    pub fn restore_temporary_from(&mut self, state: &TableStorage) {
+        self.temporary.clear();
+        self.names.clear();
+        for table_name in state.temporary_names() {
+            if let Some((columns, rows)) = state.tables.get(&table_name) {
+                self.temporary.insert_table(table_name.clone(), columns.clone(), rows.clone());
+            }
+            self.names.insert(table_name);
+        }
+    }
+}
+
+impl<S: Storage> Storage for TemporaryOverlay<S> {
+    type NewArgs = S::NewArgs;
+
+    fn new(args: S::NewArgs) -> Self {
+        Self::over(S::new(args))
+    }
+
+    fn insert_table(&mut self, table_name: String, columns: Vec<ColumnDef>, rows: Vec<Vec<DataValue>>) {
+        if self.is_temporary(&table_name) {
+            self.temporary.insert_table(table_name, columns, rows)
+        } else {
+            self.base.insert_table(table_name, columns, rows)
+        }
+    }
+
+    fn get_table(&mut self, table_name: &str) -> Option<&mut (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
+        if self.is_temporary(table_name) {
+            self.temporary.get_table(table_name)
+        } else {
+            self.base.get_table(table_name)
+        }
+    }
+
+    fn get_table_ref(&self, table_name: &str) -> Option<&(Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
+        if self.is_temporary(table_name) {
+            self.temporary.get_table_ref(table_name)
+        } else {
+            self.base.get_table_ref(table_name)
+        }
+    }
+
+    fn push_value(&mut self, table_name: &str, row: Vec<DataValue>) -> Result<usize, ReefDBError> {
+        if self.is_temporary(table_name) {
+            self.temporary.push_value(table_name, row)
+        } else {
+            self.base.push_value(table_name, row)
+        }
+    }
+
+    fn reserve(&mut self, table_name: &str, additional: usize) {
+        if self.is_temporary(table_name) {
+            self.temporary.reserve(table_name, additional)
+        } else {
+            self.base.reserve(table_name, additional)
+        }
+    }
+
+    fn append_rows(&mut self, table_name: &str, rows: Vec<Vec<DataValue>>) -> Result<Vec<usize>, ReefDBError> {
+        if self.is_temporary(table_name) {
+            self.temporary.append_rows(table_name, rows)
+        } else {
+            self.base.append_rows(table_name, rows)
+        }
+    }
+
+    fn update_table(
+        &mut self,
+        table_name: &str,
+        updates: Vec<(String, DataValue)>,
+        where_clause: Option<(String, DataValue)>,
+    ) -> usize {
+        if self.is_temporary(table_name) {
+            self.temporary.update_table(table_name, updates, where_clause)
+        } else {
+            self.base.update_table(table_name, updates, where_clause)
+        }
+    }
+
+    fn delete_table(&mut self, table_name: &str, where_clause: Option<(String, DataValue)>) -> usize {
+        if self.is_temporary(table_name) {
+            self.temporary.delete_table(table_name, where_clause)
+        } else {
+            self.base.delete_table(table_name, where_clause)
+        }
+    }
+
+    fn table_exists(&self, table_name: &str) -> bool {
+        if self.is_temporary(table_name) {
+            self.temporary.table_exists(table_name)
+        } else {
+            self.base.table_exists(table_name)
+        }
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        let mut names = self.base.table_names();
+        names.extend(self.temporary.table_names());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn remove_table(&mut self, table_name: &str) -> bool {
+        if self.names.remove(table_name) {
+            self.temporary.remove_table(table_name)
+        } else {
+            self.base.remove_table(table_name)
+        }
+    }
+
+    fn add_column(&mut self, table_name: &str, column_def: ColumnDef) -> Result<(), ReefDBError> {
+        if self.is_temporary(table_name) {
+            self.temporary.add_column(table_name, column_def)
+        } else {
+            self.base.add_column(table_name, column_def)
+        }
+    }
+
+    fn drop_column(&mut self, table_name: &str, column_name: &str) -> Result<(), ReefDBError> {
+        if self.is_temporary(table_name) {
+            self.temporary.drop_column(table_name, column_name)
+        } else {
+            self.base.drop_column(table_name, column_name)
+        }
+    }
+
+    fn rename_column(&mut self, table_name: &str, old_name: &str, new_name: &str) -> Result<(), ReefDBError> {
+        if self.is_temporary(table_name) {
+            self.temporary.rename_column(table_name, old_name, new_name)
+        } else {
+            self.base.rename_column(table_name, old_name, new_name)
+        }
+    }
+
+    fn drop_table(&mut self, table_name: &str) {
+        if self.names.remove(table_name) {
+            self.temporary.drop_table(table_name)
+        } else {
+            self.base.drop_table(table_name)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clear(&mut self) {
+        self.base.clear();
+        self.temporary.clear();
+        self.names.clear();
+    }
+
+    // The backend's tables; temporary tables are only reachable by name
+    fn get_all_tables(&self) -> &HashMap<String, (Vec<ColumnDef>, Vec<Vec<DataValue>>)> {
+        self.base.get_all_tables()
+    }
+
+    fn schema_version(&self) -> u32 {
+        self.base.schema_version()
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.base.set_schema_version(version)
+    }
+
+    fn flush(&mut self) -> Result<(), ReefDBError> {
+        self.base.flush()
+    }
+
+    // Temporary tables of the state go back to the in-memory layer, the tables they hide to the backend
+    fn restore_from(&mut self, state: &TableStorage) {
+        self.base.restore_from(&state.without_temporary());
+        self.restore_temporary_from(state);
+    }
+}
+
+impl<S: IndexManager> IndexManager for TemporaryOverlay<S> {
+    fn create_index(&mut self, table: &str, column: &str, index_type: IndexType) -> Result<(), ReefDBError> {
+        if self.is_temporary(table) {
+            self.temporary.create_index(table, column, index_type)
+        } else {
+            self.base.create_index(table, column, index_type)
+        }
+    }
+
+    fn drop_index(&mut self, table: &str, column: &str) {
+        if self.is_temporary(table) {
+            self.temporary.drop_index(table, column)
+        } else {
+            self.base.drop_index(table, column)
+        }
+    }
+
+    fn get_index(&self, table: &str, column: &str) -> Result<&IndexType, ReefDBError> {
+        if self.is_temporary(table) {
+            self.temporary.get_index(table, column)
+        } else {
+            self.base.get_index(table, column)
+        }
+    }
+
+    fn update_index(&mut self, table: &str, column: &str, old_value: Vec<u8>, new_value: Vec<u8>, row_id: usize) -> Result<(), ReefDBError> {
+        if self.is_temporary(table) {
+            self.temporary.update_index(table, column, old_value, new_value, row_id)
+        } else {
+            self.base.update_index(table, column, old_value, new_value, row_id)
+        }
+    }
+
+    fn track_index_update(&mut self, update: IndexUpdate) -> Result<(), ReefDBError> {
+        if self.is_temporary(&update.table_name) {
+            self.temporary.track_index_update(update)
+        } else {
+            self.base.track_index_update(update)
+        }
+    }
+
+    fn commit_index_transaction(&mut self, transaction_id: u64) -> Result<(), ReefDBError> {
+        self.temporary.commit_index_transaction(transaction_id)?;
+        self.base.commit_index_transaction(transaction_id)
+    }
+
+    fn rollback_index_transaction(&mut self, transaction_id: u64) -> Result<(), ReefDBError> {
+        self.temporary.rollback_index_transaction(transaction_id)?;
+        self.base.rollback_index_transaction(transaction_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::data_type::DataType;
+
+    #[test]
+    fn test_temporary_table_hides_base_table_until_dropped() {
+        let mut storage = TemporaryOverlay::over(InMemoryStorage::new());
+        let columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+        storage.insert_table("users".to_string(), columns.clone(), vec![vec![DataValue::Integer(1)]]);
+
+        storage.add_temporary("users");
+        storage.insert_table("users".to_string(), columns, vec![]);
+        storage.push_value("users", vec![DataValue::Integer(2)]).unwrap();
+        assert_eq!(storage.get_table_ref("users").unwrap().1, vec![vec![DataValue::Integer(2)]]);
+        assert_eq!(storage.base().get_table_ref("users").unwrap().1, vec![vec![DataValue::Integer(1)]]);
+
+        storage.drop_table("users");
+        assert!(!storage.is_temporary("users"));
+        assert_eq!(storage.get_table_ref("users").unwrap().1, vec![vec![DataValue::Integer(1)]]);
+    }
+}
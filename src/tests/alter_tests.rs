@@ -18,13 +18,14 @@ fn test_add_column() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
     // Insert some data
     let stmt = Statement::Insert(InsertStatement::IntoTable(
         "users".to_string(),
-        vec![DataValue::Integer(1), DataValue::Text("John".to_string())],
+        vec![DataValue::Integer(1), DataValue::Text("John".into())],
     ));
     db.execute_statement(stmt).unwrap();
 
@@ -66,6 +67,7 @@ fn test_drop_column() {
             ColumnDef::new("name", DataType::Text, vec![]),
             ColumnDef::new("age", DataType::Integer, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
@@ -74,7 +76,7 @@ fn test_drop_column() {
         "users".to_string(),
         vec![
             DataValue::Integer(1),
-            DataValue::Text("John".to_string()),
+            DataValue::Text("John".into()),
             DataValue::Integer(25),
         ],
     ));
@@ -102,7 +104,7 @@ fn test_drop_column() {
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].len(), 2); // Only id and name columns should remain
         assert_eq!(rows[0][0], DataValue::Integer(1));
-        assert_eq!(rows[0][1], DataValue::Text("John".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("John".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -119,13 +121,14 @@ fn test_rename_column() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
     // Insert some data
     let stmt = Statement::Insert(InsertStatement::IntoTable(
         "users".to_string(),
-        vec![DataValue::Integer(1), DataValue::Text("John".to_string())],
+        vec![DataValue::Integer(1), DataValue::Text("John".into())],
     ));
     db.execute_statement(stmt).unwrap();
 
@@ -149,7 +152,7 @@ fn test_rename_column() {
     ));
     if let ReefDBResult::Select(rows) = db.execute_statement(stmt).unwrap() {
         assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0][0], DataValue::Text("John".to_string()));
+        assert_eq!(rows[0][0], DataValue::Text("John".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -166,6 +169,7 @@ fn test_alter_errors() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
@@ -196,4 +200,25 @@ fn test_alter_errors() {
         alter_type: AlterType::AddColumn(ColumnDef::new("test", DataType::Text, vec![])),
     });
     assert!(db.execute_statement(stmt).is_err());
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_alter_bumps_schema_version() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY)")?;
+    assert_eq!(db.schema_version(), 0);
+
+    db.query("ALTER TABLE users ADD COLUMN name TEXT")?;
+    db.query("ALTER TABLE users RENAME COLUMN name TO fullname")?;
+    assert_eq!(db.schema_version(), 2);
+
+    // A failed ALTER leaves the version alone
+    assert!(db.query("ALTER TABLE users DROP COLUMN missing").is_err());
+    assert_eq!(db.schema_version(), 2);
+
+    assert_eq!(db.query("SET SCHEMA VERSION 10")?, ReefDBResult::SetSchemaVersion);
+    assert_eq!(db.schema_version(), 10);
+    assert!(db.check_schema_version(10).is_ok());
+    assert_eq!(db.check_schema_version(9), Err(ReefDBError::SchemaVersionMismatch(10, 9)));
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use super::*;
+use crate::error::ReefDBError;
+use crate::result::ReefDBResult;
+use crate::storage::{disk::OnDiskStorage, memory::InMemoryStorage, Storage};
+use crate::InMemoryReefDB;
+use tempfile::tempdir;
+
+fn create_table(db: &mut InMemoryReefDB, name: &str) -> Result<(), ReefDBError> {
+    db.execute_statement(Statement::Create(CreateStatement::Table(
+        name.to_string(),
+        vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+            ColumnDef::new("name", DataType::Text, vec![]),
+        ],
+        false,
+    )))?;
+    Ok(())
+}
+
+fn shown_tables(result: ReefDBResult) -> Vec<DataValue> {
+    match result {
+        ReefDBResult::Select(query_result) => {
+            assert_eq!(query_result.columns.len(), 1);
+            assert_eq!(query_result.columns[0].name, "table_name");
+            query_result.rows.into_iter().map(|(_, row)| row[0].clone()).collect()
+        }
+        _ => panic!("Expected Select result"),
+    }
+}
+
+#[test]
+fn test_parse_show_tables() {
+    let (remaining, stmt) = Statement::parse("SHOW TABLES").unwrap();
+    assert_eq!(remaining, "");
+    assert_eq!(stmt, Statement::ShowTables);
+
+    let (_, stmt) = Statement::parse("show   tables").unwrap();
+    assert_eq!(stmt, Statement::ShowTables);
+
+    let (_, stmt) = Statement::parse("SHOW LOCKS").unwrap();
+    assert_eq!(stmt, Statement::ShowLocks);
+}
+
+#[test]
+fn test_show_tables_after_creates_and_drops() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    assert!(shown_tables(db.query("SHOW TABLES")?).is_empty());
+
+    create_table(&mut db, "users")?;
+    create_table(&mut db, "orders")?;
+    create_table(&mut db, "products")?;
+    assert_eq!(
+        shown_tables(db.query("SHOW TABLES")?),
+        vec![
+            DataValue::Text("orders".into()),
+            DataValue::Text("products".into()),
+            DataValue::Text("users".into()),
+        ]
+    );
+
+    db.execute_statement(Statement::Drop(DropStatement {
+        table_name: "orders".to_string(),
+        if_exists: false,
+    }))?;
+    assert_eq!(
+        shown_tables(db.execute_statement(Statement::ShowTables)?),
+        vec![
+            DataValue::Text("products".into()),
+            DataValue::Text("users".into()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_storage_table_names() {
+    let columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+
+    let mut memory = InMemoryStorage::new();
+    memory.insert_table("b".to_string(), columns.clone(), vec![]);
+    memory.insert_table("a".to_string(), columns.clone(), vec![]);
+    assert_eq!(memory.table_names(), vec!["a".to_string(), "b".to_string()]);
+    memory.drop_table("a");
+    assert_eq!(memory.table_names(), vec!["b".to_string()]);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("catalog.db").to_str().unwrap().to_string();
+    let mut disk = OnDiskStorage::new(path).unwrap();
+    disk.insert_table("y".to_string(), columns.clone(), vec![]);
+    disk.insert_table("x".to_string(), columns, vec![]);
+    assert_eq!(disk.table_names(), vec!["x".to_string(), "y".to_string()]);
+    disk.drop_table("y");
+    assert_eq!(disk.table_names(), vec!["x".to_string()]);
+}
+
+#[test]
+fn test_parse_describe() {
+    let (_, stmt) = Statement::parse("DESCRIBE users").unwrap();
+    assert_eq!(stmt, Statement::Describe("users".to_string()));
+
+    let (_, stmt) = Statement::parse("SHOW COLUMNS FROM user_accounts").unwrap();
+    assert_eq!(stmt, Statement::Describe("user_accounts".to_string()));
+}
+
+#[test]
+fn test_describe_table_with_mixed_constraints() -> Result<(), ReefDBError> {
+    use crate::sql::constraints::foreignkey::ForeignKeyConstraint;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    create_table(&mut db, "users")?;
+    db.execute_statement(Statement::Create(CreateStatement::Table(
+        "orders".to_string(),
+        vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey, Constraint::NotNull]),
+            ColumnDef::new("code", DataType::Text, vec![Constraint::Unique]),
+            ColumnDef::new("user_id", DataType::Integer, vec![Constraint::ForeignKey(ForeignKeyConstraint {
+                table_name: "users".to_string(),
+                column_name: "id".to_string(),
+            })]),
+            ColumnDef::new("shipped", DataType::Boolean, vec![]),
+        ],
+        false,
+    )))?;
+
+    let text = |s: &str| DataValue::Text(s.into());
+    let expected = vec![
+        vec![text("id"), text("INTEGER"), text("PRIMARY KEY, NOT NULL")],
+        vec![text("code"), text("TEXT"), text("UNIQUE")],
+        vec![text("user_id"), text("INTEGER"), text("FOREIGN KEY (id) REFERENCES users")],
+        vec![text("shipped"), text("BOOLEAN"), text("")],
+    ];
+
+    for sql in ["DESCRIBE orders", "SHOW COLUMNS FROM orders"] {
+        match db.query(sql)? {
+            ReefDBResult::Select(query_result) => {
+                let names: Vec<_> = query_result.columns.iter().map(|c| c.name.as_str()).collect();
+                assert_eq!(names, vec!["column_name", "data_type", "constraints"]);
+                let rows: Vec<_> = query_result.rows.into_iter().map(|(_, row)| row).collect();
+                assert_eq!(rows, expected);
+            }
+            _ => panic!("Expected Select result"),
+        }
+    }
+
+    assert_eq!(
+        db.query("DESCRIBE missing"),
+        Err(ReefDBError::TableNotFound("missing".to_string()))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_table_rejects_reserved_names() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    assert_eq!(
+        db.query("CREATE TABLE reef_tables (id INTEGER PRIMARY KEY)"),
+        Err(ReefDBError::ReservedName("reef_tables".to_string()))
+    );
+    assert!(matches!(db.query("CREATE TABLE REEF_locks (id INTEGER)"), Err(ReefDBError::ReservedName(_))));
+    assert!(shown_tables(db.query("SHOW TABLES")?).is_empty());
+
+    // Only the configured prefix is reserved
+    db.query("CREATE TABLE reefs (id INTEGER)")?;
+    db.set_reserved_prefix("sys_");
+    db.query("CREATE TABLE reef_tables (id INTEGER)")?;
+    assert!(matches!(db.query("CREATE TABLE sys_columns (id INTEGER)"), Err(ReefDBError::ReservedName(_))));
+
+    // Transactions enforce the same prefix
+    db.query("BEGIN TRANSACTION")?;
+    assert!(matches!(db.query("CREATE TABLE sys_columns (id INTEGER)"), Err(ReefDBError::ReservedName(_))));
+    db.query("CREATE TABLE reef_locks (id INTEGER)")?;
+    db.query("COMMIT")?;
+    Ok(())
+}
@@ -29,7 +29,7 @@ fn test_create_statement() -> Result<(), ReefDBError> {
         ColumnDef::new("name", DataType::Text, vec![]),
         ColumnDef::new("active", DataType::Integer, vec![]),  // Used as boolean
     ];
-    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns)))?;
+    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns, false)))?;
     assert_eq!(result, ReefDBResult::CreateTable);
 
     // Test 2: Verify table exists and has correct schema
@@ -59,7 +59,7 @@ fn test_create_statement() -> Result<(), ReefDBError> {
             })
         ]),
     ];
-    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("employees".to_string(), columns)))?;
+    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("employees".to_string(), columns, false)))?;
     assert_eq!(result, ReefDBResult::CreateTable);
 
     // Test 4: Create table with full-text search column
@@ -68,7 +68,7 @@ fn test_create_statement() -> Result<(), ReefDBError> {
         ColumnDef::new("title", DataType::Text, vec![]),
         ColumnDef::new("content", DataType::TSVector, vec![]),  // Full-text search column
     ];
-    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("articles".to_string(), columns)))?;
+    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("articles".to_string(), columns, false)))?;
     assert_eq!(result, ReefDBResult::CreateTable);
 
     // Test 5: Attempt to create table that already exists (should fail)
@@ -76,19 +76,19 @@ fn test_create_statement() -> Result<(), ReefDBError> {
         ColumnDef::new("id", DataType::Integer, vec![]),
         ColumnDef::new("name", DataType::Text, vec![]),
     ];
-    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns)));
+    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns, false)));
     assert!(matches!(result, Err(ReefDBError::Other(_))));
 
     // Test 6: Create table with empty column list (should fail)
-    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("empty".to_string(), vec![])));
+    let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("empty".to_string(), vec![], false)));
     assert!(matches!(result, Err(ReefDBError::Other(_))));
 
     // Test 7: Insert data to verify constraints
     // Test PRIMARY KEY constraint
     let values = vec![
         DataValue::Integer(1),
-        DataValue::Text("john_doe".to_string()),
-        DataValue::Text("john@example.com".to_string()),
+        DataValue::Text("john_doe".into()),
+        DataValue::Text("john@example.com".into()),
         DataValue::Integer(30),
         DataValue::Integer(1),
     ];
@@ -102,8 +102,8 @@ fn test_create_statement() -> Result<(), ReefDBError> {
     // Test NOT NULL constraint (should fail)
     let values = vec![
         DataValue::Integer(2),
-        DataValue::Text("".to_string()),  // Empty string for NOT NULL column
-        DataValue::Text("jane@example.com".to_string()),
+        DataValue::Text("".into()),  // Empty string for NOT NULL column
+        DataValue::Text("jane@example.com".into()),
         DataValue::Integer(25),
         DataValue::Integer(1),
     ];
@@ -113,8 +113,8 @@ fn test_create_statement() -> Result<(), ReefDBError> {
     // Test 8: Verify FTS functionality
     let values = vec![
         DataValue::Integer(1),
-        DataValue::Text("Rust Programming".to_string()),
-        DataValue::Text("Learn Rust programming language basics".to_string()),
+        DataValue::Text("Rust Programming".into()),
+        DataValue::Text("Learn Rust programming language basics".into()),
     ];
     let result = db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Insert(InsertStatement::IntoTable("articles".to_string(), values)))?;
     assert_eq!(result, ReefDBResult::Insert(1));
@@ -150,4 +150,31 @@ fn test_create_statement() -> Result<(), ReefDBError> {
     db.transaction_manager.as_mut().unwrap().commit_transaction(transaction_id)?;
 
     Ok(())
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_create_table_if_not_exists() -> Result<(), ReefDBError> {
+    use crate::storage::Storage;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'Alice')")?;
+
+    // Without the modifier recreating the table still fails
+    assert!(db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").is_err());
+
+    // With it the statement is a no-op that keeps the existing schema and rows
+    assert_eq!(
+        db.query("CREATE TABLE IF NOT EXISTS users (other TEXT)")?,
+        ReefDBResult::CreateTable
+    );
+    let (schema, rows) = db.storage.get_table_ref("users").unwrap();
+    assert_eq!(schema.len(), 2);
+    assert_eq!(rows.len(), 1);
+
+    // A missing table is created as usual
+    db.query("CREATE TABLE IF NOT EXISTS orders (id INTEGER)")?;
+    assert!(db.storage.table_exists("orders"));
+
+    Ok(())
+}
@@ -19,7 +19,7 @@ fn test_data_types() {
 
     // Insert a test row
     let row = vec![
-        DataValue::Text("Hello".to_string()),
+        DataValue::Text("Hello".into()),
         DataValue::Integer(42),
         DataValue::Boolean(true),
         DataValue::Float(3.14),
@@ -31,7 +31,7 @@ fn test_delete_basic() -> Result<()> {
     if let ReefDBResult::Select(rows) = db.query("SELECT * FROM users")? {
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0][0], DataValue::Integer(2));
-        assert_eq!(rows[0][1], DataValue::Text("Bob".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("Bob".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -53,7 +53,7 @@ fn test_delete_multiple_rows() -> Result<()> {
     let where_clause = WhereType::Regular(WhereClause::new(
         "status".to_string(),
         Op::Equal,
-        DataValue::Text("inactive".to_string()),
+        DataValue::Text("inactive".into()),
         None,
     ));
 
@@ -63,8 +63,8 @@ fn test_delete_multiple_rows() -> Result<()> {
     if let ReefDBResult::Select(rows) = db.query("SELECT * FROM users")? {
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0][0], DataValue::Integer(3));
-        assert_eq!(rows[0][1], DataValue::Text("Charlie".to_string()));
-        assert_eq!(rows[0][2], DataValue::Text("active".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("Charlie".into()));
+        assert_eq!(rows[0][2], DataValue::Text("active".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -99,7 +99,7 @@ fn parse_delete_with_where_text_test() {
     let where_clause = WhereType::Regular(WhereClause::new(
         "status".to_string(),
         Op::Equal,
-        DataValue::Text("inactive".to_string()),
+        DataValue::Text("inactive".into()),
         None,
     ));
     assert_eq!(
@@ -18,19 +18,21 @@ fn test_drop_table() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
     // Insert some data
     let stmt = Statement::Insert(InsertStatement::IntoTable(
         "users".to_string(),
-        vec![DataValue::Integer(1), DataValue::Text("John".to_string())],
+        vec![DataValue::Integer(1), DataValue::Text("John".into())],
     ));
     db.execute_statement(stmt).unwrap();
 
     // Drop the table
     let stmt = Statement::Drop(DropStatement {
         table_name: "users".to_string(),
+        if_exists: false,
     });
     db.execute_statement(stmt).unwrap();
 
@@ -55,6 +57,7 @@ fn test_drop_nonexistent_table() {
     // Try to drop a non-existent table
     let stmt = Statement::Drop(DropStatement {
         table_name: "nonexistent".to_string(),
+        if_exists: false,
     });
     assert!(db.execute_statement(stmt).is_err());
 }
@@ -70,26 +73,28 @@ fn test_operations_after_drop() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     db.execute_statement(stmt).unwrap();
 
     // Drop the table
     let stmt = Statement::Drop(DropStatement {
         table_name: "users".to_string(),
+        if_exists: false,
     });
     db.execute_statement(stmt).unwrap();
 
     // Try to insert into dropped table
     let stmt = Statement::Insert(InsertStatement::IntoTable(
         "users".to_string(),
-        vec![DataValue::Integer(1), DataValue::Text("John".to_string())],
+        vec![DataValue::Integer(1), DataValue::Text("John".into())],
     ));
     assert!(db.execute_statement(stmt).is_err());
 
     // Try to update dropped table
     let stmt = Statement::Update(UpdateStatement::UpdateTable(
         "users".to_string(),
-        vec![("name".to_string(), DataValue::Text("Jane".to_string()))],
+        vec![("name".to_string(), DataValue::Text("Jane".into()))],
         None,
     ));
     assert!(db.execute_statement(stmt).is_err());
@@ -108,6 +113,63 @@ fn test_operations_after_drop() {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ],
+        false,
     ));
     assert!(db.execute_statement(stmt).is_ok());
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_drop_table_if_exists() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+
+    // Without the modifier dropping a missing table still fails
+    assert!(db.query("DROP TABLE missing").is_err());
+
+    // With it the statement is a no-op
+    assert_eq!(db.query("DROP TABLE IF EXISTS missing")?, ReefDBResult::DropTable);
+
+    // An existing table is still dropped
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")?;
+    assert_eq!(db.query("DROP TABLE IF EXISTS users")?, ReefDBResult::DropTable);
+    assert!(db.query("DROP TABLE users").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_drop_table_cascades_to_indexes_mvcc_and_fts() -> Result<(), ReefDBError> {
+    use crate::indexes::index_manager::IndexManager;
+    use crate::key_format::KeyFormat;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE docs (id INTEGER PRIMARY KEY, body TSVECTOR)")?;
+    db.query("CREATE INDEX ON docs(id)")?;
+    db.query("INSERT INTO docs VALUES (1, 'stale archived text')")?;
+
+    // Record a committed row version as an update would
+    let key = KeyFormat::row("docs", 0, "1");
+    {
+        let mut mvcc = db.mvcc_manager.lock().unwrap();
+        mvcc.begin_transaction(42);
+        mvcc.write(42, key.clone(), vec![DataValue::Integer(1), DataValue::Text("stale archived text".into())])?;
+        mvcc.commit(42)?;
+    }
+    assert!(db.storage.get_index("docs", "id").is_ok());
+    assert!(!db.inverted_index.search("docs", "body", "archived").is_empty());
+
+    db.query("DROP TABLE docs")?;
+
+    assert!(db.storage.get_index("docs", "id").is_err());
+    assert_eq!(db.mvcc_manager.lock().unwrap().read_committed(42, &key)?, None);
+    assert!(db.inverted_index.search("docs", "body", "archived").is_empty());
+
+    // Recreating the table under the same name starts from a clean slate
+    db.query("CREATE TABLE docs (id INTEGER PRIMARY KEY, body TSVECTOR)")?;
+    db.query("INSERT INTO docs VALUES (1, 'fresh content')")?;
+
+    assert!(db.storage.get_index("docs", "id").is_err());
+    assert_eq!(db.mvcc_manager.lock().unwrap().read_committed(42, &key)?, None);
+    assert!(db.inverted_index.search("docs", "body", "archived").is_empty());
+    assert!(!db.inverted_index.search("docs", "body", "fresh").is_empty());
+
+    Ok(())
+}
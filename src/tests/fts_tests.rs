@@ -43,7 +43,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Rust Programming", "Web Development", "Rust Web"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
     } else {
@@ -69,7 +69,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Web Development", "Rust Web"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
     } else {
@@ -95,7 +95,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Web Development", "Rust Web"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
     } else {
@@ -121,7 +121,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Web Development", "Database Design", "Rust Web"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
     } else {
@@ -147,7 +147,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Rust Programming"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
     } else {
@@ -176,7 +176,7 @@ fn test_full_text_search_e2e() -> Result<(), ReefDBError> {
         let expected_titles = vec!["Rust Programming", "Web Development", "Rust Web"];
         for title in expected_titles {
             assert!(results.rows.iter().any(|(_, row)| {
-                row[1] == DataValue::Text(title.to_string())
+                row[1] == DataValue::Text(title.into())
             }), "Missing article: {}", title);
         }
         
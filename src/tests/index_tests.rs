@@ -14,6 +14,7 @@ use crate::{
         constraints::constraint::Constraint,
     },
 };
+use super::select_ids;
 
 #[test]
 fn test_index_operations() -> Result<(), ReefDBError> {
@@ -25,7 +26,7 @@ fn test_index_operations() -> Result<(), ReefDBError> {
         ColumnDef::new("name", DataType::Text, vec![]),
         ColumnDef::new("age", DataType::Integer, vec![]),
     ];
-    let result = db.execute_statement(Statement::Create(CreateStatement::Table("users".to_string(), columns)))?;
+    let result = db.execute_statement(Statement::Create(CreateStatement::Table("users".to_string(), columns, false)))?;
     assert_eq!(result, ReefDBResult::CreateTable);
 
     // Test 2: Create an index on the age column
@@ -33,6 +34,8 @@ fn test_index_operations() -> Result<(), ReefDBError> {
         table_name: "users".to_string(),
         column_name: "age".to_string(),
         index_type: IndexType::BTree,
+        expression: None,
+        unique: false,
     };
     let result = db.execute_statement(Statement::CreateIndex(create_index_stmt))?;
     assert_eq!(result, ReefDBResult::CreateIndex);
@@ -40,16 +43,144 @@ fn test_index_operations() -> Result<(), ReefDBError> {
     // Test 3: Insert some data
     let values1 = vec![
         DataValue::Integer(1),
-        DataValue::Text("Alice".to_string()),
+        DataValue::Text("Alice".into()),
         DataValue::Integer(25),
     ];
     let values2 = vec![
         DataValue::Integer(2),
-        DataValue::Text("Bob".to_string()),
+        DataValue::Text("Bob".into()),
         DataValue::Integer(30),
     ];
     db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values1)))?;
     db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values2)))?;
 
     Ok(())
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_index_covers_existing_and_new_rows() -> Result<(), ReefDBError> {
+    use crate::indexes::{btree::BTreeIndex, index_manager::IndexManager, IndexType as StorageIndexType};
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER)")?;
+    db.query("INSERT INTO users VALUES (1, 25)")?;
+    db.query("CREATE INDEX ON users(age)")?;
+    db.query("INSERT INTO users VALUES (2, 25)")?;
+
+    match db.storage.get_index("users", "age")? {
+        StorageIndexType::BTree(btree) => {
+            let row_ids = btree.search(BTreeIndex::key_for(&DataValue::Integer(25))).unwrap();
+            assert_eq!(row_ids.len(), 2);
+        }
+        StorageIndexType::GIN(_) => panic!("Expected a B-Tree index"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_expression_index_serves_matching_predicate() -> Result<(), ReefDBError> {
+    use crate::sql::clauses::wheres::where_type::parse_where_clause;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'Alice@Example.com')")?;
+    db.query("INSERT INTO users VALUES (2, 'bob@example.com')")?;
+    db.query("CREATE INDEX idx_email ON users (LOWER(email))")?;
+    db.query("INSERT INTO users VALUES (3, 'CAROL@example.com')")?;
+
+    let (_, predicate) = parse_where_clause("WHERE LOWER(email) = 'carol@example.com'").unwrap();
+    assert_eq!(db.indexed_rows("users", &predicate), Some(vec![2]));
+    assert_eq!(select_ids(db.query("SELECT id FROM users WHERE LOWER(email) = 'alice@example.com'")?), [DataValue::Integer(1)]);
+    assert_eq!(select_ids(db.query("SELECT id FROM users WHERE LOWER(email) = 'carol@example.com'")?), [DataValue::Integer(3)]);
+
+    // The index follows updated values
+    db.query("UPDATE users SET email = 'Dave@Example.com' WHERE id = 2")?;
+    assert_eq!(select_ids(db.query("SELECT id FROM users WHERE LOWER(email) = 'bob@example.com'")?), Vec::<DataValue>::new());
+    assert_eq!(select_ids(db.query("SELECT id FROM users WHERE LOWER(email) = 'dave@example.com'")?), [DataValue::Integer(2)]);
+
+    // Without the index the predicate falls back to a scan with the same answer
+    db.query("DROP INDEX ON users (LOWER(email))")?;
+    assert_eq!(db.indexed_rows("users", &predicate), None);
+    assert_eq!(select_ids(db.query("SELECT id FROM users WHERE LOWER(email) = 'dave@example.com'")?), [DataValue::Integer(2)]);
+    Ok(())
+}
+
+#[test]
+fn test_unique_index_rejects_existing_duplicates() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'a@example.com')")?;
+    db.query("INSERT INTO users VALUES (2, 'a@example.com')")?;
+
+    assert!(matches!(
+        db.query("CREATE UNIQUE INDEX idx_email ON users (email)"),
+        Err(ReefDBError::DuplicateKey(_))
+    ));
+    // No index was left behind, so duplicates may still be added
+    db.query("INSERT INTO users VALUES (3, 'a@example.com')")?;
+    Ok(())
+}
+
+#[test]
+fn test_unique_index_enforced_until_dropped() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'a@example.com')")?;
+    db.query("INSERT INTO users VALUES (2, 'b@example.com')")?;
+    db.query("INSERT INTO users VALUES (3, NULL)")?;
+    db.query("CREATE UNIQUE INDEX idx_email ON users (email)")?;
+
+    assert!(matches!(
+        db.query("INSERT INTO users VALUES (4, 'a@example.com')"),
+        Err(ReefDBError::DuplicateKey(_))
+    ));
+    assert!(matches!(
+        db.query("UPDATE users SET email = 'a@example.com' WHERE id = 2"),
+        Err(ReefDBError::DuplicateKey(_))
+    ));
+    // The failed update left the row as it was
+    match db.query("SELECT email FROM users WHERE id = 2")? {
+        ReefDBResult::Select(result) => assert_eq!(result.rows[0].1, vec![DataValue::Text("b@example.com".into())]),
+        other => panic!("Expected a select result, got {:?}", other),
+    }
+    db.query("INSERT INTO users VALUES (4, NULL)")?;
+    db.query("INSERT INTO users VALUES (5, 'c@example.com')")?;
+
+    // An update moves only the rewritten row's entry: its old key is free, its new one taken
+    db.query("UPDATE users SET email = 'd@example.com' WHERE id = 2")?;
+    db.query("INSERT INTO users VALUES (7, 'b@example.com')")?;
+    assert!(matches!(
+        db.query("INSERT INTO users VALUES (8, 'd@example.com')"),
+        Err(ReefDBError::DuplicateKey(_))
+    ));
+
+    db.query("DROP INDEX ON users (email)")?;
+    db.query("INSERT INTO users VALUES (6, 'a@example.com')")?;
+    Ok(())
+}
+
+#[test]
+fn test_text_index_serves_prefix_like() -> Result<(), ReefDBError> {
+    use crate::sql::clauses::wheres::where_type::parse_where_clause;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE books (id INTEGER PRIMARY KEY, name TEXT)")?;
+    for (id, name) in [(1, "Bookkeeping"), (2, "A Book"), (3, "Boot Camp"), (4, "Books of Ages"), (5, "Ebooks")] {
+        db.query(&format!("INSERT INTO books VALUES ({}, '{}')", id, name))?;
+    }
+    let prefix = "SELECT id FROM books WHERE name LIKE 'Book%'";
+    let suffix = "SELECT id FROM books WHERE name LIKE '%ook%'";
+    let scanned = (select_ids(db.query(prefix)?), select_ids(db.query(suffix)?));
+    assert_eq!(scanned.0, [1, 4].map(DataValue::Integer));
+
+    db.query("CREATE TEXT INDEX ON books (name)")?;
+    db.query("INSERT INTO books VALUES (6, 'Bookends')")?;
+    let (_, predicate) = parse_where_clause("WHERE name LIKE 'Book%'").unwrap();
+    assert_eq!(db.indexed_rows("books", &predicate), Some(vec![0, 3, 5]));
+    let (_, predicate) = parse_where_clause("WHERE name LIKE '%ook%'").unwrap();
+    assert_eq!(db.indexed_rows("books", &predicate), None);
+
+    db.query("DELETE FROM books WHERE id = 6")?;
+    assert_eq!(select_ids(db.query(prefix)?), scanned.0);
+    assert_eq!(select_ids(db.query(suffix)?), scanned.1);
+    Ok(())
+}
@@ -4,6 +4,7 @@ use crate::result::ReefDBResult;
 use crate::sql::table_reference::TableReference;
 use crate::sql::column::ColumnType;
 use crate::InMemoryReefDB;
+use super::{select_ids, select_rows};
 
 #[test]
 fn test_insert_statement() -> Result<(), ReefDBError> {
@@ -16,14 +17,14 @@ fn test_insert_statement() -> Result<(), ReefDBError> {
         ColumnDef::new("age", DataType::Integer, vec![]),
         ColumnDef::new("email", DataType::Text, vec![Constraint::Unique]),
     ];
-    db.execute_statement(Statement::Create(CreateStatement::Table("users".to_string(), columns)))?;
+    db.execute_statement(Statement::Create(CreateStatement::Table("users".to_string(), columns, false)))?;
 
     // Test 2: Basic insert with all columns
     let values = vec![
         DataValue::Integer(1),
-        DataValue::Text("Alice".to_string()),
+        DataValue::Text("Alice".into()),
         DataValue::Integer(25),
-        DataValue::Text("alice@example.com".to_string()),
+        DataValue::Text("alice@example.com".into()),
     ];
     let result = db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values)))?;
     assert_eq!(result, ReefDBResult::Insert(1)); // Should return rowid 1
@@ -44,9 +45,9 @@ fn test_insert_statement() -> Result<(), ReefDBError> {
         assert_eq!(rows.len(), 1);
         let values = &rows[0];
         assert_eq!(values[0], DataValue::Integer(1));
-        assert_eq!(values[1], DataValue::Text("Alice".to_string()));
+        assert_eq!(values[1], DataValue::Text("Alice".into()));
         assert_eq!(values[2], DataValue::Integer(25));
-        assert_eq!(values[3], DataValue::Text("alice@example.com".to_string()));
+        assert_eq!(values[3], DataValue::Text("alice@example.com".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -54,18 +55,18 @@ fn test_insert_statement() -> Result<(), ReefDBError> {
     // Test 4: Insert with wrong number of values (should fail)
     let values = vec![
         DataValue::Integer(2),
-        DataValue::Text("Bob".to_string()),
+        DataValue::Text("Bob".into()),
         DataValue::Integer(30),
     ];
     let result = db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values)));
-    assert!(matches!(result, Err(ReefDBError::Other(_))));
+    assert_eq!(result, Err(ReefDBError::ColumnCountMismatch { expected: 4, found: 3 }));
 
     // Test 5: Insert with type mismatch (should fail)
     let values = vec![
-        DataValue::Text("not an integer".to_string()),  // Wrong type for id
-        DataValue::Text("Charlie".to_string()),
+        DataValue::Text("not an integer".into()),  // Wrong type for id
+        DataValue::Text("Charlie".into()),
         DataValue::Integer(35),
-        DataValue::Text("charlie@example.com".to_string()),
+        DataValue::Text("charlie@example.com".into()),
     ];
     let result = db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values)));
     assert!(matches!(result, Err(ReefDBError::Other(_))));
@@ -78,15 +79,15 @@ fn test_insert_statement() -> Result<(), ReefDBError> {
     // Test 7: Multiple successful inserts
     let values2 = vec![
         DataValue::Integer(2),
-        DataValue::Text("Bob".to_string()),
+        DataValue::Text("Bob".into()),
         DataValue::Integer(30),
-        DataValue::Text("bob@example.com".to_string()),
+        DataValue::Text("bob@example.com".into()),
     ];
     let values3 = vec![
         DataValue::Integer(3),
-        DataValue::Text("Charlie".to_string()),
+        DataValue::Text("Charlie".into()),
         DataValue::Integer(35),
-        DataValue::Text("charlie@example.com".to_string()),
+        DataValue::Text("charlie@example.com".into()),
     ];
 
     let result = db.execute_statement(Statement::Insert(InsertStatement::IntoTable("users".to_string(), values2)))?;
@@ -112,24 +113,356 @@ fn test_insert_statement() -> Result<(), ReefDBError> {
         
         // First row (Alice)
         assert_eq!(values[0][0], DataValue::Integer(1));
-        assert_eq!(values[0][1], DataValue::Text("Alice".to_string()));
+        assert_eq!(values[0][1], DataValue::Text("Alice".into()));
         assert_eq!(values[0][2], DataValue::Integer(25));
-        assert_eq!(values[0][3], DataValue::Text("alice@example.com".to_string()));
+        assert_eq!(values[0][3], DataValue::Text("alice@example.com".into()));
         
         // Second row (Bob)
         assert_eq!(values[1][0], DataValue::Integer(2));
-        assert_eq!(values[1][1], DataValue::Text("Bob".to_string()));
+        assert_eq!(values[1][1], DataValue::Text("Bob".into()));
         assert_eq!(values[1][2], DataValue::Integer(30));
-        assert_eq!(values[1][3], DataValue::Text("bob@example.com".to_string()));
+        assert_eq!(values[1][3], DataValue::Text("bob@example.com".into()));
         
         // Third row (Charlie)
         assert_eq!(values[2][0], DataValue::Integer(3));
-        assert_eq!(values[2][1], DataValue::Text("Charlie".to_string()));
+        assert_eq!(values[2][1], DataValue::Text("Charlie".into()));
         assert_eq!(values[2][2], DataValue::Integer(35));
-        assert_eq!(values[2][3], DataValue::Text("charlie@example.com".to_string()));
+        assert_eq!(values[2][3], DataValue::Text("charlie@example.com".into()));
     } else {
         panic!("Expected Select result");
     }
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_generated_column_computed_on_write() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, price INTEGER, quantity INTEGER, total INTEGER AS (price * quantity))")?;
+
+    // Inserts supply every column except the generated one
+    assert_eq!(db.query("INSERT INTO orders VALUES (1, 3, 4)")?, ReefDBResult::Insert(1));
+    assert_eq!(db.query("INSERT INTO orders VALUES (2, 5, 2)")?, ReefDBResult::Insert(2));
+    assert_eq!(
+        db.query("INSERT INTO orders VALUES (3, 1, 1, 99)"),
+        Err(ReefDBError::GeneratedColumnWrite("total".to_string()))
+    );
+
+    let totals = |db: &mut InMemoryReefDB| select_rows(db.query("SELECT id, total FROM orders ORDER BY id").unwrap());
+    assert_eq!(totals(&mut db), vec![
+        vec![DataValue::Integer(1), DataValue::Integer(12)],
+        vec![DataValue::Integer(2), DataValue::Integer(10)],
+    ]);
+
+    // Updating an input recomputes the total; the total itself can't be written
+    db.query("UPDATE orders SET quantity = 10 WHERE id = 1")?;
+    assert_eq!(totals(&mut db), vec![
+        vec![DataValue::Integer(1), DataValue::Integer(30)],
+        vec![DataValue::Integer(2), DataValue::Integer(10)],
+    ]);
+    assert_eq!(
+        db.query("UPDATE orders SET total = 0 WHERE id = 1"),
+        Err(ReefDBError::GeneratedColumnWrite("total".to_string()))
+    );
+
+    // Expressions may only read other stored columns of the same row
+    assert_eq!(
+        db.query("CREATE TABLE bad (a INTEGER, b INTEGER AS (missing + 1))"),
+        Err(ReefDBError::ColumnNotFound("missing".to_string()))
+    );
+    assert!(db.query("CREATE TABLE bad (a INTEGER, b INTEGER AS (a + 1), c INTEGER AS (b + 1))").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_foreign_keys_with_self_reference_and_cycles() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+
+    // Self-reference: the root row may point at itself, later rows at any existing row
+    db.query("CREATE TABLE employees (id INTEGER PRIMARY KEY, manager_id INTEGER NOT NULL FOREIGN KEY (id) REFERENCES employees)")?;
+    assert_eq!(db.query("INSERT INTO employees VALUES (1, 1)")?, ReefDBResult::Insert(1));
+    assert_eq!(db.query("INSERT INTO employees VALUES (2, 1)")?, ReefDBResult::Insert(2));
+    assert!(matches!(
+        db.query("INSERT INTO employees VALUES (3, 7)"),
+        Err(ReefDBError::ForeignKeyViolation(_))
+    ));
+
+    // Two-table cycle with a nullable link: insert with NULL first, then the other side
+    db.query("CREATE TABLE teams (id INTEGER PRIMARY KEY, lead_id INTEGER FOREIGN KEY (id) REFERENCES members)")?;
+    db.query("CREATE TABLE members (id INTEGER PRIMARY KEY, team_id INTEGER NOT NULL FOREIGN KEY (id) REFERENCES teams)")?;
+    assert!(matches!(
+        db.query("INSERT INTO members VALUES (1, 1)"),
+        Err(ReefDBError::ForeignKeyViolation(_))
+    ));
+    db.query("INSERT INTO teams VALUES (1, NULL)")?;
+    db.query("INSERT INTO members VALUES (1, 1)")?;
+    db.query("INSERT INTO teams VALUES (2, 1)")?;
+    assert!(matches!(
+        db.query("INSERT INTO teams VALUES (3, 5)"),
+        Err(ReefDBError::ForeignKeyViolation(_))
+    ));
+
+    // Both links NOT NULL: no row could ever go in first
+    db.query("CREATE TABLE a (id INTEGER PRIMARY KEY, b_id INTEGER NOT NULL FOREIGN KEY (id) REFERENCES b)")?;
+    assert_eq!(
+        db.query("CREATE TABLE b (id INTEGER PRIMARY KEY, a_id INTEGER NOT NULL FOREIGN KEY (id) REFERENCES a)"),
+        Err(ReefDBError::ForeignKeyCycle("b -> a -> b".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_referenced_rows_cannot_be_deleted_or_rekeyed() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE teams (id INTEGER PRIMARY KEY, name TEXT)")?;
+    db.query("CREATE TABLE members (id INTEGER PRIMARY KEY, team INTEGER FOREIGN KEY (id) REFERENCES teams)")?;
+    db.query("CREATE INDEX idx_team ON teams (id)")?;
+    db.query("INSERT INTO teams VALUES (1, 'red')")?;
+    db.query("INSERT INTO teams VALUES (2, 'blue')")?;
+    db.query("INSERT INTO members VALUES (10, 1)")?;
+
+    assert!(matches!(db.query("DELETE FROM teams WHERE id = 1"), Err(ReefDBError::ForeignKeyViolation(_))));
+    assert!(matches!(
+        db.query("UPDATE teams SET id = 3 WHERE name = 'red'"),
+        Err(ReefDBError::ForeignKeyViolation(_))
+    ));
+    // Both statements left the parent as it was, and unreferenced rows may still go
+    match db.query("SELECT id FROM teams WHERE name = 'red'")? {
+        ReefDBResult::Select(result) => assert_eq!(result.rows[0].1, vec![DataValue::Integer(1)]),
+        other => panic!("Expected a select result, got {:?}", other),
+    }
+    assert_eq!(db.query("DELETE FROM teams WHERE id = 2")?, ReefDBResult::Delete(1));
+
+    // Once nothing points at the row it can be removed
+    db.query("UPDATE members SET team = NULL WHERE id = 10")?;
+    assert_eq!(db.query("DELETE FROM teams WHERE id = 1")?, ReefDBResult::Delete(1));
+    assert!(matches!(db.query("INSERT INTO members VALUES (11, 1)"), Err(ReefDBError::ForeignKeyViolation(_))));
+    Ok(())
+}
+
+#[test]
+fn test_varchar_length_limit() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE codes (id INTEGER PRIMARY KEY, code VARCHAR(5), note TEXT)")?;
+
+    db.query("INSERT INTO codes VALUES (1, 'abc', 'under the limit')")?;
+    db.query("INSERT INTO codes VALUES (2, 'abcde', 'at the limit')")?;
+    // Five characters but ten bytes
+    db.query("INSERT INTO codes VALUES (3, 'ééééé', 'multibyte')")?;
+    assert_eq!(
+        db.query("INSERT INTO codes VALUES (4, 'abcdef', 'over the limit')"),
+        Err(ReefDBError::ValueTooLong("code".to_string(), 5))
+    );
+
+    assert_eq!(
+        db.query("UPDATE codes SET code = 'toolong' WHERE id = 1"),
+        Err(ReefDBError::ValueTooLong("code".to_string(), 5))
+    );
+    db.query("UPDATE codes SET code = 'xyz' WHERE id = 1")?;
+
+    // TEXT stays unbounded
+    let long_note = "n".repeat(1000);
+    db.query(&format!("INSERT INTO codes VALUES (5, 'ok', '{}')", long_note))?;
+
+    match db.query("SELECT * FROM codes")? {
+        ReefDBResult::Select(rows) => assert_eq!(rows.len(), 4),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_uuid_default_and_comparisons() -> Result<(), ReefDBError> {
+    use crate::sql::{data_value::DataValue, uuid::Uuid};
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE sessions (id UUID PRIMARY KEY DEFAULT gen_uuid(), owner TEXT)")?;
+
+    // Leaving out the defaulted column generates an id
+    db.query("INSERT INTO sessions VALUES ('alice')")?;
+    db.query("INSERT INTO sessions VALUES ('bob')")?;
+    db.query("INSERT INTO sessions VALUES ('550e8400-e29b-41d4-a716-446655440000', 'carol')")?;
+
+    // Rows come back in key order, so pick the ids out by owner
+    let id_of = |owner: &str, rows: &[Vec<DataValue>]| rows.iter()
+        .find(|row| row[1] == DataValue::Text(owner.into()))
+        .map(|row| match row[0] {
+            DataValue::Uuid(uuid) => uuid,
+            ref other => panic!("Expected a UUID, got {:?}", other),
+        })
+        .unwrap();
+    let all = select_rows(db.query("SELECT * FROM sessions")?);
+    let ids: Vec<Uuid> = ["alice", "bob", "carol"].iter().map(|owner| id_of(owner, &all)).collect();
+    assert_eq!(all.len(), 3);
+    assert_ne!(ids[0], ids[1]);
+    assert_eq!(ids[0].version(), 4);
+    assert_eq!(ids[2].to_string(), "550e8400-e29b-41d4-a716-446655440000");
+
+    assert_eq!(
+        select_rows(db.query("SELECT owner FROM sessions WHERE id = '550e8400-e29b-41d4-a716-446655440000'")?),
+        vec![vec![DataValue::Text("carol".into())]]
+    );
+    let generated = format!("SELECT owner FROM sessions WHERE id = '{}'", ids[1]);
+    assert_eq!(select_rows(db.query(&generated)?), vec![vec![DataValue::Text("bob".into())]]);
+
+    // Ordering follows the bytes
+    let expected_below = ids.iter().filter(|id| id.as_bytes()[0] < 0x55).count();
+    assert_eq!(
+        select_rows(db.query("SELECT id FROM sessions WHERE id < '55000000-0000-0000-0000-000000000000'")?).len(),
+        expected_below
+    );
+    Ok(())
+}
+
+#[test]
+fn test_uuid_shaped_text_stays_text() -> Result<(), ReefDBError> {
+    use crate::sql::data_value::DataValue;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE tokens (id INTEGER PRIMARY KEY, token TEXT)")?;
+    db.query("INSERT INTO tokens VALUES (1, '550e8400-e29b-41d4-a716-446655440000')")?;
+    db.query("INSERT INTO tokens VALUES (2, 'not-a-uuid')")?;
+
+    let token = DataValue::Text("550e8400-e29b-41d4-a716-446655440000".into());
+    match db.query("SELECT id, token FROM tokens WHERE token = '550e8400-e29b-41d4-a716-446655440000'")? {
+        ReefDBResult::Select(result) => assert_eq!(result.rows, vec![(0, vec![DataValue::Integer(1), token])]),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+
+    // Only a UUID column turns the text into a UUID, and rejects text that isn't one
+    db.query("CREATE TABLE sessions (id UUID PRIMARY KEY, owner TEXT)")?;
+    assert!(db.query("INSERT INTO sessions VALUES ('not-a-uuid', 'alice')").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_insert_value_count_must_match_columns() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")?;
+
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (1, 'alice')"),
+        Err(ReefDBError::ColumnCountMismatch { expected: 3, found: 2 })
+    );
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (1, 'alice', 30, 'extra')"),
+        Err(ReefDBError::ColumnCountMismatch { expected: 3, found: 4 })
+    );
+    assert_eq!(
+        db.copy_into("users", vec![vec![DataValue::Integer(1)]]),
+        Err(ReefDBError::ColumnCountMismatch { expected: 3, found: 1 })
+    );
+
+    // Nothing reached storage, so later statements see a consistent table
+    db.query("INSERT INTO users VALUES (1, 'alice', 30)")?;
+    assert_eq!(db.query("UPDATE users SET age = 31 WHERE id = 1")?, ReefDBResult::Update(1));
+    match db.query("SELECT * FROM users")? {
+        ReefDBResult::Select(result) => assert_eq!(result.rows, vec![(0, vec![
+            DataValue::Integer(1),
+            DataValue::Text("alice".into()),
+            DataValue::Integer(31),
+        ])]),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_insert_on_conflict() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, visits INTEGER)")?;
+    let users = |db: &mut InMemoryReefDB| select_rows(db.query("SELECT * FROM users").unwrap());
+    let user = |id: i64, email: &str, visits: i64| vec![
+        DataValue::Integer(id),
+        DataValue::Text(email.into()),
+        DataValue::Integer(visits),
+    ];
+
+    // No clash: a plain insert
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (1, 'a@x.io', 1) ON CONFLICT (id) DO UPDATE SET visits = 2")?,
+        ReefDBResult::Insert(1)
+    );
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (2, 'b@x.io', 1) ON CONFLICT DO NOTHING")?,
+        ReefDBResult::Insert(2)
+    );
+
+    // DO NOTHING leaves the existing row alone, whichever unique column clashes
+    assert_eq!(db.query("INSERT INTO users VALUES (1, 'c@x.io', 9) ON CONFLICT DO NOTHING")?, ReefDBResult::Update(0));
+    assert_eq!(db.query("INSERT INTO users VALUES (3, 'b@x.io', 9) ON CONFLICT DO NOTHING")?, ReefDBResult::Update(0));
+    assert_eq!(users(&mut db), vec![user(1, "a@x.io", 1), user(2, "b@x.io", 1)]);
+
+    // DO UPDATE rewrites the row the insert clashed with
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (1, 'a@x.io', 1) ON CONFLICT (id) DO UPDATE SET visits = 2")?,
+        ReefDBResult::Update(1)
+    );
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (7, 'b@x.io', 1) ON CONFLICT (email) DO UPDATE SET visits = 5")?,
+        ReefDBResult::Update(1)
+    );
+    assert_eq!(users(&mut db), vec![user(1, "a@x.io", 2), user(2, "b@x.io", 5)]);
+
+    // Only PRIMARY KEY and UNIQUE columns can be conflict targets
+    assert!(db.query("INSERT INTO users VALUES (1, 'a@x.io', 1) ON CONFLICT (visits) DO NOTHING").is_err());
+    assert_eq!(
+        db.query("INSERT INTO users VALUES (1, 'a@x.io', 1) ON CONFLICT (missing) DO NOTHING"),
+        Err(ReefDBError::ColumnNotFound("missing".to_string()))
+    );
+
+    // A clash on a column outside the target is still an error
+    assert!(db.query("INSERT INTO users VALUES (3, 'a@x.io', 1) ON CONFLICT (id) DO NOTHING").is_err());
+    assert_eq!(users(&mut db).len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_now_and_sequence_defaults_evaluated_per_insert() -> Result<(), ReefDBError> {
+    use crate::clock::MockClock;
+    use crate::sql::data_value::DataValue;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    db.set_clock(Arc::new(clock.clone()));
+    db.query("CREATE TABLE events (id INTEGER PRIMARY KEY DEFAULT nextval('event_ids'), name TEXT, created TIMESTAMP DEFAULT now())")?;
+
+    db.query("INSERT INTO events VALUES ('start')")?;
+    clock.advance(Duration::from_secs(90));
+    db.query("INSERT INTO events VALUES ('stop')")?;
+
+    match db.query("SELECT * FROM events")? {
+        ReefDBResult::Select(result) => {
+            let rows: Vec<Vec<DataValue>> = result.rows.into_iter().map(|(_, row)| row).collect();
+            assert_eq!(rows, vec![
+                vec![DataValue::Integer(1), DataValue::Text("start".into()), DataValue::Timestamp("2023-11-14 22:13:20".to_string())],
+                vec![DataValue::Integer(2), DataValue::Text("stop".into()), DataValue::Timestamp("2023-11-14 22:14:50".to_string())],
+            ]);
+        }
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sequences_continue_after_reopening() -> Result<(), ReefDBError> {
+    use crate::sql::data_value::DataValue;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("sequences.db").to_str().unwrap().to_string();
+    let ids = |db: &mut crate::OnDiskReefDB| db.query("SELECT id FROM events").map(select_ids);
+    {
+        let mut db = crate::OnDiskReefDB::create_on_disk(path.clone(), String::new())?;
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY DEFAULT nextval('event_ids'), name TEXT)")?;
+        db.query("INSERT INTO events VALUES ('start')")?;
+        db.query("INSERT INTO events VALUES ('stop')")?;
+    }
+
+    let mut db = crate::OnDiskReefDB::create_on_disk(path, String::new())?;
+    db.query("INSERT INTO events VALUES ('restart')")?;
+    assert_eq!(ids(&mut db)?, (1..=3).map(DataValue::Integer).collect::<Vec<_>>());
+    assert_eq!(db.next_sequence_value("event_ids"), 4);
+    Ok(())
+}
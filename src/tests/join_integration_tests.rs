@@ -11,6 +11,7 @@ mod tests {
         },
         transaction::IsolationLevel,
         InMemoryReefDB,
+        tests::select_rows,
     };
 
     type Result<T> = std::result::Result<T, ReefDBError>;
@@ -77,8 +78,8 @@ mod tests {
             
             // Verify all combinations exist
             let expected_combinations = vec![
-                (DataValue::Text("Alice".to_string()), DataValue::Text("Book 1".to_string())),
-                (DataValue::Text("Bob".to_string()), DataValue::Text("Book 2".to_string())),
+                (DataValue::Text("Alice".into()), DataValue::Text("Book 1".into())),
+                (DataValue::Text("Bob".into()), DataValue::Text("Book 2".into())),
             ];
 
             for (name, title) in expected_combinations {
@@ -165,8 +166,8 @@ mod tests {
 
             for (name, title, year) in expected_combinations {
                 assert!(results.rows.iter().any(|(_, row)| {
-                    row[0] == DataValue::Text(name.to_string()) &&
-                    row[1] == DataValue::Text(title.to_string()) &&
+                    row[0] == DataValue::Text(name.into()) &&
+                    row[1] == DataValue::Text(title.into()) &&
                     row[2] == DataValue::Integer(year)
                 }), "Missing combination: {} - {} - {}", name, title, year);
             }
@@ -252,8 +253,8 @@ mod tests {
 
             for (name, title) in expected_combinations {
                 assert!(results.rows.iter().any(|(_, row)| {
-                    row[0] == DataValue::Text(name.to_string()) &&
-                    row[1] == DataValue::Text(title.to_string())
+                    row[0] == DataValue::Text(name.into()) &&
+                    row[1] == DataValue::Text(title.into())
                 }), "Missing combination: {} - {}", name, title);
             }
         } else {
@@ -342,8 +343,8 @@ mod tests {
 
             for (name, title) in expected_combinations {
                 assert!(results.rows.iter().any(|(_, row)| {
-                    row[0] == DataValue::Text(name.to_string()) &&
-                    row[1] == DataValue::Text(title.to_string())
+                    row[0] == DataValue::Text(name.into()) &&
+                    row[1] == DataValue::Text(title.into())
                 }), "Missing combination: {} - {}", name, title);
             }
         } else {
@@ -380,8 +381,8 @@ mod tests {
 
             for (name, title) in expected_combinations {
                 assert!(results.rows.iter().any(|(_, row)| {
-                    row[0] == DataValue::Text(name.to_string()) &&
-                    row[1] == DataValue::Text(title.to_string())
+                    row[0] == DataValue::Text(name.into()) &&
+                    row[1] == DataValue::Text(title.into())
                 }), "Missing combination: {} - {}", name, title);
             }
         } else {
@@ -391,4 +392,115 @@ mod tests {
         cleanup_test_files(kv_path, index_path);
         Ok(())
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_null_join_keys_never_match() -> Result<()> {
+        let mut db = InMemoryReefDB::create_in_memory()?;
+        db.query("CREATE TABLE employees (id INTEGER PRIMARY KEY, name TEXT, dept_code TEXT)")?;
+        db.query("CREATE TABLE departments (id INTEGER PRIMARY KEY, title TEXT, code TEXT)")?;
+        db.query("INSERT INTO employees VALUES (1, 'Alice', 'ENG')")?;
+        db.query("INSERT INTO employees VALUES (2, 'Bob', NULL)")?;
+        db.query("INSERT INTO departments VALUES (1, 'Engineering', 'ENG')")?;
+        db.query("INSERT INTO departments VALUES (2, 'Unassigned', NULL)")?;
+
+        let text = |s: &str| DataValue::Text(s.into());
+
+        // NULL = NULL is not a match
+        let inner = select_rows(db.query(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code = departments.code"
+        )?);
+        assert_eq!(inner, vec![vec![text("Alice"), text("Engineering")]]);
+
+        // ...but outer joins still keep the row, padded with NULLs
+        let left = select_rows(db.query(
+            "SELECT employees.name, departments.title FROM employees LEFT JOIN departments ON employees.dept_code = departments.code"
+        )?);
+        assert_eq!(left, vec![
+            vec![text("Alice"), text("Engineering")],
+            vec![text("Bob"), DataValue::Null],
+        ]);
+        let right = select_rows(db.query(
+            "SELECT employees.name, departments.title FROM employees RIGHT JOIN departments ON employees.dept_code = departments.code"
+        )?);
+        assert_eq!(right, vec![
+            vec![text("Alice"), text("Engineering")],
+            vec![DataValue::Null, text("Unassigned")],
+        ]);
+
+        // The transaction manager's hash join skips NULL keys as well
+        let tx = db.transaction_manager.as_mut().unwrap().begin_transaction(IsolationLevel::ReadCommitted)?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        for stmt in [
+            "CREATE TABLE employees (id INTEGER PRIMARY KEY, name TEXT, dept_code TEXT)",
+            "CREATE TABLE departments (id INTEGER PRIMARY KEY, title TEXT, code TEXT)",
+            "INSERT INTO employees VALUES (1, 'Alice', 'ENG')",
+            "INSERT INTO employees VALUES (2, 'Bob', NULL)",
+            "INSERT INTO departments VALUES (1, 'Engineering', 'ENG')",
+            "INSERT INTO departments VALUES (2, 'Unassigned', NULL)",
+        ] {
+            tm.execute_statement(tx, Statement::parse(stmt).unwrap().1)?;
+        }
+        let inner = select_rows(tm.execute_statement(tx, Statement::parse(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code = departments.code"
+        ).unwrap().1)?);
+        assert_eq!(inner, vec![vec![text("Alice"), text("Engineering")]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_safe_equality_matches_null_keys() -> Result<()> {
+        let setup = [
+            "CREATE TABLE employees (id INTEGER PRIMARY KEY, name TEXT, dept_code TEXT)",
+            "CREATE TABLE departments (id INTEGER PRIMARY KEY, title TEXT, code TEXT)",
+            "INSERT INTO employees VALUES (1, 'Alice', 'ENG')",
+            "INSERT INTO employees VALUES (2, 'Bob', NULL)",
+            "INSERT INTO departments VALUES (1, 'Engineering', 'ENG')",
+            "INSERT INTO departments VALUES (2, 'Unassigned', NULL)",
+        ];
+        let mut db = InMemoryReefDB::create_in_memory()?;
+        for stmt in setup {
+            db.query(stmt)?;
+        }
+
+        let text = |s: &str| DataValue::Text(s.into());
+        let both = vec![
+            vec![text("Alice"), text("Engineering")],
+            vec![text("Bob"), text("Unassigned")],
+        ];
+
+        // `=` never matches NULL, `<=>` matches NULL against NULL
+        assert!(select_rows(db.query("SELECT name FROM employees WHERE dept_code = NULL")?).is_empty());
+        assert_eq!(select_rows(db.query("SELECT name FROM employees WHERE dept_code <=> NULL")?), vec![vec![text("Bob")]]);
+        assert_eq!(
+            select_rows(db.query("SELECT name FROM employees WHERE dept_code IS NOT DISTINCT FROM 'ENG'")?),
+            vec![vec![text("Alice")]],
+        );
+        let inner = select_rows(db.query(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code = departments.code"
+        )?);
+        assert_eq!(inner, vec![vec![text("Alice"), text("Engineering")]]);
+        let null_safe = select_rows(db.query(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code <=> departments.code"
+        )?);
+        assert_eq!(null_safe, both);
+
+        // The transaction manager falls back to a nested loop, since its hash join drops NULL keys
+        let tx = db.transaction_manager.as_mut().unwrap().begin_transaction(IsolationLevel::ReadCommitted)?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        for stmt in setup {
+            tm.execute_statement(tx, Statement::parse(stmt).unwrap().1)?;
+        }
+        let mut query = |sql: &str| -> Result<Vec<Vec<DataValue>>> {
+            Ok(select_rows(tm.execute_statement(tx, Statement::parse(sql).unwrap().1)?))
+        };
+        assert!(query("SELECT name FROM employees WHERE dept_code = NULL")?.is_empty());
+        assert_eq!(query("SELECT name FROM employees WHERE dept_code <=> NULL")?, vec![vec![text("Bob")]]);
+        assert_eq!(query(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code = departments.code"
+        )?, vec![vec![text("Alice"), text("Engineering")]]);
+        assert_eq!(query(
+            "SELECT employees.name, departments.title FROM employees INNER JOIN departments ON employees.dept_code IS NOT DISTINCT FROM departments.code"
+        )?, both);
+        Ok(())
+    }
+}
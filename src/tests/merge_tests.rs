@@ -0,0 +1,79 @@
+use crate::{InMemoryReefDB, error::ReefDBError, result::ReefDBResult};
+use crate::sql::data_value::DataValue;
+use super::select_rows;
+
+type Result<T> = std::result::Result<T, ReefDBError>;
+
+fn rows(db: &mut InMemoryReefDB, sql: &str) -> Vec<Vec<DataValue>> {
+    select_rows(db.query(sql).unwrap())
+}
+
+fn account(id: i64, owner: &str, balance: i64) -> Vec<DataValue> {
+    vec![DataValue::Integer(id), DataValue::Text(owner.into()), DataValue::Integer(balance)]
+}
+
+#[test]
+fn test_merge_updates_matched_and_inserts_unmatched() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT, balance INTEGER)")?;
+    db.query("CREATE TABLE feed (id INTEGER PRIMARY KEY, owner TEXT, balance INTEGER)")?;
+    db.query("INSERT INTO accounts VALUES (1, 'alice', 100)")?;
+    db.query("INSERT INTO accounts VALUES (2, 'bob', 200)")?;
+    db.query("INSERT INTO feed VALUES (2, 'bob', 250)")?;
+    db.query("INSERT INTO feed VALUES (3, 'carol', 300)")?;
+
+    let merge = "MERGE INTO accounts AS a USING feed AS f ON a.id = f.id \
+                 WHEN MATCHED THEN UPDATE SET balance = f.balance \
+                 WHEN NOT MATCHED THEN INSERT VALUES (f.id, f.owner, f.balance)";
+    assert_eq!(db.query(merge)?, ReefDBResult::Merge { updated: 1, inserted: 1 });
+    assert_eq!(rows(&mut db, "SELECT * FROM accounts"), vec![
+        account(1, "alice", 100),
+        account(2, "bob", 250),
+        account(3, "carol", 300),
+    ]);
+
+    // Merging the same feed again has nothing left to insert
+    assert_eq!(db.query(merge)?, ReefDBResult::Merge { updated: 2, inserted: 0 });
+    assert_eq!(rows(&mut db, "SELECT * FROM accounts").len(), 3);
+
+    // Unqualified names that only one table has, and a single action
+    db.query("INSERT INTO feed VALUES (4, 'dave', 400)")?;
+    assert_eq!(
+        db.query("MERGE INTO accounts USING feed ON accounts.id = feed.id WHEN NOT MATCHED THEN INSERT VALUES (feed.id, feed.owner, 0)")?,
+        ReefDBResult::Merge { updated: 0, inserted: 1 }
+    );
+    assert_eq!(rows(&mut db, "SELECT * FROM accounts WHERE id = 4"), vec![account(4, "dave", 0)]);
+    Ok(())
+}
+
+#[test]
+fn test_merge_failure_leaves_target_unchanged() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT, balance INTEGER)")?;
+    db.query("CREATE TABLE feed (account_id INTEGER, owner TEXT, balance INTEGER)")?;
+    db.query("INSERT INTO accounts VALUES (1, 'alice', 100)")?;
+    db.query("INSERT INTO feed VALUES (1, 'alice', 150)")?;
+    db.query("INSERT INTO feed VALUES (2, 'bob', 200)")?;
+
+    // The second source row can't be inserted, so the first row's update is not applied
+    let merge = "MERGE INTO accounts USING feed ON accounts.id = feed.account_id \
+                 WHEN MATCHED THEN UPDATE SET balance = feed.balance \
+                 WHEN NOT MATCHED THEN INSERT VALUES (feed.account_id, feed.balance, feed.owner)";
+    assert!(db.query(merge).is_err());
+    assert_eq!(rows(&mut db, "SELECT * FROM accounts"), vec![account(1, "alice", 100)]);
+
+    // A target row may be updated by one source row only
+    db.query("INSERT INTO feed VALUES (1, 'alice', 175)")?;
+    assert!(db.query("MERGE INTO accounts USING feed ON accounts.id = feed.account_id \
+                      WHEN MATCHED THEN UPDATE SET balance = feed.balance").is_err());
+    assert_eq!(rows(&mut db, "SELECT * FROM accounts"), vec![account(1, "alice", 100)]);
+
+    // The ON condition has to compare the two tables
+    assert!(db.query("MERGE INTO accounts USING feed ON accounts.id = accounts.balance \
+                      WHEN MATCHED THEN UPDATE SET balance = 0").is_err());
+    assert_eq!(
+        db.query("MERGE INTO accounts USING feed ON accounts.id = feed.missing WHEN MATCHED THEN UPDATE SET balance = 0"),
+        Err(ReefDBError::ColumnNotFound("feed.missing".to_string()))
+    );
+    Ok(())
+}
@@ -32,12 +32,12 @@ fn test_mmap_basic_operations() {
         let rows = vec![
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("John".to_string()),
+                DataValue::Text("John".into()),
                 DataValue::Integer(20),
             ],
             vec![
                 DataValue::Integer(2),
-                DataValue::Text("Jane".to_string()),
+                DataValue::Text("Jane".into()),
                 DataValue::Integer(25),
             ],
         ];
@@ -62,7 +62,7 @@ fn test_mmap_basic_operations() {
         
         // Verify data
         assert_eq!(rows[0][0], DataValue::Integer(1));
-        assert_eq!(rows[0][1], DataValue::Text("John".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("John".into()));
         assert_eq!(rows[0][2], DataValue::Integer(20));
     }
 
@@ -73,7 +73,7 @@ fn test_mmap_basic_operations() {
         // Test push_value
         let new_row = vec![
             DataValue::Integer(3),
-            DataValue::Text("Bob".to_string()),
+            DataValue::Text("Bob".into()),
             DataValue::Integer(30),
         ];
         let result = storage.push_value("users", new_row);
@@ -84,7 +84,7 @@ fn test_mmap_basic_operations() {
         let updates = vec![
             ("age".to_string(), DataValue::Integer(21)),
         ];
-        let where_clause = Some(("name".to_string(), DataValue::Text("John".to_string())));
+        let where_clause = Some(("name".to_string(), DataValue::Text("John".into())));
         println!("Before update - Getting table data:");
         let (_, rows_before) = storage.get_table("users").unwrap();
         for (i, row) in rows_before.iter().enumerate() {
@@ -109,11 +109,11 @@ fn test_mmap_basic_operations() {
         let (_, rows) = storage.get_table("users").unwrap();
         
         // Verify the update worked
-        let john_row = rows.iter().find(|row| row[1] == DataValue::Text("John".to_string())).unwrap();
+        let john_row = rows.iter().find(|row| row[1] == DataValue::Text("John".into())).unwrap();
         assert_eq!(john_row[2], DataValue::Integer(21));
         
         // Verify the new row was added
-        let bob_row = rows.iter().find(|row| row[1] == DataValue::Text("Bob".to_string())).unwrap();
+        let bob_row = rows.iter().find(|row| row[1] == DataValue::Text("Bob".into())).unwrap();
         assert_eq!(bob_row[2], DataValue::Integer(30));
     }
 
@@ -157,14 +157,14 @@ fn test_mmap_basic_operations() {
         let mut storage = MmapStorage::new(file_path.clone());
         
         // Test delete with where clause
-        let where_clause = Some(("name".to_string(), DataValue::Text("Bob".to_string())));
+        let where_clause = Some(("name".to_string(), DataValue::Text("Bob".into())));
         let deleted = storage.delete_table("users", where_clause);
         assert_eq!(deleted, 1);
         
         // Verify deletion
         let (_, rows) = storage.get_table("users").unwrap();
         assert_eq!(rows.len(), 2);
-        assert!(rows.iter().all(|row| row[1] != DataValue::Text("Bob".to_string())));
+        assert!(rows.iter().all(|row| row[1] != DataValue::Text("Bob".into())));
     }
 
     // Test table removal
@@ -14,6 +14,23 @@ pub mod join_integration_tests;
 pub mod fts_tests;
 pub mod mmap_tests;
 pub mod data_types;
+pub mod catalog_tests;
+pub mod statistics_tests;
+pub mod merge_tests;
+pub mod wal_tests;
+
+/// The rows of a SELECT result, without their row ids
+pub fn select_rows(result: crate::result::ReefDBResult) -> Vec<Vec<DataValue>> {
+    match result {
+        crate::result::ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row).collect(),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+}
+
+/// The first column of each row of a SELECT result
+pub fn select_ids(result: crate::result::ReefDBResult) -> Vec<DataValue> {
+    select_rows(result).into_iter().map(|mut row| row.swap_remove(0)).collect()
+}
 use crate::sql::{
     column_def::ColumnDef,
     data_type::DataType,
@@ -77,6 +94,7 @@ mod tests {
                     constraints: vec![],
                 },
             ],
+            false,
         ));
         db.execute_statement(create_stmt)?;
         
@@ -85,7 +103,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("Alice".to_string()),
+                DataValue::Text("Alice".into()),
             ],
         ));
         db.execute_statement(insert_stmt)?;
@@ -111,7 +129,7 @@ mod tests {
         
         if let ReefDBResult::Select(rows) = result {
             assert_eq!(rows.len(), 1);
-            assert_eq!(rows[0][1], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("Alice".into()));
         } else {
             panic!("Expected Select result");
         }
@@ -138,6 +156,7 @@ mod tests {
                     constraints: vec![],
                 },
             ],
+            false,
         ));
         db.execute_statement(create_stmt)?;
         
@@ -145,8 +164,8 @@ mod tests {
         let invalid_stmt = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
             vec![
-                DataValue::Text("invalid".to_string()),  // Should be Integer
-                DataValue::Text("Bob".to_string()),
+                DataValue::Text("invalid".into()),  // Should be Integer
+                DataValue::Text("Bob".into()),
             ],
         ));
         let result = db.execute_statement(invalid_stmt);
@@ -203,6 +222,7 @@ mod tests {
                     constraints: vec![],
                 },
             ],
+            false,
         ));
         db.execute_statement(create_stmt)?;
         
@@ -214,7 +234,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("Alice".to_string()),
+                DataValue::Text("Alice".into()),
             ],
         ));
         db.execute_statement(insert_stmt)?;
@@ -254,11 +274,11 @@ mod tests {
         
         if let ReefDBResult::Select(rows) = result {
             assert_eq!(rows.len(), 1);
-            assert_eq!(rows[0][1], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("Alice".into()));
         } else {
             panic!("Expected Select result");
         }
         
         Ok(())
     }
-} 
\ No newline at end of file
+}
@@ -14,7 +14,7 @@ mod tests {
         let db_path_str = db_path.to_str().unwrap().to_string();
         
         // Create initial storage
-        let mut storage = OnDiskStorage::new(db_path_str.clone());
+        let mut storage = OnDiskStorage::new(db_path_str.clone()).unwrap();
         
         // Create a table with a text column
         let columns = vec![
@@ -25,11 +25,11 @@ mod tests {
         let rows = vec![
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("hello world".to_string()),
+                DataValue::Text("hello world".into()),
             ],
             vec![
                 DataValue::Integer(2),
-                DataValue::Text("testing gin".to_string()),
+                DataValue::Text("testing gin".into()),
             ],
         ];
         
@@ -57,7 +57,7 @@ mod tests {
         drop(storage);
         
         // Create new storage instance from the same file
-        let storage = OnDiskStorage::new(db_path_str);
+        let storage = OnDiskStorage::new(db_path_str).unwrap();
         
         // Verify the table exists and has correct data
         assert!(storage.table_exists("test_table"));
@@ -67,4 +67,328 @@ mod tests {
         assert!(result.is_consistent, "Loaded index should be consistent");
         assert!(result.issues.is_empty(), "Loaded index should have no issues");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_btree_index_survives_reopen() {
+        use crate::indexes::btree::BTreeIndex;
+
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("users.db").to_str().unwrap().to_string();
+
+        {
+            let mut storage = OnDiskStorage::new(db_path.clone()).unwrap();
+            let columns = vec![
+                ColumnDef::new("id", DataType::Integer, vec![]),
+                ColumnDef::new("age", DataType::Integer, vec![]),
+            ];
+            let rows = vec![vec![DataValue::Integer(1), DataValue::Integer(25)]];
+            storage.insert_table("users".to_string(), columns, rows.clone());
+            storage.create_index("users", "age", IndexType::BTree(BTreeIndex::from_rows(&rows, 1))).unwrap();
+
+            // Rows added after the index was written to disk
+            storage.push_value("users", vec![DataValue::Integer(2), DataValue::Integer(30)]).unwrap();
+            storage.push_value("users", vec![DataValue::Integer(3), DataValue::Integer(25)]).unwrap();
+        }
+
+        // Without the index file the index has to be rebuilt from the stored definition
+        std::fs::remove_file(format!("{}.index", db_path)).unwrap();
+
+        let storage = OnDiskStorage::new(db_path).unwrap();
+        match storage.get_index("users", "age").unwrap() {
+            IndexType::BTree(btree) => {
+                let mut row_ids: Vec<usize> = btree.search(BTreeIndex::key_for(&DataValue::Integer(25)))
+                    .unwrap()
+                    .iter()
+                    .copied()
+                    .collect();
+                row_ids.sort();
+                assert_eq!(row_ids, vec![1, 3]);
+                assert!(btree.search(BTreeIndex::key_for(&DataValue::Integer(30))).unwrap().contains(&2));
+            }
+            IndexType::GIN(_) => panic!("Expected a B-Tree index"),
+        }
+
+        let result = storage.verify_index_consistency("users", "age").unwrap();
+        assert!(result.is_consistent);
+    }
+
+    #[test]
+    fn test_compressed_storage_round_trip() {
+        use crate::storage::disk::Compression;
+
+        let temp_dir = tempdir().unwrap();
+        let plain_path = temp_dir.path().join("plain.db").to_str().unwrap().to_string();
+        let gzip_path = temp_dir.path().join("gzip.db").to_str().unwrap().to_string();
+
+        let columns = vec![
+            ColumnDef::new("id", DataType::Integer, vec![]),
+            ColumnDef::new("body", DataType::Text, vec![]),
+        ];
+        let rows: Vec<Vec<DataValue>> = (0..500)
+            .map(|i| vec![DataValue::Integer(i), DataValue::Text("the same log line repeated over and over".into())])
+            .collect();
+
+        for (path, compression) in [(&plain_path, Compression::None), (&gzip_path, Compression::Gzip)] {
+            let mut storage = OnDiskStorage::with_compression(path.clone(), compression).unwrap();
+            storage.insert_table("logs".to_string(), columns.clone(), rows.clone());
+        }
+
+        let plain_size = std::fs::metadata(&plain_path).unwrap().len();
+        let gzip_size = std::fs::metadata(&gzip_path).unwrap().len();
+        assert!(gzip_size * 4 < plain_size, "gzip {} bytes vs plain {} bytes", gzip_size, plain_size);
+
+        // The header tells readers the file is compressed, whatever they were opened with
+        let storage = OnDiskStorage::new(gzip_path).unwrap();
+        assert_eq!(storage.compression(), Compression::None);
+        let (schema, loaded) = storage.get_table_ref("logs").unwrap();
+        assert_eq!(schema, &columns);
+        assert_eq!(loaded, &rows);
+    }
+
+    #[test]
+    fn test_headerless_data_file_still_loads() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("legacy.db").to_str().unwrap().to_string();
+
+        // Files from before the header hold the bincode-encoded table map directly
+        let mut tables = std::collections::HashMap::new();
+        tables.insert(
+            "users".to_string(),
+            (vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![vec![DataValue::Integer(7)]]),
+        );
+        std::fs::write(&path, bincode::serialize(&tables).unwrap()).unwrap();
+
+        let storage = OnDiskStorage::new(path).unwrap();
+        assert_eq!(storage.get_table_ref("users").unwrap().1, vec![vec![DataValue::Integer(7)]]);
+    }
+
+    #[test]
+    fn test_serialization_formats_round_trip() {
+        use crate::storage::disk::{Compression, SerializationFormat};
+
+        let temp_dir = tempdir().unwrap();
+        let columns = vec![
+            ColumnDef::new("id", DataType::Integer, vec![]),
+            ColumnDef::new("name", DataType::Text, vec![]),
+            ColumnDef::new("score", DataType::Float, vec![]),
+        ];
+        let rows = vec![
+            vec![DataValue::Integer(1), DataValue::Text("alice".into()), DataValue::Float(9.5)],
+            vec![DataValue::Integer(2), DataValue::Text("bob".into()), DataValue::Float(7.25)],
+        ];
+
+        for format in [SerializationFormat::Bincode, SerializationFormat::Json] {
+            for compression in [Compression::None, Compression::Gzip] {
+                let path = temp_dir.path()
+                    .join(format!("{:?}-{:?}.db", format, compression))
+                    .to_str().unwrap().to_string();
+                {
+                    let mut storage = OnDiskStorage::with_options(path.clone(), compression, format).unwrap();
+                    storage.insert_table("users".to_string(), columns.clone(), rows.clone());
+                }
+
+                // The header records the format, so a default reader loads it either way
+                let storage = OnDiskStorage::new(path.clone()).unwrap();
+                let (schema, loaded) = storage.get_table_ref("users").unwrap();
+                assert_eq!(schema, &columns);
+                assert_eq!(loaded, &rows);
+
+                let contents = std::fs::read(&path).unwrap();
+                assert_eq!(&contents[..4], b"RDB4");
+                if compression == Compression::None && format == SerializationFormat::Json {
+                    let text = String::from_utf8(contents[15..].to_vec()).unwrap();
+                    assert!(text.contains("\"alice\""), "{}", text);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_version_one_header_loads_as_bincode() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("v1.db").to_str().unwrap().to_string();
+
+        let mut tables = std::collections::HashMap::new();
+        tables.insert(
+            "users".to_string(),
+            (vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![vec![DataValue::Integer(3)]]),
+        );
+        let btree_columns: Vec<(String, String)> = Vec::new();
+        let mut contents = b"RDB1".to_vec();
+        contents.push(0);
+        contents.extend(bincode::serialize(&(tables, btree_columns)).unwrap());
+        std::fs::write(&path, contents).unwrap();
+
+        let storage = OnDiskStorage::new(path).unwrap();
+        assert_eq!(storage.get_table_ref("users").unwrap().1, vec![vec![DataValue::Integer(3)]]);
+    }
+
+    #[test]
+    fn test_older_row_format_fills_missing_columns() {
+        use crate::sql::constraints::constraint::Constraint;
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("rows-v0.db").to_str().unwrap().to_string();
+
+        // An "RDB2" file holds row format 0, whose rows may stop short of the schema
+        let schema = vec![
+            ColumnDef::new("id", DataType::Integer, vec![]),
+            ColumnDef::new("status", DataType::Text, vec![Constraint::Default(DataValue::Text("active".into()))]),
+            ColumnDef::new("score", DataType::Integer, vec![]),
+        ];
+        let mut tables = std::collections::HashMap::new();
+        tables.insert("users".to_string(), (schema, vec![
+            vec![DataValue::Integer(1)],
+            vec![DataValue::Integer(2), DataValue::Text("banned".into())],
+            vec![DataValue::Integer(3), DataValue::Text("idle".into()), DataValue::Integer(9)],
+        ]));
+        let btree_columns: Vec<(String, String)> = Vec::new();
+        let mut contents = b"RDB2".to_vec();
+        contents.extend([0, 0]);
+        contents.extend(bincode::serialize(&(tables, btree_columns, 0u32)).unwrap());
+        std::fs::write(&path, contents).unwrap();
+
+        let mut storage = OnDiskStorage::new(path.clone()).unwrap();
+        let text = |s: &str| DataValue::Text(s.into());
+        let expected = vec![
+            vec![DataValue::Integer(1), text("active"), DataValue::Null],
+            vec![DataValue::Integer(2), text("banned"), DataValue::Null],
+            vec![DataValue::Integer(3), text("idle"), DataValue::Integer(9)],
+        ];
+        assert_eq!(storage.get_table_ref("users").unwrap().1, expected);
+
+        // Saving rewrites the file in the current row format
+        storage.flush().unwrap();
+        let contents = std::fs::read(&path).unwrap();
+        assert_eq!(&contents[..4], b"RDB4");
+        assert_eq!(contents[6], crate::storage::disk::ROW_FORMAT_VERSION);
+        assert_eq!(OnDiskStorage::new(path).unwrap().get_table_ref("users").unwrap().1, expected);
+    }
+
+    #[test]
+    fn test_unreadable_data_files_are_refused() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("refused.db").to_str().unwrap().to_string();
+        let mut storage = OnDiskStorage::new(path.clone()).unwrap();
+        storage.insert_table("users".to_string(), vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![
+            vec![DataValue::Integer(1)],
+        ]);
+        let written = std::fs::read(&path).unwrap();
+
+        // Rows in a format newer than this code knows, an unknown compression and a payload
+        // that doesn't decode all fail to open, and the file is left as it was
+        let mut newer = written.clone();
+        newer[6] = crate::storage::disk::ROW_FORMAT_VERSION + 1;
+        let mut unknown_compression = written.clone();
+        unknown_compression[4] = 9;
+        let mut garbage = written[..7].to_vec();
+        garbage.extend(b"not a table map");
+        for contents in [newer, unknown_compression, garbage] {
+            std::fs::write(&path, &contents).unwrap();
+            assert!(OnDiskStorage::new(path.clone()).is_err());
+            assert!(crate::OnDiskReefDB::create_on_disk(path.clone(), String::new()).is_err());
+            // Opened through the trait, the storage reports the error instead of writing over the file
+            let mut opened = <OnDiskStorage as crate::storage::Storage>::new(path.clone());
+            opened.insert_table("other".to_string(), vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![]);
+            assert!(opened.push_value("other", vec![DataValue::Integer(2)]).is_err());
+            assert!(opened.flush().is_err());
+            drop(opened);
+            assert_eq!(std::fs::read(&path).unwrap(), contents);
+        }
+    }
+
+    #[test]
+    fn test_newer_schema_version_is_rejected() {
+        use crate::{error::ReefDBError, OnDiskReefDB};
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("versioned.db").to_str().unwrap().to_string();
+
+        // Simulate a file written by code that migrated the schema further than we know about
+        let mut storage = OnDiskStorage::new(path.clone()).unwrap();
+        storage.insert_table("users".to_string(), vec![ColumnDef::new("id", DataType::Integer, vec![])], vec![]);
+        storage.set_schema_version(7);
+        assert_eq!(OnDiskStorage::new(path.clone()).unwrap().schema_version(), 7);
+
+        let result = OnDiskReefDB::open_with_schema_version(path.clone(), String::new(), 6);
+        assert_eq!(result.err(), Some(ReefDBError::SchemaVersionMismatch(7, 6)));
+
+        let db = OnDiskReefDB::open_with_schema_version(path, String::new(), 7).unwrap();
+        assert_eq!(db.schema_version(), 7);
+    }
+
+    #[test]
+    fn test_buffer_pool_serves_hot_tables_and_flushes_dirty_evictions() {
+        use crate::storage::disk::BufferPoolStats;
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("pooled.db").to_str().unwrap().to_string();
+        let columns = || vec![ColumnDef::new("id", DataType::Integer, vec![])];
+        let ids = |storage: &OnDiskStorage, table: &str| storage.get_table_ref(table).unwrap().1.clone();
+        let row = |id: i64| vec![DataValue::Integer(id)];
+
+        let mut storage = OnDiskStorage::with_buffer_pool(path.clone(), 1).unwrap();
+        storage.insert_table("hot".to_string(), columns(), vec![row(1)]);
+        // Loading a second table evicts the first, writing its unsaved rows out
+        storage.insert_table("cold".to_string(), columns(), vec![row(2)]);
+        assert_eq!(storage.buffer_pool_stats().evictions, 1);
+        assert_eq!(storage.buffer_pool_stats().flushes, 1);
+
+        let before = storage.buffer_pool_stats();
+        for _ in 0..3 {
+            assert_eq!(ids(&storage, "cold"), vec![row(2)]);
+        }
+        assert_eq!(storage.buffer_pool_stats(), BufferPoolStats { hits: before.hits + 3, ..before });
+
+        // Reading an evicted table back loads it; reads never evict
+        assert_eq!(ids(&storage, "hot"), vec![row(1)]);
+        assert_eq!(ids(&storage, "hot"), vec![row(1)]);
+        let stats = storage.buffer_pool_stats();
+        assert_eq!((stats.misses, stats.hits), (before.misses + 1, before.hits + 4));
+        assert_eq!((stats.evictions, stats.flushes), (1, 1));
+
+        // Changing a table evicts the other one; "hot" is clean, so nothing is written
+        storage.push_value("cold", row(3)).unwrap();
+        assert_eq!((storage.buffer_pool_stats().evictions, storage.buffer_pool_stats().flushes), (2, 1));
+        assert_eq!(ids(&OnDiskStorage::new(path.clone()).unwrap(), "cold"), vec![row(2)]);
+
+        // Evicting the changed table persists its new row
+        storage.push_value("hot", row(4)).unwrap();
+        assert_eq!(storage.buffer_pool_stats().flushes, 2);
+        assert_eq!(ids(&OnDiskStorage::new(path.clone()).unwrap(), "cold"), vec![row(2), row(3)]);
+        assert_eq!(ids(&OnDiskStorage::new(path.clone()).unwrap(), "hot"), vec![row(1)]);
+
+        // Changes to the tables still in memory reach the file when the storage is dropped
+        drop(storage);
+        assert_eq!(ids(&OnDiskStorage::new(path.clone()).unwrap(), "hot"), vec![row(1), row(4)]);
+
+        // Tables load one at a time, and one that can't be read is an error, not a missing table
+        let mut storage = OnDiskStorage::with_buffer_pool(path.clone(), 1).unwrap();
+        let contents = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &contents[..contents.len() - 1]).unwrap();
+        assert_eq!(ids(&storage, "cold"), vec![row(2), row(3)]);
+        assert!(matches!(storage.push_value("hot", row(5)), Err(crate::error::ReefDBError::IoError(_))));
+        assert!(storage.table_exists("hot"));
+        assert_eq!(storage.get_table_ref("hot"), None);
+        assert!(matches!(storage.flush(), Err(crate::error::ReefDBError::IoError(_))));
+    }
+
+    #[test]
+    fn test_append_rows_is_all_or_nothing() {
+        use crate::sql::constraints::constraint::Constraint;
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("bulk.db").to_str().unwrap().to_string();
+        let row = |id: i64| vec![DataValue::Integer(id)];
+        let mut storage = OnDiskStorage::new(path.clone()).unwrap();
+        storage.insert_table("users".to_string(), vec![ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey])], vec![row(1)]);
+
+        // A clash with a stored row or one earlier in the batch rejects the whole batch
+        assert!(storage.append_rows("users", vec![row(2), row(1)]).is_err());
+        assert!(storage.append_rows("users", vec![row(2), row(3), row(2)]).is_err());
+        assert_eq!(storage.get_table_ref("users").unwrap().1, vec![row(1)]);
+
+        assert_eq!(storage.append_rows("users", vec![row(2), row(3)]).unwrap(), vec![2, 3]);
+        assert_eq!(OnDiskStorage::new(path).unwrap().get_table_ref("users").unwrap().1, vec![row(1), row(2), row(3)]);
+    }
+}
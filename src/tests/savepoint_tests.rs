@@ -20,7 +20,7 @@ mod tests {
             ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
             ColumnDef::new("name", DataType::Text, vec![]),
         ];
-        db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns)))?;
+        db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, Statement::Create(CreateStatement::Table("users".to_string(), columns, false)))?;
         Ok(())
     }
 
@@ -37,7 +37,7 @@ mod tests {
         // Insert initial data
         let insert_stmt = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(1), DataValue::Text("Alice".to_string())],
+            vec![DataValue::Integer(1), DataValue::Text("Alice".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt)?;
 
@@ -48,7 +48,7 @@ mod tests {
         // Insert more data
         let insert_stmt2 = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(2), DataValue::Text("Bob".to_string())],
+            vec![DataValue::Integer(2), DataValue::Text("Bob".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt2)?;
 
@@ -62,7 +62,7 @@ mod tests {
         
         if let ReefDBResult::Select(rows) = result {
             assert_eq!(rows.len(), 1);
-            assert_eq!(rows[0][1], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("Alice".into()));
         } else {
             panic!("Expected Select result");
         }
@@ -104,7 +104,7 @@ mod tests {
         // Insert initial data
         let insert_stmt = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(1), DataValue::Text("Alice".to_string())],
+            vec![DataValue::Integer(1), DataValue::Text("Alice".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt)?;
 
@@ -115,7 +115,7 @@ mod tests {
         // Insert second record
         let insert_stmt2 = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(2), DataValue::Text("Bob".to_string())],
+            vec![DataValue::Integer(2), DataValue::Text("Bob".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt2)?;
 
@@ -126,7 +126,7 @@ mod tests {
         // Insert third record
         let insert_stmt3 = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(3), DataValue::Text("Charlie".to_string())],
+            vec![DataValue::Integer(3), DataValue::Text("Charlie".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt3)?;
 
@@ -140,7 +140,7 @@ mod tests {
         
         if let ReefDBResult::Select(rows) = result {
             assert_eq!(rows.len(), 1);
-            assert_eq!(rows[0][1], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("Alice".into()));
         } else {
             panic!("Expected Select result");
         }
@@ -191,7 +191,7 @@ mod tests {
         // Insert initial data
         let insert_stmt = Statement::Insert(InsertStatement::IntoTable(
             "users".to_string(),
-            vec![DataValue::Integer(1), DataValue::Text("Alice".to_string())],
+            vec![DataValue::Integer(1), DataValue::Text("Alice".into())],
         ));
         db.transaction_manager.as_mut().unwrap().execute_statement(transaction_id, insert_stmt)?;
 
@@ -223,7 +223,7 @@ mod tests {
         
         if let ReefDBResult::Select(rows) = result {
             assert_eq!(rows.len(), 1);
-            assert_eq!(rows[0][1], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[0][1], DataValue::Text("Alice".into()));
         } else {
             panic!("Expected Select result");
         }
@@ -233,4 +233,65 @@ mod tests {
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_list_savepoints_in_creation_order() -> Result<(), ReefDBError> {
+        let mut db = InMemoryReefDB::create_in_memory()?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        let transaction_id = tm.begin_transaction(IsolationLevel::Serializable)?;
+        setup_test_table(&mut db, transaction_id)?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        assert!(tm.list_savepoints(transaction_id)?.is_empty());
+
+        for name in ["outer", "middle", "inner"] {
+            tm.create_savepoint(transaction_id, name.to_string())?;
+        }
+        assert_eq!(tm.list_savepoints(transaction_id)?, vec!["outer", "middle", "inner"]);
+
+        // Rolling back drops the newer savepoints, releasing drops just the one
+        tm.rollback_to_savepoint(transaction_id, "middle")?;
+        assert_eq!(tm.list_savepoints(transaction_id)?, vec!["outer", "middle"]);
+        tm.release_savepoint(transaction_id, "outer")?;
+        assert_eq!(tm.list_savepoints(transaction_id)?, vec!["middle"]);
+
+        tm.commit_transaction(transaction_id)?;
+        assert!(matches!(tm.list_savepoints(transaction_id), Err(ReefDBError::TransactionNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymous_savepoints_roll_back_by_handle() -> Result<(), ReefDBError> {
+        let mut db = InMemoryReefDB::create_in_memory()?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        let transaction_id = tm.begin_transaction(IsolationLevel::Serializable)?;
+        setup_test_table(&mut db, transaction_id)?;
+        let tm = db.transaction_manager.as_mut().unwrap();
+        let insert = |id: i64, name: &str| Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(id), DataValue::Text(name.into())],
+        ));
+
+        tm.execute_statement(transaction_id, insert(1, "Alice"))?;
+        let first = tm.create_anonymous_savepoint(transaction_id)?;
+        tm.execute_statement(transaction_id, insert(2, "Bob"))?;
+        let second = tm.create_anonymous_savepoint(transaction_id)?;
+        tm.execute_statement(transaction_id, insert(3, "Carol"))?;
+        assert_ne!(first, second);
+        assert_eq!(tm.list_savepoints(transaction_id)?, vec![first.to_string(), second.to_string()]);
+
+        let restored = tm.rollback_to(transaction_id, first)?;
+        assert_eq!(
+            restored.tables["users"].1,
+            vec![vec![DataValue::Integer(1), DataValue::Text("Alice".into())]]
+        );
+        assert_eq!(tm.list_savepoints(transaction_id)?, vec![first.to_string()]);
+
+        // The rollback dropped the newer savepoint, so its handle no longer resolves
+        assert_eq!(tm.rollback_to(transaction_id, second).err(), Some(ReefDBError::SavepointNotFound(second.to_string())));
+        tm.release(transaction_id, first)?;
+        assert!(tm.list_savepoints(transaction_id)?.is_empty());
+
+        tm.commit_transaction(transaction_id)?;
+        Ok(())
+    }
+}
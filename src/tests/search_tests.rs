@@ -18,27 +18,27 @@ fn test_fts_search_with_select() -> Result<(), ReefDBError> {
         ColumnDef::new("author", DataType::Text, vec![]),
         ColumnDef::new("description", DataType::TSVector, vec![]),
     ];
-    db.execute_statement(Statement::Create(CreateStatement::Table("books".to_string(), columns)))?;
+    db.execute_statement(Statement::Create(CreateStatement::Table("books".to_string(), columns, false)))?;
 
     // Insert test data
     let values = vec![
         vec![
             DataValue::Integer(1),
-            DataValue::Text("Book 1".to_string()),
-            DataValue::Text("Author 1".to_string()),
-            DataValue::Text("A book about the history of computer science.".to_string()),
+            DataValue::Text("Book 1".into()),
+            DataValue::Text("Author 1".into()),
+            DataValue::Text("A book about the history of computer science.".into()),
         ],
         vec![
             DataValue::Integer(2),
-            DataValue::Text("Book 2".to_string()),
-            DataValue::Text("Author 2".to_string()),
-            DataValue::Text("A book about modern programming languages.".to_string()),
+            DataValue::Text("Book 2".into()),
+            DataValue::Text("Author 2".into()),
+            DataValue::Text("A book about modern programming languages.".into()),
         ],
         vec![
             DataValue::Integer(3),
-            DataValue::Text("Book 3".to_string()),
-            DataValue::Text("Author 3".to_string()),
-            DataValue::Text("A book about the future of artificial intelligence.".to_string()),
+            DataValue::Text("Book 3".into()),
+            DataValue::Text("Author 3".into()),
+            DataValue::Text("A book about the future of artificial intelligence.".into()),
         ],
     ];
 
@@ -1,4 +1,5 @@
 use crate::{InMemoryReefDB, error::ReefDBError, result::ReefDBResult};
+use super::{select_ids, select_rows};
 use crate::sql::{
     clauses::wheres::where_type::{WhereType, WhereClause},
     data_value::DataValue,
@@ -28,7 +29,7 @@ fn test_select_with_where() -> Result<()> {
     if let ReefDBResult::Select(results) = db.query("SELECT * FROM users WHERE id = 1")? {
         assert_eq!(results.len(), 1);
         assert_eq!(results[0][0], DataValue::Integer(1));
-        assert_eq!(results[0][1], DataValue::Text("Alice".to_string()));
+        assert_eq!(results[0][1], DataValue::Text("Alice".into()));
 
         // Verify column information
         assert_eq!(results.columns.len(), 2);
@@ -58,9 +59,9 @@ fn test_select_all() -> Result<()> {
     if let ReefDBResult::Select(results) = db.query("SELECT * FROM users")? {
         assert_eq!(results.len(), 2);
         assert_eq!(results[0][0], DataValue::Integer(1));
-        assert_eq!(results[0][1], DataValue::Text("Alice".to_string()));
+        assert_eq!(results[0][1], DataValue::Text("Alice".into()));
         assert_eq!(results[1][0], DataValue::Integer(2));
-        assert_eq!(results[1][1], DataValue::Text("Bob".to_string()));
+        assert_eq!(results[1][1], DataValue::Text("Bob".into()));
 
         // Verify column information
         assert_eq!(results.columns.len(), 2);
@@ -75,4 +76,901 @@ fn test_select_all() -> Result<()> {
     }
 
     Ok(())
-} 
\ No newline at end of file
+} 
+
+#[test]
+fn test_keyset_pagination_matches_offset_pages() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT)")?;
+    // Insert out of key order, with keys whose encodings don't sort by their bytes
+    for id in [7, 300, -3, 11, 1, 9, 256, -40, 2, 10, 4, 8, 6] {
+        db.query(&format!("INSERT INTO items VALUES ({}, 'item{}')", id, id))?;
+    }
+    db.query("DELETE FROM items WHERE id = 9")?;
+
+    let page_size = 4;
+    let pages = |db: &InMemoryReefDB| -> Result<Vec<Vec<Vec<DataValue>>>> {
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db.select_page("items", cursor.as_ref(), page_size)?;
+            pages.push(page.rows);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(pages),
+            }
+        }
+    };
+    // Pages that skip an offset into the ordered rows
+    let ordered = select_rows(db.query("SELECT * FROM items ORDER BY id")?);
+    let offset_pages: Vec<Vec<Vec<DataValue>>> = ordered.chunks(page_size).map(|page| page.to_vec()).collect();
+    assert_eq!(ordered.len(), 12);
+
+    // Scanned without an index, then read through the B-Tree on the key
+    assert_eq!(pages(&db)?, offset_pages);
+    db.query("CREATE INDEX ON items(id)")?;
+    let (schema, _) = db.get_table_schema("items")?;
+    assert!(db.ordered_key_index("items", &schema[0], Some(&DataValue::Integer(0))).is_some());
+    assert_eq!(pages(&db)?, offset_pages);
+    // The index follows later writes
+    db.query("INSERT INTO items VALUES (5, 'item5')")?;
+    db.query("DELETE FROM items WHERE id = 6")?;
+    assert_eq!(db.select_page("items", Some(&DataValue::Integer(4)), 2)?.rows, vec![
+        vec![DataValue::Integer(5), DataValue::Text("item5".into())],
+        vec![DataValue::Integer(7), DataValue::Text("item7".into())],
+    ]);
+
+    // A cursor past the last key yields an empty final page
+    let page = db.select_page("items", Some(&DataValue::Integer(300)), page_size)?;
+    assert!(page.rows.is_empty());
+    assert_eq!(page.next_cursor, None);
+
+    db.query("CREATE TABLE logs (message TEXT)")?;
+    assert!(db.select_page("logs", None, page_size).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_select_case_expression() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER)")?;
+    db.query("INSERT INTO users VALUES (1, 30)")?;
+    db.query("INSERT INTO users VALUES (2, 15)")?;
+    db.query("INSERT INTO users VALUES (3, 8)")?;
+
+
+    // Matched and ELSE branches
+    let rows = select_rows(db.query(
+        "SELECT id, CASE WHEN age >= 18 THEN 'adult' WHEN age > 12 THEN 'teen' ELSE 'child' END AS label FROM users ORDER BY id"
+    )?);
+    assert_eq!(rows, vec![
+        vec![DataValue::Integer(1), DataValue::Text("adult".into())],
+        vec![DataValue::Integer(2), DataValue::Text("teen".into())],
+        vec![DataValue::Integer(3), DataValue::Text("child".into())],
+    ]);
+
+    // Without ELSE, unmatched rows yield NULL
+    let rows = select_rows(db.query("SELECT id, CASE WHEN age >= 18 THEN 'adult' END FROM users ORDER BY id")?);
+    assert_eq!(rows, vec![
+        vec![DataValue::Integer(1), DataValue::Text("adult".into())],
+        vec![DataValue::Integer(2), DataValue::Null],
+        vec![DataValue::Integer(3), DataValue::Null],
+    ]);
+
+    if let ReefDBResult::Select(result) = db.query("SELECT CASE WHEN age >= 18 THEN 'adult' END AS label FROM users")? {
+        assert_eq!(result.columns[0].name, "label");
+        assert_eq!(result.columns[0].data_type, DataType::Text);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_select_string_functions() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")?;
+    db.query("INSERT INTO people VALUES (1, 'Alice', 30)")?;
+    db.query("INSERT INTO people VALUES (2, 'Zoë', 25)")?;
+
+    let text = |s: &str| DataValue::Text(s.into());
+
+    let result = db.query(
+        "SELECT id, LOWER(name), UPPER(name), LENGTH(name) AS len, SUBSTR(name, 2, 2) FROM people ORDER BY id"
+    )?;
+    if let ReefDBResult::Select(result) = &result {
+        assert_eq!(result.columns[1].data_type, DataType::Text);
+        assert_eq!(result.columns[3].name, "len");
+        assert_eq!(result.columns[3].data_type, DataType::Integer);
+    }
+    assert_eq!(select_rows(result), vec![
+        vec![DataValue::Integer(1), text("alice"), text("ALICE"), DataValue::Integer(5), text("li")],
+        vec![DataValue::Integer(2), text("zoë"), text("ZOË"), DataValue::Integer(3), text("oë")],
+    ]);
+
+    // Functions compose and work in WHERE comparisons
+    assert_eq!(select_rows(db.query("SELECT id, UPPER(SUBSTR(name, 1, 3)) FROM people WHERE LOWER(name) = 'zoë'")?), vec![
+        vec![DataValue::Integer(2), text("ZOË")],
+    ]);
+    assert_eq!(select_rows(db.query("SELECT id FROM people WHERE LENGTH(name) > 3")?), vec![
+        vec![DataValue::Integer(1)],
+    ]);
+
+    // Quoted text is a literal even when it spells a column name
+    assert_eq!(select_rows(db.query("SELECT UPPER('name') FROM people WHERE id = 1")?), vec![vec![text("NAME")]]);
+    assert_eq!(select_rows(db.query("SELECT id FROM people WHERE LOWER(name) = 'name'")?), Vec::<Vec<DataValue>>::new());
+
+    // Non-text input is rejected, as are columns the table doesn't have
+    assert!(db.query("SELECT LOWER(age) FROM people").is_err());
+    assert_eq!(
+        db.query("SELECT UPPER(nickname) FROM people"),
+        Err(ReefDBError::ColumnNotFound("nickname".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_select_numeric_functions() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE readings (id INTEGER PRIMARY KEY, delta INTEGER, value FLOAT)")?;
+    db.query("INSERT INTO readings VALUES (1, -5, 2.5)")?;
+    db.query("INSERT INTO readings VALUES (2, 7, -1.25)")?;
+
+    let rows = select_rows(db.query(
+        "SELECT id, ABS(delta), ABS(value), ROUND(value), ROUND(value, 1), ROUND(delta), MOD(delta, 3), MOD(delta, 0) FROM readings ORDER BY id"
+    )?);
+    assert_eq!(rows, vec![
+        vec![
+            DataValue::Integer(1), DataValue::Integer(5), DataValue::Float(2.5), DataValue::Float(3.0),
+            DataValue::Float(2.5), DataValue::Integer(-5), DataValue::Integer(-2), DataValue::Null,
+        ],
+        vec![
+            DataValue::Integer(2), DataValue::Integer(7), DataValue::Float(1.25), DataValue::Float(-1.0),
+            DataValue::Float(-1.3), DataValue::Integer(7), DataValue::Integer(1), DataValue::Null,
+        ],
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_select_concatenation() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE people (id INTEGER PRIMARY KEY, first TEXT, last TEXT)")?;
+    db.query("INSERT INTO people VALUES (1, 'Ada', 'Lovelace')")?;
+    db.query("INSERT INTO people VALUES (2, 'Plato', NULL)")?;
+
+    let rows = match db.query(
+        "SELECT id, first || ' ' || last AS fullname, CONCAT(first, ' ', last), id || '-' || first, CONCAT('last', ':', last) FROM people ORDER BY id"
+    )? {
+        ReefDBResult::Select(result) => {
+            assert_eq!(result.columns[1].name, "fullname");
+            assert_eq!(result.columns[1].data_type, DataType::Text);
+            result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>()
+        }
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    let text = |s: &str| DataValue::Text(s.into());
+    assert_eq!(rows, vec![
+        vec![DataValue::Integer(1), text("Ada Lovelace"), text("Ada Lovelace"), text("1-Ada"), text("last:Lovelace")],
+        // `||` yields NULL for a NULL operand while CONCAT skips it
+        vec![DataValue::Integer(2), DataValue::Null, text("Plato "), text("2-Plato"), text("last:")],
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_join_pushes_right_table_predicates_into_index() -> Result<()> {
+    let setup = |indexed: bool| -> Result<InMemoryReefDB> {
+        let mut db = InMemoryReefDB::create_in_memory()?;
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")?;
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, status TEXT, amount INTEGER)")?;
+        if indexed {
+            db.query("CREATE INDEX ON orders(status)")?;
+        }
+        for id in 1..=20 {
+            db.query(&format!("INSERT INTO users VALUES ({}, 'user{}')", id, id))?;
+        }
+        for id in 1..=400 {
+            let status = if id % 50 == 0 { "open" } else { "closed" };
+            db.query(&format!("INSERT INTO orders VALUES ({}, {}, '{}', {})", id, id % 20 + 1, status, id % 7))?;
+        }
+        // Shifts row ids and rewrites values the index was built from
+        db.query("DELETE FROM orders WHERE id = 100")?;
+        db.query("UPDATE orders SET status = 'open' WHERE id = 7")?;
+        Ok(db)
+    };
+
+    #[derive(Default)]
+    struct Scanned(std::sync::Mutex<Vec<(String, usize)>>);
+    impl crate::metrics::MetricsSink for Scanned {
+        fn rows_scanned(&self, table: &str, rows: usize) {
+            self.0.lock().unwrap().push((table.to_string(), rows));
+        }
+    }
+
+    let mut indexed = setup(true)?;
+    let mut unindexed = setup(false)?;
+    let (indexed_scans, unindexed_scans) = (std::sync::Arc::new(Scanned::default()), std::sync::Arc::new(Scanned::default()));
+    indexed.set_metrics_sink(indexed_scans.clone());
+    unindexed.set_metrics_sink(unindexed_scans.clone());
+    let queries = [
+        "SELECT users.name, orders.id FROM users INNER JOIN orders ON users.id = orders.user_id WHERE orders.status = 'open'",
+        "SELECT users.name, orders.id FROM users INNER JOIN orders ON users.id = orders.user_id WHERE orders.status = 'open' AND amount > 2",
+        "SELECT users.name, orders.id FROM users INNER JOIN orders ON users.id = orders.user_id WHERE users.id < 5 AND orders.status = 'closed'",
+        "SELECT users.name, orders.id FROM users INNER JOIN orders ON users.id = orders.user_id WHERE orders.status = 'open' OR users.id = 1",
+    ];
+    for sql in queries {
+        assert_eq!(select_rows(indexed.query(sql)?), select_rows(unindexed.query(sql)?), "{}", sql);
+    }
+
+    // The index hands the join only the orders with the wanted status, 8 open or 391
+    // closed; without it, or under an OR, all 399 are read
+    let orders_read = |scans: &Scanned| scans.0.lock().unwrap().iter()
+        .filter(|(table, _)| table == "orders").map(|(_, rows)| *rows).collect::<Vec<_>>();
+    assert_eq!(orders_read(&indexed_scans), vec![8, 8, 391, 399]);
+    assert_eq!(orders_read(&unindexed_scans), vec![399; 4]);
+
+    let text = |s: &str| DataValue::Text(s.into());
+    assert_eq!(select_rows(indexed.query(queries[0])?), vec![
+        vec![text("user1"), DataValue::Integer(200)],
+        vec![text("user1"), DataValue::Integer(300)],
+        vec![text("user1"), DataValue::Integer(400)],
+        vec![text("user8"), DataValue::Integer(7)],
+        vec![text("user11"), DataValue::Integer(50)],
+        vec![text("user11"), DataValue::Integer(150)],
+        vec![text("user11"), DataValue::Integer(250)],
+        vec![text("user11"), DataValue::Integer(350)],
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_rank_and_dense_rank_ties() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE staff (id INTEGER PRIMARY KEY, dept TEXT, salary INTEGER)")?;
+    for (id, dept, salary) in [(1, "eng", 100), (2, "eng", 90), (3, "eng", 100), (4, "eng", 80), (5, "ops", 70), (6, "ops", 70), (7, "ops", 60)] {
+        db.query(&format!("INSERT INTO staff VALUES ({}, '{}', {})", id, dept, salary))?;
+    }
+
+    let rows = match db.query(
+        "SELECT id, ROW_NUMBER() OVER (PARTITION BY dept ORDER BY salary DESC, id) AS n, \
+         RANK() OVER (PARTITION BY dept ORDER BY salary DESC) AS r, \
+         DENSE_RANK() OVER (PARTITION BY dept ORDER BY salary DESC) AS d FROM staff"
+    )? {
+        ReefDBResult::Select(result) => {
+            assert_eq!(result.columns[2].name, "r");
+            assert_eq!(result.columns[2].data_type, DataType::Integer);
+            result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>()
+        }
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    let expected: Vec<Vec<DataValue>> = [
+        // eng: 100, 100 tie; RANK skips to 3, DENSE_RANK continues at 2
+        (1, 1, 1, 1), (2, 3, 3, 2), (3, 2, 1, 1), (4, 4, 4, 3),
+        // ops: partition restarts numbering
+        (5, 1, 1, 1), (6, 2, 1, 1), (7, 3, 3, 2),
+    ].into_iter()
+        .map(|(id, n, r, d)| vec![DataValue::Integer(id), DataValue::Integer(n), DataValue::Integer(r), DataValue::Integer(d)])
+        .collect();
+    assert_eq!(rows, expected);
+
+    // Without PARTITION BY the whole result is one window
+    let rows = match db.query("SELECT id, RANK() OVER (ORDER BY salary) FROM staff WHERE dept = 'ops'")? {
+        ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row[1].clone()).collect::<Vec<_>>(),
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    assert_eq!(rows, vec![DataValue::Integer(2), DataValue::Integer(2), DataValue::Integer(1)]);
+    Ok(())
+}
+
+#[test]
+fn test_recursive_cte_walks_hierarchy() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE employees (id INTEGER PRIMARY KEY, name TEXT, manager_id INTEGER)")?;
+    for (id, name, manager) in [(1, "ceo", "NULL"), (2, "cto", "1"), (3, "dev", "2"), (4, "intern", "3"), (5, "cfo", "1"), (6, "other", "NULL")] {
+        db.query(&format!("INSERT INTO employees VALUES ({}, '{}', {})", id, name, manager))?;
+    }
+
+    // Everyone reporting, directly or not, to the CTO
+    let names = match db.query(
+        "WITH RECURSIVE reports (id, name) AS (\
+            SELECT id, name FROM employees WHERE id = 2 \
+            UNION ALL \
+            SELECT employees.id, employees.name FROM employees INNER JOIN reports ON employees.manager_id = reports.id\
+        ) SELECT name FROM reports"
+    )? {
+        ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row[0].clone()).collect::<Vec<_>>(),
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    assert_eq!(names, vec![
+        DataValue::Text("cto".into()),
+        DataValue::Text("dev".into()),
+        DataValue::Text("intern".into()),
+    ]);
+    // The CTE only lives for the statement
+    assert!(db.query("SELECT * FROM reports").is_err());
+
+    // A cycle never runs dry under UNION ALL and hits the iteration cap
+    db.query("CREATE TABLE links (id INTEGER PRIMARY KEY, parent INTEGER)")?;
+    for (id, parent) in [(1, 3), (2, 1), (3, 2)] {
+        db.query(&format!("INSERT INTO links VALUES ({}, {})", id, parent))?;
+    }
+    let result = db.query(
+        "WITH RECURSIVE walk AS (\
+            SELECT id FROM links WHERE id = 1 \
+            UNION ALL \
+            SELECT links.id FROM links INNER JOIN walk ON links.parent = walk.id\
+        ) SELECT id FROM walk"
+    );
+    assert!(matches!(result, Err(ReefDBError::Other(msg)) if msg.contains("did not finish")));
+    assert!(db.query("SELECT * FROM walk").is_err());
+
+    // UNION drops rows already produced, so the same cycle terminates
+    let ids = match db.query(
+        "WITH RECURSIVE walk AS (\
+            SELECT id FROM links WHERE id = 1 \
+            UNION \
+            SELECT links.id FROM links INNER JOIN walk ON links.parent = walk.id\
+        ) SELECT id FROM walk"
+    )? {
+        ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row[0].clone()).collect::<Vec<_>>(),
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    assert_eq!(ids, vec![DataValue::Integer(1), DataValue::Integer(2), DataValue::Integer(3)]);
+    Ok(())
+}
+
+#[test]
+fn test_cte_rows_never_reach_the_backend() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("cte.db").to_str().unwrap().to_string();
+    let mut db = crate::OnDiskReefDB::create_on_disk(path.clone(), path.clone() + ".idx")?;
+    db.query("CREATE TABLE links (id INTEGER PRIMARY KEY, parent INTEGER)")?;
+    for (id, parent) in [(1, "NULL"), (2, "1"), (3, "2")] {
+        db.query(&format!("INSERT INTO links VALUES ({}, {})", id, parent))?;
+    }
+
+    // Any write to the data file would bring it back
+    std::fs::remove_file(&path).unwrap();
+    let result = db.query(
+        "WITH RECURSIVE chain AS (\
+            SELECT id FROM links WHERE id = 1 \
+            UNION ALL \
+            SELECT links.id FROM links INNER JOIN chain ON links.parent = chain.id\
+        ) SELECT id FROM chain"
+    )?;
+    match result {
+        ReefDBResult::Select(result) => assert_eq!(result.rows.len(), 3),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+    assert!(!std::path::Path::new(&path).exists());
+    assert!(db.query("SELECT * FROM chain").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_cte_referenced_twice() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")?;
+    for (id, name, age) in [(1, "ann", 70), (2, "bob", 15), (3, "cid", 40), (4, "dee", 81)] {
+        db.query(&format!("INSERT INTO users VALUES ({}, '{}', {})", id, name, age))?;
+    }
+
+    let rows = select_rows(db.query("WITH adults AS (SELECT id, name, age FROM users WHERE age >= 18) SELECT * FROM adults WHERE age < 75")?);
+    assert_eq!(rows, vec![
+        vec![DataValue::Integer(1), DataValue::Text("ann".into()), DataValue::Integer(70)],
+        vec![DataValue::Integer(3), DataValue::Text("cid".into()), DataValue::Integer(40)],
+    ]);
+
+    // `adults` is read by the second CTE and again by the main query
+    let rows = select_rows(db.query(
+        "WITH adults AS (SELECT id, name, age FROM users WHERE age >= 18), \
+              seniors AS (SELECT id FROM adults WHERE age >= 65) \
+         SELECT adults.name FROM adults INNER JOIN seniors ON adults.id = seniors.id"
+    )?);
+    assert_eq!(rows, vec![
+        vec![DataValue::Text("ann".into())],
+        vec![DataValue::Text("dee".into())],
+    ]);
+
+    // Temporaries are gone afterwards, even when the main query fails
+    assert!(db.query("SELECT * FROM adults").is_err());
+    assert!(db.query("WITH adults AS (SELECT id FROM users) SELECT * FROM missing").is_err());
+    assert!(db.query("SELECT * FROM adults").is_err());
+
+    // A CTE can't shadow a real table
+    assert!(db.query("WITH users AS (SELECT id FROM users) SELECT * FROM users").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_repeated_query_reuses_cached_plan() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'Alice')")?;
+
+    let before = db.plan_cache_stats();
+    db.query("SELECT * FROM users WHERE id = 1")?;
+    let after_first = db.plan_cache_stats();
+    assert_eq!(after_first.misses, before.misses + 1);
+
+    // Same statement with different layout hits the cache
+    let result = db.query("SELECT *   FROM users\n WHERE id = 1;")?;
+    let after_second = db.plan_cache_stats();
+    assert_eq!(after_second.hits, after_first.hits + 1);
+    assert_eq!(after_second.misses, after_first.misses);
+    match result {
+        ReefDBResult::Select(rows) => assert_eq!(rows.len(), 1),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+
+    // Changing the schema evicts plans that read the table
+    db.query("ALTER TABLE users ADD COLUMN age INTEGER")?;
+    assert_eq!(db.plan_cache_stats().entries, 0);
+    let hits = db.plan_cache_stats().hits;
+    db.query("SELECT * FROM users WHERE id = 1")?;
+    assert_eq!(db.plan_cache_stats().hits, hits);
+
+    db.query("DROP TABLE users")?;
+    assert!(db.query("SELECT * FROM users WHERE id = 1").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_decimal_sums_without_float_error() -> Result<()> {
+    use crate::sql::decimal::Decimal;
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, amount DECIMAL(10,2))")?;
+    for id in 1..=10 {
+        db.query(&format!("INSERT INTO orders VALUES ({}, 0.1)", id))?;
+    }
+    // Rounded to the column's scale on the way in
+    db.query("INSERT INTO orders VALUES (11, 2.345)")?;
+    db.query("INSERT INTO orders VALUES (12, 5)")?;
+    assert!(db.query("INSERT INTO orders VALUES (13, 123456789.12)").is_err());
+
+    let dec = |s: &str| DataValue::Decimal(Decimal::parse(s).unwrap());
+
+    assert_eq!(select_rows(db.query("SELECT SUM(amount) FROM orders")?), vec![vec![dec("8.35")]]);
+    assert_eq!(select_rows(db.query("SELECT SUM(amount) FROM orders WHERE id <= 10")?), vec![vec![dec("1.00")]]);
+    assert_ne!((0..10).map(|_| 0.1f64).sum::<f64>(), 1.0);
+
+    // Literals with more digits than a float holds are stored as written
+    db.query("CREATE TABLE ledger (id INTEGER PRIMARY KEY, amount DECIMAL(38,18))")?;
+    db.query("INSERT INTO ledger VALUES (1, 12345678901234567.123456789012345678)")?;
+    db.query("INSERT INTO ledger VALUES (2, 0.000000000000000001)")?;
+    db.query("INSERT INTO ledger VALUES (3, 9876543210987654321)")?;
+    assert_eq!(select_rows(db.query("SELECT amount FROM ledger WHERE id = 1")?), vec![
+        vec![dec("12345678901234567.123456789012345678")],
+    ]);
+    assert_eq!(select_rows(db.query("SELECT SUM(amount) FROM ledger WHERE id <= 2")?), vec![
+        vec![dec("12345678901234567.123456789012345679")],
+    ]);
+    assert_eq!(select_rows(db.query("SELECT amount FROM ledger WHERE id = 3")?), vec![vec![dec("9876543210987654321")]]);
+    assert!(db.query("INSERT INTO ledger VALUES (4, 198765432109876543210)").is_err());
+
+    // Comparisons against numeric literals and exact arithmetic
+    assert_eq!(select_rows(db.query("SELECT id, amount FROM orders WHERE amount = 2.35")?), vec![
+        vec![DataValue::Integer(11), dec("2.35")],
+    ]);
+    assert_eq!(select_rows(db.query("SELECT id FROM orders WHERE amount > 2")?).len(), 2);
+    assert_eq!(select_rows(db.query("SELECT multiply(amount, 3) FROM orders WHERE id = 11")?), vec![
+        vec![dec("7.05")],
+    ]);
+
+    db.query("UPDATE orders SET amount = 1.005 WHERE id = 1")?;
+    assert_eq!(select_rows(db.query("SELECT amount FROM orders WHERE id = 1")?), vec![vec![dec("1.01")]]);
+    Ok(())
+}
+
+#[test]
+fn test_json_extract_in_projection_and_where() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE events (id INTEGER PRIMARY KEY, payload JSON)")?;
+    db.query(r#"INSERT INTO events VALUES (1, '{"user": {"name": "ann", "age": 31}, "tags": ["x", "y"]}')"#)?;
+    db.query(r#"INSERT INTO events VALUES (2, '{"user": {"name": "bo", "age": 17}, "tags": []}')"#)?;
+    assert!(db.query("INSERT INTO events VALUES (3, '{not json')").is_err());
+
+    let text = |s: &str| DataValue::Text(s.into());
+
+    assert_eq!(select_rows(db.query(
+        "SELECT id, json_extract(payload, '$.user.name'), json_extract(payload, '$.tags[1]') FROM events"
+    )?), vec![
+        vec![DataValue::Integer(1), text("ann"), text("y")],
+        vec![DataValue::Integer(2), text("bo"), DataValue::Null],
+    ]);
+
+    assert_eq!(
+        select_rows(db.query("SELECT id FROM events WHERE json_extract(payload, '$.user.age') > 18")?),
+        vec![vec![DataValue::Integer(1)]]
+    );
+
+    // Path segments named like columns are not column references; bad paths are NULL
+    assert_eq!(select_rows(db.query("SELECT json_extract(payload, '$.id'), json_extract(payload, 'user') FROM events WHERE id = 1")?), vec![
+        vec![DataValue::Null, DataValue::Null],
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_array_column_containment() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE posts (id INTEGER PRIMARY KEY, tags TEXT[])")?;
+    db.query("INSERT INTO posts VALUES (1, ['rust', 'db'])")?;
+    db.query("INSERT INTO posts VALUES (2, ['go'])")?;
+    db.query("INSERT INTO posts VALUES (3, [])")?;
+    // Elements must match the declared element type
+    assert!(db.query("INSERT INTO posts VALUES (4, [1, 2])").is_err());
+
+    let text = |s: &str| DataValue::Text(s.into());
+
+    assert_eq!(select_ids(db.query("SELECT tags FROM posts WHERE id = 1")?), vec![
+        DataValue::Array(vec![text("rust"), text("db")]),
+    ]);
+    assert_eq!(select_ids(db.query("SELECT id FROM posts WHERE tags @> ['rust']")?), vec![DataValue::Integer(1)]);
+    assert_eq!(select_ids(db.query("SELECT id FROM posts WHERE tags @> ['db', 'rust']")?), vec![DataValue::Integer(1)]);
+    assert!(select_ids(db.query("SELECT id FROM posts WHERE tags @> ['rust', 'go']")?).is_empty());
+    assert_eq!(select_ids(db.query("SELECT id FROM posts WHERE tags @> []")?).len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_enum_values_validated_and_sorted_by_declaration() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE tickets (id INTEGER PRIMARY KEY, status ENUM('open', 'in_progress', 'closed'))")?;
+    db.query("INSERT INTO tickets VALUES (1, 'closed')")?;
+    db.query("INSERT INTO tickets VALUES (2, 'open')")?;
+    db.query("INSERT INTO tickets VALUES (3, 'in_progress')")?;
+    assert_eq!(
+        db.query("INSERT INTO tickets VALUES (4, 'archived')"),
+        Err(ReefDBError::InvalidEnumValue("status".to_string(), "archived".to_string()))
+    );
+    assert_eq!(
+        db.query("UPDATE tickets SET status = 'reopened' WHERE id = 1"),
+        Err(ReefDBError::InvalidEnumValue("status".to_string(), "reopened".to_string()))
+    );
+
+    assert_eq!(select_ids(db.query("SELECT * FROM tickets ORDER BY status")?), vec![
+        DataValue::Integer(2), DataValue::Integer(3), DataValue::Integer(1),
+    ]);
+    assert_eq!(select_ids(db.query("SELECT * FROM tickets ORDER BY status DESC")?), vec![
+        DataValue::Integer(1), DataValue::Integer(3), DataValue::Integer(2),
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_nocase_collation_matches_and_sorts_ignoring_case() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE members (id INTEGER PRIMARY KEY, name TEXT COLLATE NOCASE UNIQUE, nick TEXT)")?;
+    db.query("INSERT INTO members VALUES (1, 'Alice', 'Al')")?;
+    db.query("INSERT INTO members VALUES (2, 'bob', 'Bobby')")?;
+    db.query("INSERT INTO members VALUES (3, 'Carol', 'Caz')")?;
+    // Uniqueness ignores case too
+    assert!(db.query("INSERT INTO members VALUES (4, 'ALICE', 'x')").is_err());
+
+    assert_eq!(select_ids(db.query("SELECT id FROM members WHERE name = 'aLiCe'")?), vec![DataValue::Integer(1)]);
+    // Columns without COLLATE stay case-sensitive
+    assert!(select_ids(db.query("SELECT id FROM members WHERE nick = 'al'")?).is_empty());
+
+    // Binary order would put 'bob' after 'Carol'
+    assert_eq!(select_ids(db.query("SELECT * FROM members ORDER BY name")?), vec![
+        DataValue::Integer(1), DataValue::Integer(2), DataValue::Integer(3),
+    ]);
+
+    db.query("CREATE TABLE pets (owner TEXT, pet TEXT)")?;
+    db.query("INSERT INTO pets VALUES ('BOB', 'cat')")?;
+    let joined = match db.query("SELECT members.id, pets.pet FROM members INNER JOIN pets ON members.name = pets.owner")? {
+        ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    assert_eq!(joined, vec![vec![DataValue::Integer(2), DataValue::Text("cat".into())]]);
+    Ok(())
+}
+
+#[test]
+fn test_distinct_on_keeps_latest_order_per_user() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, created INTEGER)")?;
+    for (id, user, created) in [(1, 1, 10), (2, 2, 15), (3, 1, 30), (4, 2, 5), (5, 1, 20), (6, 3, 1)] {
+        db.query(&format!("INSERT INTO orders VALUES ({}, {}, {})", id, user, created))?;
+    }
+
+    let rows = match db.query("SELECT DISTINCT ON (user_id) * FROM orders ORDER BY user_id, created DESC")? {
+        ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    let int = DataValue::Integer;
+    assert_eq!(rows, vec![
+        vec![int(3), int(1), int(30)],
+        vec![int(2), int(2), int(15)],
+        vec![int(6), int(3), int(1)],
+    ]);
+
+    assert!(matches!(
+        db.query("SELECT DISTINCT ON (created) id FROM orders"),
+        Err(ReefDBError::ColumnNotFound(column)) if column == "created"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_aggregates_over_empty_input_return_one_row() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")?;
+    db.query("INSERT INTO users VALUES (1, 'alice', 30)")?;
+    db.query("INSERT INTO users VALUES (2, 'bob', NULL)")?;
+    db.query("INSERT INTO users VALUES (3, 'carol', 20)")?;
+
+
+    for (aggregate, empty) in [
+        ("COUNT(*)", DataValue::Integer(0)),
+        ("COUNT(age)", DataValue::Integer(0)),
+        ("SUM(age)", DataValue::Null),
+        ("AVG(age)", DataValue::Null),
+        ("MIN(age)", DataValue::Null),
+        ("MAX(age)", DataValue::Null),
+    ] {
+        let result = select_rows(db.query(&format!("SELECT {} FROM users WHERE age > 100", aggregate))?);
+        assert_eq!(result, vec![vec![empty]], "{} over no rows", aggregate);
+    }
+
+    let all = select_rows(db.query("SELECT COUNT(*), COUNT(age), SUM(age), AVG(age), MIN(age), MAX(age) AS oldest FROM users")?);
+    assert_eq!(all, vec![vec![
+        DataValue::Integer(3),
+        DataValue::Integer(2),
+        DataValue::Integer(50),
+        DataValue::Float(25.0),
+        DataValue::Integer(20),
+        DataValue::Integer(30),
+    ]]);
+
+    assert!(db.query("SELECT name, COUNT(*) FROM users").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_default_order_is_primary_key_on_every_backend() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("default_order.db").to_str().unwrap().to_string();
+    let mut memory = InMemoryReefDB::create_in_memory()?;
+    let mut disk = crate::OnDiskReefDB::create_on_disk(path.clone(), path + ".idx")?;
+
+    let expected: Vec<DataValue> = (1..=5).map(DataValue::Integer).collect();
+    type Query<'a> = &'a mut dyn FnMut(&str) -> Result<ReefDBResult>;
+    let backends: [(&str, Query); 2] = [
+        ("memory", &mut |sql: &str| memory.query(sql)),
+        ("disk", &mut |sql: &str| disk.query(sql)),
+    ];
+    for (name, query) in backends {
+        query("CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT)")?;
+        for id in [4, 1, 5, 3, 2] {
+            query(&format!("INSERT INTO items VALUES ({}, 'item{}')", id, id))?;
+        }
+        assert_eq!(select_ids(query("SELECT * FROM items")?), expected, "{} backend", name);
+        assert_eq!(select_ids(query("SELECT id FROM items WHERE id > 2")?), expected[2..].to_vec(), "{} backend", name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rows_expire_after_table_ttl() -> Result<()> {
+    let clock = crate::clock::MockClock::default();
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.set_clock(std::sync::Arc::new(clock.clone()));
+    db.query("CREATE TABLE cache (key TEXT PRIMARY KEY, value TEXT) WITH (TTL = 60)")?;
+    db.query("INSERT INTO cache VALUES ('a', 'first')")?;
+    clock.advance(std::time::Duration::from_secs(30));
+    db.query("INSERT INTO cache VALUES ('b', 'second')")?;
+
+    let text = |s: &str| DataValue::Text(s.into());
+    assert_eq!(select_ids(db.query("SELECT key FROM cache")?), vec![text("a"), text("b")]);
+
+    // 'a' reaches its TTL, 'b' is only 30 seconds old
+    clock.advance(std::time::Duration::from_secs(30));
+    assert_eq!(select_ids(db.query("SELECT key FROM cache")?), vec![text("b")]);
+    assert_eq!(select_ids(db.query("SELECT key FROM cache WHERE key = 'a'")?), Vec::<DataValue>::new());
+
+    clock.advance(std::time::Duration::from_secs(30));
+    assert!(select_ids(db.query("SELECT * FROM cache")?).is_empty());
+
+    // Rows carry their insertion time in the column the TTL adds
+    db.query("INSERT INTO cache VALUES ('c', 'third')")?;
+    match db.query("SELECT * FROM cache")? {
+        ReefDBResult::Select(result) => assert_eq!(result.rows[0].1, vec![
+            text("c"),
+            text("third"),
+            DataValue::Timestamp("1970-01-01 00:01:30".to_string()),
+        ]),
+        other => panic!("Expected Select result, got {:?}", other),
+    }
+    assert!(db.query("CREATE TABLE logs (line TEXT, inserted_at TIMESTAMP) WITH (TTL = 5)").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_order_by_unknown_column_is_an_error() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'alice')")?;
+
+    assert_eq!(
+        db.query("SELECT * FROM users ORDER BY nickname"),
+        Err(ReefDBError::ColumnNotFound("nickname".to_string()))
+    );
+    // Checked even when there are no rows to sort
+    db.query("DELETE FROM users")?;
+    assert_eq!(
+        db.query("SELECT * FROM users ORDER BY name, nickname DESC"),
+        Err(ReefDBError::ColumnNotFound("nickname".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_group_by_ordered_by_count_descending() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, country TEXT, total INTEGER)")?;
+    for (id, country, total) in [(1, "fr", 10), (2, "de", 20), (3, "fr", 5), (4, "us", 7), (5, "fr", 1), (6, "de", 3)] {
+        db.query(&format!("INSERT INTO orders VALUES ({}, '{}', {})", id, country, total))?;
+    }
+    let group = |country: &str, count: i64| vec![DataValue::Text(country.into()), DataValue::Integer(count)];
+
+    let expected = vec![group("fr", 3), group("de", 2), group("us", 1)];
+    assert_eq!(select_rows(db.query("SELECT country, COUNT(*) FROM orders GROUP BY country ORDER BY COUNT(*) DESC")?), expected);
+    assert_eq!(select_rows(db.query("SELECT country, COUNT(*) AS n FROM orders GROUP BY country ORDER BY n DESC")?), expected);
+    assert_eq!(select_rows(db.query("SELECT country, COUNT(*) AS n FROM orders GROUP BY country ORDER BY COUNT(*) DESC")?), expected);
+
+    // Without ORDER BY groups come in key order; WHERE filters before grouping
+    assert_eq!(
+        select_rows(db.query("SELECT country, SUM(total) FROM orders WHERE total > 4 GROUP BY country")?),
+        vec![group("de", 20), group("fr", 15), group("us", 7)]
+    );
+
+    assert!(db.query("SELECT id, COUNT(*) FROM orders GROUP BY country").is_err());
+    assert_eq!(
+        db.query("SELECT country, COUNT(*) FROM orders GROUP BY country ORDER BY MAX(total)"),
+        Err(ReefDBError::ColumnNotFound("max".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_group_by_rollup_adds_subtotals() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE sales (id INTEGER PRIMARY KEY, region TEXT, product TEXT, amount INTEGER)")?;
+    for (id, region, product, amount) in [(1, "east", "pen", 3), (2, "east", "ink", 4), (3, "east", "pen", 5), (4, "west", "pen", 7)] {
+        db.query(&format!("INSERT INTO sales VALUES ({}, '{}', '{}', {})", id, region, product, amount))?;
+    }
+    let text = |s: &str| DataValue::Text(s.into());
+
+    assert_eq!(
+        select_rows(db.query("SELECT region, product, SUM(amount) FROM sales GROUP BY ROLLUP (region, product)")?),
+        vec![
+            vec![text("east"), text("ink"), DataValue::Integer(4)],
+            vec![text("east"), text("pen"), DataValue::Integer(8)],
+            vec![text("west"), text("pen"), DataValue::Integer(7)],
+            vec![text("east"), DataValue::Null, DataValue::Integer(12)],
+            vec![text("west"), DataValue::Null, DataValue::Integer(7)],
+            vec![DataValue::Null, DataValue::Null, DataValue::Integer(19)],
+        ]
+    );
+    assert_eq!(
+        select_rows(db.query("SELECT region, product, COUNT(*) FROM sales GROUP BY GROUPING SETS ((region), product, ())")?),
+        vec![
+            vec![text("east"), DataValue::Null, DataValue::Integer(3)],
+            vec![text("west"), DataValue::Null, DataValue::Integer(1)],
+            vec![DataValue::Null, text("ink"), DataValue::Integer(1)],
+            vec![DataValue::Null, text("pen"), DataValue::Integer(3)],
+            vec![DataValue::Null, DataValue::Null, DataValue::Integer(4)],
+        ]
+    );
+
+    // The grand total is still produced when no row matches
+    assert_eq!(
+        select_rows(db.query("SELECT region, COUNT(*) FROM sales WHERE amount > 100 GROUP BY ROLLUP (region)")?),
+        vec![vec![DataValue::Null, DataValue::Integer(0)]]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_map_rows_into_struct() -> Result<()> {
+    use crate::row::{FromRow, Row};
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+        email: Option<String>,
+    }
+
+    impl FromRow for User {
+        fn from_row(row: &Row<'_>) -> Result<Self> {
+            Ok(User {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                email: row.get("email")?,
+            })
+        }
+    }
+
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT)")?;
+    db.query("INSERT INTO users VALUES (1, 'alice', 'alice@example.com')")?;
+    db.query("INSERT INTO users VALUES (2, 'bob', NULL)")?;
+
+    let ReefDBResult::Select(result) = db.query("SELECT email, name, id FROM users")? else {
+        panic!("Expected Select result");
+    };
+    assert_eq!(result.map_rows::<User>()?, vec![
+        User { id: 1, name: "alice".to_string(), email: Some("alice@example.com".to_string()) },
+        User { id: 2, name: "bob".to_string(), email: None },
+    ]);
+
+    // A field whose column the query didn't select
+    let ReefDBResult::Select(result) = db.query("SELECT id, name FROM users")? else {
+        panic!("Expected Select result");
+    };
+    assert_eq!(result.map_rows::<User>(), Err(ReefDBError::ColumnNotFound("email".to_string())));
+    Ok(())
+}
+
+#[test]
+fn test_fetch_first_with_ties_keeps_rows_tied_at_the_boundary() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE scores (id INTEGER PRIMARY KEY, player TEXT, points INTEGER)")?;
+    for (id, player, points) in [(1, "ann", 90), (2, "bob", 80), (3, "cid", 70), (4, "dee", 70), (5, "eve", 70), (6, "fay", 60)] {
+        db.query(&format!("INSERT INTO scores VALUES ({}, '{}', {})", id, player, points))?;
+    }
+    let row = |id: i64| vec![DataValue::Integer(id)];
+
+    // The third row scores 70 like the two after it
+    assert_eq!(select_rows(db.query("SELECT id FROM scores ORDER BY points DESC, id LIMIT 3")?), vec![row(1), row(2), row(3)]);
+    assert_eq!(
+        select_rows(db.query("SELECT id FROM scores ORDER BY points DESC, id FETCH FIRST 3 ROWS ONLY")?),
+        vec![row(1), row(2), row(3)]
+    );
+    // The ORDER BY key isn't selected, yet only the selected column comes back
+    let mut tied = select_rows(db.query("SELECT id FROM scores ORDER BY points DESC FETCH FIRST 3 ROWS WITH TIES")?);
+    tied[2..].sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(tied, vec![row(1), row(2), row(3), row(4), row(5)]);
+    // Without a tie at the boundary WITH TIES returns exactly the limit
+    assert_eq!(
+        select_rows(db.query("SELECT id, points FROM scores ORDER BY points DESC FETCH FIRST 2 ROWS WITH TIES")?),
+        vec![
+            vec![DataValue::Integer(1), DataValue::Integer(90)],
+            vec![DataValue::Integer(2), DataValue::Integer(80)],
+        ]
+    );
+    // Every ORDER BY key has to tie, not just the first
+    assert_eq!(
+        select_rows(db.query("SELECT id FROM scores ORDER BY points DESC, id FETCH FIRST 3 ROWS WITH TIES")?),
+        vec![row(1), row(2), row(3)]
+    );
+
+    assert_eq!(
+        db.query("SELECT id FROM scores FETCH FIRST 3 ROWS WITH TIES"),
+        Err(ReefDBError::Other("WITH TIES needs an ORDER BY".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_interned_text_reads_like_plain_text() -> Result<()> {
+    let queries = [
+        "SELECT id FROM orders WHERE status = 'paid'",
+        "SELECT status, id FROM orders ORDER BY status DESC, id",
+        "SELECT * FROM orders WHERE status LIKE 'p%'",
+    ];
+    let mut results = Vec::new();
+    for mut db in [InMemoryReefDB::create_in_memory()?, InMemoryReefDB::create_in_memory_with_interned_text()?] {
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, status TEXT)")?;
+        for (id, status) in ["pending", "paid", "shipped", "paid", "pending", "paid"].iter().enumerate() {
+            db.query(&format!("INSERT INTO orders VALUES ({}, '{}')", id, status))?;
+        }
+        db.query("UPDATE orders SET status = 'shipped' WHERE id = 0")?;
+        results.push(queries.iter().map(|sql| db.query(sql)).collect::<Result<Vec<_>>>()?);
+    }
+    assert_eq!(results[0], results[1]);
+    Ok(())
+}
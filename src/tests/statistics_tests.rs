@@ -0,0 +1,55 @@
+use super::*;
+use crate::error::ReefDBError;
+use crate::result::ReefDBResult;
+use crate::statistics::DEFAULT_HISTOGRAM_BUCKETS;
+use crate::InMemoryReefDB;
+
+#[test]
+fn test_parse_analyze() {
+    let (remaining, stmt) = Statement::parse("ANALYZE measurements").unwrap();
+    assert_eq!(remaining, "");
+    assert_eq!(stmt, Statement::Analyze("measurements".to_string()));
+}
+
+#[test]
+fn test_analyze_builds_histograms() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE measurements (id INTEGER PRIMARY KEY, reading FLOAT, label TEXT)")?;
+    // ids 0..=99 spread evenly; readings cluster in the low range
+    for id in 0..100 {
+        let reading = if id < 80 { 1.0 } else { 10.0 };
+        db.query(&format!("INSERT INTO measurements VALUES ({}, {:.1}, 'm{}')", id, reading, id))?;
+    }
+
+    assert!(db.table_statistics("measurements").is_none());
+    assert_eq!(db.query("ANALYZE measurements")?, ReefDBResult::Analyze);
+
+    let stats = db.table_statistics("measurements").expect("statistics after ANALYZE");
+    assert_eq!(stats.row_count, 100);
+    assert!(!stats.histograms.contains_key("label"));
+
+    let ids = &stats.histograms["id"];
+    assert_eq!((ids.min, ids.max), (0.0, 99.0));
+    assert_eq!(ids.buckets, vec![10; DEFAULT_HISTOGRAM_BUCKETS]);
+
+    let readings = &stats.histograms["reading"];
+    assert_eq!((readings.min, readings.max), (1.0, 10.0));
+    assert_eq!(readings.buckets.first(), Some(&80));
+    assert_eq!(readings.buckets.last(), Some(&20));
+    assert_eq!(readings.total(), 100);
+
+    let below_half = db.estimate_selectivity("measurements", "id", &Op::LessThan, &DataValue::Integer(50)).unwrap();
+    assert!((below_half - 0.5).abs() < 0.02);
+    let high = db.estimate_selectivity("measurements", "reading", &Op::GreaterThan, &DataValue::Float(5.0)).unwrap();
+    assert!((high - 0.2).abs() < 0.01);
+    assert!(db.estimate_selectivity("measurements", "label", &Op::Equal, &DataValue::Text("m1".into())).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_analyze_missing_table() -> Result<(), ReefDBError> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    assert!(matches!(db.query("ANALYZE nope"), Err(ReefDBError::TableNotFound(_))));
+    Ok(())
+}
@@ -31,7 +31,7 @@ fn test_update_basic() -> Result<()> {
     if let ReefDBResult::Select(rows) = db.query("SELECT * FROM users WHERE id = 1")? {
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0][0], DataValue::Integer(1));
-        assert_eq!(rows[0][1], DataValue::Text("Alice Updated".to_string()));
+        assert_eq!(rows[0][1], DataValue::Text("Alice Updated".into()));
     } else {
         panic!("Expected Select result");
     }
@@ -53,7 +53,7 @@ fn test_update_multiple_rows() -> Result<()> {
     let where_clause = WhereType::Regular(WhereClause::new(
         "status".to_string(),
         Op::Equal,
-        DataValue::Text("active".to_string()),
+        DataValue::Text("active".into()),
         None,
     ));
 
@@ -63,7 +63,7 @@ fn test_update_multiple_rows() -> Result<()> {
     if let ReefDBResult::Select(rows) = db.query("SELECT * FROM users")? {
         assert_eq!(rows.len(), 3);
         for row in rows.rows {
-            assert_eq!(row.1[2], DataValue::Text("inactive".to_string()));
+            assert_eq!(row.1[2], DataValue::Text("inactive".into()));
         }
     } else {
         panic!("Expected Select result");
@@ -87,7 +87,7 @@ fn parse_update_with_where_test() {
             "",
             Statement::Update(UpdateStatement::UpdateTable(
                 "users".to_string(),
-                vec![("name".to_string(), DataValue::Text("John".to_string()))],
+                vec![("name".to_string(), DataValue::Text("John".into()))],
                 Some(where_clause),
             ))
         ))
@@ -102,7 +102,7 @@ fn parse_update_multiple_columns_test() {
     let where_clause = WhereType::Regular(WhereClause::new(
         "status".to_string(),
         Op::Equal,
-        DataValue::Text("active".to_string()),
+        DataValue::Text("active".into()),
         None,
     ));
     assert_eq!(
@@ -112,12 +112,63 @@ fn parse_update_multiple_columns_test() {
             Statement::Update(UpdateStatement::UpdateTable(
                 "users".to_string(),
                 vec![
-                    ("name".to_string(), DataValue::Text("John".to_string())),
+                    ("name".to_string(), DataValue::Text("John".into())),
                     ("age".to_string(), DataValue::Integer(30)),
-                    ("status".to_string(), DataValue::Text("active".to_string())),
+                    ("status".to_string(), DataValue::Text("active".into())),
                 ],
                 Some(where_clause),
             ))
         ))
     );
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_deferred_unique_swap() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE seats (id INTEGER PRIMARY KEY, code TEXT UNIQUE DEFERRABLE INITIALLY DEFERRED)")?;
+    db.query("INSERT INTO seats VALUES (1, 'a')")?;
+    db.query("INSERT INTO seats VALUES (2, 'b')")?;
+
+    // Halfway through the swap both rows hold 'b'; only the committed state is checked
+    db.query("BEGIN TRANSACTION")?;
+    db.query("UPDATE seats SET code = 'b' WHERE id = 1")?;
+    db.query("UPDATE seats SET code = 'a' WHERE id = 2")?;
+    assert_eq!(db.query("COMMIT")?, ReefDBResult::Commit);
+    if let ReefDBResult::Select(rows) = db.query("SELECT code FROM seats WHERE id = 1")? {
+        assert_eq!(rows[0][0], DataValue::Text("b".into()));
+    } else {
+        panic!("Expected Select result");
+    }
+
+    // Still violated at COMMIT: the commit fails and the transaction stays open
+    db.query("BEGIN TRANSACTION")?;
+    db.query("UPDATE seats SET code = 'a' WHERE id = 1")?;
+    assert!(matches!(db.query("COMMIT"), Err(ReefDBError::DuplicateKey(_))));
+    db.query("UPDATE seats SET code = 'b' WHERE id = 2")?;
+    assert_eq!(db.query("COMMIT")?, ReefDBResult::Commit);
+
+    // Outside a transaction a deferred column is checked per statement
+    assert!(matches!(db.query("INSERT INTO seats VALUES (3, 'a')"), Err(ReefDBError::DuplicateKey(_))));
+    Ok(())
+}
+
+#[test]
+fn test_update_rejects_duplicate_unique_value() -> Result<()> {
+    let mut db = InMemoryReefDB::create_in_memory()?;
+    db.query("CREATE TABLE seats (id INTEGER PRIMARY KEY, code TEXT UNIQUE)")?;
+    db.query("INSERT INTO seats VALUES (1, 'a')")?;
+    db.query("INSERT INTO seats VALUES (2, 'b')")?;
+
+    db.query("BEGIN TRANSACTION")?;
+    assert!(matches!(
+        db.query("UPDATE seats SET code = 'b' WHERE id = 1"),
+        Err(ReefDBError::DuplicateKey(_))
+    ));
+    db.query("COMMIT")?;
+    if let ReefDBResult::Select(rows) = db.query("SELECT code FROM seats WHERE id = 1")? {
+        assert_eq!(rows[0][0], DataValue::Text("a".into()));
+    } else {
+        panic!("Expected Select result");
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::error::ReefDBError;
+
+/// Flag shared with other threads to stop a transaction's running statement. Scan loops
+/// poll it and fail with `ReefDBError::Cancelled` once it is set. It stays set, so later
+/// statements of the transaction fail too until `reset` is called.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<(), ReefDBError> {
+        if self.is_cancelled() {
+            return Err(ReefDBError::Cancelled);
+        }
+        Ok(())
+    }
+}
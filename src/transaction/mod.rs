@@ -1,6 +1,8 @@
+mod cancellation;
 mod savepoint_handler;
 mod state_handler;
 
+pub use cancellation::CancellationToken;
 pub use savepoint_handler::SavepointHandler;
 pub use state_handler::{TransactionState, TransactionStateHandler, IsolationLevel};
 use crate::fts::search::Search;
@@ -14,20 +16,65 @@ use crate::{
         statements::{
             Statement,
             select::SelectStatement,
-            create::CreateStatement,
             insert::InsertStatement,
             update::UpdateStatement,
             delete::DeleteStatement,
             alter::AlterStatement,
             drop::DropStatement,
+            create::CreateStatement,
         },
+        data_value::DataValue,
     },
    
     ReefDB,
     acid::AcidManager,
     TableStorage,
-    savepoint::{Savepoint, SavepointState},
+    wal::WALOperation,
 };
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Rows one statement wrote to `table_name`: the new values for inserts and updates, the
+/// removed ones for deletes. A transaction keeps them to replay on the shared database when
+/// it commits.
+#[derive(Clone, Debug)]
+pub(crate) struct RowChange {
+    pub operation: WALOperation,
+    pub table_name: String,
+    pub rows: Vec<Vec<DataValue>>,
+}
+
+/// A committing transaction's row changes to one table, resolved to positions in the shared
+/// table: rows rewritten in place, then rows removed, then rows appended. Appended rows the
+/// transaction deleted again are `None`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RowDelta {
+    pub rewrites: BTreeMap<usize, Vec<DataValue>>,
+    pub deletes: BTreeSet<usize>,
+    pub appends: Vec<Option<Vec<DataValue>>>,
+}
+
+impl RowDelta {
+    pub fn appended(&self) -> impl Iterator<Item = &Vec<DataValue>> {
+        self.appends.iter().flatten()
+    }
+
+    pub fn apply(&self, rows: &mut Vec<Vec<DataValue>>) {
+        for (position, row) in &self.rewrites {
+            rows[*position] = row.clone();
+        }
+        if !self.deletes.is_empty() {
+            let mut position = 0;
+            rows.retain(|_| {
+                position += 1;
+                !self.deletes.contains(&(position - 1))
+            });
+        }
+        rows.extend(self.appended().cloned());
+    }
+}
+
+/// Row deltas of a committing transaction by table
+pub(crate) type RowDeltas = HashMap<String, RowDelta>;
 
 #[derive(Clone)]
 pub struct Transaction<S: Storage + IndexManager + Clone, FTS: Search + Clone>
@@ -38,6 +85,13 @@ where
     pub(crate) savepoint_handler: SavepointHandler,
     pub(crate) reef_db: ReefDB<S, FTS>,
     pub(crate) acid_manager: AcidManager,
+    pub(crate) cancellation: CancellationToken,
+    /// Set once the transaction runs anything that may change data
+    pub(crate) dirty: bool,
+    /// Row writes to publish at commit, oldest first
+    pub(crate) changes: Vec<RowChange>,
+    /// Tables created, dropped or altered, published whole at commit
+    pub(crate) replaced_tables: HashSet<String>,
 }
 
 impl<S: Storage + IndexManager + Clone, FTS: Search + Clone> Transaction<S, FTS>
@@ -50,11 +104,20 @@ where
         let savepoint_handler = SavepointHandler::new();
         let acid_manager = AcidManager::new(reef_db.tables.clone(), isolation_level);
 
+        // Deferred columns queue their checks on the transaction's own copy until it commits,
+        // and the rows its statements write are recorded for the transaction manager to log
+        let mut own = reef_db.clone();
+        own.pending_constraints = Some(Vec::new());
+        own.written_rows = Some(Vec::new());
         let mut transaction = Transaction {
             state_handler,
             savepoint_handler,
-            reef_db: reef_db.clone(),
+            reef_db: own,
             acid_manager,
+            cancellation: CancellationToken::new(),
+            dirty: false,
+            changes: Vec::new(),
+            replaced_tables: HashSet::new(),
         };
 
         // Take initial snapshot
@@ -84,6 +147,7 @@ where
                 storage_state.tables.insert(table_name.clone(), (columns.clone(), rows.clone()));
             }
         }
+        storage_state.copy_temporary_from(&self.reef_db.tables);
         
         self.savepoint_handler.create_savepoint(name, storage_state)
     }
@@ -99,13 +163,8 @@ where
         self.reef_db.tables = TableStorage::new();
         self.reef_db.tables.restore_from(&snapshot);
         
-        // Clear and restore storage state
-        self.reef_db.storage.clear();
-        for (table_name, (columns, rows)) in snapshot.tables.iter() {
-            if !rows.is_empty() {
-                self.reef_db.storage.insert_table(table_name.clone(), columns.clone(), rows.clone());
-            }
-        }
+        // Restore storage state, temporary tables back in the in-memory layer
+        self.reef_db.storage.restore_from(&snapshot);
         
         // Update the ACID manager's snapshot
         let mut current_state = self.acid_manager.get_committed_snapshot();
@@ -124,51 +183,43 @@ where
     }
 
     pub fn commit(&mut self, reef_db: &mut ReefDB<S, FTS>) -> Result<(), ReefDBError> {
+        let deltas = self.prepare_commit(reef_db)?;
+        self.publish(reef_db, deltas)
+    }
+
+    /// Checks the transaction's row changes against the shared database `reef_db`, on top
+    /// of whatever other transactions committed since it began, and resolves them to the
+    /// row deltas `publish` applies. Nothing is written to `reef_db`. The foreign keys of
+    /// deferred columns are checked here too, on the transaction's own rows.
+    pub(crate) fn prepare_commit(&mut self, reef_db: &ReefDB<S, FTS>) -> Result<RowDeltas, ReefDBError> {
         if *self.state_handler.get_state() != TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
         }
 
-        // For serializable isolation, we need to ensure we're working with the correct snapshot
-        if self.state_handler.get_isolation_level() == IsolationLevel::Serializable {
-            // First commit changes to ACID manager
-            self.acid_manager.commit()?;
-
-            // Get our snapshot from the start of the transaction
-            let snapshot = self.acid_manager.get_committed_snapshot();
-            
-            // Apply our changes to the snapshot
-            let mut final_state = snapshot.clone();
-            final_state.restore_from(&self.reef_db.tables);
-
-            // Update only the storage with our changes, not the in-memory tables
-            reef_db.storage.restore_from(&final_state);
-        } else {
-            // For other isolation levels, commit normally
-            self.acid_manager.commit()?;
-
-            // Get the final state from ACID manager
-            let final_state = self.acid_manager.get_committed_snapshot();
-
-            // Update both storage and tables
-            reef_db.tables.restore_from(&final_state);
-            reef_db.storage.restore_from(&final_state);
-        }
-
-        // Update transaction state
-        self.state_handler.commit()?;
+        self.reef_db.check_pending_references()?;
+        let deltas = reef_db.stage_transaction(&self.replaced_tables, &self.changes)?;
+        self.acid_manager.commit()?;
+        Ok(deltas)
+    }
 
-        Ok(())
+    /// Applies the row deltas `prepare_commit` resolved against `reef_db` and ends the
+    /// transaction as committed
+    pub(crate) fn publish(&mut self, reef_db: &mut ReefDB<S, FTS>, deltas: RowDeltas) -> Result<(), ReefDBError> {
+        reef_db.publish_transaction(&self.reef_db, &self.replaced_tables, deltas)?;
+        self.state_handler.commit()
     }
 
-    pub fn rollback(&mut self, reef_db: &mut ReefDB<S, FTS>) -> Result<(), ReefDBError> {
+    pub fn rollback(&mut self) -> Result<(), ReefDBError> {
         if *self.state_handler.get_state() != TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
         }
 
-        // Rollback to the initial snapshot
+        // Nothing was published, so only the transaction's own copy goes back to the
+        // initial snapshot
         let snapshot = self.acid_manager.rollback_atomic();
-        reef_db.tables.restore_from(&snapshot);
         self.reef_db.tables.restore_from(&snapshot);
+        self.changes.clear();
+        self.replaced_tables.clear();
         
         self.state_handler.rollback()?;
         Ok(())
@@ -190,32 +241,88 @@ where
         self.state_handler.get_start_timestamp()
     }
 
+    /// Whether the transaction has run a statement that may have changed data
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn copy_into(&mut self, table_name: &str, rows: Vec<Vec<DataValue>>) -> Result<usize, ReefDBError> {
+        if *self.state_handler.get_state() != TransactionState::Active {
+            return Err(ReefDBError::TransactionNotActive);
+        }
+        self.dirty = true;
+        self.reef_db.copy_into(table_name, rows)
+    }
+
     pub fn execute_statement(&mut self, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
         if *self.state_handler.get_state() != TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
         }
+        if !matches!(
+            stmt,
+            Statement::Select(_)
+                | Statement::BeginTransaction
+                | Statement::Commit
+                | Statement::ShowTables
+                | Statement::ShowLocks
+                | Statement::Describe(_)
+        ) {
+            self.dirty = true;
+        }
         
-        match stmt {
-            Statement::Create(CreateStatement::Table(name, columns)) => {
-                self.reef_db.handle_create(name, columns)
+        // Tables redefined by DDL are published whole at commit; temporary ones never are
+        let redefined = match &stmt {
+            Statement::Create(CreateStatement::Table(table_name, ..) | CreateStatement::TableWithTtl(table_name, ..)) => Some(table_name.clone()),
+            Statement::Drop(DropStatement { table_name, .. }) | Statement::Alter(AlterStatement { table_name, .. })
+                if !self.reef_db.tables.is_temporary(table_name) => Some(table_name.clone()),
+            _ => None,
+        };
+
+        let result = match stmt {
+            Statement::Create(create_stmt) => {
+                self.reef_db.handle_create_statement(create_stmt)
             },
             Statement::Select(SelectStatement::FromTable(table_name, columns, where_clause, joins, order_by)) => {
                 self.reef_db.handle_select(table_name, columns, where_clause, joins, order_by)
             },
+            Statement::Select(SelectStatement::With(ctes, query)) => {
+                self.reef_db.handle_with(ctes, *query)
+            },
+            Statement::Select(SelectStatement::DistinctOn(keys, query)) => {
+                self.reef_db.handle_distinct_on(keys, *query)
+            },
+            Statement::Select(SelectStatement::GroupBy(keys, query)) => {
+                self.reef_db.handle_group_by(keys, *query)
+            },
+            Statement::Select(SelectStatement::GroupingSets(sets, query)) => {
+                self.reef_db.handle_grouping_sets(sets, *query)
+            },
+            Statement::Select(SelectStatement::Limit(limit, query)) => {
+                self.reef_db.handle_limit(limit, *query)
+            },
+            Statement::Select(SelectStatement::AsOf(_, _)) => {
+                self.reef_db.handle_as_of()
+            },
             Statement::Insert(InsertStatement::IntoTable(table_name, values)) => {
                 self.reef_db.handle_insert(table_name, values)
             },
+            Statement::Insert(InsertStatement::Upsert(table_name, values, on_conflict)) => {
+                self.reef_db.handle_upsert(table_name, values, on_conflict)
+            },
             Statement::Update(UpdateStatement::UpdateTable(table_name, updates, where_clause)) => {
                 self.reef_db.handle_update(table_name, updates, where_clause)
             },
+            Statement::Merge(merge) => {
+                self.reef_db.handle_merge(merge)
+            },
             Statement::Delete(DeleteStatement::FromTable(table_name, where_clause)) => {
                 self.reef_db.handle_delete(table_name, where_clause)
             },
             Statement::Alter(AlterStatement { table_name, alter_type }) => {
                 self.reef_db.handle_alter(table_name, alter_type)
             },
-            Statement::Drop(DropStatement { table_name }) => {
-                self.reef_db.handle_drop(table_name)
+            Statement::Drop(DropStatement { table_name, if_exists }) => {
+                self.reef_db.handle_drop(table_name, if_exists)
             },
             Statement::CreateIndex(stmt) => {
                 self.reef_db.handle_create_index(stmt)
@@ -241,7 +348,26 @@ where
             Statement::Commit => {
                 Ok(ReefDBResult::Commit)
             },
+            Statement::ShowTables => {
+                self.reef_db.handle_show_tables()
+            },
+            Statement::ShowLocks => {
+                self.reef_db.handle_show_locks()
+            },
+            Statement::Describe(table_name) => {
+                self.reef_db.handle_describe(&table_name)
+            },
+            Statement::Analyze(table_name) => {
+                self.reef_db.handle_analyze(&table_name)
+            },
+            Statement::SetSchemaVersion(version) => {
+                self.reef_db.handle_set_schema_version(version)
+            },
+        };
+        if let (Ok(_), Some(table_name)) = (&result, redefined) {
+            self.replaced_tables.insert(table_name);
         }
+        result
     }
 
     pub fn get_table_state(&self) -> TableStorage {
@@ -251,5 +377,6 @@ where
     pub fn restore_table_state(&mut self, state: &TableStorage) {
         self.reef_db.tables = TableStorage::new();
         self.reef_db.tables.restore_from(state);
+        self.reef_db.storage.restore_temporary_from(state);
     }
 } 
\ No newline at end of file
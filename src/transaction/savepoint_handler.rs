@@ -1,89 +1,54 @@
-use std::collections::HashMap;
 use crate::{
     error::ReefDBError,
     TableStorage,
-    transaction::TransactionState,
-    savepoint::{Savepoint, SavepointState},
 };
 
+/// Savepoints of a single transaction, oldest first, each with the tables as they were
+/// when it was taken
 #[derive(Clone)]
 pub struct SavepointHandler {
-    savepoints: HashMap<String, Savepoint>,
+    savepoints: Vec<(String, TableStorage)>,
 }
 
 impl SavepointHandler {
     pub fn new() -> Self {
         Self {
-            savepoints: HashMap::new(),
+            savepoints: Vec::new(),
         }
     }
 
+    fn position(&self, name: &str) -> Result<usize, ReefDBError> {
+        self.savepoints.iter()
+            .position(|(sp_name, _)| sp_name == name)
+            .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))
+    }
+
     pub fn create_savepoint(&mut self, name: String, tables: TableStorage) -> Result<(), ReefDBError> {
-        if self.savepoints.contains_key(&name) {
+        if self.savepoints.iter().any(|(sp_name, _)| *sp_name == name) {
             return Err(ReefDBError::Other(format!("Savepoint {} already exists", name)));
         }
-        
-        let savepoint = Savepoint {
-            name: name.clone(),
-            table_snapshot: tables,
-            timestamp: std::time::SystemTime::now(),
-            state: SavepointState::Active,
-        };
-        
-        self.savepoints.insert(name, savepoint);
+        self.savepoints.push((name, tables));
         Ok(())
     }
 
+    /// The tables as of savepoint `name`, and the names of the later savepoints it drops
     pub fn rollback_to_savepoint(&mut self, name: &str) -> Result<(TableStorage, Vec<String>), ReefDBError> {
-        let savepoint = self.savepoints.get(name)
-            .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
-            
-        if savepoint.state != SavepointState::Active {
-            return Err(ReefDBError::SavepointNotActive(name.to_string()));
-        }
-        
-        let snapshot = savepoint.table_snapshot.clone();
-        
-        // Get all savepoint names in order
-        let mut savepoint_names: Vec<_> = self.savepoints.keys().cloned().collect();
-        savepoint_names.sort();
-        
-        let savepoint_time = savepoint_names.iter()
-            .position(|sp_name| sp_name == name)
-            .unwrap();
-            
-        // Get names of savepoints to be removed
-        let removed_savepoints: Vec<String> = savepoint_names.iter()
-            .skip(savepoint_time + 1)
-            .cloned()
+        let position = self.position(name)?;
+        let removed_savepoints = self.savepoints.drain(position + 1..)
+            .map(|(sp_name, _)| sp_name)
             .collect();
-            
-        // Remove all savepoints created after this one
-        for sp_name in &removed_savepoints {
-            self.savepoints.remove(sp_name);
-        }
-        
-        Ok((snapshot, removed_savepoints))
+        Ok((self.savepoints[position].1.clone(), removed_savepoints))
     }
 
     pub fn release_savepoint(&mut self, name: &str) -> Result<(), ReefDBError> {
-        if !self.savepoints.contains_key(name) {
-            return Err(ReefDBError::SavepointNotFound(name.to_string()));
-        }
-        
-        let savepoint = self.savepoints.get(name)
-            .ok_or_else(|| ReefDBError::SavepointNotFound(name.to_string()))?;
-            
-        if savepoint.state != SavepointState::Active {
-            return Err(ReefDBError::SavepointNotActive(name.to_string()));
-        }
-        
-        self.savepoints.remove(name);
+        let position = self.position(name)?;
+        self.savepoints.remove(position);
         Ok(())
     }
 
-    pub fn get_savepoints(&self) -> &HashMap<String, Savepoint> {
-        &self.savepoints
+    /// Names of the savepoints, oldest first
+    pub fn savepoint_names(&self) -> Vec<&str> {
+        self.savepoints.iter().map(|(name, _)| name.as_str()).collect()
     }
 }
 
@@ -116,9 +81,9 @@ mod tests {
         // Test rollback to middle savepoint
         let (_, removed) = handler.rollback_to_savepoint("sp2").unwrap();
         assert_eq!(removed, vec!["sp3".to_string()]);
-        assert!(handler.get_savepoints().contains_key("sp1"));
-        assert!(handler.get_savepoints().contains_key("sp2"));
-        assert!(!handler.get_savepoints().contains_key("sp3"));
+        assert!(handler.savepoint_names().contains(&"sp1"));
+        assert!(handler.savepoint_names().contains(&"sp2"));
+        assert!(!handler.savepoint_names().contains(&"sp3"));
         
         // Test rollback to non-existent savepoint
         assert!(handler.rollback_to_savepoint("sp4").is_err());
@@ -134,7 +99,7 @@ mod tests {
         
         // Test successful release
         assert!(handler.release_savepoint("sp1").is_ok());
-        assert!(!handler.get_savepoints().contains_key("sp1"));
+        assert!(!handler.savepoint_names().contains(&"sp1"));
         
         // Test release of non-existent savepoint
         assert!(handler.release_savepoint("sp1").is_err());
@@ -145,10 +110,10 @@ mod tests {
         let mut handler = SavepointHandler::new();
         let tables = TableStorage::new();
         
-        assert!(handler.get_savepoints().is_empty());
+        assert!(handler.savepoint_names().is_empty());
         
         handler.create_savepoint("sp1".to_string(), tables.clone()).unwrap();
-        assert_eq!(handler.get_savepoints().len(), 1);
-        assert!(handler.get_savepoints().contains_key("sp1"));
+        assert_eq!(handler.savepoint_names().len(), 1);
+        assert!(handler.savepoint_names().contains(&"sp1"));
     }
 }
\ No newline at end of file
@@ -1,26 +1,33 @@
 use std::any::Any;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Receiver;
 use std::cmp::Ordering;
+use chrono::{DateTime, Utc};
 use crate::fts::search::Search;
 
 use crate::result::{ColumnInfo, QueryResult};
+use crate::statistics::TableStatistics;
 use crate::{
+    clock::Clock,
     deadlock::DeadlockDetector,
     error::ReefDBError,
     indexes::{
+        btree::BTreeIndex,
         index_manager::IndexManager,
-       
+        IndexType,
     },
     key_format::KeyFormat,
-    locks::LockManager,
+    locks::{lock_recovering, LockEntry, LockManager},
     locks::LockType,
-    mvcc::MVCCManager,
+    mvcc::{MVCCManager, VersionRetention},
     result::ReefDBResult,
-    savepoint::SavepointManager,
+    savepoint::{SavepointHandle, SavepointManager, SavepointMark},
     sql::{
+        collation::Collation,
         clauses::{
-            join_clause::JoinClause,
+            join_clause::{JoinClause, JoinType},
             wheres::where_type::WhereType,
             order_by::{OrderByClause, OrderDirection},
         },
@@ -28,6 +35,7 @@ use crate::{
         column_def::ColumnDef,
         column_value_pair::ColumnValuePair,
         data_value::DataValue,
+        operators::op::Op,
         table_reference::TableReference,
         data_type::DataType,
         constraints::constraint::Constraint,
@@ -50,11 +58,15 @@ use crate::{
         TableStorage,
     },
     transaction::{
+        CancellationToken,
+        RowChange,
+        RowDeltas,
         Transaction,
         IsolationLevel,
         TransactionState,
     },
-    wal::{WriteAheadLog, WALEntry, WALOperation},
+    wal::{GroupCommit, WriteAheadLog, WALEntry, WALOperation},
+    metrics::{MetricsSink, NoopMetrics},
     ReefDB,
 };
 
@@ -70,6 +82,325 @@ where
     mvcc_manager: Arc<Mutex<MVCCManager>>,
     deadlock_detector: Arc<Mutex<DeadlockDetector>>,
     savepoint_manager: Arc<Mutex<SavepointManager>>,
+    max_join_rows: Option<usize>,
+    hash_join_enabled: bool,
+    merge_join_enabled: bool,
+    vacuum_on_commit: bool,
+    // Set when commits share fsyncs through a commit queue
+    group_commit: Option<Arc<GroupCommit>>,
+    parallel_scan_threads: usize,
+    default_isolation_level: IsolationLevel,
+    metrics: Arc<dyn MetricsSink>,
+    clock: Arc<dyn Clock>,
+}
+
+// Row ids matched by full-text predicates, keyed by column name and query text
+type FtsMatches = HashMap<(String, String), HashSet<usize>>;
+
+// Probe column on the left side and joined row indexes bucketed by join key
+type HashJoinTable = (String, HashMap<Vec<u8>, Vec<usize>>);
+
+// A joined table's clause with its schema and rows
+type JoinedTable = (JoinClause, (Vec<ColumnDef>, Vec<Vec<DataValue>>));
+
+// Rows joined so far, each with the schema of the tables it spans
+type PartialJoinRows = Vec<(Vec<DataValue>, Vec<ColumnDef>)>;
+
+enum JoinStrategy {
+    NestedLoop,
+    Hash(HashJoinTable),
+    Merge {
+        probe_column: String,
+        key_idx: usize,
+        // Positions of the joined rows with a non-NULL key, in key order
+        order: Vec<usize>,
+    },
+}
+
+// Which of the faster join strategies may be chosen; the nested loop always can
+#[derive(Clone, Copy)]
+struct JoinMethods {
+    merge: bool,
+    hash: bool,
+}
+
+// Below this many rows a parallel scan costs more in thread startup than it saves
+pub const PARALLEL_SCAN_MIN_ROWS: usize = 4096;
+
+// filter_map over `items` split into one chunk per thread. Chunk results are
+// concatenated in input order, so the output is the same as a sequential filter_map.
+// Every worker stops at the next item once `cancel` is set.
+fn parallel_filter_map<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    cancel: &CancellationToken,
+    f: impl Fn(usize, &T) -> Option<R> + Sync,
+) -> Result<Vec<R>, ReefDBError> {
+    let results = if threads <= 1 || items.len() < 2 {
+        items.iter().enumerate()
+            .take_while(|_| !cancel.is_cancelled())
+            .filter_map(|(i, item)| f(i, item))
+            .collect()
+    } else {
+        let chunk_size = items.len().div_ceil(threads);
+        let f = &f;
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = items.chunks(chunk_size).enumerate()
+                .map(|(chunk_idx, chunk)| scope.spawn(move || {
+                    chunk.iter().enumerate()
+                        .take_while(|_| !cancel.is_cancelled())
+                        .filter_map(|(offset, item)| f(chunk_idx * chunk_size + offset, item))
+                        .collect::<Vec<R>>()
+                }))
+                .collect();
+            workers.into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        })
+    };
+    cancel.check()?;
+    Ok(results)
+}
+
+// How a scan runs: the threads it may split across, the token that stops it and
+// where its row counts are reported
+#[derive(Clone, Copy)]
+struct ScanControl<'a> {
+    threads: usize,
+    cancel: &'a CancellationToken,
+    metrics: &'a dyn MetricsSink,
+}
+
+// Where each table's columns sit in a joined row: the FROM table first, then each
+// joined table in join order
+struct JoinLayout<'a> {
+    tables: Vec<(&'a str, usize, &'a [ColumnDef])>,
+    width: usize,
+}
+
+impl<'a> JoinLayout<'a> {
+    fn new(
+        table_name: &'a str,
+        schema: &'a [ColumnDef],
+        joined_tables: &'a [JoinedTable],
+    ) -> Self {
+        let mut tables = vec![(table_name, 0, schema)];
+        let mut width = schema.len();
+        for (join, (join_schema, _)) in joined_tables {
+            tables.push((join.table_ref.name.as_str(), width, join_schema.as_slice()));
+            width += join_schema.len();
+        }
+        JoinLayout { tables, width }
+    }
+
+    // Position of a column in the joined row. A qualified column only matches its own
+    // table; an unqualified one the first table that has it.
+    fn resolve(&self, table: Option<&str>, name: &str) -> Result<usize, ReefDBError> {
+        if let Some(table) = table {
+            if !self.tables.iter().any(|(joined, _, _)| *joined == table) {
+                return Err(ReefDBError::TableNotFound(table.to_string()));
+            }
+        }
+        self.tables.iter()
+            .filter(|(joined, _, _)| table.is_none_or(|table| table == *joined))
+            .find_map(|(_, offset, schema)| schema.iter().position(|c| c.name == name).map(|pos| offset + pos))
+            .ok_or_else(|| ReefDBError::ColumnNotFound(match table {
+                Some(table) => format!("{}.{}", table, name),
+                None => name.to_string(),
+            }))
+    }
+
+    fn column(&self, idx: usize) -> &'a ColumnDef {
+        self.tables.iter()
+            .rev()
+            .find(|(_, offset, _)| *offset <= idx)
+            .map(|(_, offset, schema)| &schema[idx - offset])
+            .expect("column index resolved against this layout")
+    }
+
+    // A joined row must hold exactly one value per column of every table
+    fn check_row(&self, row: &[DataValue]) -> Result<(), ReefDBError> {
+        if row.len() != self.width {
+            return Err(ReefDBError::ColumnCountMismatch { expected: self.width, found: row.len() });
+        }
+        Ok(())
+    }
+
+    // Full-text predicates never match a joined row, as they're answered by the index
+    fn evaluate_where(&self, where_clause: &WhereType, row: &[DataValue]) -> Result<bool, ReefDBError> {
+        match where_clause {
+            WhereType::Regular(clause) => {
+                let idx = self.resolve(clause.table.as_deref(), &clause.col_name)?;
+                Ok(self.column(idx).collation().evaluate(&clause.operator, &row[idx], &clause.value))
+            }
+            WhereType::And(left, right) => Ok(self.evaluate_where(left, row)? && self.evaluate_where(right, row)?),
+            WhereType::Or(left, right) => Ok(self.evaluate_where(left, row)? || self.evaluate_where(right, row)?),
+            WhereType::FTS(_) => Ok(false),
+        }
+    }
+}
+
+// A WHERE clause resolved against one table's schema before a scan: column names become
+// positions and literals are put in the column's collation form, so checking a row is
+// index lookups and comparisons instead of a walk of the predicate's names per row
+enum CompiledWhere<'a> {
+    Compare {
+        column: usize,
+        collation: Collation,
+        operator: &'a Op,
+        value: Cow<'a, DataValue>,
+    },
+    // A column the schema doesn't have, e.g. a function call, never matches
+    Never,
+    // Row ids the full-text predicate matched, if it was looked up
+    Fts(Option<&'a HashSet<usize>>),
+    // Chains of AND and OR are flattened so `reorder` can pick the order terms run in
+    All(Vec<CompiledWhere<'a>>),
+    Any(Vec<CompiledWhere<'a>>),
+}
+
+// Relative cost of checking one row against a comparison. Pattern and text operators
+// scan the whole value, so they are run after the plain comparisons next to them.
+fn operator_cost(operator: &Op) -> f64 {
+    match operator {
+        Op::Like | Op::Match | Op::TextSearch | Op::Contains => 10.0,
+        _ => 1.0,
+    }
+}
+
+// Fraction of rows a comparison is guessed to match when the table hasn't been analyzed
+fn default_selectivity(operator: &Op) -> f64 {
+    match operator {
+        Op::Equal | Op::NullSafeEqual => 0.1,
+        Op::NotEqual => 0.9,
+        Op::LessThan | Op::LessThanOrEqual | Op::GreaterThan | Op::GreaterThanOrEqual => 0.33,
+        Op::Like | Op::Match | Op::TextSearch | Op::Contains => 0.25,
+    }
+}
+
+impl<'a> CompiledWhere<'a> {
+    fn compile(where_clause: &'a WhereType, schema: &[ColumnDef], fts_matches: &'a FtsMatches) -> Self {
+        match where_clause {
+            WhereType::Regular(clause) => match schema.iter().position(|c| c.name == clause.col_name) {
+                Some(column) => {
+                    let collation = schema[column].collation();
+                    CompiledWhere::Compare {
+                        column,
+                        collation,
+                        operator: &clause.operator,
+                        value: collation.key(&clause.value),
+                    }
+                }
+                None => CompiledWhere::Never,
+            },
+            WhereType::FTS(clause) => {
+                CompiledWhere::Fts(fts_matches.get(&(clause.column.name.clone(), clause.query.text.clone())))
+            }
+            WhereType::And(left, right) => {
+                let mut terms = Vec::new();
+                for side in [left, right] {
+                    match Self::compile(side, schema, fts_matches) {
+                        CompiledWhere::All(nested) => terms.extend(nested),
+                        term => terms.push(term),
+                    }
+                }
+                CompiledWhere::All(terms)
+            }
+            WhereType::Or(left, right) => {
+                let mut terms = Vec::new();
+                for side in [left, right] {
+                    match Self::compile(side, schema, fts_matches) {
+                        CompiledWhere::Any(nested) => terms.extend(nested),
+                        term => terms.push(term),
+                    }
+                }
+                CompiledWhere::Any(terms)
+            }
+        }
+    }
+
+    // Orders the terms of every AND so the cheapest, most selective run first, and those
+    // of every OR so the cheapest, most likely to match do. Compiled terms can't fail and
+    // have no side effects, so only the number of terms checked per row changes.
+    fn reorder(mut self, schema: &[ColumnDef], stats: Option<&TableStatistics>) -> Self {
+        self.reorder_terms(schema, stats);
+        self
+    }
+
+    // Returns the estimated (cost per row, fraction of rows matched) of the reordered term
+    fn reorder_terms(&mut self, schema: &[ColumnDef], stats: Option<&TableStatistics>) -> (f64, f64) {
+        match self {
+            CompiledWhere::Compare { column, operator, value, .. } => {
+                let selectivity = stats
+                    .and_then(|stats| stats.estimate_selectivity(&schema[*column].name, operator, value))
+                    .unwrap_or_else(|| default_selectivity(operator));
+                (operator_cost(operator), selectivity)
+            }
+            CompiledWhere::Never => (0.0, 0.0),
+            CompiledWhere::Fts(hits) => {
+                let selectivity = match (hits, stats) {
+                    (None, _) => 0.0,
+                    (Some(hits), Some(stats)) if stats.row_count > 0 => {
+                        (hits.len() as f64 / stats.row_count as f64).min(1.0)
+                    }
+                    (Some(_), _) => default_selectivity(&Op::Match),
+                };
+                (1.0, selectivity)
+            }
+            CompiledWhere::All(terms) => Self::reorder_list(terms, true, schema, stats),
+            CompiledWhere::Any(terms) => Self::reorder_list(terms, false, schema, stats),
+        }
+    }
+
+    fn reorder_list(
+        terms: &mut Vec<CompiledWhere<'a>>,
+        conjunction: bool,
+        schema: &[ColumnDef],
+        stats: Option<&TableStatistics>,
+    ) -> (f64, f64) {
+        let mut estimated: Vec<(f64, f64, CompiledWhere<'a>)> = terms.drain(..)
+            .map(|mut term| {
+                let (cost, selectivity) = term.reorder_terms(schema, stats);
+                (cost, selectivity, term)
+            })
+            .collect();
+        // A term ranks by its cost per row it settles: rows it rejects under AND, rows it
+        // accepts under OR
+        let rank = |(cost, selectivity, _): &(f64, f64, CompiledWhere<'a>)| {
+            let settled = if conjunction { 1.0 - selectivity } else { *selectivity };
+            if settled <= 0.0 { f64::INFINITY } else { cost / settled }
+        };
+        estimated.sort_by(|a, b| rank(a).total_cmp(&rank(b)));
+
+        // Later terms only run on the rows earlier ones left unsettled
+        let (mut cost, mut selectivity) = (0.0, if conjunction { 1.0 } else { 0.0 });
+        for (term_cost, term_selectivity, term) in estimated {
+            if conjunction {
+                cost += selectivity * term_cost;
+                selectivity *= term_selectivity;
+            } else {
+                cost += (1.0 - selectivity) * term_cost;
+                selectivity += (1.0 - selectivity) * term_selectivity;
+            }
+            terms.push(term);
+        }
+        (cost, selectivity)
+    }
+
+    fn matches(&self, row: &[DataValue]) -> bool {
+        match self {
+            CompiledWhere::Compare { column, collation, operator, value } => {
+                operator.evaluate(&collation.key(&row[*column]), value)
+            }
+            CompiledWhere::Never => false,
+            CompiledWhere::Fts(hits) => match row.first() {
+                Some(DataValue::Integer(id)) => hits.is_some_and(|hits| hits.contains(&(*id as usize))),
+                _ => false,
+            },
+            CompiledWhere::All(terms) => terms.iter().all(|term| term.matches(row)),
+            CompiledWhere::Any(terms) => terms.iter().any(|term| term.matches(row)),
+        }
+    }
 }
 
 // Helper structs
@@ -80,7 +411,6 @@ where
     FTS::NewArgs: Clone,
 {
     transaction: &'a mut Transaction<S, FTS>,
-    isolation_level: IsolationLevel,
 }
 
 impl<S: Storage + IndexManager + Clone + Any, FTS: Search + Clone> TransactionManager<S, FTS>
@@ -88,6 +418,11 @@ where
     FTS::NewArgs: Clone,
 {
     pub fn create(reef_db: ReefDB<S, FTS>, wal: WriteAheadLog) -> Self {
+        Self::create_with_isolation(reef_db, wal, IsolationLevel::default())
+    }
+
+    /// A manager whose `begin_transaction_default` begins transactions at `default_isolation_level`
+    pub fn create_with_isolation(reef_db: ReefDB<S, FTS>, wal: WriteAheadLog, default_isolation_level: IsolationLevel) -> Self {
         TransactionManager {
             active_transactions: HashMap::new(),
             lock_manager: Arc::new(Mutex::new(LockManager::new())),
@@ -96,20 +431,164 @@ where
             mvcc_manager: reef_db.mvcc_manager.clone(),
             deadlock_detector: Arc::new(Mutex::new(DeadlockDetector::new())),
             savepoint_manager: Arc::new(Mutex::new(SavepointManager::new())),
+            max_join_rows: None,
+            hash_join_enabled: true,
+            merge_join_enabled: true,
+            vacuum_on_commit: false,
+            group_commit: None,
+            parallel_scan_threads: 1,
+            default_isolation_level,
+            metrics: Arc::new(NoopMetrics),
+            clock: reef_db.clock.clone(),
+        }
+    }
+
+    /// Caps the number of intermediate rows a join may produce before the
+    /// query is aborted with `ReefDBError::JoinTooLarge`. `None` disables the check.
+    pub fn set_max_join_rows(&mut self, limit: Option<usize>) {
+        self.max_join_rows = limit;
+    }
+
+    pub fn get_max_join_rows(&self) -> Option<usize> {
+        self.max_join_rows
+    }
+
+    /// Enables or disables the hash-join path. When disabled every join
+    /// is evaluated with a nested loop.
+    pub fn set_hash_join_enabled(&mut self, enabled: bool) {
+        self.hash_join_enabled = enabled;
+    }
+
+    /// Enables or disables the merge-join path used when both join inputs
+    /// are already sorted on the join column.
+    pub fn set_merge_join_enabled(&mut self, enabled: bool) {
+        self.merge_join_enabled = enabled;
+    }
+
+    /// Number of threads single-table SELECTs split their scan and projection
+    /// across. Tables smaller than `PARALLEL_SCAN_MIN_ROWS` are always scanned
+    /// on the calling thread; 1 (the default) disables parallel scans.
+    pub fn set_parallel_scan_threads(&mut self, threads: usize) {
+        self.parallel_scan_threads = threads.max(1);
+    }
+
+    fn scan_threads(threads: usize, rows: usize) -> usize {
+        if rows < PARALLEL_SCAN_MIN_ROWS {
+            1
+        } else {
+            threads
+        }
+    }
+
+    /// Sink notified of rows scanned and returned, lock waits, deadlocks and commits
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = sink;
+    }
+
+    /// Clock for WAL timestamps, fsync intervals, MVCC commit times and row expiry. Tests
+    /// can pass a `MockClock` to control time-based behavior.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        lock_recovering(&self.wal).set_clock(clock.clone());
+        lock_recovering(&self.mvcc_manager).set_clock(clock.clone());
+        lock_recovering(&self.reef_db).clock = clock.clone();
+        self.clock = clock;
+    }
+
+    /// Sets the reserved table name prefix enforced by transactions begun from now on
+    pub fn set_reserved_prefix(&mut self, prefix: &str) {
+        lock_recovering(&self.reef_db).reserved_prefix = prefix.to_string();
+    }
+
+    /// When enabled, every commit is followed by an MVCC vacuum
+    pub fn set_vacuum_on_commit(&mut self, enabled: bool) {
+        self.vacuum_on_commit = enabled;
+    }
+
+    /// Batches concurrent commits so one WAL fsync makes several durable: a commit waits
+    /// up to `window` for others to join before the fsync. A commit still returns only
+    /// once its entry is fsynced. `None` syncs commits by the WAL's fsync policy.
+    /// Commits from this manager and the sessions opened after the call share the queue.
+    pub fn set_group_commit_window(&mut self, window: Option<std::time::Duration>) {
+        self.group_commit = window.map(|window| Arc::new(GroupCommit::new(window)));
+    }
+
+    /// Another handle on the same database, WAL, locks and commit queue, with no
+    /// transactions of its own, for running transactions from another thread
+    pub fn session(&self) -> Self {
+        TransactionManager {
+            active_transactions: HashMap::new(),
+            lock_manager: self.lock_manager.clone(),
+            wal: self.wal.clone(),
+            reef_db: self.reef_db.clone(),
+            mvcc_manager: self.mvcc_manager.clone(),
+            deadlock_detector: self.deadlock_detector.clone(),
+            savepoint_manager: self.savepoint_manager.clone(),
+            max_join_rows: self.max_join_rows,
+            hash_join_enabled: self.hash_join_enabled,
+            merge_join_enabled: self.merge_join_enabled,
+            vacuum_on_commit: self.vacuum_on_commit,
+            group_commit: self.group_commit.clone(),
+            parallel_scan_threads: self.parallel_scan_threads,
+            default_isolation_level: self.default_isolation_level,
+            metrics: self.metrics.clone(),
+            clock: self.clock.clone(),
         }
     }
 
+    /// How much MVCC history vacuum keeps for time-travel reads
+    pub fn set_version_retention(&self, retention: VersionRetention) {
+        lock_recovering(&self.mvcc_manager).set_retention(retention);
+    }
+
+    /// Reclaims MVCC row versions no active transaction can see anymore and
+    /// returns how many were removed
+    pub fn vacuum(&self) -> Result<usize, ReefDBError> {
+        Ok(lock_recovering(&self.mvcc_manager)
+            .vacuum())
+    }
+
+    /// Streams committed WAL entries as transactions commit, for building a replica.
+    /// See `WriteAheadLog::subscribe`.
+    pub fn subscribe_wal(&self) -> Result<Receiver<WALEntry>, ReefDBError> {
+        Ok(self.wal.lock()
+            .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+            .subscribe())
+    }
+
+    /// Isolation level `begin_transaction_default` starts transactions with;
+    /// `ReadCommitted` unless changed
+    pub fn set_default_isolation_level(&mut self, level: IsolationLevel) {
+        self.default_isolation_level = level;
+    }
+
+    pub fn get_default_isolation_level(&self) -> IsolationLevel {
+        self.default_isolation_level
+    }
+
+    /// Token that cancels the statement `transaction_id` is running when set from
+    /// another thread
+    pub fn cancellation_token(&self, transaction_id: u64) -> Result<CancellationToken, ReefDBError> {
+        self.active_transactions.get(&transaction_id)
+            .map(|transaction| transaction.cancellation.clone())
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))
+    }
+
+    pub fn begin_transaction_default(&mut self) -> Result<u64, ReefDBError> {
+        self.begin_transaction(self.default_isolation_level)
+    }
+
     pub fn begin_transaction(&mut self, isolation_level: IsolationLevel) -> Result<u64, ReefDBError> {
         let reef_db = self.reef_db.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire database lock".to_string()))?;
+            .map_err(|_| ReefDBError::LockPoisoned("database lock".to_string()))?;
         
         let transaction = Transaction::create((*reef_db).clone(), isolation_level);
         let id = transaction.get_id();
         
         // Initialize MVCC timestamp for the transaction
-        self.mvcc_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?
-            .begin_transaction(id);
+        let mut mvcc_manager = lock_recovering(&self.mvcc_manager);
+        mvcc_manager.begin_transaction(id);
+        mvcc_manager.set_isolation_level(id, isolation_level);
+        drop(mvcc_manager);
         
         self.active_transactions.insert(id, transaction);
         Ok(id)
@@ -117,116 +596,175 @@ where
 
     pub fn commit_transaction(&mut self, id: u64) -> Result<(), ReefDBError> {
         let mut transaction = self.active_transactions.remove(&id)
-            .ok_or_else(|| ReefDBError::Other("Transaction not found".to_string()))?;
+            .ok_or(ReefDBError::TransactionNotFound(id))?;
         
         if transaction.get_state() != &TransactionState::Active {
-            return Err(ReefDBError::Other("Transaction is not active".to_string()));
+            return Err(ReefDBError::TransactionNotActive);
         }
 
-        // Get the final transaction state before commit
-        let final_state = transaction.get_table_state();
-
-        // Write to WAL before committing
-        let wal_entry = WALEntry {
-            transaction_id: id,
-            timestamp: std::time::SystemTime::now(),
-            operation: WALOperation::Commit,
-            table_name: String::new(),
-            data: vec![],
-        };
-
-        self.wal.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire WAL lock".to_string()))?
-            .append_entry(wal_entry)?;
-
-        // Commit MVCC changes first
-        let commit_result = self.mvcc_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?
-            .commit(id);
+        // A serializable transaction that lost a write race must not reach the WAL
+        let conflict = lock_recovering(&self.mvcc_manager)
+            .check_write_conflicts(id);
+        if let Err(e) = conflict {
+            self.active_transactions.insert(id, transaction);
+            self.rollback_transaction(id)?;
+            return Err(e);
+        }
 
-        if let Err(e) = commit_result {
-            // If MVCC commit fails, rollback the transaction
+        // Temporary tables end with the transaction and are never published
+        if let Err(e) = transaction.reef_db.drop_temporary_tables() {
+            self.active_transactions.insert(id, transaction);
             self.rollback_transaction(id)?;
             return Err(e);
         }
 
-        // Only update the database state after MVCC commit succeeds
-        let mut reef_db = self.reef_db.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire database lock".to_string()))?;
-        
-        // Update database state with final transaction state
-        reef_db.tables.restore_from(&final_state);
-        
-        // Commit the transaction
-        transaction.commit(&mut reef_db)?;
+        // A transaction that changed nothing has nothing to log or publish. Otherwise the
+        // shared database stays locked from the check of its row changes until they're
+        // published, so no other commit can write a clashing row in between.
+        let committed = if transaction.is_dirty() {
+            let shared = Arc::clone(&self.reef_db);
+            let mut reef_db = shared.lock()
+                .map_err(|_| ReefDBError::LockPoisoned("database lock".to_string()))?;
+            self.log_commit(id, &mut transaction, &reef_db)
+                .map(|(deltas, sequence)| (transaction.publish(&mut reef_db, deltas), sequence))
+        } else {
+            lock_recovering(&self.mvcc_manager).commit(id)
+                .map(|_| (transaction.state_handler.commit(), None))
+        };
+        let (published, sequence) = match committed {
+            Ok(committed) => committed,
+            Err(e) => {
+                // Nothing was published, so the rollback releases its locks and keeps its
+                // WAL entries from subscribers
+                self.active_transactions.insert(id, transaction);
+                self.rollback_transaction(id)?;
+                return Err(e);
+            }
+        };
+        // Under group commit the commit entry shares an fsync with those of others, waited
+        // for once the database is unlocked
+        let durable = match (&self.group_commit, sequence) {
+            (Some(group_commit), Some(sequence)) => group_commit.wait_durable(&self.wal, sequence),
+            _ => Ok(()),
+        };
 
         // Release locks and remove from deadlock detector
-        self.lock_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire lock manager".to_string()))?
+        lock_recovering(&self.lock_manager)
             .release_transaction_locks(id);
         
-        self.deadlock_detector.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire deadlock detector".to_string()))?
+        lock_recovering(&self.deadlock_detector)
             .remove_transaction(id);
+        published?;
+        durable?;
+
+        self.metrics.transaction_committed(id);
+        if self.vacuum_on_commit {
+            self.vacuum()?;
+        }
 
         Ok(())
     }
 
+    // Checks a committing transaction's row changes against the shared database, then writes
+    // its commit entry and commits its MVCC versions. Returns the row deltas to publish and,
+    // under group commit, the sequence number of the entry still to be made durable.
+    fn log_commit(&self, id: u64, transaction: &mut Transaction<S, FTS>, reef_db: &ReefDB<S, FTS>) -> Result<(RowDeltas, Option<u64>), ReefDBError> {
+        let deltas = transaction.prepare_commit(reef_db)?;
+        let wal_entry = WALEntry {
+            transaction_id: id,
+            timestamp: self.clock.now(),
+            operation: WALOperation::Commit,
+            table_name: String::new(),
+            data: vec![],
+        };
+        let sequence = match &self.group_commit {
+            Some(group_commit) => Some(group_commit.append(&self.wal, wal_entry)?),
+            None => {
+                self.wal.lock()
+                    .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+                    .append_entry(wal_entry)?;
+                None
+            }
+        };
+        lock_recovering(&self.mvcc_manager).commit(id)?;
+        Ok((deltas, sequence))
+    }
+
     pub fn rollback_transaction(&mut self, id: u64) -> Result<(), ReefDBError> {
         let mut transaction = self.active_transactions.remove(&id)
-            .ok_or_else(|| ReefDBError::Other("Transaction not found".to_string()))?;
+            .ok_or(ReefDBError::TransactionNotFound(id))?;
 
-        let mut reef_db = self.reef_db.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire database lock".to_string()))?;
-        
-        transaction.rollback(&mut reef_db)?;
+        transaction.rollback()?;
 
-        // Rollback MVCC changes
-        self.mvcc_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?
+        // Rollback MVCC changes. Its error is returned once the transaction's locks and
+        // savepoints are cleaned up, so a failure here doesn't leave them behind
+        let mvcc_rollback = lock_recovering(&self.mvcc_manager)
             .rollback(id);
 
+        // Row entries already in the WAL must never reach subscribers
+        self.wal.lock()
+            .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+            .discard_pending(id);
+
         // Release locks and remove from deadlock detector
-        self.lock_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire lock manager".to_string()))?
+        lock_recovering(&self.lock_manager)
             .release_transaction_locks(id);
         
-        self.deadlock_detector.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire deadlock detector".to_string()))?
+        lock_recovering(&self.deadlock_detector)
             .remove_transaction(id);
 
         // Clear savepoints for this transaction
-        let mut savepoint_manager = self.savepoint_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire savepoint manager lock".to_string()))?;
+        let mut savepoint_manager = lock_recovering(&self.savepoint_manager);
         savepoint_manager.clear_transaction_savepoints(id);
 
-        Ok(())
+        mvcc_rollback
+    }
+
+    /// Rolls back every still-active transaction, syncs the WAL to disk and flushes the
+    /// database storage. Calling it again only repeats the sync and flush.
+    pub fn shutdown(&mut self) -> Result<(), ReefDBError> {
+        let mut active: Vec<u64> = self.active_transactions.keys().copied().collect();
+        active.sort_unstable();
+        for id in active {
+            self.rollback_transaction(id)?;
+        }
+
+        self.wal.lock()
+            .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+            .sync()?;
+
+        self.reef_db.lock()
+            .map_err(|_| ReefDBError::LockPoisoned("database lock".to_string()))?
+            .storage
+            .flush()
     }
 
     pub fn acquire_lock(&self, transaction_id: u64, table_name: &str, lock_type: LockType) -> Result<(), ReefDBError> {
-        let mut lock_manager = self.lock_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire lock manager".to_string()))?;
+        self.metrics.lock_requested(transaction_id, table_name);
+        let mut lock_manager = lock_recovering(&self.lock_manager);
         
         // Check for deadlocks before acquiring lock
-        let mut deadlock_detector = self.deadlock_detector.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire deadlock detector".to_string()))?;
+        let mut deadlock_detector = lock_recovering(&self.deadlock_detector);
         
-        // Get current lock holders for this table
+        // Get current lock holders for this table; row lock holders show up through their intention locks
         let lock_holders = lock_manager.get_lock_holders(table_name);
+        let upgrade = lock_manager.is_upgrade(transaction_id, table_name, &lock_type);
+        let queued = lock_manager.upgrade_waiters(table_name).contains(&transaction_id);
         
-        // If there are existing locks and we don't already have a lock, add wait-for edges
-        if !lock_holders.is_empty() && !lock_manager.has_lock(transaction_id, table_name) {
+        // If there are existing locks and we don't already have a lock (or are upgrading it), add wait-for edges
+        if !lock_holders.is_empty() && (upgrade || !lock_manager.has_lock(transaction_id, table_name)) {
             for holder_id in lock_holders {
                 if holder_id != transaction_id {
                     deadlock_detector.add_wait(transaction_id, holder_id, table_name.to_string());
                     
                     // Check for deadlocks
                     let active_txs: Vec<&Transaction<S, FTS>> = self.active_transactions.values().collect();
-                    if let Some(victim_tx) = deadlock_detector.detect_deadlock(&active_txs) {
+                    if let Some((victim_tx, cycle)) = deadlock_detector.detect_deadlock_cycle(&active_txs) {
                         if victim_tx == transaction_id {
                             // Remove the wait edge since we're aborting
                             deadlock_detector.remove_transaction(transaction_id);
-                            return Err(ReefDBError::Deadlock);
+                            self.metrics.deadlock(transaction_id);
+                            return Err(ReefDBError::Deadlock(cycle));
                         }
                     }
                 }
@@ -241,16 +779,115 @@ where
                 Ok(())
             }
             Err(e) => {
-                // Failed to acquire lock, remove any wait edges
-                deadlock_detector.remove_transaction(transaction_id);
+                // Failed to acquire lock, remove any wait edges. A failed upgrade stays queued
+                // in the lock manager, so its edges are kept for later deadlock checks
+                if !upgrade {
+                    deadlock_detector.remove_transaction(transaction_id);
+                } else if !queued && lock_manager.upgrade_waiters(table_name).contains(&transaction_id) {
+                    // Conflicts fail straight away; only an upgrade newly queued behind other holders waits
+                    self.metrics.lock_waited(transaction_id, table_name);
+                }
                 Err(e)
             }
         }
     }
 
+    /// Every lock held or queued for, read under the lock manager's mutex
+    pub fn lock_snapshot(&self) -> Vec<LockEntry> {
+        lock_recovering(&self.lock_manager).snapshot()
+    }
+
+    /// Locks a single row keyed by `(table_name, primary_key)`, so transactions touching
+    /// different rows of the same table don't block each other
+    pub fn acquire_row_lock(&self, transaction_id: u64, table_name: &str, primary_key: &str, lock_type: LockType) -> Result<(), ReefDBError> {
+        let mut lock_manager = lock_recovering(&self.lock_manager);
+
+        let mut deadlock_detector = lock_recovering(&self.deadlock_detector);
+
+        // Only transactions holding a conflicting lock on this row (or the whole table) are waited on
+        let resource = LockManager::row_resource(table_name, primary_key);
+        self.metrics.lock_requested(transaction_id, &resource);
+        for holder_id in lock_manager.row_lock_conflicts(transaction_id, table_name, primary_key, &lock_type) {
+            deadlock_detector.add_wait(transaction_id, holder_id, resource.clone());
+
+            let active_txs: Vec<&Transaction<S, FTS>> = self.active_transactions.values().collect();
+            if let Some((victim_tx, cycle)) = deadlock_detector.detect_deadlock_cycle(&active_txs) {
+                if victim_tx == transaction_id {
+                    deadlock_detector.remove_transaction(transaction_id);
+                    self.metrics.deadlock(transaction_id);
+                    return Err(ReefDBError::Deadlock(cycle));
+                }
+            }
+        }
+
+        let upgrade = lock_manager.is_upgrade(transaction_id, table_name, &lock_type.intention());
+        let queued = lock_manager.upgrade_waiters(table_name).contains(&transaction_id);
+        let result = lock_manager.acquire_row_lock(transaction_id, table_name, primary_key, lock_type);
+        // Like table upgrades, a queued intention upgrade keeps its wait edges
+        if result.is_ok() || !upgrade {
+            deadlock_detector.remove_transaction(transaction_id);
+        } else if !queued && lock_manager.upgrade_waiters(table_name).contains(&transaction_id) {
+            self.metrics.lock_waited(transaction_id, table_name);
+        }
+        result
+    }
+
+    // Primary keys of the rows a DML statement will touch, as seen by the transaction:
+    // its copy of the tables overlaid with the MVCC versions visible to it
+    fn affected_primary_keys(&self, transaction_id: u64, table_name: &str, where_clause: Option<&WhereType>) -> Result<Vec<String>, ReefDBError> {
+        let visible_rows = lock_recovering(&self.mvcc_manager).transaction_snapshot(transaction_id, table_name);
+        let transaction = self.active_transactions.get(&transaction_id)
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
+        let (schema, rows) = transaction.reef_db.storage.get_table_ref(table_name)
+            .ok_or_else(|| ReefDBError::TableNotFound(table_name.to_string()))?;
+        let pk_idx = Self::primary_key_index(schema);
+        let no_fts = FtsMatches::new();
+        let stats = transaction.reef_db.table_statistics(table_name);
+        let compiled = where_clause.map(|clause| {
+            CompiledWhere::compile(clause, schema, &no_fts).reorder(schema, stats.as_ref())
+        });
+
+        let extra = Self::version_only_rows(table_name, rows, &visible_rows);
+        Ok(rows.iter()
+            .map(|row| match &row[0] {
+                DataValue::Integer(n) => visible_rows.get(&KeyFormat::row(table_name, 0, &n.to_string())).unwrap_or(row),
+                _ => row,
+            })
+            .chain(&extra)
+            .filter(|row| !row.is_empty())
+            .filter(|row| compiled.as_ref().is_none_or(|compiled| compiled.matches(row)))
+            .filter_map(|row| row.get(pk_idx).map(Self::primary_key_string))
+            .collect())
+    }
+
+    fn primary_key_index(schema: &[ColumnDef]) -> usize {
+        schema.iter()
+            .position(|c| c.constraints.contains(&Constraint::PrimaryKey))
+            .unwrap_or(0)
+    }
+
+    fn primary_key_string(value: &DataValue) -> String {
+        match value {
+            DataValue::Integer(n) => n.to_string(),
+            DataValue::Text(s) => s.to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+
     pub fn create_savepoint(&mut self, transaction_id: u64, name: String) -> Result<(), ReefDBError> {
+        self.add_savepoint(transaction_id, Some(name))?;
+        Ok(())
+    }
+
+    /// Creates a savepoint without a name, to be rolled back to or released through the
+    /// returned handle
+    pub fn create_anonymous_savepoint(&mut self, transaction_id: u64) -> Result<SavepointHandle, ReefDBError> {
+        self.add_savepoint(transaction_id, None)
+    }
+
+    fn add_savepoint(&mut self, transaction_id: u64, name: Option<String>) -> Result<SavepointHandle, ReefDBError> {
         let transaction = self.active_transactions.get(&transaction_id)
-            .ok_or_else(|| ReefDBError::TransactionNotFound(transaction_id))?;
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
         
         if transaction.get_state() != &TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
@@ -259,51 +896,64 @@ where
         // Get the transaction's current state
         let table_state = transaction.get_table_state();
         
-        // Create the savepoint with this state
-        self.savepoint_manager.lock()
-            .map_err(|_| ReefDBError::LockAcquisitionFailed("Failed to acquire savepoint manager lock".to_string()))?
-            .create_savepoint(transaction_id, name, table_state)?;
-        
-        Ok(())
+        // Create the savepoint with this state, the locks held and the rows written so far
+        let mark = SavepointMark {
+            locks: lock_recovering(&self.lock_manager).grant_count(transaction_id),
+            changes: transaction.changes.len(),
+        };
+        let mut savepoint_manager = lock_recovering(&self.savepoint_manager);
+        match name {
+            Some(name) => savepoint_manager.create_savepoint(transaction_id, name, table_state, mark),
+            None => savepoint_manager.create_anonymous_savepoint(transaction_id, table_state, mark),
+        }
+    }
+
+    /// `rollback_to_savepoint` for a savepoint known by its handle
+    pub fn rollback_to(&mut self, transaction_id: u64, handle: SavepointHandle) -> Result<TableStorage, ReefDBError> {
+        let name = lock_recovering(&self.savepoint_manager).savepoint_name(transaction_id, handle)?;
+        self.rollback_to_savepoint(transaction_id, &name)
+    }
+
+    /// `release_savepoint` for a savepoint known by its handle
+    pub fn release(&mut self, transaction_id: u64, handle: SavepointHandle) -> Result<(), ReefDBError> {
+        let name = lock_recovering(&self.savepoint_manager).savepoint_name(transaction_id, handle)?;
+        self.release_savepoint(transaction_id, &name)
     }
 
     pub fn rollback_to_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<TableStorage, ReefDBError> {
         let transaction = self.active_transactions.get_mut(&transaction_id)
-            .ok_or_else(|| ReefDBError::TransactionNotFound(transaction_id))?;
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
         
         if transaction.get_state() != &TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
         }
         
         // Get the savepoint state
-        let restored_state = self.savepoint_manager.lock()
-            .map_err(|_| ReefDBError::LockAcquisitionFailed("Failed to acquire savepoint manager lock".to_string()))?
-            .rollback_to_savepoint(transaction_id, name)?;
+        let (restored_state, mark) = {
+            let mut savepoint_manager = lock_recovering(&self.savepoint_manager);
+            let mark = savepoint_manager.mark(transaction_id, name)?;
+            (savepoint_manager.rollback_to_savepoint(transaction_id, name)?, mark)
+        };
+
+        // Give up the locks taken since the savepoint, and with them any waits on them
+        let released = lock_recovering(&self.lock_manager).release_locks_since(transaction_id, mark.locks);
+        lock_recovering(&self.deadlock_detector).remove_waits_on(transaction_id, &released);
         
-        // Update transaction's state
+        // Update transaction's state; rows written since the savepoint are no longer published
         transaction.restore_table_state(&restored_state);
-        
-        // Update database state
-        let mut reef_db = self.reef_db.lock()
-            .map_err(|_| ReefDBError::LockAcquisitionFailed("Failed to acquire database lock".to_string()))?;
-        reef_db.tables.restore_from(&restored_state);
-        
-        // Update storage state
-        for (table_name, (columns, rows)) in restored_state.tables.iter() {
-            reef_db.storage.insert_table(table_name.clone(), columns.clone(), rows.clone());
-        }
+        transaction.changes.truncate(mark.changes);
         
         // Write WAL entry for rollback
         let wal_entry = WALEntry {
             transaction_id,
-            timestamp: std::time::SystemTime::now(),
+            timestamp: self.clock.now(),
             operation: WALOperation::Rollback,
             table_name: String::new(),
             data: vec![],
         };
         
         self.wal.lock()
-            .map_err(|_| ReefDBError::LockAcquisitionFailed("Failed to acquire WAL lock".to_string()))?
+            .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
             .append_entry(wal_entry)?;
         
         Ok(restored_state)
@@ -311,121 +961,432 @@ where
 
     pub fn release_savepoint(&mut self, transaction_id: u64, name: &str) -> Result<(), ReefDBError> {
         let transaction = self.active_transactions.get(&transaction_id)
-            .ok_or_else(|| ReefDBError::TransactionNotFound(transaction_id))?;
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
         
         if transaction.get_state() != &TransactionState::Active {
             return Err(ReefDBError::TransactionNotActive);
         }
         
-        let mut savepoint_manager = self.savepoint_manager.lock()
-            .map_err(|_| ReefDBError::LockAcquisitionFailed("Failed to acquire savepoint manager lock".to_string()))?;
+        let mut savepoint_manager = lock_recovering(&self.savepoint_manager);
         
         savepoint_manager.release_savepoint(transaction_id, name)
     }
 
+    /// Names of the savepoints `transaction_id` currently holds, in creation order
+    pub fn list_savepoints(&self, transaction_id: u64) -> Result<Vec<String>, ReefDBError> {
+        if !self.active_transactions.contains_key(&transaction_id) {
+            return Err(ReefDBError::TransactionNotFound(transaction_id));
+        }
+        Ok(lock_recovering(&self.savepoint_manager).list_savepoints(transaction_id))
+    }
+
     fn get_transaction_guard(&mut self, transaction_id: u64) -> Result<TransactionGuard<S, FTS>, ReefDBError> {
         let transaction = self.get_transaction_mut(transaction_id)?;
-        let isolation_level = transaction.get_isolation_level();
-        Ok(TransactionGuard {
-            transaction,
-            isolation_level,
-        })
+        Ok(TransactionGuard { transaction })
     }
 
-    fn evaluate_where_clause(
+    // Row ids matching each full-text predicate of `where_clause`, keyed by column and
+    // query text. Looked up while the transaction is borrowed so scans can filter on them.
+    fn collect_fts_matches(
+        reef_db: &ReefDB<S, FTS>,
         where_clause: &WhereType,
-        row_data: &[DataValue],
-        schema: &[ColumnDef],
         table_name: &str,
-    ) -> bool {
+        matches: &mut FtsMatches,
+    ) {
         match where_clause {
-            WhereType::Regular(clause) => {
-                // Find the column in the schema
-                let col_idx = if let Some(ref clause_table) = clause.table {
-                    // If table is specified, only look in that table's columns
-                    if clause_table == table_name {
-                        schema.iter().position(|c| c.name == clause.col_name)
-                    } else {
-                        // If the table doesn't match, we might be looking at joined data
-                        // In this case, we need to look through all columns
-                        schema.iter().position(|c| c.name == clause.col_name)
-                    }
-                } else {
-                    // If no table specified, look in all columns
-                    schema.iter().position(|c| c.name == clause.col_name)
-                };
-                
-                if let Some(idx) = col_idx {
-                    clause.operator.evaluate(&row_data[idx], &clause.value)
-                } else {
-                    false
-                }
-            },
-            WhereType::FTS(_) => {
-                // FTS search is handled separately by the FTS index
-                false
-            },
-            WhereType::And(left, right) => {
-                Self::evaluate_where_clause(left, row_data, schema, table_name) &&
-                Self::evaluate_where_clause(right, row_data, schema, table_name)
-            },
-            WhereType::Or(left, right) => {
-                Self::evaluate_where_clause(left, row_data, schema, table_name) ||
-                Self::evaluate_where_clause(right, row_data, schema, table_name)
-            },
+            WhereType::FTS(clause) => {
+                let table = clause.column.table.as_deref().unwrap_or(table_name);
+                let hits = reef_db.inverted_index.search(table, &clause.column.name, &clause.query.text);
+                matches.insert((clause.column.name.clone(), clause.query.text.clone()), hits);
+            }
+            WhereType::And(left, right) | WhereType::Or(left, right) => {
+                Self::collect_fts_matches(reef_db, left, table_name, matches);
+                Self::collect_fts_matches(reef_db, right, table_name, matches);
+            }
+            WhereType::Regular(_) => {}
         }
     }
 
+    // `evaluate_where_clause` with full-text predicates answered from `collect_fts_matches`
+    // The left side is every table joined so far, so the condition may name any of them
     fn evaluate_join_condition(
         condition: &(ColumnValuePair, ColumnValuePair),
+        null_safe: bool,
         left_data: &[DataValue],
-        left_schema: &[ColumnDef],
+        left_layout: &JoinLayout,
         right_data: &[DataValue],
         right_schema: &[ColumnDef],
-        left_table: &str,
         right_table: &str,
     ) -> bool {
         let (left_pair, right_pair) = condition;
-        
-        // Get values from both tables
-        let left_value = if left_pair.table_name.is_empty() || left_pair.table_name == left_table {
-            if let Some(idx) = left_schema.iter().position(|c| c.name == left_pair.column_name) {
-                Some(&left_data[idx])
+        let value_of = |pair: &ColumnValuePair| {
+            let on_right = pair.table_name == right_table
+                && !left_layout.tables.iter().any(|(table, _, _)| *table == right_table);
+            if on_right {
+                right_schema.iter()
+                    .position(|c| c.name == pair.column_name)
+                    .map(|idx| (&right_data[idx], right_schema[idx].collation()))
             } else {
-                None
-            }
-        } else if left_pair.table_name == right_table {
-            if let Some(idx) = right_schema.iter().position(|c| c.name == left_pair.column_name) {
-                Some(&right_data[idx])
-            } else {
-                None
+                let table = Some(pair.table_name.as_str()).filter(|table| !table.is_empty());
+                left_layout.resolve(table, &pair.column_name)
+                    .ok()
+                    .map(|idx| (&left_data[idx], left_layout.column(idx).collation()))
             }
-        } else {
-            None
         };
+        let left_value = value_of(left_pair);
+        let right_value = value_of(right_pair);
 
-        let right_value = if right_pair.table_name.is_empty() || right_pair.table_name == left_table {
-            if let Some(idx) = left_schema.iter().position(|c| c.name == right_pair.column_name) {
-                Some(&left_data[idx])
-            } else {
-                None
-            }
-        } else if right_pair.table_name == right_table {
-            if let Some(idx) = right_schema.iter().position(|c| c.name == right_pair.column_name) {
-                Some(&right_data[idx])
+        // Compare the values if both were found
+        if let (Some((left_val, left_collation)), Some((right_val, right_collation))) = (left_value, right_value) {
+            let collation = left_collation.combine(right_collation);
+            if null_safe {
+                collation.evaluate(&Op::NullSafeEqual, left_val, right_val)
             } else {
-                None
+                collation.sql_eq(left_val, right_val)
             }
         } else {
-            None
+            false
+        }
+    }
+
+    fn join_key(value: &DataValue) -> Option<Vec<u8>> {
+        match value {
+            // Floats compare equal across distinct encodings (0.0 and -0.0), as do
+            // decimals of different scales, so they are left to the nested loop
+            DataValue::Float(_) | DataValue::Decimal(_) => None,
+            _ => bincode::serialize(value).ok(),
+        }
+    }
+
+    /// Resolves which side of the ON condition belongs to the joined table.
+    /// Returns the pair to probe on the left side and the pair to look up in the
+    /// joined table, or `None` if the condition does not split across both tables.
+    fn split_join_condition<'a>(
+        join: &'a JoinClause,
+        left_table: &str,
+    ) -> Option<(&'a ColumnValuePair, &'a ColumnValuePair)> {
+        let right_table = &join.table_ref.name;
+        if right_table == left_table {
+            return None;
+        }
+
+        // Mirror the column resolution of evaluate_join_condition
+        let is_left = |pair: &ColumnValuePair| pair.table_name.is_empty() || pair.table_name == left_table;
+        let (left_pair, right_pair) = &join.on;
+        if is_left(left_pair) && right_pair.table_name == *right_table {
+            Some((left_pair, right_pair))
+        } else if is_left(right_pair) && left_pair.table_name == *right_table {
+            Some((right_pair, left_pair))
+        } else {
+            None
+        }
+    }
+
+    /// Picks how a join is evaluated. A merge join is used when a B-Tree index
+    /// of the joined table gives its rows in join column order, a hash join for
+    /// any other equi-join, and the nested loop when neither applies.
+    fn choose_join_strategy(
+        methods: JoinMethods,
+        reef_db: &ReefDB<S, FTS>,
+        join: &JoinClause,
+        (joined_schema, joined_rows): &(Vec<ColumnDef>, Vec<Vec<DataValue>>),
+        left_schema: &[ColumnDef],
+        left_table: &str,
+    ) -> JoinStrategy {
+        // Hash and merge joins leave NULL keys out, which `<=>` has to match
+        if join.null_safe {
+            return JoinStrategy::NestedLoop;
+        }
+        let (probe_pair, build_pair) = match Self::split_join_condition(join, left_table) {
+            Some(pairs) => pairs,
+            None => return JoinStrategy::NestedLoop,
         };
+        let build_idx = match joined_schema.iter().position(|c| c.name == build_pair.column_name) {
+            Some(idx) => idx,
+            None => return JoinStrategy::NestedLoop,
+        };
+        // Hash and merge keys compare values as written
+        let probe_nocase = left_schema.iter()
+            .find(|c| c.name == probe_pair.column_name)
+            .is_some_and(|c| c.collation() != Collation::Binary);
+        if probe_nocase || joined_schema[build_idx].collation() != Collation::Binary {
+            return JoinStrategy::NestedLoop;
+        }
 
-        // Compare the values if both were found
-        if let (Some(left_val), Some(right_val)) = (left_value, right_value) {
-            left_val == right_val
+        if methods.merge {
+            if let Some(order) = Self::index_order(reef_db, &join.table_ref.name, &joined_schema[build_idx], joined_rows.len()) {
+                return JoinStrategy::Merge {
+                    probe_column: probe_pair.column_name.clone(),
+                    key_idx: build_idx,
+                    order,
+                };
+            }
+        }
+
+        if methods.hash {
+            if let Some(table) = Self::build_hash_join(&probe_pair.column_name, build_idx, joined_rows) {
+                return JoinStrategy::Hash(table);
+            }
+        }
+
+        JoinStrategy::NestedLoop
+    }
+
+    /// Builds a hash table over the joined table keyed on its side of the ON
+    /// condition. Returns `None` if a key cannot be hashed. NULL keys never
+    /// match, so their rows are left out.
+    fn build_hash_join(
+        probe_column: &str,
+        build_idx: usize,
+        joined_rows: &[Vec<DataValue>],
+    ) -> Option<HashJoinTable> {
+        let mut buckets: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+        for (idx, row) in joined_rows.iter().enumerate() {
+            if row[build_idx] == DataValue::Null {
+                continue;
+            }
+            let key = Self::join_key(&row[build_idx])?;
+            buckets.entry(key).or_default().push(idx);
+        }
+
+        Some((probe_column.to_string(), buckets))
+    }
+
+    /// Looks up the joined rows matching the current left row. Returns `None`
+    /// when the probe value cannot be hashed and a nested loop is required.
+    fn probe_hash_join(
+        probe_column: &str,
+        buckets: &HashMap<Vec<u8>, Vec<usize>>,
+        left_data: &[DataValue],
+        left_schema: &[ColumnDef],
+    ) -> Option<Vec<usize>> {
+        let probe_idx = match left_schema.iter().position(|c| c.name == probe_column) {
+            Some(idx) => idx,
+            None => return Some(Vec::new()),
+        };
+        if left_data[probe_idx] == DataValue::Null {
+            return Some(Vec::new());
+        }
+        let key = Self::join_key(&left_data[probe_idx])?;
+        Some(buckets.get(&key).cloned().unwrap_or_default())
+    }
+
+    /// Whether two values can be ordered consistently with their equality,
+    /// which is what a merge join relies on.
+    fn is_mergeable(value: &DataValue) -> bool {
+        matches!(
+            value,
+            DataValue::Integer(_)
+                | DataValue::Text(_)
+                | DataValue::Boolean(_)
+                | DataValue::Date(_)
+                | DataValue::Timestamp(_)
+        )
+    }
+
+    /// Positions of the `rows_read` rows of `table` in ascending order of `column`,
+    /// read off the column's B-Tree index rather than checked row by row. Only
+    /// indexes whose keys sort like the values qualify: integer columns under a
+    /// regular index and text columns under a text index. `None` when the index
+    /// doesn't cover exactly the rows read, e.g. after expired rows were skipped.
+    fn index_order(reef_db: &ReefDB<S, FTS>, table: &str, column: &ColumnDef, rows_read: usize) -> Option<Vec<usize>> {
+        let text_index = reef_db.is_text_index(table, &column.name);
+        let ordered = match column.data_type {
+            DataType::Integer => !text_index,
+            DataType::Text | DataType::Varchar(_) => text_index,
+            _ => false,
+        };
+        if !ordered || reef_db.storage.get_table_ref(table)?.1.len() != rows_read {
+            return None;
+        }
+        let Ok(IndexType::BTree(btree)) = reef_db.storage.get_index(table, &column.name) else {
+            return None;
+        };
+
+        // NULL keys never match, so their rows are left out
+        let null = BTreeIndex::key_for(&DataValue::Null);
+        let mut covered = 0;
+        let mut order = Vec::with_capacity(rows_read);
+        for (key, row_ids) in btree.iter() {
+            covered += row_ids.len();
+            if *key == null {
+                continue;
+            }
+            let start = order.len();
+            order.extend(row_ids.iter().filter_map(|id| id.checked_sub(1)));
+            // Rows under one key keep their table order
+            order[start..].sort_unstable();
+        }
+        (covered == rows_read && order.iter().all(|&idx| idx < rows_read)).then_some(order)
+    }
+
+    /// Advances the merge cursor over the joined rows in key order and returns
+    /// the run of rows whose key equals the current left value. Left rows are
+    /// probed in the order they are read: while their keys ascend the cursor
+    /// only moves forward, otherwise it binary-searches back. Returns `None`
+    /// when the probe value cannot be merged and a nested loop is required.
+    fn probe_merge_join(
+        probe_column: &str,
+        key_idx: usize,
+        joined_rows: &[Vec<DataValue>],
+        order: &[usize],
+        left_data: &[DataValue],
+        left_schema: &[ColumnDef],
+        cursor: &mut usize,
+    ) -> Option<Vec<usize>> {
+        let probe_idx = match left_schema.iter().position(|c| c.name == probe_column) {
+            Some(idx) => idx,
+            None => return Some(Vec::new()),
+        };
+        let value = &left_data[probe_idx];
+        if *value == DataValue::Null {
+            return Some(Vec::new());
+        }
+        let key = |position: usize| &joined_rows[order[position]][key_idx];
+        let key_type_matches = order.first().is_none_or(|&idx| {
+            std::mem::discriminant(&joined_rows[idx][key_idx]) == std::mem::discriminant(value)
+        });
+        if !Self::is_mergeable(value) || !key_type_matches {
+            return None;
+        }
+
+        if *cursor > 0 && key(*cursor - 1) >= value {
+            *cursor = order.partition_point(|&idx| joined_rows[idx][key_idx] < *value);
+        }
+        while *cursor < order.len() && key(*cursor) < value {
+            *cursor += 1;
+        }
+
+        let matches = (*cursor..order.len())
+            .take_while(|&position| key(position) == value)
+            .map(|position| order[position])
+            .collect();
+        Some(matches)
+    }
+
+    /// Versions whose key matches no stored row, such as a row an update moved to a new
+    /// primary key, ordered by key. Tombstones are left out.
+    fn version_only_rows(table_name: &str, rows: &[Vec<DataValue>], versions: &HashMap<String, Vec<DataValue>>) -> Vec<Vec<DataValue>> {
+        if versions.is_empty() {
+            return Vec::new();
+        }
+        let stored: HashSet<String> = rows.iter()
+            .filter_map(|row| match &row[0] {
+                DataValue::Integer(n) => Some(KeyFormat::row(table_name, 0, &n.to_string())),
+                _ => None,
+            })
+            .collect();
+        let mut extra: Vec<(&String, &Vec<DataValue>)> = versions.iter()
+            .filter(|(key, data)| !data.is_empty() && !stored.contains(*key))
+            .collect();
+        extra.sort_by(|a, b| a.0.cmp(b.0));
+        extra.into_iter().map(|(_, data)| data.clone()).collect()
+    }
+
+    // Single-table SELECT. Only the projected and ORDER BY columns are copied out of
+    // each row, and as rows are resolved independently the scan can use several threads.
+    fn select_single_table(
+        reef_db: &ReefDB<S, FTS>,
+        committed_rows: &HashMap<String, Vec<DataValue>>,
+        scan: ScanControl,
+        table_name: &str,
+        columns: &[Column],
+        where_clause: Option<&WhereType>,
+        order_by: &[OrderByClause],
+    ) -> Result<ReefDBResult, ReefDBError> {
+        let (schema, rows) = reef_db.storage.get_table_ref(table_name)
+            .ok_or_else(|| ReefDBError::TableNotFound(table_name.to_string()))?;
+        let column_info = ColumnInfo::from_schema_and_columns(schema, columns, table_name)?;
+
+        let mut fts_matches = FtsMatches::new();
+        if let Some(where_clause) = where_clause {
+            Self::collect_fts_matches(reef_db, where_clause, table_name, &mut fts_matches);
+        }
+
+        // Columns qualified with another table's name are skipped, as in the join projection
+        let in_table = |col: &Column| col.table.as_deref().is_none_or(|table| table == table_name);
+        let projection: Vec<usize> = if columns.iter().any(|c| c.name == "*") {
+            (0..schema.len()).collect()
         } else {
-            false
+            columns.iter()
+                .filter(|col| in_table(col))
+                .filter_map(|col| schema.iter().position(|c| c.name == col.name))
+                .collect()
+        };
+        // ORDER BY values are carried after the projected ones and cut off once sorted
+        let sort_keys: Vec<(usize, &OrderDirection)> = order_by.iter()
+            .filter(|clause| in_table(&clause.column))
+            .map(|clause| {
+                schema.iter().position(|c| c.name == clause.column.name)
+                    .map(|idx| (idx, &clause.direction))
+                    .ok_or_else(|| ReefDBError::ColumnNotFound(clause.column.name.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        // Without ORDER BY rows come back in primary-key order, as from `ReefDB::query`
+        let sort_keys = if order_by.is_empty() {
+            schema.iter()
+                .position(|c| c.constraints.contains(&Constraint::PrimaryKey))
+                .map(|idx| vec![(idx, &OrderDirection::Asc)])
+                .unwrap_or_default()
+        } else {
+            sort_keys
+        };
+
+        let stats = reef_db.table_statistics(table_name);
+        let compiled = where_clause.map(|where_clause| {
+            CompiledWhere::compile(where_clause, schema, &fts_matches).reorder(schema, stats.as_ref())
+        });
+        let expiry = reef_db.expiry(schema);
+        let scan_row = |i: usize, data: &[DataValue]| {
+            let include = !expiry.is_expired(data)
+                && compiled.as_ref().is_none_or(|compiled| compiled.matches(data));
+            include.then(|| {
+                let values = projection.iter()
+                    .chain(sort_keys.iter().map(|(idx, _)| idx))
+                    .map(|&idx| data[idx].clone())
+                    .collect::<Vec<_>>();
+                (i, values)
+            })
+        };
+        let mut results = parallel_filter_map(rows, Self::scan_threads(scan.threads, rows.len()), scan.cancel, |i, row| {
+            // Get the ID from the first column (primary key)
+            let id = match &row[0] {
+                DataValue::Integer(n) => n.to_string(),
+                _ => return None,
+            };
+            // Prefer the latest visible version; rows without one are read as stored
+            // and rows whose version is a tombstone are gone
+            let data = committed_rows.get(&KeyFormat::row(table_name, 0, &id)).unwrap_or(row);
+            if data.is_empty() {
+                return None;
+            }
+            scan_row(i, data)
+        })?;
+        let moved = Self::version_only_rows(table_name, rows, committed_rows);
+        results.extend(moved.iter().enumerate().filter_map(|(j, data)| scan_row(rows.len() + j, data)));
+
+        if !sort_keys.is_empty() {
+            results.sort_by(|a, b| {
+                for (offset, (idx, direction)) in sort_keys.iter().enumerate() {
+                    let pos = projection.len() + offset;
+                    let cmp = schema[*idx].compare(&a.1[pos], &b.1[pos]).unwrap_or(Ordering::Less);
+                    if cmp != Ordering::Equal {
+                        return match direction {
+                            OrderDirection::Desc => cmp.reverse(),
+                            OrderDirection::Asc => cmp,
+                        };
+                    }
+                }
+                Ordering::Equal
+            });
+            for (_, values) in &mut results {
+                values.truncate(projection.len());
+            }
         }
+
+        scan.metrics.rows_scanned(table_name, rows.len());
+        scan.metrics.rows_returned(results.len());
+        Ok(ReefDBResult::Select(QueryResult::with_columns(results, column_info)))
     }
 
     fn sort_results(
@@ -434,117 +1395,134 @@ where
         order_by: &[OrderByClause],
         schema: &[ColumnDef],
         table_name: &str,
-        joined_tables: &[(JoinClause, (Vec<ColumnDef>, Vec<Vec<DataValue>>))],
-    ) -> Vec<(usize, Vec<DataValue>)> {
-        if order_by.is_empty() || results.is_empty() {
-            return results;
+        joined_tables: &[JoinedTable],
+    ) -> Result<Vec<(usize, Vec<DataValue>)>, ReefDBError> {
+        if order_by.is_empty() {
+            return Ok(results);
         }
 
-        results.sort_by(|a, b| {
-            for order_clause in order_by {
-                let col_name = &order_clause.column.name;
-                
-                // Find the column index in the result values
-                let col_idx = match &order_clause.column.table {
-                    Some(table) => {
-                        // For columns with explicit table references
-                        if table == table_name {
-                            // Column is from the main table
-                            schema.iter().position(|c| c.name == *col_name)
-                        } else {
-                            // Column is from a joined table
-                            joined_tables.iter()
-                                .find(|(join, _)| join.table_ref.name == *table)
-                                .and_then(|(_, (schema, _))| schema.iter().position(|c| c.name == *col_name))
-                                .map(|pos| pos + schema.len())
-                        }
-                    },
-                    None => {
-                        // For columns without table references, find the first matching column
-                        schema.iter().position(|c| c.name == *col_name).or_else(|| {
-                            joined_tables.iter()
-                                .find_map(|(_, (schema, _))| {
-                                    schema.iter().position(|c| c.name == *col_name)
-                                        .map(|pos| pos + schema.len())
-                                })
-                        })
-                    }
-                };
+        // Resolve every key before sorting so an unknown column is an error, not a no-op
+        let layout = JoinLayout::new(table_name, schema, joined_tables);
+        let keys = order_by.iter()
+            .map(|clause| {
+                let idx = layout.resolve(clause.column.table.as_deref(), &clause.column.name)?;
+                Ok((idx, layout.column(idx), &clause.direction))
+            })
+            .collect::<Result<Vec<_>, ReefDBError>>()?;
 
-                if let Some(idx) = col_idx {
-                    if idx < a.1.len() && idx < b.1.len() {
-                        let cmp = a.1[idx].cmp(&b.1[idx]);
-                        if cmp != Ordering::Equal {
-                            return match order_clause.direction {
-                                OrderDirection::Desc => cmp.reverse(),
-                                OrderDirection::Asc => cmp,
-                            };
-                        }
-                    }
+        results.sort_by(|a, b| {
+            for (idx, column, direction) in &keys {
+                let cmp = column.compare(&a.1[*idx], &b.1[*idx]).unwrap_or(Ordering::Less);
+                if cmp != Ordering::Equal {
+                    return match direction {
+                        OrderDirection::Desc => cmp.reverse(),
+                        OrderDirection::Asc => cmp,
+                    };
                 }
             }
             Ordering::Equal
         });
 
-        results
+        Ok(results)
     }
 
+    /// Runs `stmt` in the transaction. Writes first take exclusive locks on the rows they
+    /// touch, or on the whole table for MERGE and DDL, so a conflicting transaction gets
+    /// `LockConflict` or `Deadlock`. Reads take no locks.
     pub fn execute_statement(&mut self, transaction_id: u64, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
+        let transaction = self.active_transactions.get(&transaction_id)
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
+        if transaction.get_state() != &TransactionState::Active {
+            return Err(ReefDBError::TransactionNotActive);
+        }
+
+        if matches!(stmt, Statement::ShowLocks) {
+            return Ok(ReefDB::<S, FTS>::show_locks_result(self.lock_snapshot()));
+        }
+        // No other transaction can see a temporary table, so there's nothing to lock
+        if !self.targets_temporary_table(transaction_id, &stmt) {
+            self.lock_for_statement(transaction_id, &stmt)?;
+        }
+        self.run_statement(transaction_id, stmt)
+    }
+
+    fn run_statement(&mut self, transaction_id: u64, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
+        if let Statement::Select(SelectStatement::Limit(limit, query)) = stmt {
+            let (query, ties) = ReefDB::<S, FTS>::prepare_row_limit(limit, *query)?;
+            return match self.execute_statement(transaction_id, Statement::Select(query))? {
+                ReefDBResult::Select(result) => Ok(ReefDBResult::Select(ReefDB::<S, FTS>::limit_rows(limit, &ties, result)?)),
+                other => Ok(other),
+            };
+        }
+        // An AS OF read is the plain SELECT reading historical versions instead
+        let (stmt, as_of) = match stmt {
+            Statement::Select(SelectStatement::AsOf(at, query)) => match *query {
+                query @ SelectStatement::FromTable(..) => (Statement::Select(query), Some(at)),
+                _ => return Err(ReefDBError::Other(
+                    "AS OF is only supported on a SELECT without GROUP BY or DISTINCT ON".to_string()
+                )),
+            },
+            stmt => (stmt, None),
+        };
+        // Temporary tables only exist in the transaction's own copy of the database, so
+        // statements on them bypass the MVCC versions and the WAL
+        if self.targets_temporary_table(transaction_id, &stmt) {
+            let transaction = self.get_transaction(transaction_id)?;
+            return transaction.execute_statement(stmt);
+        }
         match stmt {
             Statement::Create(create_stmt) => {
                 let transaction = self.get_transaction(transaction_id)?;
                 transaction.execute_statement(Statement::Create(create_stmt))
             }
-            Statement::Insert(insert_stmt) => {
-                let transaction = self.get_transaction(transaction_id)?;
-                transaction.execute_statement(Statement::Insert(insert_stmt))
-            }
+            // Logged as stored, with defaults and generated columns filled in
+            Statement::Insert(insert) => self.execute_logged(transaction_id, Statement::Insert(insert)),
             Statement::Update(UpdateStatement::UpdateTable(table_name, updates, where_clause)) => {
+                // Rows this transaction already updated are updated again from its own version
+                let visible_rows = lock_recovering(&self.mvcc_manager)
+                    .transaction_snapshot(transaction_id, &table_name);
+
                 // First get the transaction guard
-                let mut guard = self.get_transaction_guard(transaction_id)?;
-                
-                // Handle serializable mode if needed
-                if guard.isolation_level == IsolationLevel::Serializable {
-                    let snapshot = guard.transaction.acid_manager.get_committed_snapshot();
-                    let mut final_state = snapshot.clone();
-                    final_state.restore_from(&guard.transaction.reef_db.tables);
-                    guard.transaction.reef_db.tables.restore_from(&final_state);
-                }
+                let guard = self.get_transaction_guard(transaction_id)?;
+                guard.transaction.dirty = true;
 
                 // Get table data
                 let table_data = guard.transaction.reef_db.storage.get_table_ref(&table_name)
                     .ok_or_else(|| ReefDBError::TableNotFound(table_name.clone()))?;
-                let (schema, rows) = table_data.clone(); // Clone to avoid lifetime issues
-                
-                // Drop the guard before getting the MVCC manager
-                drop(guard);
-
-                // Now get the MVCC manager
-                let mut mvcc_manager = self.mvcc_manager.lock()
-                    .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?;
-                
-                let mut updated_count = 0;
+                let (schema, mut rows) = table_data.clone(); // Clone to avoid lifetime issues
+                rows.extend(Self::version_only_rows(&table_name, &rows, &visible_rows));
+                let mut updates = updates;
+                ReefDB::<S, FTS>::check_assignments(&schema, &mut updates)?;
 
-                // Process each row
+                // Build the new versions while the transaction is borrowed, since
+                // generated columns are recomputed by its evaluator. Each is
+                // (old key, new key, old row, new row); the keys differ when the
+                // primary key changes.
+                let mut new_versions = Vec::new();
+                let mut untouched_keys = HashSet::new();
+                let mut untouched_rows = Vec::new();
+                let scanned = rows.len();
+                let no_fts = FtsMatches::new();
+                let stats = guard.transaction.reef_db.table_statistics(&table_name);
+                let compiled = where_clause.as_ref().map(|where_clause| {
+                    CompiledWhere::compile(where_clause, &schema, &no_fts).reorder(&schema, stats.as_ref())
+                });
                 for row in rows {
+                    guard.transaction.cancellation.check()?;
                     // Get the ID from the first column (primary key)
                     let id = match &row[0] {
                         DataValue::Integer(n) => n.to_string(),
                         _ => continue,
                     };
                     let key = KeyFormat::row(&table_name, 0, &id);
-                    
+                    let row = visible_rows.get(&key).cloned().unwrap_or(row);
+                    // Deleted, or moved to another key, earlier on
+                    if row.is_empty() {
+                        continue;
+                    }
+
                     // Check where clause
-                    let should_update = if let Some(ref where_clause) = where_clause {
-                        Self::evaluate_where_clause(
-                            where_clause,
-                            &row,
-                            &schema,
-                            &table_name,
-                        )
-                    } else {
-                        true
-                    };
+                    let should_update = compiled.as_ref().is_none_or(|compiled| compiled.matches(&row));
 
                     if should_update {
                         // Create a new version with the updated values
@@ -554,39 +1532,144 @@ where
                                 new_data[col_idx] = new_value.clone();
                             }
                         }
-                        
-                        // Write the new version using MVCC
-                        mvcc_manager.write(transaction_id, key, new_data)?;
-                        updated_count += 1;
+                        guard.transaction.reef_db.compute_generated_columns(&schema, &mut new_data)?;
+                        let new_key = match &new_data[0] {
+                            DataValue::Integer(n) => KeyFormat::row(&table_name, 0, &n.to_string()),
+                            other => return Err(ReefDBError::Other(format!(
+                                "Primary key of {} must be an integer, got {:?}", table_name, other
+                            ))),
+                        };
+                        new_versions.push((key, new_key, row, new_data));
+                    } else {
+                        untouched_keys.insert(key);
+                        untouched_rows.push(row);
                     }
                 }
 
-                Ok(ReefDBResult::Update(updated_count))
-            }
-            Statement::Delete(delete_stmt) => {
-                let transaction = self.get_transaction(transaction_id)?;
-                transaction.execute_statement(Statement::Delete(delete_stmt))
+                // Key columns and unique indexes hold each value once among the rows the
+                // transaction sees after the update, as ReefDB updates check them. Deferred
+                // key columns wait for the commit, which checks every written row.
+                if !new_versions.is_empty() {
+                    let reef_db = &guard.transaction.reef_db;
+                    let after = || untouched_rows.iter().chain(new_versions.iter().map(|(_, _, _, new_data)| new_data));
+                    for (i, column) in schema.iter().enumerate() {
+                        if ReefDB::<S, FTS>::is_key(column) && !column.is_deferred() && updates.iter().any(|(name, _)| *name == column.name) {
+                            ReefDB::<S, FTS>::check_unique(&table_name, column, i, after())?;
+                        }
+                    }
+                    for index in reef_db.unique_indexes.get(&table_name).into_iter().flatten() {
+                        let mut seen = HashSet::new();
+                        for row in after() {
+                            let value = reef_db.index_value(&table_name, index, &schema, row)?;
+                            if value != DataValue::Null && !seen.insert(BTreeIndex::key_for(&value)) {
+                                return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, index, value)));
+                            }
+                        }
+                    }
+                }
+
+                // Drop the guard before getting the MVCC manager
+                drop(guard);
+                self.metrics.rows_scanned(&table_name, scanned);
+
+                // A changed primary key may not land on a row that keeps its key, or on
+                // the key another updated row ends up with
+                let mut taken = untouched_keys;
+                for (_, new_key, _, new_data) in &new_versions {
+                    if !taken.insert(new_key.clone()) {
+                        return Err(ReefDBError::DuplicateKey(format!("{}.{} = {:?}", table_name, schema[0].name, new_data[0])));
+                    }
+                }
+
+                // Now get the MVCC manager
+                let mut mvcc_manager = lock_recovering(&self.mvcc_manager);
+
+                // Storage gets the new rows at commit, so reads as of earlier times need
+                // what the keys held before
+                for (key, new_key, old_data, _) in &new_versions {
+                    mvcc_manager.record_original(key, old_data.clone());
+                    if key != new_key {
+                        mvcc_manager.record_original(new_key, Vec::new());
+                    }
+                }
+
+                // Tombstones go first so a row can move onto a key another row just left
+                let mut moved_from = Vec::new();
+                for (key, new_key, old_data, _) in &new_versions {
+                    if key != new_key {
+                        mvcc_manager.delete(transaction_id, key.clone())?;
+                        moved_from.push(old_data.clone());
+                    }
+                }
+                let mut updated_rows = Vec::new();
+                let mut moved_to = Vec::new();
+                for (key, new_key, _, new_data) in new_versions {
+                    // Write the new version using MVCC
+                    mvcc_manager.write(transaction_id, new_key.clone(), new_data.clone())?;
+                    if key == new_key {
+                        updated_rows.push(new_data);
+                    } else {
+                        moved_to.push(new_data);
+                    }
+                }
+                drop(mvcc_manager);
+
+                // A moved row is logged as deleted under its old key and inserted under the new one
+                let updated = updated_rows.len() + moved_to.len();
+                self.log_row_changes(transaction_id, WALOperation::Update, &table_name, updated_rows)?;
+                self.log_row_changes(transaction_id, WALOperation::Delete, &table_name, moved_from)?;
+                self.log_row_changes(transaction_id, WALOperation::Insert, &table_name, moved_to)?;
+                Ok(ReefDBResult::Update(updated))
             }
+            Statement::Merge(merge) => self.execute_logged(transaction_id, Statement::Merge(merge)),
+            Statement::Delete(delete) => self.execute_logged(transaction_id, Statement::Delete(delete)),
             Statement::Drop(drop_stmt) => {
                 let transaction = self.get_transaction(transaction_id)?;
                 transaction.execute_statement(Statement::Drop(drop_stmt))
             }
             Statement::Select(SelectStatement::FromTable(table_ref, columns, where_clause, joins, order_by)) => {
+                // Snapshot the committed row versions, plus this transaction's own writes, and
+                // release the MVCC lock before scanning, so concurrent readers don't serialize on it.
+                // AS OF reads take the versions committed by then instead.
+                let committed_rows = match as_of {
+                    Some(at) => lock_recovering(&self.mvcc_manager)
+                        .snapshot_as_of(&table_ref.name, at)
+                        .ok_or_else(|| ReefDBError::SnapshotTooOld(format!(
+                            "history of {} at {} is no longer retained",
+                            table_ref.name, DateTime::<Utc>::from(at).to_rfc3339()
+                        )))?,
+                    None => lock_recovering(&self.mvcc_manager)
+                        .transaction_snapshot(transaction_id, &table_ref.name),
+                };
+                let parallel_scan_threads = self.parallel_scan_threads;
+                let metrics = self.metrics.clone();
+                let join_methods = JoinMethods { merge: self.merge_join_enabled, hash: self.hash_join_enabled };
+                let max_join_rows = self.max_join_rows;
+
                 // First get the transaction guard and storage data
                 let guard = self.get_transaction_guard(transaction_id)?;
 
-                // Handle serializable mode if needed
-                if guard.isolation_level == IsolationLevel::Serializable {
-                    let snapshot = guard.transaction.acid_manager.get_committed_snapshot();
-                    guard.transaction.reef_db.tables.restore_from(&snapshot);
+                let cancel = guard.transaction.cancellation.clone();
+                if joins.is_empty() {
+                    return Self::select_single_table(
+                        &guard.transaction.reef_db,
+                        &committed_rows,
+                        ScanControl { threads: parallel_scan_threads, cancel: &cancel, metrics: metrics.as_ref() },
+                        &table_ref.name,
+                        &columns,
+                        where_clause.as_ref(),
+                        &order_by,
+                    );
                 }
 
                 // Get table data and clone what we need
                 let table_data = guard.transaction.reef_db.storage.get_table_ref(&table_ref.name)
                     .ok_or_else(|| ReefDBError::TableNotFound(table_ref.name.clone()))?;
                 let schema = table_data.0.to_vec();
-                let rows = table_data.1.to_vec();
-                let current_isolation_level = guard.isolation_level.clone();
+                let mut rows = table_data.1.to_vec();
+                rows.extend(Self::version_only_rows(&table_ref.name, &rows, &committed_rows));
+                let expiry = guard.transaction.reef_db.expiry(&schema);
+                rows.retain(|row| !expiry.is_expired(row));
 
                 // Get all joined table data upfront
                 let mut joined_tables = Vec::new();
@@ -595,240 +1678,186 @@ where
                     let joined_table = guard.transaction.reef_db.storage.get_table_ref(&join.table_ref.name)
                         .ok_or_else(|| ReefDBError::TableNotFound(join.table_ref.name.clone()))?;
                     joined_schemas.push((join.table_ref.name.as_str(), joined_table.0.as_slice()));
-                    joined_tables.push((join.clone(), (joined_table.0.to_vec(), joined_table.1.to_vec())));
+                    let join_expiry = guard.transaction.reef_db.expiry(&joined_table.0);
+                    let joined_rows = joined_table.1.iter().filter(|row| !join_expiry.is_expired(row)).cloned().collect();
+                    joined_tables.push((join.clone(), (joined_table.0.to_vec(), joined_rows)));
                 }
 
                 // Create column info for all tables
-                let column_info = if joins.is_empty() {
-                    ColumnInfo::from_schema_and_columns(&schema, &columns, &table_ref.name)?
+                let column_info = ColumnInfo::from_joined_schemas(&schema, &table_ref.name, &joined_schemas, &columns)?;
+
+                let layout = JoinLayout::new(&table_ref.name, &schema, &joined_tables);
+                // What each join sees on its left: the FROM table and the joins before it
+                let left_layouts: Vec<JoinLayout> = (0..joined_tables.len())
+                    .map(|join_idx| JoinLayout::new(&table_ref.name, &schema, &joined_tables[..join_idx]))
+                    .collect();
+                let projection = if columns.iter().any(|c| c.name == "*") {
+                    None
                 } else {
-                    ColumnInfo::from_joined_schemas(&schema, &table_ref.name, &joined_schemas, &columns)?
+                    Some(columns.iter()
+                        .map(|col| layout.resolve(col.table.as_deref(), &col.name))
+                        .collect::<Result<Vec<_>, _>>()?)
                 };
 
-                // Get the MVCC manager
-                let mut mvcc_manager = self.mvcc_manager.lock()
-                    .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?;
-                
                 let mut results = Vec::new();
 
-                // Process each row
-                for (i, row) in rows.iter().enumerate() {
-                    // Get the ID from the first column (primary key)
-                    let id = match &row[0] {
-                        DataValue::Integer(n) => n.to_string(),
-                        _ => continue,
-                    };
-                    let key = KeyFormat::row(&table_ref.name, 0, &id);
-                    
-                    // Read MVCC data - use read_committed to ensure we see committed changes
-                    let data = if current_isolation_level == IsolationLevel::ReadCommitted {
-                        match mvcc_manager.read_committed(transaction_id, &key)? {
-                            Some(data) => data,
-                            None => {
-                                // If no committed version exists, check for uncommitted changes
-                                match mvcc_manager.read_uncommitted(&key)? {
-                                    Some(_) => row.clone(), // If there are uncommitted changes, use original row
-                                    None => row.clone()     // If no changes at all, use original row
-                                }
-                            }
-                        }
-                    } else {
-                        match mvcc_manager.read_committed(transaction_id, &key)? {
-                            Some(data) => data,
-                            None => row.clone()
-                        }
-                    };
+                // Decide per join whether a merge, hash or nested-loop join is used
+                let join_strategies: Vec<_> = joined_tables.iter()
+                    .map(|(join, joined)| {
+                        Self::choose_join_strategy(join_methods, &guard.transaction.reef_db, join, joined, &schema, &table_ref.name)
+                    })
+                    .collect();
+                let mut merge_cursors = vec![0usize; joined_tables.len()];
+                let mut intermediate_rows = 0usize;
+                // Joined rows that matched some row on their left, for RIGHT and FULL joins
+                let mut joined_matched: Vec<Vec<bool>> = joined_tables.iter()
+                    .map(|(_, (_, joined_rows))| vec![false; joined_rows.len()])
+                    .collect();
 
-                    // Handle joins if present
-                    let mut matched_rows = vec![(data.clone(), schema.clone())];
-                    
-                    for (join, (joined_schema, joined_rows)) in &joined_tables {
+                // Joins the rows built so far with the tables from `first_join` on. LEFT and
+                // FULL joins keep a row without a match, padded with NULLs for the joined table.
+                let mut join_from = |first_join: usize,
+                                     mut matched_rows: PartialJoinRows,
+                                     joined_matched: &mut [Vec<bool>]|
+                 -> Result<PartialJoinRows, ReefDBError> {
+                    for (join_idx, ((join, (joined_schema, joined_rows)), strategy)) in joined_tables.iter().zip(&join_strategies).enumerate().skip(first_join) {
                         let mut new_matched_rows = Vec::new();
-                        
+                    
                         for (curr_row, curr_schema) in matched_rows {
-                            for joined_row in joined_rows {
-                                let should_join = Self::evaluate_join_condition(
-                                    &join.on,
+                            let candidates = match strategy {
+                                JoinStrategy::Hash((probe_column, buckets)) => Self::probe_hash_join(
+                                    probe_column,
+                                    buckets,
+                                    &curr_row,
+                                    &curr_schema,
+                                ),
+                                JoinStrategy::Merge { probe_column, key_idx, order } => Self::probe_merge_join(
+                                    probe_column,
+                                    *key_idx,
+                                    joined_rows,
+                                    order,
                                     &curr_row,
                                     &curr_schema,
-                                    joined_row,
-                                    joined_schema,
-                                    &table_ref.name,
-                                    &join.table_ref.name,
-                                );
-                                
-                                if should_join {
-                                    let mut combined_row = curr_row.clone();
-                                    combined_row.extend(joined_row.clone());
-                                    
-                                    let mut combined_schema = curr_schema.clone();
-                                    combined_schema.extend(joined_schema.clone());
-                                    
-                                    // Check where clause on the complete joined data
-                                    let should_include = if let Some(ref where_clause) = where_clause {
-                                        let mut result = true;
-                                        match where_clause {
-                                            WhereType::Regular(clause) => {
-                                                // Find the column in the schema
-                                                let col_idx = if let Some(ref clause_table) = clause.table {
-                                                    // If table is specified, find the correct schema section
-                                                    let (schema_start, schema_len) = if clause_table == &table_ref.name {
-                                                        (0, schema.len())
-                                                    } else {
-                                                        let mut start = schema.len();
-                                                        let mut len = 0;
-                                                        for (join_info, (join_schema, _)) in &joined_tables {
-                                                            if &join_info.table_ref.name == clause_table {
-                                                                len = join_schema.len();
-                                                                break;
-                                                            }
-                                                            start += join_schema.len();
-                                                        }
-                                                        (start, len)
-                                                    };
-                                                    
-                                                    // Add safety check for schema boundaries
-                                                    if schema_start >= combined_schema.len() {
-                                                        None
-                                                    } else {
-                                                        let end = std::cmp::min(schema_start + schema_len, combined_schema.len());
-                                                        combined_schema[schema_start..end]
-                                                            .iter()
-                                                            .position(|c| c.name == clause.col_name)
-                                                            .map(|pos| schema_start + pos)
-                                                    }
-                                                } else {
-                                                    // If no table specified, look in all columns
-                                                    combined_schema.iter().position(|c| c.name == clause.col_name)
-                                                };
-
-                                                if let Some(idx) = col_idx {
-                                                    result = clause.operator.evaluate(&combined_row[idx], &clause.value);
-                                                } else {
-                                                    result = false;
-                                                }
-                                            }
-                                            WhereType::And(left, right) => {
-                                                result = Self::evaluate_where_clause(left, &combined_row, &combined_schema, &table_ref.name) &&
-                                                        Self::evaluate_where_clause(right, &combined_row, &combined_schema, &table_ref.name);
-                                            }
-                                            WhereType::Or(left, right) => {
-                                                result = Self::evaluate_where_clause(left, &combined_row, &combined_schema, &table_ref.name) ||
-                                                        Self::evaluate_where_clause(right, &combined_row, &combined_schema, &table_ref.name);
-                                            }
-                                            WhereType::FTS(_) => {
-                                                result = false;
-                                            }
-                                        }
-                                        result
-                                    } else {
-                                        true
-                                    };
-
-                                    if should_include {
-                                        new_matched_rows.push((combined_row, combined_schema));
+                                    &mut merge_cursors[join_idx],
+                                ),
+                                JoinStrategy::NestedLoop => None,
+                            };
+                            // Fall back to a nested-loop scan when neither fast path applies
+                            let candidates = candidates.unwrap_or_else(|| {
+                                (0..joined_rows.len())
+                                    .filter(|&idx| Self::evaluate_join_condition(
+                                        &join.on,
+                                        join.null_safe,
+                                        &curr_row,
+                                        &left_layouts[join_idx],
+                                        &joined_rows[idx],
+                                        joined_schema,
+                                        &join.table_ref.name,
+                                    ))
+                                    .collect()
+                            });
+
+                            let padding = vec![DataValue::Null; joined_schema.len()];
+                            let unmatched = (candidates.is_empty() && matches!(join.join_type, JoinType::Left | JoinType::Full))
+                                .then_some(&padding);
+                            for joined_row in candidates.iter().map(|&idx| &joined_rows[idx]).chain(unmatched) {
+                                let mut combined_row = curr_row.clone();
+                                combined_row.extend(joined_row.clone());
+                            
+                                let mut combined_schema = curr_schema.clone();
+                                combined_schema.extend(joined_schema.clone());
+                            
+                                new_matched_rows.push((combined_row, combined_schema));
+                                intermediate_rows += 1;
+                                if let Some(limit) = max_join_rows {
+                                    if intermediate_rows > limit {
+                                        return Err(ReefDBError::JoinTooLarge(limit));
                                     }
                                 }
                             }
+                            for idx in candidates {
+                                joined_matched[join_idx][idx] = true;
+                            }
                         }
                         matched_rows = new_matched_rows;
                     }
+                    Ok(matched_rows)
+                };
 
-                    // Process each matched row
+                // WHERE sees the complete joined row, so it may name any table's columns
+                let mut keep = |i: usize, matched_rows: PartialJoinRows| -> Result<(), ReefDBError> {
                     for (joined_data, _) in matched_rows {
-                        results.push((i, joined_data));
+                        layout.check_row(&joined_data)?;
+                        let include = match &where_clause {
+                            Some(where_clause) => layout.evaluate_where(where_clause, &joined_data)?,
+                            None => true,
+                        };
+                        if include {
+                            results.push((i, joined_data));
+                        }
+                    }
+                    Ok(())
+                };
+
+                // Process each row
+                for (i, row) in rows.iter().enumerate() {
+                    cancel.check()?;
+                    // Get the ID from the first column (primary key)
+                    let id = match &row[0] {
+                        DataValue::Integer(n) => n.to_string(),
+                        _ => continue,
+                    };
+                    let key = KeyFormat::row(&table_ref.name, 0, &id);
+                
+                    // Prefer the latest visible version; rows without one are read as stored
+                    let data = committed_rows.get(&key).cloned().unwrap_or_else(|| row.clone());
+                    if data.is_empty() {
+                        continue;
+                    }
+
+                    let matched_rows = join_from(0, vec![(data, schema.clone())], &mut joined_matched)?;
+                    keep(i, matched_rows)?;
+                }
+
+                // RIGHT and FULL joins add the joined rows nothing matched, with NULLs for
+                // every table to their left, and carry them through the joins that follow
+                let mut padded = rows.len();
+                for (join_idx, (join, (joined_schema, joined_rows))) in joined_tables.iter().enumerate() {
+                    if !matches!(join.join_type, JoinType::Right | JoinType::Full) {
+                        continue;
+                    }
+                    let left_schema: Vec<ColumnDef> = left_layouts[join_idx].tables.iter()
+                        .flat_map(|(_, _, table_schema)| table_schema.iter().cloned())
+                        .collect();
+                    let unmatched: Vec<usize> = (0..joined_rows.len()).filter(|&idx| !joined_matched[join_idx][idx]).collect();
+                    for idx in unmatched {
+                        cancel.check()?;
+                        let mut padded_row = vec![DataValue::Null; left_layouts[join_idx].width];
+                        padded_row.extend(joined_rows[idx].clone());
+                        let mut padded_schema = left_schema.clone();
+                        padded_schema.extend(joined_schema.clone());
+                        let matched_rows = join_from(join_idx + 1, vec![(padded_row, padded_schema)], &mut joined_matched)?;
+                        keep(padded, matched_rows)?;
+                        padded += 1;
                     }
                 }
 
                 // Sort results if order by clauses are present
-                results = self.sort_results(results, &order_by, &schema, &table_ref.name, &joined_tables);
+                results = self.sort_results(results, &order_by, &schema, &table_ref.name, &joined_tables)?;
 
                 // Project columns after sorting
-                let mut projected_results = Vec::new();
-                for (i, joined_data) in results {
-                    let mut projected = Vec::new();
-                    if columns.iter().any(|c| c.name == "*") {
-                        projected = joined_data;
-                    } else {
-                        for col in &columns {
-                            let col_value = if let Some(table) = &col.table {
-                                // Find column in specific table's schema
-                                let (schema_start, schema_len) = if table == &table_ref.name {
-                                    (0, schema.len())
-                                } else {
-                                    let mut start = schema.len();
-                                    let mut found = false;
-                                    let mut len = 0;
-                                    for (join, (join_schema, _)) in &joined_tables {
-                                        if &join.table_ref.name == table {
-                                            len = join_schema.len();
-                                            found = true;
-                                            break;
-                                        }
-                                        start += join_schema.len();
-                                    }
-                                    if !found {
-                                        (0, 0) // Table not found
-                                    } else {
-                                        (start, len)
-                                    }
-                                };
-                                
-                                // Ensure we don't exceed the data boundaries
-                                if schema_start < joined_data.len() {
-                                    let end = std::cmp::min(schema_start + schema_len, joined_data.len());
-                                    let schema_slice = if schema_start < schema.len() {
-                                        &schema[schema_start..std::cmp::min(schema_start + schema_len, schema.len())]
-                                    } else {
-                                        for (join, (join_schema, _)) in &joined_tables {
-                                            if &join.table_ref.name == table {
-                                                if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
-                                                    if schema_start + idx < joined_data.len() {
-                                                        projected.push(joined_data[schema_start + idx].clone());
-                                                    }
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        &[]
-                                    };
-                                    
-                                    if let Some(idx) = schema_slice.iter().position(|c| c.name == col.name) {
-                                        Some(joined_data[schema_start + idx].clone())
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else {
-                                // Try to find column in any table
-                                if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
-                                    Some(joined_data[idx].clone())
-                                } else {
-                                    // Try joined tables
-                                    let mut start = schema.len();
-                                    for (_, (join_schema, _)) in &joined_tables {
-                                        if let Some(idx) = join_schema.iter().position(|c| c.name == col.name) {
-                                            if start + idx < joined_data.len() {
-                                                projected.push(joined_data[start + idx].clone());
-                                                break;
-                                            }
-                                        }
-                                        start += join_schema.len();
-                                    }
-                                    None
-                                }
-                            };
-                            
-                            if let Some(value) = col_value {
-                                projected.push(value);
-                            }
-                        }
-                    }
-                    projected_results.push((i, projected));
-                }
+                let projected_results = match projection {
+                    None => results,
+                    Some(projection) => parallel_filter_map(&results, Self::scan_threads(parallel_scan_threads, results.len()), &cancel, |_, (i, joined_data)| {
+                        Some((*i, projection.iter().map(|&idx| joined_data[idx].clone()).collect()))
+                    })?,
+                };
 
+                metrics.rows_scanned(&table_ref.name, rows.len());
+                for (join, (_, joined_rows)) in &joined_tables {
+                    metrics.rows_scanned(&join.table_ref.name, joined_rows.len());
+                }
+                metrics.rows_returned(projected_results.len());
                 Ok(ReefDBResult::Select(QueryResult::with_columns(projected_results, column_info)))
             }
             Statement::CreateIndex(create_index_stmt) => {
@@ -843,6 +1872,11 @@ where
                 let transaction = self.get_transaction(transaction_id)?;
                 transaction.execute_statement(Statement::Alter(alter_stmt))
             }
+            // The transaction's own database copy has no view of the lock table
+            Statement::ShowLocks => {
+                self.get_transaction(transaction_id)?;
+                Ok(ReefDB::<S, FTS>::show_locks_result(self.lock_snapshot()))
+            }
             _ => {
                 let transaction = self.get_transaction(transaction_id)?;
                 transaction.execute_statement(stmt)
@@ -852,12 +1886,11 @@ where
 
     pub fn execute_statement_committed(&mut self, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
         let reef_db = self.reef_db.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire database lock".to_string()))?;
+            .map_err(|_| ReefDBError::LockPoisoned("database lock".to_string()))?;
 
         match stmt {
             Statement::Select(SelectStatement::FromTable(table_ref, columns, where_clause, _joins, order_by)) => {
-                let mvcc_manager = self.mvcc_manager.lock()
-                    .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?;
+                let mvcc_manager = lock_recovering(&self.mvcc_manager);
 
                 // Get the table data
                 let (schema, rows) = reef_db.storage.get_table_ref(&table_ref.name)
@@ -865,6 +1898,7 @@ where
 
                 println!("MVCC Debug - Table {} has {} rows in storage", table_ref.name, rows.len());
 
+                let expiry = reef_db.expiry(schema);
                 let mut results: Vec<(usize, Vec<DataValue>)> = Vec::new();
                 for (i, row) in rows.iter().enumerate() {
                     // Get the ID from the first column (primary key)
@@ -874,11 +1908,13 @@ where
                     };
                     let key = KeyFormat::row(&table_ref.name, 0, &id);
                     println!("MVCC Debug - Checking visibility for key: {}", key);
-                    if let Ok(Some(data)) = mvcc_manager.read_committed(0, &key) {
+                    if let Ok(Some(data)) = mvcc_manager.read_committed(0, &key).map(|data| data.filter(|d| !d.is_empty())) {
                         println!("MVCC Debug - Found visible version for key: {} with data: {:?}", key, data);
                         
                         // First check if the row matches the where clause
-                        let should_include = if let Some(ref where_clause) = where_clause {
+                        let should_include = if expiry.is_expired(&data) {
+                            false
+                        } else if let Some(ref where_clause) = where_clause {
                             println!("MVCC Debug - Evaluating where clause: {:?}", where_clause);
                             println!("MVCC Debug - Row data: {:?}", data);
                             println!("MVCC Debug - Schema: {:?}", schema);
@@ -897,33 +1933,22 @@ where
                         println!("MVCC Debug - Row should be included: {}", should_include);
 
                         if should_include {
-                            // If the row matches, then select the requested columns
-                            let row_data = if columns.iter().any(|c| c.name != "*") {
-                                let mut selected_data = Vec::new();
-                                for col in &columns {
-                                    if col.name == "*" {
-                                        // Include all columns
-                                        selected_data = data.clone();
-                                        break;
-                                    }
-                                    if let Some(idx) = schema.iter().position(|c| c.name == col.name) {
-                                        selected_data.push(data[idx].clone());
-                                    }
-                                }
-                                selected_data
-                            } else {
-                                // If no specific columns or only * is specified, include all columns
-                                data.clone()
-                            };
-
-                            println!("MVCC Debug - Including row in results: {:?}", row_data);
-                            results.push((i, row_data));
+                            println!("MVCC Debug - Including row in results: {:?}", data);
+                            results.push((i, data));
                         }
                     }
                 }
 
-                // Sort results if order by clauses are present
-                results = self.sort_results(results, &order_by, schema, &table_ref.name, &[]);
+                // Sort the full rows, then select the requested columns
+                results = self.sort_results(results, &order_by, schema, &table_ref.name, &[])?;
+                if !columns.iter().any(|c| c.name == "*") {
+                    let positions: Vec<usize> = columns.iter()
+                        .filter_map(|col| schema.iter().position(|c| c.name == col.name))
+                        .collect();
+                    for (_, row) in &mut results {
+                        *row = positions.iter().map(|&idx| row[idx].clone()).collect();
+                    }
+                }
 
                 println!("MVCC Debug - Final results count: {}", results.len());
                 let column_infos = ColumnInfo::from_schema_and_columns(&schema, &columns, &table_ref.name)?;
@@ -933,98 +1958,163 @@ where
         }
     }
 
-    fn try_execute_with_retry(&mut self, transaction_id: u64, stmt: Statement, max_retries: u32) -> Result<ReefDBResult, ReefDBError> {
-        if !self.mvcc_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?
-            .is_active(transaction_id)
-        {
-            return Err(ReefDBError::TransactionNotActive);
-        }
-
+    /// Runs `body` in a fresh transaction and commits it, starting over in a new
+    /// transaction up to `max_retries` times when the body or the commit fails with
+    /// `Deadlock` or `SerializationFailure`. Any other error rolls back and is returned.
+    pub fn run_with_retry<T, F>(&mut self, isolation_level: IsolationLevel, max_retries: u32, mut body: F) -> Result<T, ReefDBError>
+    where
+        F: FnMut(&mut Self, u64) -> Result<T, ReefDBError>,
+    {
         let mut retries = 0;
         loop {
-            match self.execute_statement_internal(transaction_id, stmt.clone()) {
-                Ok(result) => return Ok(result),
-                Err(ReefDBError::Deadlock) if retries < max_retries => {
-                    // On deadlock, wait briefly with exponential backoff and retry
-                    std::thread::sleep(std::time::Duration::from_millis(10 * (1 << retries)));
+            let transaction_id = self.begin_transaction(isolation_level)?;
+            let outcome = match body(self, transaction_id) {
+                Ok(value) => self.commit_transaction(transaction_id).map(|_| value),
+                Err(e) => {
+                    if self.active_transactions.contains_key(&transaction_id) {
+                        self.rollback_transaction(transaction_id)?;
+                    }
+                    Err(e)
+                }
+            };
+            match outcome {
+                Err(ReefDBError::Deadlock(_) | ReefDBError::SerializationFailure(_)) if retries < max_retries => {
+                    std::thread::sleep(Self::retry_backoff(retries));
                     retries += 1;
-                    continue;
                 }
-                Err(e) => return Err(e),
+                outcome => return outcome,
             }
         }
     }
 
-    fn execute_statement_internal(&mut self, transaction_id: u64, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
-        // Check transaction state first
-        let transaction = self.active_transactions.get(&transaction_id)
-            .ok_or_else(|| ReefDBError::TransactionNotFound(transaction_id))?;
+    // Exponential backoff before the next attempt
+    fn retry_backoff(retries: u32) -> std::time::Duration {
+        std::time::Duration::from_millis(10 * (1 << retries.min(10)))
+    }
 
-        if transaction.get_state() != &TransactionState::Active {
-            return Err(ReefDBError::TransactionNotActive);
+    // Whether `stmt` reads or writes one of the transaction's temporary tables
+    fn targets_temporary_table(&self, transaction_id: u64, stmt: &Statement) -> bool {
+        let Some(transaction) = self.active_transactions.get(&transaction_id) else {
+            return false;
+        };
+        let tables = &transaction.reef_db.tables;
+        match stmt {
+            Statement::Insert(InsertStatement::IntoTable(table_name, _) | InsertStatement::Upsert(table_name, _, _))
+            | Statement::Update(UpdateStatement::UpdateTable(table_name, _, _))
+            | Statement::Delete(DeleteStatement::FromTable(table_name, _)) => tables.is_temporary(table_name),
+            Statement::Select(SelectStatement::FromTable(table_ref, _, _, joins, _)) => {
+                tables.is_temporary(&table_ref.name)
+                    || joins.iter().any(|join| tables.is_temporary(&join.table_ref.name))
+            }
+            Statement::Merge(merge) => tables.is_temporary(&merge.target.name) || tables.is_temporary(&merge.source.name),
+            Statement::Drop(drop_stmt) => tables.is_temporary(&drop_stmt.table_name),
+            Statement::Alter(alter_stmt) => tables.is_temporary(&alter_stmt.table_name),
+            Statement::CreateIndex(index_stmt) => tables.is_temporary(&index_stmt.table_name),
+            Statement::DropIndex(index_stmt) => tables.is_temporary(&index_stmt.table_name),
+            _ => false,
         }
+    }
 
-        let isolation_level = transaction.get_isolation_level().clone();
-        drop(transaction);
-
-        // First acquire any needed locks based on the statement type
-        match &stmt {
-            Statement::Insert(InsertStatement::IntoTable(table_name, _)) => {
-                self.acquire_lock(transaction_id, table_name, LockType::Exclusive)?;
+    // Takes the locks `stmt` needs before it runs. DML locks the rows it touches; DDL
+    // falls back to locking the whole table. SELECTs never lock, whatever the isolation
+    // level: they read MVCC versions.
+    fn lock_for_statement(&self, transaction_id: u64, stmt: &Statement) -> Result<(), ReefDBError> {
+        match stmt {
+            Statement::Insert(InsertStatement::IntoTable(table_name, values) | InsertStatement::Upsert(table_name, values, _)) => {
+                let schema = self.active_transactions.get(&transaction_id)
+                    .and_then(|tx| tx.reef_db.storage.get_schema_ref(table_name).cloned())
+                    .ok_or_else(|| ReefDBError::TableNotFound(table_name.clone()))?;
+                if let Some(pk) = values.get(Self::primary_key_index(&schema)) {
+                    self.acquire_row_lock(transaction_id, table_name, &Self::primary_key_string(pk), LockType::Exclusive)?;
+                }
             }
-            Statement::Update(UpdateStatement::UpdateTable(table_name, _, _)) => {
-                self.acquire_lock(transaction_id, table_name, LockType::Exclusive)?;
+            Statement::Update(UpdateStatement::UpdateTable(table_name, _, where_clause))
+            | Statement::Delete(DeleteStatement::FromTable(table_name, where_clause)) => {
+                for pk in self.affected_primary_keys(transaction_id, table_name, where_clause.as_ref())? {
+                    self.acquire_row_lock(transaction_id, table_name, &pk, LockType::Exclusive)?;
+                }
             }
-            Statement::Delete(DeleteStatement::FromTable(table_name, _)) => {
-                self.acquire_lock(transaction_id, table_name, LockType::Exclusive)?;
+            // A MERGE may touch any row of its target, including rows it inserts
+            Statement::Merge(merge) => {
+                self.acquire_lock(transaction_id, &merge.target.name, LockType::Exclusive)?;
             }
-            Statement::Create(CreateStatement::Table(table_name, _)) => {
+            Statement::Create(CreateStatement::Table(table_name, _, _))
+            | Statement::Create(CreateStatement::TableWithTtl(table_name, _, _, _)) => {
                 self.acquire_lock(transaction_id, table_name, LockType::Exclusive)?;
             }
-            Statement::Select(SelectStatement::FromTable(table_ref, _, _, _,_)) => {
-                // For serializable isolation, we need shared locks to prevent phantom reads
-                // But with MVCC, we don't need to acquire locks for reads since each transaction
-                // sees its own snapshot of the data
-                if isolation_level == IsolationLevel::Serializable && !self.mvcc_manager.lock()
-                    .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?
-                    .is_active(transaction_id) {
-                    self.acquire_lock(transaction_id, &table_ref.name, LockType::Shared)?;
-                }
+            Statement::Drop(drop_stmt) => {
+                self.acquire_lock(transaction_id, &drop_stmt.table_name, LockType::Exclusive)?;
+            }
+            Statement::Alter(alter_stmt) => {
+                self.acquire_lock(transaction_id, &alter_stmt.table_name, LockType::Exclusive)?;
             }
             _ => {}
         }
+        Ok(())
+    }
 
-        // Get transaction again for execution
-        let transaction = self.active_transactions.get_mut(&transaction_id)
-            .ok_or_else(|| ReefDBError::TransactionNotFound(transaction_id))?;
+    // Runs an INSERT, DELETE or MERGE in the transaction and logs the rows its copy of the
+    // database recorded writing, as the statement matched them
+    fn execute_logged(&mut self, transaction_id: u64, stmt: Statement) -> Result<ReefDBResult, ReefDBError> {
+        let transaction = self.get_transaction(transaction_id)?;
+        let result = transaction.execute_statement(stmt);
+        let written = transaction.reef_db.written_rows.replace(Vec::new()).unwrap_or_default();
+        let result = result?;
+        for change in written {
+            self.log_row_changes(transaction_id, change.operation, &change.table_name, change.rows)?;
+        }
+        Ok(result)
+    }
 
-        // For serializable mode, ensure we're using the correct snapshot
-        // from the start of the transaction for all operations
-        if isolation_level == IsolationLevel::Serializable {
-            // Get our snapshot from the start of the transaction
-            let snapshot = transaction.acid_manager.get_committed_snapshot();
-            
-            // For SELECT statements, we want to see the snapshot from when the transaction started
-            match &stmt {
-                Statement::Select(SelectStatement::FromTable(_, _, _, _,_)) => {
-                    transaction.reef_db.tables.restore_from(&snapshot);
-                }
-                _ => {
-                    // For other statements, we want to see our own changes plus the snapshot
-                    let mut final_state = snapshot.clone();
-                    final_state.restore_from(&transaction.reef_db.tables);
-                    transaction.reef_db.tables.restore_from(&final_state);
-                }
-            }
+    /// Loads `rows` into `table_name` within the transaction, bypassing statement execution.
+    /// The table is locked exclusively first, as a MERGE locks its target. Rows are
+    /// validated against the schema before any is written, indexes are updated once the
+    /// rows are in, and the whole load is logged as a single WAL entry.
+    pub fn copy_into(
+        &mut self,
+        transaction_id: u64,
+        table_name: &str,
+        rows: impl IntoIterator<Item = Vec<DataValue>>,
+    ) -> Result<usize, ReefDBError> {
+        self.get_transaction(transaction_id)?;
+        self.acquire_lock(transaction_id, table_name, LockType::Exclusive)?;
+        let rows: Vec<Vec<DataValue>> = rows.into_iter().collect();
+        let entry = WALEntry::for_rows(transaction_id, table_name, &rows, self.clock.now())?;
+        let transaction = self.get_transaction(transaction_id)?;
+        let count = transaction.copy_into(table_name, rows.clone())?;
+        transaction.changes.push(RowChange { operation: WALOperation::Insert, table_name: table_name.to_string(), rows });
+        if count > 0 {
+            self.wal.lock()
+                .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+                .append_entry(entry)?;
         }
+        Ok(count)
+    }
 
-        transaction.execute_statement(stmt)
+    // Writes one WAL entry per row touched by a DML statement, ahead of the commit record,
+    // and keeps the rows for the transaction to publish when it commits
+    fn log_row_changes(&mut self, transaction_id: u64, operation: WALOperation, table_name: &str, rows: Vec<Vec<DataValue>>) -> Result<(), ReefDBError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        {
+            let mut wal = self.wal.lock()
+                .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?;
+            for row in &rows {
+                wal.append_entry(WALEntry::for_row(transaction_id, operation.clone(), table_name, row, self.clock.now())?)?;
+            }
+        }
+        self.get_transaction(transaction_id)?.changes.push(RowChange {
+            operation,
+            table_name: table_name.to_string(),
+            rows,
+        });
+        Ok(())
     }
 
     pub fn get_transaction_state(&self, transaction_id: u64) -> Result<TableStorage, ReefDBError> {
         let transaction = self.active_transactions.get(&transaction_id)
-            .ok_or_else(|| ReefDBError::Other("Transaction not found".to_string()))?;
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))?;
         
         Ok(transaction.get_table_state())
     }
@@ -1038,9 +2128,12 @@ where
             let updated_state = reef_db.tables.clone();
             drop(reef_db); // Release the lock before updating transactions
             
-            // Update all active transactions to see the new state
+            // Update all active transactions to see the new state, except serializable
+            // ones, whose copies have to stay at their snapshot
             for tx in self.active_transactions.values_mut() {
-                if tx.get_state() == &TransactionState::Active {
+                if tx.get_state() == &TransactionState::Active
+                    && tx.get_isolation_level() != IsolationLevel::Serializable
+                {
                     tx.reef_db.tables.restore_from(&updated_state);
                     tx.acid_manager.begin_atomic(&updated_state);
                 }
@@ -1051,19 +2144,18 @@ where
     fn get_transaction(&mut self, transaction_id: u64) -> Result<&mut Transaction<S, FTS>, ReefDBError> {
         self.active_transactions
             .get_mut(&transaction_id)
-            .ok_or_else(|| ReefDBError::Other("Transaction not found".to_string()))
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))
     }
 
     // Helper methods for MVCC operations
     fn read_mvcc_data(&self, key: &str) -> Result<Option<Vec<DataValue>>, ReefDBError> {
-        let mvcc_manager = self.mvcc_manager.lock().unwrap();
+        let mvcc_manager = lock_recovering(&self.mvcc_manager);
         // Use a special system transaction ID (0) for direct reads
         mvcc_manager.read_committed(0, key)
     }
 
     fn write_mvcc_data(&self, transaction_id: u64, key: String, data: Vec<DataValue>) -> Result<(), ReefDBError> {
-        let mut mvcc_manager = self.mvcc_manager.lock()
-            .map_err(|_| ReefDBError::Other("Failed to acquire MVCC manager lock".to_string()))?;
+        let mut mvcc_manager = lock_recovering(&self.mvcc_manager);
         mvcc_manager.write(transaction_id, key, data)
     }
 
@@ -1071,7 +2163,7 @@ where
     fn get_transaction_mut(&mut self, transaction_id: u64) -> Result<&mut Transaction<S, FTS>, ReefDBError> {
         self.active_transactions
             .get_mut(&transaction_id)
-            .ok_or_else(|| ReefDBError::Other("Transaction not found".to_string()))
+            .ok_or(ReefDBError::TransactionNotFound(transaction_id))
     }
 }
 
@@ -1081,6 +2173,7 @@ mod tests {
     use tempfile::tempdir;
     use crate::InMemoryReefDB;
     use crate::sql::data_type::DataType;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
     #[test]
     fn test_transaction_manager() {
@@ -1140,6 +2233,7 @@ mod tests {
                     constraints: vec![Constraint::NotNull],
                 },
             ],
+            false,
         ));
         tm.execute_statement(tx_id, create_stmt).unwrap();
 
@@ -1148,7 +2242,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("Alice".to_string()),
+                DataValue::Text("Alice".into()),
                 DataValue::Integer(25),
             ],
         ));
@@ -1158,7 +2252,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(2),
-                DataValue::Text("Bob".to_string()),
+                DataValue::Text("Bob".into()),
                 DataValue::Integer(30),
             ],
         ));
@@ -1168,7 +2262,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(3),
-                DataValue::Text("Charlie".to_string()),
+                DataValue::Text("Charlie".into()),
                 DataValue::Integer(20),
             ],
         ));
@@ -1210,11 +2304,11 @@ mod tests {
             let rows = query_result.rows;
             assert_eq!(rows.len(), 3);
             // Check order: Bob (30), Alice (25), Charlie (20)
-            assert_eq!(rows[0].1[0], DataValue::Text("Bob".to_string()));
+            assert_eq!(rows[0].1[0], DataValue::Text("Bob".into()));
             assert_eq!(rows[0].1[1], DataValue::Integer(30));
-            assert_eq!(rows[1].1[0], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[1].1[0], DataValue::Text("Alice".into()));
             assert_eq!(rows[1].1[1], DataValue::Integer(25));
-            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".to_string()));
+            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".into()));
             assert_eq!(rows[2].1[1], DataValue::Integer(20));
         } else {
             panic!("Expected Select result");
@@ -1266,11 +2360,11 @@ mod tests {
             let rows = query_result.rows;
             assert_eq!(rows.len(), 3);
             // Check order: Charlie (20), Alice (25), Bob (30)
-            assert_eq!(rows[0].1[0], DataValue::Text("Charlie".to_string()));
+            assert_eq!(rows[0].1[0], DataValue::Text("Charlie".into()));
             assert_eq!(rows[0].1[1], DataValue::Integer(20));
-            assert_eq!(rows[1].1[0], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[1].1[0], DataValue::Text("Alice".into()));
             assert_eq!(rows[1].1[1], DataValue::Integer(25));
-            assert_eq!(rows[2].1[0], DataValue::Text("Bob".to_string()));
+            assert_eq!(rows[2].1[0], DataValue::Text("Bob".into()));
             assert_eq!(rows[2].1[1], DataValue::Integer(30));
         } else {
             panic!("Expected Select result");
@@ -1311,6 +2405,7 @@ mod tests {
                     constraints: vec![Constraint::NotNull],
                 },
             ],
+            false,
         ));
         tm.execute_statement(tx_id, create_stmt).unwrap();
 
@@ -1319,7 +2414,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(1),
-                DataValue::Text("Alice".to_string()),
+                DataValue::Text("Alice".into()),
                 DataValue::Integer(25),
             ],
         ));
@@ -1329,7 +2424,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(2),
-                DataValue::Text("Bob".to_string()),
+                DataValue::Text("Bob".into()),
                 DataValue::Integer(30),
             ],
         ));
@@ -1339,7 +2434,7 @@ mod tests {
             "users".to_string(),
             vec![
                 DataValue::Integer(3),
-                DataValue::Text("Charlie".to_string()),
+                DataValue::Text("Charlie".into()),
                 DataValue::Integer(20),
             ],
         ));
@@ -1365,6 +2460,7 @@ mod tests {
                     constraints: vec![Constraint::NotNull],
                 },
             ],
+            false,
         ));
         tm.execute_statement(tx_id, create_orders_stmt).unwrap();
 
@@ -1435,11 +2531,11 @@ mod tests {
             let rows = query_result.rows;
             assert_eq!(rows.len(), 3);
             // Check order: Bob (30), Alice (25), Charlie (20)
-            assert_eq!(rows[0].1[0], DataValue::Text("Bob".to_string()));
+            assert_eq!(rows[0].1[0], DataValue::Text("Bob".into()));
             assert_eq!(rows[0].1[1], DataValue::Integer(30));
-            assert_eq!(rows[1].1[0], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[1].1[0], DataValue::Text("Alice".into()));
             assert_eq!(rows[1].1[1], DataValue::Integer(25));
-            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".to_string()));
+            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".into()));
             assert_eq!(rows[2].1[1], DataValue::Integer(20));
         } else {
             panic!("Expected Select result");
@@ -1462,6 +2558,7 @@ mod tests {
                 },
             ),
             join_type: crate::sql::clauses::join_clause::JoinType::Inner,
+            null_safe: false,
         };
 
         let select_stmt = Statement::Select(SelectStatement::FromTable(
@@ -1514,11 +2611,11 @@ mod tests {
             let rows = query_result.rows;
             assert_eq!(rows.len(), 3);
             // Check order: Bob (30), Alice (25), Charlie (20)
-            assert_eq!(rows[0].1[0], DataValue::Text("Bob".to_string()));
+            assert_eq!(rows[0].1[0], DataValue::Text("Bob".into()));
             assert_eq!(rows[0].1[1], DataValue::Integer(30));
-            assert_eq!(rows[1].1[0], DataValue::Text("Alice".to_string()));
+            assert_eq!(rows[1].1[0], DataValue::Text("Alice".into()));
             assert_eq!(rows[1].1[1], DataValue::Integer(25));
-            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".to_string()));
+            assert_eq!(rows[2].1[0], DataValue::Text("Charlie".into()));
             assert_eq!(rows[2].1[1], DataValue::Integer(20));
         } else {
             panic!("Expected Select result");
@@ -1526,4 +2623,2252 @@ mod tests {
 
         tm.commit_transaction(tx_id).unwrap();
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_join_row_limit() {
+        let dir = tempdir().unwrap();
+        let wal_path = dir.path().join("test.wal");
+        let wal = WriteAheadLog::new(wal_path).unwrap();
+
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+        let tx_id = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+
+        // Two tables whose join column holds the same value in every row,
+        // so the join degenerates into a Cartesian product
+        for table in ["left_side", "right_side"] {
+            tm.execute_statement(tx_id, Statement::Create(CreateStatement::Table(
+                table.to_string(),
+                vec![
+                    ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+                    ColumnDef::new("grp", DataType::Integer, vec![]),
+                ],
+                false,
+            ))).unwrap();
+            for id in 1..=5 {
+                tm.execute_statement(tx_id, Statement::Insert(InsertStatement::IntoTable(
+                    table.to_string(),
+                    vec![DataValue::Integer(id), DataValue::Integer(1)],
+                ))).unwrap();
+            }
+        }
+
+        let select_stmt = Statement::Select(SelectStatement::FromTable(
+            TableReference {
+                name: "left_side".to_string(),
+                alias: None,
+            },
+            vec![Column {
+                table: None,
+                name: "*".to_string(),
+                column_type: crate::sql::column::ColumnType::Wildcard,
+            }],
+            None,
+            vec![JoinClause {
+                table_ref: TableReference {
+                    name: "right_side".to_string(),
+                    alias: None,
+                },
+                on: (
+                    ColumnValuePair::new("grp", "left_side"),
+                    ColumnValuePair::new("grp", "right_side"),
+                ),
+                join_type: crate::sql::clauses::join_clause::JoinType::Inner,
+                null_safe: false,
+            }],
+            vec![],
+        ));
+
+        // Without a limit the full product is returned
+        match tm.execute_statement(tx_id, select_stmt.clone()).unwrap() {
+            ReefDBResult::Select(query_result) => assert_eq!(query_result.rows.len(), 25),
+            _ => panic!("Expected Select result"),
+        }
+
+        tm.set_max_join_rows(Some(10));
+        assert_eq!(
+            tm.execute_statement(tx_id, select_stmt).unwrap_err(),
+            ReefDBError::JoinTooLarge(10)
+        );
+
+        tm.commit_transaction(tx_id).unwrap();
+    }
+
+    fn join_fixture(
+        users: i64,
+        orders: i64,
+        user_id_for: impl Fn(i64) -> i64,
+    ) -> (TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, u64, Statement) {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+        let tx_id = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+
+        tm.execute_statement(tx_id, Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![]),
+                ColumnDef::new("name", DataType::Text, vec![]),
+            ],
+            false,
+        ))).unwrap();
+        tm.execute_statement(tx_id, Statement::Create(CreateStatement::Table(
+            "orders".to_string(),
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![]),
+                ColumnDef::new("user_id", DataType::Integer, vec![]),
+            ],
+            false,
+        ))).unwrap();
+
+        for id in 1..=users {
+            tm.execute_statement(tx_id, Statement::Insert(InsertStatement::IntoTable(
+                "users".to_string(),
+                vec![DataValue::Integer(id), DataValue::Text(format!("user{}", id).into())],
+            ))).unwrap();
+        }
+        for id in 1..=orders {
+            let user_id = user_id_for(id);
+            tm.execute_statement(tx_id, Statement::Insert(InsertStatement::IntoTable(
+                "orders".to_string(),
+                vec![DataValue::Integer(id), DataValue::Integer(user_id)],
+            ))).unwrap();
+        }
+
+        let select_stmt = Statement::Select(SelectStatement::FromTable(
+            TableReference {
+                name: "users".to_string(),
+                alias: None,
+            },
+            vec![Column {
+                table: None,
+                name: "*".to_string(),
+                column_type: crate::sql::column::ColumnType::Wildcard,
+            }],
+            None,
+            vec![JoinClause {
+                table_ref: TableReference {
+                    name: "orders".to_string(),
+                    alias: None,
+                },
+                // Right table referenced first to exercise both ON orientations
+                on: (
+                    ColumnValuePair::new("user_id", "orders"),
+                    ColumnValuePair::new("id", "users"),
+                ),
+                join_type: crate::sql::clauses::join_clause::JoinType::Inner,
+                null_safe: false,
+            }],
+            vec![],
+        ));
+
+        (tm, tx_id, select_stmt)
+    }
+
+    fn select_rows(result: ReefDBResult) -> Vec<(usize, Vec<DataValue>)> {
+        match result {
+            ReefDBResult::Select(query_result) => query_result.rows,
+            _ => panic!("Expected Select result"),
+        }
+    }
+
+    #[test]
+    fn test_select_without_order_by_returns_primary_key_order() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let mut run = |sql: &str| {
+            let (_, stmt) = Statement::parse(sql).unwrap();
+            tm.execute_statement(tx, stmt).unwrap()
+        };
+        run("CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT)");
+        for id in [4, 1, 5, 3, 2] {
+            run(&format!("INSERT INTO items VALUES ({}, 'item{}')", id, id));
+        }
+        // The row moved to a new key is read from its version, after the stored rows
+        run("UPDATE items SET id = 0 WHERE id = 4");
+
+        let ids: Vec<DataValue> = select_rows(run("SELECT id FROM items"))
+            .into_iter()
+            .map(|(_, row)| row[0].clone())
+            .collect();
+        assert_eq!(ids, [0, 1, 2, 3, 5].map(DataValue::Integer));
+    }
+
+    #[test]
+    fn test_hash_join_matches_nested_loop() {
+        // Every seventh order points at a user that does not exist
+        let (mut tm, tx_id, select_stmt) = join_fixture(5, 12, |id| if id % 7 == 0 { 6 } else { id % 5 + 1 });
+
+        let hashed = select_rows(tm.execute_statement(tx_id, select_stmt.clone()).unwrap());
+        tm.set_hash_join_enabled(false);
+        let nested = select_rows(tm.execute_statement(tx_id, select_stmt).unwrap());
+
+        assert_eq!(hashed.len(), 11);
+        assert_eq!(hashed, nested);
+    }
+
+    #[test]
+    fn test_hash_join_large_dataset() {
+        let (mut tm, tx_id, select_stmt) = join_fixture(300, 1200, |id| if id % 7 == 0 { 301 } else { id % 300 + 1 });
+
+        let hashed = select_rows(tm.execute_statement(tx_id, select_stmt.clone()).unwrap());
+        // Every order except each seventh one finds its user
+        assert_eq!(hashed.len(), 1200 - 1200 / 7);
+        for (_, row) in &hashed {
+            assert_eq!(row[0], row[3]);
+        }
+
+        tm.set_hash_join_enabled(false);
+        let nested = select_rows(tm.execute_statement(tx_id, select_stmt).unwrap());
+        assert_eq!(hashed, nested);
+    }
+
+    #[test]
+    fn test_merge_join_on_sorted_inputs() {
+        // Orders are inserted in descending user_id order: users 1 to 6 have two orders
+        // each, users 7 and 8 have none and the last order points past the users
+        let (mut tm, tx_id, select_stmt) = join_fixture(8, 13, |id| if id == 13 { 9 } else { (14 - id) / 2 });
+
+        let join = match &select_stmt {
+            Statement::Select(SelectStatement::FromTable(_, _, _, joins, _)) => joins[0].clone(),
+            _ => unreachable!(),
+        };
+        let strategy = |tm: &TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>| {
+            let reef_db = &tm.active_transactions[&tx_id].reef_db;
+            let users_schema = &reef_db.storage.get_table_ref("users").unwrap().0;
+            let orders = reef_db.storage.get_table_ref("orders").unwrap();
+            TransactionManager::choose_join_strategy(JoinMethods { merge: true, hash: true }, reef_db, &join, orders, users_schema, "users")
+        };
+        // Without an index nothing says the orders are sorted
+        assert!(matches!(strategy(&tm), JoinStrategy::Hash(_)));
+        tm.execute_statement(tx_id, Statement::CreateIndex(CreateIndexStatement {
+            table_name: "orders".to_string(),
+            column_name: "user_id".to_string(),
+            index_type: crate::sql::statements::create_index::IndexType::BTree,
+            expression: None,
+            unique: false,
+        })).unwrap();
+        match strategy(&tm) {
+            JoinStrategy::Merge { order, .. } => assert_eq!(order, vec![10, 11, 8, 9, 6, 7, 4, 5, 2, 3, 0, 1, 12]),
+            _ => panic!("expected a merge join"),
+        }
+
+        let merged = select_rows(tm.execute_statement(tx_id, select_stmt.clone()).unwrap());
+        tm.set_merge_join_enabled(false);
+        let hashed = select_rows(tm.execute_statement(tx_id, select_stmt.clone()).unwrap());
+        tm.set_hash_join_enabled(false);
+        let nested = select_rows(tm.execute_statement(tx_id, select_stmt).unwrap());
+
+        assert_eq!(merged.len(), 12);
+        assert_eq!(merged, hashed);
+        assert_eq!(merged, nested);
+    }
+
+    #[test]
+    fn test_concurrent_selects_do_not_hold_mvcc_lock_for_scan() {
+        use std::time::{Duration, Instant};
+
+        // Nested-loop joins make each scan slow relative to taking the snapshot
+        fn reader_fixture() -> (TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, u64, Statement) {
+            let (mut tm, tx_id, select) = join_fixture(200, 200, |id| id);
+            tm.set_hash_join_enabled(false);
+            tm.set_merge_join_enabled(false);
+            (tm, tx_id, select)
+        }
+
+        let (mut tm, tx_id, select) = reader_fixture();
+        let start = Instant::now();
+        tm.execute_statement(tx_id, select).unwrap();
+        let scan_time = start.elapsed();
+
+        let shared_mvcc = Arc::new(Mutex::new(MVCCManager::new()));
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let mvcc = shared_mvcc.clone();
+                std::thread::spawn(move || {
+                    let (mut tm, tx_id, select) = reader_fixture();
+                    tm.mvcc_manager = mvcc;
+                    for _ in 0..3 {
+                        tm.execute_statement(tx_id, select.clone()).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // While the readers scan, the MVCC lock should only be held briefly. The median
+        // wait is used so an occasional descheduled lock holder doesn't fail the test
+        let mut waits = Vec::new();
+        while readers.iter().any(|reader| !reader.is_finished()) {
+            let start = Instant::now();
+            drop(shared_mvcc.lock().unwrap());
+            waits.push(start.elapsed());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        waits.sort();
+        let median_wait = waits.get(waits.len() / 2).copied().unwrap_or_default();
+        assert!(
+            median_wait < scan_time / 4,
+            "MVCC lock wait was {:?}, a full scan takes {:?}",
+            median_wait,
+            scan_time
+        );
+    }
+
+    #[test]
+    fn test_row_locks_let_disjoint_updates_proceed() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'user1')").unwrap();
+        db.query("INSERT INTO users VALUES (2, 'user2')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+
+        let tx1 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let tx2 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let update = |id: i64| {
+            let (_, stmt) = Statement::parse(&format!("UPDATE users SET name = 'renamed' WHERE id = {}", id)).unwrap();
+            stmt
+        };
+
+        assert_eq!(tm.execute_statement(tx1, update(1)).unwrap(), ReefDBResult::Update(1));
+        assert_eq!(tm.execute_statement(tx2, update(2)).unwrap(), ReefDBResult::Update(1));
+
+        // Touching a row the other transaction holds still conflicts
+        assert!(matches!(
+            tm.execute_statement(tx2, update(1)),
+            Err(ReefDBError::LockConflict(_))
+        ));
+
+        // DDL needs the whole table
+        assert!(matches!(
+            tm.acquire_lock(tx2, "users", LockType::Exclusive),
+            Err(ReefDBError::LockConflict(_))
+        ));
+
+        tm.commit_transaction(tx1).unwrap();
+        tm.commit_transaction(tx2).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint_releases_later_locks() {
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let tx1 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let tx2 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+
+        // tx1 keeps the lock it held before the savepoint and loses the one taken after it
+        tm.acquire_lock(tx1, "orders", LockType::Exclusive).unwrap();
+        tm.create_savepoint(tx1, "sp".to_string()).unwrap();
+        tm.acquire_lock(tx1, "users", LockType::Exclusive).unwrap();
+        assert!(tm.acquire_lock(tx2, "users", LockType::Shared).is_err());
+
+        tm.rollback_to_savepoint(tx1, "sp").unwrap();
+        tm.acquire_lock(tx2, "users", LockType::Shared).unwrap();
+        assert!(tm.acquire_lock(tx2, "orders", LockType::Shared).is_err());
+
+        // A transaction queued behind a released lock no longer waits on tx1
+        tm.create_savepoint(tx1, "sp2".to_string()).unwrap();
+        tm.acquire_lock(tx1, "users", LockType::Shared).unwrap();
+        assert!(tm.acquire_lock(tx2, "users", LockType::Exclusive).is_err());
+        assert_eq!(tm.deadlock_detector.lock().unwrap().wait_for_graph().len(), 1);
+        tm.rollback_to_savepoint(tx1, "sp2").unwrap();
+        assert!(tm.deadlock_detector.lock().unwrap().wait_for_graph().is_empty());
+        tm.acquire_lock(tx2, "users", LockType::Exclusive).unwrap();
+    }
+
+    #[test]
+    fn test_show_locks_lists_holders_and_waiters() {
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let tx1 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let tx2 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+
+        tm.acquire_row_lock(tx1, "orders", "1", LockType::Exclusive).unwrap();
+        tm.acquire_lock(tx2, "users", LockType::Shared).unwrap();
+        tm.acquire_lock(tx1, "users", LockType::Shared).unwrap();
+        // tx2's upgrade queues behind tx1's shared lock
+        assert!(tm.acquire_lock(tx2, "users", LockType::Exclusive).is_err());
+
+        let ReefDBResult::Select(result) = tm.execute_statement(tx1, Statement::parse("SHOW LOCKS").unwrap().1).unwrap() else {
+            panic!("Expected Select result");
+        };
+        let rows: Vec<Vec<DataValue>> = result.rows.into_iter().map(|(_, row)| row).collect();
+        let row = |tx: u64, resource: &str, lock_type: &str, status: &str| vec![
+            DataValue::Integer(tx as i64),
+            DataValue::Text(resource.into()),
+            DataValue::Text(lock_type.into()),
+            DataValue::Text(status.into()),
+        ];
+        let row_resource = LockManager::row_resource("orders", "1");
+        let expected = [
+            row(tx1, "orders", "IntentionExclusive", "granted"),
+            row(tx1, &row_resource, "Exclusive", "granted"),
+            row(tx1, "users", "Shared", "granted"),
+            row(tx2, "users", "Shared", "granted"),
+            row(tx2, "users", "Exclusive", "waiting"),
+        ];
+        assert_eq!(rows.len(), expected.len());
+        for lock in &expected {
+            assert!(rows.contains(lock), "missing {:?} in {:?}", lock, rows);
+        }
+    }
+
+    #[test]
+    fn test_read_then_update_upgrades_table_lock() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'user1')").unwrap();
+        db.query("INSERT INTO users VALUES (2, 'user2')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+
+        let tx1 = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let tx2 = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+
+        let update = |id: i64| {
+            let (_, stmt) = Statement::parse(&format!("UPDATE users SET name = 'renamed' WHERE id = {}", id)).unwrap();
+            stmt
+        };
+
+        // Both transactions read the table before writing to it
+        tm.acquire_lock(tx1, "users", LockType::Shared).unwrap();
+        tm.acquire_lock(tx2, "users", LockType::Shared).unwrap();
+
+        // tx1 can't upgrade while tx2 shares the table, so it queues
+        assert!(matches!(
+            tm.execute_statement(tx1, update(1)),
+            Err(ReefDBError::LockConflict(_))
+        ));
+        assert_eq!(tm.lock_manager.lock().unwrap().upgrade_waiters("users"), vec![tx1]);
+
+        // tx2 upgrading as well would wait on tx1, closing the cycle
+        match tm.execute_statement(tx2, update(2)) {
+            Err(ReefDBError::Deadlock(mut cycle)) => {
+                cycle.sort();
+                let mut expected = vec![tx1, tx2];
+                expected.sort();
+                assert_eq!(cycle, expected);
+            }
+            other => panic!("expected a deadlock, got {:?}", other),
+        }
+        tm.rollback_transaction(tx2).unwrap();
+
+        // With tx2 gone the upgrade goes through
+        assert_eq!(tm.execute_statement(tx1, update(1)).unwrap(), ReefDBResult::Update(1));
+        assert!(tm.lock_manager.lock().unwrap().upgrade_waiters("users").is_empty());
+        tm.commit_transaction(tx1).unwrap();
+    }
+
+    #[test]
+    fn test_wal_logs_row_operations() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+                ColumnDef::new("name", DataType::Text, vec![]),
+            ],
+            false,
+        ))).unwrap();
+        for id in 1..=3 {
+            tm.execute_statement(tx, Statement::Insert(InsertStatement::IntoTable(
+                "users".to_string(),
+                vec![DataValue::Integer(id), DataValue::Text(format!("user{}", id).into())],
+            ))).unwrap();
+        }
+        let (_, update) = Statement::parse("UPDATE users SET name = 'renamed' WHERE id = 2").unwrap();
+        tm.execute_statement(tx, update).unwrap();
+        let (_, delete) = Statement::parse("DELETE FROM users WHERE id = 3").unwrap();
+        tm.execute_statement(tx, delete).unwrap();
+        tm.commit_transaction(tx).unwrap();
+
+        let entries = tm.wal.lock().unwrap().read_entries().unwrap();
+        let operations: Vec<WALOperation> = entries.iter().map(|e| e.operation.clone()).collect();
+        assert_eq!(operations, vec![
+            WALOperation::Insert,
+            WALOperation::Insert,
+            WALOperation::Insert,
+            WALOperation::Update,
+            WALOperation::Delete,
+            WALOperation::Commit,
+        ]);
+        assert!(entries.iter().all(|e| e.transaction_id == tx));
+
+        let rows: Vec<Vec<DataValue>> = entries[..5].iter().map(|e| {
+            assert_eq!(e.table_name, "users");
+            e.row().unwrap()
+        }).collect();
+        assert_eq!(rows[0], vec![DataValue::Integer(1), DataValue::Text("user1".into())]);
+        assert_eq!(rows[3], vec![DataValue::Integer(2), DataValue::Text("renamed".into())]);
+        assert_eq!(rows[4], vec![DataValue::Integer(3), DataValue::Text("user3".into())]);
+        assert!(entries[5].data.is_empty());
+    }
+
+    #[test]
+    fn test_wal_entries_are_stamped_by_injected_clock() {
+        use crate::clock::MockClock;
+        use std::time::{Duration, SystemTime};
+
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000));
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        tm.set_clock(Arc::new(clock.clone()));
+        let receiver = tm.subscribe_wal().unwrap();
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey])],
+            false,
+        ))).unwrap();
+        tm.execute_statement(tx, Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(1)],
+        ))).unwrap();
+        clock.advance(Duration::from_secs(5));
+        tm.commit_transaction(tx).unwrap();
+
+        let stamps: Vec<SystemTime> = receiver.try_iter().map(|e| e.timestamp).collect();
+        assert_eq!(stamps, vec![
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_000),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_005),
+        ]);
+    }
+
+    #[test]
+    fn test_order_by_unknown_column_is_an_error() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'alice')").unwrap();
+        db.query("INSERT INTO orders VALUES (1, 1)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+
+        for sql in [
+            "SELECT * FROM users ORDER BY nickname",
+            "SELECT * FROM users INNER JOIN orders ON users.id = orders.user_id ORDER BY nickname",
+        ] {
+            let (_, select) = Statement::parse(sql).unwrap();
+            assert_eq!(
+                tm.execute_statement(tx, select),
+                Err(ReefDBError::ColumnNotFound("nickname".to_string())),
+                "{}", sql
+            );
+        }
+        let (_, select) = Statement::parse(
+            "SELECT * FROM users INNER JOIN orders ON users.id = orders.user_id ORDER BY orders.name"
+        ).unwrap();
+        assert_eq!(tm.execute_statement(tx, select), Err(ReefDBError::ColumnNotFound("orders.name".to_string())));
+
+        // Columns of a joined table still resolve
+        let (_, select) = Statement::parse(
+            "SELECT * FROM users INNER JOIN orders ON users.id = orders.user_id ORDER BY orders.user_id DESC"
+        ).unwrap();
+        assert!(tm.execute_statement(tx, select).is_ok());
+    }
+
+    #[test]
+    fn test_three_table_join_resolves_columns_by_table() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER)").unwrap();
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, order_id INTEGER, sku TEXT)").unwrap();
+        for sql in [
+            "INSERT INTO users VALUES (1, 'alice')",
+            "INSERT INTO users VALUES (2, 'bob')",
+            "INSERT INTO orders VALUES (10, 1)",
+            "INSERT INTO orders VALUES (20, 2)",
+            "INSERT INTO items VALUES (100, 10, 'apple')",
+            "INSERT INTO items VALUES (200, 20, 'pear')",
+            "INSERT INTO items VALUES (300, 20, 'plum')",
+        ] {
+            db.query(sql).unwrap();
+        }
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let joins = "FROM users INNER JOIN orders ON users.id = orders.user_id INNER JOIN items ON orders.id = items.order_id";
+        let mut query = |sql: String| {
+            let (_, select) = Statement::parse(&sql).unwrap();
+            tm.execute_statement(tx, select).map(|result| match result {
+                ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+                other => panic!("Expected Select result, got {:?}", other),
+            })
+        };
+        let text = |s: &str| DataValue::Text(s.into());
+
+        // Every table has an id; each qualified one comes from its own table
+        assert_eq!(
+            query(format!("SELECT items.id, users.name, orders.id, sku {} ORDER BY items.id", joins)).unwrap(),
+            vec![
+                vec![DataValue::Integer(100), text("alice"), DataValue::Integer(10), text("apple")],
+                vec![DataValue::Integer(200), text("bob"), DataValue::Integer(20), text("pear")],
+                vec![DataValue::Integer(300), text("bob"), DataValue::Integer(20), text("plum")],
+            ]
+        );
+
+        // WHERE may name the last joined table, and qualified columns inside AND keep their table
+        assert_eq!(
+            query(format!("SELECT sku {} WHERE items.sku = 'plum'", joins)).unwrap(),
+            vec![vec![text("plum")]]
+        );
+        assert_eq!(
+            query(format!("SELECT sku {} WHERE orders.id = 20 AND users.name = 'bob' ORDER BY sku DESC", joins)).unwrap(),
+            vec![vec![text("plum")], vec![text("pear")]]
+        );
+
+        // Columns that don't exist are errors rather than being left out of the row
+        assert_eq!(
+            query(format!("SELECT users.name, items.price {}", joins)),
+            Err(ReefDBError::ColumnNotFound("price".to_string()))
+        );
+        assert_eq!(
+            query(format!("SELECT payments.id {}", joins)),
+            Err(ReefDBError::TableNotFound("payments".to_string()))
+        );
+        assert_eq!(
+            query(format!("SELECT sku {} WHERE orders.sku = 'pear'", joins)),
+            Err(ReefDBError::ColumnNotFound("orders.sku".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_outer_joins_pad_unmatched_rows_with_nulls() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER)").unwrap();
+        for sql in [
+            "INSERT INTO users VALUES (1, 'alice')",
+            "INSERT INTO users VALUES (2, 'bob')",
+            "INSERT INTO users VALUES (3, 'carol')",
+            "INSERT INTO orders VALUES (10, 1)",
+            "INSERT INTO orders VALUES (20, 1)",
+            "INSERT INTO orders VALUES (30, 4)",
+        ] {
+            db.query(sql).unwrap();
+        }
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let text = |s: &str| DataValue::Text(s.into());
+        let alice = |order: i64| vec![text("alice"), DataValue::Integer(order)];
+        let no_orders = |name: &str| vec![text(name), DataValue::Null];
+        let no_user = vec![DataValue::Null, DataValue::Integer(30)];
+
+        // The same rows whether the join is hashed or a nested loop
+        for hash_join in [true, false] {
+            tm.set_hash_join_enabled(hash_join);
+            let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+            let mut query = |join: &str| {
+                let sql = format!("SELECT users.name, orders.id FROM users {} orders ON users.id = orders.user_id", join);
+                select_rows(tm.execute_statement(tx, Statement::parse(&sql).unwrap().1).unwrap())
+                    .into_iter()
+                    .map(|(_, row)| row)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(query("INNER JOIN"), vec![alice(10), alice(20)]);
+            assert_eq!(query("LEFT JOIN"), vec![alice(10), alice(20), no_orders("bob"), no_orders("carol")]);
+            assert_eq!(query("RIGHT JOIN"), vec![alice(10), alice(20), no_user.clone()]);
+            assert_eq!(
+                query("FULL JOIN"),
+                vec![alice(10), alice(20), no_orders("bob"), no_orders("carol"), no_user.clone()]
+            );
+            tm.commit_transaction(tx).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_join_layout_rejects_rows_of_the_wrong_width() {
+        let schema = vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+            ColumnDef::new("name", DataType::Text, vec![]),
+        ];
+        let layout = JoinLayout::new("users", &schema, &[]);
+        assert!(layout.check_row(&[DataValue::Integer(1), DataValue::Null]).is_ok());
+        assert_eq!(
+            layout.check_row(&[DataValue::Integer(1)]),
+            Err(ReefDBError::ColumnCountMismatch { expected: 2, found: 1 })
+        );
+        assert_eq!(layout.resolve(Some("users"), "name"), Ok(1));
+        assert_eq!(layout.resolve(None, "email"), Err(ReefDBError::ColumnNotFound("email".to_string())));
+    }
+
+    #[test]
+    fn test_upsert_logs_the_row_it_wrote() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE counters (id INTEGER PRIMARY KEY, hits INTEGER)").unwrap();
+        db.query("INSERT INTO counters VALUES (1, 1)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let receiver = tm.subscribe_wal().unwrap();
+        let upsert = |id: i64| Statement::parse(&format!(
+            "INSERT INTO counters VALUES ({}, 1) ON CONFLICT (id) DO UPDATE SET hits = 2", id
+        )).unwrap().1;
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(tm.execute_statement(tx, upsert(1)).unwrap(), ReefDBResult::Update(1));
+        assert_eq!(tm.execute_statement(tx, upsert(2)).unwrap(), ReefDBResult::Insert(2));
+        assert_eq!(tm.active_transactions[&tx].reef_db.storage.get_table_ref("counters").unwrap().1, vec![
+            vec![DataValue::Integer(1), DataValue::Integer(2)],
+            vec![DataValue::Integer(2), DataValue::Integer(1)],
+        ]);
+        tm.commit_transaction(tx).unwrap();
+
+        let entries: Vec<(WALOperation, Vec<DataValue>)> = receiver.try_iter()
+            .filter(|e| e.operation != WALOperation::Commit)
+            .map(|e| (e.operation.clone(), e.row().unwrap()))
+            .collect();
+        assert_eq!(entries, vec![
+            (WALOperation::Update, vec![DataValue::Integer(1), DataValue::Integer(2)]),
+            (WALOperation::Insert, vec![DataValue::Integer(2), DataValue::Integer(1)]),
+        ]);
+    }
+
+    #[test]
+    fn test_select_sees_own_update_at_every_isolation_level() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER, owner TEXT)").unwrap();
+        db.query("INSERT INTO accounts VALUES (1, 100, 'alice')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+
+        for level in [
+            IsolationLevel::ReadUncommitted,
+            IsolationLevel::ReadCommitted,
+            IsolationLevel::RepeatableRead,
+            IsolationLevel::Serializable,
+        ] {
+            let tx = tm.begin_transaction(level).unwrap();
+            tm.execute_statement(tx, parse("UPDATE accounts SET balance = 50 WHERE id = 1")).unwrap();
+            // A second update builds on the first instead of the stored row
+            tm.execute_statement(tx, parse("UPDATE accounts SET owner = 'bob' WHERE balance = 50")).unwrap();
+            let ReefDBResult::Select(result) = tm.execute_statement(tx, parse("SELECT * FROM accounts WHERE id = 1")).unwrap() else {
+                panic!("Expected Select result");
+            };
+            let rows: Vec<Vec<DataValue>> = result.rows.into_iter().map(|(_, row)| row).collect();
+            assert_eq!(
+                rows,
+                vec![vec![DataValue::Integer(1), DataValue::Integer(50), DataValue::Text("bob".into())]],
+                "{:?}", level
+            );
+            tm.rollback_transaction(tx).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_update_primary_key_moves_row() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT)").unwrap();
+        db.query("INSERT INTO accounts VALUES (1, 'alice')").unwrap();
+        db.query("INSERT INTO accounts VALUES (2, 'bob')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+        let select = |tm: &mut TransactionManager<_, _>, tx, sql: &str| match tm.execute_statement(tx, parse(sql)).unwrap() {
+            ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+            other => panic!("Expected Select result, got {:?}", other),
+        };
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        // Moving onto a key another row still holds is refused
+        assert!(matches!(
+            tm.execute_statement(tx, parse("UPDATE accounts SET id = 2 WHERE id = 1")),
+            Err(ReefDBError::DuplicateKey(_))
+        ));
+        assert_eq!(tm.execute_statement(tx, parse("UPDATE accounts SET id = 10 WHERE id = 1")).unwrap(), ReefDBResult::Update(1));
+        tm.commit_transaction(tx).unwrap();
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(
+            select(&mut tm, tx, "SELECT * FROM accounts WHERE id = 10"),
+            vec![vec![DataValue::Integer(10), DataValue::Text("alice".into())]]
+        );
+        assert!(select(&mut tm, tx, "SELECT * FROM accounts WHERE id = 1").is_empty());
+        assert_eq!(select(&mut tm, tx, "SELECT * FROM accounts").len(), 2);
+
+        // The moved row can be updated again under its new key, and its old key reused
+        tm.execute_statement(tx, parse("UPDATE accounts SET owner = 'carol' WHERE id = 10")).unwrap();
+        tm.execute_statement(tx, parse("UPDATE accounts SET id = 1 WHERE id = 2")).unwrap();
+        assert_eq!(select(&mut tm, tx, "SELECT * FROM accounts ORDER BY id"), vec![
+            vec![DataValue::Integer(1), DataValue::Text("bob".into())],
+            vec![DataValue::Integer(10), DataValue::Text("carol".into())],
+        ]);
+    }
+
+    #[test]
+    fn test_update_enforces_varchar_length() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE codes (id INTEGER PRIMARY KEY, code VARCHAR(3))").unwrap();
+        db.query("INSERT INTO codes VALUES (1, 'abc')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(
+            tm.execute_statement(tx, parse("UPDATE codes SET code = 'abcd' WHERE id = 1")),
+            Err(ReefDBError::ValueTooLong("code".to_string(), 3))
+        );
+        assert_eq!(tm.execute_statement(tx, parse("UPDATE codes SET code = 'xyz' WHERE id = 1")).unwrap(), ReefDBResult::Update(1));
+        let ReefDBResult::Select(result) = tm.execute_statement(tx, parse("SELECT code FROM codes WHERE id = 1")).unwrap() else {
+            panic!("Expected Select result");
+        };
+        assert_eq!(result.rows[0].1, vec![DataValue::Text("xyz".into())]);
+    }
+
+    #[test]
+    fn test_update_checks_enum_labels_and_decimal_scale() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE tickets (id INTEGER PRIMARY KEY, status ENUM('open','closed'), price DECIMAL(10,2))").unwrap();
+        db.query("INSERT INTO tickets VALUES (1, 'open', 5)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(
+            tm.execute_statement(tx, parse("UPDATE tickets SET status = 'pending' WHERE id = 1")),
+            Err(ReefDBError::InvalidEnumValue("status".to_string(), "pending".to_string()))
+        );
+        tm.execute_statement(tx, parse("UPDATE tickets SET status = 'closed' WHERE id = 1")).unwrap();
+        tm.execute_statement(tx, parse("UPDATE tickets SET price = 7 WHERE id = 1")).unwrap();
+        let ReefDBResult::Select(result) = tm.execute_statement(tx, parse("SELECT status, price FROM tickets WHERE id = 1")).unwrap() else {
+            panic!("Expected Select result");
+        };
+        // The new price is stored at the column's scale
+        assert_eq!(result.rows[0].1, vec![DataValue::Text("closed".into()), DataValue::Decimal(crate::sql::decimal::Decimal::new(700, 2))]);
+    }
+
+    #[test]
+    fn test_update_enforces_unique_indexes_and_columns() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT, handle TEXT UNIQUE)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'a@example.com', 'a')").unwrap();
+        db.query("INSERT INTO users VALUES (2, 'b@example.com', 'b')").unwrap();
+        db.query("CREATE UNIQUE INDEX idx_email ON users (email)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert!(matches!(
+            tm.execute_statement(tx, parse("UPDATE users SET email = 'a@example.com' WHERE id = 2")),
+            Err(ReefDBError::DuplicateKey(_))
+        ));
+        assert!(matches!(
+            tm.execute_statement(tx, parse("UPDATE users SET handle = 'a' WHERE id = 2")),
+            Err(ReefDBError::DuplicateKey(_))
+        ));
+        // Checked against the transaction's own writes too
+        tm.execute_statement(tx, parse("UPDATE users SET email = 'c@example.com' WHERE id = 1")).unwrap();
+        tm.execute_statement(tx, parse("UPDATE users SET email = 'a@example.com' WHERE id = 2")).unwrap();
+        assert!(matches!(
+            tm.execute_statement(tx, parse("UPDATE users SET email = 'c@example.com' WHERE id = 2")),
+            Err(ReefDBError::DuplicateKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_commit_clashing_with_an_earlier_commit_is_rolled_back_whole() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE t (id INTEGER PRIMARY KEY, email TEXT UNIQUE)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+
+        let a = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(a, parse("INSERT INTO t VALUES (1, 'x')")).unwrap();
+        let b = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(b, parse("INSERT INTO t VALUES (3, 'y')")).unwrap();
+        tm.execute_statement(b, parse("INSERT INTO t VALUES (2, 'x')")).unwrap();
+        tm.commit_transaction(a).unwrap();
+        assert!(matches!(tm.commit_transaction(b), Err(ReefDBError::DuplicateKey(_))));
+
+        // None of b was published or committed, and it's gone with its locks
+        assert_eq!(tm.rollback_transaction(b), Err(ReefDBError::TransactionNotFound(b)));
+        let entries = lock_recovering(&tm.wal).read_entries().unwrap();
+        assert!(!entries.iter().any(|entry| entry.transaction_id == b && entry.operation == WALOperation::Commit));
+        let reader = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let ids = |tm: &mut TransactionManager<_, _>, tx| select_rows(tm.execute_statement(tx, parse("SELECT id FROM t")).unwrap())
+            .into_iter()
+            .map(|(_, row)| row[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids(&mut tm, reader), vec![DataValue::Integer(1)]);
+        tm.commit_transaction(reader).unwrap();
+
+        let c = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(tm.execute_statement(c, parse("UPDATE t SET email = 'z' WHERE id = 3")).unwrap(), ReefDBResult::Update(0));
+        tm.execute_statement(c, parse("INSERT INTO t VALUES (3, 'y')")).unwrap();
+        tm.commit_transaction(c).unwrap();
+        let reader = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(ids(&mut tm, reader), vec![DataValue::Integer(1), DataValue::Integer(3)]);
+    }
+
+    #[test]
+    fn test_deferred_unique_swap_through_transaction_manager() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE seats (id INTEGER PRIMARY KEY, code TEXT UNIQUE DEFERRABLE INITIALLY DEFERRED)").unwrap();
+        db.query("INSERT INTO seats VALUES (1, 'a')").unwrap();
+        db.query("INSERT INTO seats VALUES (2, 'b')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+        let codes = |tm: &mut TransactionManager<_, _>| {
+            let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+            let rows = select_rows(tm.execute_statement(tx, parse("SELECT id, code FROM seats")).unwrap());
+            tm.commit_transaction(tx).unwrap();
+            let mut codes: Vec<Vec<DataValue>> = rows.into_iter().map(|(_, row)| row).collect();
+            codes.sort_by_key(|row| format!("{:?}", row[0]));
+            codes
+        };
+
+        // Halfway through the swap both rows hold 'a'; only the committed state is checked
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, parse("UPDATE seats SET code = 'a' WHERE id = 2")).unwrap();
+        tm.execute_statement(tx, parse("UPDATE seats SET code = 'b' WHERE id = 1")).unwrap();
+        tm.commit_transaction(tx).unwrap();
+        assert_eq!(codes(&mut tm), vec![
+            vec![DataValue::Integer(1), DataValue::Text("b".into())],
+            vec![DataValue::Integer(2), DataValue::Text("a".into())],
+        ]);
+
+        // Still violated at commit: nothing of it is published
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, parse("UPDATE seats SET code = 'a' WHERE id = 1")).unwrap();
+        tm.execute_statement(tx, parse("INSERT INTO seats VALUES (3, 'b')")).unwrap();
+        assert!(matches!(tm.commit_transaction(tx), Err(ReefDBError::DuplicateKey(_))));
+        assert_eq!(codes(&mut tm).len(), 2);
+        assert_eq!(codes(&mut tm)[0][1], DataValue::Text("b".into()));
+    }
+
+    #[test]
+    fn test_commit_of_row_changes_keeps_cached_plans() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'Alice')").unwrap();
+        db.query("SELECT * FROM users WHERE id = 1").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+        let entries = |tm: &TransactionManager<_, _>| tm.reef_db.lock().unwrap().plan_cache_stats().entries;
+        let cached = entries(&tm);
+        assert!(cached > 0);
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, parse("INSERT INTO users VALUES (2, 'Bob')")).unwrap();
+        tm.execute_statement(tx, parse("UPDATE users SET name = 'Alicia' WHERE id = 1")).unwrap();
+        tm.commit_transaction(tx).unwrap();
+        assert_eq!(entries(&tm), cached);
+        let mut db = tm.reef_db.lock().unwrap();
+        assert_eq!(
+            select_rows(db.query("SELECT * FROM users WHERE id = 1").unwrap()),
+            vec![(0, vec![DataValue::Integer(1), DataValue::Text("Alicia".into())])]
+        );
+        assert_eq!(db.tables.row_count("users"), Some(2));
+        drop(db);
+
+        // DDL still drops the table's plans
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, parse("ALTER TABLE users ADD COLUMN age INTEGER")).unwrap();
+        tm.commit_transaction(tx).unwrap();
+        assert_eq!(entries(&tm), 0);
+    }
+
+    #[test]
+    fn test_select_as_of_reads_history_until_vacuumed() {
+        use crate::clock::MockClock;
+        use std::time::{Duration, SystemTime};
+
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER)").unwrap();
+        db.query("INSERT INTO accounts VALUES (1, 100)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        // 2024-01-01T00:00:00Z
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+        tm.set_clock(Arc::new(clock.clone()));
+        tm.set_version_retention(VersionRetention::Duration(Duration::from_secs(24 * 3600)));
+        let parse = |sql: &str| Statement::parse(sql).unwrap().1;
+        let balance = |tm: &mut TransactionManager<_, _>, tx, sql: &str| match tm.execute_statement(tx, parse(sql)) {
+            Ok(ReefDBResult::Select(result)) => Ok(result.rows[0].1[0].clone()),
+            Ok(other) => panic!("Expected Select result, got {:?}", other),
+            Err(err) => Err(err),
+        };
+
+        // Balance 50 from midnight, 20 from 01:00
+        for value in [50, 20] {
+            let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+            tm.execute_statement(tx, parse(&format!("UPDATE accounts SET balance = {} WHERE id = 1", value))).unwrap();
+            tm.commit_transaction(tx).unwrap();
+            clock.advance(Duration::from_secs(3600));
+        }
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(balance(&mut tm, tx, "SELECT balance FROM accounts WHERE id = 1"), Ok(DataValue::Integer(20)));
+        assert_eq!(
+            balance(&mut tm, tx, "SELECT balance FROM accounts AS OF '2024-01-01T00:30:00Z' WHERE id = 1"),
+            Ok(DataValue::Integer(50))
+        );
+        // Before the first update the row reads as stored
+        assert_eq!(
+            balance(&mut tm, tx, "SELECT balance FROM accounts AS OF '2023-12-31T12:00:00Z'"),
+            Ok(DataValue::Integer(100))
+        );
+
+        // With no retention beyond live readers, vacuum drops the midnight version
+        tm.set_version_retention(VersionRetention::Minimal);
+        assert_eq!(tm.vacuum().unwrap(), 1);
+        assert!(matches!(
+            balance(&mut tm, tx, "SELECT balance FROM accounts AS OF '2024-01-01T00:30:00Z' WHERE id = 1"),
+            Err(ReefDBError::SnapshotTooOld(_))
+        ));
+        assert_eq!(
+            balance(&mut tm, tx, "SELECT balance FROM accounts AS OF '2024-01-01T01:30:00Z' WHERE id = 1"),
+            Ok(DataValue::Integer(20))
+        );
+    }
+
+    #[test]
+    fn test_upsert_that_changes_the_key_is_logged_as_a_move() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'a')").unwrap();
+        db.query("INSERT INTO users VALUES (2, 'b')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let receiver = tm.subscribe_wal().unwrap();
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let upsert = Statement::parse("INSERT INTO users VALUES (5, 'a') ON CONFLICT (email) DO UPDATE SET id = 9").unwrap().1;
+        assert_eq!(tm.execute_statement(tx, upsert).unwrap(), ReefDBResult::Update(1));
+        tm.execute_statement(tx, Statement::parse("DELETE FROM users WHERE id = 2").unwrap().1).unwrap();
+        tm.commit_transaction(tx).unwrap();
+
+        let entries: Vec<(WALOperation, Vec<DataValue>)> = receiver.try_iter()
+            .filter(|e| e.operation != WALOperation::Commit)
+            .map(|e| (e.operation.clone(), e.row().unwrap()))
+            .collect();
+        assert_eq!(entries, vec![
+            (WALOperation::Delete, vec![DataValue::Integer(1), DataValue::Text("a".into())]),
+            (WALOperation::Insert, vec![DataValue::Integer(9), DataValue::Text("a".into())]),
+            (WALOperation::Delete, vec![DataValue::Integer(2), DataValue::Text("b".into())]),
+        ]);
+        assert_eq!(
+            tm.reef_db.lock().unwrap().storage.get_table_ref("users").unwrap().1,
+            vec![vec![DataValue::Integer(9), DataValue::Text("a".into())]]
+        );
+    }
+
+    #[test]
+    fn test_merge_logs_updated_and_inserted_rows() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE counters (id INTEGER PRIMARY KEY, hits INTEGER)").unwrap();
+        db.query("CREATE TABLE batch (id INTEGER PRIMARY KEY, hits INTEGER)").unwrap();
+        db.query("INSERT INTO counters VALUES (1, 1)").unwrap();
+        db.query("INSERT INTO batch VALUES (1, 5)").unwrap();
+        db.query("INSERT INTO batch VALUES (2, 7)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let receiver = tm.subscribe_wal().unwrap();
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let merge = Statement::parse("MERGE INTO counters USING batch ON counters.id = batch.id \
+                                      WHEN MATCHED THEN UPDATE SET hits = batch.hits \
+                                      WHEN NOT MATCHED THEN INSERT VALUES (batch.id, batch.hits)").unwrap().1;
+        assert_eq!(tm.execute_statement(tx, merge).unwrap(), ReefDBResult::Merge { updated: 1, inserted: 1 });
+        tm.commit_transaction(tx).unwrap();
+
+        let entries: Vec<(WALOperation, Vec<DataValue>)> = receiver.try_iter()
+            .filter(|e| e.operation != WALOperation::Commit)
+            .map(|e| (e.operation.clone(), e.row().unwrap()))
+            .collect();
+        assert_eq!(entries, vec![
+            (WALOperation::Update, vec![DataValue::Integer(1), DataValue::Integer(5)]),
+            (WALOperation::Insert, vec![DataValue::Integer(2), DataValue::Integer(7)]),
+        ]);
+    }
+
+    #[test]
+    fn test_read_only_commit_appends_no_wal_entry() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY)").unwrap();
+        db.query("INSERT INTO users VALUES (1)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let receiver = tm.subscribe_wal().unwrap();
+
+        let reader = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let select = Statement::Select(SelectStatement::FromTable(
+            TableReference { name: "users".to_string(), alias: None },
+            vec![Column {
+                table: None,
+                name: "*".to_string(),
+                column_type: crate::sql::column::ColumnType::Wildcard,
+            }],
+            None,
+            vec![],
+            vec![],
+        ));
+        match tm.execute_statement(reader, select).unwrap() {
+            ReefDBResult::Select(rows) => assert_eq!(rows.len(), 1),
+            other => panic!("Expected Select result, got {:?}", other),
+        }
+        assert!(!tm.get_transaction(reader).unwrap().is_dirty());
+        tm.commit_transaction(reader).unwrap();
+        assert_eq!(receiver.try_iter().count(), 0);
+        assert!(tm.get_transaction(reader).is_err());
+
+        // A writing transaction still logs its row and its commit
+        let writer = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(writer, Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(2)],
+        ))).unwrap();
+        assert!(tm.get_transaction(writer).unwrap().is_dirty());
+        tm.commit_transaction(writer).unwrap();
+        let operations: Vec<WALOperation> = receiver.try_iter().map(|e| e.operation).collect();
+        assert_eq!(operations, vec![WALOperation::Insert, WALOperation::Commit]);
+    }
+
+    #[test]
+    fn test_wal_subscriber_receives_committed_inserts() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+        let receiver = tm.subscribe_wal().unwrap();
+
+        let insert = |id: i64| Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(id), DataValue::Text(format!("user{}", id).into())],
+        ));
+        let create = || Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+                ColumnDef::new("name", DataType::Text, vec![]),
+            ],
+            false,
+        ));
+
+        // A rolled back transaction is never streamed
+        let aborted = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(aborted, create()).unwrap();
+        tm.execute_statement(aborted, insert(99)).unwrap();
+        tm.rollback_transaction(aborted).unwrap();
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, create()).unwrap();
+        tm.execute_statement(tx, insert(1)).unwrap();
+        tm.execute_statement(tx, insert(2)).unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        tm.commit_transaction(tx).unwrap();
+        let received: Vec<WALEntry> = receiver.try_iter().collect();
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|e| e.transaction_id == tx));
+        assert_eq!(received[0].operation, WALOperation::Insert);
+        assert_eq!(received[0].row().unwrap()[0], DataValue::Integer(1));
+        assert_eq!(received[1].row().unwrap()[0], DataValue::Integer(2));
+        assert_eq!(received[2].operation, WALOperation::Commit);
+    }
+
+    #[test]
+    fn test_vacuum_after_commits() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, Statement::parse("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap().1).unwrap();
+        tm.execute_statement(tx, Statement::parse("INSERT INTO users VALUES (1, 'v0')").unwrap().1).unwrap();
+        tm.commit_transaction(tx).unwrap();
+
+        // Each transaction commits a new version of the row
+        let update = |tm: &mut TransactionManager<_, _>, name: &str| {
+            let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+            let (_, stmt) = Statement::parse(&format!("UPDATE users SET name = '{}' WHERE id = 1", name)).unwrap();
+            tm.execute_statement(tx, stmt).unwrap();
+            tm.commit_transaction(tx).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        };
+        let key = KeyFormat::row("users", 0, "1");
+
+        for name in ["v1", "v2", "v3"] {
+            update(&mut tm, name);
+        }
+        assert_eq!(tm.vacuum().unwrap(), 2);
+        assert_eq!(tm.vacuum().unwrap(), 0);
+
+        tm.set_vacuum_on_commit(true);
+        for name in ["v4", "v5"] {
+            update(&mut tm, name);
+        }
+        assert_eq!(tm.vacuum().unwrap(), 0);
+        assert_eq!(
+            tm.read_mvcc_data(&key).unwrap(),
+            Some(vec![DataValue::Integer(1), DataValue::Text("v5".into())])
+        );
+    }
+
+    #[test]
+    fn test_run_with_retry_restarts_after_serialization_failure() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'original')").unwrap();
+        let wal = WriteAheadLog::new_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+        let rename = |name: &str| Statement::parse(&format!("UPDATE users SET name = '{}' WHERE id = 1", name)).unwrap().1;
+
+        let mut attempts = 0;
+        let updated = tm.run_with_retry(IsolationLevel::Serializable, 3, |tm, tx| {
+            attempts += 1;
+            if attempts == 1 {
+                // A rival commits the same row after this attempt began, so it can't commit
+                let rival = tm.begin_transaction(IsolationLevel::Serializable)?;
+                tm.execute_statement(rival, rename("rival"))?;
+                tm.commit_transaction(rival)?;
+            }
+            tm.execute_statement(tx, rename("mine"))
+        }).unwrap();
+
+        assert_eq!(updated, ReefDBResult::Update(1));
+        assert_eq!(attempts, 2);
+        assert!(tm.active_transactions.is_empty());
+        assert_eq!(
+            tm.read_mvcc_data(&KeyFormat::row("users", 0, "1")).unwrap(),
+            Some(vec![DataValue::Integer(1), DataValue::Text("mine".into())])
+        );
+
+        // Other errors are returned without retrying
+        let mut attempts = 0;
+        let missing = tm.run_with_retry(IsolationLevel::Serializable, 3, |tm, tx| {
+            attempts += 1;
+            tm.execute_statement(tx, Statement::parse("DELETE FROM missing").unwrap().1)
+        });
+        assert!(matches!(missing, Err(ReefDBError::TableNotFound(_))));
+        assert_eq!(attempts, 1);
+        assert!(tm.active_transactions.is_empty());
+    }
+
+    #[test]
+    fn test_serializable_write_write_conflict_aborts_second_committer() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'original')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let rename = |name: &str| Statement::parse(&format!("UPDATE users SET name = '{}' WHERE id = 1", name)).unwrap().1;
+
+        let tx1 = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        let tx2 = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        // While tx1 holds the row, tx2 can't write it at all
+        tm.execute_statement(tx1, rename("first")).unwrap();
+        assert!(matches!(tm.execute_statement(tx2, rename("second")), Err(ReefDBError::LockConflict(_))));
+        tm.commit_transaction(tx1).unwrap();
+
+        // Once tx1 has committed the lock is free, but tx2's snapshot predates the new version
+        tm.execute_statement(tx2, rename("second")).unwrap();
+        assert!(matches!(tm.commit_transaction(tx2), Err(ReefDBError::SerializationFailure(_))));
+        assert!(!tm.active_transactions.contains_key(&tx2));
+        assert_eq!(
+            tm.read_mvcc_data(&KeyFormat::row("users", 0, "1")).unwrap(),
+            Some(vec![DataValue::Integer(1), DataValue::Text("first".into())])
+        );
+
+        // The aborted transaction never reaches the WAL as committed
+        let entries = tm.wal.lock().unwrap().read_entries().unwrap();
+        assert!(!entries.iter().any(|e| e.transaction_id == tx2 && e.operation == WALOperation::Commit));
+    }
+
+    #[test]
+    fn test_serializable_reads_its_own_writes() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.query("INSERT INTO users VALUES (1, 'alice')").unwrap();
+        let wal = WriteAheadLog::new_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+        let statement = |sql: &str| Statement::parse(sql).unwrap().1;
+        let names = |result: ReefDBResult| match result {
+            ReefDBResult::Select(result) => result.rows.into_iter().map(|(_, row)| row[1].clone()).collect::<Vec<_>>(),
+            other => panic!("Expected Select result, got {:?}", other),
+        };
+        let text = |s: &str| DataValue::Text(s.into());
+
+        let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        tm.execute_statement(tx, statement("INSERT INTO users VALUES (2, 'bob')")).unwrap();
+        tm.execute_statement(tx, statement("UPDATE users SET name = 'alicia' WHERE id = 1")).unwrap();
+        let select = || statement("SELECT id, name FROM users ORDER BY id");
+        assert_eq!(names(tm.execute_statement(tx, select()).unwrap()), vec![text("alicia"), text("bob")]);
+
+        // A row committed by another transaction stays out of the snapshot
+        let other = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        tm.execute_statement(other, statement("INSERT INTO users VALUES (3, 'carol')")).unwrap();
+        tm.commit_transaction(other).unwrap();
+        assert_eq!(names(tm.execute_statement(tx, select()).unwrap()), vec![text("alicia"), text("bob")]);
+
+        // Committing publishes the transaction's own insert and update without undoing
+        // the row committed meanwhile: carol is not lost to tx's older snapshot
+        tm.commit_transaction(tx).unwrap();
+        let after = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(names(tm.execute_statement(after, select()).unwrap()), vec![text("alicia"), text("bob"), text("carol")]);
+        assert_eq!(
+            names(tm.execute_statement(after, statement("SELECT id, name FROM users WHERE id = 3")).unwrap()),
+            vec![text("carol")]
+        );
+        let reef_db = tm.reef_db.lock().unwrap();
+        let (_, mut rows) = reef_db.storage.get_table_ref("users").unwrap().clone();
+        rows.sort();
+        assert_eq!(rows, vec![
+            vec![DataValue::Integer(1), text("alicia")],
+            vec![DataValue::Integer(2), text("bob")],
+            vec![DataValue::Integer(3), text("carol")],
+        ]);
+    }
+
+    // Run with `cargo test --release -- --ignored serializable_statements_on_large_table --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_serializable_statements_on_large_table() {
+        use std::time::Instant;
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        db.copy_into("users", (0..100_000)
+            .map(|i| vec![DataValue::Integer(i), DataValue::Text(format!("user{}", i).into())])).unwrap();
+        let wal = WriteAheadLog::new_in_memory().unwrap();
+        let mut tm = TransactionManager::create(db, wal);
+
+        let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        let start = Instant::now();
+        for i in 0..200 {
+            let (_, insert) = Statement::parse(&format!("INSERT INTO users VALUES ({}, 'new{}')", 100_000 + i, i)).unwrap();
+            tm.execute_statement(tx, insert).unwrap();
+        }
+        let elapsed = start.elapsed();
+        tm.commit_transaction(tx).unwrap();
+
+        println!("100k rows: 200 serializable inserts in {:?}", elapsed);
+        assert_eq!(tm.reef_db.lock().unwrap().tables.row_count("users"), Some(100_200));
+    }
+
+    #[test]
+    fn test_errors_are_typed_variants() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+
+        assert_eq!(tm.commit_transaction(42), Err(ReefDBError::TransactionNotFound(42)));
+        assert_eq!(tm.rollback_transaction(42), Err(ReefDBError::TransactionNotFound(42)));
+        assert!(matches!(tm.get_transaction_state(42), Err(ReefDBError::TransactionNotFound(42))));
+
+        // Lock errors keep the messages callers used to see as strings
+        assert_eq!(ReefDBError::LockPoisoned("WAL lock".to_string()).to_string(), "Failed to acquire WAL lock");
+    }
+
+    #[test]
+    fn test_begin_transaction_default_uses_configured_level() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap()
+            .with_default_isolation_level(IsolationLevel::Serializable).unwrap();
+        let tm = db.transaction_manager.as_mut().unwrap();
+        let serializable = tm.begin_transaction_default().unwrap();
+
+        tm.set_default_isolation_level(IsolationLevel::ReadCommitted);
+        assert_eq!(tm.get_default_isolation_level(), IsolationLevel::ReadCommitted);
+        let read_committed = tm.begin_transaction_default().unwrap();
+
+        let level = |tm: &TransactionManager<_, _>, id| tm.active_transactions[&id].get_isolation_level();
+        assert_eq!(level(tm, serializable), IsolationLevel::Serializable);
+        assert_eq!(level(tm, read_committed), IsolationLevel::ReadCommitted);
+
+        let wal = WriteAheadLog::new_in_memory().unwrap();
+        let mut tm = TransactionManager::create_with_isolation(
+            InMemoryReefDB::create_in_memory().unwrap(), wal, IsolationLevel::RepeatableRead,
+        );
+        let repeatable_read = tm.begin_transaction_default().unwrap();
+        assert_eq!(level(&tm, repeatable_read), IsolationLevel::RepeatableRead);
+
+        // Without a transaction manager there is nothing to configure
+        db.transaction_manager = None;
+        assert!(db.set_default_isolation_level(IsolationLevel::Serializable).is_err());
+    }
+
+    #[test]
+    fn test_poisoned_locks_are_recovered() {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+
+        fn poison<T: Send + 'static>(mutex: Arc<Mutex<T>>) {
+            let _ = std::thread::spawn(move || {
+                let _guard = mutex.lock().unwrap();
+                panic!("poisoning the lock");
+            }).join();
+        }
+        poison(tm.mvcc_manager.clone());
+        poison(tm.lock_manager.clone());
+        poison(tm.deadlock_detector.clone());
+        poison(tm.savepoint_manager.clone());
+        assert!(tm.mvcc_manager.is_poisoned());
+
+        let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        tm.acquire_lock(tx, "users", LockType::Exclusive).unwrap();
+        tm.create_savepoint(tx, "sp".to_string()).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey])],
+            false,
+        ))).unwrap();
+        tm.execute_statement(tx, Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(1)],
+        ))).unwrap();
+        tm.commit_transaction(tx).unwrap();
+
+        // The WAL isn't recovered: a torn append could otherwise be replayed
+        let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "orders".to_string(),
+            vec![ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey])],
+            false,
+        ))).unwrap();
+        poison(tm.wal.clone());
+        assert_eq!(
+            tm.commit_transaction(tx),
+            Err(ReefDBError::LockPoisoned("WAL lock".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_shutdown_rolls_back_active_transactions() {
+        let dir = tempdir().unwrap();
+        let wal_path = dir.path().join("test.wal");
+        let mut wal = WriteAheadLog::new(&wal_path).unwrap();
+        wal.set_fsync_policy(crate::wal::FsyncEvery::Never);
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+
+        let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "users".to_string(),
+            vec![ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey])],
+            false,
+        ))).unwrap();
+        tm.execute_statement(tx, Statement::Insert(InsertStatement::IntoTable(
+            "users".to_string(),
+            vec![DataValue::Integer(1)],
+        ))).unwrap();
+
+        tm.shutdown().unwrap();
+        tm.shutdown().unwrap();
+        assert!(tm.active_transactions.is_empty());
+        assert!(!tm.reef_db.lock().unwrap().tables.table_exists("users"));
+
+        // A fresh reader of the log file sees the logged insert but never a commit
+        let entries = WriteAheadLog::new(&wal_path).unwrap().read_entries().unwrap();
+        assert!(entries.iter().any(|e| e.transaction_id == tx && e.operation == WALOperation::Insert));
+        assert!(!entries.iter().any(|e| e.operation == WALOperation::Commit));
+    }
+
+    #[test]
+    fn test_copy_into_matches_per_row_inserts() {
+        let columns = vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+            ColumnDef::new("name", DataType::Text, vec![]),
+        ];
+        let rows: Vec<Vec<DataValue>> = (0..100_000)
+            .map(|i| vec![DataValue::Integer(i), DataValue::Text(format!("user{}", i % 1000).into())])
+            .collect();
+
+        // push_value scans the whole table per row for the primary key, so the per-row
+        // reference is loaded without it to keep this test fast
+        let mut expected = InMemoryReefDB::create_in_memory().unwrap();
+        let unconstrained = columns.iter().map(|c| ColumnDef::new(&c.name, c.data_type.clone(), vec![])).collect();
+        expected.handle_create("users".to_string(), unconstrained, false).unwrap();
+        expected.query("CREATE INDEX ON users(name)").unwrap();
+        for row in &rows {
+            expected.handle_insert("users".to_string(), row.clone()).unwrap();
+        }
+
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table("users".to_string(), columns, false))).unwrap();
+        let (_, create_index) = Statement::parse("CREATE INDEX ON users(name)").unwrap();
+        tm.execute_statement(tx, create_index).unwrap();
+
+        // A bad row anywhere rejects the whole load
+        let mut invalid = rows[..10].to_vec();
+        invalid.push(vec![DataValue::Text("oops".into()), DataValue::Text("x".into())]);
+        assert!(tm.copy_into(tx, "users", invalid).is_err());
+        let mut duplicate = rows[..10].to_vec();
+        duplicate.push(rows[3].clone());
+        assert!(tm.copy_into(tx, "users", duplicate).is_err());
+        assert_eq!(tm.get_transaction_state(tx).unwrap().row_count("users"), Some(0));
+
+        assert_eq!(tm.copy_into(tx, "users", rows.clone()).unwrap(), rows.len());
+        assert!(tm.copy_into(tx, "users", vec![rows[0].clone()]).is_err());
+
+        let transaction = &tm.active_transactions[&tx];
+        assert_eq!(transaction.reef_db.storage.get_table_ref("users").unwrap().1, rows);
+        assert_eq!(transaction.reef_db.tables.get_table_ref("users").unwrap().1, rows);
+        match (
+            transaction.reef_db.storage.get_index("users", "name").unwrap(),
+            expected.storage.get_index("users", "name").unwrap(),
+        ) {
+            (crate::indexes::index_manager::IndexType::BTree(loaded), crate::indexes::index_manager::IndexType::BTree(inserted)) => assert!(loaded.iter().eq(inserted.iter())),
+            _ => panic!("Expected B-Tree indexes"),
+        }
+
+        tm.commit_transaction(tx).unwrap();
+        let entries = tm.wal.lock().unwrap().read_entries().unwrap();
+        let operations: Vec<WALOperation> = entries.iter().map(|e| e.operation.clone()).collect();
+        assert_eq!(operations, vec![WALOperation::BulkInsert, WALOperation::Commit]);
+        assert_eq!(entries[0].rows().unwrap(), rows);
+    }
+
+    #[test]
+    fn test_copy_into_locks_the_table() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let row = vec![DataValue::Integer(1), DataValue::Text("Alice".into())];
+
+        let tx1 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let tx2 = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert_eq!(tm.copy_into(tx1, "users", vec![row.clone()]).unwrap(), 1);
+        assert!(matches!(tm.copy_into(tx2, "users", vec![row.clone()]), Err(ReefDBError::LockConflict(_))));
+        tm.commit_transaction(tx1).unwrap();
+        tm.rollback_transaction(tx2).unwrap();
+        assert_eq!(tm.reef_db.lock().unwrap().storage.get_table_ref("users").unwrap().1, vec![row]);
+    }
+
+    fn scan_fixture(rows: i64) -> (TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, u64) {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table(
+            "items".to_string(),
+            vec![
+                ColumnDef::new("id", DataType::Integer, vec![]),
+                ColumnDef::new("category", DataType::Text, vec![]),
+                ColumnDef::new("price", DataType::Integer, vec![]),
+            ],
+            false,
+        ))).unwrap();
+        tm.copy_into(tx, "items", (1..=rows).map(|id| vec![
+            DataValue::Integer(id),
+            DataValue::Text(format!("c{}", id % 10).into()),
+            DataValue::Integer((id * 7919) % 1000),
+        ])).unwrap();
+
+        // A concurrent writer commits new versions of a tenth of the rows, which
+        // scans must read from the MVCC snapshot rather than from storage
+        let mut mvcc_manager = lock_recovering(&tm.mvcc_manager);
+        let writer = tx.wrapping_add(1);
+        mvcc_manager.begin_transaction(writer);
+        for id in (3..=rows).step_by(10) {
+            let row = vec![DataValue::Integer(id), DataValue::Text("c3".into()), DataValue::Integer(5000)];
+            mvcc_manager.write(writer, KeyFormat::row("items", 0, &id.to_string()), row).unwrap();
+        }
+        mvcc_manager.commit(writer).unwrap();
+        drop(mvcc_manager);
+        (tm, tx)
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_sequential() {
+        let (mut tm, reader) = scan_fixture(20_000);
+        for sql in [
+            "SELECT * FROM items",
+            "SELECT id, price FROM items WHERE price > 900",
+            "SELECT category, id FROM items WHERE category = 'c3' ORDER BY id DESC",
+            "SELECT id, category, price FROM items ORDER BY price",
+        ] {
+            let (_, select) = Statement::parse(sql).unwrap();
+            tm.set_parallel_scan_threads(1);
+            let sequential = select_rows(tm.execute_statement(reader, select.clone()).unwrap());
+            tm.set_parallel_scan_threads(4);
+            let parallel = select_rows(tm.execute_statement(reader, select).unwrap());
+            assert!(!sequential.is_empty(), "{}", sql);
+            assert_eq!(sequential, parallel, "{}", sql);
+        }
+
+        // Committed versions are read from the shared snapshot on every worker
+        let (_, select) = Statement::parse("SELECT id FROM items WHERE price = 5000").unwrap();
+        assert_eq!(select_rows(tm.execute_statement(reader, select).unwrap()).len(), 2_000);
+    }
+
+    // Run with `cargo test --release -- --ignored parallel_scan_large_table --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_parallel_scan_large_table() {
+        let (mut tm, reader) = scan_fixture(200_000);
+        let (_, select) = Statement::parse("SELECT id, price FROM items WHERE price > 500").unwrap();
+
+        let start = std::time::Instant::now();
+        let sequential = select_rows(tm.execute_statement(reader, select.clone()).unwrap());
+        let sequential_time = start.elapsed();
+
+        tm.set_parallel_scan_threads(std::thread::available_parallelism().map_or(4, |n| n.get()));
+        let start = std::time::Instant::now();
+        let parallel = select_rows(tm.execute_statement(reader, select).unwrap());
+        let parallel_time = start.elapsed();
+
+        println!("Scan of 200000 rows: sequential {:?}, parallel {:?}", sequential_time, parallel_time);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_cancel_stops_statement() {
+        let (mut tm, reader) = scan_fixture(1_000);
+        let (_, select) = Statement::parse("SELECT id, price FROM items WHERE price > 500").unwrap();
+        let token = tm.cancellation_token(reader).unwrap();
+
+        // A token set before the statement starts stops it at the first row
+        token.cancel();
+        assert_eq!(tm.execute_statement(reader, select.clone()), Err(ReefDBError::Cancelled));
+        let (_, update) = Statement::parse("UPDATE items SET price = 0 WHERE price > 500").unwrap();
+        assert_eq!(tm.execute_statement(reader, update), Err(ReefDBError::Cancelled));
+        token.reset();
+        assert!(tm.execute_statement(reader, select).is_ok());
+    }
+
+    #[test]
+    fn test_scan_stops_at_the_row_after_cancel() {
+        let items: Vec<usize> = (0..10_000).collect();
+        for threads in [1, 4] {
+            let token = CancellationToken::new();
+            let visited = AtomicUsize::new(0);
+            let result = parallel_filter_map(&items, threads, &token, |_, item| {
+                if visited.fetch_add(1, AtomicOrdering::SeqCst) + 1 == 100 {
+                    token.cancel();
+                }
+                Some(*item)
+            });
+            assert_eq!(result, Err(ReefDBError::Cancelled));
+            // Each worker finishes at most the row it was on when the token was set
+            let visited = visited.load(AtomicOrdering::SeqCst);
+            assert!(visited >= 100 && visited < 100 + threads, "visited {} rows with {} threads", visited, threads);
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        scanned: Mutex<Vec<(String, usize)>>,
+        returned: AtomicUsize,
+        lock_requests: AtomicUsize,
+        lock_waits: AtomicUsize,
+        commits: AtomicUsize,
+    }
+
+    impl MetricsSink for RecordingMetrics {
+        fn rows_scanned(&self, table: &str, rows: usize) {
+            self.scanned.lock().unwrap().push((table.to_string(), rows));
+        }
+
+        fn rows_returned(&self, rows: usize) {
+            self.returned.fetch_add(rows, AtomicOrdering::SeqCst);
+        }
+
+        fn lock_requested(&self, _transaction_id: u64, _resource: &str) {
+            self.lock_requests.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+
+        fn lock_waited(&self, _transaction_id: u64, _resource: &str) {
+            self.lock_waits.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+
+        fn transaction_committed(&self, _transaction_id: u64) {
+            self.commits.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_metrics_sink_records_scans_and_commits() {
+        let (mut tm, reader) = scan_fixture(1_000);
+        let metrics = Arc::new(RecordingMetrics::default());
+        tm.set_metrics_sink(metrics.clone());
+
+        let (_, select) = Statement::parse("SELECT id FROM items WHERE price > 500").unwrap();
+        let returned = select_rows(tm.execute_statement(reader, select).unwrap()).len();
+        assert_eq!(*metrics.scanned.lock().unwrap(), vec![("items".to_string(), 1_000)]);
+        assert_eq!(metrics.returned.load(AtomicOrdering::SeqCst), returned);
+        tm.commit_transaction(reader).unwrap();
+        assert_eq!(metrics.commits.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_metrics_sink_records_only_queued_lock_waits() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, price INTEGER)").unwrap();
+        db.query("INSERT INTO items VALUES (7, 70)").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let metrics = Arc::new(RecordingMetrics::default());
+        tm.set_metrics_sink(metrics.clone());
+
+        // A second writer of a locked row is refused at once rather than queued
+        let (_, update) = Statement::parse("UPDATE items SET price = 1 WHERE id = 7").unwrap();
+        let writer = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(writer, update.clone()).unwrap();
+        let other = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert!(matches!(tm.execute_statement(other, update.clone()), Err(ReefDBError::LockConflict(_))));
+        assert_eq!(metrics.lock_waits.load(AtomicOrdering::SeqCst), 0);
+        tm.rollback_transaction(writer).unwrap();
+        tm.rollback_transaction(other).unwrap();
+
+        // Upgrading a shared table lock another reader also holds queues the writer, once
+        let first = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let second = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.acquire_lock(first, "items", LockType::Shared).unwrap();
+        tm.acquire_lock(second, "items", LockType::Shared).unwrap();
+        assert!(matches!(tm.execute_statement(first, update.clone()), Err(ReefDBError::LockConflict(_))));
+        assert!(matches!(tm.execute_statement(first, update), Err(ReefDBError::LockConflict(_))));
+        assert_eq!(metrics.lock_waits.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    // WAL file in memory that keeps a copy of its contents as of each fsync
+    #[derive(Clone, Default)]
+    struct SyncedFile {
+        contents: Arc<Mutex<std::io::Cursor<Vec<u8>>>>,
+        durable: Arc<Mutex<Vec<u8>>>,
+        syncs: Arc<AtomicUsize>,
+    }
+
+    impl std::io::Read for SyncedFile {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.contents.lock().unwrap().read(buf)
+        }
+    }
+
+    impl std::io::Write for SyncedFile {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.contents.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl std::io::Seek for SyncedFile {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.contents.lock().unwrap().seek(pos)
+        }
+    }
+
+    impl crate::wal::WalFile for SyncedFile {
+        fn sync_all(&mut self) -> std::io::Result<()> {
+            *self.durable.lock().unwrap() = self.contents.lock().unwrap().get_ref().clone();
+            self.syncs.fetch_add(1, AtomicOrdering::SeqCst);
+            Ok(())
+        }
+
+        fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+            self.contents.lock().unwrap().get_mut().truncate(len as usize);
+            Ok(())
+        }
+    }
+
+    impl SyncedFile {
+        // Transactions whose commit entry an fsync has made durable
+        fn durable_commits(&self) -> HashSet<u64> {
+            let durable = self.durable.lock().unwrap();
+            let mut commits = HashSet::new();
+            let mut rest = durable.as_slice();
+            while rest.len() >= 8 {
+                let len = u64::from_le_bytes(rest[..8].try_into().unwrap()) as usize;
+                let entry: WALEntry = bincode::deserialize(&rest[8..8 + len]).unwrap();
+                if entry.operation == WALOperation::Commit {
+                    commits.insert(entry.transaction_id);
+                }
+                rest = &rest[8 + len..];
+            }
+            commits
+        }
+    }
+
+    #[test]
+    fn test_group_commit_batches_fsyncs_of_concurrent_commits() {
+        use crate::wal::FsyncEvery;
+
+        const THREADS: usize = 8;
+        const COMMITS: usize = 5;
+        let file = SyncedFile::default();
+        let mut wal = WriteAheadLog::with_file(Box::new(file.clone()), 0);
+        wal.set_fsync_policy(FsyncEvery::Commit);
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let setup = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        tm.execute_statement(setup, Statement::parse("CREATE TABLE events (id INTEGER PRIMARY KEY, writer INTEGER)").unwrap().1).unwrap();
+        tm.commit_transaction(setup).unwrap();
+        tm.set_group_commit_window(Some(std::time::Duration::from_millis(5)));
+        let syncs_before = file.syncs.load(AtomicOrdering::SeqCst);
+        let start = Arc::new(std::sync::Barrier::new(THREADS));
+
+        let writers: Vec<_> = (0..THREADS)
+            .map(|writer| {
+                let (file, mut session, start) = (file.clone(), tm.session(), start.clone());
+                std::thread::spawn(move || {
+                    start.wait();
+                    let mut committed = Vec::new();
+                    for i in 0..COMMITS {
+                        let tx = session.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+                        let sql = format!("INSERT INTO events VALUES ({}, {})", writer * COMMITS + i, writer);
+                        session.execute_statement(tx, Statement::parse(&sql).unwrap().1).unwrap();
+                        session.commit_transaction(tx).unwrap();
+                        // Durable by the time the commit returns
+                        assert!(file.durable_commits().contains(&tx), "commit of {} returned before its fsync", tx);
+                        committed.push(tx);
+                    }
+                    committed
+                })
+            })
+            .collect();
+        let committed: Vec<u64> = writers.into_iter().flat_map(|writer| writer.join().unwrap()).collect();
+
+        assert_eq!(committed.len(), THREADS * COMMITS);
+        let mut durable: HashSet<u64> = committed.iter().copied().collect();
+        durable.insert(setup);
+        assert_eq!(file.durable_commits(), durable);
+        // Every commit entry follows its transaction's insert, and every row made it in
+        let entries = lock_recovering(&tm.wal).read_entries().unwrap();
+        for tx in &committed {
+            let position = |operation: WALOperation| entries.iter()
+                .position(|entry| entry.transaction_id == *tx && entry.operation == operation);
+            assert!(position(WALOperation::Insert).unwrap() < position(WALOperation::Commit).unwrap());
+        }
+        let reader = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let (_, count) = Statement::parse("SELECT id FROM events").unwrap();
+        assert_eq!(select_rows(tm.execute_statement(reader, count).unwrap()).len(), THREADS * COMMITS);
+        let syncs = file.syncs.load(AtomicOrdering::SeqCst) - syncs_before;
+        assert!(syncs < committed.len(), "{} fsyncs for {} commits", syncs, committed.len());
+    }
+
+    #[test]
+    fn test_fetch_first_with_ties_in_transaction() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE scores (id INTEGER PRIMARY KEY, points INTEGER)").unwrap();
+        for (id, points) in [(1, 90), (2, 70), (3, 70), (4, 60)] {
+            db.query(&format!("INSERT INTO scores VALUES ({}, {})", id, points)).unwrap();
+        }
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let fetch = |tm: &mut TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, sql: &str| {
+            select_rows(tm.execute_statement(tx, Statement::parse(sql).unwrap().1).unwrap())
+                .into_iter().map(|(_, row)| row).collect::<Vec<_>>()
+        };
+        let ids = |ids: &[i64]| ids.iter().map(|&id| vec![DataValue::Integer(id)]).collect::<Vec<_>>();
+        assert_eq!(fetch(&mut tm, "SELECT id FROM scores ORDER BY points DESC, id LIMIT 2"), ids(&[1, 2]));
+        assert_eq!(fetch(&mut tm, "SELECT id FROM scores ORDER BY points DESC FETCH FIRST 2 ROWS WITH TIES").len(), 3);
+        assert_eq!(
+            tm.execute_statement(tx, Statement::parse("SELECT id FROM scores FETCH NEXT 2 ROWS WITH TIES").unwrap().1),
+            Err(ReefDBError::Other("WITH TIES needs an ORDER BY".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_temporary_tables_are_private_and_dropped_on_commit() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT)").unwrap();
+        db.query("INSERT INTO events VALUES (1, 'base')").unwrap();
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let metrics = Arc::new(RecordingMetrics::default());
+        tm.set_metrics_sink(metrics.clone());
+        let run = |tm: &mut TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, tx: u64, sql: &str| {
+            tm.execute_statement(tx, Statement::parse(sql).unwrap().1)
+        };
+        let rows = |result: Result<ReefDBResult, ReefDBError>| {
+            select_rows(result.unwrap()).into_iter().map(|(_, row)| row).collect::<Vec<_>>()
+        };
+        let text = |s: &str| DataValue::Text(s.into());
+
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let other = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let requests = metrics.lock_requests.load(AtomicOrdering::SeqCst);
+        run(&mut tm, tx, "CREATE TEMPORARY TABLE events (id INTEGER PRIMARY KEY, note TEXT)").unwrap();
+        run(&mut tm, tx, "INSERT INTO events VALUES (7, 'scratch')").unwrap();
+        run(&mut tm, tx, "CREATE TEMP TABLE staging (id INTEGER PRIMARY KEY)").unwrap();
+        run(&mut tm, tx, "INSERT INTO staging VALUES (1)").unwrap();
+        run(&mut tm, tx, "UPDATE staging SET id = 2 WHERE id = 1").unwrap();
+        assert_eq!(metrics.lock_requests.load(AtomicOrdering::SeqCst), requests);
+
+        // The temporary table hides the base table from its own transaction only
+        assert_eq!(rows(run(&mut tm, tx, "SELECT * FROM events")), vec![vec![DataValue::Integer(7), text("scratch")]]);
+        assert_eq!(rows(run(&mut tm, tx, "SELECT * FROM staging")), vec![vec![DataValue::Integer(2)]]);
+        assert_eq!(rows(run(&mut tm, other, "SELECT * FROM events")), vec![vec![DataValue::Integer(1), text("base")]]);
+        assert!(matches!(run(&mut tm, other, "SELECT * FROM staging"), Err(ReefDBError::TableNotFound(_))));
+
+        // Dropping one brings the base table back for the rest of the transaction
+        run(&mut tm, tx, "DROP TABLE events").unwrap();
+        assert_eq!(rows(run(&mut tm, tx, "SELECT * FROM events")), vec![vec![DataValue::Integer(1), text("base")]]);
+        run(&mut tm, tx, "CREATE TEMP TABLE events (id INTEGER PRIMARY KEY)").unwrap();
+
+        tm.commit_transaction(tx).unwrap();
+        tm.commit_transaction(other).unwrap();
+        let after = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert!(matches!(run(&mut tm, after, "SELECT * FROM staging"), Err(ReefDBError::TableNotFound(_))));
+        assert_eq!(rows(run(&mut tm, after, "SELECT * FROM events")), vec![vec![DataValue::Integer(1), text("base")]]);
+        assert!(!tm.get_transaction_state(after).unwrap().table_exists("staging"));
+
+        // Rolling back ends them the same way
+        run(&mut tm, after, "CREATE TEMP TABLE staging (id INTEGER)").unwrap();
+        tm.rollback_transaction(after).unwrap();
+        let last = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        assert!(matches!(run(&mut tm, last, "SELECT * FROM staging"), Err(ReefDBError::TableNotFound(_))));
+    }
+
+    #[test]
+    fn test_temporary_tables_never_reach_the_backend() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.db").to_str().unwrap().to_string();
+        let text = |s: &str| DataValue::Text(s.into());
+        let base_rows = vec![vec![DataValue::Integer(1), text("base")]];
+        {
+            let mut db = crate::OnDiskReefDB::create_on_disk(path.clone(), String::new()).unwrap();
+            db.query("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT)").unwrap();
+            db.query("INSERT INTO events VALUES (1, 'base')").unwrap();
+            let mut tm = TransactionManager::create(db, WriteAheadLog::new(dir.path().join("app.wal")).unwrap());
+            let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+            for sql in [
+                "CREATE TEMP TABLE events (id INTEGER PRIMARY KEY, note TEXT)",
+                "CREATE INDEX ON events(note)",
+                "INSERT INTO events VALUES (7, 'scratch')",
+                "CREATE TEMP TABLE staging (id INTEGER PRIMARY KEY)",
+                "INSERT INTO staging VALUES (1)",
+            ] {
+                tm.execute_statement(tx, Statement::parse(sql).unwrap().1).unwrap();
+            }
+            let storage = &tm.active_transactions[&tx].reef_db.storage;
+            assert_eq!(storage.get_table_ref("events").unwrap().1, vec![vec![DataValue::Integer(7), text("scratch")]]);
+            assert_eq!(storage.base().get_table_ref("events").unwrap().1, base_rows);
+            assert!(!storage.base().table_exists("staging"));
+            tm.commit_transaction(tx).unwrap();
+        }
+
+        let mut db = crate::OnDiskReefDB::create_on_disk(path, String::new()).unwrap();
+        assert!(!db.storage.table_exists("staging"));
+        assert_eq!(db.storage.get_table_ref("events").unwrap().1, base_rows);
+
+        // With autocommit off, CREATE TEMP TABLE runs in an implicit transaction that ends it
+        db.set_autocommit(false);
+        db.execute_statement(Statement::parse("CREATE TEMP TABLE staging (id INTEGER)").unwrap().1).unwrap();
+        assert!(!db.tables.is_temporary("staging"));
+        assert!(!db.storage.table_exists("staging"));
+    }
+
+    #[test]
+    fn test_expired_rows_are_skipped_by_transactions_and_after_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cache.db").to_str().unwrap().to_string();
+        let clock = crate::clock::MockClock::default();
+        let keys = |result: ReefDBResult| select_rows(result).into_iter().map(|(_, row)| row[0].clone()).collect::<Vec<_>>();
+        let (_, select) = Statement::parse("SELECT id FROM cache").unwrap();
+        {
+            let db = crate::OnDiskReefDB::create_on_disk(path.clone(), String::new()).unwrap();
+            let mut tm = TransactionManager::create(db, WriteAheadLog::new(dir.path().join("cache.wal")).unwrap());
+            tm.set_clock(Arc::new(clock.clone()));
+            for (sql, advance) in [
+                ("CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT)", 0),
+                ("INSERT INTO tags VALUES (1, 'one')", 0),
+                ("INSERT INTO tags VALUES (2, 'two')", 0),
+                ("CREATE TABLE cache (id INTEGER PRIMARY KEY, value TEXT) WITH (TTL = 60)", 0),
+                ("INSERT INTO cache VALUES (1, 'first')", 30),
+                ("INSERT INTO cache VALUES (2, 'second')", 30),
+            ] {
+                let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+                tm.execute_statement(tx, Statement::parse(sql).unwrap().1).unwrap();
+                tm.commit_transaction(tx).unwrap();
+                clock.advance(std::time::Duration::from_secs(advance));
+            }
+
+            // Row 1 reached its TTL, row 2 is only 30 seconds old
+            let tx = tm.begin_transaction(IsolationLevel::Serializable).unwrap();
+            assert_eq!(keys(tm.execute_statement(tx, select.clone()).unwrap()), vec![DataValue::Integer(2)]);
+            let (_, join) = Statement::parse("SELECT tags.name FROM tags INNER JOIN cache ON tags.id = cache.id").unwrap();
+            assert_eq!(keys(tm.execute_statement(tx, join).unwrap()), vec![DataValue::Text("two".into())]);
+            tm.commit_transaction(tx).unwrap();
+        }
+
+        // Insertion times are stored with the rows, so expiry carries on after a reopen
+        let mut db = crate::OnDiskReefDB::create_on_disk(path, String::new()).unwrap();
+        db.set_clock(Arc::new(clock.clone()));
+        assert_eq!(keys(db.execute_statement(select.clone()).unwrap()), vec![DataValue::Integer(2)]);
+        clock.advance(std::time::Duration::from_secs(30));
+        assert!(keys(db.execute_statement(select).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_read_committed_selects_skip_the_lock_manager() {
+        let mut db = InMemoryReefDB::create_in_memory().unwrap();
+        db.query("CREATE TABLE items (id INTEGER PRIMARY KEY, price INTEGER)").unwrap();
+        for id in 1..=5 {
+            db.query(&format!("INSERT INTO items VALUES ({}, {})", id, id * 10)).unwrap();
+        }
+        let mut tm = TransactionManager::create(db, WriteAheadLog::new_in_memory().unwrap());
+        let metrics = Arc::new(RecordingMetrics::default());
+        tm.set_metrics_sink(metrics.clone());
+        let reader = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+
+        let writer = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let (_, update) = Statement::parse("UPDATE items SET price = 99 WHERE id = 3").unwrap();
+        tm.execute_statement(writer, update).unwrap();
+        tm.commit_transaction(writer).unwrap();
+        let requests = metrics.lock_requests.load(AtomicOrdering::SeqCst);
+
+        // Selects read the version committed after the reader began, without a lock request
+        let (_, select) = Statement::parse("SELECT id, price FROM items WHERE id = 3").unwrap();
+        for _ in 0..10 {
+            let rows = select_rows(tm.execute_statement(reader, select.clone()).unwrap());
+            assert_eq!(rows[0].1, vec![DataValue::Integer(3), DataValue::Integer(99)]);
+        }
+        assert_eq!(metrics.lock_requests.load(AtomicOrdering::SeqCst), requests);
+
+        // Writes still lock the rows they touch, and later reads see them from the
+        // transaction's own copy
+        let (_, update) = Statement::parse("UPDATE items SET price = 1 WHERE id = 3").unwrap();
+        tm.execute_statement(reader, update).unwrap();
+        assert_eq!(metrics.lock_requests.load(AtomicOrdering::SeqCst), requests + 1);
+        let rows = select_rows(tm.execute_statement(reader, select).unwrap());
+        assert_eq!(rows[0].1, vec![DataValue::Integer(3), DataValue::Integer(1)]);
+        assert_eq!(metrics.lock_requests.load(AtomicOrdering::SeqCst), requests + 1);
+    }
+
+    fn wide_table_fixture(width: usize, rows: i64) -> (TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, u64, Vec<Vec<DataValue>>) {
+        let dir = tempdir().unwrap();
+        let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+        let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+        let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+        let mut columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+        columns.extend((1..width).map(|c| ColumnDef::new(&format!("c{}", c), DataType::Text, vec![])));
+        tm.execute_statement(tx, Statement::Create(CreateStatement::Table("wide".to_string(), columns, false))).unwrap();
+
+        let data: Vec<Vec<DataValue>> = (1..=rows)
+            .map(|id| {
+                let mut row = vec![DataValue::Integer(id)];
+                row.extend((1..width).map(|c| DataValue::Text(format!("row {} column {}", (id * 37) % rows, c).into())));
+                row
+            })
+            .collect();
+        tm.copy_into(tx, "wide", data.clone()).unwrap();
+        (tm, tx, data)
+    }
+
+    #[test]
+    fn test_single_table_select_projects_before_copying() {
+        let (mut tm, tx, data) = wide_table_fixture(12, 200);
+        let query = |tm: &mut TransactionManager<InMemoryStorage, crate::fts::default::DefaultSearchIdx>, sql: &str| {
+            let (_, select) = Statement::parse(sql).unwrap();
+            select_rows(tm.execute_statement(tx, select).unwrap())
+        };
+
+        let all = query(&mut tm, "SELECT * FROM wide");
+        assert_eq!(all.iter().map(|(_, row)| row.clone()).collect::<Vec<_>>(), data);
+
+        // Projected columns come back in the order they were asked for
+        let projected = query(&mut tm, "SELECT c7, id FROM wide WHERE id <= 3");
+        assert_eq!(projected, (0..3).map(|i| (i, vec![data[i][7].clone(), data[i][0].clone()])).collect::<Vec<_>>());
+
+        // Sorting on a column that isn't projected, qualified or not
+        let mut expected: Vec<_> = data.iter().enumerate().map(|(i, row)| (i, vec![row[0].clone()])).collect();
+        expected.sort_by(|a, b| data[b.0][3].cmp(&data[a.0][3]));
+        assert_eq!(query(&mut tm, "SELECT id FROM wide ORDER BY c3 DESC"), expected);
+        assert_eq!(query(&mut tm, "SELECT wide.id FROM wide ORDER BY wide.c3 DESC"), expected);
+
+        // Columns and sort keys that belong to another table are ignored
+        let (_, select) = Statement::parse("SELECT id, other.c1 FROM wide ORDER BY other.c1").unwrap();
+        let ignored = select_rows(tm.execute_statement(tx, select).unwrap());
+        assert_eq!(ignored, data.iter().enumerate().map(|(i, row)| (i, vec![row[0].clone()])).collect::<Vec<_>>());
+    }
+
+    fn compile_fixture() -> (Vec<ColumnDef>, Vec<Vec<DataValue>>) {
+        use crate::sql::{collation::Collation, constraints::constraint::Constraint};
+        let schema = vec![
+            ColumnDef::new("id", DataType::Integer, vec![]),
+            ColumnDef::new("name", DataType::Text, vec![Constraint::Collate(Collation::NoCase)]),
+            ColumnDef::new("score", DataType::Integer, vec![]),
+        ];
+        let names = ["alice", "Bob", "ALICE", "carol", "bob"];
+        let rows = (0..50)
+            .map(|i| vec![
+                DataValue::Integer(i),
+                DataValue::Text(names[i as usize % names.len()].into()),
+                if i % 7 == 0 { DataValue::Null } else { DataValue::Integer(i * 3 % 17) },
+            ])
+            .collect();
+        (schema, rows)
+    }
+
+    #[test]
+    fn test_compiled_where_matches_interpreted_evaluation() {
+        use crate::sql::clauses::wheres::where_type::parse_where_clause;
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let (schema, rows) = compile_fixture();
+        let stats = TableStatistics::analyze(&schema, &rows, crate::statistics::DEFAULT_HISTOGRAM_BUCKETS);
+        let no_fts = FtsMatches::new();
+
+        for sql in [
+            "WHERE id = 3",
+            "WHERE name = 'alice'",
+            "WHERE name != 'BOB'",
+            "WHERE score > 8",
+            "WHERE score <= 4 AND name = 'carol'",
+            "WHERE id < 10 OR score >= 12",
+            "WHERE (name = 'bob' OR name = 'carol') AND id > 20",
+            "WHERE missing = 1",
+            "WHERE missing = 1 AND id = 0",
+            "WHERE name LIKE 'a%' AND id = 5",
+            "WHERE name LIKE '%o%' OR score = 3 OR id > 45",
+            "WHERE (name LIKE 'b%' AND score > 2) OR (id < 5 AND name LIKE '%e')",
+        ] {
+            let (_, where_clause) = parse_where_clause(sql).unwrap();
+            let compiled = CompiledWhere::compile(&where_clause, &schema, &no_fts);
+            let reordered = CompiledWhere::compile(&where_clause, &schema, &no_fts)
+                .reorder(&schema, Some(&stats));
+            for row in &rows {
+                let interpreted = db
+                    .evaluate_where_clause(&where_clause, row, &[], &schema, &[], "t")
+                    .unwrap_or(false);
+                assert_eq!(compiled.matches(row), interpreted, "{} on {:?}", sql, row);
+                assert_eq!(reordered.matches(row), interpreted, "reordered {} on {:?}", sql, row);
+            }
+        }
+    }
+
+    // Run with `cargo test --release -- --ignored compiled_where_million_row_scan --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_compiled_where_million_row_scan() {
+        use crate::sql::clauses::wheres::where_type::parse_where_clause;
+        use std::time::Instant;
+        let db = InMemoryReefDB::create_in_memory().unwrap();
+        let (schema, _) = compile_fixture();
+        let rows: Vec<Vec<DataValue>> = (0..1_000_000)
+            .map(|i| vec![
+                DataValue::Integer(i),
+                DataValue::Text(format!("name{}", i % 100).into()),
+                DataValue::Integer(i % 1000),
+            ])
+            .collect();
+        let (_, where_clause) = parse_where_clause("WHERE score > 500 AND (id < 900000 OR name = 'name7')").unwrap();
+        let no_fts = FtsMatches::new();
+
+        let start = Instant::now();
+        let interpreted = rows.iter()
+            .filter(|row| db.evaluate_where_clause(&where_clause, row, &[], &schema, &[], "t").unwrap_or(false))
+            .count();
+        let interpreted_time = start.elapsed();
+
+        let start = Instant::now();
+        let compiled = CompiledWhere::compile(&where_clause, &schema, &no_fts);
+        let compiled_count = rows.iter().filter(|row| compiled.matches(row)).count();
+        let compiled_time = start.elapsed();
+
+        println!(
+            "1M rows: interpreted {:?} ({:.1} ns/row), compiled {:?} ({:.1} ns/row)",
+            interpreted_time,
+            interpreted_time.as_nanos() as f64 / rows.len() as f64,
+            compiled_time,
+            compiled_time.as_nanos() as f64 / rows.len() as f64,
+        );
+        assert_eq!(interpreted, compiled_count);
+        assert!(compiled_time < interpreted_time);
+    }
+
+    // Checks a row the way `CompiledWhere::matches` does, counting the LIKE terms run
+    fn count_like_checks(predicate: &CompiledWhere, row: &[DataValue], checks: &mut usize) -> bool {
+        match predicate {
+            CompiledWhere::Compare { operator: Op::Like, .. } => {
+                *checks += 1;
+                predicate.matches(row)
+            }
+            CompiledWhere::All(terms) => terms.iter().all(|term| count_like_checks(term, row, checks)),
+            CompiledWhere::Any(terms) => terms.iter().any(|term| count_like_checks(term, row, checks)),
+            _ => predicate.matches(row),
+        }
+    }
+
+    fn term_columns(predicate: &CompiledWhere) -> Vec<usize> {
+        match predicate {
+            CompiledWhere::All(terms) | CompiledWhere::Any(terms) => terms.iter()
+                .map(|term| match term {
+                    CompiledWhere::Compare { column, .. } => *column,
+                    _ => usize::MAX,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_reorder_runs_cheap_and_selective_terms_first() {
+        use crate::sql::clauses::wheres::where_type::parse_where_clause;
+        let (schema, rows) = compile_fixture();
+        let no_fts = FtsMatches::new();
+
+        // Without statistics equality runs before a range and LIKE runs last
+        let (_, where_clause) = parse_where_clause("WHERE name LIKE '%a%' AND score > 3 AND id = 7").unwrap();
+        let reordered = CompiledWhere::compile(&where_clause, &schema, &no_fts).reorder(&schema, None);
+        assert_eq!(term_columns(&reordered), vec![0, 2, 1]);
+
+        // Histograms override the defaults: `id >= 49` keeps one row of 50, `score = 5` about
+        // one in seventeen
+        let stats = TableStatistics::analyze(&schema, &rows, crate::statistics::DEFAULT_HISTOGRAM_BUCKETS);
+        let (_, where_clause) = parse_where_clause("WHERE score = 5 AND id >= 49").unwrap();
+        let reordered = CompiledWhere::compile(&where_clause, &schema, &no_fts).reorder(&schema, Some(&stats));
+        assert_eq!(term_columns(&reordered), vec![0, 2]);
+
+        // Under OR the term most likely to match runs first
+        let (_, where_clause) = parse_where_clause("WHERE id = 3 OR name LIKE '%' OR id != 3").unwrap();
+        let reordered = CompiledWhere::compile(&where_clause, &schema, &no_fts).reorder(&schema, None);
+        assert_eq!(term_columns(&reordered), vec![0, 0, 1]);
+        assert!(matches!(&reordered, CompiledWhere::Any(terms)
+            if matches!(terms[0], CompiledWhere::Compare { operator: Op::NotEqual, .. })));
+
+        // The LIKE only runs on rows the equality lets through
+        let (_, where_clause) = parse_where_clause("WHERE name LIKE '%o%' AND id = 1").unwrap();
+        let as_written = CompiledWhere::compile(&where_clause, &schema, &no_fts);
+        let reordered = CompiledWhere::compile(&where_clause, &schema, &no_fts).reorder(&schema, Some(&stats));
+        let (mut written_checks, mut reordered_checks) = (0, 0);
+        for row in &rows {
+            assert_eq!(
+                count_like_checks(&as_written, row, &mut written_checks),
+                count_like_checks(&reordered, row, &mut reordered_checks),
+            );
+        }
+        assert_eq!(written_checks, rows.len());
+        assert_eq!(reordered_checks, 1);
+    }
+
+    // Run with `cargo test --release -- --ignored reordered_where_million_row_scan --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_reordered_where_million_row_scan() {
+        use crate::sql::clauses::wheres::where_type::parse_where_clause;
+        use std::time::Instant;
+        let (schema, _) = compile_fixture();
+        let rows: Vec<Vec<DataValue>> = (0..1_000_000)
+            .map(|i| vec![
+                DataValue::Integer(i),
+                DataValue::Text(format!("customer-{}-{}", i % 100, i % 7).into()),
+                DataValue::Integer(i % 1000),
+            ])
+            .collect();
+        let stats = TableStatistics::analyze(&schema, &rows, crate::statistics::DEFAULT_HISTOGRAM_BUCKETS);
+        let (_, where_clause) = parse_where_clause("WHERE name LIKE '%-4%-3' AND score = 250").unwrap();
+        let no_fts = FtsMatches::new();
+
+        let timed = |predicate: &CompiledWhere| {
+            let mut checks = 0;
+            let start = Instant::now();
+            let count = rows.iter().filter(|row| count_like_checks(predicate, row, &mut checks)).count();
+            (count, checks, start.elapsed())
+        };
+        let (written_count, written_checks, written_time) =
+            timed(&CompiledWhere::compile(&where_clause, &schema, &no_fts));
+        let (reordered_count, reordered_checks, reordered_time) =
+            timed(&CompiledWhere::compile(&where_clause, &schema, &no_fts).reorder(&schema, Some(&stats)));
+
+        println!(
+            "1M rows: as written {} LIKE checks in {:?}, reordered {} LIKE checks in {:?}",
+            written_checks, written_time, reordered_checks, reordered_time,
+        );
+        assert_eq!(written_count, reordered_count);
+        assert_eq!(written_checks, rows.len());
+        assert_eq!(reordered_checks, 1_000);
+        assert!(reordered_time < written_time);
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+
+use crate::sql::column_def::ColumnDef;
+use crate::sql::constraints::constraint::Constraint;
+use crate::sql::data_type::DataType;
+use crate::sql::data_value::DataValue;
+
+/// Column added to tables created `WITH (TTL = n)` holding when each row was inserted
+pub const INSERTED_AT_COLUMN: &str = "inserted_at";
+
+// Matches the format of now(), so timestamps compare as strings
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The `inserted_at` column of a table whose rows expire `ttl` after they are inserted.
+/// Inserts that leave it out get the current time.
+pub fn inserted_at_column(ttl: Duration) -> ColumnDef {
+    ColumnDef::new(INSERTED_AT_COLUMN, DataType::Timestamp, vec![
+        Constraint::Default(DataValue::Function { name: "now".to_string(), args: vec![] }),
+        Constraint::Ttl(ttl),
+    ])
+}
+
+/// Which rows of a table have expired at a given time. Expiry is read from the rows
+/// themselves, so it holds across restarts and for every reader of the table; expired
+/// rows stay stored until deleted but are skipped by scans.
+#[derive(Debug, Clone, Default)]
+pub struct Expiry {
+    // Position of the TTL column and the latest time it may hold for a row to be expired
+    cutoff: Option<(usize, String)>,
+}
+
+impl Expiry {
+    pub fn new(schema: &[ColumnDef], now: SystemTime) -> Self {
+        let cutoff = schema.iter().enumerate().find_map(|(idx, column)| {
+            let cutoff = now.checked_sub(column.ttl()?)?;
+            Some((idx, DateTime::<Utc>::from(cutoff).format(TIMESTAMP_FORMAT).to_string()))
+        });
+        Self { cutoff }
+    }
+
+    /// Whether the table has a TTL at all
+    pub fn applies(&self) -> bool {
+        self.cutoff.is_some()
+    }
+
+    pub fn is_expired(&self, row: &[DataValue]) -> bool {
+        let Some((idx, cutoff)) = &self.cutoff else { return false };
+        matches!(row.get(*idx), Some(DataValue::Timestamp(inserted)) if inserted.as_str() <= cutoff.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_expire_after_ttl() {
+        let schema = vec![
+            ColumnDef::new("id", DataType::Integer, vec![Constraint::PrimaryKey]),
+            inserted_at_column(Duration::from_secs(10)),
+        ];
+        let row = |inserted: &str| vec![DataValue::Integer(1), DataValue::Timestamp(inserted.to_string())];
+        let start = SystemTime::UNIX_EPOCH;
+
+        let expiry = Expiry::new(&schema, start + Duration::from_secs(9));
+        assert!(!expiry.is_expired(&row("1970-01-01 00:00:00")));
+        let expiry = Expiry::new(&schema, start + Duration::from_secs(10));
+        assert!(expiry.is_expired(&row("1970-01-01 00:00:00")));
+        assert!(!expiry.is_expired(&row("1970-01-01 00:00:05")));
+        // Rows without an insertion time never expire
+        assert!(!expiry.is_expired(&[DataValue::Integer(1), DataValue::Null]));
+
+        // Tables without a TTL keep every row
+        let expiry = Expiry::new(&schema[..1], start + Duration::from_secs(60));
+        assert!(!expiry.applies());
+        assert!(!expiry.is_expired(&row("1970-01-01 00:00:00")));
+    }
+}
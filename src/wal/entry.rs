@@ -1,6 +1,9 @@
 use std::time::SystemTime;
 use serde::{Serialize, Deserialize};
 
+use crate::error::ReefDBError;
+use crate::sql::data_value::DataValue;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum WALOperation {
     Insert,
@@ -11,6 +14,8 @@ pub enum WALOperation {
     AlterTable,
     Commit,
     Rollback,
+    /// Rows loaded by `copy_into`, all carried by a single entry
+    BulkInsert,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -20,4 +25,45 @@ pub struct WALEntry {
     pub operation: WALOperation,
     pub table_name: String,
     pub data: Vec<u8>,
-} 
\ No newline at end of file
+}
+
+impl WALEntry {
+    /// Entry for a single row written by an INSERT, UPDATE or DELETE. `data` holds the
+    /// bincode-encoded row: the new values for inserts and updates, the removed values for deletes.
+    pub fn for_row(transaction_id: u64, operation: WALOperation, table_name: &str, row: &[DataValue], timestamp: SystemTime) -> Result<Self, ReefDBError> {
+        let data = bincode::serialize(row)
+            .map_err(|e| ReefDBError::WALError(format!("Failed to serialize WAL row: {}", e)))?;
+        Ok(WALEntry {
+            transaction_id,
+            timestamp,
+            operation,
+            table_name: table_name.to_string(),
+            data,
+        })
+    }
+
+    /// Entry for all rows of a bulk load. `data` holds the bincode-encoded rows.
+    pub fn for_rows(transaction_id: u64, table_name: &str, rows: &[Vec<DataValue>], timestamp: SystemTime) -> Result<Self, ReefDBError> {
+        let data = bincode::serialize(rows)
+            .map_err(|e| ReefDBError::WALError(format!("Failed to serialize WAL rows: {}", e)))?;
+        Ok(WALEntry {
+            transaction_id,
+            timestamp,
+            operation: WALOperation::BulkInsert,
+            table_name: table_name.to_string(),
+            data,
+        })
+    }
+
+    /// Decodes the rows carried by an entry created with `for_rows`
+    pub fn rows(&self) -> Result<Vec<Vec<DataValue>>, ReefDBError> {
+        bincode::deserialize(&self.data)
+            .map_err(|e| ReefDBError::WALError(format!("Failed to deserialize WAL rows: {}", e)))
+    }
+
+    /// Decodes the row carried by an entry created with `for_row`
+    pub fn row(&self) -> Result<Vec<DataValue>, ReefDBError> {
+        bincode::deserialize(&self.data)
+            .map_err(|e| ReefDBError::WALError(format!("Failed to deserialize WAL row: {}", e)))
+    }
+}
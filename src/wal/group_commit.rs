@@ -0,0 +1,79 @@
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::ReefDBError;
+use crate::locks::lock_recovering;
+use super::entry::WALEntry;
+use super::log::WriteAheadLog;
+
+/// Commit queue sharing one fsync between transactions committing at the same time.
+/// The first committer to find no fsync under way waits `window` for others to append
+/// their commit entries, then fsyncs the log for all of them while they wait.
+pub struct GroupCommit {
+    window: Duration,
+    state: Mutex<QueueState>,
+    synced: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    // Sequence number of the last entry known to be fsynced
+    durable: u64,
+    syncing: bool,
+}
+
+impl GroupCommit {
+    pub fn new(window: Duration) -> Self {
+        GroupCommit {
+            window,
+            state: Mutex::new(QueueState::default()),
+            synced: Condvar::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Appends a commit entry and returns once an fsync has made it durable, whatever
+    /// the log's own fsync policy
+    pub fn commit(&self, wal: &Mutex<WriteAheadLog>, entry: WALEntry) -> Result<(), ReefDBError> {
+        let sequence = self.append(wal, entry)?;
+        self.wait_durable(wal, sequence)
+    }
+
+    /// Appends a commit entry without waiting for it to be durable and returns the
+    /// sequence number to hand to `wait_durable`
+    pub fn append(&self, wal: &Mutex<WriteAheadLog>, entry: WALEntry) -> Result<u64, ReefDBError> {
+        wal.lock()
+            .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))?
+            .append_unsynced(entry)
+    }
+
+    /// Returns once an fsync made the entry `sequence` durable, running it after the
+    /// window unless another caller is already doing so
+    pub fn wait_durable(&self, wal: &Mutex<WriteAheadLog>, sequence: u64) -> Result<(), ReefDBError> {
+        let mut state = lock_recovering(&self.state);
+        while state.durable < sequence {
+            if state.syncing {
+                state = self.synced.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+            state.syncing = true;
+            drop(state);
+
+            thread::sleep(self.window);
+            let synced = wal.lock()
+                .map_err(|_| ReefDBError::LockPoisoned("WAL lock".to_string()))
+                .and_then(|mut wal| wal.sync().map(|_| wal.synced_through()));
+
+            // A failed fsync hands the next waiter the job of retrying it
+            state = lock_recovering(&self.state);
+            state.syncing = false;
+            self.synced.notify_all();
+            state.durable = state.durable.max(synced?);
+        }
+        Ok(())
+    }
+}
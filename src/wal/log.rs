@@ -1,15 +1,59 @@
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use bincode;
 
+use crate::clock::{Clock, SystemClock};
 use crate::error::ReefDBError;
-use super::entry::WALEntry;
+use super::entry::{WALEntry, WALOperation};
+
+/// When the WAL is fsynced, trading durability for throughput
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsyncEvery {
+    /// After every entry
+    Append,
+    /// When a commit entry is written, before `append_entry` returns
+    Commit,
+    /// On the first append once the interval has passed since the last fsync
+    Interval(Duration),
+    /// Only on explicit `sync` calls
+    Never,
+}
+
+/// Backing file of a write-ahead log, abstracted so other writers can stand in for a `File`
+pub trait WalFile: Read + Write + Seek + Send {
+    fn sync_all(&mut self) -> io::Result<()>;
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+}
+
+impl WalFile for File {
+    fn sync_all(&mut self) -> io::Result<()> {
+        File::sync_all(self)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+}
 
 pub struct WriteAheadLog {
-    file: File,
+    file: Box<dyn WalFile>,
     current_position: u64,
-    sync_on_append: bool,
+    fsync: FsyncEvery,
+    clock: Arc<dyn Clock>,
+    last_sync: SystemTime,
+    subscribers: Vec<Sender<WALEntry>>,
+    // Entries of transactions that haven't committed yet, held back from subscribers
+    pending: HashMap<u64, Vec<WALEntry>>,
+    // Commit entries written by `append_unsynced`, published once an fsync covers them
+    unsynced_commits: Vec<WALEntry>,
+    // Entries appended since the log was opened, and how many of them were fsynced
+    appended: u64,
+    synced: u64,
 }
 
 impl WriteAheadLog {
@@ -22,32 +66,98 @@ impl WriteAheadLog {
             .open(path)?;
         
         let current_position = file.metadata()?.len();
-        
-        Ok(WriteAheadLog {
-            file,
-            current_position,
-            sync_on_append: true,
-        })
+        Ok(Self::with_file(Box::new(file), current_position))
     }
 
     pub fn new_in_memory() -> io::Result<Self> {
         let file = tempfile::tempfile()?;
-        Ok(WriteAheadLog {
+        Ok(Self::with_file(Box::new(file), 0))
+    }
+
+    /// Builds a log over any `WalFile` whose existing contents are `len` bytes long
+    pub fn with_file(file: Box<dyn WalFile>, len: u64) -> Self {
+        WriteAheadLog {
             file,
-            current_position: 0,
-            sync_on_append: true,
-        })
+            current_position: len,
+            fsync: FsyncEvery::Append,
+            clock: Arc::new(SystemClock),
+            last_sync: SystemTime::now(),
+            subscribers: Vec::new(),
+            pending: HashMap::new(),
+            unsynced_commits: Vec::new(),
+            appended: 0,
+            synced: 0,
+        }
     }
 
     pub fn set_sync_on_append(&mut self, sync: bool) {
-        self.sync_on_append = sync;
+        self.fsync = if sync { FsyncEvery::Append } else { FsyncEvery::Never };
+    }
+
+    pub fn set_fsync_policy(&mut self, policy: FsyncEvery) {
+        self.fsync = policy;
+    }
+
+    pub fn fsync_policy(&self) -> FsyncEvery {
+        self.fsync
+    }
+
+    /// Replaces the clock the fsync interval is measured with; the interval restarts
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_sync = clock.now();
+        self.clock = clock;
+    }
+
+    /// Current time by the log's clock, for stamping entries
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    fn should_sync(&self, operation: &WALOperation) -> bool {
+        match self.fsync {
+            FsyncEvery::Append => true,
+            FsyncEvery::Commit => *operation == WALOperation::Commit,
+            // A clock that went backwards counts as no time passed
+            FsyncEvery::Interval(interval) => self.clock.now().duration_since(self.last_sync)
+                .is_ok_and(|elapsed| elapsed >= interval),
+            FsyncEvery::Never => false,
+        }
     }
 
     pub fn append_entry(&mut self, entry: WALEntry) -> Result<(), ReefDBError> {
+        self.write_entry(&entry)?;
+        if self.should_sync(&entry.operation) {
+            self.sync()?;
+        }
+        self.publish(entry);
+        Ok(())
+    }
+
+    /// Appends `entry` without fsyncing whatever the policy, returning its sequence number
+    /// for `synced_through`. A commit entry reaches subscribers after the next `sync`.
+    pub fn append_unsynced(&mut self, entry: WALEntry) -> Result<u64, ReefDBError> {
+        self.write_entry(&entry)?;
+        if entry.operation == WALOperation::Commit {
+            self.unsynced_commits.push(entry);
+        } else {
+            self.publish(entry);
+        }
+        Ok(self.appended)
+    }
+
+    /// Sequence number of the last entry an fsync made durable
+    pub fn synced_through(&self) -> u64 {
+        self.synced
+    }
+
+    fn write_entry(&mut self, entry: &WALEntry) -> Result<(), ReefDBError> {
         let serialized = bincode::serialize(&entry)
             .map_err(|e| ReefDBError::WALError(format!("Failed to serialize WAL entry: {}", e)))?;
         
         let len = serialized.len() as u64;
+        // Reads move the cursor, so always write at the end of the log
+        self.file.seek(SeekFrom::Start(self.current_position))
+            .map_err(|e| ReefDBError::WALError(format!("Failed to seek WAL: {}", e)))?;
         self.file.write_all(&len.to_le_bytes())
             .map_err(|e| ReefDBError::WALError(format!("Failed to write WAL entry length: {}", e)))?;
         
@@ -57,15 +167,44 @@ impl WriteAheadLog {
         self.file.flush()
             .map_err(|e| ReefDBError::WALError(format!("Failed to flush WAL: {}", e)))?;
         
-        if self.sync_on_append {
-            self.file.sync_all()
-                .map_err(|e| ReefDBError::WALError(format!("Failed to sync WAL to disk: {}", e)))?;
-        }
-        
         self.current_position += 8 + len;
+        self.appended += 1;
         Ok(())
     }
 
+    /// Streams committed entries in commit order: each transaction's operations followed by
+    /// its `Commit` entry, delivered once the commit is durable. Uncommitted and rolled back
+    /// work is never sent. A transaction already running when the subscription starts is
+    /// only delivered from that point on.
+    pub fn subscribe(&mut self) -> Receiver<WALEntry> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Drops the entries buffered for a transaction that rolled back
+    pub fn discard_pending(&mut self, transaction_id: u64) {
+        self.pending.remove(&transaction_id);
+    }
+
+    fn publish(&mut self, entry: WALEntry) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        match entry.operation {
+            WALOperation::Commit => {
+                let mut batch = self.pending.remove(&entry.transaction_id).unwrap_or_default();
+                batch.push(entry);
+                for entry in batch {
+                    // Subscribers whose receiver was dropped are forgotten
+                    self.subscribers.retain(|subscriber| subscriber.send(entry.clone()).is_ok());
+                }
+            }
+            WALOperation::Rollback => {}
+            _ => self.pending.entry(entry.transaction_id).or_default().push(entry),
+        }
+    }
+
     pub fn read_entries(&mut self) -> Result<Vec<WALEntry>, ReefDBError> {
         self.file.seek(SeekFrom::Start(0))
             .map_err(|e| ReefDBError::WALError(format!("Failed to seek WAL: {}", e)))?;
@@ -94,22 +233,52 @@ impl WriteAheadLog {
         Ok(entries)
     }
 
+    /// Row operations of every transaction that committed at or before `cutoff`, in commit
+    /// order. Work committed later, or never committed, is left out.
+    pub fn committed_entries_until(&mut self, cutoff: SystemTime) -> Result<Vec<WALEntry>, ReefDBError> {
+        let mut in_flight: HashMap<u64, Vec<WALEntry>> = HashMap::new();
+        let mut committed = Vec::new();
+        for entry in self.read_entries()? {
+            match entry.operation {
+                WALOperation::Commit => {
+                    let operations = in_flight.remove(&entry.transaction_id).unwrap_or_default();
+                    if entry.timestamp <= cutoff {
+                        committed.extend(operations);
+                    }
+                }
+                WALOperation::Insert | WALOperation::Update | WALOperation::Delete => {
+                    in_flight.entry(entry.transaction_id).or_default().push(entry);
+                }
+                _ => {}
+            }
+        }
+        Ok(committed)
+    }
+
     pub fn truncate(&mut self) -> Result<(), ReefDBError> {
         self.file.set_len(0)
             .map_err(|e| ReefDBError::WALError(format!("Failed to truncate WAL: {}", e)))?;
         
-        if self.sync_on_append {
+        if self.fsync != FsyncEvery::Never {
             self.file.sync_all()
                 .map_err(|e| ReefDBError::WALError(format!("Failed to sync WAL after truncate: {}", e)))?;
+            self.last_sync = self.clock.now();
         }
         
         self.current_position = 0;
+        self.pending.clear();
+        self.unsynced_commits.clear();
         Ok(())
     }
 
     pub fn sync(&mut self) -> Result<(), ReefDBError> {
         self.file.sync_all()
             .map_err(|e| ReefDBError::WALError(format!("Failed to sync WAL to disk: {}", e)))?;
+        self.last_sync = self.clock.now();
+        self.synced = self.appended;
+        for entry in std::mem::take(&mut self.unsynced_commits) {
+            self.publish(entry);
+        }
         Ok(())
     }
 }
@@ -196,12 +365,12 @@ mod tests {
         let mut wal = WriteAheadLog::new_in_memory().unwrap();
         
         // Test with sync_on_append enabled (default)
-        assert!(wal.sync_on_append);
+        assert_eq!(wal.fsync_policy(), FsyncEvery::Append);
         wal.append_entry(create_test_entry(1, WALOperation::Insert)).unwrap();
         
         // Test with sync_on_append disabled
         wal.set_sync_on_append(false);
-        assert!(!wal.sync_on_append);
+        assert_eq!(wal.fsync_policy(), FsyncEvery::Never);
         wal.append_entry(create_test_entry(2, WALOperation::Insert)).unwrap();
         
         // Verify both entries were written correctly
@@ -279,4 +448,169 @@ mod tests {
         let result = WriteAheadLog::new("/nonexistent/directory/test.wal");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_subscribe_only_sees_committed_entries() {
+        let mut wal = WriteAheadLog::new_in_memory().unwrap();
+        let receiver = wal.subscribe();
+
+        wal.append_entry(create_test_entry(1, WALOperation::Insert)).unwrap();
+        wal.append_entry(create_test_entry(2, WALOperation::Insert)).unwrap();
+        wal.append_entry(create_test_entry(1, WALOperation::Update)).unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        wal.append_entry(create_test_entry(1, WALOperation::Commit)).unwrap();
+        let received: Vec<WALOperation> = receiver.try_iter().map(|e| e.operation).collect();
+        assert_eq!(received, vec![WALOperation::Insert, WALOperation::Update, WALOperation::Commit]);
+
+        // Transaction 2 rolls back, so nothing of it is ever sent
+        wal.discard_pending(2);
+        wal.append_entry(create_test_entry(3, WALOperation::Delete)).unwrap();
+        wal.append_entry(create_test_entry(3, WALOperation::Commit)).unwrap();
+        let received: Vec<u64> = receiver.try_iter().map(|e| e.transaction_id).collect();
+        assert_eq!(received, vec![3, 3]);
+
+        // A dropped receiver doesn't break appends
+        drop(receiver);
+        wal.append_entry(create_test_entry(4, WALOperation::Commit)).unwrap();
+        assert!(wal.subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_unsynced_commits_wait_for_fsync() {
+        use std::sync::atomic::Ordering;
+
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Append);
+        let receiver = wal.subscribe();
+        wal.append_unsynced(create_test_entry(1, WALOperation::Insert)).unwrap();
+        let sequence = wal.append_unsynced(create_test_entry(1, WALOperation::Commit)).unwrap();
+        assert_eq!(sequence, 2);
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+        assert!(wal.synced_through() < sequence);
+        assert!(receiver.try_recv().is_err());
+
+        wal.sync().unwrap();
+        assert_eq!(wal.synced_through(), sequence);
+        let received: Vec<WALOperation> = receiver.try_iter().map(|e| e.operation).collect();
+        assert_eq!(received, vec![WALOperation::Insert, WALOperation::Commit]);
+    }
+
+    // In-memory WAL file that counts fsyncs
+    struct CountingFile {
+        inner: io::Cursor<Vec<u8>>,
+        syncs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Read for CountingFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for CountingFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for CountingFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl WalFile for CountingFile {
+        fn sync_all(&mut self) -> io::Result<()> {
+            self.syncs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn set_len(&mut self, len: u64) -> io::Result<()> {
+            self.inner.get_mut().truncate(len as usize);
+            Ok(())
+        }
+    }
+
+    fn counting_wal(policy: FsyncEvery) -> (WriteAheadLog, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        let syncs = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let file = CountingFile { inner: io::Cursor::new(Vec::new()), syncs: syncs.clone() };
+        let mut wal = WriteAheadLog::with_file(Box::new(file), 0);
+        wal.set_fsync_policy(policy);
+        (wal, syncs)
+    }
+
+    #[test]
+    fn test_fsync_policy() {
+        use std::sync::atomic::Ordering;
+
+        // Commit: one fsync per commit, none for the operations before it
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Commit);
+        for tx in 1..=2 {
+            wal.append_entry(create_test_entry(tx, WALOperation::Insert)).unwrap();
+            wal.append_entry(create_test_entry(tx, WALOperation::Update)).unwrap();
+            assert_eq!(syncs.load(Ordering::SeqCst), tx as usize - 1);
+            wal.append_entry(create_test_entry(tx, WALOperation::Commit)).unwrap();
+            assert_eq!(syncs.load(Ordering::SeqCst), tx as usize);
+        }
+        assert_eq!(wal.read_entries().unwrap().len(), 6);
+
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Append);
+        wal.append_entry(create_test_entry(1, WALOperation::Insert)).unwrap();
+        wal.append_entry(create_test_entry(1, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 2);
+
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Never);
+        wal.append_entry(create_test_entry(1, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+        wal.sync().unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Interval(std::time::Duration::from_secs(3600)));
+        wal.append_entry(create_test_entry(1, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+        wal.set_fsync_policy(FsyncEvery::Interval(std::time::Duration::ZERO));
+        wal.append_entry(create_test_entry(2, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_fsync_interval_follows_clock() {
+        use std::sync::atomic::Ordering;
+        use crate::clock::MockClock;
+
+        let clock = MockClock::default();
+        let (mut wal, syncs) = counting_wal(FsyncEvery::Interval(Duration::from_secs(10)));
+        wal.set_clock(Arc::new(clock.clone()));
+
+        clock.advance(Duration::from_secs(9));
+        wal.append_entry(create_test_entry(1, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+
+        // The interval elapses exactly, without waiting on real time
+        clock.advance(Duration::from_secs(1));
+        wal.append_entry(create_test_entry(2, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+
+        // Counting restarts from that fsync
+        clock.advance(Duration::from_secs(5));
+        wal.append_entry(create_test_entry(3, WALOperation::Commit)).unwrap();
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+        assert_eq!(wal.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_append_after_read() {
+        let mut wal = WriteAheadLog::new_in_memory().unwrap();
+        wal.append_entry(create_test_entry(1, WALOperation::Insert)).unwrap();
+        assert_eq!(wal.read_entries().unwrap().len(), 1);
+
+        // Reading rewinds the file; the next append must not overwrite the first entry
+        wal.append_entry(create_test_entry(2, WALOperation::Insert)).unwrap();
+        let entries = wal.read_entries().unwrap();
+        assert_eq!(entries.iter().map(|e| e.transaction_id).collect::<Vec<_>>(), vec![1, 2]);
+    }
 } 
\ No newline at end of file
@@ -1,5 +1,7 @@
 mod entry;
+mod group_commit;
 mod log;
 
 pub use entry::{WALEntry, WALOperation};
-pub use log::WriteAheadLog; 
\ No newline at end of file
+pub use group_commit::GroupCommit;
+pub use log::{FsyncEvery, WalFile, WriteAheadLog};
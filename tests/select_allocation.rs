@@ -0,0 +1,73 @@
+//! Measures what a single-table SELECT allocates. It lives in its own test binary because
+//! counting allocations needs a global allocator, which would otherwise sit under every
+//! unit test.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use reefdb::result::ReefDBResult;
+use reefdb::sql::{column_def::ColumnDef, data_type::DataType, data_value::DataValue, statements::create::CreateStatement, Statement};
+use reefdb::transaction::IsolationLevel;
+use reefdb::transaction_manager::TransactionManager;
+use reefdb::wal::WriteAheadLog;
+use reefdb::InMemoryReefDB;
+
+// Counts bytes allocated per thread, so a test can measure its own allocations
+// while other tests run concurrently
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocated_bytes<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATED.with(Cell::get);
+    let result = f();
+    (result, ALLOCATED.with(Cell::get) - before)
+}
+
+#[test]
+fn test_single_table_select_allocation_on_wide_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let wal = WriteAheadLog::new(dir.path().join("test.wal")).unwrap();
+    let mut tm = TransactionManager::create(InMemoryReefDB::create_in_memory().unwrap(), wal);
+    let tx = tm.begin_transaction(IsolationLevel::ReadCommitted).unwrap();
+    let mut columns = vec![ColumnDef::new("id", DataType::Integer, vec![])];
+    columns.extend((1..40).map(|c| ColumnDef::new(&format!("c{}", c), DataType::Text, vec![])));
+    tm.execute_statement(tx, Statement::Create(CreateStatement::Table("wide".to_string(), columns, false))).unwrap();
+    let data: Vec<Vec<DataValue>> = (1..=2_000)
+        .map(|id| {
+            let mut row = vec![DataValue::Integer(id)];
+            row.extend((1..40).map(|c| DataValue::Text(format!("row {} column {}", (id * 37) % 2_000, c).into())));
+            row
+        })
+        .collect();
+    tm.copy_into(tx, "wide", data).unwrap();
+
+    let (_, narrow) = Statement::parse("SELECT id, c1 FROM wide").unwrap();
+    let (_, full) = Statement::parse("SELECT * FROM wide").unwrap();
+    let rows = |result: ReefDBResult| match result {
+        ReefDBResult::Select(result) => result.rows,
+        other => panic!("Expected Select result, got {:?}", other),
+    };
+    let (narrow_rows, narrow_bytes) = allocated_bytes(|| rows(tm.execute_statement(tx, narrow).unwrap()));
+    let (full_rows, full_bytes) = allocated_bytes(|| rows(tm.execute_statement(tx, full).unwrap()));
+
+    assert_eq!(narrow_rows.len(), full_rows.len());
+    // Unselected columns are never cloned, so the narrow scan allocates a fraction as much
+    assert!(narrow_bytes * 5 < full_bytes, "{} vs {}", narrow_bytes, full_bytes);
+}